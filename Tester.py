@@ -1,7 +1,12 @@
 #!/usr/bin/env python3
+import glob
 import os
+import random
+import shutil
+import struct
 import subprocess
 import sys
+import zipfile
 
 def read_from_file(path: str) -> str:
     with open(path, "r") as f:
@@ -19,6 +24,125 @@ def write_to_file(path: str, content: str):
     with open(path, "w") as f:
         f.write(content)
 
+# With --bless, the expected output and exit code of the tests are updated to what they are
+BLESS = False
+
+def jar_entries(jar_path: str) -> list:
+    """The entries of a JAR, one line each: the name, `stored` or `deflated`, and for stored
+    entries whether their data starts at an offset `aligned` on 4 bytes or `unaligned`"""
+    lines = []
+    with open(jar_path, "rb") as jar, zipfile.ZipFile(jar) as archive:
+        for info in archive.infolist():
+            if info.compress_type != zipfile.ZIP_STORED:
+                lines.append(f"{info.filename} deflated")
+                continue
+            # The data follows the local header, whose name and extra field lengths end it
+            jar.seek(info.header_offset + 26)
+            name_length, extra_length = struct.unpack("<HH", jar.read(4))
+            offset = info.header_offset + 30 + name_length + extra_length
+            lines.append(f"{info.filename} stored {'aligned' if offset % 4 == 0 else 'unaligned'}")
+    return lines
+
+def check_javap(test_dir: str, jar_path: str, checks: str) -> bool:
+    """Checks the `javap -v -p` disassembly of the classes of the jar, like LLVM's FileCheck.
+
+    The checks are lines of `javap.check`, run in order against the text selected by the last
+    line naming one before them:
+    - `CLASS: <name>` disassembles a class, or a class file relative to the test, and may give
+      javap options first, as in `CLASS: --multi-release 17 Point`
+    - `ENTRY: <name>` reads an entry of the jar, and `ENTRIES:` lists them, see `jar_entries`
+    - `FILE: <pattern>` reads the one file matching the pattern, relative to the test
+    - `JAR: <pattern>` reads the classes and entries of the one jar matching the pattern from then
+      on, relative to the test
+    Then:
+    - `CHECK: <text>` finds the next line containing the text
+    - `CHECK-NEXT: <text>` expects the text on the line after the last match
+    - `CHECK-NOT: <text>` expects the text on no line up to the next match, or the end
+    Empty lines and lines starting with `#` are ignored.
+    """
+    disassembly = []
+    position = 0
+    forbidden = []
+    failures = []
+
+    def find(text: str, start: int) -> int:
+        for index in range(start, len(disassembly)):
+            if text in disassembly[index]:
+                return index
+        return -1
+
+    def check_forbidden(end: int):
+        for number, text in forbidden:
+            for index in range(position, end):
+                if text in disassembly[index]:
+                    failures.append(f"line {number}: CHECK-NOT: {text} found on line {index + 1}: {disassembly[index].strip()}")
+        forbidden.clear()
+
+    for number, line in enumerate(checks.splitlines(), start=1):
+        line = line.strip()
+        if not line or line.startswith("#"):
+            continue
+        directive, _, text = line.partition(":")
+        text = text.strip()
+        if directive == "JAR":
+            paths = glob.glob(os.path.join(test_dir, text))
+            if len(paths) != 1:
+                failures.append(f"line {number}: {len(paths)} files match {text}")
+            else:
+                jar_path = paths[0]
+        elif directive in ("CLASS", "ENTRY", "ENTRIES", "FILE"):
+            check_forbidden(len(disassembly))
+            disassembly = []
+            position = 0
+            if directive == "CLASS":
+                # In the test's directory, for the paths of class files the build wrote
+                proc = run_command(["javap", "-v", "-p", "-cp", os.path.abspath(jar_path), *text.split()], cwd=test_dir)
+                if proc.returncode != 0:
+                    failures.append(f"line {number}: javap {text} failed:\n{proc.stderr}")
+                else:
+                    disassembly = proc.stdout.splitlines()
+            elif directive == "FILE":
+                paths = glob.glob(os.path.join(test_dir, text))
+                if len(paths) != 1:
+                    failures.append(f"line {number}: {len(paths)} files match {text}")
+                else:
+                    with open(paths[0], "rb") as f:
+                        disassembly = f.read().decode("utf-8", "replace").splitlines()
+            else:
+                try:
+                    if directive == "ENTRIES":
+                        disassembly = jar_entries(jar_path)
+                    else:
+                        with zipfile.ZipFile(jar_path) as archive:
+                            disassembly = archive.read(text).decode("utf-8", "replace").splitlines()
+                except (OSError, KeyError, zipfile.BadZipFile) as err:
+                    failures.append(f"line {number}: {directive} {text} failed: {err}")
+        elif directive == "CHECK":
+            index = find(text, position)
+            if index < 0:
+                failures.append(f"line {number}: CHECK: {text} not found after line {position}")
+                continue
+            check_forbidden(index)
+            position = index + 1
+        elif directive == "CHECK-NEXT":
+            if position >= len(disassembly) or text not in disassembly[position]:
+                failures.append(f"line {number}: CHECK-NEXT: {text} not on line {position + 1}")
+                continue
+            check_forbidden(position)
+            position += 1
+        elif directive == "CHECK-NOT":
+            forbidden.append((number, text))
+        else:
+            failures.append(f"line {number}: unknown directive {directive}")
+    check_forbidden(len(disassembly))
+
+    if failures:
+        fail_path = os.path.join(test_dir, "javap-check-fail.generated")
+        write_to_file(fail_path, "\n".join(failures) + "\n")
+        print(f"|---- ❌ {len(failures)} javap checks failed")
+        return False
+    return True
+
 def process_test(test_dir: str):
     test_name = os.path.basename(test_dir)
     normalized = normalize_name(test_name)
@@ -44,19 +168,38 @@ def process_test(test_dir: str):
         print(f"|---- ❌ cargo build exited with code {proc.returncode}")
         return False
 
+    # Check the bytecode of the classes against {test_dir}/javap.check
+    jar_path = os.path.join(test_dir, "target", "jvm-unknown-unknown", "debug", f"{test_name}.jar")
+    check_file = os.path.join(test_dir, "javap.check")
+    if os.path.exists(check_file):
+        print("|--- 🔍 Checking the bytecode with javap...")
+        if not check_javap(test_dir, jar_path, read_from_file(check_file)):
+            return False
+        print("|--- ✅ Bytecode matches the checks!")
+
     # Run java with the generated jar.
     print("|--- 🤖 Running with Java...")
-    jar_path = os.path.join(test_dir, "target", "jvm-unknown-unknown", "debug", f"{test_name}.jar")
     proc = run_command(["java", "-jar", jar_path])
-    if proc.returncode != 0:
+    # Compare the exit code to {test_dir}/java_exit_code.expected, 0 if there is none
+    exit_code_file = os.path.join(test_dir, "java_exit_code.expected")
+    if BLESS and proc.returncode != 0:
+        write_to_file(exit_code_file, f"{proc.returncode}\n")
+    elif BLESS and os.path.exists(exit_code_file):
+        os.remove(exit_code_file)
+    expected_exit_code = 0
+    if os.path.exists(exit_code_file):
+        expected_exit_code = int(read_from_file(exit_code_file).strip())
+    if proc.returncode != expected_exit_code:
         fail_path = os.path.join(test_dir, "java-fail.generated")
         output = f"STDOUT:\n{proc.stdout}\n\nSTDERR:\n{proc.stderr}"
         write_to_file(fail_path, output)
-        print(f"|---- ❌ java exited with code {proc.returncode}")
+        print(f"|---- ❌ java exited with code {proc.returncode}, expected {expected_exit_code}")
         return False
 
     # Compare the STDOUT to {test_dir}/java_output.expected
     expected_file = os.path.join(test_dir, "java_output.expected")
+    if BLESS:
+        write_to_file(expected_file, proc.stdout.strip() + "\n")
     if os.path.exists(expected_file):
         expected_output = read_from_file(expected_file)
         actual_output = proc.stdout.strip()
@@ -74,7 +217,169 @@ def process_test(test_dir: str):
     print("|--- ✅ Binary test passed!")
     return True
 
+# The integer types of the differential tests, with their width in bits
+INTEGER_TYPES = {"i8": 8, "i16": 16, "i32": 32, "i64": 64, "u8": 8, "u16": 16, "u32": 32, "u64": 64}
+
+def generate_literal(rng: random.Random, ty: str) -> str:
+    """A `black_box`ed literal of an integer type, often at the edges of its range. Negative
+    values are cast from their unsigned bits, as `no_core` has no `Neg`."""
+    bits = INTEGER_TYPES[ty]
+    value = rng.choice([0, 1, 2, (1 << (bits - 1)) - 1, 1 << (bits - 1), (1 << bits) - 1, rng.getrandbits(bits), rng.getrandbits(8)])
+    if ty.startswith("i") and value >= 1 << (bits - 1):
+        return f"black_box({value:#x}_u{bits} as {ty})"
+    return f"black_box({value:#x}_{ty})"
+
+def generate_expression(rng: random.Random, ty: str, depth: int) -> str:
+    """A random expression of an integer type: arithmetic, bitwise operations, shifts and casts"""
+    if depth == 0 or rng.random() < 0.25:
+        return generate_literal(rng, ty)
+    kind = rng.choice(["binary", "binary", "shift", "cast"])
+    if kind == "binary":
+        op = rng.choice(["+", "-", "*", "&", "|", "^"])
+        return f"({generate_expression(rng, ty, depth - 1)} {op} {generate_expression(rng, ty, depth - 1)})"
+    if kind == "shift":
+        # Shifts by more than the width wrap around without overflow checks
+        amount = rng.randrange(2 * INTEGER_TYPES[ty])
+        op = rng.choice(["<<", ">>"])
+        return f"({generate_expression(rng, ty, depth - 1)} {op} black_box({amount}_u32))"
+    source = rng.choice(list(INTEGER_TYPES))
+    return f"({generate_expression(rng, source, depth - 1)} as {ty})"
+
+def generate_differential_crate(crate_dir: str, seed: int, count: int) -> list:
+    """Writes a crate of `count` `case_<n>` functions computing random expressions, the JVM build
+    `src/main.rs` and the native one `native.rs`, returning the return type of each case"""
+    rng = random.Random(seed)
+    types = [rng.choice(list(INTEGER_TYPES)) for _ in range(count)]
+    cases = "".join(
+        f"#[unsafe(no_mangle)]\npub fn case_{index}() -> {ty} {{\n    {generate_expression(rng, ty, 3)}\n}}\n\n"
+        for index, ty in enumerate(types)
+    )
+    copies = "".join(f"impl Copy for {ty} {{}}\n" for ty in INTEGER_TYPES)
+    # The operator traits, whose impls for the integer types are the built-in operators
+    operators = {"add": ("Add", "+"), "sub": ("Sub", "-"), "mul": ("Mul", "*"), "bitand": ("BitAnd", "&"), "bitor": ("BitOr", "|"), "bitxor": ("BitXor", "^"), "shl": ("Shl", "<<"), "shr": ("Shr", ">>")}
+    traits = "".join(
+        f"#[lang = \"{name}\"]\ntrait {trait}<Rhs> {{\n    type Output;\n    fn {name}(self, rhs: Rhs) -> Self::Output;\n}}\n\n"
+        for name, (trait, _) in operators.items()
+    )
+    impls = "".join(
+        f"impl {trait}<{'u32' if name in ('shl', 'shr') else ty}> for {ty} {{\n    type Output = {ty};\n    fn {name}(self, rhs: {'u32' if name in ('shl', 'shr') else ty}) -> {ty} {{\n        self {op} rhs\n    }}\n}}\n\n"
+        for name, (trait, op) in operators.items()
+        for ty in INTEGER_TYPES
+    )
+    jvm_source = f"""// Generated by `python3 Tester.py --differential --seed={seed}`
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(intrinsics)]
+#![allow(dead_code, internal_features, unused_parens)]
+#![no_core]
+
+#[rustc_intrinsic]
+fn black_box<T>(x: T) -> T;
+
+{cases}fn main() {{}}
+
+#[lang = "sized"]
+trait Sized {{}}
+
+#[lang = "copy"]
+trait Copy {{}}
+
+{copies}
+{traits}{impls}"""
+    prints = "".join(f"    println!(\"{{}}\", case_{index}());\n" for index in range(count))
+    native_source = f"""// Generated by `python3 Tester.py --differential --seed={seed}`
+#![allow(unused_parens)]
+
+use std::hint::black_box;
+
+{cases}fn main() {{
+{prints}}}
+"""
+    os.makedirs(os.path.join(crate_dir, "src"), exist_ok=True)
+    write_to_file(os.path.join(crate_dir, "src", "main.rs"), jvm_source)
+    write_to_file(os.path.join(crate_dir, "native.rs"), native_source)
+    write_to_file(os.path.join(crate_dir, "Cargo.toml"), """[package]
+name = "differential"
+version = "0.1.0"
+edition = "2024"
+
+[dependencies]
+""")
+    return types
+
+def run_differential(seed: int, count: int) -> bool:
+    """Builds random programs natively and with the backend, and compares what they compute.
+    Both builds turn overflow checks off, so that overflow wraps instead of panicking."""
+    print(f"|- 🎲 Differential test of {count} cases, seed {seed}...")
+    crate_dir = os.path.join("tests", "differential", "generated")
+    shutil.rmtree(crate_dir, ignore_errors=True)
+    types = generate_differential_crate(crate_dir, seed, count)
+    target_dir = os.path.join(crate_dir, "target")
+    os.makedirs(target_dir, exist_ok=True)
+
+    def fail(name: str, step: str, proc) -> bool:
+        write_to_file(os.path.join(crate_dir, name), f"STDOUT:\n{proc.stdout}\n\nSTDERR:\n{proc.stderr}")
+        print(f"|-- ❌ {step} exited with code {proc.returncode}")
+        return False
+
+    print("|-- ⚒️ Building natively...")
+    native_path = os.path.join(target_dir, "native")
+    proc = run_command(["rustc", "--edition", "2024", "-C", "overflow-checks=off", "-o", native_path, os.path.join(crate_dir, "native.rs")])
+    if proc.returncode != 0:
+        return fail("native-build-fail.generated", "rustc", proc)
+    native = run_command([native_path])
+    if native.returncode != 0:
+        return fail("native-fail.generated", "the native build", native)
+
+    print("|-- ⚒️ Building with the JVM backend...")
+    env = dict(os.environ, RUSTFLAGS="-C overflow-checks=off")
+    proc = subprocess.run(["cargo", "build", "--target", "../../../jvm-unknown-unknown.json"], cwd=crate_dir, env=env, stdout=subprocess.PIPE, stderr=subprocess.PIPE, text=True)
+    if proc.returncode != 0:
+        return fail("cargo-build-fail.generated", "cargo build", proc)
+    runner_dir = os.path.join(target_dir, "runner")
+    proc = run_command(["javac", "-d", runner_dir, os.path.join("tests", "differential", "Runner.java")])
+    if proc.returncode != 0:
+        return fail("javac-fail.generated", "javac", proc)
+    jar_path = os.path.join(target_dir, "jvm-unknown-unknown", "debug", "differential.jar")
+    jvm = run_command(["java", "-cp", os.pathsep.join([jar_path, runner_dir]), "Runner", "differential"] + types)
+    if jvm.returncode != 0:
+        return fail("java-fail.generated", "java", jvm)
+
+    # The cases whose results differ, with their source
+    source = read_from_file(os.path.join(crate_dir, "native.rs"))
+    native_lines = native.stdout.splitlines()
+    jvm_lines = jvm.stdout.splitlines()
+    differences = []
+    for index in range(count):
+        expected = native_lines[index] if index < len(native_lines) else "nothing"
+        actual = jvm_lines[index] if index < len(jvm_lines) else "nothing"
+        if expected != actual:
+            start = source.index(f"pub fn case_{index}()")
+            function = source[start:source.index("\n}", start) + 2]
+            differences.append(f"{function}\nnative: {expected}\nJVM:    {actual}\n")
+    if differences:
+        write_to_file(os.path.join(crate_dir, "differences.generated"), "\n".join(differences))
+        print(f"|-- ❌ {len(differences)} of {count} cases differ, see {crate_dir}/differences.generated")
+        return False
+    print(f"|-- ✅ All {count} cases match!")
+    return True
+
 def main():
+    # Usage: python3 Tester.py [--bless] [<test name>...]
+    #        python3 Tester.py --differential[=<cases>] [--seed=<seed>]
+    global BLESS
+    args = sys.argv[1:]
+    differential = [arg for arg in args if arg.startswith("--differential")]
+    if differential:
+        _, _, count = differential[0].partition("=")
+        seeds = [arg.partition("=")[2] for arg in args if arg.startswith("--seed=")]
+        seed = int(seeds[0]) if seeds else random.randrange(1 << 32)
+        print("🧪 Tester for Rustc's JVM Codegen Backend started!")
+        print(" ")
+        sys.exit(0 if run_differential(seed, int(count) if count else 200) else 1)
+    BLESS = "--bless" in args
+    selected = [arg for arg in args if arg != "--bless"]
     print("🧪 Tester for Rustc's JVM Codegen Backend started!")
     overall_success = True
 
@@ -86,6 +391,8 @@ def main():
         binary_tests = [os.path.join(binary_dir, d) for d in os.listdir(binary_dir) if os.path.isdir(os.path.join(binary_dir, d))]
     else:
         binary_tests = []
+    if selected:
+        binary_tests = [test for test in binary_tests if os.path.basename(test) in selected]
     print(f"|- 📦 Running {len(binary_tests)} binary build tests...")
     for idx, test_dir in enumerate(binary_tests):
         if not process_test(test_dir):
@@ -1,4 +1,7 @@
 pushd java-linker
 cargo build
 popd
+pushd cargo-jvm
+cargo build
+popd
 cargo build
\ No newline at end of file
@@ -0,0 +1,385 @@
+//! `cargo jvm`, which runs Cargo with the JVM backend.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const USAGE: &str = "Usage: cargo jvm <build|run|test|<cargo command>> [<cargo arguments>...] \
+                     [-- <program arguments>...]";
+
+/// The name of the target, which the spec's file is named after
+const TARGET_NAME: &str = "jvm-unknown-unknown";
+
+fn main() -> Result<(), i32> {
+    let args = jvm_args(env::args().skip(1).collect());
+    let Some(command) = args.first().cloned() else {
+        eprintln!("{USAGE}");
+        return Err(1);
+    };
+    if command == "--help" || command == "-h" || command == "help" {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let (cargo_args, program_args) = split_program_args(&args[1..]);
+
+    let target_spec = match backend_root().and_then(|root| write_target_spec(&root)) {
+        Ok(target_spec) => target_spec,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            return Err(1);
+        }
+    };
+    match command.as_str() {
+        "run" => run(&target_spec, cargo_args, program_args),
+        "test" => test(&target_spec, cargo_args, program_args),
+        _ => {
+            let status = cargo(&command, &target_spec)
+                .args(&args[1..])
+                .status()
+                .map_err(|err| could_not_run("cargo", &err))?;
+            exit_code(status.code())
+        }
+    }
+}
+
+/// The arguments of `cargo jvm`, which Cargo runs as `cargo-jvm jvm ...`
+fn jvm_args(mut args: Vec<String>) -> Vec<String> {
+    if args.first().is_some_and(|arg| arg == "jvm") {
+        args.remove(0);
+    }
+    args
+}
+
+/// Splits the arguments after the command into Cargo's and the program's, at the first `--`
+fn split_program_args(args: &[String]) -> (&[String], &[String]) {
+    match args.iter().position(|arg| arg == "--") {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &[]),
+    }
+}
+
+/// The repository of the backend: `RUSTC_CODEGEN_JVM_HOME`, or the one this executable was built
+/// in, as `<root>/cargo-jvm/target/<profile>/cargo-jvm`
+fn backend_root() -> io::Result<PathBuf> {
+    let root = match env::var_os("RUSTC_CODEGEN_JVM_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => {
+            let executable = env::current_exe()?.canonicalize()?;
+            match executable.ancestors().nth(4) {
+                Some(root) => root.to_path_buf(),
+                None => PathBuf::new(),
+            }
+        }
+    };
+    if root.join(format!("{TARGET_NAME}.json")).is_file() {
+        Ok(root)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "`{}` is not the rustc_codegen_jvm repository, set `RUSTC_CODEGEN_JVM_HOME` to it",
+                root.display()
+            ),
+        ))
+    }
+}
+
+/// Writes the repository's target spec with the absolute paths of the backend and `java-linker`
+/// to its `target` directory, returning the path of the written spec
+fn write_target_spec(root: &Path) -> io::Result<PathBuf> {
+    let backend = root.join("target").join("debug").join(format!(
+        "{}rustc_codegen_jvm{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    ));
+    let linker = root
+        .join("java-linker")
+        .join("target")
+        .join("debug")
+        .join(format!("java-linker{}", env::consts::EXE_SUFFIX));
+    for (path, build) in [(&backend, "cargo build"), (&linker, "cargo build` in `java-linker")] {
+        if !path.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` is missing, run `{build}` first", path.display()),
+            ));
+        }
+    }
+
+    let spec = fs::read_to_string(root.join(format!("{TARGET_NAME}.json")))?;
+    let edited = edit_target_spec(&spec, &backend, &linker);
+
+    let spec_dir = root.join("target").join("cargo-jvm");
+    fs::create_dir_all(&spec_dir)?;
+    let spec_path = spec_dir.join(format!("{TARGET_NAME}.json"));
+    // Rewritten only when it changes, as Cargo rebuilds everything when the spec does
+    if fs::read_to_string(&spec_path).ok().as_deref() != Some(edited.as_str()) {
+        fs::write(&spec_path, edited)?;
+    }
+    Ok(spec_path)
+}
+
+/// The target spec with its `linker` and `default-codegen-backend` set to the paths
+fn edit_target_spec(spec: &str, backend: &Path, linker: &Path) -> String {
+    let mut edited = String::with_capacity(spec.len());
+    for line in spec.lines() {
+        let key = line.trim_start().split(':').next().unwrap_or_default();
+        let path = match key {
+            "\"linker\"" => Some(linker),
+            "\"default-codegen-backend\"" => Some(backend),
+            _ => None,
+        };
+        match path {
+            Some(path) => {
+                let indentation = &line[..line.len() - line.trim_start().len()];
+                let comma = if line.trim_end().ends_with(',') { "," } else { "" };
+                edited.push_str(&format!(
+                    "{indentation}{key}: {}{comma}",
+                    json_string(&path.to_string_lossy())
+                ));
+            }
+            None => edited.push_str(line),
+        }
+        edited.push('\n');
+    }
+    edited
+}
+
+/// A Cargo command for the target, from `CARGO` when run by Cargo
+fn cargo(command: &str, target_spec: &Path) -> Command {
+    let mut cargo = Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")));
+    cargo.arg(command).arg("--target").arg(target_spec);
+    cargo
+}
+
+/// The build of `cargo jvm run`
+fn run_build(target_spec: &Path, cargo_args: &[String]) -> Command {
+    let mut build = cargo("build", target_spec);
+    build.args(cargo_args);
+    build
+}
+
+/// The build of `cargo jvm test`, which builds the tests without running them
+fn test_build(target_spec: &Path, cargo_args: &[String]) -> Command {
+    let mut build = cargo("test", target_spec);
+    build.arg("--no-run").args(cargo_args);
+    build
+}
+
+/// Builds the crate's binary and runs its JAR
+fn run(target_spec: &Path, cargo_args: &[String], program_args: &[String]) -> Result<(), i32> {
+    let jars = build_executables(run_build(target_spec, cargo_args))?;
+    let jar = match jars.as_slice() {
+        [jar] => jar,
+        [] => {
+            eprintln!("Error: no binary was built, `cargo jvm run` runs the crate's binary");
+            return Err(1);
+        }
+        _ => {
+            eprintln!("Error: several binaries were built, pick the one to run with `--bin`");
+            return Err(1);
+        }
+    };
+    let status = java()
+        .arg("-jar")
+        .arg(jar)
+        .args(program_args)
+        .status()
+        .map_err(|err| could_not_run("java", &err))?;
+    exit_code(status.code())
+}
+
+/// Builds the crate's tests and runs the JAR of each, with the harness arguments
+fn test(target_spec: &Path, cargo_args: &[String], harness_args: &[String]) -> Result<(), i32> {
+    let jars = build_executables(test_build(target_spec, cargo_args))?;
+    let mut failed = 0;
+    for jar in &jars {
+        eprintln!("     Running {}", jar.display());
+        let status = java()
+            .arg("-jar")
+            .arg(jar)
+            .args(harness_args)
+            .status()
+            .map_err(|err| could_not_run("java", &err))?;
+        if !status.success() {
+            failed += 1;
+        }
+    }
+    if failed == 0 {
+        Ok(())
+    } else {
+        eprintln!("Error: {failed} of {} test JARs failed", jars.len());
+        Err(101)
+    }
+}
+
+/// Runs a Cargo build, returning the JARs of the executables it built, which Cargo reports in its
+/// JSON messages
+fn build_executables(mut build: Command) -> Result<Vec<PathBuf>, i32> {
+    let output = build
+        .arg("--message-format=json-render-diagnostics")
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| could_not_run("cargo", &err))?;
+    if !output.status.success() {
+        return exit_code(output.status.code()).map(|()| Vec::new());
+    }
+    Ok(executables(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The executables of Cargo's JSON messages
+fn executables(messages: &str) -> Vec<PathBuf> {
+    messages
+        .lines()
+        .filter(|message| message.contains("\"reason\":\"compiler-artifact\""))
+        .filter_map(|message| json_string_field(message, "executable"))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// `java`, from `JAVA_HOME` or the `PATH`
+fn java() -> Command {
+    match env::var_os("JAVA_HOME") {
+        Some(java_home) => Command::new(Path::new(&java_home).join("bin").join("java")),
+        None => Command::new("java"),
+    }
+}
+
+fn could_not_run(program: &str, err: &io::Error) -> i32 {
+    eprintln!("Error: could not run `{program}`: {err}");
+    1
+}
+
+/// Exits with the code a command exited with, 1 if it was killed
+fn exit_code(code: Option<i32>) -> Result<(), i32> {
+    match code {
+        Some(0) => Ok(()),
+        Some(code) => Err(code),
+        None => Err(1),
+    }
+}
+
+/// The value of a string field of a JSON object on a single line, `None` if it is missing or not
+/// a string, as the `"executable": null` of libraries
+fn json_string_field(json: &str, name: &str) -> Option<String> {
+    let start = json.find(&format!("\"{name}\":\""))? + name.len() + 4;
+    let mut value = String::new();
+    let mut chars = json[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// A JSON string of the text
+fn json_string(text: &str) -> String {
+    let mut json = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                json.push('\\');
+                json.push(c);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn command_args(command: &Command) -> Vec<String> {
+        command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn drops_the_subcommand_of_cargo() {
+        assert_eq!(jvm_args(strings(&["jvm", "build", "--release"])), strings(&["build", "--release"]));
+        assert_eq!(jvm_args(strings(&["build", "jvm"])), strings(&["build", "jvm"]));
+        assert!(jvm_args(strings(&["jvm"])).is_empty());
+    }
+
+    #[test]
+    fn splits_program_args() {
+        let args = strings(&["--release", "--", "a", "--", "b"]);
+        let (cargo_args, program_args) = split_program_args(&args);
+        assert_eq!(cargo_args, strings(&["--release"]));
+        assert_eq!(program_args, strings(&["a", "--", "b"]));
+
+        let args = strings(&["--bin", "app"]);
+        let (cargo_args, program_args) = split_program_args(&args);
+        assert_eq!(cargo_args, strings(&["--bin", "app"]));
+        assert!(program_args.is_empty());
+
+        let args = strings(&["--"]);
+        let (cargo_args, program_args) = split_program_args(&args);
+        assert!(cargo_args.is_empty() && program_args.is_empty());
+    }
+
+    #[test]
+    fn builds_for_the_target() {
+        let target_spec = Path::new("/jvm/target/cargo-jvm/jvm-unknown-unknown.json");
+        assert_eq!(
+            command_args(&run_build(target_spec, &strings(&["--bin", "app"]))),
+            strings(&["build", "--target", "/jvm/target/cargo-jvm/jvm-unknown-unknown.json", "--bin", "app"])
+        );
+        assert_eq!(
+            command_args(&test_build(target_spec, &strings(&["--lib"]))),
+            strings(&["test", "--target", "/jvm/target/cargo-jvm/jvm-unknown-unknown.json", "--no-run", "--lib"])
+        );
+    }
+
+    #[test]
+    fn sets_the_paths_of_the_target_spec() {
+        let spec = "{\n  \"arch\": \"jvm\",\n  \"linker\": \"java-linker\",\n  \
+                    \"default-codegen-backend\": \"rustc_codegen_jvm\"\n}\n";
+        assert_eq!(
+            edit_target_spec(spec, Path::new(r"C:\jvm\backend.dll"), Path::new("/jvm/\"linker\"")),
+            "{\n  \"arch\": \"jvm\",\n  \"linker\": \"/jvm/\\\"linker\\\"\",\n  \
+             \"default-codegen-backend\": \"C:\\\\jvm\\\\backend.dll\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn finds_executables() {
+        let messages = concat!(
+            r#"{"reason":"compiler-artifact","target":{"name":"lib"},"executable":null}"#,
+            "\n",
+            r#"{"reason":"compiler-artifact","target":{"name":"app"},"executable":"/app/target/app.jar"}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":true}"#,
+        );
+        assert_eq!(executables(messages), vec![PathBuf::from("/app/target/app.jar")]);
+    }
+
+    #[test]
+    fn reads_json_strings() {
+        assert_eq!(json_string_field(r#"{"a":"b\"c\\d\u00e9"}"#, "a").as_deref(), Some("b\"c\\d\u{e9}"));
+        assert_eq!(json_string_field(r#"{"a":1,"b":"x"}"#, "a"), None);
+        assert_eq!(json_string_field(r#"{"a":"unterminated"#, "a"), None);
+        assert_eq!(json_string(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+}
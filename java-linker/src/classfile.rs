@@ -0,0 +1,223 @@
+//! Reading the class files the linker rewrites or looks into, up to what it needs of them.
+
+use std::io;
+
+/// A class file, or an attribute of one, read from the start
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pub offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, offset: 0 }
+    }
+
+    pub fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset + length).ok_or_else(invalid)?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// The bytes of an attribute, whose length comes first
+    pub fn attribute_info(&mut self) -> io::Result<&'a [u8]> {
+        let length = self.u32()?;
+        self.bytes(usize::try_from(length).map_err(|_| invalid())?)
+    }
+
+    fn skip_attributes(&mut self) -> io::Result<()> {
+        let count = self.u16()?;
+        for _ in 0..count {
+            // The name
+            self.bytes(2)?;
+            self.attribute_info()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a class file")
+}
+
+/// An entry of a constant pool: its tag and the bytes that follow it
+#[derive(Clone, Copy)]
+pub struct Constant<'a> {
+    pub tag: u8,
+    pub info: &'a [u8],
+}
+
+impl Constant<'_> {
+    /// The constant pool index at an offset of the entry's bytes
+    pub fn index(&self, offset: usize) -> Option<u16> {
+        let bytes = self.info.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// The constant pool of a class file, by index
+pub struct ConstantPool<'a> {
+    /// The entries from index 0, `None` for index 0 and for the second index `long` and `double`
+    /// constants take
+    entries: Vec<Option<Constant<'a>>>,
+}
+
+impl<'a> ConstantPool<'a> {
+    /// Reads a class file up to the end of its constant pool
+    pub fn read(reader: &mut Reader<'a>) -> io::Result<ConstantPool<'a>> {
+        if reader.bytes(4)? != [0xCA, 0xFE, 0xBA, 0xBE] {
+            return Err(invalid());
+        }
+        // The version
+        reader.bytes(4)?;
+        let constant_pool_count = reader.u16()?;
+        let mut entries = vec![None];
+        while entries.len() < usize::from(constant_pool_count) {
+            let tag = reader.u8()?;
+            let info = match tag {
+                1 => {
+                    let length = reader.u16()?;
+                    let start = reader.offset - 2;
+                    reader.bytes(usize::from(length))?;
+                    &reader.data[start..reader.offset]
+                }
+                7 | 8 | 16 | 19 | 20 => reader.bytes(2)?,
+                15 => reader.bytes(3)?,
+                3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => reader.bytes(4)?,
+                5 | 6 => reader.bytes(8)?,
+                _ => return Err(invalid()),
+            };
+            entries.push(Some(Constant { tag, info }));
+            // `long` and `double` constants take two entries
+            if matches!(tag, 5 | 6) {
+                entries.push(None);
+            }
+        }
+        Ok(ConstantPool { entries })
+    }
+
+    /// The constant pool count of the class file, one more than the last index
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entries with their indices, in order
+    pub fn iter(&self) -> impl Iterator<Item = (u16, Constant<'a>)> + '_ {
+        self.entries.iter().enumerate().filter_map(|(index, entry)| {
+            Some((u16::try_from(index).ok()?, (*entry)?))
+        })
+    }
+
+    pub fn get(&self, index: u16) -> Option<Constant<'a>> {
+        *self.entries.get(usize::from(index))?
+    }
+
+    /// The value of a `CONSTANT_Utf8` entry, in the modified UTF-8 of class files
+    pub fn utf8(&self, index: u16) -> Option<&'a [u8]> {
+        self.get(index).filter(|constant| constant.tag == 1).map(|constant| &constant.info[2..])
+    }
+}
+
+/// Whether a class declares a `public static` method of a name and descriptor
+pub fn declares_static_method(data: &[u8], name: &[u8], descriptor: &[u8]) -> io::Result<bool> {
+    const PUBLIC_STATIC: u16 = 0x0001 | 0x0008;
+    let mut reader = Reader::new(data);
+    let constant_pool = ConstantPool::read(&mut reader)?;
+    // The access flags, this class and the super class
+    reader.bytes(6)?;
+    let interfaces_count = reader.u16()?;
+    reader.bytes(2 * usize::from(interfaces_count))?;
+    let fields_count = reader.u16()?;
+    for _ in 0..fields_count {
+        // The access flags, name and descriptor
+        reader.bytes(6)?;
+        reader.skip_attributes()?;
+    }
+    let methods_count = reader.u16()?;
+    for _ in 0..methods_count {
+        let access_flags = reader.u16()?;
+        let method_name = constant_pool.utf8(reader.u16()?).ok_or_else(invalid)?;
+        let method_descriptor = constant_pool.utf8(reader.u16()?).ok_or_else(invalid)?;
+        if access_flags & PUBLIC_STATIC == PUBLIC_STATIC
+            && method_name == name
+            && method_descriptor == descriptor
+        {
+            return Ok(true);
+        }
+        reader.skip_attributes()?;
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf8(value: &str) -> Vec<u8> {
+        let mut entry = vec![1];
+        entry.extend_from_slice(&u16::try_from(value.len()).unwrap().to_be_bytes());
+        entry.extend_from_slice(value.as_bytes());
+        entry
+    }
+
+    /// A class `App` with a method `main` of the access flags, a static field `main` and a string
+    /// constant of the name, after a `long` constant
+    fn class_file(access_flags: u16) -> Vec<u8> {
+        let mut data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 11];
+        data.extend(utf8("App"));
+        data.extend([7, 0, 1]);
+        data.extend(utf8("java/lang/Object"));
+        data.extend([7, 0, 3]);
+        data.extend([5, 0, 0, 0, 0, 0, 0, 0, 1]);
+        data.extend(utf8("main"));
+        data.extend(utf8("([Ljava/lang/String;)V"));
+        data.extend([8, 0, 7]);
+        data.extend(utf8("I"));
+        // Public, this class, the super class and no interfaces
+        data.extend([0, 0x21, 0, 2, 0, 4, 0, 0]);
+        // A field `main` with an attribute
+        data.extend([0, 1, 0, 9, 0, 7, 0, 10, 0, 1, 0, 7, 0, 0, 0, 2, 0, 0]);
+        data.extend([0, 1]);
+        data.extend(access_flags.to_be_bytes());
+        data.extend([0, 7, 0, 8, 0, 0]);
+        data.extend([0, 0]);
+        data
+    }
+
+    const MAIN_DESCRIPTOR: &[u8] = b"([Ljava/lang/String;)V";
+
+    #[test]
+    fn reads_constant_pools() {
+        let data = class_file(0x0009);
+        let constant_pool = ConstantPool::read(&mut Reader::new(&data)).unwrap();
+        assert_eq!(constant_pool.count(), 11);
+        assert_eq!(constant_pool.utf8(7), Some(&b"main"[..]));
+        // The second index of the `long`
+        assert!(constant_pool.get(6).is_none());
+        assert_eq!(constant_pool.get(9).and_then(|string| string.index(0)), Some(7));
+        assert!(ConstantPool::read(&mut Reader::new(b"PK\x03\x04")).is_err());
+    }
+
+    #[test]
+    fn finds_static_methods() {
+        assert!(declares_static_method(&class_file(0x0009), b"main", MAIN_DESCRIPTOR).unwrap());
+        assert!(!declares_static_method(&class_file(0x0001), b"main", MAIN_DESCRIPTOR).unwrap());
+        assert!(!declares_static_method(&class_file(0x0009), b"main", b"()V").unwrap());
+        assert!(!declares_static_method(&class_file(0x0009), b"I", MAIN_DESCRIPTOR).unwrap());
+        assert!(declares_static_method(&class_file(0x0009)[..60], b"main", MAIN_DESCRIPTOR).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Debug information split into a companion JAR, with `--split-debuginfo`.
+
+use crate::classfile::{ConstantPool, Reader, invalid};
+use std::collections::HashSet;
+use std::io;
+
+/// The attributes of classes and of their methods' code that only carry debug information
+const DEBUG_ATTRIBUTES: [&[u8]; 5] = [
+    b"SourceFile",
+    b"SourceDebugExtension",
+    b"LineNumberTable",
+    b"LocalVariableTable",
+    b"LocalVariableTypeTable",
+];
+
+/// The path of the debug JAR of an output JAR, as `app-debug.jar` for `app.jar`
+pub fn debug_jar_path(output_jar_path: &str) -> String {
+    match output_jar_path.strip_suffix(".jar") {
+        Some(stem) => format!("{stem}-debug.jar"),
+        None => format!("{output_jar_path}-debug.jar"),
+    }
+}
+
+/// The constant pool indices of the names of the attributes the stripping looks at
+struct AttributeNames {
+    debug: HashSet<u16>,
+    code: Option<u16>,
+}
+
+/// The bytes of a class file without its debug attributes, or `None` if it has none. The names of
+/// the attributes are left in the constant pool, which the rest of the class refers to by index
+pub fn strip_debug_attributes(data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut reader = Reader::new(data);
+    let constant_pool = ConstantPool::read(&mut reader)?;
+    let mut names = AttributeNames { debug: HashSet::new(), code: None };
+    for (index, _) in constant_pool.iter() {
+        match constant_pool.utf8(index) {
+            Some(value) if DEBUG_ATTRIBUTES.contains(&value) => {
+                names.debug.insert(index);
+            }
+            Some(b"Code") => names.code = Some(index),
+            _ => {}
+        }
+    }
+    if names.debug.is_empty() {
+        return Ok(None);
+    }
+
+    // The access flags, this class and the super class
+    reader.bytes(6)?;
+    let interfaces_count = reader.u16()?;
+    reader.bytes(2 * usize::from(interfaces_count))?;
+    let mut result = data[..reader.offset].to_vec();
+    let mut stripped = false;
+    // The fields, then the methods
+    for _ in 0..2 {
+        let count = reader.u16()?;
+        result.extend_from_slice(&count.to_be_bytes());
+        for _ in 0..count {
+            // The access flags, name and descriptor
+            result.extend_from_slice(reader.bytes(6)?);
+            stripped |= copy_attributes(&mut reader, &names, &mut result)?;
+        }
+    }
+    stripped |= copy_attributes(&mut reader, &names, &mut result)?;
+    Ok(stripped.then_some(result))
+}
+
+/// Copies a table of attributes, leaving out the debug attributes, and those of the code.
+/// Returns whether any was left out
+fn copy_attributes(
+    reader: &mut Reader<'_>,
+    names: &AttributeNames,
+    result: &mut Vec<u8>,
+) -> io::Result<bool> {
+    let count = reader.u16()?;
+    let mut attributes = Vec::new();
+    let mut kept: u16 = 0;
+    let mut stripped = false;
+    for _ in 0..count {
+        let name_index = reader.u16()?;
+        let info = reader.attribute_info()?;
+        if names.debug.contains(&name_index) {
+            stripped = true;
+            continue;
+        }
+        kept += 1;
+        attributes.extend_from_slice(&name_index.to_be_bytes());
+        if names.code == Some(name_index) {
+            let mut code_reader = Reader::new(info);
+            // The maximum stack and locals
+            code_reader.bytes(4)?;
+            let code_length = code_reader.u32()?;
+            code_reader.bytes(usize::try_from(code_length).map_err(|_| invalid())?)?;
+            let exception_table_length = code_reader.u16()?;
+            code_reader.bytes(8 * usize::from(exception_table_length))?;
+            let mut code = info[..code_reader.offset].to_vec();
+            stripped |= copy_attributes(&mut code_reader, names, &mut code)?;
+            let length = u32::try_from(code.len()).map_err(|_| invalid())?;
+            attributes.extend_from_slice(&length.to_be_bytes());
+            attributes.extend_from_slice(&code);
+        } else {
+            let length = u32::try_from(info.len()).map_err(|_| invalid())?;
+            attributes.extend_from_slice(&length.to_be_bytes());
+            attributes.extend_from_slice(info);
+        }
+    }
+    result.extend_from_slice(&kept.to_be_bytes());
+    result.extend_from_slice(&attributes);
+    Ok(stripped)
+}
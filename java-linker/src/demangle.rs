@@ -0,0 +1,144 @@
+//! Demangling of the Rust symbols in JVM stack traces, with `java-linker --demangle`.
+
+/// The line with each mangled symbol in it demangled
+pub fn demangle_line(line: &str) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("_ZN") {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match demangle_symbol(rest) {
+            Some((path, length)) => {
+                result.push_str(&path);
+                rest = &rest[length..];
+            }
+            None => {
+                result.push_str("_ZN");
+                rest = &rest["_ZN".len()..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The path a symbol at the start of the text names, and the length of the symbol. A symbol is
+/// `_ZN`, its path's segments each prefixed by their length, and `E`, with a last segment of `h`
+/// and the hash of the instance
+fn demangle_symbol(text: &str) -> Option<(String, usize)> {
+    let mut rest = text.strip_prefix("_ZN")?;
+    let mut segments = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let length: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + length)?;
+        segments.push(segment);
+        rest = &rest[digits + length..];
+    }
+    let length = text.len() - rest.len() + 1;
+    if let Some(hash) = segments.last()
+        && hash.len() == 17
+        && hash.starts_with('h')
+        && hash[1..].chars().all(|c| c.is_ascii_hexdigit())
+    {
+        segments.pop();
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    let path: Vec<String> = segments.into_iter().map(demangle_segment).collect();
+    Some((path.join("::"), length))
+}
+
+/// A segment of a path, with its escapes replaced by the characters they stand for. The `..` of
+/// the paths inside a segment, as in the type of `drop_in_place<alloc..vec..Vec>`, are `$$`s once
+/// the `.`s are replaced, and a single `.` a `$`
+fn demangle_segment(segment: &str) -> String {
+    // A segment starting with an escape is prefixed by a `_`
+    let segment = segment
+        .strip_prefix('_')
+        .filter(|rest| rest.starts_with('$'))
+        .unwrap_or(segment);
+    let mut result = String::new();
+    let mut rest = segment;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some((character, length)) = escape(rest) {
+            result.push(character);
+            rest = &rest[length..];
+        } else if rest.starts_with("$$") {
+            result.push_str("::");
+            rest = &rest[2..];
+        } else {
+            result.push('.');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The character an escape at the start of the text stands for, as `<` for `$LT$`, and the
+/// length of the escape
+fn escape(text: &str) -> Option<(char, usize)> {
+    let end = text[1..].find('$')? + 2;
+    let character = match &text[1..end - 1] {
+        "SP" => '@',
+        "BP" => '*',
+        "RF" => '&',
+        "LT" => '<',
+        "GT" => '>',
+        "LP" => '(',
+        "RP" => ')',
+        "C" => ',',
+        code => {
+            let hex = code.strip_prefix('u')?;
+            if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            // The escaped characters are printable, unlike those of `.u16` in `$u16$`
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?).filter(|c| !c.is_control())?
+        }
+    };
+    Some((character, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangles_legacy_symbols() {
+        assert_eq!(
+            demangle_line(
+                "\tat app._ZN4core3ptr34drop_in_place$LT$std$$fs$$File$GT$17h0123456789abcdefE(Unknown Source)"
+            ),
+            "\tat app.core::ptr::drop_in_place<std::fs::File>(Unknown Source)"
+        );
+        assert_eq!(demangle_line("_ZN3app4mainE _ZN3app3runE"), "app::main app::run");
+    }
+
+    #[test]
+    fn keeps_hashes_that_are_not_hashes() {
+        assert_eq!(demangle_line("_ZN3app5hello17habcdefghijklmnopE"), "app::hello::habcdefghijklmnop");
+    }
+
+    #[test]
+    fn leaves_other_text() {
+        assert_eq!(demangle_line("at java.lang.Thread.run(Thread.java:1583)"), "at java.lang.Thread.run(Thread.java:1583)");
+        assert_eq!(demangle_line("_ZN3app"), "_ZN3app");
+        assert_eq!(demangle_line("_ZNE _ZN99appE"), "_ZNE _ZN99appE");
+        assert_eq!(demangle_line("_RNvCs1234_3app4main"), "_RNvCs1234_3app4main");
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(demangle_segment("_$LT$impl$u20$app$$Trait$u20$for$u20$$RF$str$GT$"), "<impl app::Trait for &str>");
+        assert_eq!(demangle_segment("$LP$$RP$$C$$BP$$SP$"), "(),*@");
+        assert_eq!(demangle_segment("closure$u7b$$u7b$closure$u7d$$u7d$"), "closure{{closure}}");
+        // A `.` of the symbol, and an escape of a control character
+        assert_eq!(demangle_segment("call_once$vtable.shim"), "call_once.vtable.shim");
+        assert_eq!(demangle_segment("a$u1f$b"), "a.u1f.b");
+    }
+}
@@ -1,21 +1,73 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use regex::Regex;
 use zip::write::{SimpleFileOptions, ZipWriter};
+use zip::ZipArchive;
 use zip::CompressionMethod;
 
+mod classfile;
+mod debuginfo;
+mod demangle;
+mod relocate;
+
+use relocate::Relocation;
+
 fn main() -> Result<(), i32> {
-    let args: Vec<String> = env::args().collect();
+    let args = match expand_response_files(env::args().collect()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error reading response file: {err}");
+            return Err(1);
+        }
+    };
+    if args.get(1).is_some_and(|arg| arg == "--demangle") {
+        return demangle_stack_traces(&args[2..]);
+    }
     if args.len() < 3 {
-        eprintln!("Usage: java-linker <input_class_files...> -o <output_jar_file>");
+        eprintln!(
+            "Usage: java-linker [@<response-file>...] <input_class_jar_and_rlib_files...> \
+             -o <output_jar_file> \
+             [--automatic-module-name[=<name>]] [--osgi] [--sign-keystore=<keystore> \
+             --sign-alias=<alias> [--sign-password-env=<variable>]] \
+             [--resource <path>[:<jar-path>]...] [--include=<glob>...] [--exclude=<glob>...] \
+             [--relocate <package>:<package>...] [--duplicates=error|first|last] \
+             [--compression store|deflate] [--compression-level <0-9>] [--align] \
+             [--split-debuginfo] [--native-library <path>...] [--no-launcher] \
+             [--dynamic-crates[=<dir>]]\n       \
+             java-linker --demangle [<stack-trace-file>...]"
+        );
         return Err(1);
     }
 
     let mut input_files: Vec<String> = Vec::new();
     let mut output_file: Option<String> = None;
+    // `--automatic-module-name[=<name>]` and `--osgi`, given as `-C link-arg=...`
+    let mut module_name: Option<String> = None;
+    let mut osgi = false;
+    // `--sign-keystore=<keystore>`, `--sign-alias=<alias>` and `--sign-password-env=<variable>`
+    let mut signing = Signing::default();
+    // `--resource <path>[:<jar-path>]`, `--native-library <path>`, `--include=<glob>` and
+    // `--exclude=<glob>`
+    let mut resources = Vec::new();
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+    // `--relocate <package>:<package>`
+    let mut relocations = Vec::new();
+    // `--duplicates=error|first|last`
+    let mut duplicates = DuplicatePolicy::First;
+    // `--compression store|deflate`, `--compression-level <level>` and `--align`
+    let mut compression = Compression::default();
+    // `--split-debuginfo`
+    let mut split_debuginfo = false;
+    // `--no-launcher`
+    let mut launcher = true;
+    // `--dynamic-crates[=<dir>]`
+    let mut dynamic_crates: Option<String> = None;
 
     let mut i = 1;
     while i < args.len() {
@@ -28,6 +80,162 @@ fn main() -> Result<(), i32> {
                 eprintln!("Error: -o flag requires an output file path");
                 return Err(1);
             }
+        } else if arg == "--automatic-module-name" {
+            module_name.get_or_insert_with(String::new);
+            i += 1;
+        } else if let Some(name) = arg.strip_prefix("--automatic-module-name=") {
+            module_name = Some(name.to_string());
+            i += 1;
+        } else if arg == "--osgi" {
+            osgi = true;
+            i += 1;
+        } else if arg == "--resource" {
+            if i + 1 < args.len() {
+                resources.push(args[i + 1].clone());
+                i += 2;
+            } else {
+                eprintln!("Error: --resource flag requires a path");
+                return Err(1);
+            }
+        } else if let Some(resource) = arg.strip_prefix("--resource=") {
+            resources.push(resource.to_string());
+            i += 1;
+        } else if arg == "--native-library" || arg.starts_with("--native-library=") {
+            let path = match arg.strip_prefix("--native-library=") {
+                Some(path) => path,
+                None if i + 1 < args.len() => {
+                    i += 1;
+                    &args[i]
+                }
+                None => {
+                    eprintln!("Error: --native-library flag requires a path");
+                    return Err(1);
+                }
+            };
+            match native_library_resource(path) {
+                Some(resource) => resources.push(resource),
+                None => {
+                    eprintln!("Error: --native-library {} is not a file", path);
+                    return Err(1);
+                }
+            }
+            i += 1;
+        } else if arg == "--relocate" || arg.starts_with("--relocate=") {
+            let value = match arg.strip_prefix("--relocate=") {
+                Some(value) => value,
+                None if i + 1 < args.len() => {
+                    i += 1;
+                    args[i].as_str()
+                }
+                None => {
+                    eprintln!("Error: --relocate flag requires a relocation");
+                    return Err(1);
+                }
+            };
+            match Relocation::parse(value) {
+                Some(relocation) => relocations.push(relocation),
+                None => {
+                    eprintln!(
+                        "Error: invalid relocation `{value}`, expected `<package>:<package>`"
+                    );
+                    return Err(1);
+                }
+            }
+            i += 1;
+        } else if arg == "--compression" || arg.starts_with("--compression=") {
+            let value = match arg.strip_prefix("--compression=") {
+                Some(value) => value,
+                None if i + 1 < args.len() => {
+                    i += 1;
+                    args[i].as_str()
+                }
+                None => {
+                    eprintln!("Error: --compression flag requires a method");
+                    return Err(1);
+                }
+            };
+            compression.method = match value {
+                "store" => CompressionMethod::Stored,
+                "deflate" => CompressionMethod::DEFLATE,
+                _ => {
+                    eprintln!(
+                        "Error: invalid compression `{value}`, expected `store` or `deflate`"
+                    );
+                    return Err(1);
+                }
+            };
+            i += 1;
+        } else if arg == "--compression-level" || arg.starts_with("--compression-level=") {
+            let value = match arg.strip_prefix("--compression-level=") {
+                Some(value) => value,
+                None if i + 1 < args.len() => {
+                    i += 1;
+                    args[i].as_str()
+                }
+                None => {
+                    eprintln!("Error: --compression-level flag requires a level");
+                    return Err(1);
+                }
+            };
+            match value.parse() {
+                Ok(level @ 0..=9) => compression.level = Some(level),
+                _ => {
+                    eprintln!("Error: invalid compression level `{value}`, expected 0 to 9");
+                    return Err(1);
+                }
+            }
+            i += 1;
+        } else if arg == "--split-debuginfo" {
+            split_debuginfo = true;
+            i += 1;
+        } else if arg == "--align" {
+            compression.align = true;
+            i += 1;
+        } else if arg == "--no-launcher" {
+            launcher = false;
+            i += 1;
+        } else if arg == "--dynamic-crates" || arg.starts_with("--dynamic-crates=") {
+            let directory =
+                arg.strip_prefix("--dynamic-crates=").unwrap_or(DYNAMIC_CRATES_DIRECTORY);
+            // The `Class-Path` of the manifest is relative to the JAR
+            if directory.is_empty() || Path::new(directory).is_absolute() {
+                eprintln!(
+                    "Error: --dynamic-crates needs a directory relative to the output JAR, not \
+                     `{directory}`"
+                );
+                return Err(1);
+            }
+            dynamic_crates = Some(directory.trim_end_matches('/').to_string());
+            i += 1;
+        } else if let Some(policy) = arg.strip_prefix("--duplicates=") {
+            duplicates = match policy {
+                "error" => DuplicatePolicy::Error,
+                "first" => DuplicatePolicy::First,
+                "last" => DuplicatePolicy::Last,
+                _ => {
+                    eprintln!(
+                        "Error: invalid --duplicates policy `{policy}`, expected `error`, \
+                         `first` or `last`"
+                    );
+                    return Err(1);
+                }
+            };
+            i += 1;
+        } else if let Some(glob) = arg.strip_prefix("--include=") {
+            includes.push(glob_regex(glob));
+            i += 1;
+        } else if let Some(glob) = arg.strip_prefix("--exclude=") {
+            excludes.push(glob_regex(glob));
+            i += 1;
+        } else if let Some(keystore) = arg.strip_prefix("--sign-keystore=") {
+            signing.keystore = Some(keystore.to_string());
+            i += 1;
+        } else if let Some(alias) = arg.strip_prefix("--sign-alias=") {
+            signing.alias = Some(alias.to_string());
+            i += 1;
+        } else if let Some(variable) = arg.strip_prefix("--sign-password-env=") {
+            signing.password_env = Some(variable.to_string());
+            i += 1;
         } else if !arg.starts_with("-Wl") && arg != "-no-pie" && arg != "-nodefaultlibs" {
             input_files.push(arg.clone());
             i += 1;
@@ -41,6 +249,24 @@ fn main() -> Result<(), i32> {
         return Err(1);
     }
 
+    if signing.keystore.is_some() != signing.alias.is_some() {
+        eprintln!("Error: --sign-keystore and --sign-alias must be given together");
+        return Err(1);
+    }
+
+    if compression.level.is_some() && compression.method != CompressionMethod::DEFLATE {
+        eprintln!("Error: --compression-level only applies to --compression deflate");
+        return Err(1);
+    }
+
+    // jarsigner writes the JAR anew, without the alignment
+    if compression.align && signing.keystore.is_some() {
+        eprintln!(
+            "Error: --align can't be used with --sign-keystore, align the signed JAR instead"
+        );
+        return Err(1);
+    }
+
     let output_file_path = match output_file {
         Some(path) => path,
         None => {
@@ -49,107 +275,1199 @@ fn main() -> Result<(), i32> {
         }
     };
 
-    let main_classes = find_main_classes(&input_files);
+    // The rlibs of the crates the backend lists, which rustc leaves out with `-C lto`
+    match listed_rlibs(&input_files) {
+        Ok(rlibs) => input_files.extend(rlibs),
+        Err(err) => {
+            eprintln!("Error reading the linked crates: {}", err);
+            return Err(1);
+        }
+    }
+
+    let class_map = match loose_class_map(&input_files) {
+        Ok(class_map) => class_map,
+        Err(err) => {
+            eprintln!("Error reading class map: {}", err);
+            return Err(1);
+        }
+    };
+
+    let main_classes = find_method_classes(&input_files, b"main", b"([Ljava/lang/String;)V");
 
     if main_classes.len() > 1 {
         eprintln!("Error: Multiple classes with 'main' method found: {:?}", main_classes);
         return Err(1);
     }
 
-    // Prepare the regex for sanitizing the main class file name.
-    let re = Regex::new(r"^(.*?)-[0-9a-f]+(\.class)$").unwrap();
-    let main_class_name = main_classes.first().map(|class_path| {
+    // The `premain` of a Java agent, see the backend's `#[jvm::premain]`
+    let premain_descriptor = b"(Ljava/lang/String;Ljava/lang/instrument/Instrumentation;)V";
+    let premain_classes = find_method_classes(&input_files, b"premain", premain_descriptor);
+    if premain_classes.len() > 1 {
+        eprintln!("Error: Multiple classes with 'premain' method found: {:?}", premain_classes);
+        return Err(1);
+    }
+
+    let binary_class_name = |class_path: &String| {
         let file_name = Path::new(class_path)
             .file_name()
             .unwrap()
             .to_str()
             .unwrap();
-        // Sanitize the file name if it matches the pattern.
-        let cleaned_name = if let Some(caps) = re.captures(file_name) {
-            format!("{}{}", &caps[1], &caps[2])
-        } else {
-            file_name.to_string()
-        };
+        let entry_name = class_entry_name(&class_map, file_name);
         // Remove the ".class" extension and replace "/" with "." to get the fully qualified name.
-        cleaned_name.trim_end_matches(".class").replace("/", ".")
+        entry_name.trim_end_matches(".class").replace("/", ".")
+    };
+    let main_class_name = main_classes.first().map(binary_class_name);
+
+    // Without a name of its own, the module is named after the crate, or the JAR when the class
+    // map doesn't name it
+    let crate_name = match loose_crate_name(&input_files) {
+        Ok(crate_name) => crate_name,
+        Err(err) => {
+            eprintln!("Error reading class map: {}", err);
+            return Err(1);
+        }
+    };
+    let bundle_name = (module_name.is_some() || osgi).then(|| match &module_name {
+        Some(name) if !name.is_empty() => name.clone(),
+        _ => default_module_name(crate_name.as_deref().unwrap_or_else(|| {
+            Path::new(&output_file_path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+        })),
     });
+    let headers = ManifestHeaders {
+        automatic_module_name: module_name.and(bundle_name.clone()),
+        bundle_symbolic_name: bundle_name.filter(|_| osgi),
+        premain_class: premain_classes.first().map(binary_class_name),
+    };
+
+    let resources = match resource_files(&resources) {
+        Ok(resources) => resources,
+        Err(err) => {
+            eprintln!("Error reading resources: {}", err);
+            return Err(1);
+        }
+    };
+    let filter = EntryFilter { includes, excludes };
+    // The JAR of a binary starts with its launcher, which `jarsigner` would drop, so a signed JAR
+    // gets it once it is signed
+    let launcher = launcher && main_class_name.is_some();
+    let signed = signing.keystore.is_some();
+    let contents = JarContents {
+        class_map,
+        resources,
+        filter,
+        relocations,
+        duplicates,
+        split_debuginfo,
+        launcher: launcher && !signed,
+        dynamic_crates,
+    };
 
-    if let Err(err) = create_jar(&input_files, &output_file_path, main_class_name.as_deref()) {
+    let main_class_name = main_class_name.as_deref();
+    let output_jar_path = output_file_path.as_str();
+    if let Err(err) = create_jar(
+        &input_files,
+        output_jar_path,
+        &compression,
+        main_class_name,
+        &headers,
+        &contents,
+    ) {
         eprintln!("Error creating JAR: {}", err);
         return Err(1);
     }
+    if let Err(err) = sign_jar(&output_file_path, &signing) {
+        eprintln!("Error signing JAR: {}", err);
+        return Err(1);
+    }
+    if launcher && signed && let Err(err) = prepend_launcher(&output_file_path) {
+        eprintln!("Error adding the launcher: {}", err);
+        return Err(1);
+    }
+    if launcher && let Err(err) = make_executable(&output_file_path) {
+        eprintln!("Error making the JAR executable: {}", err);
+        return Err(1);
+    }
 
     println!("JAR file created successfully: {}", output_file_path);
     Ok(())
 }
 
-fn find_main_classes(class_files: &[String]) -> Vec<String> {
-    // currently very simplified, will implement proper parsing later
+/// The arguments with each `@<file>` replaced by the arguments the file holds. rustc passes the
+/// arguments in a response file when the command line would be too long for the system, as with
+/// crates of many codegen units
+fn expand_response_files(args: Vec<String>) -> io::Result<Vec<String>> {
+    expand_nested_response_files(args, &mut HashSet::new())
+}
+
+/// The arguments with their response files expanded, given the canonical paths of the response
+/// files they come from, which a response file referring back to one of them would never end
+fn expand_nested_response_files(
+    args: Vec<String>,
+    including: &mut HashSet<PathBuf>,
+) -> io::Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => {
+                let with_path = |err: io::Error| io::Error::new(err.kind(), format!("`{path}`: {err}"));
+                let canonical_path = fs::canonicalize(path).map_err(with_path)?;
+                let content = fs::read_to_string(&canonical_path).map_err(with_path)?;
+                if !including.insert(canonical_path.clone()) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("`{path}` refers to itself through its response files"),
+                    ));
+                }
+                // A response file may refer to others
+                expanded.extend(expand_nested_response_files(response_file_args(&content), including)?);
+                including.remove(&canonical_path);
+            }
+            _ => expanded.push(arg),
+        }
+    }
+    Ok(expanded)
+}
+
+/// The arguments of a response file, separated by whitespace as GCC reads them: a backslash
+/// escapes the next character, as rustc escapes spaces and backslashes, and quotes group
+/// characters into one argument
+fn response_file_args(content: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether an argument started, even if it is empty, as `""`
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    arg.push(escaped);
+                }
+                in_arg = true;
+            }
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            _ if quote == Some(c) => quote = None,
+            _ if c.is_whitespace() && quote.is_none() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            _ => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+/// Writes the stack traces of the files, or of the standard input without any, with their Rust
+/// symbols demangled
+fn demangle_stack_traces(files: &[String]) -> Result<(), i32> {
+    let input = if files.is_empty() {
+        io::read_to_string(io::stdin())
+    } else {
+        files.iter().map(fs::read_to_string).collect::<io::Result<String>>()
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("Error reading stack trace: {}", err);
+            return Err(1);
+        }
+    };
+    let mut stdout = io::stdout().lock();
+    for line in input.lines() {
+        if writeln!(stdout, "{}", demangle::demangle_line(line)).is_err() {
+            return Err(1);
+        }
+    }
+    Ok(())
+}
+
+/// The class files that declare a `public static` method, as `main`, leaving out the files that
+/// cannot be read as classes
+fn find_method_classes(class_files: &[String], name: &[u8], descriptor: &[u8]) -> Vec<String> {
+    let mut classes = Vec::new();
+    for file in class_files.iter().filter(|file| has_extension(file, "class")) {
+        let declares_method = fs::read(file).is_ok_and(|data| {
+            classfile::declares_static_method(&data, name, descriptor).unwrap_or(false)
+        });
+        if declares_method {
+            classes.push(file.clone());
+        }
+    }
+    classes
+}
+
+/// The keystore entry the JAR is signed with, if any
+#[derive(Default)]
+struct Signing {
+    keystore: Option<String>,
+    alias: Option<String>,
+    /// The environment variable holding the keystore's password, which `jarsigner` asks for
+    /// otherwise
+    password_env: Option<String>,
+}
+
+/// Signs the JAR in place with `jarsigner`, from `JAVA_HOME` or the `PATH`, which adds the
+/// digests of its entries to the manifest and the `.SF` signature file and signature block under
+/// `META-INF`
+fn sign_jar(jar_path: &str, signing: &Signing) -> io::Result<()> {
+    let (Some(keystore), Some(alias)) = (&signing.keystore, &signing.alias) else {
+        return Ok(());
+    };
+    let jarsigner = match env::var_os("JAVA_HOME") {
+        Some(java_home) => Path::new(&java_home).join("bin").join("jarsigner"),
+        None => "jarsigner".into(),
+    };
+    let mut command = Command::new(&jarsigner);
+    command.arg("-keystore").arg(keystore);
+    if let Some(variable) = &signing.password_env {
+        // The password is read from the variable by `jarsigner`, never passed on its command line
+        command.arg("-storepass:env").arg(variable);
+    }
+    command.arg(jar_path).arg(alias);
+    let status = command.status().map_err(|err| {
+        io::Error::new(err.kind(), format!("could not run `{}`: {err}", jarsigner.display()))
+    })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("`{}` failed with {status}", jarsigner.display())))
+    }
+}
+
+/// The optional headers of the manifest, from the linker's flags
+struct ManifestHeaders {
+    /// The `Automatic-Module-Name`, naming the JAR's module on the module path
+    automatic_module_name: Option<String>,
+    /// The `Bundle-SymbolicName` of an OSGi bundle, which exports all of its packages
+    bundle_symbolic_name: Option<String>,
+    /// The `Premain-Class` of a Java agent, the class with its `premain` method
+    premain_class: Option<String>,
+}
+
+/// What the JAR holds besides the linked classes
+struct JarContents {
+    /// The names of the entries of the classes given as files, by file name
+    class_map: HashMap<String, String>,
+    /// The files bundled with the classes, with the names of their entries
+    resources: Vec<(PathBuf, String)>,
+    /// Which classes and resources are written, by the names of their entries before any
+    /// relocation
+    filter: EntryFilter,
+    /// The packages of the classes that are moved under others
+    relocations: Vec<Relocation>,
+    /// Which input provides an entry several inputs provide
+    duplicates: DuplicatePolicy,
+    /// Whether the debug attributes of the classes are moved to a debug JAR
+    split_debuginfo: bool,
+    /// Whether the JAR starts with the [`LAUNCHER`], as the JAR of a binary does
+    launcher: bool,
+    /// The directory the crates of the rlibs are written to as JARs of their own, relative to the
+    /// JAR, instead of being merged into it
+    dynamic_crates: Option<String>,
+}
+
+impl JarContents {
+    /// The entry of a class, with its name and bytes relocated
+    fn relocated_class(&self, name: &str, data: &[u8]) -> io::Result<(String, Vec<u8>)> {
+        if self.relocations.is_empty() {
+            return Ok((name.to_string(), data.to_vec()));
+        }
+        Ok((
+            relocate::relocate_name(&self.relocations, name),
+            relocate::relocate_class(&self.relocations, data)?,
+        ))
+    }
+}
 
+/// The `--include` and `--exclude` globs the names of the JAR's entries are matched against
+struct EntryFilter {
+    includes: Vec<Regex>,
+    excludes: Vec<Regex>,
+}
 
-    let mut main_classes = Vec::new();
-    // Byte sequences to look for.
-    let main_name = b"main";
-    let main_descriptor = b"([Ljava/lang/String;)V";
+impl EntryFilter {
+    /// Whether an entry is written: it matches an include, if any is given, and no exclude
+    fn includes(&self, name: &str) -> bool {
+        (self.includes.is_empty() || self.includes.iter().any(|glob| glob.is_match(name)))
+            && !self.excludes.iter().any(|glob| glob.is_match(name))
+    }
+}
 
-    for file in class_files {
-        if let Ok(data) = fs::read(file) {
-            let has_main_name = data.windows(main_name.len()).any(|w| w == main_name);
-            let has_main_descriptor = data.windows(main_descriptor.len()).any(|w| w == main_descriptor);
-            if has_main_name && has_main_descriptor {
-                main_classes.push(file.clone());
+/// The regex of a glob over entry names: `*` matches within a directory, `**` across them and `?`
+/// a single character, as in `com/example/**/*.class`
+fn glob_regex(glob: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
             }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
         }
     }
-    main_classes
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+/// The resource of a `--native-library <path>` flag: the library under `META-INF/native`, where
+/// the `rustjvm/NativeLoader` of the backend loads the libraries the JVM does not find from
+fn native_library_resource(path: &str) -> Option<String> {
+    let path_buf = Path::new(path);
+    if !path_buf.is_file() {
+        return None;
+    }
+    let file_name = path_buf.file_name()?.to_str()?;
+    Some(format!("{}:META-INF/native/{}", path, file_name))
+}
+
+/// The files of `--resource <path>[:<jar-path>]` flags with the names of their entries, the files
+/// of a directory under its entry as a directory. A resource without a JAR path is named after the
+/// file or directory
+fn resource_files(resources: &[String]) -> io::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    for resource in resources {
+        let (path, jar_path) = match resource.rsplit_once(':') {
+            Some((path, jar_path)) if !path.is_empty() && !jar_path.contains('\\') => {
+                (PathBuf::from(path), jar_path.trim_matches('/').to_string())
+            }
+            _ => {
+                let path = PathBuf::from(resource);
+                let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                let name = name.to_string();
+                (path, name)
+            }
+        };
+        push_resource_files(&path, jar_path, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn push_resource_files(
+    path: &Path,
+    jar_path: String,
+    files: &mut Vec<(PathBuf, String)>,
+) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push((path.to_path_buf(), jar_path));
+        return Ok(());
+    }
+    // Sorted, so the JAR does not depend on the order the file system lists them in
+    let mut entries: Vec<_> = fs::read_dir(path)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let entry_path = if jar_path.is_empty() { name } else { format!("{jar_path}/{name}") };
+        push_resource_files(&entry.path(), entry_path, files)?;
+    }
+    Ok(())
 }
 
 fn create_jar(
     input_files: &[String],
     output_jar_path: &str,
+    compression: &Compression,
     main_class_name: Option<&str>,
+    headers: &ManifestHeaders,
+    contents: &JarContents,
 ) -> io::Result<()> {
-    let output_file = fs::File::create(output_jar_path)?;
+    // The entries are gathered before any is written, so that the manifest, which comes first,
+    // knows them, and the duplicates are resolved
+    let mut entries = Entries::new(contents.duplicates);
+    // The providers listed in the `META-INF/services` files of every input, by service
+    let mut services: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for input_file in input_files.iter().filter(|file| has_extension(file, "class")) {
+        let path = Path::new(input_file);
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let file_name = class_entry_name(&contents.class_map, file_name);
+        if contents.filter.includes(&file_name) {
+            let (name, data) = contents.relocated_class(&file_name, &fs::read(input_file)?)?;
+            entries.insert(name, data, input_file);
+        }
+    }
+
+    for input_file in input_files.iter().filter(|file| has_extension(file, "jar")) {
+        let mut archive = ZipArchive::new(fs::File::open(input_file)?)?;
+        read_jar_entries(&mut archive, input_file, contents, &mut entries, &mut services)?;
+    }
+
+    // The classes of the rlibs of the crates linked in, and the JARs they carry, like the services
+    // of their providers, or with `--dynamic-crates`, the JARs of the crates the JAR's `Class-Path`
+    // names
+    let mut class_path = Vec::new();
+    for input_file in input_files.iter().filter(|file| has_extension(file, "rlib")) {
+        match &contents.dynamic_crates {
+            Some(directory) => {
+                let jar_name = format!("{directory}/{}", crate_jar_name(input_file));
+                let jar_path = Path::new(output_jar_path).with_file_name(&jar_name);
+                write_crate_jar(input_file, &jar_path, compression, contents)?;
+                class_path.push(jar_name);
+            }
+            None => read_rlib_entries(input_file, contents, &mut entries, &mut services)?,
+        }
+    }
+
+    // The resources, like configuration files, native libraries or assets
+    for (path, name) in &contents.resources {
+        if !contents.filter.includes(name) {
+            continue;
+        }
+        let data = fs::read(path)?;
+        if name.starts_with(SERVICES_DIRECTORY) {
+            push_service_providers(&mut services, name, &data);
+        } else {
+            entries.insert(name.clone(), data, &path.to_string_lossy());
+        }
+    }
+
+    entries.report()?;
+
+    let mut output_file = fs::File::create(output_jar_path)?;
+    // The offsets of the entries count from the start of the file, past the launcher
+    if contents.launcher {
+        output_file.write_all(LAUNCHER.as_bytes())?;
+    }
     let mut zip_writer = ZipWriter::new(output_file);
-    let options = SimpleFileOptions::default()
-        .compression_method(CompressionMethod::DEFLATE)
-        .unix_permissions(0o644);
+    let options = compression.file_options();
 
     // Create META-INF/MANIFEST.MF with the appropriate Main-Class.
-    let manifest_content = create_manifest_content(main_class_name);
+    let class_names: Vec<String> = entries
+        .entries
+        .iter()
+        .filter(|entry| has_extension(&entry.name, "class"))
+        .map(|entry| entry.name.clone())
+        .collect();
+    let manifest_content =
+        create_manifest_content(main_class_name, headers, &class_names, &class_path);
     zip_writer.start_file("META-INF/MANIFEST.MF", options)?;
     zip_writer.write_all(manifest_content.as_bytes())?;
 
-    // Regex to match file names with a -randomnumbers suffix.
-    let re = Regex::new(r"^(.*?)-[0-9a-f]+(\.class)$").unwrap();
-
-    for input_file in input_files {
-        let path = Path::new(input_file);
-        let original_file_name = path.file_name().unwrap().to_str().unwrap();
-        // Remove the random numbers suffix if it exists.
-        let file_name = if let Some(caps) = re.captures(original_file_name) {
-            format!("{}{}", &caps[1], &caps[2])
+    // The classes as they were, when their debug attributes are stripped
+    let mut debug_classes = Vec::new();
+    for entry in &entries.entries {
+        zip_writer.start_file(entry.name.as_str(), options)?;
+        let stripped = if contents.split_debuginfo && has_extension(&entry.name, "class") {
+            debuginfo::strip_debug_attributes(&entry.data)?
         } else {
-            original_file_name.to_string()
+            None
         };
+        match stripped {
+            Some(data) => {
+                zip_writer.write_all(&data)?;
+                debug_classes.push(entry);
+            }
+            None => zip_writer.write_all(&entry.data)?,
+        }
+    }
 
-        let data = fs::read(input_file)?;
-        zip_writer.start_file(file_name, options)?;
-        zip_writer.write_all(&data)?;
+    write_services(&mut zip_writer, services, &contents.relocations, options)?;
+    zip_writer.finish()?;
+
+    if !debug_classes.is_empty() {
+        let debug_jar_path = debuginfo::debug_jar_path(output_jar_path);
+        let mut debug_writer = ZipWriter::new(fs::File::create(&debug_jar_path)?);
+        for entry in debug_classes {
+            debug_writer.start_file(entry.name.as_str(), options)?;
+            debug_writer.write_all(&entry.data)?;
+        }
+        debug_writer.finish()?;
+        println!("Debug JAR file created successfully: {}", debug_jar_path);
+    }
+    Ok(())
+}
+
+/// Reads the classes of an rlib, and the entries of the JARs it carries, into the entries of a JAR
+fn read_rlib_entries(
+    input_file: &str,
+    contents: &JarContents,
+    entries: &mut Entries,
+    services: &mut BTreeMap<String, Vec<String>>,
+) -> io::Result<()> {
+    let data = fs::read(input_file)?;
+    let members = rlib_members(&data)?;
+    // Each crate maps the names of its own classes
+    let mut class_map = HashMap::new();
+    for (member_name, member) in &members {
+        if has_extension(member_name, CLASS_MAP_EXTENSION) {
+            push_class_map_entries(&mut class_map, member);
+        }
+    }
+    for (member_name, member) in members {
+        let source = format!("{input_file}({member_name})");
+        if has_extension(&member_name, CLASS_MAP_EXTENSION) {
+            continue;
+        }
+        if has_extension(&member_name, "jar") {
+            let mut archive = ZipArchive::new(io::Cursor::new(member))?;
+            read_jar_entries(&mut archive, &source, contents, entries, services)?;
+            continue;
+        }
+        let file_name = class_entry_name(&class_map, &member_name);
+        if contents.filter.includes(&file_name) {
+            let (name, data) = contents.relocated_class(&file_name, member)?;
+            entries.insert(name, data, &source);
+        }
+    }
+    Ok(())
+}
+
+/// The directory `--dynamic-crates` writes the JARs of the crates to by default, next to the JAR
+const DYNAMIC_CRATES_DIRECTORY: &str = "lib";
+
+/// The name of the JAR of the crate of an rlib, after the rlib, as in `geometry-1a2b3c.jar` for
+/// `libgeometry-1a2b3c.rlib`. The hash Cargo gives the rlib keeps two versions of a crate apart,
+/// and only changes with the crate's version or features, so a crate rebuilt from changed sources
+/// replaces its JAR for every application using it
+fn crate_jar_name(rlib_path: &str) -> String {
+    let stem = Path::new(rlib_path).file_stem().unwrap_or_default().to_string_lossy();
+    format!("{}.jar", stem.strip_prefix("lib").unwrap_or(&stem))
+}
+
+/// Writes the JAR of the crate of an rlib, with its classes and services, for `--dynamic-crates`
+fn write_crate_jar(
+    input_file: &str,
+    jar_path: &Path,
+    compression: &Compression,
+    contents: &JarContents,
+) -> io::Result<()> {
+    let mut entries = Entries::new(contents.duplicates);
+    let mut services = BTreeMap::new();
+    read_rlib_entries(input_file, contents, &mut entries, &mut services)?;
+    entries.report()?;
+
+    if let Some(directory) = jar_path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut zip_writer = ZipWriter::new(fs::File::create(jar_path)?);
+    let options = compression.file_options();
+    let headers = ManifestHeaders {
+        automatic_module_name: None,
+        bundle_symbolic_name: None,
+        premain_class: None,
+    };
+    zip_writer.start_file("META-INF/MANIFEST.MF", options)?;
+    zip_writer.write_all(create_manifest_content(None, &headers, &[], &[]).as_bytes())?;
+    for entry in &entries.entries {
+        zip_writer.start_file(entry.name.as_str(), options)?;
+        zip_writer.write_all(&entry.data)?;
+    }
+    write_services(&mut zip_writer, services, &contents.relocations, options)?;
+    zip_writer.finish()?;
+    println!("Crate JAR file created successfully: {}", jar_path.display());
+    Ok(())
+}
+
+/// Writes the `META-INF/services` files of the providers of each service
+fn write_services<W: io::Write + io::Seek>(
+    zip_writer: &mut ZipWriter<W>,
+    services: BTreeMap<String, Vec<String>>,
+    relocations: &[Relocation],
+    options: SimpleFileOptions,
+) -> io::Result<()> {
+    for (name, providers) in services {
+        let service = name.trim_start_matches(SERVICES_DIRECTORY);
+        let service = relocate::relocate_binary_name(relocations, service);
+        zip_writer.start_file(format!("{SERVICES_DIRECTORY}{service}"), options)?;
+        for provider in providers {
+            let provider = relocate::relocate_binary_name(relocations, &provider);
+            writeln!(zip_writer, "{provider}")?;
+        }
     }
+    Ok(())
+}
 
+/// The shell script the JAR of a binary starts with, running the JAR it is in with `java -jar`,
+/// so that the JAR runs as a program, as `cargo run` runs it. The JVM, like other ZIP readers,
+/// finds the entries from the end of the file, past the script
+const LAUNCHER: &str = "#!/bin/sh\n\
+    exec \"${JAVA_HOME:+$JAVA_HOME/bin/}java\" $JAVA_OPTS -jar \"$0\" \"$@\"\n";
+
+/// Rewrites the JAR with the [`LAUNCHER`] first, copying its entries as they are, which keeps the
+/// signature of a signed JAR valid
+fn prepend_launcher(jar_path: &str) -> io::Result<()> {
+    let mut archive = ZipArchive::new(io::Cursor::new(fs::read(jar_path)?))?;
+    let mut output_file = fs::File::create(jar_path)?;
+    output_file.write_all(LAUNCHER.as_bytes())?;
+    let mut zip_writer = ZipWriter::new(output_file);
+    for index in 0..archive.len() {
+        zip_writer.raw_copy_file(archive.by_index_raw(index)?)?;
+    }
     zip_writer.finish()?;
     Ok(())
 }
 
-fn create_manifest_content(main_class_name: Option<&str>) -> String {
+/// Lets the JAR run as a program, as its owner, group and others can read it
+#[cfg(unix)]
+fn make_executable(jar_path: &str) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(jar_path)?.permissions();
+    permissions.set_mode(permissions.mode() | ((permissions.mode() & 0o444) >> 2));
+    fs::set_permissions(jar_path, permissions)
+}
+
+/// Files have no executable bit outside Unix, where the JAR runs with `java -jar`
+#[cfg(not(unix))]
+fn make_executable(_jar_path: &str) -> io::Result<()> {
+    Ok(())
+}
+
+/// How the entries of the JAR are compressed, from the linker's flags
+struct Compression {
+    /// `store` or `deflate`, the default
+    method: CompressionMethod,
+    /// The level of `deflate`, from 0 to 9, or its default
+    level: Option<i64>,
+    /// Whether the uncompressed entries start at offsets aligned on 4 bytes, as `zipalign` aligns
+    /// them for Android, which maps them from the APK instead of reading them
+    align: bool,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression { method: CompressionMethod::DEFLATE, level: None, align: false }
+    }
+}
+
+impl Compression {
+    fn file_options(&self) -> SimpleFileOptions {
+        let options = SimpleFileOptions::default()
+            .compression_method(self.method)
+            .compression_level(self.level)
+            .unix_permissions(0o644);
+        if self.align && self.method == CompressionMethod::Stored {
+            options.with_alignment(4)
+        } else {
+            options
+        }
+    }
+}
+
+/// Which input provides an entry several inputs provide with different contents, with
+/// `--duplicates=error|first|last`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DuplicatePolicy {
+    /// The duplicates are reported as errors, and no JAR is written
+    Error,
+    /// The first input in the order of the linker's inputs wins: the classes, then the JARs and
+    /// then the rlibs, with the resources last
+    First,
+    /// The last input wins
+    Last,
+}
+
+/// An entry of the JAR, with the input it comes from
+struct Entry {
+    name: String,
+    data: Vec<u8>,
+    source: String,
+}
+
+/// The entries of the JAR, in the order they were first provided
+struct Entries {
+    policy: DuplicatePolicy,
+    entries: Vec<Entry>,
+    indices: HashMap<String, usize>,
+    /// The names of the entries provided with different contents, with the inputs they came from
+    duplicates: Vec<(String, String, String)>,
+}
+
+impl Entries {
+    fn new(policy: DuplicatePolicy) -> Entries {
+        Entries { policy, entries: Vec::new(), indices: HashMap::new(), duplicates: Vec::new() }
+    }
+
+    /// Adds an entry, resolving it against an entry of the same name by the policy. Identical
+    /// copies, like the runtime classes each library carries, are not duplicates
+    fn insert(&mut self, name: String, data: Vec<u8>, source: &str) {
+        let Some(&index) = self.indices.get(&name) else {
+            self.indices.insert(name.clone(), self.entries.len());
+            self.entries.push(Entry { name, data, source: source.to_string() });
+            return;
+        };
+        let entry = &mut self.entries[index];
+        if entry.data == data {
+            return;
+        }
+        self.duplicates.push((name, entry.source.clone(), source.to_string()));
+        if self.policy == DuplicatePolicy::Last {
+            entry.data = data;
+            entry.source = source.to_string();
+        }
+    }
+
+    /// Reports the duplicates, failing with the `error` policy
+    fn report(&self) -> io::Result<()> {
+        for (name, first, other) in &self.duplicates {
+            match self.policy {
+                DuplicatePolicy::Error => {
+                    eprintln!("error: duplicate entry `{name}` in `{first}` and `{other}`")
+                }
+                DuplicatePolicy::First => eprintln!(
+                    "warning: duplicate entry `{name}` in `{first}` and `{other}`, keeping the \
+                     first one"
+                ),
+                DuplicatePolicy::Last => eprintln!(
+                    "warning: duplicate entry `{name}` in `{first}` and `{other}`, keeping the \
+                     last one"
+                ),
+            }
+        }
+        if self.policy == DuplicatePolicy::Error && !self.duplicates.is_empty() {
+            return Err(io::Error::other(format!(
+                "{} duplicate entries, `--duplicates=first` or `--duplicates=last` keeps one of \
+                 each",
+                self.duplicates.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The directory of the files listing the providers of services
+const SERVICES_DIRECTORY: &str = "META-INF/services/";
+
+/// Reads the entries of a JAR, like the backend's `rustlib-jvm` runtime, leaving out its
+/// manifest. The Maven metadata the backend generates with `maven-group` is kept, so the JAR can
+/// be deployed to a Maven repository as is, and the providers of its `META-INF/services` files
+/// are gathered, to be merged with those of the other inputs
+fn read_jar_entries<R: io::Read + io::Seek>(
+    archive: &mut ZipArchive<R>,
+    source: &str,
+    contents: &JarContents,
+    entries: &mut Entries,
+    services: &mut BTreeMap<String, Vec<String>>,
+) -> io::Result<()> {
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || !contents.filter.includes(&name) {
+            continue;
+        }
+        let is_metadata = name.starts_with("META-INF/")
+            && !name.starts_with("META-INF/maven/")
+            && !name.starts_with(SERVICES_DIRECTORY);
+        if is_metadata {
+            continue;
+        }
+        let mut data = Vec::new();
+        io::Read::read_to_end(&mut entry, &mut data)?;
+        if name.starts_with(SERVICES_DIRECTORY) {
+            push_service_providers(services, &name, &data);
+        } else if has_extension(&name, "class") {
+            let (name, data) = contents.relocated_class(&name, &data)?;
+            entries.insert(name, data, source);
+        } else {
+            entries.insert(name, data, source);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the providers a services file lists to those of its service, once each and leaving out
+/// its comments
+fn push_service_providers(services: &mut BTreeMap<String, Vec<String>>, name: &str, data: &[u8]) {
+    let providers = services.entry(name.to_string()).or_default();
+    for line in String::from_utf8_lossy(data).lines() {
+        let provider = line.split('#').next().unwrap_or_default().trim();
+        if !provider.is_empty() && !providers.iter().any(|known| known == provider) {
+            providers.push(provider.to_string());
+        }
+    }
+}
+
+/// The module name of a JAR without one, after the name of its crate or its file name, as the JDK
+/// names automatic modules: `my_app` and `my-app.jar` are `my.app`
+fn default_module_name(stem: &str) -> String {
+    // Leaving out a version, as the `-1.0` of `my-app-1.0.jar`
+    let stem = stem
+        .match_indices('-')
+        .find(|(index, _)| stem[index + 1..].starts_with(|c: char| c.is_ascii_digit()))
+        .map_or(stem, |(index, _)| &stem[..index]);
+    stem.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            // A part of a module name cannot start with a digit
+            if part.starts_with(|c: char| c.is_ascii_digit()) {
+                format!("_{part}")
+            } else {
+                part.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn has_extension(file: &str, extension: &str) -> bool {
+    Path::new(file).extension().is_some_and(|file_extension| file_extension == extension)
+}
+
+/// The `.class`, `.jar` and class map members of an rlib, a GNU `ar` archive, with their names
+fn rlib_members(data: &[u8]) -> io::Result<Vec<(String, &[u8])>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an rlib archive");
+    let mut rest = data.strip_prefix(b"!<arch>\n").ok_or_else(invalid)?;
+    // The names longer than 15 bytes, each member referring to its own by offset as `/<offset>`
+    let mut long_names: &[u8] = &[];
+    let mut classes = Vec::new();
+    while rest.len() >= 60 {
+        let (header, after) = rest.split_at(60);
+        let size: usize = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|size| size.trim().parse().ok())
+            .ok_or_else(invalid)?;
+        let content = after.get(..size).ok_or_else(invalid)?;
+        let name = std::str::from_utf8(&header[..16]).map_err(|_| invalid())?.trim_end();
+        if name == "//" {
+            long_names = content;
+        } else {
+            let name = match name.strip_prefix('/').and_then(|offset| offset.parse::<usize>().ok()) {
+                Some(offset) => {
+                    let long_name = long_names.get(offset..).ok_or_else(invalid)?;
+                    let end = long_name
+                        .iter()
+                        .position(|byte| *byte == b'\n')
+                        .unwrap_or(long_name.len());
+                    String::from_utf8_lossy(&long_name[..end]).into_owned()
+                }
+                None => name.to_string(),
+            };
+            let name = name.trim_end_matches('/');
+            let is_input = ["class", "jar", CLASS_MAP_EXTENSION]
+                .iter()
+                .any(|extension| has_extension(name, extension));
+            if is_input {
+                classes.push((name.to_string(), content));
+            }
+        }
+        // Members start at even offsets
+        rest = after.get(size + size % 2..).unwrap_or_default();
+    }
+    Ok(classes)
+}
+
+/// The extension of the class maps the backend writes next to the classes of each crate
+const CLASS_MAP_EXTENSION: &str = "classmap";
+
+/// The extension of the list of the rlibs of the crates an executable links, which the backend
+/// writes next to its classes
+const CRATES_EXTENSION: &str = "crates";
+
+/// The rlibs of the crate lists given to the linker that aren't given to it themselves, in the
+/// order of the lists
+fn listed_rlibs(input_files: &[String]) -> io::Result<Vec<String>> {
+    let mut linked: HashSet<PathBuf> = input_files
+        .iter()
+        .filter(|file| has_extension(file, "rlib"))
+        .map(|file| fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file)))
+        .collect();
+    let mut rlibs = Vec::new();
+    for input_file in input_files.iter().filter(|file| has_extension(file, CRATES_EXTENSION)) {
+        for rlib in fs::read_to_string(input_file)?.lines().filter(|line| !line.is_empty()) {
+            let path = fs::canonicalize(rlib).map_err(|err| {
+                io::Error::new(err.kind(), format!("{rlib}, listed in {input_file}: {err}"))
+            })?;
+            if linked.insert(path) {
+                rlibs.push(rlib.to_string());
+            }
+        }
+    }
+    Ok(rlibs)
+}
+
+/// The class map of the class files given to the linker, from the class maps given with them
+fn loose_class_map(input_files: &[String]) -> io::Result<HashMap<String, String>> {
+    let mut class_map = HashMap::new();
+    for input_file in input_files.iter().filter(|file| has_extension(file, CLASS_MAP_EXTENSION)) {
+        push_class_map_entries(&mut class_map, &fs::read(input_file)?);
+    }
+    Ok(class_map)
+}
+
+/// The name of the crate being linked, which the class maps given to the linker name on their
+/// `# <crate>` line
+fn loose_crate_name(input_files: &[String]) -> io::Result<Option<String>> {
+    for input_file in input_files.iter().filter(|file| has_extension(file, CLASS_MAP_EXTENSION)) {
+        let class_map = fs::read_to_string(input_file)?;
+        let crate_name = class_map.lines().find_map(|line| line.strip_prefix("# "));
+        if let Some(crate_name) = crate_name {
+            return Ok(Some(crate_name.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Adds the entries of a class map, which lists the file name rustc gave each class of a crate
+/// (like `my-crate-1a2b3c.my_crate.Point.rcgu.class`) and the name of its entry in the JAR (like
+/// `my_crate/Point.class`), separated by a tab, one class per line
+fn push_class_map_entries(class_map: &mut HashMap<String, String>, data: &[u8]) {
+    for line in String::from_utf8_lossy(data).lines() {
+        if let Some((file_name, entry_name)) = line.split_once('\t') {
+            class_map.insert(file_name.to_string(), entry_name.to_string());
+        }
+    }
+}
+
+/// The name of the entry of a class file in the JAR: the one its class map gives, or its file
+/// name, as for the class files given to the linker by hand
+fn class_entry_name(class_map: &HashMap<String, String>, file_name: &str) -> String {
+    class_map.get(file_name).cloned().unwrap_or_else(|| file_name.to_string())
+}
+
+fn create_manifest_content(
+    main_class_name: Option<&str>,
+    headers: &ManifestHeaders,
+    class_names: &[String],
+    class_path: &[String],
+) -> String {
     let mut manifest = String::new();
     manifest.push_str("Manifest-Version: 1.0\r\n");
     manifest.push_str("Created-By: java-linker-rs\r\n");
 
+    // The backend's `multi-release` option names the classes for newer Java versions after their
+    // directory
+    if class_names.iter().any(|name| name.starts_with("META-INF/versions/")) {
+        push_manifest_header(&mut manifest, "Multi-Release", "true");
+    }
+
     if let Some(main_class) = main_class_name {
-        manifest.push_str(&format!("Main-Class: {}\r\n", main_class));
+        push_manifest_header(&mut manifest, "Main-Class", main_class);
+    }
+    // The JARs of the crates linked with `--dynamic-crates`, which the JVM loads with the JAR
+    if !class_path.is_empty() {
+        push_manifest_header(&mut manifest, "Class-Path", &class_path.join(" "));
+    }
+    if let Some(premain_class) = &headers.premain_class {
+        push_manifest_header(&mut manifest, "Premain-Class", premain_class);
+        push_manifest_header(&mut manifest, "Can-Retransform-Classes", "true");
+    }
+    if let Some(module_name) = &headers.automatic_module_name {
+        push_manifest_header(&mut manifest, "Automatic-Module-Name", module_name);
+    }
+    if let Some(symbolic_name) = &headers.bundle_symbolic_name {
+        push_manifest_header(&mut manifest, "Bundle-ManifestVersion", "2");
+        push_manifest_header(&mut manifest, "Bundle-SymbolicName", symbolic_name);
+        // The bundle version is the package's, when Cargo runs the build and it is a plain
+        // `major.minor.micro` that OSGi reads
+        if let Ok(version) = env::var("CARGO_PKG_VERSION")
+            && version
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        {
+            push_manifest_header(&mut manifest, "Bundle-Version", &version);
+        }
+        // Every package but the default one, which cannot be exported
+        let packages: BTreeSet<String> = class_names
+            .iter()
+            .filter(|name| !name.starts_with("META-INF/"))
+            .filter_map(|name| name.rsplit_once('/'))
+            .map(|(package, _)| package.replace('/', "."))
+            .collect();
+        if !packages.is_empty() {
+            let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+            push_manifest_header(&mut manifest, "Export-Package", &packages.join(","));
+        }
     }
     manifest.push_str("\r\n");
     manifest
 }
+
+/// Appends a manifest header, continuing it on lines starting with a space past the 72 bytes a
+/// manifest line can have
+fn push_manifest_header(manifest: &mut String, name: &str, value: &str) {
+    let header = format!("{name}: {value}");
+    let mut line_start = 0;
+    let mut line_length = 0;
+    for (index, c) in header.char_indices() {
+        let limit = if line_start == 0 { 72 } else { 71 };
+        if line_length + c.len_utf8() > limit {
+            if line_start > 0 {
+                manifest.push(' ');
+            }
+            manifest.push_str(&header[line_start..index]);
+            manifest.push_str("\r\n");
+            line_start = index;
+            line_length = 0;
+        }
+        line_length += c.len_utf8();
+    }
+    if line_start > 0 {
+        manifest.push(' ');
+    }
+    manifest.push_str(&header[line_start..]);
+    manifest.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    /// A directory of its own for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("java-linker-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn splits_response_files_as_gcc() {
+        assert_eq!(response_file_args("a  b\n\tc\n"), strings(&["a", "b", "c"]));
+        assert_eq!(response_file_args(r"dir\ with\ spaces/a.class"), strings(&["dir with spaces/a.class"]));
+        assert_eq!(response_file_args(r"C:\\out\\app.jar"), strings(&[r"C:\out\app.jar"]));
+        assert_eq!(response_file_args(r#""a b" 'c "d"' e"f"g"#), strings(&["a b", r#"c "d""#, "efg"]));
+        assert_eq!(response_file_args(r#"'' "" x"#), strings(&["", "", "x"]));
+        assert_eq!(response_file_args("  "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn expands_nested_response_files() {
+        let dir = test_dir("nested");
+        let inner = dir.join("inner.rsp");
+        let outer = dir.join("outer.rsp");
+        fs::write(&inner, "b c").unwrap();
+        fs::write(&outer, format!("a @{} @{}", inner.display(), inner.display())).unwrap();
+        let args = expand_response_files(strings(&["linker", &format!("@{}", outer.display()), "@"]));
+        assert_eq!(args.unwrap(), strings(&["linker", "a", "b", "c", "b", "c", "@"]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_response_file_cycles() {
+        let dir = test_dir("cycle");
+        let first = dir.join("first.rsp");
+        let second = dir.join("second.rsp");
+        fs::write(&first, format!("a @{}", second.display())).unwrap();
+        fs::write(&second, format!("b @{}", first.display())).unwrap();
+        let err = expand_response_files(strings(&[&format!("@{}", first.display())])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn matches_globs() {
+        let classes = glob_regex("com/example/**/*.class");
+        assert!(classes.is_match("com/example/App.class"));
+        assert!(classes.is_match("com/example/impl/deep/Util.class"));
+        assert!(!classes.is_match("com/example/App.java"));
+        assert!(!classes.is_match("org/com/example/App.class"));
+        let single = glob_regex("META-INF/*.SF");
+        assert!(single.is_match("META-INF/APP.SF"));
+        assert!(!single.is_match("META-INF/sub/APP.SF"));
+        let character = glob_regex("a?c.txt");
+        assert!(character.is_match("abc.txt"));
+        assert!(!character.is_match("a/c.txt"));
+        assert!(!character.is_match("aXcXtxt"));
+        assert!(glob_regex("**").is_match("any/entry"));
+    }
+
+    #[test]
+    fn filters_entries() {
+        let filter = EntryFilter {
+            includes: vec![glob_regex("com/**")],
+            excludes: vec![glob_regex("**/*Test.class")],
+        };
+        assert!(filter.includes("com/example/App.class"));
+        assert!(!filter.includes("com/example/AppTest.class"));
+        assert!(!filter.includes("org/example/App.class"));
+        let excludes_only = EntryFilter { includes: Vec::new(), excludes: vec![glob_regex("*.txt")] };
+        assert!(excludes_only.includes("org/example/App.class"));
+        assert!(!excludes_only.includes("notes.txt"));
+    }
+
+    #[test]
+    fn resolves_duplicate_entries() {
+        for (policy, kept) in [(DuplicatePolicy::First, "a"), (DuplicatePolicy::Last, "b")] {
+            let mut entries = Entries::new(policy);
+            entries.insert("App.class".to_string(), b"a".to_vec(), "a.jar");
+            entries.insert("App.class".to_string(), b"b".to_vec(), "b.jar");
+            entries.insert("Other.class".to_string(), b"c".to_vec(), "a.jar");
+            assert_eq!(entries.entries.len(), 2);
+            assert_eq!(entries.entries[0].data, kept.as_bytes());
+            assert_eq!(entries.entries[0].source, format!("{kept}.jar"));
+            assert!(entries.report().is_ok());
+        }
+        let mut entries = Entries::new(DuplicatePolicy::Error);
+        entries.insert("App.class".to_string(), b"a".to_vec(), "a.jar");
+        entries.insert("App.class".to_string(), b"a".to_vec(), "b.jar");
+        assert!(entries.duplicates.is_empty());
+        assert!(entries.report().is_ok());
+        entries.insert("App.class".to_string(), b"b".to_vec(), "c.jar");
+        assert_eq!(
+            entries.duplicates,
+            [("App.class".to_string(), "a.jar".to_string(), "c.jar".to_string())]
+        );
+        assert!(entries.report().is_err());
+    }
+
+    #[test]
+    fn names_automatic_modules() {
+        assert_eq!(default_module_name("my_app"), "my.app");
+        assert_eq!(default_module_name("my-app-1.0"), "my.app");
+        assert_eq!(default_module_name("app_2d"), "app._2d");
+        assert_eq!(default_module_name("app-2d"), "app");
+        assert_eq!(default_module_name("app"), "app");
+    }
+
+    /// A member of an `ar` archive, with its header
+    fn ar_member(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut member = format!("{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", 0, 0, 0, 644, content.len())
+            .into_bytes();
+        member.extend_from_slice(content);
+        if content.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    #[test]
+    fn reads_rlib_members() {
+        let long_name = "a_very_long_class_name.class";
+        let mut data = b"!<arch>\n".to_vec();
+        data.extend(ar_member("//", format!("{long_name}/\n").as_bytes()));
+        data.extend(ar_member("lib.rmeta/", b"metadata"));
+        data.extend(ar_member("App.class/", b"odd"));
+        data.extend(ar_member("/0", b"long"));
+        data.extend(ar_member("app.classmap/", b"map"));
+        data.extend(ar_member("runtime.jar/", b"jar"));
+        let members = rlib_members(&data).unwrap();
+        let expected: [(&str, &[u8]); 4] = [
+            ("App.class", b"odd"),
+            (long_name, b"long"),
+            ("app.classmap", b"map"),
+            ("runtime.jar", b"jar"),
+        ];
+        assert_eq!(
+            members,
+            expected.map(|(name, content)| (name.to_string(), content)).to_vec()
+        );
+        assert!(rlib_members(b"not an archive").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+//! Relocation of the packages of bundled classes, with `--relocate <package>:<package>`.
+
+use crate::classfile::{ConstantPool, Reader, invalid};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+
+/// A package moved under another, in the internal form of class names, as `com/fasterxml`
+pub struct Relocation {
+    from: String,
+    to: String,
+}
+
+impl Relocation {
+    /// Parses `<package>:<package>`, as in `com.fasterxml:shaded.com.fasterxml`
+    pub fn parse(value: &str) -> Option<Relocation> {
+        let (from, to) = value.split_once(':')?;
+        if from.is_empty() || to.is_empty() {
+            return None;
+        }
+        Some(Relocation { from: from.replace('.', "/"), to: to.replace('.', "/") })
+    }
+}
+
+/// The name of a class or its entry in the JAR, as `com/fasterxml/Json.class`, once relocated
+pub fn relocate_name(relocations: &[Relocation], name: &str) -> String {
+    for relocation in relocations {
+        if let Some(rest) = name.strip_prefix(relocation.from.as_str())
+            && rest.starts_with('/')
+        {
+            return format!("{}{rest}", relocation.to);
+        }
+    }
+    name.to_string()
+}
+
+/// The binary name of a class, as `com.fasterxml.Json`, once relocated
+pub fn relocate_binary_name(relocations: &[Relocation], name: &str) -> String {
+    relocate_name(relocations, &name.replace('.', "/")).replace('/', ".")
+}
+
+/// The value of a `CONSTANT_Utf8` entry, once relocated: a class name starting with a relocated
+/// package, or a descriptor or signature referring to one as `Lcom/fasterxml/Json;`
+fn relocate_utf8(relocations: &[Relocation], value: &[u8]) -> Vec<u8> {
+    let mut value = value.to_vec();
+    for relocation in relocations {
+        let from = format!("{}/", relocation.from);
+        let to = format!("{}/", relocation.to);
+        if let Some(rest) = value.strip_prefix(from.as_bytes()) {
+            value = [to.as_bytes(), rest].concat();
+        }
+        value = replace_bytes(&value, format!("L{from}").as_bytes(), format!("L{to}").as_bytes());
+    }
+    value
+}
+
+fn replace_bytes(data: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut index = 0;
+    while index < data.len() {
+        if data[index..].starts_with(from) {
+            result.extend_from_slice(to);
+            index += from.len();
+        } else {
+            result.push(data[index]);
+            index += 1;
+        }
+    }
+    result
+}
+
+/// The `CONSTANT_Utf8` entries of a class that hold class names, descriptors or signatures, and
+/// those that hold the value of string constants
+#[derive(Default)]
+struct Utf8Uses {
+    names: HashSet<u16>,
+    strings: HashSet<u16>,
+}
+
+impl Utf8Uses {
+    fn name(&mut self, index: Option<u16>) -> io::Result<()> {
+        self.names.insert(index.ok_or_else(invalid)?);
+        Ok(())
+    }
+}
+
+/// The bytes of a class file with its class names, descriptors and signatures relocated. They
+/// are all `CONSTANT_Utf8` entries of the constant pool, which the rest of the class refers to by
+/// index, so it is kept as it is. A string constant sharing its entry with a relocated name gets
+/// an entry of its own, added at the end of the constant pool, that keeps its value
+pub fn relocate_class(relocations: &[Relocation], data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = Reader::new(data);
+    let constant_pool = ConstantPool::read(&mut reader)?;
+    let constant_pool_end = reader.offset;
+    let mut uses = Utf8Uses::default();
+    for (_, constant) in constant_pool.iter() {
+        match constant.tag {
+            // `CONSTANT_Class` and `CONSTANT_MethodType`
+            7 | 16 => uses.name(constant.index(0))?,
+            // `CONSTANT_String`
+            8 => {
+                uses.strings.insert(constant.index(0).ok_or_else(invalid)?);
+            }
+            // `CONSTANT_NameAndType`, by its descriptor
+            12 => uses.name(constant.index(2))?,
+            _ => {}
+        }
+    }
+    // The access flags, this class and the super class, which are `CONSTANT_Class` entries
+    reader.bytes(6)?;
+    let interfaces_count = reader.u16()?;
+    reader.bytes(2 * usize::from(interfaces_count))?;
+    // The fields, then the methods
+    for _ in 0..2 {
+        let count = reader.u16()?;
+        for _ in 0..count {
+            // The access flags and name, then the descriptor
+            reader.bytes(4)?;
+            uses.name(Some(reader.u16()?))?;
+            attribute_uses(&mut reader, &constant_pool, &mut uses)?;
+        }
+    }
+    attribute_uses(&mut reader, &constant_pool, &mut uses)?;
+
+    let mut relocated = HashMap::new();
+    for &index in &uses.names {
+        let value = constant_pool.utf8(index).ok_or_else(invalid)?;
+        let relocated_value = relocate_utf8(relocations, value);
+        if relocated_value != value {
+            relocated.insert(index, relocated_value);
+        }
+    }
+    // The strings that keep the value of a relocated entry, by the index of that entry
+    let mut strings = BTreeMap::new();
+    for &index in relocated.keys().filter(|index| uses.strings.contains(index)) {
+        strings.insert(index, constant_pool.utf8(index).ok_or_else(invalid)?);
+    }
+    let string_indices: HashMap<u16, u16> = strings
+        .keys()
+        .enumerate()
+        .map(|(position, &index)| {
+            let string_index = u16::try_from(constant_pool.count() + position);
+            Ok((index, string_index.map_err(|_| invalid())?))
+        })
+        .collect::<io::Result<_>>()?;
+    let constant_pool_count =
+        u16::try_from(constant_pool.count() + strings.len()).map_err(|_| invalid())?;
+
+    // The magic number and the version
+    let mut result = data[..8].to_vec();
+    result.extend_from_slice(&constant_pool_count.to_be_bytes());
+    for (index, constant) in constant_pool.iter() {
+        let string_index = constant.index(0).and_then(|index| string_indices.get(&index));
+        if let Some(value) = relocated.get(&index) {
+            push_utf8(&mut result, value)?;
+        } else if constant.tag == 8
+            && let Some(string_index) = string_index
+        {
+            result.push(constant.tag);
+            result.extend_from_slice(&string_index.to_be_bytes());
+        } else {
+            result.push(constant.tag);
+            result.extend_from_slice(constant.info);
+        }
+    }
+    for value in strings.values() {
+        push_utf8(&mut result, value)?;
+    }
+    result.extend_from_slice(&data[constant_pool_end..]);
+    Ok(result)
+}
+
+fn push_utf8(result: &mut Vec<u8>, value: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(value.len()).map_err(|_| invalid())?;
+    result.push(1);
+    result.extend_from_slice(&length.to_be_bytes());
+    result.extend_from_slice(value);
+    Ok(())
+}
+
+/// Collects the descriptors and signatures held by a table of attributes: those of the
+/// `Signature` attributes, of the local variables and record components, and of the types and
+/// values of annotations. Type annotations are not looked into
+fn attribute_uses(
+    reader: &mut Reader<'_>,
+    constant_pool: &ConstantPool<'_>,
+    uses: &mut Utf8Uses,
+) -> io::Result<()> {
+    let count = reader.u16()?;
+    for _ in 0..count {
+        let name = constant_pool.utf8(reader.u16()?).ok_or_else(invalid)?;
+        let mut info = Reader::new(reader.attribute_info()?);
+        match name {
+            b"Signature" => uses.name(Some(info.u16()?))?,
+            b"Code" => {
+                // The maximum stack and locals
+                info.bytes(4)?;
+                let code_length = info.u32()?;
+                info.bytes(usize::try_from(code_length).map_err(|_| invalid())?)?;
+                let exception_table_length = info.u16()?;
+                info.bytes(8 * usize::from(exception_table_length))?;
+                attribute_uses(&mut info, constant_pool, uses)?;
+            }
+            b"LocalVariableTable" | b"LocalVariableTypeTable" => {
+                let count = info.u16()?;
+                for _ in 0..count {
+                    // The start, length and name, then the descriptor or signature, and the slot
+                    info.bytes(6)?;
+                    uses.name(Some(info.u16()?))?;
+                    info.bytes(2)?;
+                }
+            }
+            b"Record" => {
+                let count = info.u16()?;
+                for _ in 0..count {
+                    // The name, then the descriptor
+                    info.bytes(2)?;
+                    uses.name(Some(info.u16()?))?;
+                    attribute_uses(&mut info, constant_pool, uses)?;
+                }
+            }
+            b"RuntimeVisibleAnnotations" | b"RuntimeInvisibleAnnotations" => {
+                annotations_uses(&mut info, uses)?;
+            }
+            b"RuntimeVisibleParameterAnnotations" | b"RuntimeInvisibleParameterAnnotations" => {
+                let count = info.u8()?;
+                for _ in 0..count {
+                    annotations_uses(&mut info, uses)?;
+                }
+            }
+            b"AnnotationDefault" => element_value_uses(&mut info, uses)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn annotations_uses(reader: &mut Reader<'_>, uses: &mut Utf8Uses) -> io::Result<()> {
+    let count = reader.u16()?;
+    for _ in 0..count {
+        annotation_uses(reader, uses)?;
+    }
+    Ok(())
+}
+
+/// Collects the type of an annotation and those of its values
+fn annotation_uses(reader: &mut Reader<'_>, uses: &mut Utf8Uses) -> io::Result<()> {
+    uses.name(Some(reader.u16()?))?;
+    let count = reader.u16()?;
+    for _ in 0..count {
+        // The name of the element
+        reader.bytes(2)?;
+        element_value_uses(reader, uses)?;
+    }
+    Ok(())
+}
+
+fn element_value_uses(reader: &mut Reader<'_>, uses: &mut Utf8Uses) -> io::Result<()> {
+    match reader.u8()? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+            reader.bytes(2)?;
+        }
+        // An enum constant, by the descriptor of its type and its name
+        b'e' => {
+            uses.name(Some(reader.u16()?))?;
+            reader.bytes(2)?;
+        }
+        // A class literal, by its return descriptor
+        b'c' => uses.name(Some(reader.u16()?))?,
+        b'@' => annotation_uses(reader, uses)?,
+        b'[' => {
+            let count = reader.u16()?;
+            for _ in 0..count {
+                element_value_uses(reader, uses)?;
+            }
+        }
+        _ => return Err(invalid()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relocations() -> Vec<Relocation> {
+        vec![Relocation::parse("com.fasterxml:shaded.com.fasterxml").unwrap()]
+    }
+
+    fn utf8(value: &str) -> Vec<u8> {
+        let mut entry = vec![1];
+        entry.extend_from_slice(&u16::try_from(value.len()).unwrap().to_be_bytes());
+        entry.extend_from_slice(value.as_bytes());
+        entry
+    }
+
+    /// A class `com/fasterxml/Json` with a field of its own type, the string constant of its name
+    /// and one of another name in the package
+    fn class_file() -> Vec<u8> {
+        let mut data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 10];
+        data.extend(utf8("com/fasterxml/Json"));
+        data.extend([7, 0, 1]);
+        data.extend(utf8("java/lang/Object"));
+        data.extend([7, 0, 3]);
+        data.extend([8, 0, 1]);
+        data.extend(utf8("value"));
+        data.extend(utf8("Lcom/fasterxml/Json;"));
+        data.extend(utf8("com/fasterxml/Other"));
+        data.extend([8, 0, 8]);
+        // Public, this class, the super class and no interfaces
+        data.extend([0, 0x21, 0, 2, 0, 4, 0, 0]);
+        // The field, no methods and no attributes
+        data.extend([0, 1, 0, 0, 0, 6, 0, 7, 0, 0]);
+        data.extend([0, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn parses_relocations() {
+        assert!(Relocation::parse("com.fasterxml").is_none());
+        assert!(Relocation::parse(":shaded").is_none());
+        assert!(Relocation::parse("com.fasterxml:").is_none());
+    }
+
+    #[test]
+    fn relocates_names_in_the_package() {
+        let relocations = relocations();
+        assert_eq!(
+            relocate_name(&relocations, "com/fasterxml/Json.class"),
+            "shaded/com/fasterxml/Json.class"
+        );
+        assert_eq!(relocate_name(&relocations, "com/fasterxmlx/Json"), "com/fasterxmlx/Json");
+        assert_eq!(relocate_name(&relocations, "org/com/fasterxml/Json"), "org/com/fasterxml/Json");
+        assert_eq!(
+            relocate_binary_name(&relocations, "com.fasterxml.Json"),
+            "shaded.com.fasterxml.Json"
+        );
+    }
+
+    #[test]
+    fn relocates_class_names_and_descriptors() {
+        let relocations = relocations();
+        assert_eq!(
+            relocate_utf8(&relocations, b"com/fasterxml/Json"),
+            b"shaded/com/fasterxml/Json"
+        );
+        assert_eq!(
+            relocate_utf8(&relocations, b"(Lcom/fasterxml/Json;I)Lcom/fasterxml/Other;"),
+            b"(Lshaded/com/fasterxml/Json;I)Lshaded/com/fasterxml/Other;"
+        );
+        assert_eq!(
+            relocate_utf8(&relocations, b"Ljava/util/List<Lcom/fasterxml/Json;>;"),
+            b"Ljava/util/List<Lshaded/com/fasterxml/Json;>;"
+        );
+        assert_eq!(relocate_utf8(&relocations, b"Lorg/Json;"), b"Lorg/Json;");
+    }
+
+    #[test]
+    fn relocates_class_files() {
+        let relocated = relocate_class(&relocations(), &class_file()).unwrap();
+        let mut reader = Reader::new(&relocated);
+        let constant_pool = ConstantPool::read(&mut reader).unwrap();
+        assert_eq!(constant_pool.count(), 11);
+        assert_eq!(constant_pool.utf8(1), Some(&b"shaded/com/fasterxml/Json"[..]));
+        assert_eq!(constant_pool.utf8(7), Some(&b"Lshaded/com/fasterxml/Json;"[..]));
+        // The string constant of the class's name keeps its value in an entry of its own
+        assert_eq!(constant_pool.get(5).and_then(|string| string.index(0)), Some(10));
+        assert_eq!(constant_pool.utf8(10), Some(&b"com/fasterxml/Json"[..]));
+        assert_eq!(constant_pool.utf8(8), Some(&b"com/fasterxml/Other"[..]));
+        let class_file = class_file();
+        let mut class_file_reader = Reader::new(&class_file);
+        ConstantPool::read(&mut class_file_reader).unwrap();
+        assert_eq!(&relocated[reader.offset..], &class_file[class_file_reader.offset..]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(relocate_class(&relocations(), b"PK\x03\x04").is_err());
+    }
+}
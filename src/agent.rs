@@ -0,0 +1,95 @@
+//! Java agents, whose `premain` is a function marked `#[jvm::premain]`.
+
+use crate::constant_pool::ConstantPool;
+use crate::{MethodCode, attributes, create_method, method_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{Field, FieldAccessFlags, FieldType, Method, MethodAccessFlags};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+
+/// The static field of the crate class holding the agent's `Instrumentation`
+const INSTRUMENTATION_FIELD: &str = "instrumentation";
+const INSTRUMENTATION_DESCRIPTOR: &str = "Ljava/lang/instrument/Instrumentation;";
+
+/// The descriptors of the functions that can be an agent's `premain`
+const PREMAIN_DESCRIPTORS: [&str; 2] = ["(Ljava/lang/String;)V", "()V"];
+
+/// Whether the function is the agent's `premain`
+pub(crate) fn is_premain(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    attributes::has_jvm_attr(tcx, def_id, "premain")
+}
+
+/// Reports the `#[jvm::premain]` functions that cannot be an agent's `premain`: those not taking
+/// a `&str` or nothing and returning nothing, and any after the first
+pub(crate) fn check_premain_functions(tcx: TyCtxt<'_>, functions: &[DefId]) {
+    let mut premain = None;
+    for def_id in functions.iter().filter(|def_id| is_premain(tcx, **def_id)) {
+        if !PREMAIN_DESCRIPTORS.contains(&method_descriptor(tcx, *def_id, false).as_str()) {
+            tcx.dcx().span_err(
+                tcx.def_span(*def_id),
+                "`#[jvm::premain]` functions take the agent's options as a `&str`, or nothing, \
+                 and return nothing",
+            );
+        }
+        match premain {
+            None => premain = Some(*def_id),
+            Some(first) => {
+                tcx.dcx()
+                    .struct_span_err(
+                        tcx.def_span(*def_id),
+                        "a crate has a single `#[jvm::premain]` function",
+                    )
+                    .with_span_note(tcx.def_span(first), "the first one is here")
+                    .emit();
+            }
+        }
+    }
+}
+
+/// The `public static Instrumentation instrumentation` field of the crate class
+pub(crate) fn instrumentation_field(
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<Field> {
+    Ok(Field {
+        access_flags: FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC,
+        name_index: constant_pool.add_utf8(INSTRUMENTATION_FIELD)?,
+        descriptor_index: constant_pool.add_utf8(INSTRUMENTATION_DESCRIPTOR)?,
+        field_type: FieldType::parse(INSTRUMENTATION_DESCRIPTOR)?,
+        attributes: Vec::new(),
+    })
+}
+
+/// The synthetic `public static void premain(String options, Instrumentation instrumentation)`
+/// calling the lowered `#[jvm::premain]` function
+pub(crate) fn premain_wrapper(
+    constant_pool: &mut ConstantPool,
+    crate_name: &str,
+    premain_name: &str,
+    premain_descriptor: &str,
+) -> ristretto_classfile::Result<Method> {
+    let this_class = constant_pool.add_class(crate_name)?;
+    let instrumentation = constant_pool.add_field_ref(
+        this_class,
+        INSTRUMENTATION_FIELD,
+        INSTRUMENTATION_DESCRIPTOR,
+    )?;
+    let premain = constant_pool.add_method_ref(this_class, premain_name, premain_descriptor)?;
+
+    // instrumentation = instrumentation; premain(options);
+    let mut instructions = vec![
+        Instruction::Aload_1,
+        Instruction::Putstatic(instrumentation),
+    ];
+    if premain_descriptor.starts_with("(L") {
+        instructions.push(Instruction::Aload_0);
+    }
+    instructions.extend([Instruction::Invokestatic(premain), Instruction::Return]);
+
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        "premain",
+        &format!("(Ljava/lang/String;{INSTRUMENTATION_DESCRIPTOR})V"),
+        &MethodCode::from(instructions),
+    )
+}
@@ -0,0 +1,194 @@
+//! `alloc` without an allocator. The JVM allocates the objects `Box`, `Vec` and `String` are
+//! lowered to, so a `#[global_allocator]` is not used.
+
+use crate::constant_pool::ConstantPool;
+use crate::trace::trace;
+use crate::{MethodCode, MirToBytecodeVisitor, create_method, ldc, naming, options};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{ClassAccessFlags, ClassFile, MethodAccessFlags};
+use rustc_ast::expand::allocator::{ALLOCATOR_METHODS, AllocatorTy, global_fn_name};
+use rustc_codegen_ssa::base::allocator_kind_for_codegen;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::hygiene::{ExpnKind, MacroKind};
+use rustc_span::{Span, sym};
+
+const OUT_OF_MEMORY_ERROR: &str = "java/lang/OutOfMemoryError";
+const OUT_OF_MEMORY_MESSAGE: &str = "memory allocation failed";
+
+/// The call site of the `#[global_allocator]` whose expansion generated a function, if it did
+pub(crate) fn global_allocator_of(tcx: TyCtxt<'_>, def_id: DefId) -> Option<Span> {
+    let expn_data = tcx.def_span(def_id).ctxt().outer_expn_data();
+    match expn_data.kind {
+        ExpnKind::Macro(MacroKind::Attr, name) if name == sym::global_allocator => {
+            Some(expn_data.call_site)
+        }
+        _ => None,
+    }
+}
+
+/// Warns that a `#[global_allocator]` is not used on the JVM
+pub(crate) fn warn_unused_global_allocator(tcx: TyCtxt<'_>, span: Span) {
+    tcx.dcx()
+        .struct_span_warn(span, "the `#[global_allocator]` is not used on the JVM")
+        .with_note(
+            "the JVM allocates every object and array itself and its garbage collector frees \
+             them, so the allocator is never called",
+        )
+        .emit();
+}
+
+/// Whether a function is a method of an impl of `GlobalAlloc`, which is not lowered
+pub(crate) fn is_global_alloc_method(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    tcx.impl_of_method(def_id)
+        .and_then(|impl_def_id| tcx.trait_id_of_impl(impl_def_id))
+        .is_some_and(|trait_def_id| {
+            tcx.crate_name(trait_def_id.krate) == sym::core
+                && tcx.item_name(trait_def_id).as_str() == "GlobalAlloc"
+        })
+}
+
+/// The instructions throwing a new exception of the given class with a message
+fn throw(
+    constant_pool: &mut ConstantPool,
+    class_name: &str,
+    message: &str,
+) -> ristretto_classfile::Result<Vec<Instruction>> {
+    let class = constant_pool.add_class(class_name)?;
+    let init = constant_pool.add_method_ref(class, "<init>", "(Ljava/lang/String;)V")?;
+    let message = constant_pool.add_string(message)?;
+    Ok(vec![
+        Instruction::New(class),
+        Instruction::Dup,
+        ldc(message),
+        Instruction::Invokespecial(init),
+        Instruction::Athrow,
+    ])
+}
+
+/// The descriptor of an argument or result of an allocator shim method
+fn allocator_ty_descriptor(ty: AllocatorTy) -> String {
+    let usize_descriptor = options::get().usize_descriptor();
+    match ty {
+        // The size and alignment
+        AllocatorTy::Layout => usize_descriptor.repeat(2),
+        AllocatorTy::Usize => usize_descriptor.to_string(),
+        AllocatorTy::Ptr | AllocatorTy::ResultPtr => "Ljava/lang/Object;".to_string(),
+        AllocatorTy::Unit | AllocatorTy::Never => "V".to_string(),
+    }
+}
+
+/// The class of the allocator module, if rustc asks for the allocator shim, with its name
+pub(crate) fn generate_allocator_class(
+    tcx: TyCtxt<'_>,
+) -> ristretto_classfile::Result<Option<(String, Vec<u8>)>> {
+    if allocator_kind_for_codegen(tcx).is_none() {
+        return Ok(None);
+    }
+    let class_name = format!("{}$allocator", naming::crate_class_name(tcx));
+    let mut constant_pool = ConstantPool::default();
+    let this_class = constant_pool.add_class(&class_name)?;
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+
+    let mut methods = Vec::new();
+    for method in ALLOCATOR_METHODS {
+        let inputs: String = method
+            .inputs
+            .iter()
+            .map(|input| allocator_ty_descriptor(input.ty))
+            .collect();
+        let descriptor = format!("({inputs}){}", allocator_ty_descriptor(method.output));
+        let instructions = if method.name == sym::dealloc {
+            vec![Instruction::Return]
+        } else {
+            throw(
+                &mut constant_pool,
+                "java/lang/UnsupportedOperationException",
+                "the JVM has no untyped memory to allocate",
+            )?
+        };
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            &global_fn_name(method.name),
+            &descriptor,
+            &MethodCode::from(instructions),
+        )?);
+    }
+    let instructions = throw(
+        &mut constant_pool,
+        OUT_OF_MEMORY_ERROR,
+        OUT_OF_MEMORY_MESSAGE,
+    )?;
+    methods.push(create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        &global_fn_name(sym::alloc_error_handler),
+        &format!("({})V", allocator_ty_descriptor(AllocatorTy::Layout)),
+        &MethodCode::from(instructions),
+    )?);
+
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::FINAL | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    };
+    class_file.verify()?;
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok(Some((class_name, bytes)))
+}
+
+impl MirToBytecodeVisitor<'_, '_> {
+    /// Lowers calls of the allocation functions of `alloc::alloc`, returning `false` for any other
+    /// call
+    pub(crate) fn lower_alloc_call(&mut self, callee: DefId) -> bool {
+        let tcx = self.tcx;
+        if tcx.crate_name(callee.krate) != sym::alloc {
+            return false;
+        }
+        let path = tcx.def_path(callee).to_string_no_crate_verbose();
+        match path.as_str() {
+            "::alloc::dealloc" => {
+                // The garbage collector frees the object once it is unreachable
+                trace!(
+                    "      Generated bytecode: nothing for {}",
+                    tcx.def_path_str(callee)
+                );
+            }
+            "::alloc::alloc" | "::alloc::alloc_zeroed" | "::alloc::realloc" => {
+                let allocation = tcx.def_path_str(callee);
+                if self.record_unsupported("raw-allocation", format_args!("`{allocation}`")) {
+                    return true;
+                }
+                let function = tcx.def_path_str(self.instance.def_id());
+                tcx.dcx()
+                    .struct_span_err(
+                        self.span,
+                        format!("unsupported raw allocation in `{function}`: `{allocation}`"),
+                    )
+                    .with_note(
+                        "the JVM has no untyped memory to allocate, use `Box`, `Vec` or `String`, \
+                         which are lowered to JVM objects",
+                    )
+                    .emit();
+            }
+            "::alloc::handle_alloc_error" => {
+                let instructions = throw(
+                    self.constant_pool,
+                    OUT_OF_MEMORY_ERROR,
+                    OUT_OF_MEMORY_MESSAGE,
+                )
+                .expect("Could not add the OutOfMemoryError to the constant pool");
+                self.method_bytecode_instructions.extend(instructions);
+                trace!("      Generated bytecode: throw of an OutOfMemoryError");
+            }
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,172 @@
+//! Annotations on generated classes, fields and methods.
+
+use crate::attributes::{self, AnnotationValue};
+use crate::constant_pool::ConstantPool;
+use crate::rust_ty_to_jvm_descriptor;
+use ristretto_classfile::attributes::{
+    Annotation, AnnotationElement, AnnotationValuePair, Attribute, ParameterAnnotation,
+};
+use rustc_attr_data_structures::{DeprecatedSince, Deprecation};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_span::sym;
+
+const NOT_NULL: &str = "Lorg/jetbrains/annotations/NotNull;";
+const NULLABLE: &str = "Lorg/jetbrains/annotations/Nullable;";
+const DEPRECATED: &str = "Ljava/lang/Deprecated;";
+
+/// The nullability annotation of a value of the given type, if it is a reference on the JVM
+fn nullability<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<&'static str> {
+    if !matches!(
+        rust_ty_to_jvm_descriptor(ty, tcx).as_bytes().first(),
+        Some(b'L' | b'[')
+    ) {
+        return None;
+    }
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) if tcx.is_diagnostic_item(sym::Option, adt_def.did()) => {
+            Some(NULLABLE)
+        }
+        _ => Some(NOT_NULL),
+    }
+}
+
+/// An annotation without elements
+fn marker_annotation(
+    constant_pool: &mut ConstantPool,
+    descriptor: &str,
+) -> ristretto_classfile::Result<Annotation> {
+    Ok(Annotation {
+        type_index: constant_pool.add_utf8(descriptor)?,
+        elements: Vec::new(),
+    })
+}
+
+/// The nullability annotation attribute of a field of the given type
+pub(crate) fn field_nullability<'tcx>(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'tcx>,
+    ty: Ty<'tcx>,
+) -> ristretto_classfile::Result<Option<Attribute>> {
+    let Some(descriptor) = nullability(ty, tcx) else {
+        return Ok(None);
+    };
+    Ok(Some(Attribute::RuntimeInvisibleAnnotations {
+        name_index: constant_pool.add_utf8("RuntimeInvisibleAnnotations")?,
+        annotations: vec![marker_annotation(constant_pool, descriptor)?],
+    }))
+}
+
+/// The nullability annotation attributes of a method with the given parameter and return types
+pub(crate) fn method_nullability<'tcx>(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'tcx>,
+    parameters: &[Ty<'tcx>],
+    return_ty: Ty<'tcx>,
+) -> ristretto_classfile::Result<Vec<Attribute>> {
+    let mut attributes = Vec::new();
+    if let Some(descriptor) = nullability(return_ty, tcx) {
+        attributes.push(Attribute::RuntimeInvisibleAnnotations {
+            name_index: constant_pool.add_utf8("RuntimeInvisibleAnnotations")?,
+            annotations: vec![marker_annotation(constant_pool, descriptor)?],
+        });
+    }
+
+    let mut parameter_annotations = Vec::with_capacity(parameters.len());
+    for parameter in parameters {
+        let annotations = match nullability(*parameter, tcx) {
+            Some(descriptor) => vec![marker_annotation(constant_pool, descriptor)?],
+            None => Vec::new(),
+        };
+        parameter_annotations.push(ParameterAnnotation { annotations });
+    }
+    if parameter_annotations
+        .iter()
+        .any(|parameter| !parameter.annotations.is_empty())
+    {
+        attributes.push(Attribute::RuntimeInvisibleParameterAnnotations {
+            name_index: constant_pool.add_utf8("RuntimeInvisibleParameterAnnotations")?,
+            parameter_annotations,
+        });
+    }
+    Ok(attributes)
+}
+
+/// The nullability annotation attributes of the method generated for a Rust function, see
+/// [`crate::method_descriptor`]
+pub(crate) fn fn_nullability(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    skip_receiver: bool,
+) -> ristretto_classfile::Result<Vec<Attribute>> {
+    let fn_sig = tcx.fn_sig(def_id).skip_binder().skip_binder();
+    let parameters = &fn_sig.inputs()[usize::from(skip_receiver)..];
+    method_nullability(constant_pool, tcx, parameters, fn_sig.output())
+}
+
+/// The `@Deprecated` annotation of a deprecated item, with the version it was deprecated in
+fn deprecated_annotation(
+    constant_pool: &mut ConstantPool,
+    deprecation: &Deprecation,
+) -> ristretto_classfile::Result<Annotation> {
+    let mut annotation = marker_annotation(constant_pool, DEPRECATED)?;
+    if let DeprecatedSince::NonStandard(since) = deprecation.since {
+        annotation.elements.push(AnnotationValuePair {
+            name_index: constant_pool.add_utf8("since")?,
+            value: AnnotationElement::String {
+                const_value_index: constant_pool.add_utf8(since.as_str())?,
+            },
+        });
+    }
+    Ok(annotation)
+}
+
+/// The attributes holding the annotations of the item: the `RuntimeVisibleAnnotations` requested
+/// with `#[jvm::annotation(...)]` and the `@Deprecated` of a `#[deprecated]` item, with its
+/// `Deprecated` attribute
+pub(crate) fn java_annotations(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+) -> ristretto_classfile::Result<Vec<Attribute>> {
+    let mut attributes = Vec::new();
+    let mut annotations = Vec::new();
+    if let Some(deprecation) = tcx.lookup_deprecation(def_id) {
+        attributes.push(Attribute::Deprecated {
+            name_index: constant_pool.add_utf8("Deprecated")?,
+        });
+        annotations.push(deprecated_annotation(constant_pool, &deprecation)?);
+    }
+    for java_annotation in attributes::java_annotations(tcx, def_id) {
+        let mut elements = Vec::with_capacity(java_annotation.elements.len());
+        for (name, value) in &java_annotation.elements {
+            let value = match value {
+                AnnotationValue::String(value) => AnnotationElement::String {
+                    const_value_index: constant_pool.add_utf8(value)?,
+                },
+                AnnotationValue::Int(value) => AnnotationElement::Int {
+                    const_value_index: constant_pool.add_integer(*value)?,
+                },
+                AnnotationValue::Boolean(value) => AnnotationElement::Boolean {
+                    const_value_index: constant_pool.add_integer(i32::from(*value))?,
+                },
+            };
+            elements.push(AnnotationValuePair {
+                name_index: constant_pool.add_utf8(name)?,
+                value,
+            });
+        }
+        annotations.push(Annotation {
+            type_index: constant_pool.add_utf8(format!("L{};", java_annotation.class_name))?,
+            elements,
+        });
+    }
+    if !annotations.is_empty() {
+        attributes.push(Attribute::RuntimeVisibleAnnotations {
+            name_index: constant_pool.add_utf8("RuntimeVisibleAnnotations")?,
+            annotations,
+        });
+    }
+    Ok(attributes)
+}
@@ -0,0 +1,163 @@
+//! Arrays and slices as JVM arrays.
+
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::{ArrayType, Instruction};
+use rustc_abi::FieldIdx;
+use rustc_index::IndexVec;
+use rustc_middle::mir::Operand;
+use rustc_middle::ty::{self, EarlyBinder, Ty, TypingEnv};
+
+/// The instruction loading an element with the given descriptor from an array
+pub(crate) fn array_load_instruction(descriptor: &str) -> Instruction {
+    match descriptor.as_bytes().first() {
+        Some(b'Z' | b'B') => Instruction::Baload,
+        Some(b'C') => Instruction::Caload,
+        Some(b'S') => Instruction::Saload,
+        Some(b'I') => Instruction::Iaload,
+        Some(b'J') => Instruction::Laload,
+        Some(b'F') => Instruction::Faload,
+        Some(b'D') => Instruction::Daload,
+        _ => Instruction::Aaload,
+    }
+}
+
+/// The instruction storing an element with the given descriptor into an array
+pub(crate) fn array_store_instruction(descriptor: &str) -> Instruction {
+    match descriptor.as_bytes().first() {
+        Some(b'Z' | b'B') => Instruction::Bastore,
+        Some(b'C') => Instruction::Castore,
+        Some(b'S') => Instruction::Sastore,
+        Some(b'I') => Instruction::Iastore,
+        Some(b'J') => Instruction::Lastore,
+        Some(b'F') => Instruction::Fastore,
+        Some(b'D') => Instruction::Dastore,
+        _ => Instruction::Aastore,
+    }
+}
+
+/// The primitive array type of an element descriptor, `None` for arrays of objects
+fn primitive_array_type(descriptor: &str) -> Option<ArrayType> {
+    Some(match descriptor.as_bytes().first()? {
+        b'Z' => ArrayType::Boolean,
+        b'B' => ArrayType::Byte,
+        b'C' => ArrayType::Char,
+        b'S' => ArrayType::Short,
+        b'I' => ArrayType::Int,
+        b'J' => ArrayType::Long,
+        b'F' => ArrayType::Float,
+        b'D' => ArrayType::Double,
+        _ => return None,
+    })
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes a new array holding `operands`
+    pub(crate) fn load_array_aggregate(
+        &mut self,
+        element_ty: Ty<'tcx>,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let descriptor = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        self.push_new_array(&descriptor, operands.len());
+        for (index, operand) in operands.iter().enumerate() {
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            let index = self.int_constant(index as i32);
+            self.method_bytecode_instructions.push(index);
+            self.load_operand(operand);
+            self.method_bytecode_instructions
+                .push(array_store_instruction(&descriptor));
+        }
+    }
+
+    /// Pushes a new array of `count` copies of `operand`, as `[operand; count]`. Returns whether
+    /// the array was pushed, which it is not when its length has no value
+    pub(crate) fn load_array_repeat(
+        &mut self,
+        operand: &Operand<'tcx>,
+        count: ty::Const<'tcx>,
+    ) -> bool {
+        let Some(length) = self.array_length(count) else {
+            if !self.record_unsupported("array-length", format_args!("{count:?}")) {
+                self.tcx.dcx().span_err(
+                    self.span,
+                    format!("the array length `{count}` has no value"),
+                );
+            }
+            return false;
+        };
+        // JVM arrays are indexed by `int`s
+        let Some(length) = usize::try_from(length)
+            .ok()
+            .filter(|length| i32::try_from(*length).is_ok())
+        else {
+            self.tcx.dcx().span_err(
+                self.span,
+                format!("the array length {length} does not fit a JVM array"),
+            );
+            return false;
+        };
+
+        let element_ty = self.monomorphize(operand.ty(self.body, self.tcx));
+        let descriptor = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        self.push_new_array(&descriptor, length);
+        // Arrays.fill(array, value), with the `Object[]` overload for arrays of objects
+        let fill_descriptor = if primitive_array_type(&descriptor).is_some() {
+            format!("([{descriptor}{descriptor})V")
+        } else {
+            "([Ljava/lang/Object;Ljava/lang/Object;)V".to_string()
+        };
+        let arrays = self
+            .constant_pool
+            .add_class("java/util/Arrays")
+            .expect("Could not add class to constant pool");
+        let fill = self
+            .constant_pool
+            .add_method_ref(arrays, "fill", &fill_descriptor)
+            .expect("Could not add method to constant pool");
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        self.load_operand(operand);
+        self.method_bytecode_instructions
+            .push(Instruction::Invokestatic(fill));
+        true
+    }
+
+    /// The value of an array length, instantiated for the instance being lowered and evaluated,
+    /// `None` if it has none
+    pub(crate) fn array_length(&self, length: ty::Const<'tcx>) -> Option<u64> {
+        self.monomorphize(length).try_to_target_usize(self.tcx)
+    }
+
+    /// A type or constant of the body, with the generic arguments of the instance being lowered,
+    /// and its constants evaluated
+    pub(crate) fn monomorphize<T>(&self, value: T) -> T
+    where
+        T: ty::TypeFoldable<ty::TyCtxt<'tcx>>,
+    {
+        self.instance.instantiate_mir_and_normalize_erasing_regions(
+            self.tcx,
+            TypingEnv::fully_monomorphized(),
+            EarlyBinder::bind(value),
+        )
+    }
+
+    /// Pushes a new array of `length` elements with the given descriptor
+    pub(crate) fn push_new_array(&mut self, descriptor: &str, length: usize) {
+        let length = self.int_constant(length as i32);
+        self.method_bytecode_instructions.push(length);
+        if let Some(array_type) = primitive_array_type(descriptor) {
+            self.method_bytecode_instructions
+                .push(Instruction::Newarray(array_type));
+        } else {
+            let class_name = match descriptor.strip_prefix('L') {
+                Some(class_name) => class_name.trim_end_matches(';'),
+                None => descriptor,
+            };
+            let class_index = self
+                .constant_pool
+                .add_class(class_name)
+                .expect("Could not add class to constant pool");
+            self.method_bytecode_instructions
+                .push(Instruction::Anewarray(class_index));
+        }
+    }
+}
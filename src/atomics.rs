@@ -0,0 +1,326 @@
+//! Atomic types as `java.util.concurrent.atomic` classes.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, control_flow, options, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind, TypingEnv};
+use rustc_span::Symbol;
+use rustc_span::source_map::Spanned;
+
+/// The atomic types, by diagnostic name, and the Java class and value descriptor they map to
+const ATOMICS: [(&str, &str, &str); 8] = [
+    (
+        "AtomicBool",
+        "java/util/concurrent/atomic/AtomicBoolean",
+        "Z",
+    ),
+    (
+        "AtomicI32",
+        "java/util/concurrent/atomic/AtomicInteger",
+        "I",
+    ),
+    (
+        "AtomicU32",
+        "java/util/concurrent/atomic/AtomicInteger",
+        "I",
+    ),
+    (
+        "AtomicIsize",
+        "java/util/concurrent/atomic/AtomicInteger",
+        "I",
+    ),
+    (
+        "AtomicUsize",
+        "java/util/concurrent/atomic/AtomicInteger",
+        "I",
+    ),
+    ("AtomicI64", "java/util/concurrent/atomic/AtomicLong", "J"),
+    ("AtomicU64", "java/util/concurrent/atomic/AtomicLong", "J"),
+    (
+        "AtomicPtr",
+        "java/util/concurrent/atomic/AtomicReference",
+        "Ljava/lang/Object;",
+    ),
+];
+
+/// The first class version whose atomic classes have the weaker access modes of `VarHandle`s,
+/// from Java 9. Before it relaxed and acquire loads are volatile `get`s, relaxed and release
+/// stores `lazySet`s, and `compare_exchange` retries `compareAndSet` until it can tell the
+/// value it replaced.
+const ACCESS_MODES_CLASS_VERSION: u16 = 9;
+
+/// `core::sync::atomic::Ordering`, by discriminant
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ordering {
+    Relaxed,
+    Release,
+    Acquire,
+    AcqRel,
+    SeqCst,
+}
+
+/// The Java class and value descriptor of an atomic type
+pub(crate) fn java_class(ty: Ty<'_>, tcx: TyCtxt<'_>) -> Option<(&'static str, &'static str)> {
+    let TyKind::Adt(adt_def, _) = ty.kind() else {
+        return None;
+    };
+    ATOMICS
+        .iter()
+        .find(|(name, ..)| tcx.is_diagnostic_item(Symbol::intern(name), adt_def.did()))
+        .map(|(_, class, descriptor)| (*class, *descriptor))
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of atomic methods, returning `false` for any other call
+    pub(crate) fn lower_atomic_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        let atomic_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        let Some((class, descriptor)) = java_class(atomic_ty, tcx) else {
+            return false;
+        };
+        // The class pointers point to, more precise than `Object`
+        let value_descriptor = match atomic_ty.kind() {
+            TyKind::Adt(_, atomic_args) if descriptor.starts_with('L') => {
+                let pointee = rust_ty_to_jvm_descriptor(atomic_args.type_at(0), tcx);
+                if matches!(pointee.as_bytes().first(), Some(b'L' | b'[')) {
+                    pointee
+                } else {
+                    descriptor.to_string()
+                }
+            }
+            _ => descriptor.to_string(),
+        };
+        let is_long = descriptor == "J";
+        let name = tcx.item_name(callee);
+        match name.as_str() {
+            "new" => {
+                let class_index = self
+                    .constant_pool
+                    .add_class(class)
+                    .expect("Could not add class to constant pool");
+                let constructor = self
+                    .constant_pool
+                    .add_method_ref(class_index, "<init>", &format!("({descriptor})V"))
+                    .expect("Could not add constructor to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::New(class_index));
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokespecial(constructor));
+            }
+            "load" | "into_inner" => {
+                let method = match self.ordering(args.get(1).map(|arg| &arg.node)) {
+                    _ if !has_access_modes() => "get",
+                    Ordering::Relaxed => "getOpaque",
+                    Ordering::Acquire => "getAcquire",
+                    _ => "get",
+                };
+                self.load_operand(&args[0].node);
+                self.invoke_atomic(class, method, &format!("(){descriptor}"));
+                self.cast_from_object(&value_descriptor);
+            }
+            "store" => {
+                let method = match self.ordering(Some(&args[2].node)) {
+                    Ordering::Relaxed | Ordering::Release if !has_access_modes() => "lazySet",
+                    Ordering::Relaxed => "setOpaque",
+                    Ordering::Release => "setRelease",
+                    _ => "set",
+                };
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.invoke_atomic(class, method, &format!("({descriptor})V"));
+            }
+            "swap" => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.invoke_atomic(class, "getAndSet", &format!("({descriptor}){descriptor}"));
+                self.cast_from_object(&value_descriptor);
+            }
+            "fetch_add" | "fetch_sub" if matches!(descriptor, "I" | "J") => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                if name.as_str() == "fetch_sub" {
+                    self.method_bytecode_instructions.push(if is_long {
+                        Instruction::Lneg
+                    } else {
+                        Instruction::Ineg
+                    });
+                }
+                self.invoke_atomic(class, "getAndAdd", &format!("({descriptor}){descriptor}"));
+            }
+            "compare_exchange" | "compare_exchange_weak" => {
+                return self.lower_compare_exchange(
+                    class,
+                    descriptor,
+                    &value_descriptor,
+                    args,
+                    destination,
+                );
+            }
+            _ => {
+                trace!(
+                    "      Unsupported atomic method: {}",
+                    tcx.def_path_str(callee)
+                );
+                return false;
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `compare_exchange(current, new, success, failure)` returns `Ok` of the previous value if
+    /// it was `current` and got replaced, otherwise `Err` of the value found
+    fn lower_compare_exchange(
+        &mut self,
+        class: &str,
+        descriptor: &str,
+        value_descriptor: &str,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for compare_exchange: {destination:?}");
+            return false;
+        }
+        let method = match self.ordering(Some(&args[3].node)) {
+            Ordering::Acquire => "compareAndExchangeAcquire",
+            Ordering::Release => "compareAndExchangeRelease",
+            _ => "compareAndExchange",
+        };
+        let ok = VariantIdx::from_u32(0);
+        let err = VariantIdx::from_u32(1);
+
+        let retry = self.method_bytecode_instructions.len();
+        if has_access_modes() {
+            // witness = atomic.compareAndExchange(current, new)
+            self.load_operand(&args[0].node);
+            self.load_operand(&args[1].node);
+            self.load_operand(&args[2].node);
+            self.invoke_atomic(
+                class,
+                method,
+                &format!("({descriptor}{descriptor}){descriptor}"),
+            );
+        } else {
+            // witness = atomic.get(), then below, if it is `current` and
+            // atomic.compareAndSet(current, new) fails, start over
+            self.extra_frames.push((retry, Vec::new()));
+            self.load_operand(&args[0].node);
+            self.invoke_atomic(class, "get", &format!("(){descriptor}"));
+        }
+        self.cast_from_object(value_descriptor);
+
+        // if (witness == current) Ok(witness) else Err(witness)
+        let to_err = match descriptor {
+            "J" => {
+                self.method_bytecode_instructions.push(Instruction::Dup2);
+                self.load_operand(&args[1].node);
+                self.method_bytecode_instructions.push(Instruction::Lcmp);
+                Instruction::Ifne(0)
+            }
+            "Z" | "I" => {
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                self.load_operand(&args[1].node);
+                Instruction::If_icmpne(0)
+            }
+            _ => {
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                self.load_operand(&args[1].node);
+                Instruction::If_acmpne(0)
+            }
+        };
+        let branch = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(to_err);
+        if !has_access_modes() {
+            self.method_bytecode_instructions
+                .push(if descriptor == "J" {
+                    Instruction::Pop2
+                } else {
+                    Instruction::Pop
+                });
+            self.load_operand(&args[0].node);
+            self.load_operand(&args[1].node);
+            self.load_operand(&args[2].node);
+            self.invoke_atomic(
+                class,
+                "compareAndSet",
+                &format!("({descriptor}{descriptor})Z"),
+            );
+            self.method_bytecode_instructions
+                .push(Instruction::Ifeq(retry as u16));
+            self.load_operand(&args[1].node);
+        }
+        self.store_enum_field(destination.local, ok, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, ok);
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        let err_start = self.method_bytecode_instructions.len();
+        let witness = control_flow::verification_type(value_descriptor, self.constant_pool)
+            .expect("Could not add class to constant pool");
+        self.extra_frames.push((err_start, vec![witness]));
+        self.store_enum_field(destination.local, err, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, err);
+
+        let end = self.method_bytecode_instructions.len();
+        self.extra_frames.push((end, Vec::new()));
+        self.method_bytecode_instructions[branch] = control_flow::with_branch_target(
+            &self.method_bytecode_instructions[branch],
+            err_start as u16,
+        );
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        trace!("      Generated bytecode: compare_exchange");
+        true
+    }
+
+    /// The memory ordering of a constant `Ordering` operand, `SeqCst` if it is not a constant
+    fn ordering(&self, operand: Option<&Operand<'tcx>>) -> Ordering {
+        let Some(Operand::Constant(constant)) = operand else {
+            return Ordering::SeqCst;
+        };
+        let discriminant = constant
+            .const_
+            .try_eval_scalar_int(self.tcx, TypingEnv::fully_monomorphized())
+            .map(|scalar| scalar.to_bits_unchecked());
+        match discriminant {
+            Some(0) => Ordering::Relaxed,
+            Some(1) => Ordering::Release,
+            Some(2) => Ordering::Acquire,
+            Some(3) => Ordering::AcqRel,
+            _ => Ordering::SeqCst,
+        }
+    }
+
+    fn invoke_atomic(&mut self, class_name: &str, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(method));
+    }
+}
+
+/// Whether the atomic classes have the access modes of `VarHandle`s in the class version
+fn has_access_modes() -> bool {
+    options::get().class_version >= ACCESS_MODES_CLASS_VERSION
+}
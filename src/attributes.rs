@@ -0,0 +1,98 @@
+//! Helpers for reading the `#[jvm::...]` tool attributes that control how Rust items are exposed
+//! to Java.
+
+use rustc_ast::LitKind;
+use rustc_hir::Attribute;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Symbol;
+
+/// Returns every `#[jvm::<name>]` attribute on the given item
+fn jvm_attrs<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId, name: &str) -> Vec<&'tcx Attribute> {
+    let path = [Symbol::intern("jvm"), Symbol::intern(name)];
+    tcx.get_attrs_by_path(def_id, &path).collect()
+}
+
+/// Whether the item carries `#[jvm::<name>]`
+pub(crate) fn has_jvm_attr(tcx: TyCtxt<'_>, def_id: DefId, name: &str) -> bool {
+    !jvm_attrs(tcx, def_id, name).is_empty()
+}
+
+/// The string literal arguments of every `#[jvm::<name>("...")]` attribute on the item
+pub(crate) fn jvm_attr_string_args(tcx: TyCtxt<'_>, def_id: DefId, name: &str) -> Vec<String> {
+    jvm_attrs(tcx, def_id, name)
+        .iter()
+        .filter_map(|attr| attr.meta_item_list())
+        .flatten()
+        .filter_map(|item| item.lit().and_then(|lit| lit.kind.str()))
+        .map(|symbol| symbol.to_string())
+        .collect()
+}
+
+/// Converts a Java binary class name like `java.lang.Runnable` to its internal form
+/// `java/lang/Runnable`
+pub(crate) fn internal_class_name(java_name: &str) -> String {
+    java_name.replace('.', "/")
+}
+
+/// A value given to an element of a Java annotation
+pub(crate) enum AnnotationValue {
+    String(String),
+    Int(i32),
+    Boolean(bool),
+}
+
+/// A Java annotation requested with `#[jvm::annotation("<class>", element = value, ...)]`
+pub(crate) struct JavaAnnotation {
+    pub(crate) class_name: String,
+    pub(crate) elements: Vec<(String, AnnotationValue)>,
+}
+
+/// The Java annotations requested on the item, reporting malformed ones
+pub(crate) fn java_annotations(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<JavaAnnotation> {
+    let mut annotations = Vec::new();
+    for attr in jvm_attrs(tcx, def_id, "annotation") {
+        let items = attr.meta_item_list().unwrap_or_default();
+        let Some(class_name) = items
+            .first()
+            .and_then(|item| item.lit())
+            .and_then(|lit| lit.kind.str())
+        else {
+            tcx.dcx().span_err(
+                attr.span(),
+                "expected `#[jvm::annotation(\"<annotation class>\", ...)]`",
+            );
+            continue;
+        };
+
+        let mut elements = Vec::new();
+        for item in &items[1..] {
+            let value = item
+                .meta_item()
+                .and_then(|meta_item| meta_item.name_value_literal())
+                .and_then(|lit| match lit.kind {
+                    LitKind::Str(value, _) => Some(AnnotationValue::String(value.to_string())),
+                    LitKind::Int(value, _) => {
+                        i32::try_from(value.get()).ok().map(AnnotationValue::Int)
+                    }
+                    LitKind::Bool(value) => Some(AnnotationValue::Boolean(value)),
+                    _ => None,
+                });
+            match (item.name(), value) {
+                (Some(name), Some(value)) => elements.push((name.to_string(), value)),
+                _ => {
+                    tcx.dcx().span_err(
+                        item.span(),
+                        "annotation elements must be `name = value` with a string, integer or \
+                         boolean value",
+                    );
+                }
+            }
+        }
+        annotations.push(JavaAnnotation {
+            class_name: internal_class_name(class_name.as_str()),
+            elements,
+        });
+    }
+    annotations
+}
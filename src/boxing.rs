@@ -0,0 +1,85 @@
+//! Conversions between JVM primitives and objects.
+
+use crate::MirToBytecodeVisitor;
+use crate::constant_pool::ConstantPool;
+use ristretto_classfile::attributes::Instruction;
+
+/// The wrapper class and unboxing method of a primitive field descriptor
+pub(crate) fn wrapper(descriptor: &str) -> Option<(&'static str, &'static str)> {
+    Some(match descriptor.as_bytes().first()? {
+        b'Z' => ("java/lang/Boolean", "booleanValue"),
+        b'B' => ("java/lang/Byte", "byteValue"),
+        b'C' => ("java/lang/Character", "charValue"),
+        b'S' => ("java/lang/Short", "shortValue"),
+        b'I' => ("java/lang/Integer", "intValue"),
+        b'J' => ("java/lang/Long", "longValue"),
+        b'F' => ("java/lang/Float", "floatValue"),
+        b'D' => ("java/lang/Double", "doubleValue"),
+        _ => return None,
+    })
+}
+
+/// The instructions turning the value on top of the stack into an object. Objects are left
+/// untouched.
+pub(crate) fn box_instructions(
+    constant_pool: &mut ConstantPool,
+    descriptor: &str,
+) -> ristretto_classfile::Result<Vec<Instruction>> {
+    let Some((wrapper_class, _)) = wrapper(descriptor) else {
+        return Ok(Vec::new());
+    };
+    let wrapper_class_index = constant_pool.add_class(wrapper_class)?;
+    let value_of = constant_pool.add_method_ref(
+        wrapper_class_index,
+        "valueOf",
+        &format!("({descriptor})L{wrapper_class};"),
+    )?;
+    Ok(vec![Instruction::Invokestatic(value_of)])
+}
+
+/// The instructions turning the `Object` on top of the stack into a value with the given
+/// descriptor: unboxing primitives, and casting objects to their class
+fn cast_instructions(
+    constant_pool: &mut ConstantPool,
+    descriptor: &str,
+) -> ristretto_classfile::Result<Vec<Instruction>> {
+    if let Some((wrapper_class, unbox_method)) = wrapper(descriptor) {
+        let wrapper_class_index = constant_pool.add_class(wrapper_class)?;
+        let unbox = constant_pool.add_method_ref(
+            wrapper_class_index,
+            unbox_method,
+            &format!("(){descriptor}"),
+        )?;
+        return Ok(vec![
+            Instruction::Checkcast(wrapper_class_index),
+            Instruction::Invokevirtual(unbox),
+        ]);
+    }
+    let class_name = match descriptor.as_bytes().first() {
+        Some(b'L') => &descriptor[1..descriptor.len() - 1],
+        Some(b'[') => descriptor,
+        _ => return Ok(Vec::new()),
+    };
+    if class_name == "java/lang/Object" {
+        return Ok(Vec::new());
+    }
+    Ok(vec![Instruction::Checkcast(
+        constant_pool.add_class(class_name)?,
+    )])
+}
+
+impl MirToBytecodeVisitor<'_, '_> {
+    /// Boxes the primitive on top of the stack, see [`box_instructions`]
+    pub(crate) fn box_value(&mut self, descriptor: &str) {
+        let instructions = box_instructions(self.constant_pool, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions.extend(instructions);
+    }
+
+    /// Casts the object on top of the stack to a value, see [`cast_instructions`]
+    pub(crate) fn cast_from_object(&mut self, descriptor: &str) {
+        let instructions = cast_instructions(self.constant_pool, descriptor)
+            .expect("Could not add class to constant pool");
+        self.method_bytecode_instructions.extend(instructions);
+    }
+}
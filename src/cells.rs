@@ -0,0 +1,211 @@
+//! `Cell` and `RefCell` as runtime wrapper classes.
+
+use crate::runtime::{RuntimeClass, ValueKind};
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::Symbol;
+use rustc_span::source_map::Spanned;
+
+/// Whether the type is the core type with the given diagnostic name
+fn is_diagnostic_adt(tcx: TyCtxt<'_>, ty: Ty<'_>, name: &str) -> bool {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) => tcx.is_diagnostic_item(Symbol::intern(name), adt_def.did()),
+        _ => false,
+    }
+}
+
+/// The value type of a `Cell`, `RefCell`, `Ref` or `RefMut`
+fn value_ty<'tcx>(ty: Ty<'tcx>) -> Option<Ty<'tcx>> {
+    match ty.kind() {
+        TyKind::Adt(_, generic_args) => generic_args.types().next(),
+        _ => None,
+    }
+}
+
+/// The runtime class a `Cell`, `RefCell` or one of its guards is lowered to
+pub(crate) fn runtime_class<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<RuntimeClass> {
+    let is_cell = is_diagnostic_adt(tcx, ty, "Cell");
+    let is_ref_cell = ["RefCell", "RefCellRef", "RefCellRefMut"]
+        .iter()
+        .any(|name| is_diagnostic_adt(tcx, ty, name));
+    if !is_cell && !is_ref_cell {
+        return None;
+    }
+    let kind = ValueKind::of(&rust_ty_to_jvm_descriptor(value_ty(ty)?, tcx))?;
+    Some(if is_cell {
+        RuntimeClass::Cell(kind)
+    } else {
+        RuntimeClass::RefCell(kind)
+    })
+}
+
+/// Whether the type is a `Ref` or `RefMut` guard
+fn is_guard(tcx: TyCtxt<'_>, ty: Ty<'_>) -> bool {
+    is_diagnostic_adt(tcx, ty, "RefCellRef") || is_diagnostic_adt(tcx, ty, "RefCellRefMut")
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `Cell` and `RefCell` methods and of `deref()` on their guards, returning
+    /// `false` for any other call
+    pub(crate) fn lower_cell_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let trait_def_id = tcx.trait_of_item(callee);
+        if trait_def_id.is_some()
+            && (trait_def_id == tcx.lang_items().deref_trait()
+                || trait_def_id == tcx.lang_items().deref_mut_trait())
+        {
+            let guard_ty = generic_args.type_at(0);
+            if !is_guard(tcx, guard_ty) {
+                return false;
+            }
+            let (Some(class), Some(value_ty)) = (runtime_class(guard_ty, tcx), value_ty(guard_ty))
+            else {
+                return false;
+            };
+            if class != RuntimeClass::RefCell(ValueKind::Reference) {
+                trace!("      Unsupported dereference of a RefCell of a primitive");
+                return false;
+            }
+            self.load_operand(&args[0].node);
+            self.load_cell_value(class, value_ty);
+            self.store_call_result(destination);
+            return true;
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        let cell_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        let (Some(class), Some(value_ty)) = (runtime_class(cell_ty, tcx), value_ty(cell_ty)) else {
+            return false;
+        };
+        let is_ref_cell = matches!(class, RuntimeClass::RefCell(_));
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        match (tcx.item_name(callee).as_str(), is_ref_cell) {
+            ("new", _) => {
+                let descriptor = match class {
+                    RuntimeClass::Cell(kind) | RuntimeClass::RefCell(kind) => kind.descriptor(),
+                    _ => unreachable!("not a cell class: {class:?}"),
+                };
+                let constructor = self
+                    .constant_pool
+                    .add_method_ref(class_index, "<init>", &format!("({descriptor})V"))
+                    .expect("Could not add constructor to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::New(class_index));
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokespecial(constructor));
+            }
+            ("get", false) | ("into_inner", _) => {
+                self.load_operand(&args[0].node);
+                self.load_cell_value(class, value_ty);
+            }
+            ("set", false) => {
+                let value = self.cell_value_field(class);
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Putfield(value));
+            }
+            ("replace", false) => {
+                let value = self.cell_value_field(class);
+                self.load_operand(&args[0].node);
+                self.load_cell_value(class, value_ty);
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Putfield(value));
+            }
+            (name @ ("borrow" | "borrow_mut"), true) => {
+                let method_name = if name == "borrow" {
+                    "borrow"
+                } else {
+                    "borrowMut"
+                };
+                let method = self
+                    .constant_pool
+                    .add_method_ref(class_index, method_name, &format!("()L{};", class.name()))
+                    .expect("Could not add method ref to constant pool");
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokevirtual(method));
+            }
+            _ => {
+                trace!(
+                    "      Unsupported cell method: {}",
+                    tcx.def_path_str(callee)
+                );
+                return false;
+            }
+        }
+        self.runtime_classes.insert(class);
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Releases the borrow of a `Ref` or `RefMut` guard being dropped
+    pub(crate) fn lower_cell_drop(&mut self, place: &Place<'tcx>) {
+        let ty = place.ty(self.body, self.tcx).ty;
+        if !place.projection.is_empty() || !is_guard(self.tcx, ty) {
+            return;
+        }
+        let Some(class) = runtime_class(ty, self.tcx) else {
+            return;
+        };
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        let release = self
+            .constant_pool
+            .add_method_ref(class_index, "release", "()V")
+            .expect("Could not add method ref to constant pool");
+        self.load_local(place.local);
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(release));
+    }
+
+    /// The `value` field of a cell class
+    fn cell_value_field(&mut self, class: RuntimeClass) -> u16 {
+        let descriptor = match class {
+            RuntimeClass::Cell(kind) | RuntimeClass::RefCell(kind) => kind.descriptor(),
+            _ => unreachable!("not a cell class: {class:?}"),
+        };
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        self.constant_pool
+            .add_field_ref(class_index, "value", descriptor)
+            .expect("Could not add field ref to constant pool")
+    }
+
+    /// Replaces the cell on top of the stack with its value, cast back to the value's class
+    fn load_cell_value(&mut self, class: RuntimeClass, value_ty: Ty<'tcx>) {
+        let value = self.cell_value_field(class);
+        self.method_bytecode_instructions
+            .push(Instruction::Getfield(value));
+        if class == RuntimeClass::RefCell(ValueKind::Reference)
+            || class == RuntimeClass::Cell(ValueKind::Reference)
+        {
+            let descriptor = rust_ty_to_jvm_descriptor(value_ty, self.tcx);
+            self.cast_from_object(&descriptor);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Codegen units, as classes of their own. Every unit but the last is a `<crate>$cgu<n>` superclass
+//! of the crate class, so calls on the crate class find all of their methods.
+
+use crate::constant_pool::{class_name, utf8};
+use crate::runtime::{RuntimeClass, RuntimeClasses};
+use crate::{
+    agent, entry, lower_class_unit, method_descriptor, mono, naming, options, support, trace,
+};
+use ristretto_classfile::{ClassFile, Constant};
+use rustc_codegen_ssa::assert_module_sources::CguReuse;
+use rustc_codegen_ssa::base::determine_cgu_reuse;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
+use rustc_hir::def_id::DefId;
+use rustc_incremental::{copy_cgu_workproduct_to_incr_comp_cache_dir, in_incr_comp_dir_sess};
+use rustc_middle::dep_graph::{WorkProduct, WorkProductId};
+use rustc_middle::mir::mono::MonoItem;
+use rustc_middle::ty::{Instance, TyCtxt};
+use rustc_session::Session;
+use rustc_session::config::CrateType;
+use rustc_span::Symbol;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// The key of the class file among the saved files of a work product, and its extension in the
+/// incremental directory
+const CLASS_FILE_KEY: &str = "class";
+
+/// The functions and instances lowered into one class
+#[derive(Debug)]
+pub(crate) struct ClassUnit<'tcx> {
+    pub(crate) class_name: String,
+    pub(crate) super_class: String,
+    pub(crate) functions: Vec<DefId>,
+    pub(crate) instances: Vec<Instance<'tcx>>,
+    /// The codegen unit the class holds all of, `None` for the crate class
+    codegen_unit: Option<Symbol>,
+}
+
+impl ClassUnit<'_> {
+    fn new(class_name: String, codegen_unit: Option<Symbol>) -> Self {
+        ClassUnit {
+            class_name,
+            super_class: "java/lang/Object".to_string(),
+            functions: Vec::new(),
+            instances: Vec::new(),
+            codegen_unit,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.instances.is_empty()
+    }
+}
+
+/// Partitions the free functions and collected instances of the crate into classes, following
+/// rustc's codegen units. The crate class comes first, the others in the order they inherit in.
+pub(crate) fn partition<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    crate_name: &str,
+    functions: Vec<DefId>,
+    instances: Vec<Instance<'tcx>>,
+) -> Vec<ClassUnit<'tcx>> {
+    // Units ordered by name, so that the classes of a crate keep their names across builds
+    let mut codegen_units: Vec<_> = tcx
+        .collect_and_partition_mono_items(())
+        .codegen_units
+        .iter()
+        .collect();
+    codegen_units.sort_by(|a, b| a.name().as_str().cmp(b.name().as_str()));
+    // Every function is left to the crate class
+    if options::get().hot_swap {
+        codegen_units.clear();
+    }
+    let mut unit_of = FxHashMap::default();
+    for (index, codegen_unit) in codegen_units.iter().enumerate() {
+        for item in codegen_unit.items().keys() {
+            if let MonoItem::Fn(instance) = item {
+                unit_of.entry(*instance).or_insert(index);
+            }
+        }
+    }
+
+    let mut crate_unit = ClassUnit::new(crate_name.to_string(), None);
+    let mut units: Vec<_> = codegen_units
+        .iter()
+        .enumerate()
+        .map(|(index, codegen_unit)| {
+            ClassUnit::new(
+                format!("{crate_name}$cgu{index}"),
+                Some(codegen_unit.name()),
+            )
+        })
+        .collect();
+    let strip_unreachable = options::get().strip_unreachable
+        && tcx.sess.crate_types() == [CrateType::Executable]
+        && !tcx.sess.is_test_crate();
+    for def_id in functions {
+        // The JVM starts programs and agents on the crate class
+        let is_entry = entry::is_entry_fn(tcx, def_id) || agent::is_premain(tcx, def_id);
+        match unit_of.get(&Instance::mono(tcx, def_id)) {
+            Some(index) if !is_entry => units[*index].functions.push(def_id),
+            None if strip_unreachable && !is_entry => {
+                trace::trace!("Left out unreachable function {}", tcx.def_path_str(def_id));
+            }
+            _ => crate_unit.functions.push(def_id),
+        }
+    }
+    for instance in instances {
+        match unit_of.get(&instance) {
+            Some(index) => units[*index].instances.push(instance),
+            None => crate_unit.instances.push(instance),
+        }
+    }
+
+    // The crate class takes the last unit in, which leaves it alone for a single unit
+    units.retain(|unit| !unit.is_empty());
+    if let Some(last) = units.pop() {
+        crate_unit.functions.extend(last.functions);
+        crate_unit.instances.extend(last.instances);
+    }
+    let mut super_class = "java/lang/Object".to_string();
+    for unit in &mut units {
+        unit.super_class = std::mem::replace(&mut super_class, unit.class_name.clone());
+    }
+    crate_unit.super_class = super_class;
+
+    let mut classes = vec![crate_unit];
+    classes.extend(units);
+    classes
+}
+
+/// How the class of a codegen unit is kept in the incremental directory
+pub(crate) enum UnitWorkProduct {
+    /// Lowered in this build, to be saved for the codegen unit of the given name
+    Lowered(String),
+    /// Copied forward from the previous build
+    Reused(WorkProduct),
+}
+
+/// The class of a unit, with the runtime classes it uses
+pub(crate) struct UnitClass {
+    pub(crate) class_name: String,
+    pub(crate) bytecode: Vec<u8>,
+    pub(crate) runtime_classes: RuntimeClasses,
+    pub(crate) work_product: Option<UnitWorkProduct>,
+}
+
+/// The class of a unit, reused from the previous build if its codegen unit is unchanged, lowered
+/// otherwise
+pub(crate) fn codegen_unit_class<'tcx>(tcx: TyCtxt<'tcx>, unit: ClassUnit<'tcx>) -> UnitClass {
+    let class_name = unit.class_name.clone();
+    let Some(codegen_unit) = unit.codegen_unit else {
+        let (bytecode, runtime_classes) = lower_class_unit(tcx, unit);
+        return UnitClass {
+            class_name,
+            bytecode,
+            runtime_classes,
+            work_product: None,
+        };
+    };
+
+    if let Some((bytecode, runtime_classes, work_product)) = reused_class(tcx, &unit) {
+        return UnitClass {
+            class_name,
+            bytecode,
+            runtime_classes,
+            work_product: Some(UnitWorkProduct::Reused(work_product)),
+        };
+    }
+    let dep_node = tcx.codegen_unit(codegen_unit).codegen_dep_node(tcx);
+    let ((bytecode, runtime_classes), _) =
+        tcx.dep_graph
+            .with_task(dep_node, tcx, unit, lower_tracked_class_unit, None);
+    UnitClass {
+        class_name,
+        bytecode,
+        runtime_classes,
+        work_product: Some(UnitWorkProduct::Lowered(codegen_unit.to_string())),
+    }
+}
+
+/// Lowers a unit as the dep graph task of its codegen unit
+fn lower_tracked_class_unit<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    unit: ClassUnit<'tcx>,
+) -> (Vec<u8>, RuntimeClasses) {
+    lower_class_unit(tcx, unit)
+}
+
+/// The class the previous build saved for the unit's codegen unit, with the runtime classes it
+/// uses and its work product, if the codegen unit is unchanged and the class is the one the unit
+/// would get
+fn reused_class<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    unit: &ClassUnit<'tcx>,
+) -> Option<(Vec<u8>, RuntimeClasses, WorkProduct)> {
+    let codegen_unit = unit.codegen_unit?;
+    if trace::is_enabled() || support::is_enabled() {
+        return None;
+    }
+    let work_product = tcx
+        .dep_graph
+        .previous_work_product(&WorkProductId::from_cgu_name(codegen_unit.as_str()))?;
+    let saved_file = work_product.saved_files.get(CLASS_FILE_KEY)?;
+    let bytecode = std::fs::read(in_incr_comp_dir_sess(tcx.sess, saved_file)).ok()?;
+    let class_file = ClassFile::from_bytes(&mut Cursor::new(bytecode.clone())).ok()?;
+
+    // Only now mark the codegen unit green, as the class is lowered under its dep node otherwise
+    if !is_class_of(tcx, unit, &class_file)
+        || matches!(
+            determine_cgu_reuse(tcx, tcx.codegen_unit(codegen_unit)),
+            CguReuse::No
+        )
+    {
+        return None;
+    }
+    let runtime_classes = class_file
+        .constant_pool
+        .iter()
+        .filter_map(|constant| match constant {
+            Constant::Class(name_index) => {
+                utf8(&class_file.constant_pool, *name_index).and_then(RuntimeClass::from_name)
+            }
+            _ => None,
+        })
+        .collect();
+    Some((bytecode, runtime_classes, work_product))
+}
+
+/// Whether a saved class has the name, superclass and methods the unit would get
+fn is_class_of<'tcx>(tcx: TyCtxt<'tcx>, unit: &ClassUnit<'tcx>, class_file: &ClassFile) -> bool {
+    let constant_pool = &class_file.constant_pool;
+    if class_name(constant_pool, class_file.this_class) != Some(unit.class_name.as_str())
+        || class_name(constant_pool, class_file.super_class) != Some(unit.super_class.as_str())
+    {
+        return false;
+    }
+
+    let saved_methods: Option<FxHashSet<(&str, &str)>> = class_file
+        .methods
+        .iter()
+        .map(|method| {
+            Some((
+                utf8(constant_pool, method.name_index)?,
+                utf8(constant_pool, method.descriptor_index)?,
+            ))
+        })
+        .collect();
+    let methods: FxHashSet<(String, String)> = unit
+        .functions
+        .iter()
+        .map(|def_id| {
+            (
+                naming::method_name(tcx, *def_id),
+                method_descriptor(tcx, *def_id, false),
+            )
+        })
+        .chain(unit.instances.iter().map(|instance| {
+            (
+                naming::instance_method_name(tcx, *instance),
+                mono::instance_descriptor(tcx, *instance),
+            )
+        }))
+        .collect();
+    saved_methods.is_some_and(|saved_methods| {
+        saved_methods.len() == methods.len()
+            && methods.iter().all(|(name, descriptor)| {
+                saved_methods.contains(&(name.as_str(), descriptor.as_str()))
+            })
+    })
+}
+
+/// Saves the classes lowered in this build as the work products of their codegen units, given
+/// the paths they were written to, and keeps the work products of the reused ones
+pub(crate) fn save_work_products(
+    sess: &Session,
+    work_products: Vec<(String, UnitWorkProduct)>,
+    class_paths: &FxHashMap<&str, &PathBuf>,
+) -> FxIndexMap<WorkProductId, WorkProduct> {
+    let mut saved = FxIndexMap::default();
+    for (class_name, work_product) in work_products {
+        match work_product {
+            UnitWorkProduct::Lowered(codegen_unit) => {
+                let class_path = class_paths[class_name.as_str()];
+                saved.extend(copy_cgu_workproduct_to_incr_comp_cache_dir(
+                    sess,
+                    &codegen_unit,
+                    &[(CLASS_FILE_KEY, class_path.as_path())],
+                    &[],
+                ));
+            }
+            UnitWorkProduct::Reused(work_product) => {
+                saved.insert(
+                    WorkProductId::from_cgu_name(&work_product.cgu_name),
+                    work_product,
+                );
+            }
+        }
+    }
+    saved
+}
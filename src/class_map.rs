@@ -0,0 +1,24 @@
+//! The map from the files of the classes handed to the linker to their entries in the JAR.
+
+use std::fmt::Write;
+use std::path::Path;
+
+/// The extension of the class map's file
+pub(crate) const CLASS_MAP_EXTENSION: &str = "classmap";
+
+/// The class map of the crate's classes, by internal class name, and the files they were written
+/// to
+pub(crate) fn class_map<'a>(
+    crate_name: &str,
+    class_files: impl IntoIterator<Item = (&'a str, &'a Path)>,
+) -> String {
+    let mut class_map = format!("# {crate_name}\n");
+    for (class_name, class_path) in class_files {
+        let file_name = class_path
+            .file_name()
+            .expect("class files have a file name")
+            .to_string_lossy();
+        let _ = writeln!(class_map, "{file_name}\t{class_name}.class");
+    }
+    class_map
+}
@@ -0,0 +1,138 @@
+//! The comparison operators `==`, `!=`, `<`, `<=`, `>` and `>=` on primitives.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_middle::mir::{BinOp, Operand};
+use rustc_middle::ty::{TyKind, UintTy};
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes the `boolean` result of a comparison operator. Returns `false` if the operands are
+    /// not primitives, in which case nothing was pushed.
+    pub(crate) fn lower_comparison(
+        &mut self,
+        bin_op: BinOp,
+        left: &Operand<'tcx>,
+        right: &Operand<'tcx>,
+    ) -> bool {
+        let ty = left.ty(self.body, self.tcx);
+        let descriptor = rust_ty_to_jvm_descriptor(ty, self.tcx);
+        let nan_is_greater = matches!(bin_op, BinOp::Lt | BinOp::Le);
+        let mask = match ty.kind() {
+            TyKind::Uint(UintTy::U8) => Some(0xFF),
+            TyKind::Uint(UintTy::U16) => Some(0xFFFF),
+            _ => None,
+        };
+        let unsigned = matches!(ty.kind(), TyKind::Uint(_));
+
+        match descriptor.as_str() {
+            "Z" | "B" | "C" | "S" | "I" | "J" | "F" | "D" | "Ljava/math/BigInteger;" => {}
+            _ => {
+                trace!("      Unsupported comparison of: {ty:?}");
+                return false;
+            }
+        }
+        self.load_compared(left, mask);
+        self.load_compared(right, mask);
+        // `lcmp`, `fcmpl` and the like push exactly -1, 0 or 1, the methods only the sign of it
+        let exact = match (descriptor.as_str(), unsigned) {
+            ("J", false) => {
+                self.method_bytecode_instructions.push(Instruction::Lcmp);
+                true
+            }
+            ("J", true) => {
+                self.invoke_static("java/lang/Long", "compareUnsigned", "(JJ)I");
+                false
+            }
+            ("F", _) => {
+                self.method_bytecode_instructions.push(if nan_is_greater {
+                    Instruction::Fcmpg
+                } else {
+                    Instruction::Fcmpl
+                });
+                true
+            }
+            ("D", _) => {
+                self.method_bytecode_instructions.push(if nan_is_greater {
+                    Instruction::Dcmpg
+                } else {
+                    Instruction::Dcmpl
+                });
+                true
+            }
+            ("Ljava/math/BigInteger;", _) => {
+                let big_integer = self
+                    .constant_pool
+                    .add_class("java/math/BigInteger")
+                    .expect("Could not add class to constant pool");
+                let compare_to = self
+                    .constant_pool
+                    .add_method_ref(big_integer, "compareTo", "(Ljava/math/BigInteger;)I")
+                    .expect("Could not add method ref to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokevirtual(compare_to));
+                false
+            }
+            ("I", true) if mask.is_none() => {
+                self.invoke_static("java/lang/Integer", "compareUnsigned", "(II)I");
+                false
+            }
+            _ => {
+                self.invoke_static("java/lang/Integer", "compare", "(II)I");
+                false
+            }
+        };
+        if !exact {
+            self.invoke_static("java/lang/Integer", "signum", "(I)I");
+        }
+        self.compare_result_to_boolean(bin_op);
+        trace!("      Generated bytecode: comparison {bin_op:?} of {ty:?}");
+        true
+    }
+
+    /// Pushes an operand of a comparison, masked to its bits if it is a `u8` or `u16`
+    fn load_compared(&mut self, operand: &Operand<'tcx>, mask: Option<i32>) {
+        self.load_operand(operand);
+        if let Some(mask) = mask {
+            let mask = self.int_constant(mask);
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Iand]);
+        }
+    }
+
+    /// Turns the -1, 0 or 1 on top of the stack into the `boolean` result of the operator: bit 0
+    /// is set for anything but 0, and bit 31 for -1 only
+    fn compare_result_to_boolean(&mut self, bin_op: BinOp) {
+        let instructions: &[Instruction] = match bin_op {
+            BinOp::Eq => &[
+                Instruction::Iconst_1,
+                Instruction::Iand,
+                Instruction::Iconst_1,
+                Instruction::Ixor,
+            ],
+            BinOp::Ne => &[Instruction::Iconst_1, Instruction::Iand],
+            BinOp::Lt => &[Instruction::Bipush(31), Instruction::Iushr],
+            BinOp::Ge => &[
+                Instruction::Bipush(31),
+                Instruction::Iushr,
+                Instruction::Iconst_1,
+                Instruction::Ixor,
+            ],
+            BinOp::Gt => &[
+                Instruction::Ineg,
+                Instruction::Bipush(31),
+                Instruction::Iushr,
+            ],
+            // `Le`
+            _ => &[
+                Instruction::Ineg,
+                Instruction::Bipush(31),
+                Instruction::Iushr,
+                Instruction::Iconst_1,
+                Instruction::Ixor,
+            ],
+        };
+        self.method_bytecode_instructions
+            .extend_from_slice(instructions);
+    }
+}
@@ -0,0 +1,194 @@
+//! The constant pool of a generated class, with every entry interned.
+
+use rustc_data_structures::fx::FxHashMap;
+use std::ops::Deref;
+
+/// The identity of a constant pool entry, floating point values by their bits so that `NaN`s
+/// are interned too
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Utf8(String),
+    Integer(i32),
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    Class(u16),
+    String(u16),
+    NameAndType(u16, u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+}
+
+/// A constant pool adding every distinct entry once
+#[derive(Default)]
+pub(crate) struct ConstantPool {
+    pool: ristretto_classfile::ConstantPool,
+    indices: FxHashMap<Key, u16>,
+}
+
+impl ConstantPool {
+    /// The pool to build the class file with
+    pub(crate) fn into_inner(self) -> ristretto_classfile::ConstantPool {
+        self.pool
+    }
+
+    /// The index of the reference to a method of a class, if it was added
+    pub(crate) fn method_ref_index(
+        &self,
+        class_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<u16> {
+        let name_index = self.indices.get(&Key::Utf8(name.to_string()))?;
+        let descriptor_index = self.indices.get(&Key::Utf8(descriptor.to_string()))?;
+        let name_and_type_index = self
+            .indices
+            .get(&Key::NameAndType(*name_index, *descriptor_index))?;
+        self.indices
+            .get(&Key::MethodRef(class_index, *name_and_type_index))
+            .copied()
+    }
+
+    /// The index of the entry with the given identity, adding it with `add` the first time
+    fn intern(
+        &mut self,
+        key: Key,
+        add: impl FnOnce(&mut ristretto_classfile::ConstantPool) -> ristretto_classfile::Result<u16>,
+    ) -> ristretto_classfile::Result<u16> {
+        if let Some(index) = self.indices.get(&key) {
+            return Ok(*index);
+        }
+        let index = add(&mut self.pool)?;
+        self.indices.insert(key, index);
+        Ok(index)
+    }
+
+    pub(crate) fn add_utf8(&mut self, value: impl AsRef<str>) -> ristretto_classfile::Result<u16> {
+        let value = value.as_ref();
+        self.intern(Key::Utf8(value.to_string()), |pool| pool.add_utf8(value))
+    }
+
+    pub(crate) fn add_integer(&mut self, value: i32) -> ristretto_classfile::Result<u16> {
+        self.intern(Key::Integer(value), |pool| pool.add_integer(value))
+    }
+
+    pub(crate) fn add_float(&mut self, value: f32) -> ristretto_classfile::Result<u16> {
+        self.intern(Key::Float(value.to_bits()), |pool| pool.add_float(value))
+    }
+
+    pub(crate) fn add_long(&mut self, value: i64) -> ristretto_classfile::Result<u16> {
+        self.intern(Key::Long(value), |pool| pool.add_long(value))
+    }
+
+    pub(crate) fn add_double(&mut self, value: f64) -> ristretto_classfile::Result<u16> {
+        self.intern(Key::Double(value.to_bits()), |pool| pool.add_double(value))
+    }
+
+    pub(crate) fn add_class(&mut self, name: impl AsRef<str>) -> ristretto_classfile::Result<u16> {
+        let name_index = self.add_utf8(name)?;
+        self.intern(Key::Class(name_index), |pool| {
+            pool.add(ristretto_classfile::Constant::Class(name_index))
+        })
+    }
+
+    pub(crate) fn add_string(
+        &mut self,
+        value: impl AsRef<str>,
+    ) -> ristretto_classfile::Result<u16> {
+        let utf8_index = self.add_utf8(value)?;
+        self.intern(Key::String(utf8_index), |pool| {
+            pool.add(ristretto_classfile::Constant::String(utf8_index))
+        })
+    }
+
+    pub(crate) fn add_name_and_type(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+    ) -> ristretto_classfile::Result<u16> {
+        let name_index = self.add_utf8(name)?;
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.intern(Key::NameAndType(name_index, descriptor_index), |pool| {
+            pool.add(ristretto_classfile::Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            })
+        })
+    }
+
+    pub(crate) fn add_field_ref(
+        &mut self,
+        class_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> ristretto_classfile::Result<u16> {
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.intern(Key::FieldRef(class_index, name_and_type_index), |pool| {
+            pool.add(ristretto_classfile::Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            })
+        })
+    }
+
+    pub(crate) fn add_method_ref(
+        &mut self,
+        class_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> ristretto_classfile::Result<u16> {
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.intern(Key::MethodRef(class_index, name_and_type_index), |pool| {
+            pool.add(ristretto_classfile::Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            })
+        })
+    }
+
+    pub(crate) fn add_interface_method_ref(
+        &mut self,
+        class_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> ristretto_classfile::Result<u16> {
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.intern(
+            Key::InterfaceMethodRef(class_index, name_and_type_index),
+            |pool| {
+                pool.add(ristretto_classfile::Constant::InterfaceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                })
+            },
+        )
+    }
+}
+
+impl Deref for ConstantPool {
+    type Target = ristretto_classfile::ConstantPool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// The string of a `Utf8` entry of a read class's constant pool
+pub(crate) fn utf8(constant_pool: &ristretto_classfile::ConstantPool, index: u16) -> Option<&str> {
+    match constant_pool.get(index) {
+        Some(ristretto_classfile::Constant::Utf8(value)) => Some(value.as_str()),
+        _ => None,
+    }
+}
+
+/// The internal name of a `Class` entry of a read class's constant pool
+pub(crate) fn class_name(
+    constant_pool: &ristretto_classfile::ConstantPool,
+    index: u16,
+) -> Option<&str> {
+    match constant_pool.get(index) {
+        Some(ristretto_classfile::Constant::Class(name_index)) => utf8(constant_pool, *name_index),
+        _ => None,
+    }
+}
@@ -0,0 +1,247 @@
+//! Branch bookkeeping and `StackMapTable` generation for lowered methods.
+
+use crate::constant_pool::ConstantPool;
+use crate::store_instruction;
+use ristretto_classfile::attributes::{Instruction, StackFrame, VerificationType};
+
+/// The index of the instruction a branch jumps to, if the instruction is a branch
+pub(crate) fn branch_target(instruction: &Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Ifeq(target)
+        | Instruction::Ifne(target)
+        | Instruction::Iflt(target)
+        | Instruction::Ifge(target)
+        | Instruction::Ifgt(target)
+        | Instruction::Ifle(target)
+        | Instruction::If_icmpeq(target)
+        | Instruction::If_icmpne(target)
+        | Instruction::If_icmplt(target)
+        | Instruction::If_icmpge(target)
+        | Instruction::If_icmpgt(target)
+        | Instruction::If_icmple(target)
+        | Instruction::If_acmpeq(target)
+        | Instruction::If_acmpne(target)
+        | Instruction::Ifnull(target)
+        | Instruction::Ifnonnull(target)
+        | Instruction::Goto(target) => Some(*target),
+        _ => None,
+    }
+}
+
+/// Whether execution continues with the next instruction after this one, when it does not branch
+pub(crate) fn falls_through(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::Goto(_)
+            | Instruction::Athrow
+            | Instruction::Return
+            | Instruction::Ireturn
+            | Instruction::Lreturn
+            | Instruction::Freturn
+            | Instruction::Dreturn
+            | Instruction::Areturn
+    )
+}
+
+/// The same branch instruction, jumping to `target` instead
+pub(crate) fn with_branch_target(instruction: &Instruction, target: u16) -> Instruction {
+    match instruction {
+        Instruction::Ifeq(_) => Instruction::Ifeq(target),
+        Instruction::Ifne(_) => Instruction::Ifne(target),
+        Instruction::Iflt(_) => Instruction::Iflt(target),
+        Instruction::Ifge(_) => Instruction::Ifge(target),
+        Instruction::Ifgt(_) => Instruction::Ifgt(target),
+        Instruction::Ifle(_) => Instruction::Ifle(target),
+        Instruction::If_icmpeq(_) => Instruction::If_icmpeq(target),
+        Instruction::If_icmpne(_) => Instruction::If_icmpne(target),
+        Instruction::If_icmplt(_) => Instruction::If_icmplt(target),
+        Instruction::If_icmpge(_) => Instruction::If_icmpge(target),
+        Instruction::If_icmpgt(_) => Instruction::If_icmpgt(target),
+        Instruction::If_icmple(_) => Instruction::If_icmple(target),
+        Instruction::If_acmpeq(_) => Instruction::If_acmpeq(target),
+        Instruction::If_acmpne(_) => Instruction::If_acmpne(target),
+        Instruction::Ifnull(_) => Instruction::Ifnull(target),
+        Instruction::Ifnonnull(_) => Instruction::Ifnonnull(target),
+        Instruction::Goto(_) => Instruction::Goto(target),
+        other => other.clone(),
+    }
+}
+
+/// Size in bytes of an instruction starting at byte `offset` (switches are padded to 4 bytes)
+fn instruction_size(instruction: &Instruction, offset: u32) -> u32 {
+    match instruction {
+        Instruction::Bipush(_)
+        | Instruction::Ldc(_)
+        | Instruction::Iload(_)
+        | Instruction::Lload(_)
+        | Instruction::Fload(_)
+        | Instruction::Dload(_)
+        | Instruction::Aload(_)
+        | Instruction::Istore(_)
+        | Instruction::Lstore(_)
+        | Instruction::Fstore(_)
+        | Instruction::Dstore(_)
+        | Instruction::Astore(_)
+        | Instruction::Ret(_)
+        | Instruction::Newarray(_) => 2,
+        Instruction::Sipush(_)
+        | Instruction::Ldc_w(_)
+        | Instruction::Ldc2_w(_)
+        | Instruction::Iinc(..)
+        | Instruction::Getstatic(_)
+        | Instruction::Putstatic(_)
+        | Instruction::Getfield(_)
+        | Instruction::Putfield(_)
+        | Instruction::Invokevirtual(_)
+        | Instruction::Invokespecial(_)
+        | Instruction::Invokestatic(_)
+        | Instruction::New(_)
+        | Instruction::Anewarray(_)
+        | Instruction::Checkcast(_)
+        | Instruction::Instanceof(_)
+        | Instruction::Jsr(_) => 3,
+        Instruction::Iload_w(_)
+        | Instruction::Lload_w(_)
+        | Instruction::Fload_w(_)
+        | Instruction::Dload_w(_)
+        | Instruction::Aload_w(_)
+        | Instruction::Istore_w(_)
+        | Instruction::Lstore_w(_)
+        | Instruction::Fstore_w(_)
+        | Instruction::Dstore_w(_)
+        | Instruction::Astore_w(_)
+        | Instruction::Ret_w(_)
+        | Instruction::Multianewarray(..) => 4,
+        Instruction::Invokeinterface(..)
+        | Instruction::Invokedynamic(_)
+        | Instruction::Goto_w(_)
+        | Instruction::Jsr_w(_) => 5,
+        Instruction::Iinc_w(..) => 6,
+        Instruction::Tableswitch(table_switch) => {
+            1 + (3 - offset % 4) + 12 + 4 * table_switch.offsets.len() as u32
+        }
+        Instruction::Lookupswitch(lookup_switch) => {
+            1 + (3 - offset % 4) + 8 + 8 * lookup_switch.pairs.len() as u32
+        }
+        _ if branch_target(instruction).is_some() => 3,
+        _ => 1,
+    }
+}
+
+/// Byte offset of every instruction in the method
+fn byte_offsets(instructions: &[Instruction]) -> Vec<u32> {
+    let mut offsets = Vec::with_capacity(instructions.len());
+    let mut offset = 0;
+    for instruction in instructions {
+        offsets.push(offset);
+        offset += instruction_size(instruction, offset);
+    }
+    offsets
+}
+
+/// The verification type of a value with the given field descriptor
+pub(crate) fn verification_type(
+    descriptor: &str,
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<VerificationType> {
+    Ok(match descriptor.as_bytes().first() {
+        Some(b'J') => VerificationType::Long,
+        Some(b'F') => VerificationType::Float,
+        Some(b'D') => VerificationType::Double,
+        Some(b'L') => VerificationType::Object {
+            cpool_index: constant_pool.add_class(&descriptor[1..descriptor.len() - 1])?,
+        },
+        Some(b'[') => VerificationType::Object {
+            cpool_index: constant_pool.add_class(descriptor)?,
+        },
+        _ => VerificationType::Integer,
+    })
+}
+
+/// The instruction sequence storing the default value of its type into every given slot, used
+/// to give every local its declared type before the first branch
+pub(crate) fn initialize_locals<'a>(
+    slots: impl Iterator<Item = (u16, &'a str)>,
+) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    for (slot, descriptor) in slots {
+        if let Some(store) = store_instruction(descriptor, slot) {
+            instructions.push(default_value(descriptor));
+            instructions.push(store);
+        }
+    }
+    instructions
+}
+
+/// The instruction pushing the default value (zero or `null`) of the given field descriptor
+pub(crate) fn default_value(descriptor: &str) -> Instruction {
+    match descriptor.as_bytes().first() {
+        Some(b'J') => Instruction::Lconst_0,
+        Some(b'F') => Instruction::Fconst_0,
+        Some(b'D') => Instruction::Dconst_0,
+        Some(b'L' | b'[') => Instruction::Aconst_null,
+        _ => Instruction::Iconst_0,
+    }
+}
+
+/// Builds the `StackMapTable` frames for the given instruction indices, each with the method's
+/// locals and its own operand stack
+pub(crate) fn stack_map_frames(
+    instructions: &[Instruction],
+    locals: &[VerificationType],
+    mut frames: Vec<(usize, Vec<VerificationType>)>,
+) -> Vec<StackFrame> {
+    let offsets = byte_offsets(instructions);
+    frames.retain(|(index, _)| *index > 0 && *index < instructions.len());
+    frames.sort_by_key(|(index, _)| *index);
+    frames.dedup_by_key(|(index, _)| *index);
+
+    let mut stack_frames = Vec::with_capacity(frames.len());
+    let mut previous_offset = None;
+    for (index, stack) in frames {
+        let offset = offsets[index];
+        let offset_delta = match previous_offset {
+            Some(previous_offset) => offset - previous_offset - 1,
+            None => offset,
+        };
+        previous_offset = Some(offset);
+        stack_frames.push(StackFrame::FullFrame {
+            frame_type: 255,
+            offset_delta: offset_delta as u16,
+            locals: locals.to_vec(),
+            stack,
+        });
+    }
+    stack_frames
+}
+
+/// The inverse of [`stack_map_frames`]: the locals the frames share, and the instruction index and
+/// stack of every frame, `None` if a frame is not a full frame
+pub(crate) fn frame_indices(
+    instructions: &[Instruction],
+    stack_frames: &[StackFrame],
+) -> Option<(Vec<VerificationType>, Vec<(usize, Vec<VerificationType>)>)> {
+    let offsets = byte_offsets(instructions);
+    let mut shared_locals = Vec::new();
+    let mut frames = Vec::with_capacity(stack_frames.len());
+    let mut previous_offset = None;
+    for stack_frame in stack_frames {
+        let StackFrame::FullFrame {
+            offset_delta,
+            locals,
+            stack,
+            ..
+        } = stack_frame
+        else {
+            return None;
+        };
+        let offset = match previous_offset {
+            Some(previous_offset) => previous_offset + u32::from(*offset_delta) + 1,
+            None => u32::from(*offset_delta),
+        };
+        previous_offset = Some(offset);
+        frames.push((offsets.binary_search(&offset).ok()?, stack.clone()));
+        shared_locals.clone_from(locals);
+    }
+    Some((shared_locals, frames))
+}
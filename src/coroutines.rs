@@ -0,0 +1,571 @@
+//! Coroutines, and the `async fn`s and `async` blocks built on them, as state machine classes.
+
+use crate::constant_pool::ConstantPool;
+use crate::mono::instance_descriptor;
+use crate::runtime::{
+    COROUTINE_STATE_COMPLETE, COROUTINE_STATE_CONSTRUCTOR, COROUTINE_STATE_VALUE, RuntimeClass,
+};
+use crate::trace::trace;
+use crate::{
+    MirToBytecodeVisitor, control_flow, create_method, load_instruction, naming, options,
+    return_instruction, rust_ty_to_jvm_descriptor,
+};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{
+    ClassAccessFlags, ClassFile, Field, FieldAccessFlags, FieldType, MethodAccessFlags,
+};
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_data_structures::fx::FxHashSet;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_hir::{CoroutineDesugaring, CoroutineKind, CoroutineSource, LangItem};
+use rustc_index::IndexVec;
+use rustc_middle::mir::{Local, Operand, Place, PlaceElem, ProjectionElem};
+use rustc_middle::ty::{
+    EarlyBinder, GenericArgsRef, Instance, InstanceKind, Ty, TyCtxt, TyKind, TypingEnv,
+};
+use rustc_span::source_map::Spanned;
+
+/// The field of a coroutine's class holding the variant of its state machine
+const STATE_FIELD: &str = "state";
+
+/// Whether `def_id` is a coroutine, `async fn` body or `async` block of the crate. `gen` blocks
+/// and `async` closures are not supported yet.
+pub(crate) fn is_lowered_coroutine(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    def_id.is_local()
+        && tcx.is_coroutine(def_id)
+        && matches!(
+            tcx.coroutine_kind(def_id),
+            Some(
+                CoroutineKind::Coroutine(_)
+                    | CoroutineKind::Desugared(CoroutineDesugaring::Async, CoroutineSource::Fn)
+                    | CoroutineKind::Desugared(CoroutineDesugaring::Async, CoroutineSource::Block)
+            )
+        )
+}
+
+/// Whether `ty` is a `CoroutineState`, held as a `rustjvm/CoroutineState`
+pub(crate) fn is_coroutine_state<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    let TyKind::Adt(adt_def, _) = ty.kind() else {
+        return false;
+    };
+    tcx.is_lang_item(adt_def.did(), LangItem::CoroutineState)
+}
+
+/// The `T` of a `Poll<T>`, which is held as just the `T`
+pub(crate) fn poll_output<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    tcx.is_lang_item(adt_def.did(), LangItem::Poll)
+        .then(|| generic_args.type_at(0))
+}
+
+/// The pointer in a `Pin<P>`, which is held as just the pointer
+pub(crate) fn pinned_pointer<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    tcx.is_lang_item(adt_def.did(), LangItem::Pin)
+        .then(|| generic_args.type_at(0))
+}
+
+/// The name of the field of a captured variable of a coroutine
+/// The name of the class of a coroutine, in the crate's package
+pub(crate) fn class_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    naming::in_crate_package(tcx, LOCAL_CRATE, &naming::coroutine_class_name(tcx, def_id))
+}
+
+fn upvar_field_name(index: usize) -> String {
+    format!("upvar{index}")
+}
+
+/// The name of the field of a local a coroutine keeps across suspension points
+fn saved_local_field_name(index: usize) -> String {
+    format!("local{index}")
+}
+
+/// The types of the locals a coroutine keeps across suspension points, by their index in its
+/// layout
+fn saved_local_tys<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    generic_args: GenericArgsRef<'tcx>,
+) -> Vec<Ty<'tcx>> {
+    let Some(layout) = tcx.mir_coroutine_witnesses(def_id) else {
+        return Vec::new();
+    };
+    layout
+        .field_tys
+        .iter()
+        .map(|saved_local| {
+            tcx.normalize_erasing_regions(
+                TypingEnv::fully_monomorphized(),
+                EarlyBinder::bind(saved_local.ty).instantiate(tcx, generic_args),
+            )
+        })
+        .collect()
+}
+
+/// The descriptor of the constructor of a coroutine's class, taking its captured variables
+fn constructor_descriptor<'tcx>(tcx: TyCtxt<'tcx>, generic_args: GenericArgsRef<'tcx>) -> String {
+    let mut descriptor = String::from("(");
+    for upvar_ty in generic_args.as_coroutine().upvar_tys() {
+        descriptor.push_str(&rust_ty_to_jvm_descriptor(upvar_ty, tcx));
+    }
+    descriptor.push_str(")V");
+    descriptor
+}
+
+/// Generates the classes of the coroutines whose `resume` or `poll` is among the given instances
+pub(crate) fn generate_coroutine_classes<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instances: &[Instance<'tcx>],
+) -> ristretto_classfile::Result<Vec<(String, Vec<u8>)>> {
+    let mut generated = FxHashSet::default();
+    let mut classes = Vec::new();
+    for instance in instances {
+        let InstanceKind::Item(def_id) = instance.def else {
+            continue;
+        };
+        if is_lowered_coroutine(tcx, def_id) && generated.insert(def_id) {
+            classes.push(generate_coroutine_class(tcx, *instance)?);
+        }
+    }
+    Ok(classes)
+}
+
+/// `public final class f$coroutine0 { public int state; public U upvar0; ...; public L local0;
+/// ...; public S resume(R argument) }`, for the instance resuming the coroutine
+fn generate_coroutine_class<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    resume: Instance<'tcx>,
+) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let (def_id, generic_args) = (resume.def_id(), resume.args);
+    let class_name = class_name(tcx, def_id);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let this_class = constant_pool.add_class(&class_name)?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+
+    let mut fields = Vec::new();
+    let mut add_field = |constant_pool: &mut ConstantPool, name: &str, descriptor: &str| {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PUBLIC,
+            name_index: constant_pool.add_utf8(name)?,
+            descriptor_index: constant_pool.add_utf8(descriptor)?,
+            field_type: FieldType::parse(descriptor)?,
+            attributes: Vec::new(),
+        });
+        Ok::<_, ristretto_classfile::Error>(())
+    };
+    add_field(&mut constant_pool, STATE_FIELD, "I")?;
+
+    // The constructor stores the captured variables, the state starts out as `Unresumed`
+    let mut constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+    ];
+    let mut next_slot: u16 = 1; // Slot 0 holds `this`
+    for (index, upvar_ty) in generic_args.as_coroutine().upvar_tys().iter().enumerate() {
+        let descriptor = rust_ty_to_jvm_descriptor(upvar_ty, tcx);
+        if descriptor == "V" {
+            continue;
+        }
+        let name = upvar_field_name(index);
+        add_field(&mut constant_pool, &name, &descriptor)?;
+        let field_ref = constant_pool.add_field_ref(this_class, &name, &descriptor)?;
+        constructor.push(Instruction::Aload_0);
+        constructor.extend(load_instruction(&descriptor, next_slot));
+        constructor.push(Instruction::Putfield(field_ref));
+        next_slot += if matches!(descriptor.as_str(), "J" | "D") {
+            2
+        } else {
+            1
+        };
+    }
+    constructor.push(Instruction::Return);
+    for (index, saved_local_ty) in saved_local_tys(tcx, def_id, generic_args)
+        .into_iter()
+        .enumerate()
+    {
+        let descriptor = rust_ty_to_jvm_descriptor(saved_local_ty, tcx);
+        if descriptor != "V" {
+            add_field(
+                &mut constant_pool,
+                &saved_local_field_name(index),
+                &descriptor,
+            )?;
+        }
+    }
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        &constructor_descriptor(tcx, generic_args),
+        &constructor.into(),
+    )?];
+
+    // resume(argument): the static method of the crate class, with the coroutine as `this`
+    let static_descriptor = instance_descriptor(tcx, resume);
+    let (parameters, return_descriptor) = static_descriptor[1..]
+        .strip_prefix(&format!("L{class_name};"))
+        .and_then(|rest| rest.split_once(')'))
+        .expect("coroutines are resumed through a pinned reference to them");
+    let crate_class = constant_pool.add_class(naming::crate_class_name(tcx))?;
+    let static_resume = constant_pool.add_method_ref(
+        crate_class,
+        &naming::instance_method_name(tcx, resume),
+        &static_descriptor,
+    )?;
+    let mut resume_code = vec![Instruction::Aload_0];
+    resume_code.extend(load_instruction(parameters, 1));
+    resume_code.push(Instruction::Invokestatic(static_resume));
+    resume_code.push(return_instruction(return_descriptor));
+    methods.push(create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        naming::coroutine_method_name(tcx, def_id),
+        &format!("({parameters}){return_descriptor}"),
+        &resume_code.into(),
+    )?);
+
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::FINAL | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        fields,
+        methods,
+        ..Default::default()
+    };
+    class_file.verify()?;
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((class_name, bytes))
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Whether a local holds a `Poll`, held as its `Ready` value
+    pub(crate) fn is_ready_poll(&self, local: Local) -> bool {
+        poll_output(self.body.local_decls[local].ty, self.tcx).is_some()
+    }
+
+    /// Whether a local holds a `CoroutineState`
+    pub(crate) fn is_coroutine_state_local(&self, local: Local) -> bool {
+        is_coroutine_state(self.body.local_decls[local].ty, self.tcx)
+    }
+
+    /// Pushes a new `CoroutineState` of the given variant, holding its value boxed
+    pub(crate) fn load_coroutine_state_aggregate(
+        &mut self,
+        variant_index: VariantIdx,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let class = RuntimeClass::CoroutineState;
+        self.runtime_classes.insert(class);
+        let class = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class, "<init>", COROUTINE_STATE_CONSTRUCTOR)
+            .expect("Could not add constructor to constant pool");
+        let complete = self.int_constant(variant_index.as_u32() as i32);
+        self.method_bytecode_instructions.extend([
+            Instruction::New(class),
+            Instruction::Dup,
+            complete,
+        ]);
+        let value = operands.iter().next();
+        let descriptor = value
+            .map(|operand| rust_ty_to_jvm_descriptor(operand.ty(self.body, self.tcx), self.tcx));
+        match (value, descriptor.as_deref()) {
+            (Some(operand), Some(descriptor)) if descriptor != "V" => {
+                self.load_operand(operand);
+                self.box_value(descriptor);
+            }
+            _ => self
+                .method_bytecode_instructions
+                .push(Instruction::Aconst_null),
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+    }
+
+    /// Pushes the discriminant of the `CoroutineState` in `local`
+    pub(crate) fn load_coroutine_state_discriminant(&mut self, local: Local) {
+        let field = self.coroutine_state_field(COROUTINE_STATE_COMPLETE, "Z");
+        self.load_local(local);
+        self.method_bytecode_instructions
+            .push(Instruction::Getfield(field));
+    }
+
+    /// Pushes the value the `CoroutineState` in `local` yielded or returned, unboxed
+    pub(crate) fn load_coroutine_state_value(&mut self, local: Local, value_ty: Ty<'tcx>) {
+        let descriptor = rust_ty_to_jvm_descriptor(value_ty, self.tcx);
+        if descriptor == "V" {
+            return;
+        }
+        let field = self.coroutine_state_field(COROUTINE_STATE_VALUE, "Ljava/lang/Object;");
+        self.load_local(local);
+        self.method_bytecode_instructions
+            .push(Instruction::Getfield(field));
+        self.cast_from_object(&descriptor);
+    }
+
+    fn coroutine_state_field(&mut self, name: &str, descriptor: &str) -> u16 {
+        let class = RuntimeClass::CoroutineState;
+        self.runtime_classes.insert(class);
+        let class = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        self.constant_pool
+            .add_field_ref(class, name, descriptor)
+            .expect("Could not add field ref to constant pool")
+    }
+
+    /// Whether a local holds a `Pin`, held as the pointer it pins
+    pub(crate) fn is_pin_local(&self, local: Local) -> bool {
+        pinned_pointer(self.body.local_decls[local].ty, self.tcx).is_some()
+    }
+
+    /// The coroutine a place dereferences, directly or through a `Pin`, and the projections
+    /// into it, as in `(*(_1.0)).0`
+    fn coroutine_projection<'p>(
+        &self,
+        place: &'p Place<'tcx>,
+    ) -> Option<(Ty<'tcx>, &'p [PlaceElem<'tcx>])> {
+        let mut pointer_ty = self.body.local_decls[place.local].ty;
+        let mut projection = &place.projection[..];
+        if let Some(pinned_ty) = pinned_pointer(pointer_ty, self.tcx)
+            && let [ProjectionElem::Field(..), rest @ ..] = projection
+        {
+            pointer_ty = pinned_ty;
+            projection = rest;
+        }
+        let [ProjectionElem::Deref, rest @ ..] = projection else {
+            return None;
+        };
+        let coroutine_ty = pointer_ty.builtin_deref(true)?;
+        matches!(coroutine_ty.kind(), TyKind::Coroutine(..)).then_some((coroutine_ty, rest))
+    }
+
+    /// Whether a place is a whole coroutine, which is the object its local holds
+    pub(crate) fn is_coroutine_object(&self, place: &Place<'tcx>) -> bool {
+        self.coroutine_projection(place)
+            .is_some_and(|(_, projection)| projection.is_empty())
+    }
+
+    /// Resolves a place in a coroutine to the field of its class holding it: a captured variable
+    /// or a local kept across suspension points
+    pub(crate) fn coroutine_field_ref(&mut self, place: &Place<'tcx>) -> Option<u16> {
+        let (coroutine_ty, projection) = self.coroutine_projection(place)?;
+        let TyKind::Coroutine(def_id, _) = *coroutine_ty.kind() else {
+            return None;
+        };
+        let (name, field_ty) = match projection {
+            [ProjectionElem::Field(field, field_ty)] => {
+                (upvar_field_name(field.as_usize()), *field_ty)
+            }
+            [
+                ProjectionElem::Downcast(_, variant_index),
+                ProjectionElem::Field(field, field_ty),
+            ] => {
+                let layout = self.tcx.mir_coroutine_witnesses(def_id)?;
+                let saved_local = layout.variant_fields[*variant_index][*field];
+                (saved_local_field_name(saved_local.as_usize()), *field_ty)
+            }
+            _ => return None,
+        };
+        let descriptor = rust_ty_to_jvm_descriptor(field_ty, self.tcx);
+        if descriptor == "V" {
+            return None;
+        }
+        let class = self
+            .constant_pool
+            .add_class(class_name(self.tcx, def_id))
+            .expect("Could not add class to constant pool");
+        Some(
+            self.constant_pool
+                .add_field_ref(class, &name, &descriptor)
+                .expect("Could not add field ref to constant pool"),
+        )
+    }
+
+    /// The `state` field of the class of the coroutine a place is
+    fn coroutine_state_ref(&mut self, place: &Place<'tcx>) -> u16 {
+        let (coroutine_ty, _) = self
+            .coroutine_projection(place)
+            .expect("the place is a coroutine");
+        let TyKind::Coroutine(def_id, _) = *coroutine_ty.kind() else {
+            unreachable!("coroutine projections end at coroutines");
+        };
+        let class = self
+            .constant_pool
+            .add_class(class_name(self.tcx, def_id))
+            .expect("Could not add class to constant pool");
+        self.constant_pool
+            .add_field_ref(class, STATE_FIELD, "I")
+            .expect("Could not add field ref to constant pool")
+    }
+
+    /// Pushes the variant of the state machine of a coroutine
+    pub(crate) fn load_coroutine_state(&mut self, place: &Place<'tcx>) {
+        let state = self.coroutine_state_ref(place);
+        self.load_local(place.local);
+        self.method_bytecode_instructions
+            .push(Instruction::Getfield(state));
+    }
+
+    /// Sets the variant of the state machine of a coroutine, which is its discriminant
+    pub(crate) fn store_coroutine_state(&mut self, place: &Place<'tcx>, variant_index: VariantIdx) {
+        let state = self.coroutine_state_ref(place);
+        self.load_local(place.local);
+        let variant = self.int_constant(variant_index.as_u32() as i32);
+        self.method_bytecode_instructions
+            .extend([variant, Instruction::Putfield(state)]);
+    }
+
+    /// Pushes a new coroutine capturing the given operands
+    pub(crate) fn load_coroutine_aggregate(
+        &mut self,
+        def_id: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let class = self
+            .constant_pool
+            .add_class(class_name(self.tcx, def_id))
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(
+                class,
+                "<init>",
+                &constructor_descriptor(self.tcx, generic_args),
+            )
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions
+            .extend([Instruction::New(class), Instruction::Dup]);
+        for operand in operands {
+            self.load_operand(operand);
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+    }
+
+    /// Pushes a `Poll`: the value for `Ready`. `Pending` is only returned after an `.await` of a
+    /// future that was not ready, which never happens, so it is a default value.
+    pub(crate) fn load_poll_aggregate(
+        &mut self,
+        poll_ty: Ty<'tcx>,
+        variant_index: VariantIdx,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let is_ready = poll_ty.ty_adt_def().is_some_and(|adt_def| {
+            self.tcx
+                .is_lang_item(adt_def.variant(variant_index).def_id, LangItem::PollReady)
+        });
+        match operands.iter().next() {
+            Some(operand) if is_ready => self.load_operand(operand),
+            _ => {
+                let output_ty = poll_output(poll_ty, self.tcx).expect("the type is a `Poll`");
+                let descriptor = rust_ty_to_jvm_descriptor(output_ty, self.tcx);
+                if descriptor != "V" {
+                    self.method_bytecode_instructions
+                        .push(control_flow::default_value(&descriptor));
+                }
+            }
+        }
+    }
+
+    /// Lowers `block_on(future)` of a future of the crate, and the calls `.await` and pinning
+    /// make that only pass their argument on, returning `false` for any other call
+    pub(crate) fn lower_coroutine_call(
+        &mut self,
+        callee: DefId,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let is_coroutine = |operand: &Operand<'tcx>| {
+            matches!(operand.ty(self.body, tcx).kind(), TyKind::Coroutine(..))
+        };
+        let passes_on = (tcx.is_lang_item(callee, LangItem::IntoFutureIntoFuture)
+            && is_coroutine(&args[0].node))
+            || tcx.is_lang_item(callee, LangItem::GetContext)
+            || self.is_pin_method(callee);
+        if passes_on {
+            self.load_operand(&args[0].node);
+            self.store_call_result(destination);
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            return true;
+        }
+
+        let Some(future) = args.last() else {
+            return false;
+        };
+        let TyKind::Coroutine(def_id, generic_args) = *future.node.ty(self.body, tcx).kind() else {
+            return false;
+        };
+        if tcx
+            .opt_item_name(callee)
+            .is_none_or(|name| name.as_str() != "block_on")
+            || !is_lowered_coroutine(tcx, def_id)
+            || !tcx.coroutine_is_async(def_id)
+        {
+            return false;
+        }
+        // Crate.f$async0$poll(future, null)
+        let poll = Instance {
+            def: InstanceKind::Item(def_id),
+            args: generic_args,
+        };
+        let crate_class = self
+            .constant_pool
+            .add_class(naming::crate_class_name(tcx))
+            .expect("Could not add class to constant pool");
+        let poll_method = self
+            .constant_pool
+            .add_method_ref(
+                crate_class,
+                &naming::instance_method_name(tcx, poll),
+                &instance_descriptor(tcx, poll),
+            )
+            .expect("Could not add method ref to constant pool");
+        self.load_operand(&future.node);
+        self.method_bytecode_instructions.extend([
+            Instruction::Aconst_null,
+            Instruction::Invokestatic(poll_method),
+        ]);
+        trace!(
+            "      Generated bytecode: {} of {def_id:?}",
+            tcx.def_path_str(callee)
+        );
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Whether a function is an inherent method of `Pin` that returns the pointer it is given,
+    /// pinned or not
+    fn is_pin_method(&self, callee: DefId) -> bool {
+        let tcx = self.tcx;
+        tcx.impl_of_method(callee).is_some_and(|impl_def_id| {
+            tcx.impl_trait_ref(impl_def_id).is_none()
+                && pinned_pointer(tcx.type_of(impl_def_id).instantiate_identity(), tcx).is_some()
+                && matches!(
+                    tcx.item_name(callee).as_str(),
+                    "new"
+                        | "new_unchecked"
+                        | "as_mut"
+                        | "get_mut"
+                        | "get_unchecked_mut"
+                        | "into_inner"
+                        | "into_inner_unchecked"
+                )
+        })
+    }
+}
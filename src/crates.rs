@@ -0,0 +1,22 @@
+//! The list of the crates an executable links, which `java-linker` takes their classes from.
+
+use rustc_codegen_ssa::CrateInfo;
+use std::fmt::Write;
+
+/// The extension of the file listing the crates
+pub(crate) const CRATES_EXTENSION: &str = "crates";
+
+/// The list of the rlibs of the crates the executable uses, in the order rustc links them
+pub(crate) fn crate_list(crate_info: &CrateInfo) -> String {
+    let mut list = String::new();
+    for cnum in &crate_info.used_crates {
+        let Some(source) = crate_info.used_crate_source.get(cnum) else {
+            continue;
+        };
+        // Crates only built as dylibs have no rlib
+        if let Some((rlib, _)) = &source.rlib {
+            let _ = writeln!(list, "{}", rlib.display());
+        }
+    }
+    list
+}
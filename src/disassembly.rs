@@ -0,0 +1,38 @@
+//! `--emit=asm` as a textual listing of the generated classes.
+
+use ristretto_classfile::ClassFile;
+use rustc_session::Session;
+use rustc_session::config::{OutFileName, OutputFilenames, OutputType};
+use std::fmt::Write;
+use std::io::Cursor;
+
+/// Writes the listing of `classes` to the `--emit=asm` output, the standard output for `-`
+pub(crate) fn emit_listing(
+    sess: &Session,
+    classes: &[(String, Vec<u8>)],
+    outputs: &OutputFilenames,
+) {
+    let mut listing = String::new();
+    for (class_name, bytecode) in classes {
+        match ClassFile::from_bytes(&mut Cursor::new(bytecode.clone())) {
+            Ok(class_file) => {
+                let _ = writeln!(listing, "// class {class_name}\n{class_file}\n");
+            }
+            Err(err) => {
+                let _ = writeln!(
+                    listing,
+                    "// class {class_name} could not be read: {err:?}\n"
+                );
+            }
+        }
+    }
+    match outputs.path(OutputType::Assembly) {
+        OutFileName::Stdout => print!("{listing}"),
+        OutFileName::Real(path) => {
+            if let Err(err) = std::fs::write(&path, listing) {
+                sess.dcx()
+                    .err(format!("could not write `{}`: {err}", path.display()));
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! The JVM entry point of a binary crate.
+
+use crate::constant_pool::ConstantPool;
+use crate::runtime::{RuntimeClass, RuntimeClasses};
+use crate::{MethodCode, MirToBytecodeVisitor, control_flow, create_method, env, ldc};
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction, VerificationType};
+use ristretto_classfile::{Method, MethodAccessFlags};
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::RETURN_PLACE;
+use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_span::sym;
+
+/// The exit code of a panicking Rust program
+const PANIC_EXIT_CODE: i8 = 101;
+
+/// Whether the function is the crate's `main`
+pub(crate) fn is_entry_fn(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    tcx.entry_fn(())
+        .is_some_and(|(entry_def_id, _)| entry_def_id == def_id)
+}
+
+/// Whether the function is the crate's `main` and returns a `Result`
+pub(crate) fn is_result_main(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    if !is_entry_fn(tcx, def_id) {
+        return false;
+    }
+    let return_ty = tcx.fn_sig(def_id).skip_binder().output().skip_binder();
+    match return_ty.kind() {
+        TyKind::Adt(adt_def, _) => tcx.is_diagnostic_item(sym::Result, adt_def.did()),
+        _ => false,
+    }
+}
+
+/// The descriptor of the lowered Rust `main`
+pub(crate) fn main_descriptor(tcx: TyCtxt<'_>, def_id: DefId) -> &'static str {
+    if is_result_main(tcx, def_id) {
+        "()Ljava/lang/Object;"
+    } else {
+        "()V"
+    }
+}
+
+/// The synthetic `public static void main(String[] args)` calling the lowered Rust `main`
+pub(crate) fn main_wrapper(
+    constant_pool: &mut ConstantPool,
+    crate_name: &str,
+    main_name: &str,
+    main_descriptor: &str,
+    runtime_classes: &mut RuntimeClasses,
+) -> ristretto_classfile::Result<Method> {
+    let this_class = constant_pool.add_class(crate_name)?;
+    let args = env::args_field_ref(constant_pool, crate_name)?;
+    let rust_main = constant_pool.add_method_ref(this_class, main_name, main_descriptor)?;
+    let system = constant_pool.add_class("java/lang/System")?;
+    let stderr = constant_pool.add_field_ref(system, "err", "Ljava/io/PrintStream;")?;
+    let exit = constant_pool.add_method_ref(system, "exit", "(I)V")?;
+    let print_stream = constant_pool.add_class("java/io/PrintStream")?;
+    let println = constant_pool.add_method_ref(print_stream, "println", "(Ljava/lang/String;)V")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/Object;)Ljava/lang/StringBuilder;",
+    )?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let panic_class = constant_pool.add_class(RuntimeClass::Panic.name())?;
+    runtime_classes.insert(RuntimeClass::Panic);
+    let error_prefix = constant_pool.add_string("Error: ")?;
+
+    // error = null; args = arguments; try { error = main(); } catch (Panic panic) { .. }
+    // The frames see slot 1 as the `Object` it holds, so it is set before the first branch
+    let mut instructions = vec![
+        Instruction::Aconst_null,
+        Instruction::Astore_1,
+        Instruction::Aload_0,
+        Instruction::Putstatic(args),
+    ];
+    let try_start = instructions.len();
+    instructions.push(Instruction::Invokestatic(rust_main));
+    let try_end = instructions.len();
+    let mut frames = Vec::new();
+    if main_descriptor.ends_with(")V") {
+        instructions.push(Instruction::Return);
+    } else {
+        // if (error != null) { System.err.println("Error: " + error); System.exit(1); }
+        instructions.extend([
+            Instruction::Astore_1,
+            Instruction::Aload_1,
+            Instruction::Ifnonnull(0),
+        ]);
+        let branch = instructions.len() - 1;
+        instructions.push(Instruction::Return);
+        let failed = instructions.len();
+        frames.push((failed, Vec::new()));
+        instructions[branch] = Instruction::Ifnonnull(failed as u16);
+        // System.err.println(new StringBuilder("Error: ").append(error).toString())
+        instructions.extend([
+            Instruction::Getstatic(stderr),
+            Instruction::New(string_builder),
+            Instruction::Dup,
+            ldc(error_prefix),
+            Instruction::Invokespecial(string_builder_init),
+            Instruction::Aload_1,
+            Instruction::Invokevirtual(append),
+            Instruction::Invokevirtual(to_string),
+            Instruction::Invokevirtual(println),
+        ]);
+        instructions.extend([
+            Instruction::Iconst_1,
+            Instruction::Invokestatic(exit),
+            Instruction::Return,
+        ]);
+    }
+
+    // catch (Panic panic) { System.exit(101); }
+    let handler = instructions.len();
+    frames.push((
+        handler,
+        vec![VerificationType::Object {
+            cpool_index: panic_class,
+        }],
+    ));
+    instructions.extend([
+        Instruction::Pop,
+        Instruction::Bipush(PANIC_EXIT_CODE),
+        Instruction::Invokestatic(exit),
+        Instruction::Return,
+    ]);
+
+    let locals = [
+        VerificationType::Object {
+            cpool_index: constant_pool.add_class("[Ljava/lang/String;")?,
+        },
+        VerificationType::Object {
+            cpool_index: constant_pool.add_class("java/lang/Object")?,
+        },
+    ];
+    let stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+    let exception_table = vec![ExceptionTableEntry {
+        range_pc: try_start as u16..try_end as u16,
+        handler_pc: handler as u16,
+        catch_type: panic_class,
+    }];
+
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        "main",
+        "([Ljava/lang/String;)V",
+        &MethodCode {
+            instructions,
+            exception_table,
+            stack_map_frames,
+        },
+    )
+}
+
+impl MirToBytecodeVisitor<'_, '_> {
+    /// Returns from a `Result`-returning `main`: `null` for `Ok`, the boxed error for `Err`
+    pub(crate) fn return_main_result(&mut self) {
+        let ok = VariantIdx::from_u32(0);
+        let err = VariantIdx::from_u32(1);
+        self.load_local(RETURN_PLACE);
+        let ok_discriminant = self.int_constant(self.discriminant_value(RETURN_PLACE, ok));
+        self.method_bytecode_instructions.push(ok_discriminant);
+        let to_err = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions
+            .push(Instruction::If_icmpne(0));
+        self.method_bytecode_instructions
+            .push(Instruction::Aconst_null);
+        self.method_bytecode_instructions.push(Instruction::Areturn);
+
+        let err_start = self.method_bytecode_instructions.len();
+        self.extra_frames.push((err_start, Vec::new()));
+        self.method_bytecode_instructions[to_err] = Instruction::If_icmpne(err_start as u16);
+        let field = FieldIdx::from_u32(0);
+        match self.enum_field_slots.get(&(RETURN_PLACE, err, field)) {
+            Some((_, descriptor)) => {
+                let descriptor = descriptor.clone();
+                self.load_enum_field(RETURN_PLACE, err, field);
+                self.box_value(&descriptor);
+            }
+            // A zero-sized error, such as `()`
+            None => self.load_str_constant("()"),
+        }
+        self.method_bytecode_instructions.push(Instruction::Areturn);
+    }
+}
@@ -0,0 +1,409 @@
+//! Classes for enums marked `#[jvm::export]`.
+
+use crate::constant_pool::ConstantPool;
+use crate::trace::trace;
+use crate::{
+    MethodCode, MirToBytecodeVisitor, annotations, attributes, create_method, export, ldc,
+    load_instruction, options, records, rust_ty_to_jvm_descriptor,
+};
+use ristretto_classfile::attributes::{Attribute, Instruction};
+use ristretto_classfile::{
+    ClassAccessFlags, ClassFile, Field, FieldAccessFlags, FieldType, Method, MethodAccessFlags,
+};
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_index::IndexVec;
+use rustc_middle::mir::{ConstOperand, Operand, Place, ProjectionElem};
+use rustc_middle::ty::{FieldDef, Ty, TyCtxt, TyKind, TypingEnv, VariantDef};
+
+/// The method returning the discriminant of a variant object
+const DISCRIMINANT_METHOD: &str = "discriminant";
+
+/// The first class version with sealed classes, final in Java 17
+const SEALED_CLASS_VERSION: u16 = 17;
+
+/// Collects the enums of the current crate marked `#[jvm::export]`
+pub(crate) fn exported_enums(tcx: TyCtxt<'_>) -> Vec<DefId> {
+    tcx.hir_crate_items(())
+        .free_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .filter(|def_id| {
+            tcx.def_kind(*def_id) == DefKind::Enum
+                && attributes::has_jvm_attr(tcx, *def_id, "export")
+        })
+        .filter(|def_id| {
+            let generic = tcx.generics_of(*def_id).requires_monomorphization(tcx);
+            if generic {
+                trace!(
+                    "Skipping exported enum {}: generic enums are not supported yet",
+                    tcx.def_path_str(*def_id)
+                );
+            }
+            !generic
+        })
+        .collect()
+}
+
+/// Whether an enum is marked `#[jvm::export]`
+pub(crate) fn is_exported_enum(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    tcx.def_kind(def_id) == DefKind::Enum && attributes::has_jvm_attr(tcx, def_id, "export")
+}
+
+/// The exported enum a type is, if it is one
+fn exported_enum_of(tcx: TyCtxt<'_>, ty: Ty<'_>) -> Option<DefId> {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) if is_exported_enum(tcx, adt_def.did()) => Some(adt_def.did()),
+        _ => None,
+    }
+}
+
+/// The internal name of the class of a variant of an exported enum
+pub(crate) fn variant_class_name(tcx: TyCtxt<'_>, def_id: DefId, variant: &VariantDef) -> String {
+    format!("{}${}", export::class_name(tcx, def_id), variant.name)
+}
+
+/// The name of the field holding a variant field, `_0` for the first field of a tuple variant
+pub(crate) fn field_name(field: &FieldDef) -> String {
+    let name = field.name.as_str();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// The descriptor of the constructor of a variant class, which takes every field in order
+fn constructor_descriptor(tcx: TyCtxt<'_>, variant: &VariantDef) -> String {
+    let fields: String = variant
+        .fields
+        .iter()
+        .map(|field| rust_ty_to_jvm_descriptor(tcx.type_of(field.did).instantiate_identity(), tcx))
+        .collect();
+    format!("({fields})V")
+}
+
+/// The discriminant of a variant of an enum
+fn discriminant(tcx: TyCtxt<'_>, def_id: DefId, variant_index: VariantIdx) -> i32 {
+    tcx.adt_def(def_id)
+        .discriminant_for_variant(tcx, variant_index)
+        .val as i32
+}
+
+/// Generates the interface of an exported enum and the classes of its variants for the Java
+/// version, with their names
+pub(crate) fn generate_exported_enum_classes(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    class_version: u16,
+) -> ristretto_classfile::Result<Vec<(String, Vec<u8>)>> {
+    let adt_def = tcx.adt_def(def_id);
+    let mut classes = vec![generate_interface(tcx, def_id, class_version)?];
+    for (variant_index, variant) in adt_def.variants().iter_enumerated() {
+        classes.push(generate_variant_class(
+            tcx,
+            def_id,
+            variant_index,
+            variant,
+            class_version,
+        )?);
+    }
+    Ok(classes)
+}
+
+/// The interface of an exported enum, sealed to its variant classes on Java 17 or later
+fn generate_interface(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    class_version: u16,
+) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let class_name = export::class_name(tcx, def_id);
+    let mut constant_pool = ConstantPool::default();
+    let this_class = constant_pool.add_class(&class_name)?;
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+
+    let discriminant_method = Method {
+        access_flags: MethodAccessFlags::PUBLIC
+            | MethodAccessFlags::ABSTRACT
+            | MethodAccessFlags::SYNTHETIC,
+        name_index: constant_pool.add_utf8(DISCRIMINANT_METHOD)?,
+        descriptor_index: constant_pool.add_utf8("()I")?,
+        attributes: Vec::new(),
+    };
+
+    let mut attributes = annotations::java_annotations(&mut constant_pool, tcx, def_id)?;
+    if class_version >= SEALED_CLASS_VERSION {
+        let mut class_indexes = Vec::new();
+        for variant in tcx.adt_def(def_id).variants() {
+            class_indexes.push(constant_pool.add_class(variant_class_name(tcx, def_id, variant))?);
+        }
+        attributes.push(Attribute::PermittedSubclasses {
+            name_index: constant_pool.add_utf8("PermittedSubclasses")?,
+            class_indexes,
+        });
+    }
+
+    let class_file = ClassFile {
+        version: options::class_file_version(class_version),
+        access_flags: ClassAccessFlags::PUBLIC
+            | ClassAccessFlags::INTERFACE
+            | ClassAccessFlags::ABSTRACT,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods: vec![discriminant_method],
+        attributes,
+        ..Default::default()
+    };
+    class_file.verify()?;
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((class_name, bytes))
+}
+
+/// The class of a variant of an exported enum, a record on Java 17 or later
+fn generate_variant_class(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    variant_index: VariantIdx,
+    variant: &VariantDef,
+    class_version: u16,
+) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let record = class_version >= SEALED_CLASS_VERSION;
+    let class_name = variant_class_name(tcx, def_id, variant);
+    let mut constant_pool = ConstantPool::default();
+    let this_class = constant_pool.add_class(&class_name)?;
+    let super_class = constant_pool.add_class(if record {
+        records::RECORD_CLASS
+    } else {
+        "java/lang/Object"
+    })?;
+    let interface = constant_pool.add_class(export::class_name(tcx, def_id))?;
+    let super_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+
+    // Fields, and a constructor assigning each of them from its parameters. The components of a
+    // record are read through its accessors, unless it is the variant of a class of a
+    // multi-release JAR, see `multi_release`
+    let field_access_flags = if record && options::get().multi_release.is_none() {
+        FieldAccessFlags::empty()
+    } else {
+        FieldAccessFlags::PUBLIC
+    };
+    let mut fields = Vec::new();
+    let mut components = Vec::new();
+    let mut constructor = vec![Instruction::Aload_0, Instruction::Invokespecial(super_init)];
+    let mut next_slot: u16 = 1; // Slot 0 holds `this`
+    for field in &variant.fields {
+        let field_ty = tcx.type_of(field.did).instantiate_identity();
+        let descriptor = rust_ty_to_jvm_descriptor(field_ty, tcx);
+        let name = field_name(field);
+        fields.push(Field {
+            access_flags: field_access_flags,
+            name_index: constant_pool.add_utf8(&name)?,
+            descriptor_index: constant_pool.add_utf8(&descriptor)?,
+            field_type: FieldType::parse(&descriptor)?,
+            attributes: Vec::new(),
+        });
+        let field_ref = constant_pool.add_field_ref(this_class, &name, &descriptor)?;
+        constructor.push(Instruction::Aload_0);
+        constructor.extend(load_instruction(&descriptor, next_slot));
+        constructor.push(Instruction::Putfield(field_ref));
+        next_slot += if matches!(descriptor.as_str(), "J" | "D") {
+            2
+        } else {
+            1
+        };
+        components.push((name, field_ty));
+    }
+    constructor.push(Instruction::Return);
+
+    let discriminant_value = constant_pool.add_integer(discriminant(tcx, def_id, variant_index))?;
+    let mut methods = vec![
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "<init>",
+            &constructor_descriptor(tcx, variant),
+            &MethodCode::from(constructor),
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::SYNTHETIC,
+            DISCRIMINANT_METHOD,
+            "()I",
+            &MethodCode::from(vec![ldc(discriminant_value), Instruction::Ireturn]),
+        )?,
+    ];
+
+    let mut attributes = annotations::java_annotations(&mut constant_pool, tcx, variant.def_id)?;
+    if record {
+        let record_methods = records::record_methods(
+            tcx,
+            variant.name.as_str(),
+            &components,
+            this_class,
+            &mut constant_pool,
+            &methods,
+        )?;
+        methods.extend(record_methods);
+        attributes.push(records::record_attribute(
+            tcx,
+            &components,
+            &mut constant_pool,
+        )?);
+    }
+
+    let class_file = ClassFile {
+        version: options::class_file_version(class_version),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::FINAL | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        interfaces: vec![interface],
+        fields,
+        methods,
+        attributes,
+        ..Default::default()
+    };
+    class_file.verify()?;
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((class_name, bytes))
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Builds the object of a variant of an exported enum from its fields
+    pub(crate) fn load_variant_aggregate(
+        &mut self,
+        def_id: DefId,
+        variant_index: VariantIdx,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let variant = &self.tcx.adt_def(def_id).variants()[variant_index];
+        self.new_variant(def_id, variant, |visitor| {
+            for operand in operands {
+                visitor.load_operand(operand);
+            }
+        });
+    }
+
+    /// Emits `new Variant(..)`, the fields loaded by `load_fields`
+    fn new_variant(
+        &mut self,
+        def_id: DefId,
+        variant: &VariantDef,
+        load_fields: impl FnOnce(&mut Self),
+    ) {
+        let class = self
+            .constant_pool
+            .add_class(variant_class_name(self.tcx, def_id, variant))
+            .expect("Could not add the variant class to the constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class, "<init>", &constructor_descriptor(self.tcx, variant))
+            .expect("Could not add the variant constructor to the constant pool");
+        self.method_bytecode_instructions
+            .extend([Instruction::New(class), Instruction::Dup]);
+        load_fields(self);
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+    }
+
+    /// Loads the value of a constant of an exported enum, returning `false` if the constant is
+    /// not of one. Only the variants without fields can be read back from a constant.
+    pub(crate) fn load_exported_enum_constant(&mut self, constant: &ConstOperand<'tcx>) -> bool {
+        let tcx = self.tcx;
+        let Some(def_id) = exported_enum_of(tcx, constant.ty()) else {
+            return false;
+        };
+        let variant = constant
+            .const_
+            .try_eval_scalar_int(tcx, TypingEnv::fully_monomorphized())
+            .and_then(|scalar| {
+                let bits = scalar.to_bits_unchecked();
+                tcx.adt_def(def_id)
+                    .discriminants(tcx)
+                    .find(|(_, discriminant)| discriminant.val == bits)
+            })
+            .map(|(variant_index, _)| &tcx.adt_def(def_id).variants()[variant_index])
+            .filter(|variant| variant.fields.is_empty());
+        match variant {
+            Some(variant) => self.new_variant(def_id, variant, |_| {}),
+            None => trace!("      Unsupported constant of an exported enum: {constant:?}"),
+        }
+        true
+    }
+
+    /// Whether a place is the discriminant or a field of a variant of an exported enum, directly
+    /// or through a reference
+    pub(crate) fn is_exported_enum_place(&self, place: &Place<'tcx>) -> bool {
+        exported_enum_of(self.tcx, self.enum_place_ty(place)).is_some()
+    }
+
+    /// The type of the enum a place reads a variant field of, or of the place itself
+    fn enum_place_ty(&self, place: &Place<'tcx>) -> Ty<'tcx> {
+        let ty = self.body.local_decls[place.local].ty;
+        match &place.projection[..] {
+            [ProjectionElem::Deref, ..] => ty.builtin_deref(true).unwrap_or(ty),
+            _ => ty,
+        }
+    }
+
+    /// Loads the discriminant of the exported enum value at a place
+    pub(crate) fn load_exported_enum_discriminant(&mut self, place: &Place<'tcx>) {
+        let Some(def_id) = exported_enum_of(self.tcx, self.enum_place_ty(place)) else {
+            return;
+        };
+        self.load_local(place.local);
+        let interface = self
+            .constant_pool
+            .add_class(export::class_name(self.tcx, def_id))
+            .expect("Could not add the enum interface to the constant pool");
+        let method = self
+            .constant_pool
+            .add_interface_method_ref(interface, DISCRIMINANT_METHOD, "()I")
+            .expect("Could not add the discriminant method to the constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokeinterface(method, 1));
+    }
+
+    /// Loads a field of a variant of an exported enum
+    pub(crate) fn load_exported_enum_field(&mut self, place: &Place<'tcx>) {
+        let tcx = self.tcx;
+        let (variant_index, field) = match &place.projection[..] {
+            [
+                ProjectionElem::Downcast(_, variant_index),
+                ProjectionElem::Field(field, _),
+            ]
+            | [
+                ProjectionElem::Deref,
+                ProjectionElem::Downcast(_, variant_index),
+                ProjectionElem::Field(field, _),
+            ] => (*variant_index, *field),
+            _ => {
+                trace!("      Unsupported place of an exported enum: {place:?}");
+                return;
+            }
+        };
+        let Some(def_id) = exported_enum_of(tcx, self.enum_place_ty(place)) else {
+            return;
+        };
+        let variant = &tcx.adt_def(def_id).variants()[variant_index];
+        let field_def = &variant.fields[field];
+        let descriptor =
+            rust_ty_to_jvm_descriptor(tcx.type_of(field_def.did).instantiate_identity(), tcx);
+        let class = self
+            .constant_pool
+            .add_class(variant_class_name(tcx, def_id, variant))
+            .expect("Could not add the variant class to the constant pool");
+        let field_ref = self
+            .constant_pool
+            .add_field_ref(class, &field_name(field_def), &descriptor)
+            .expect("Could not add the variant field to the constant pool");
+        self.load_local(place.local);
+        self.method_bytecode_instructions.extend([
+            Instruction::Checkcast(class),
+            Instruction::Getfield(field_ref),
+        ]);
+    }
+}
@@ -0,0 +1,163 @@
+//! `std::env` on top of the JVM's arguments and environment.
+
+use crate::constant_pool::ConstantPool;
+use crate::runtime::RuntimeClass;
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, control_flow, naming};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{Field, FieldAccessFlags, FieldType};
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_middle::mir::{Operand, Place};
+use rustc_span::source_map::Spanned;
+
+/// The static field of the crate class holding the command line arguments
+const ARGS_FIELD: &str = "args";
+const ARGS_DESCRIPTOR: &str = "[Ljava/lang/String;";
+
+/// The `public static String[] args` field of the crate class
+pub(crate) fn args_field(constant_pool: &mut ConstantPool) -> ristretto_classfile::Result<Field> {
+    Ok(Field {
+        access_flags: FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC,
+        name_index: constant_pool.add_utf8(ARGS_FIELD)?,
+        descriptor_index: constant_pool.add_utf8(ARGS_DESCRIPTOR)?,
+        field_type: FieldType::parse(ARGS_DESCRIPTOR)?,
+        attributes: Vec::new(),
+    })
+}
+
+/// The `args` field of the crate class, in the constant pool
+pub(crate) fn args_field_ref(
+    constant_pool: &mut ConstantPool,
+    crate_name: &str,
+) -> ristretto_classfile::Result<u16> {
+    let crate_class = constant_pool.add_class(crate_name)?;
+    constant_pool.add_field_ref(crate_class, ARGS_FIELD, ARGS_DESCRIPTOR)
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `env::args`, `env::var` and `env::current_dir`, returning `false` for any
+    /// other call
+    pub(crate) fn lower_env_call(
+        &mut self,
+        callee: DefId,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let path = self.tcx.def_path_str(callee);
+        match path.as_str() {
+            "std::env::args" => {
+                // Env.args(crateName, args)
+                let crate_name = self.tcx.crate_name(LOCAL_CRATE).to_string();
+                self.load_str_constant(&crate_name);
+                let args = args_field_ref(self.constant_pool, &naming::crate_class_name(self.tcx))
+                    .expect("Could not add field ref to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Getstatic(args));
+                let class = RuntimeClass::Env;
+                let class_index = self
+                    .constant_pool
+                    .add_class(class.name())
+                    .expect("Could not add class to constant pool");
+                let method = self
+                    .constant_pool
+                    .add_method_ref(
+                        class_index,
+                        "args",
+                        "(Ljava/lang/String;[Ljava/lang/String;)Ljava/util/Iterator;",
+                    )
+                    .expect("Could not add method ref to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokestatic(method));
+                self.runtime_classes.insert(class);
+                self.store_call_result(destination);
+            }
+            "std::env::var" => return self.lower_env_var(&args[0].node, destination),
+            "std::env::current_dir" => {
+                if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+                    trace!("      Unsupported destination for env::current_dir: {destination:?}");
+                    return false;
+                }
+                let ok = VariantIdx::from_u32(0);
+                self.load_str_constant("user.dir");
+                self.invoke_static(
+                    "java/lang/System",
+                    "getProperty",
+                    "(Ljava/lang/String;)Ljava/lang/String;",
+                );
+                self.store_enum_field(destination.local, ok, FieldIdx::from_u32(0));
+                self.store_discriminant(destination.local, ok);
+            }
+            _ => return false,
+        }
+        trace!("      Generated bytecode: {path}");
+        true
+    }
+
+    /// `env::var(key)` is `Ok` of the value `System.getenv` finds, or `Err(NotPresent)` for
+    /// `null`
+    fn lower_env_var(&mut self, key: &Operand<'tcx>, destination: &Place<'tcx>) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for env::var: {destination:?}");
+            return false;
+        }
+        let ok = VariantIdx::from_u32(0);
+        let err = VariantIdx::from_u32(1);
+
+        // The key is a `String` or a `StringBuilder`
+        self.load_operand(key);
+        let object = self
+            .constant_pool
+            .add_class("java/lang/Object")
+            .expect("Could not add class to constant pool");
+        let to_string = self
+            .constant_pool
+            .add_method_ref(object, "toString", "()Ljava/lang/String;")
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(to_string));
+        self.invoke_static(
+            "java/lang/System",
+            "getenv",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+        );
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        let to_err = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions
+            .push(Instruction::Ifnull(0));
+
+        // Ok(new StringBuilder(value))
+        let string_builder = self
+            .constant_pool
+            .add_class("java/lang/StringBuilder")
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions.extend([
+            Instruction::New(string_builder),
+            Instruction::Dup_x1,
+            Instruction::Swap,
+            Instruction::Invokespecial(constructor),
+        ]);
+        self.store_enum_field(destination.local, ok, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, ok);
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        let err_start = self.method_bytecode_instructions.len();
+        let value = control_flow::verification_type("Ljava/lang/String;", self.constant_pool)
+            .expect("Could not add class to constant pool");
+        self.extra_frames.push((err_start, vec![value]));
+        self.method_bytecode_instructions.push(Instruction::Pop);
+        self.store_discriminant(destination.local, err);
+
+        let end = self.method_bytecode_instructions.len();
+        self.extra_frames.push((end, Vec::new()));
+        self.method_bytecode_instructions[to_err] = Instruction::Ifnull(err_start as u16);
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        trace!("      Generated bytecode: std::env::var");
+        true
+    }
+}
@@ -0,0 +1,506 @@
+//! Classes for structs marked `#[jvm::export]`, see [`crate::enums`] for enums.
+
+use crate::constant_pool::ConstantPool;
+use crate::runtime::{RuntimeClass, RuntimeClasses};
+use crate::trace::trace;
+use crate::{
+    MethodCode, annotations, attributes, boxing, create_method, fmt, ldc, load_instruction,
+    lower_function, method_descriptor, naming, options, records, return_instruction,
+    rust_ty_to_jvm_descriptor, signature, visibility,
+};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{
+    ClassAccessFlags, ClassFile, Field, FieldAccessFlags, FieldType, Method, MethodAccessFlags,
+};
+use rustc_hir::def::{CtorKind, DefKind};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{AssocKind, FieldDef, TyCtxt, TyKind};
+use rustc_span::{Symbol, sym};
+
+/// Collects the structs of the current crate marked `#[jvm::export]`
+pub(crate) fn exported_structs(tcx: TyCtxt<'_>) -> Vec<DefId> {
+    tcx.hir_crate_items(())
+        .free_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .filter(|def_id| {
+            tcx.def_kind(*def_id) == DefKind::Struct
+                && attributes::has_jvm_attr(tcx, *def_id, "export")
+        })
+        .filter(|def_id| {
+            let generic = tcx.generics_of(*def_id).requires_monomorphization(tcx);
+            if generic {
+                trace!(
+                    "Skipping exported struct {}: generic structs are not supported yet",
+                    tcx.def_path_str(*def_id)
+                );
+            }
+            !generic
+        })
+        .collect()
+}
+
+/// The JVM class name of an exported struct
+pub(crate) fn class_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    naming::in_crate_package(tcx, def_id.krate, tcx.item_name(def_id).as_str())
+}
+
+/// Descriptor of the generated constructor, which takes every field in declaration order
+pub(crate) fn constructor_descriptor(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let mut descriptor = String::from("(");
+    for field in &tcx.adt_def(def_id).non_enum_variant().fields {
+        let field_ty = tcx.type_of(field.did).instantiate_identity();
+        descriptor.push_str(&rust_ty_to_jvm_descriptor(field_ty, tcx));
+    }
+    descriptor.push_str(")V");
+    descriptor
+}
+
+/// The exported struct an inherent method belongs to, if any
+pub(crate) fn exported_struct_of_method(tcx: TyCtxt<'_>, def_id: DefId) -> Option<DefId> {
+    if tcx.def_kind(def_id) != DefKind::AssocFn {
+        return None;
+    }
+    let impl_def_id = tcx.impl_of_method(def_id)?;
+    if tcx.impl_trait_ref(impl_def_id).is_some() {
+        return None;
+    }
+    match tcx.type_of(impl_def_id).instantiate_identity().kind() {
+        TyKind::Adt(adt_def, _)
+            if adt_def.is_struct() && attributes::has_jvm_attr(tcx, adt_def.did(), "export") =>
+        {
+            Some(adt_def.did())
+        }
+        _ => None,
+    }
+}
+
+/// Impl blocks for the struct that carry `#[jvm::implements(...)]`
+fn interface_impls(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<DefId> {
+    tcx.hir_crate_items(())
+        .free_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .filter(|impl_def_id| {
+            matches!(tcx.def_kind(*impl_def_id), DefKind::Impl { .. })
+                && attributes::has_jvm_attr(tcx, *impl_def_id, "implements")
+                && matches!(
+                    tcx.type_of(*impl_def_id).instantiate_identity().kind(),
+                    TyKind::Adt(adt_def, _) if adt_def.did() == def_id
+                )
+        })
+        .collect()
+}
+
+/// The impl of the trait with the given diagnostic name for the struct, if the crate has one
+fn trait_impl(tcx: TyCtxt<'_>, def_id: DefId, trait_name: Symbol) -> Option<DefId> {
+    let trait_def_id = tcx.get_diagnostic_item(trait_name)?;
+    tcx.hir_crate_items(())
+        .free_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .find(|impl_def_id| {
+            matches!(tcx.def_kind(*impl_def_id), DefKind::Impl { of_trait: true })
+                && tcx.trait_id_of_impl(*impl_def_id) == Some(trait_def_id)
+                && matches!(
+                    tcx.type_of(*impl_def_id).instantiate_identity().kind(),
+                    TyKind::Adt(adt_def, _) if adt_def.did() == def_id
+                )
+        })
+}
+
+/// `toString()` formatting the struct as `#[derive(Debug)]` does: `Name { a: 1, b: "b" }`, or
+/// `Name(1, "b")` for a tuple struct
+fn debug_to_string(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    this_class: u16,
+    constant_pool: &mut ConstantPool,
+    runtime_classes: &mut RuntimeClasses,
+) -> ristretto_classfile::Result<Method> {
+    let variant = tcx.adt_def(def_id).non_enum_variant();
+    let (open, separator, close) = match variant.ctor_kind() {
+        Some(CtorKind::Fn) => ("(", ", ", ")"),
+        _ => (" { ", ", ", " }"),
+    };
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let fmt_class = constant_pool.add_class(RuntimeClass::Fmt.name())?;
+    runtime_classes.insert(RuntimeClass::Fmt);
+
+    // new StringBuilder("Name { ").append("a: ").append(Fmt.debug(a)).append(", ")...
+    let name = constant_pool.add_string(tcx.item_name(def_id).as_str())?;
+    let mut instructions = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        ldc(name),
+        Instruction::Invokespecial(string_builder_init),
+    ];
+    for (index, field) in variant.fields.iter().enumerate() {
+        let mut prefix = if index == 0 { open } else { separator }.to_string();
+        if variant.ctor_kind() != Some(CtorKind::Fn) {
+            prefix.push_str(&format!("{}: ", field.name));
+        }
+        let prefix = constant_pool.add_string(prefix)?;
+        let field_ty = tcx.type_of(field.did).instantiate_identity();
+        let descriptor = rust_ty_to_jvm_descriptor(field_ty, tcx);
+        let field_ref =
+            constant_pool.add_field_ref(this_class, field.name.as_str(), &descriptor)?;
+        let format = constant_pool.add_method_ref(
+            fmt_class,
+            fmt::format_method(field_ty, true),
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+        )?;
+        instructions.extend([
+            ldc(prefix),
+            Instruction::Invokevirtual(append),
+            Instruction::Aload_0,
+            Instruction::Getfield(field_ref),
+        ]);
+        instructions.extend(boxing::box_instructions(constant_pool, &descriptor)?);
+        instructions.extend([
+            Instruction::Invokestatic(format),
+            Instruction::Invokevirtual(append),
+        ]);
+    }
+    if !variant.fields.is_empty() {
+        let close = constant_pool.add_string(close)?;
+        instructions.extend([ldc(close), Instruction::Invokevirtual(append)]);
+    }
+    instructions.extend([Instruction::Invokevirtual(to_string), Instruction::Areturn]);
+
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "toString",
+        "()Ljava/lang/String;",
+        &MethodCode::from(instructions),
+    )
+}
+
+/// The getter of a field, and its setter unless the field is `#[jvm::readonly]`
+fn field_accessors(
+    tcx: TyCtxt<'_>,
+    field: &FieldDef,
+    this_class: u16,
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<Vec<Method>> {
+    let field_ty = tcx.type_of(field.did).instantiate_identity();
+    let descriptor = rust_ty_to_jvm_descriptor(field_ty, tcx);
+    let signature = signature::type_signature(field_ty, tcx);
+    let field_ref = constant_pool.add_field_ref(this_class, field.name.as_str(), &descriptor)?;
+    let mut property = naming::camel_case(field.name.as_str());
+    if let Some(first) = property.chars().next() {
+        property.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+    }
+
+    let prefix = if descriptor == "Z" { "is" } else { "get" };
+    let getter_descriptor = format!("(){descriptor}");
+    let mut getter = create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        &format!("{prefix}{property}"),
+        &getter_descriptor,
+        &MethodCode::from(vec![
+            Instruction::Aload_0,
+            Instruction::Getfield(field_ref),
+            return_instruction(&descriptor),
+        ]),
+    )?;
+    getter.attributes.extend(signature::signature_attribute(
+        constant_pool,
+        &format!("(){signature}"),
+        &getter_descriptor,
+    )?);
+    getter.attributes.extend(annotations::method_nullability(
+        constant_pool,
+        tcx,
+        &[],
+        field_ty,
+    )?);
+    let mut accessors = vec![getter];
+    if attributes::has_jvm_attr(tcx, field.did, "readonly") {
+        return Ok(accessors);
+    }
+
+    let setter_descriptor = format!("({descriptor})V");
+    let mut instructions = vec![Instruction::Aload_0];
+    instructions.extend(load_instruction(&descriptor, 1));
+    instructions.extend([Instruction::Putfield(field_ref), Instruction::Return]);
+    let mut setter = create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        &format!("set{property}"),
+        &setter_descriptor,
+        &MethodCode::from(instructions),
+    )?;
+    setter.attributes.extend(signature::signature_attribute(
+        constant_pool,
+        &format!("({signature})V"),
+        &setter_descriptor,
+    )?);
+    setter.attributes.extend(annotations::method_nullability(
+        constant_pool,
+        tcx,
+        &[field_ty],
+        tcx.types.unit,
+    )?);
+    accessors.push(setter);
+    Ok(accessors)
+}
+
+/// Lowers every non-generic method of an impl block into a method of the struct's class
+fn push_impl_methods(
+    tcx: TyCtxt<'_>,
+    impl_def_id: DefId,
+    constant_pool: &mut ConstantPool,
+    runtime_classes: &mut RuntimeClasses,
+    methods: &mut Vec<Method>,
+) -> ristretto_classfile::Result<()> {
+    for item in tcx.associated_items(impl_def_id).in_definition_order() {
+        if item.kind != AssocKind::Fn || tcx.generics_of(item.def_id).requires_monomorphization(tcx)
+        {
+            continue;
+        }
+        let name = naming::checked_method_name(tcx, item.def_id);
+        let code = lower_function(tcx, item.def_id, constant_pool, runtime_classes);
+
+        // Methods implementing a Java interface must be public
+        let mut access_flags = if tcx.trait_id_of_impl(impl_def_id).is_some() {
+            MethodAccessFlags::PUBLIC
+        } else {
+            visibility::fn_access_flags(tcx, item.def_id)
+        };
+        if !item.fn_has_self_parameter {
+            access_flags |= MethodAccessFlags::STATIC;
+        }
+        let descriptor = method_descriptor(tcx, item.def_id, item.fn_has_self_parameter);
+        let mut method = create_method(constant_pool, access_flags, &name, &descriptor, &code)?;
+        method.attributes.extend(signature::signature_attribute(
+            constant_pool,
+            &signature::method_signature(tcx, item.def_id, item.fn_has_self_parameter),
+            &descriptor,
+        )?);
+        method.attributes.extend(annotations::fn_nullability(
+            constant_pool,
+            tcx,
+            item.def_id,
+            item.fn_has_self_parameter,
+        )?);
+        method.attributes.extend(annotations::java_annotations(
+            constant_pool,
+            tcx,
+            item.def_id,
+        )?);
+        methods.push(method);
+    }
+    Ok(())
+}
+
+/// Generates the class of an exported struct for the Java version, returning its name and bytes
+pub(crate) fn generate_exported_struct_class(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    runtime_classes: &mut RuntimeClasses,
+    class_version: u16,
+) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let class_name = class_name(tcx, def_id);
+    let is_record = records::is_record(tcx, def_id, class_version);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class(if is_record {
+        records::RECORD_CLASS
+    } else {
+        "java/lang/Object"
+    })?;
+    let this_class = constant_pool.add_class(&class_name)?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+
+    // Fields, and a constructor assigning each of them from its parameters
+    let mut fields = Vec::new();
+    let mut accessors = Vec::new();
+    let has_accessors = attributes::has_jvm_attr(tcx, def_id, "accessors");
+    let mut constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+    ];
+    let mut constructor_signature = String::from("(");
+    let mut field_types = Vec::new();
+    let mut next_slot: u16 = 1; // Slot 0 holds `this`
+    for field in &tcx.adt_def(def_id).non_enum_variant().fields {
+        let field_ty = tcx.type_of(field.did).instantiate_identity();
+        let descriptor = rust_ty_to_jvm_descriptor(field_ty, tcx);
+        let name = field.name.to_string();
+        // The components of a record are read through its accessors, but the record variant of a
+        // multi-release JAR keeps the public fields of the baseline class Java code may read
+        let hides_fields = is_record && options::get().multi_release.is_none();
+        let access_flags = if field.vis.is_public() && !has_accessors && !hides_fields {
+            FieldAccessFlags::PUBLIC
+        } else {
+            FieldAccessFlags::empty() // package-private, like Rust's module privacy
+        };
+
+        let signature = signature::type_signature(field_ty, tcx);
+        constructor_signature.push_str(&signature);
+        let mut field_attributes = Vec::new();
+        field_attributes.extend(signature::signature_attribute(
+            &mut constant_pool,
+            &signature,
+            &descriptor,
+        )?);
+        field_attributes.extend(annotations::field_nullability(
+            &mut constant_pool,
+            tcx,
+            field_ty,
+        )?);
+        field_attributes.extend(annotations::java_annotations(
+            &mut constant_pool,
+            tcx,
+            field.did,
+        )?);
+        fields.push(Field {
+            access_flags,
+            name_index: constant_pool.add_utf8(&name)?,
+            descriptor_index: constant_pool.add_utf8(&descriptor)?,
+            field_type: FieldType::parse(&descriptor)?,
+            attributes: field_attributes,
+        });
+        field_types.push(field_ty);
+        if field.vis.is_public() && has_accessors {
+            accessors.extend(field_accessors(tcx, field, this_class, &mut constant_pool)?);
+        }
+
+        let field_ref = constant_pool.add_field_ref(this_class, &name, &descriptor)?;
+        constructor.push(Instruction::Aload_0);
+        constructor.extend(load_instruction(&descriptor, next_slot));
+        constructor.push(Instruction::Putfield(field_ref));
+        next_slot += if matches!(descriptor.as_str(), "J" | "D") {
+            2
+        } else {
+            1
+        };
+    }
+    constructor.push(Instruction::Return);
+
+    constructor_signature.push_str(")V");
+
+    let init_descriptor = constructor_descriptor(tcx, def_id);
+    let mut init = create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        &init_descriptor,
+        &MethodCode::from(constructor),
+    )?;
+    init.attributes.extend(signature::signature_attribute(
+        &mut constant_pool,
+        &constructor_signature,
+        &init_descriptor,
+    )?);
+    init.attributes.extend(annotations::method_nullability(
+        &mut constant_pool,
+        tcx,
+        &field_types,
+        tcx.types.unit,
+    )?);
+    let mut methods = vec![init];
+    methods.extend(accessors);
+
+    // Inherent methods, and trait methods implementing Java interfaces
+    let interface_impls = interface_impls(tcx, def_id);
+    for impl_def_id in tcx.inherent_impls(def_id) {
+        push_impl_methods(
+            tcx,
+            *impl_def_id,
+            &mut constant_pool,
+            runtime_classes,
+            &mut methods,
+        )?;
+    }
+    for impl_def_id in &interface_impls {
+        if matches!(tcx.def_kind(*impl_def_id), DefKind::Impl { of_trait: true }) {
+            push_impl_methods(
+                tcx,
+                *impl_def_id,
+                &mut constant_pool,
+                runtime_classes,
+                &mut methods,
+            )?;
+        }
+    }
+
+    if trait_impl(tcx, def_id, sym::Debug)
+        .is_some_and(|impl_def_id| tcx.is_automatically_derived(impl_def_id))
+        && trait_impl(tcx, def_id, sym::Display).is_none()
+    {
+        methods.push(debug_to_string(
+            tcx,
+            def_id,
+            this_class,
+            &mut constant_pool,
+            runtime_classes,
+        )?);
+    }
+
+    let mut interface_names = attributes::jvm_attr_string_args(tcx, def_id, "implements");
+    for impl_def_id in &interface_impls {
+        interface_names.extend(attributes::jvm_attr_string_args(
+            tcx,
+            *impl_def_id,
+            "implements",
+        ));
+    }
+    let mut interfaces = Vec::new();
+    for interface_name in interface_names {
+        let interface =
+            constant_pool.add_class(attributes::internal_class_name(&interface_name))?;
+        if !interfaces.contains(&interface) {
+            interfaces.push(interface);
+        }
+    }
+
+    if is_record {
+        let record_methods = records::record_methods(
+            tcx,
+            tcx.item_name(def_id).as_str(),
+            &records::struct_components(tcx, def_id),
+            this_class,
+            &mut constant_pool,
+            &methods,
+        )?;
+        methods.extend(record_methods);
+    }
+
+    let mut attributes = annotations::java_annotations(&mut constant_pool, tcx, def_id)?;
+    let mut access_flags = ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER;
+    if is_record {
+        attributes.push(records::record_attribute(
+            tcx,
+            &records::struct_components(tcx, def_id),
+            &mut constant_pool,
+        )?);
+        access_flags |= ClassAccessFlags::FINAL;
+    }
+
+    let class_file = ClassFile {
+        version: options::class_file_version(class_version),
+        access_flags,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+        ..Default::default()
+    };
+    class_file.verify()?;
+
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((class_name, bytes))
+}
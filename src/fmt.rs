@@ -0,0 +1,249 @@
+//! `core::fmt` on top of `java.lang.String`s.
+
+use crate::runtime::RuntimeClass;
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, string};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::VariantIdx;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind, UintTy};
+use rustc_span::source_map::Spanned;
+use rustc_span::{Symbol, sym};
+
+const STRING: &str = "Ljava/lang/String;";
+const STRING_BUILDER: &str = "Ljava/lang/StringBuilder;";
+
+/// The descriptor of the `Fmt` methods formatting a value
+const FORMAT_DESCRIPTOR: &str = "(Ljava/lang/Object;)Ljava/lang/String;";
+
+/// The path of an item of the standard library without its crate, e.g. `fmt::Arguments`, whether
+/// it is reached through `core`, `alloc` or `std`
+fn std_path(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let path = tcx.def_path_str(def_id);
+    let (krate, path) = path.split_once("::")?;
+    matches!(krate, "core" | "alloc" | "std").then(|| path.to_string())
+}
+
+/// The descriptor of `fmt::Arguments`, `fmt::rt::Argument` and `fmt::Formatter`
+pub(crate) fn java_descriptor(ty: Ty<'_>, tcx: TyCtxt<'_>) -> Option<&'static str> {
+    let TyKind::Adt(adt_def, _) = ty.kind() else {
+        return None;
+    };
+    match std_path(tcx, adt_def.did())?.as_str() {
+        "fmt::Arguments" | "fmt::rt::Argument" => Some(STRING),
+        "fmt::Formatter" => Some(STRING_BUILDER),
+        _ => None,
+    }
+}
+
+/// The `Fmt` method formatting a boxed value of type `ty` with `Debug`, or with `Display`
+pub(crate) fn format_method(ty: Ty<'_>, debug: bool) -> &'static str {
+    match ty.peel_refs().kind() {
+        TyKind::Uint(uint_ty) if *uint_ty != UintTy::U128 => "unsigned",
+        _ if debug => "debug",
+        _ => "display",
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of the `core::fmt` machinery `format_args!` expands to, of `format!`,
+    /// `to_string()` and `write!`, returning `false` for any other call
+    pub(crate) fn lower_fmt_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let name = tcx.item_name(callee);
+        if let Some(trait_def_id) = tcx.trait_of_item(callee) {
+            let self_ty = generic_args.type_at(0);
+            if tcx.is_diagnostic_item(sym::ToString, trait_def_id) {
+                self.load_operand(&args[0].node);
+                self.invoke_fmt(format_method(self_ty, false), FORMAT_DESCRIPTOR);
+                self.new_string_builder_from_string();
+            } else if tcx.is_diagnostic_item(Symbol::intern("FmtWrite"), trait_def_id)
+                && name == Symbol::intern("write_fmt")
+                && string::is_string(self_ty, tcx)
+            {
+                return self.lower_write(args, destination);
+            } else {
+                return false;
+            }
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            self.store_call_result(destination);
+            return true;
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            match std_path(tcx, callee).as_deref() {
+                // `format!`
+                Some("fmt::format") => {
+                    self.load_operand(&args[0].node);
+                    self.new_string_builder_from_string();
+                }
+                // `format!` wraps its result in `must_use`, which returns it
+                Some("hint::must_use") => self.load_operand(&args[0].node),
+                _ => return false,
+            }
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            self.store_call_result(destination);
+            return true;
+        };
+        let self_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        let TyKind::Adt(adt_def, _) = self_ty.kind() else {
+            return false;
+        };
+        match (std_path(tcx, adt_def.did()).as_deref(), name.as_str()) {
+            (Some("fmt::rt::Argument"), "new_display" | "new_debug") => {
+                let value_ty = args[0].node.ty(self.body, tcx);
+                self.load_operand(&args[0].node);
+                let debug = name.as_str() == "new_debug";
+                self.invoke_fmt(format_method(value_ty, debug), FORMAT_DESCRIPTOR);
+            }
+            (
+                Some("fmt::rt::Argument"),
+                "new_lower_hex" | "new_upper_hex" | "new_octal" | "new_binary",
+            ) => {
+                let (radix, upper_case) = match name.as_str() {
+                    "new_lower_hex" => (16, false),
+                    "new_upper_hex" => (16, true),
+                    "new_octal" => (8, false),
+                    _ => (2, false),
+                };
+                self.load_operand(&args[0].node);
+                let radix = self.int_constant(radix);
+                self.method_bytecode_instructions.push(radix);
+                self.method_bytecode_instructions.push(if upper_case {
+                    Instruction::Iconst_1
+                } else {
+                    Instruction::Iconst_0
+                });
+                self.invoke_fmt("radix", "(Ljava/lang/Object;IZ)Ljava/lang/String;");
+            }
+            (Some("fmt::Arguments"), "new_const") => {
+                // Fmt.format(pieces, new String[0])
+                self.load_pieces(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Iconst_0);
+                let string = self
+                    .constant_pool
+                    .add_class("java/lang/String")
+                    .expect("Could not add class to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Anewarray(string));
+                self.invoke_format();
+            }
+            (Some("fmt::Arguments"), "new_v1" | "new_v1_formatted") => {
+                // The placeholders of `new_v1_formatted` are ignored
+                self.load_pieces(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.invoke_format();
+            }
+            (Some("fmt::Formatter"), "write_str" | "write_fmt") => {
+                return self.lower_write(args, destination);
+            }
+            (Some(path), _) if path.starts_with("fmt::") => {
+                trace!(
+                    "      Unsupported formatting method: {}",
+                    tcx.def_path_str(callee)
+                );
+                return false;
+            }
+            _ => return false,
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `write_str` and `write_fmt` into a `Formatter` or a `String` append the text to its
+    /// `StringBuilder` and cannot fail, their result is `Ok(())`
+    fn lower_write(&mut self, args: &[Spanned<Operand<'tcx>>], destination: &Place<'tcx>) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for a formatted write: {destination:?}");
+            return false;
+        }
+        self.load_operand(&args[0].node);
+        self.load_operand(&args[1].node);
+        let string_builder = self
+            .constant_pool
+            .add_class("java/lang/StringBuilder")
+            .expect("Could not add class to constant pool");
+        let append = self
+            .constant_pool
+            .add_method_ref(
+                string_builder,
+                "append",
+                "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+            )
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(append));
+        self.method_bytecode_instructions.push(Instruction::Pop);
+        self.store_discriminant(destination.local, VariantIdx::from_u32(0));
+        trace!("      Generated bytecode: formatted write");
+        true
+    }
+
+    /// Pushes the literal pieces of a format string as a `String[]`
+    fn load_pieces(&mut self, pieces: &Operand<'tcx>) {
+        let literals = match pieces {
+            Operand::Constant(constant) => string::str_array_constant(constant, self.tcx),
+            _ => None,
+        };
+        let Some(literals) = literals else {
+            self.load_operand(pieces);
+            return;
+        };
+        let string = self
+            .constant_pool
+            .add_class("java/lang/String")
+            .expect("Could not add class to constant pool");
+        let length = self.int_constant(literals.len() as i32);
+        self.method_bytecode_instructions.push(length);
+        self.method_bytecode_instructions
+            .push(Instruction::Anewarray(string));
+        for (index, literal) in literals.iter().enumerate() {
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            let index = self.int_constant(index as i32);
+            self.method_bytecode_instructions.push(index);
+            self.load_str_constant(literal);
+            self.method_bytecode_instructions.push(Instruction::Aastore);
+        }
+    }
+
+    /// Turns the `String` on top of the stack into a new `StringBuilder`, an owned `String`
+    fn new_string_builder_from_string(&mut self) {
+        let string_builder = self
+            .constant_pool
+            .add_class("java/lang/StringBuilder")
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions.extend([
+            Instruction::New(string_builder),
+            Instruction::Dup_x1,
+            Instruction::Swap,
+            Instruction::Invokespecial(constructor),
+        ]);
+    }
+
+    /// `Fmt.format(pieces, args)`
+    fn invoke_format(&mut self) {
+        self.invoke_fmt(
+            "format",
+            "([Ljava/lang/String;[Ljava/lang/String;)Ljava/lang/String;",
+        );
+    }
+
+    fn invoke_fmt(&mut self, name: &str, descriptor: &str) {
+        let class = RuntimeClass::Fmt;
+        self.invoke_static(&class.name(), name, descriptor);
+        self.runtime_classes.insert(class);
+    }
+}
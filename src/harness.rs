@@ -0,0 +1,229 @@
+//! The test runner of test binaries, whose `main` runs the tests of the JUnit test class and
+//! reports them as libtest does.
+
+use crate::constant_pool::ConstantPool;
+use crate::junit::{self, TestFunction};
+use crate::trace::trace;
+use crate::{MethodCode, control_flow, create_method, ldc, naming};
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction, VerificationType};
+use ristretto_classfile::{Method, MethodAccessFlags};
+use rustc_middle::ty::TyCtxt;
+
+/// The static method of the test class running every test
+const RUN_TESTS: &str = "runTests";
+
+/// The exit code of a test binary with failed tests
+const FAILED_EXIT_CODE: i8 = 101;
+
+/// The code of the `main` of a test binary: `CrateTest.runTests()`
+pub(crate) fn harness_main(tcx: TyCtxt<'_>, constant_pool: &mut ConstantPool) -> MethodCode {
+    let test_class = constant_pool
+        .add_class(junit::test_class_name(&naming::crate_class_name(tcx)))
+        .expect("Could not add class to constant pool");
+    let run_tests = constant_pool
+        .add_method_ref(test_class, RUN_TESTS, "()V")
+        .expect("Could not add method ref to constant pool");
+    trace!("      Generated bytecode: test harness main");
+    vec![Instruction::Invokestatic(run_tests), Instruction::Return].into()
+}
+
+/// The static `runTests()` of the test class, running each of `tests` and reporting the results
+pub(crate) fn run_tests_method(
+    constant_pool: &mut ConstantPool,
+    this_class: u16,
+    tests: &[TestFunction],
+) -> ristretto_classfile::Result<Method> {
+    let this_init = constant_pool.add_method_ref(this_class, "<init>", "()V")?;
+    let system = constant_pool.add_class("java/lang/System")?;
+    let stdout = constant_pool.add_field_ref(system, "out", "Ljava/io/PrintStream;")?;
+    let stderr = constant_pool.add_field_ref(system, "err", "Ljava/io/PrintStream;")?;
+    let exit = constant_pool.add_method_ref(system, "exit", "(I)V")?;
+    let print_stream = constant_pool.add_class("java/io/PrintStream")?;
+    let print = constant_pool.add_method_ref(print_stream, "print", "(Ljava/lang/String;)V")?;
+    let println = constant_pool.add_method_ref(print_stream, "println", "(Ljava/lang/String;)V")?;
+    let println_object =
+        constant_pool.add_method_ref(print_stream, "println", "(Ljava/lang/Object;)V")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let append_int =
+        constant_pool.add_method_ref(string_builder, "append", "(I)Ljava/lang/StringBuilder;")?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let assertion_error = constant_pool.add_class("java/lang/AssertionError")?;
+    let get_message =
+        constant_pool.add_method_ref(assertion_error, "getMessage", "()Ljava/lang/String;")?;
+
+    // failures = new StringBuilder("\nfailures:"); passed = 0; failed = 0;
+    let failures_header = constant_pool.add_string("\nfailures:")?;
+    let plural = if tests.len() == 1 { "" } else { "s" };
+    let running = constant_pool.add_string(format!("\nrunning {} test{plural}", tests.len()))?;
+    let mut instructions = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        ldc(failures_header),
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Astore_0,
+        Instruction::Iconst_0,
+        Instruction::Istore_1,
+        Instruction::Iconst_0,
+        Instruction::Istore_2,
+        Instruction::Getstatic(stdout),
+        ldc(running),
+        Instruction::Invokevirtual(println),
+    ];
+    let ok = constant_pool.add_string("ok")?;
+    let failed = constant_pool.add_string("FAILED")?;
+    let mut frames = Vec::new();
+    let mut exception_table = Vec::new();
+    let mut ignored = 0;
+    for test in tests {
+        if test.ignored {
+            ignored += 1;
+            let line = constant_pool.add_string(format!("test {} ... ignored", test.path))?;
+            instructions.extend([
+                Instruction::Getstatic(stdout),
+                ldc(line),
+                Instruction::Invokevirtual(println),
+            ]);
+            continue;
+        }
+        let test_method = constant_pool.add_method_ref(this_class, &test.method_name(), "()V")?;
+        let label = constant_pool.add_string(format!("test {} ... ", test.path))?;
+        let failure = constant_pool.add_string(format!("\n    {}", test.path))?;
+
+        // try { new CrateTest().test(); System.out.println("ok"); passed++; }
+        instructions.extend([
+            Instruction::Getstatic(stdout),
+            ldc(label),
+            Instruction::Invokevirtual(print),
+        ]);
+        let try_start = instructions.len();
+        instructions.extend([
+            Instruction::New(this_class),
+            Instruction::Dup,
+            Instruction::Invokespecial(this_init),
+            Instruction::Invokevirtual(test_method),
+        ]);
+        let try_end = instructions.len();
+        instructions.extend([
+            Instruction::Getstatic(stdout),
+            ldc(ok),
+            Instruction::Invokevirtual(println),
+            Instruction::Iinc(1, 1),
+            Instruction::Goto(0),
+        ]);
+        let to_next = instructions.len() - 1;
+
+        // catch (AssertionError error) { System.out.println("FAILED"); failed++; .. }
+        let handler = instructions.len();
+        frames.push((
+            handler,
+            vec![VerificationType::Object {
+                cpool_index: assertion_error,
+            }],
+        ));
+        exception_table.push(ExceptionTableEntry {
+            range_pc: try_start as u16..try_end as u16,
+            handler_pc: handler as u16,
+            catch_type: assertion_error,
+        });
+        if test.should_panic.is_some() {
+            // The panic message was printed when the panic began, but not why a `#[should_panic]`
+            // test failed
+            instructions.extend([
+                Instruction::Getstatic(stderr),
+                Instruction::Swap,
+                Instruction::Invokevirtual(get_message),
+                Instruction::Invokevirtual(println),
+            ]);
+        } else {
+            instructions.push(Instruction::Pop);
+        }
+        instructions.extend([
+            Instruction::Getstatic(stdout),
+            ldc(failed),
+            Instruction::Invokevirtual(println),
+            Instruction::Iinc(2, 1),
+            Instruction::Aload_0,
+            ldc(failure),
+            Instruction::Invokevirtual(append),
+            Instruction::Pop,
+        ]);
+        let next = instructions.len();
+        frames.push((next, Vec::new()));
+        instructions[to_next] = Instruction::Goto(next as u16);
+    }
+
+    // if (failed != 0) { System.out.println(failures); <summary>; System.exit(101); }
+    instructions.extend([Instruction::Iload_2, Instruction::Ifeq(0)]);
+    let to_passed = instructions.len() - 1;
+    instructions.extend([
+        Instruction::Getstatic(stdout),
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(println_object),
+    ]);
+    for status in ["FAILED", "ok"] {
+        if status == "ok" {
+            let all_passed = instructions.len();
+            frames.push((all_passed, Vec::new()));
+            instructions[to_passed] = Instruction::Ifeq(all_passed as u16);
+        }
+        // System.out.println("\ntest result: <status>. " + passed + " passed; " + failed + ..);
+        let result = constant_pool.add_string(format!("\ntest result: {status}. "))?;
+        let passed = constant_pool.add_string(" passed; ")?;
+        let rest = constant_pool.add_string(format!(
+            " failed; {ignored} ignored; 0 measured; 0 filtered out\n"
+        ))?;
+        instructions.extend([
+            Instruction::Getstatic(stdout),
+            Instruction::New(string_builder),
+            Instruction::Dup,
+            ldc(result),
+            Instruction::Invokespecial(string_builder_init),
+            Instruction::Iload_1,
+            Instruction::Invokevirtual(append_int),
+            ldc(passed),
+            Instruction::Invokevirtual(append),
+            Instruction::Iload_2,
+            Instruction::Invokevirtual(append_int),
+            ldc(rest),
+            Instruction::Invokevirtual(append),
+            Instruction::Invokevirtual(to_string),
+            Instruction::Invokevirtual(println),
+        ]);
+        if status == "FAILED" {
+            instructions.extend([
+                Instruction::Bipush(FAILED_EXIT_CODE),
+                Instruction::Invokestatic(exit),
+                Instruction::Return,
+            ]);
+        }
+    }
+    instructions.push(Instruction::Return);
+
+    let locals = [
+        VerificationType::Object {
+            cpool_index: string_builder,
+        },
+        VerificationType::Integer,
+        VerificationType::Integer,
+    ];
+    let stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        RUN_TESTS,
+        "()V",
+        &MethodCode {
+            instructions,
+            exception_table,
+            stack_map_frames,
+        },
+    )
+}
@@ -0,0 +1,37 @@
+//! Crates that run on the host. Build scripts and proc-macro crates are reported as errors, as they
+//! belong on rustc's own backend.
+
+use rustc_session::Session;
+use rustc_session::config::CrateType;
+
+/// The architecture of the targets this backend compiles for
+const JVM_ARCH: &str = "jvm";
+
+const CONFIGURATION_HELP: &str = "set the backend with `default-codegen-backend` in the target \
+                                  spec, as `jvm-unknown-unknown.json` does, rather than with \
+                                  `-Z codegen-backend` in `RUSTFLAGS`, so that build scripts and \
+                                  proc macros are compiled for the host by rustc's own backend";
+
+/// Reports compiling for a target other than the JVM, as for a build script
+pub(crate) fn check_target(sess: &Session) {
+    if sess.target.arch != JVM_ARCH {
+        sess.dcx()
+            .struct_fatal(format!(
+                "the JVM backend cannot compile for `{}`, which build scripts and proc macros are \
+                 compiled for to run on the host",
+                sess.opts.target_triple
+            ))
+            .with_help(CONFIGURATION_HELP)
+            .emit();
+    }
+}
+
+/// Reports compiling a proc-macro crate, which rustc loads into itself
+pub(crate) fn check_crate_types(sess: &Session) {
+    if sess.crate_types().contains(&CrateType::ProcMacro) {
+        sess.dcx()
+            .struct_fatal("the JVM backend cannot compile proc-macro crates, which run on the host")
+            .with_help(CONFIGURATION_HELP)
+            .emit();
+    }
+}
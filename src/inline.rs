@@ -0,0 +1,137 @@
+//! Inlining of tiny crate functions, see [`crate::opt_level`].
+
+use crate::MethodCode;
+use crate::constant_pool::ConstantPool;
+use crate::control_flow;
+use crate::verifier;
+use ristretto_classfile::attributes::Instruction;
+use rustc_data_structures::fx::FxHashMap;
+
+/// The most stack operations an inlined function may have
+const MAX_INLINED_INSTRUCTIONS: usize = 8;
+
+/// Inlines the tiny methods of a class into the calls to them, through the crate class, from any
+/// of its methods, given with their name and descriptor
+pub(crate) fn inline_tiny_methods(
+    constant_pool: &ConstantPool,
+    crate_class: u16,
+    methods: &mut [(&str, &str, &mut MethodCode)],
+) {
+    let mut inlinable = FxHashMap::default();
+    for (name, descriptor, code) in methods.iter() {
+        if let Some(method_ref) = constant_pool.method_ref_index(crate_class, name, descriptor)
+            && let Some(body) = inlinable_body(descriptor, code)
+        {
+            inlinable.insert(method_ref, body);
+        }
+    }
+    if inlinable.is_empty() {
+        return;
+    }
+    for (_, _, code) in methods.iter_mut() {
+        inline_calls(code, &inlinable);
+    }
+}
+
+/// The stack operations of a method that loads its arguments in order, operates on them and
+/// returns, `None` for any other method
+fn inlinable_body(descriptor: &str, code: &MethodCode) -> Option<Vec<Instruction>> {
+    if !code.exception_table.is_empty() || !code.stack_map_frames.is_empty() {
+        return None;
+    }
+    let arguments = descriptor
+        .strip_prefix('(')
+        .and_then(|descriptor| descriptor.split_once(')'))?
+        .0;
+    let (last, instructions) = code.instructions.split_last()?;
+    let returns = matches!(
+        last,
+        Instruction::Return
+            | Instruction::Ireturn
+            | Instruction::Lreturn
+            | Instruction::Freturn
+            | Instruction::Dreturn
+            | Instruction::Areturn
+    );
+    if !returns {
+        return None;
+    }
+
+    let mut slot = 0;
+    let argument_types = verifier::argument_types(arguments);
+    let loads = instructions.get(..argument_types.len())?;
+    for (load, argument) in loads.iter().zip(argument_types) {
+        if verifier::local_access(load) != Some((slot, argument, false)) {
+            return None;
+        }
+        slot += if argument.is_wide() { 2 } else { 1 };
+    }
+    let body = &instructions[loads.len()..];
+    let is_stack_operation = |instruction: &Instruction| {
+        verifier::local_access(instruction).is_none()
+            && control_flow::branch_target(instruction).is_none()
+            && control_flow::falls_through(instruction)
+            && !matches!(
+                instruction,
+                Instruction::Iinc(..)
+                    | Instruction::Iinc_w(..)
+                    | Instruction::Invokestatic(_)
+                    | Instruction::Invokevirtual(_)
+                    | Instruction::Invokespecial(_)
+                    | Instruction::Invokeinterface(..)
+                    | Instruction::Invokedynamic(_)
+                    | Instruction::Tableswitch(_)
+                    | Instruction::Lookupswitch(_)
+            )
+    };
+    if body.len() > MAX_INLINED_INSTRUCTIONS || !body.iter().all(is_stack_operation) {
+        return None;
+    }
+    Some(body.to_vec())
+}
+
+/// Replaces the calls to inlinable methods in a method's code, moving its branch targets,
+/// exception ranges and frames along
+fn inline_calls(code: &mut MethodCode, inlinable: &FxHashMap<u16, Vec<Instruction>>) {
+    let is_inlined_call = |instruction: &Instruction| matches!(instruction, Instruction::Invokestatic(method) if inlinable.contains_key(method));
+    if !code.instructions.iter().any(is_inlined_call) {
+        return;
+    }
+    let Some((locals, frames)) =
+        control_flow::frame_indices(&code.instructions, &code.stack_map_frames)
+    else {
+        return;
+    };
+
+    let mut instructions = Vec::with_capacity(code.instructions.len());
+    let mut new_indices = Vec::with_capacity(code.instructions.len() + 1);
+    for instruction in &code.instructions {
+        new_indices.push(instructions.len());
+        match instruction {
+            Instruction::Invokestatic(method) if inlinable.contains_key(method) => {
+                instructions.extend(inlinable[method].iter().cloned());
+            }
+            _ => instructions.push(instruction.clone()),
+        }
+    }
+    new_indices.push(instructions.len());
+    let new_index = |index: u16| new_indices[usize::from(index)] as u16;
+
+    for instruction in &mut instructions {
+        if let Some(target) = control_flow::branch_target(instruction) {
+            *instruction = control_flow::with_branch_target(instruction, new_index(target));
+        }
+    }
+    code.exception_table.retain_mut(|entry| {
+        entry.range_pc = new_index(entry.range_pc.start)..new_index(entry.range_pc.end);
+        entry.handler_pc = new_index(entry.handler_pc);
+        // A range of calls inlined as nothing
+        !entry.range_pc.is_empty()
+    });
+    let frames = frames
+        .into_iter()
+        .map(|(index, stack)| (new_indices[index], stack))
+        .collect();
+    code.stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+    code.instructions = instructions;
+}
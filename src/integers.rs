@@ -0,0 +1,520 @@
+//! The checked, wrapping, saturating and overflowing arithmetic methods of integers.
+
+use crate::trace::trace;
+use crate::intrinsics::{integer_class, narrowing_instruction};
+use crate::{
+    MirToBytecodeVisitor, control_flow, options, rust_ty_to_jvm_descriptor, slot_instruction,
+};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{BinOp, Operand, Place, Rvalue};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+/// The first class version with `Math.multiplyHigh`, from Java 9
+const MULTIPLY_HIGH_CLASS_VERSION: u16 = 9;
+
+/// Whether `def_id` is one of the `overflowing_*` methods of an integer type
+pub(crate) fn is_overflowing_method(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    tcx.impl_of_method(def_id).is_some_and(|impl_def_id| {
+        tcx.impl_trait_ref(impl_def_id).is_none()
+            && matches!(
+                tcx.type_of(impl_def_id).skip_binder().kind(),
+                TyKind::Int(_) | TyKind::Uint(_)
+            )
+    }) && matches!(
+        tcx.item_name(def_id).as_str(),
+        "overflowing_add" | "overflowing_sub" | "overflowing_mul"
+    )
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of the checked, wrapping, saturating and overflowing arithmetic methods of
+    /// integers, returning `false` for any other call
+    pub(crate) fn lower_integer_method_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        if tcx.impl_trait_ref(impl_def_id).is_some() {
+            return false;
+        }
+        let ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        if !matches!(ty.kind(), TyKind::Int(_) | TyKind::Uint(_)) {
+            return false;
+        }
+        let name = tcx.item_name(callee);
+        let Some((kind, op)) = name.as_str().split_once('_') else {
+            return false;
+        };
+        if !matches!(kind, "checked" | "wrapping" | "saturating" | "overflowing")
+            || !matches!(op, "add" | "sub" | "mul")
+        {
+            return false;
+        }
+        let Some((_, bits)) = integer_class(&rust_ty_to_jvm_descriptor(ty, tcx)) else {
+            trace!(
+                "      Unsupported integer method: {}",
+                tcx.def_path_str(callee)
+            );
+            return false;
+        };
+        let (left, right) = (&args[0].node, &args[1].node);
+
+        match kind {
+            "wrapping" => {
+                self.load_wrapped(op, ty, args);
+                self.store_call_result(destination);
+            }
+            "checked" => {
+                if !self.is_option_destination(destination) {
+                    trace!("      Unsupported destination for checked result: {destination:?}");
+                    return false;
+                }
+                self.load_overflowed(op, ty, bits, left, right);
+                let overflowed = self.method_bytecode_instructions.len();
+                self.method_bytecode_instructions.push(Instruction::Ifne(0));
+                self.load_wrapped(op, ty, args);
+                let descriptor = rust_ty_to_jvm_descriptor(ty, tcx);
+                self.store_option_result(overflowed, &descriptor, destination);
+            }
+            "saturating" => self.lower_saturating(op, ty, bits, args, destination),
+            _ => {
+                let flag_slot = match destination.projection.is_empty() {
+                    true => self.overflow_flag_slots.get(&destination.local).copied(),
+                    false => None,
+                };
+                let Some(flag_slot) = flag_slot else {
+                    trace!(
+                        "      Unsupported destination for overflowing result: {destination:?}"
+                    );
+                    return false;
+                };
+                self.load_wrapped(op, ty, args);
+                self.store_local(destination.local);
+                self.load_overflowed(op, ty, bits, left, right);
+                self.method_bytecode_instructions.push(slot_instruction(
+                    flag_slot,
+                    Instruction::Istore,
+                    Instruction::Istore_w,
+                ));
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        true
+    }
+
+    /// Stores whether the `AddWithOverflow`, `SubWithOverflow` or `MulWithOverflow` assigned to
+    /// `place` overflowed in the flag slot of its local, which only exists with overflow checks
+    pub(crate) fn store_overflow_flag(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>) {
+        let Rvalue::BinaryOp(bin_op, box (left, right)) = rvalue else {
+            return;
+        };
+        let op = match bin_op {
+            BinOp::AddWithOverflow => "add",
+            BinOp::SubWithOverflow => "sub",
+            BinOp::MulWithOverflow => "mul",
+            _ => return,
+        };
+        let Some(&flag_slot) = self.overflow_flag_slots.get(&place.local) else {
+            return;
+        };
+        let ty = left.ty(self.body, self.tcx);
+        match integer_class(&rust_ty_to_jvm_descriptor(ty, self.tcx)) {
+            Some((_, bits)) => self.load_overflowed(op, ty, bits, left, right),
+            None => {
+                // `i128` and `u128` are not supported yet, their flag stays false
+                trace!("      Unsupported overflow check for: {ty:?}");
+                self.method_bytecode_instructions.push(Instruction::Iconst_0);
+            }
+        }
+        self.method_bytecode_instructions.push(slot_instruction(
+            flag_slot,
+            Instruction::Istore,
+            Instruction::Istore_w,
+        ));
+    }
+
+    /// `saturating_*`: the wrapped result, or the bound of the type it overflowed past
+    fn lower_saturating(
+        &mut self,
+        op: &str,
+        ty: Ty<'tcx>,
+        bits: u32,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) {
+        let (left, right) = (&args[0].node, &args[1].node);
+        self.load_overflowed(op, ty, bits, left, right);
+        let not_overflowed = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Ifeq(0));
+        self.load_saturated(op, ty, bits, left, right);
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        let wrapped = self.method_bytecode_instructions.len();
+        self.extra_frames.push((wrapped, Vec::new()));
+        self.load_wrapped(op, ty, args);
+
+        let end = self.method_bytecode_instructions.len();
+        let descriptor = rust_ty_to_jvm_descriptor(ty, self.tcx);
+        let end_stack = vec![
+            control_flow::verification_type(&descriptor, self.constant_pool)
+                .expect("Could not add class to constant pool"),
+        ];
+        self.extra_frames.push((end, end_stack));
+        self.method_bytecode_instructions[not_overflowed] = Instruction::Ifeq(wrapped as u16);
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        self.store_call_result(destination);
+    }
+
+    /// Pushes the result of `op` wrapped around at the bounds of `ty`
+    fn load_wrapped(&mut self, op: &str, ty: Ty<'tcx>, args: &[Spanned<Operand<'tcx>>]) {
+        let lowered = self.lower_integer_intrinsic(&format!("wrapping_{op}"), ty, args);
+        debug_assert!(lowered, "wrapping arithmetic is supported for every integer");
+    }
+
+    /// Pushes 1 if `op` overflows for the operands, 0 otherwise
+    fn load_overflowed(
+        &mut self,
+        op: &str,
+        ty: Ty<'tcx>,
+        bits: u32,
+        left: &Operand<'tcx>,
+        right: &Operand<'tcx>,
+    ) {
+        let signed = ty.is_signed();
+        if bits < 64 {
+            // Exact as a long, overflowed if truncating it changes it
+            self.load_widened(left, bits, signed);
+            self.load_widened(right, bits, signed);
+            self.method_bytecode_instructions.extend([
+                long_instruction(op),
+                Instruction::Dup2,
+                Instruction::L2i,
+            ]);
+            self.method_bytecode_instructions
+                .extend(narrowing_instruction(bits));
+            self.widen_to_long(bits, signed);
+            self.method_bytecode_instructions
+                .extend([Instruction::Lcmp, Instruction::Iconst_1, Instruction::Iand]);
+            return;
+        }
+
+        match (op, signed) {
+            ("add", true) => {
+                // ((left ^ result) & (right ^ result)) < 0
+                self.load_operand(left);
+                self.load_long_result(op, left, right);
+                self.method_bytecode_instructions.push(Instruction::Lxor);
+                self.load_operand(right);
+                self.load_long_result(op, left, right);
+                self.method_bytecode_instructions
+                    .extend([Instruction::Lxor, Instruction::Land]);
+                self.long_is_negative();
+            }
+            ("sub", true) => {
+                // ((left ^ right) & (left ^ result)) < 0
+                self.load_operand(left);
+                self.load_operand(right);
+                self.method_bytecode_instructions.push(Instruction::Lxor);
+                self.load_operand(left);
+                self.load_long_result(op, left, right);
+                self.method_bytecode_instructions
+                    .extend([Instruction::Lxor, Instruction::Land]);
+                self.long_is_negative();
+            }
+            ("mul", true) => {
+                // Math.multiplyHigh(left, right) != result >> 63
+                self.load_multiply_high(left, right);
+                self.load_long_result(op, left, right);
+                self.method_bytecode_instructions
+                    .extend([Instruction::Bipush(63), Instruction::Lshr]);
+                self.long_is_not_equal();
+            }
+            ("add", false) => {
+                // Long.compareUnsigned(result, left) < 0
+                self.load_long_result(op, left, right);
+                self.load_operand(left);
+                self.invoke_static("java/lang/Long", "compareUnsigned", "(JJ)I");
+                self.method_bytecode_instructions
+                    .extend([Instruction::Bipush(31), Instruction::Iushr]);
+            }
+            ("sub", false) => {
+                // Long.compareUnsigned(left, right) < 0
+                self.load_operand(left);
+                self.load_operand(right);
+                self.invoke_static("java/lang/Long", "compareUnsigned", "(JJ)I");
+                self.method_bytecode_instructions
+                    .extend([Instruction::Bipush(31), Instruction::Iushr]);
+            }
+            _ => {
+                // The unsigned high bits: Math.multiplyHigh(left, right)
+                //   + ((left >> 63) & right) + ((right >> 63) & left) != 0
+                self.load_multiply_high(left, right);
+                for (sign, other) in [(left, right), (right, left)] {
+                    self.load_operand(sign);
+                    self.method_bytecode_instructions
+                        .extend([Instruction::Bipush(63), Instruction::Lshr]);
+                    self.load_operand(other);
+                    self.method_bytecode_instructions
+                        .extend([Instruction::Land, Instruction::Ladd]);
+                }
+                self.method_bytecode_instructions.push(Instruction::Lconst_0);
+                self.long_is_not_equal();
+            }
+        }
+    }
+
+    /// Pushes the bound of `ty` that `op` overflowed past: the maximum, or the minimum for
+    /// unsigned subtractions and for signed results that overflowed below it
+    fn load_saturated(
+        &mut self,
+        op: &str,
+        ty: Ty<'tcx>,
+        bits: u32,
+        left: &Operand<'tcx>,
+        right: &Operand<'tcx>,
+    ) {
+        let is_long = bits == 64;
+        if !ty.is_signed() {
+            // All ones for the maximum, zero for the minimum
+            let bound = if op == "sub" { 0 } else { -1 };
+            let bound = if is_long {
+                self.long_constant(bound)
+            } else {
+                self.int_constant(bound as i32)
+            };
+            self.method_bytecode_instructions.push(bound);
+            return;
+        }
+
+        // The maximum or the minimum, flipping all of its bits with the sign of the operand
+        // deciding the direction: `right` for additions, `!right` for subtractions and
+        // `left ^ right` for multiplications
+        let max = (1i64 << (bits - 1)) - 1;
+        let (bound, sign_operands) = match op {
+            "add" => (max, vec![right]),
+            "sub" => (!max, vec![right]),
+            _ => (max, vec![left, right]),
+        };
+        let bound = if is_long {
+            self.long_constant(bound)
+        } else {
+            self.int_constant(bound as i32)
+        };
+        self.method_bytecode_instructions.push(bound);
+        for operand in &sign_operands {
+            self.load_operand(operand);
+        }
+        let (xor, shift_right, sign_bit) = if is_long {
+            (Instruction::Lxor, Instruction::Lshr, 63)
+        } else {
+            (Instruction::Ixor, Instruction::Ishr, 31)
+        };
+        if sign_operands.len() == 2 {
+            self.method_bytecode_instructions.push(xor.clone());
+        }
+        self.method_bytecode_instructions
+            .extend([Instruction::Bipush(sign_bit), shift_right, xor]);
+    }
+
+    /// Pushes the wrapped result of `op` on two longs
+    fn load_long_result(&mut self, op: &str, left: &Operand<'tcx>, right: &Operand<'tcx>) {
+        self.load_operand(left);
+        self.load_operand(right);
+        self.method_bytecode_instructions.push(long_instruction(op));
+    }
+
+    /// Pushes the high long of the signed 128-bit product of two longs, with
+    /// `Math.multiplyHigh` from Java 9 on and from their 32-bit halves before
+    fn load_multiply_high(&mut self, left: &Operand<'tcx>, right: &Operand<'tcx>) {
+        if options::get().class_version >= MULTIPLY_HIGH_CLASS_VERSION {
+            self.load_operand(left);
+            self.load_operand(right);
+            self.invoke_static("java/lang/Math", "multiplyHigh", "(JJ)J");
+            return;
+        }
+        // high(left) * high(right) + (cross >> 32)
+        //   + ((low(left) * high(right) + (cross & 0xFFFFFFFF)) >> 32)
+        self.load_half(left, true);
+        self.load_half(right, true);
+        self.method_bytecode_instructions.push(Instruction::Lmul);
+        self.load_cross_product(left, right);
+        self.method_bytecode_instructions
+            .extend([Instruction::Bipush(32), Instruction::Lshr, Instruction::Ladd]);
+        self.load_half(left, false);
+        self.load_half(right, true);
+        self.method_bytecode_instructions.push(Instruction::Lmul);
+        self.load_cross_product(left, right);
+        let mask = self.long_constant(0xFFFF_FFFF);
+        self.method_bytecode_instructions.extend([
+            mask,
+            Instruction::Land,
+            Instruction::Ladd,
+            Instruction::Bipush(32),
+            Instruction::Lshr,
+            Instruction::Ladd,
+        ]);
+    }
+
+    /// Pushes `high(left) * low(right) + (low(left) * low(right) >>> 32)`, the middle part of
+    /// the product [`MirToBytecodeVisitor::load_multiply_high`] carries into the high long
+    fn load_cross_product(&mut self, left: &Operand<'tcx>, right: &Operand<'tcx>) {
+        self.load_half(left, true);
+        self.load_half(right, false);
+        self.method_bytecode_instructions.push(Instruction::Lmul);
+        self.load_half(left, false);
+        self.load_half(right, false);
+        self.method_bytecode_instructions.extend([
+            Instruction::Lmul,
+            Instruction::Bipush(32),
+            Instruction::Lushr,
+            Instruction::Ladd,
+        ]);
+    }
+
+    /// Pushes the signed high or the unsigned low 32 bits of a long operand, as a long
+    fn load_half(&mut self, operand: &Operand<'tcx>, high: bool) {
+        self.load_operand(operand);
+        if high {
+            self.method_bytecode_instructions
+                .extend([Instruction::Bipush(32), Instruction::Lshr]);
+        } else {
+            let mask = self.long_constant(0xFFFF_FFFF);
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Land]);
+        }
+    }
+
+    /// Turns the long on top of the stack into 1 if it is negative, 0 otherwise
+    fn long_is_negative(&mut self) {
+        self.method_bytecode_instructions.extend([
+            Instruction::Bipush(63),
+            Instruction::Lushr,
+            Instruction::L2i,
+        ]);
+    }
+
+    /// Turns the two longs on top of the stack into 1 if they differ, 0 otherwise
+    fn long_is_not_equal(&mut self) {
+        self.method_bytecode_instructions
+            .extend([Instruction::Lcmp, Instruction::Iconst_1, Instruction::Iand]);
+    }
+
+    /// Pushes the result of a shift. The MIR operators take the amount modulo the width of the
+    /// shifted integer, as the JVM does for `int`s and `long`s; `byte`s and `short`s are shifted as
+    /// ints, so their amount is masked to their own width, and unsigned ones are zero-extended
+    /// before shifting right
+    pub(crate) fn lower_shift(
+        &mut self,
+        bin_op: BinOp,
+        left: &Operand<'tcx>,
+        right: &Operand<'tcx>,
+    ) -> bool {
+        let ty = left.ty(self.body, self.tcx);
+        let amount_descriptor = rust_ty_to_jvm_descriptor(right.ty(self.body, self.tcx), self.tcx);
+        let (Some((_, bits)), Some(_)) = (
+            integer_class(&rust_ty_to_jvm_descriptor(ty, self.tcx)),
+            integer_class(&amount_descriptor),
+        ) else {
+            trace!("      Unsupported shift: {bin_op:?} of {ty}");
+            self.record_unsupported("binary-operation", format_args!("{bin_op:?} of `{ty}`"));
+            return false;
+        };
+        let signed = ty.is_signed();
+        let shift_left = matches!(bin_op, BinOp::Shl | BinOp::ShlUnchecked);
+        self.load_operand(left);
+        if !shift_left && !signed && bits < 32 {
+            let mask = self.int_constant((1 << bits) - 1);
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Iand]);
+        }
+        self.load_operand(right);
+        if amount_descriptor == "J" {
+            self.method_bytecode_instructions.push(Instruction::L2i);
+        }
+        if bits < 32 {
+            let mask = self.int_constant(i32::try_from(bits - 1).expect("narrower than an int"));
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Iand]);
+        }
+        let instruction = match (bits, shift_left, signed) {
+            (64, true, _) => Instruction::Lshl,
+            (64, false, true) => Instruction::Lshr,
+            (64, false, false) => Instruction::Lushr,
+            (_, true, _) => Instruction::Ishl,
+            (_, false, true) => Instruction::Ishr,
+            (_, false, false) => Instruction::Iushr,
+        };
+        self.method_bytecode_instructions.push(instruction);
+        self.method_bytecode_instructions
+            .extend(narrowing_instruction(bits));
+        true
+    }
+
+    /// Pushes an integer, `bool` or `char` operand cast to an integer or `char` type, as `as`
+    /// does: truncated to a narrower type, or extended to a wider one by the operand's signedness
+    pub(crate) fn load_int_cast(&mut self, operand: &Operand<'tcx>, target_ty: Ty<'tcx>) -> bool {
+        let source_ty = operand.ty(self.body, self.tcx);
+        let source = rust_ty_to_jvm_descriptor(source_ty, self.tcx);
+        let target = rust_ty_to_jvm_descriptor(target_ty, self.tcx);
+        let (Some(source_bits), Some(_)) = (int_bits(&source), int_bits(&target)) else {
+            trace!("      Unsupported cast: {source_ty} as {target_ty}");
+            self.record_unsupported("cast", format_args!("`{source_ty}` as `{target_ty}`"));
+            return false;
+        };
+        let signed = source_ty.is_signed();
+        self.load_operand(operand);
+        if target == "J" {
+            if source != "J" {
+                self.widen_to_long(source_bits, signed);
+            }
+            return true;
+        }
+        if source == "J" {
+            self.method_bytecode_instructions.push(Instruction::L2i);
+        } else if !signed && matches!(source.as_str(), "B" | "S") {
+            // Unsigned `byte`s and `short`s are held sign-extended
+            let mask = self.int_constant((1 << source_bits) - 1);
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Iand]);
+        }
+        if source != target {
+            match target.as_str() {
+                "B" => self.method_bytecode_instructions.push(Instruction::I2b),
+                "S" => self.method_bytecode_instructions.push(Instruction::I2s),
+                "C" => self.method_bytecode_instructions.push(Instruction::I2c),
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+/// The width in bits of the values of an integer, `bool` or `char` descriptor
+fn int_bits(descriptor: &str) -> Option<u32> {
+    match descriptor {
+        "Z" => Some(1),
+        "B" => Some(8),
+        "C" | "S" => Some(16),
+        "I" => Some(32),
+        "J" => Some(64),
+        _ => None,
+    }
+}
+
+/// The long instruction of an arithmetic operation
+fn long_instruction(op: &str) -> Instruction {
+    match op {
+        "add" => Instruction::Ladd,
+        "sub" => Instruction::Lsub,
+        _ => Instruction::Lmul,
+    }
+}
@@ -0,0 +1,232 @@
+//! Calls into Java code, whose methods are imported with `#[jvm::import("<class>")]` on `extern`
+//! blocks.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, attributes, method_descriptor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction, VerificationType};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+/// The internal name of the Java class a foreign function is imported from, if the function or
+/// its extern block carries `#[jvm::import("...")]`
+pub(crate) fn import_class(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    if !tcx.is_foreign_item(def_id) {
+        return None;
+    }
+    attributes::jvm_attr_string_args(tcx, def_id, "import")
+        .into_iter()
+        .chain(attributes::jvm_attr_string_args(
+            tcx,
+            tcx.parent(def_id),
+            "import",
+        ))
+        .next()
+        .map(|class_name| attributes::internal_class_name(&class_name))
+}
+
+/// The name of the Java method an imported function calls
+fn java_method_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    tcx.codegen_fn_attrs(def_id)
+        .link_name
+        .unwrap_or_else(|| tcx.item_name(def_id))
+        .to_string()
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers a call to a function imported from the Java class `class_name`
+    pub(crate) fn lower_java_call(
+        &mut self,
+        callee: DefId,
+        class_name: &str,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) {
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+
+        if attributes::has_jvm_attr(self.tcx, callee, "throw") {
+            // new Exception(args...), then throw it
+            let mut descriptor = method_descriptor(self.tcx, callee, false);
+            descriptor.truncate(descriptor.rfind(')').expect("Invalid method descriptor") + 1);
+            descriptor.push('V');
+            let constructor_ref = self
+                .constant_pool
+                .add_method_ref(class_index, "<init>", &descriptor)
+                .expect("Could not add constructor to constant pool");
+            self.method_bytecode_instructions
+                .push(Instruction::New(class_index));
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            for arg in args {
+                self.load_operand(&arg.node);
+            }
+            self.method_bytecode_instructions
+                .push(Instruction::Invokespecial(constructor_ref));
+            self.method_bytecode_instructions.push(Instruction::Athrow);
+            trace!("      Generated bytecode: throw new {class_name}");
+            return;
+        }
+
+        let method_name = java_method_name(self.tcx, callee);
+        if !attributes::has_jvm_attr(self.tcx, callee, "catch") {
+            let descriptor = method_descriptor(self.tcx, callee, false);
+            let method_ref = self
+                .constant_pool
+                .add_method_ref(class_index, &method_name, &descriptor)
+                .expect("Could not add method ref to constant pool");
+            for arg in args {
+                self.load_operand(&arg.node);
+            }
+            self.method_bytecode_instructions
+                .push(Instruction::Invokestatic(method_ref));
+            trace!("      Generated bytecode: invokestatic {class_name}.{method_name}");
+            self.store_call_result(destination);
+            return;
+        }
+
+        self.lower_fallible_java_call(callee, class_index, &method_name, args, destination);
+    }
+
+    /// Lowers a call to an import marked `#[jvm::catch]` into a try/catch filling in the `Result`
+    /// it returns
+    fn lower_fallible_java_call(
+        &mut self,
+        callee: DefId,
+        class_index: u16,
+        method_name: &str,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) {
+        let result_ty = self.tcx.fn_sig(callee).skip_binder().output().skip_binder();
+        let TyKind::Adt(adt_def, generic_args) = result_ty.kind() else {
+            self.tcx.dcx().span_err(
+                self.tcx.def_span(callee),
+                "imports marked `#[jvm::catch]` must return a `Result`",
+            );
+            return;
+        };
+        let variant_named = |name: &str| {
+            adt_def
+                .variants()
+                .iter_enumerated()
+                .find(|(_, variant)| variant.name.as_str() == name)
+                .map(|(variant_index, _)| variant_index)
+        };
+        let (Some(ok_variant), Some(err_variant)) = (variant_named("Ok"), variant_named("Err"))
+        else {
+            self.tcx.dcx().span_err(
+                self.tcx.def_span(callee),
+                "imports marked `#[jvm::catch]` must return a `Result`",
+            );
+            return;
+        };
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for fallible call: {destination:?}");
+            return;
+        }
+
+        // The Java method returns the `Ok` type
+        let mut descriptor = method_descriptor(self.tcx, callee, false);
+        descriptor.truncate(descriptor.rfind(')').expect("Invalid method descriptor") + 1);
+        descriptor.push_str(&rust_ty_to_jvm_descriptor(
+            generic_args.type_at(0),
+            self.tcx,
+        ));
+        let method_ref = self
+            .constant_pool
+            .add_method_ref(class_index, method_name, &descriptor)
+            .expect("Could not add method ref to constant pool");
+
+        let mut exception_classes: Vec<String> =
+            attributes::jvm_attr_string_args(self.tcx, callee, "catch")
+                .iter()
+                .map(|class_name| attributes::internal_class_name(class_name))
+                .collect();
+        if exception_classes.is_empty() {
+            exception_classes.push("java/lang/Throwable".to_string());
+        }
+
+        // try { destination = Ok(call(args...)); }
+        for arg in args {
+            self.load_operand(&arg.node);
+        }
+        let invoke_index = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions
+            .push(Instruction::Invokestatic(method_ref));
+        let field = rustc_abi::FieldIdx::from_u32(0);
+        if self
+            .enum_field_slots
+            .contains_key(&(destination.local, ok_variant, field))
+        {
+            self.store_enum_field(destination.local, ok_variant, field);
+        } else {
+            match descriptor.as_bytes().last() {
+                Some(b'V') => {}
+                Some(b'J' | b'D') => self.method_bytecode_instructions.push(Instruction::Pop2),
+                _ => self.method_bytecode_instructions.push(Instruction::Pop),
+            }
+        }
+        self.store_discriminant(destination.local, ok_variant);
+        let goto_index = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        // catch (exception) { destination = Err(exception); }
+        let handler_index = self.method_bytecode_instructions.len();
+        let handler_class = if exception_classes.len() == 1 {
+            exception_classes[0].as_str()
+        } else {
+            "java/lang/Throwable"
+        };
+        let handler_class_index = self
+            .constant_pool
+            .add_class(handler_class)
+            .expect("Could not add class to constant pool");
+        self.extra_frames.push((
+            handler_index,
+            vec![VerificationType::Object {
+                cpool_index: handler_class_index,
+            }],
+        ));
+        for exception_class in &exception_classes {
+            let catch_type = self
+                .constant_pool
+                .add_class(exception_class)
+                .expect("Could not add class to constant pool");
+            self.exception_table.push(ExceptionTableEntry {
+                range_pc: invoke_index as u16..invoke_index as u16 + 1,
+                handler_pc: handler_index as u16,
+                catch_type,
+            });
+        }
+        match self
+            .enum_field_slots
+            .get(&(destination.local, err_variant, field))
+            .map(|(_, descriptor)| descriptor.as_str())
+        {
+            None => self.method_bytecode_instructions.push(Instruction::Pop),
+            Some("Ljava/lang/String;") => {
+                let throwable = self
+                    .constant_pool
+                    .add_class("java/lang/Throwable")
+                    .expect("Could not add class to constant pool");
+                let to_string = self
+                    .constant_pool
+                    .add_method_ref(throwable, "toString", "()Ljava/lang/String;")
+                    .expect("Could not add method ref to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokevirtual(to_string));
+                self.store_enum_field(destination.local, err_variant, field);
+            }
+            Some(_) => self.store_enum_field(destination.local, err_variant, field),
+        }
+        self.store_discriminant(destination.local, err_variant);
+
+        let end_index = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions[goto_index] = Instruction::Goto(end_index as u16);
+        self.extra_frames.push((end_index, Vec::new()));
+        trace!("      Generated bytecode: try/catch around invokestatic {method_name}");
+    }
+}
@@ -0,0 +1,316 @@
+//! Calls of rustc intrinsics, dispatched by name to JDK methods or to short inline bytecode
+//! sequences.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty};
+use rustc_span::source_map::Spanned;
+
+/// Float intrinsics implemented by `java.lang.Math`, by their name without the `f32` or `f64`
+/// suffix: the `Math` method, its number of arguments, and whether it has a `float` overload
+/// (otherwise `f32`s are computed as `double`s)
+const MATH_INTRINSICS: [(&str, &str, usize, bool); 13] = [
+    ("sqrt", "sqrt", 1, false),
+    ("fabs", "abs", 1, true),
+    ("copysign", "copySign", 2, true),
+    ("minnum", "min", 2, true),
+    ("maxnum", "max", 2, true),
+    ("floor", "floor", 1, false),
+    ("ceil", "ceil", 1, false),
+    ("sin", "sin", 1, false),
+    ("cos", "cos", 1, false),
+    ("exp", "exp", 1, false),
+    ("log", "log", 1, false),
+    ("log10", "log10", 1, false),
+    ("pow", "pow", 2, false),
+];
+
+/// The JVM wrapper class with the bit manipulation methods for an integer descriptor, and the
+/// width of the Rust integer in bits
+pub(crate) fn integer_class(descriptor: &str) -> Option<(&'static str, u32)> {
+    match descriptor {
+        "B" => Some(("java/lang/Integer", 8)),
+        "S" => Some(("java/lang/Integer", 16)),
+        "I" => Some(("java/lang/Integer", 32)),
+        "J" => Some(("java/lang/Long", 64)),
+        _ => None,
+    }
+}
+
+/// The instruction truncating an int to a narrow integer, sign-extending it again as the JVM
+/// keeps `byte`s and `short`s
+pub(crate) fn narrowing_instruction(bits: u32) -> Option<Instruction> {
+    match bits {
+        8 => Some(Instruction::I2b),
+        16 => Some(Instruction::I2s),
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of intrinsics, returning `false` for any other call and for unsupported
+    /// intrinsics
+    pub(crate) fn lower_intrinsic_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let Some(intrinsic) = self.tcx.intrinsic(callee) else {
+            return false;
+        };
+        let name = intrinsic.name.to_string();
+        if !self.lower_intrinsic(&name, generic_args, args) {
+            trace!("      Unsupported intrinsic: {name}");
+            return false;
+        }
+        trace!("      Generated bytecode: intrinsic {name}");
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Pushes the result of the intrinsic `name`, returning `false` if it is not supported
+    fn lower_intrinsic(
+        &mut self,
+        name: &str,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+    ) -> bool {
+        match name {
+            "black_box" | "likely" | "unlikely" => {
+                self.load_operand(&args[0].node);
+                return true;
+            }
+            "assume" => return true,
+            "transmute" | "transmute_unchecked" => {
+                return self.load_transmuted(
+                    &args[0].node,
+                    generic_args.type_at(0),
+                    generic_args.type_at(1),
+                );
+            }
+            _ if name.starts_with("simd_") => {
+                return self.lower_simd_intrinsic(name, generic_args, args);
+            }
+            _ => {}
+        }
+
+        if let Some((base, precision)) = name
+            .strip_suffix("f32")
+            .map(|base| (base, "F"))
+            .or_else(|| name.strip_suffix("f64").map(|base| (base, "D")))
+        {
+            let Some((_, method, arity, has_float)) = MATH_INTRINSICS
+                .iter()
+                .find(|(intrinsic, ..)| *intrinsic == base)
+            else {
+                return false;
+            };
+            let operands: Vec<_> = args[..*arity].iter().map(|arg| &arg.node).collect();
+            self.invoke_math(method, &operands, precision, *has_float);
+            return true;
+        }
+
+        self.lower_integer_intrinsic(name, generic_args.type_at(0), args)
+    }
+
+    /// Pushes the result of the integer intrinsic `name` on `ty`, returning `false` if it is not
+    /// supported
+    pub(crate) fn lower_integer_intrinsic(
+        &mut self,
+        name: &str,
+        ty: Ty<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+    ) -> bool {
+        let descriptor = rust_ty_to_jvm_descriptor(ty, self.tcx);
+        let Some((class, bits)) = integer_class(&descriptor) else {
+            return false;
+        };
+        let is_long = bits == 64;
+        let computed = if is_long { "J" } else { "I" };
+        match name {
+            "ctpop" => {
+                self.load_zero_extended(&args[0].node, bits);
+                self.invoke_static(class, "bitCount", &format!("({computed})I"));
+            }
+            "ctlz" | "ctlz_nonzero" => {
+                self.load_zero_extended(&args[0].node, bits);
+                self.invoke_static(class, "numberOfLeadingZeros", &format!("({computed})I"));
+                if bits < 32 {
+                    // Leading zeros of the upper, zero-extended bits don't count
+                    let extension = self.int_constant(32 - bits as i32);
+                    self.method_bytecode_instructions.push(extension);
+                    self.method_bytecode_instructions.push(Instruction::Isub);
+                }
+            }
+            "cttz" | "cttz_nonzero" => {
+                self.load_operand(&args[0].node);
+                if bits < 32 {
+                    // A bit right above the value caps the count at its width
+                    let cap = self.int_constant(1 << bits);
+                    self.method_bytecode_instructions.push(cap);
+                    self.method_bytecode_instructions.push(Instruction::Ior);
+                }
+                self.invoke_static(class, "numberOfTrailingZeros", &format!("({computed})I"));
+            }
+            "bswap" | "bitreverse" => {
+                self.load_operand(&args[0].node);
+                if bits == 8 && name == "bswap" {
+                    return true;
+                }
+                let method = if name == "bswap" {
+                    "reverseBytes"
+                } else {
+                    "reverse"
+                };
+                self.invoke_static(class, method, &format!("({computed}){computed}"));
+                if bits < 32 {
+                    // The result is in the upper bits
+                    let shift = self.int_constant(32 - bits as i32);
+                    self.method_bytecode_instructions.push(shift);
+                    self.method_bytecode_instructions.push(Instruction::Ishr);
+                }
+            }
+            "rotate_left" | "rotate_right" if bits >= 32 => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                let method = if name == "rotate_left" {
+                    "rotateLeft"
+                } else {
+                    "rotateRight"
+                };
+                self.invoke_static(class, method, &format!("({computed}I){computed}"));
+            }
+            "wrapping_add" | "wrapping_sub" | "wrapping_mul" => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                let instruction = match (name, is_long) {
+                    ("wrapping_add", false) => Instruction::Iadd,
+                    ("wrapping_add", true) => Instruction::Ladd,
+                    ("wrapping_sub", false) => Instruction::Isub,
+                    ("wrapping_sub", true) => Instruction::Lsub,
+                    (_, false) => Instruction::Imul,
+                    (_, true) => Instruction::Lmul,
+                };
+                self.method_bytecode_instructions.push(instruction);
+                self.method_bytecode_instructions
+                    .extend(narrowing_instruction(bits));
+            }
+            "saturating_add" | "saturating_sub" if !is_long => {
+                // Computed exactly as longs, then clamped to the range of the type
+                let signed = ty.is_signed();
+                self.load_widened(&args[0].node, bits, signed);
+                self.load_widened(&args[1].node, bits, signed);
+                self.method_bytecode_instructions
+                    .push(if name == "saturating_add" {
+                        Instruction::Ladd
+                    } else {
+                        Instruction::Lsub
+                    });
+                let (min, max) = if signed {
+                    (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1)
+                } else {
+                    (0, (1i64 << bits) - 1)
+                };
+                let max = self.long_constant(max);
+                self.method_bytecode_instructions.push(max);
+                self.invoke_static("java/lang/Math", "min", "(JJ)J");
+                let min = self.long_constant(min);
+                self.method_bytecode_instructions.push(min);
+                self.invoke_static("java/lang/Math", "max", "(JJ)J");
+                self.method_bytecode_instructions.push(Instruction::L2i);
+                self.method_bytecode_instructions
+                    .extend(narrowing_instruction(bits));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Pushes `operand` reinterpreted as `to_ty`, for types with the same layout, returning
+    /// `false` if the JVM values are not interchangeable
+    pub(crate) fn load_transmuted(
+        &mut self,
+        operand: &Operand<'tcx>,
+        from_ty: Ty<'tcx>,
+        to_ty: Ty<'tcx>,
+    ) -> bool {
+        let from = rust_ty_to_jvm_descriptor(from_ty, self.tcx);
+        let to = rust_ty_to_jvm_descriptor(to_ty, self.tcx);
+        let conversion = match (from.as_str(), to.as_str()) {
+            (from, to) if from == to => None,
+            ("F", "I") => Some(("java/lang/Float", "floatToRawIntBits", "(F)I")),
+            ("I", "F") => Some(("java/lang/Float", "intBitsToFloat", "(I)F")),
+            ("D", "J") => Some(("java/lang/Double", "doubleToRawLongBits", "(D)J")),
+            ("J", "D") => Some(("java/lang/Double", "longBitsToDouble", "(J)D")),
+            _ => {
+                self.tcx
+                    .dcx()
+                    .struct_span_err(
+                        self.span,
+                        format!(
+                            "transmuting `{from_ty}` into `{to_ty}` is not supported on the JVM"
+                        ),
+                    )
+                    .with_note(format!(
+                        "`{from_ty}` is a JVM `{from}` and `{to_ty}` a JVM `{to}`; only values of \
+                         the same JVM type, and floats and integers of the same width, can be \
+                         transmuted"
+                    ))
+                    .emit();
+                return false;
+            }
+        };
+        self.load_operand(operand);
+        if let Some((class, method, descriptor)) = conversion {
+            self.invoke_static(class, method, descriptor);
+        }
+        true
+    }
+
+    /// Pushes an integer operand of the given width, zero-extended if it is narrower than an int
+    fn load_zero_extended(&mut self, operand: &Operand<'tcx>, bits: u32) {
+        self.load_operand(operand);
+        if bits < 32 {
+            let mask = self.int_constant((1 << bits) - 1);
+            self.method_bytecode_instructions.push(mask);
+            self.method_bytecode_instructions.push(Instruction::Iand);
+        }
+    }
+
+    /// Pushes an int operand of the given width as a long, with its Rust signedness
+    pub(crate) fn load_widened(&mut self, operand: &Operand<'tcx>, bits: u32, signed: bool) {
+        self.load_operand(operand);
+        self.widen_to_long(bits, signed);
+    }
+
+    /// Widens the int of the given width on top of the stack to a long, with its Rust signedness
+    pub(crate) fn widen_to_long(&mut self, bits: u32, signed: bool) {
+        if signed {
+            self.method_bytecode_instructions.push(Instruction::I2l);
+        } else if bits < 32 {
+            let mask = self.int_constant((1 << bits) - 1);
+            self.method_bytecode_instructions
+                .extend([mask, Instruction::Iand, Instruction::I2l]);
+        } else {
+            self.invoke_static("java/lang/Integer", "toUnsignedLong", "(I)J");
+        }
+    }
+
+    pub(crate) fn invoke_static(&mut self, class_name: &str, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokestatic(method));
+    }
+}
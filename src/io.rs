@@ -0,0 +1,486 @@
+//! `std::fs` and `std::io` on top of `java.io` and `java.nio`.
+
+use crate::runtime::RuntimeClass;
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, control_flow, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction};
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+const RANDOM_ACCESS_FILE: &str = "java/io/RandomAccessFile";
+const BUFFERED_READER: &str = "java/io/BufferedReader";
+const IO_EXCEPTION: &str = "java/io/IOException";
+const PRINT_STREAM: &str = "java/io/PrintStream";
+
+/// The standard output and error streams, which `Write` to a `PrintStream`
+const STD_STREAMS: [&str; 4] = [
+    "std::io::Stdout",
+    "std::io::StdoutLock",
+    "std::io::Stderr",
+    "std::io::StderrLock",
+];
+
+/// The path of an ADT, e.g. `std::fs::File`
+fn adt_path(ty: Ty<'_>, tcx: TyCtxt<'_>) -> Option<String> {
+    match ty.peel_refs().kind() {
+        TyKind::Adt(adt_def, _) => Some(tcx.def_path_str(adt_def.did())),
+        _ => None,
+    }
+}
+
+/// The descriptor of the Java type an I/O type is lowered to
+pub(crate) fn java_descriptor(ty: Ty<'_>, tcx: TyCtxt<'_>) -> Option<String> {
+    let TyKind::Adt(_, generic_args) = ty.kind() else {
+        return None;
+    };
+    match adt_path(ty, tcx)?.as_str() {
+        "std::fs::File" => Some(format!("L{RANDOM_ACCESS_FILE};")),
+        "std::io::BufReader" => Some(format!("L{BUFFERED_READER};")),
+        "std::io::BufWriter" => Some(rust_ty_to_jvm_descriptor(generic_args.type_at(0), tcx)),
+        "std::io::Error" => Some(format!("L{IO_EXCEPTION};")),
+        "std::io::Stdin" | "std::io::StdinLock" => Some(format!("L{BUFFERED_READER};")),
+        "std::io::Stdout" | "std::io::StdoutLock" | "std::io::Stderr" | "std::io::StderrLock" => {
+            Some(format!("L{PRINT_STREAM};"))
+        }
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of the supported `std::fs` and `std::io` functions, returning `false` for any
+    /// other call
+    pub(crate) fn lower_io_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let name = tcx.item_name(callee);
+        let path = tcx.def_path_str(callee);
+        let self_path = if tcx.trait_of_item(callee).is_some() {
+            adt_path(generic_args.type_at(0), tcx)
+        } else {
+            tcx.impl_of_method(callee)
+                .and_then(|impl_def_id| adt_path(tcx.type_of(impl_def_id).skip_binder(), tcx))
+        };
+        let lowered = match (path.as_str(), self_path.as_deref(), name.as_str()) {
+            ("std::fs::read_to_string", ..) => self.try_io(destination, |visitor| {
+                // new StringBuilder(new String(Files.readAllBytes(Paths.get(path)), UTF_8))
+                visitor.push_new_string_builder();
+                visitor.load_path(&args[0].node);
+                visitor.invoke_static(
+                    "java/nio/file/Files",
+                    "readAllBytes",
+                    "(Ljava/nio/file/Path;)[B",
+                );
+                visitor.decode_utf_8();
+                visitor.invoke_io_method(
+                    "java/lang/StringBuilder",
+                    "<init>",
+                    "(Ljava/lang/String;)V",
+                );
+            }),
+            ("std::fs::write", ..) => self.try_io(destination, |visitor| {
+                visitor.load_path(&args[0].node);
+                visitor.load_bytes(&args[1].node);
+                visitor
+                    .method_bytecode_instructions
+                    .push(Instruction::Iconst_0);
+                let open_option = visitor
+                    .constant_pool
+                    .add_class("java/nio/file/OpenOption")
+                    .expect("Could not add class to constant pool");
+                visitor
+                    .method_bytecode_instructions
+                    .push(Instruction::Anewarray(open_option));
+                visitor.invoke_static(
+                    "java/nio/file/Files",
+                    "write",
+                    "(Ljava/nio/file/Path;[B[Ljava/nio/file/OpenOption;)Ljava/nio/file/Path;",
+                );
+                visitor.method_bytecode_instructions.push(Instruction::Pop);
+            }),
+            (_, Some("std::fs::File"), "open" | "create") => {
+                let mode = if name.as_str() == "open" { "r" } else { "rw" };
+                self.try_io(destination, |visitor| {
+                    // new RandomAccessFile(path, mode)
+                    let class_index = visitor
+                        .constant_pool
+                        .add_class(RANDOM_ACCESS_FILE)
+                        .expect("Could not add class to constant pool");
+                    visitor
+                        .method_bytecode_instructions
+                        .push(Instruction::New(class_index));
+                    visitor.method_bytecode_instructions.push(Instruction::Dup);
+                    visitor.load_as_java_string(&args[0].node);
+                    visitor.load_str_constant(mode);
+                    visitor.invoke_io_method(
+                        RANDOM_ACCESS_FILE,
+                        "<init>",
+                        "(Ljava/lang/String;Ljava/lang/String;)V",
+                    );
+                    if mode == "rw" {
+                        visitor.method_bytecode_instructions.push(Instruction::Dup);
+                        visitor
+                            .method_bytecode_instructions
+                            .push(Instruction::Lconst_0);
+                        visitor.invoke_io_method(RANDOM_ACCESS_FILE, "setLength", "(J)V");
+                    }
+                })
+            }
+            (_, Some("std::io::BufReader"), "new")
+                if adt_path(generic_args.type_at(0), tcx).as_deref() == Some("std::fs::File") =>
+            {
+                // new BufferedReader(new InputStreamReader(Channels.newInputStream(
+                //     file.getChannel()), UTF_8))
+                let buffered_reader = self
+                    .constant_pool
+                    .add_class(BUFFERED_READER)
+                    .expect("Could not add class to constant pool");
+                let input_stream_reader = self
+                    .constant_pool
+                    .add_class("java/io/InputStreamReader")
+                    .expect("Could not add class to constant pool");
+                self.method_bytecode_instructions.extend([
+                    Instruction::New(buffered_reader),
+                    Instruction::Dup,
+                    Instruction::New(input_stream_reader),
+                    Instruction::Dup,
+                ]);
+                self.load_operand(&args[0].node);
+                self.invoke_io_method(
+                    RANDOM_ACCESS_FILE,
+                    "getChannel",
+                    "()Ljava/nio/channels/FileChannel;",
+                );
+                self.invoke_static(
+                    "java/nio/channels/Channels",
+                    "newInputStream",
+                    "(Ljava/nio/channels/ReadableByteChannel;)Ljava/io/InputStream;",
+                );
+                self.load_utf_8();
+                self.invoke_io_method(
+                    "java/io/InputStreamReader",
+                    "<init>",
+                    "(Ljava/io/InputStream;Ljava/nio/charset/Charset;)V",
+                );
+                self.invoke_io_method(BUFFERED_READER, "<init>", "(Ljava/io/Reader;)V");
+                self.store_call_result(destination);
+                true
+            }
+            (_, Some("std::io::BufWriter"), "new") => {
+                // Writes are not buffered
+                self.load_operand(&args[0].node);
+                self.store_call_result(destination);
+                true
+            }
+            ("std::io::Read::read_to_string" | "std::io::BufRead::read_line", Some(reader), _) => {
+                let (method, reader_class) = match (name.as_str(), reader) {
+                    ("read_to_string", "std::fs::File") => ("readToString", RANDOM_ACCESS_FILE),
+                    ("read_line", "std::io::BufReader" | "std::io::StdinLock") => {
+                        ("readLine", BUFFERED_READER)
+                    }
+                    _ => {
+                        trace!("      Unsupported reader: {path} of {reader}");
+                        return false;
+                    }
+                };
+                self.try_io(destination, |visitor| {
+                    visitor.load_operand(&args[0].node);
+                    visitor.load_operand(&args[1].node);
+                    let class = RuntimeClass::Io;
+                    visitor.invoke_static(
+                        &class.name(),
+                        method,
+                        &format!("(L{reader_class};Ljava/lang/StringBuilder;)I"),
+                    );
+                    visitor.int_to_usize();
+                    visitor.runtime_classes.insert(class);
+                })
+            }
+            ("std::io::Write::write_all", Some("std::fs::File" | "std::io::BufWriter"), _) => self
+                .try_io(destination, |visitor| {
+                    visitor.load_operand(&args[0].node);
+                    visitor.load_operand(&args[1].node);
+                    visitor.invoke_io_method(RANDOM_ACCESS_FILE, "write", "([B)V");
+                }),
+            ("std::io::Write::flush", Some("std::fs::File" | "std::io::BufWriter"), _) => {
+                // Files are not buffered, so there is nothing to flush
+                self.store_io_ok(destination, |_| {})
+            }
+            ("std::io::stdin", ..) => {
+                // Every `Stdin` shares the same reader, and its buffer
+                let class = RuntimeClass::Io;
+                self.invoke_static(&class.name(), "stdin", &format!("()L{BUFFERED_READER};"));
+                self.runtime_classes.insert(class);
+                self.store_call_result(destination);
+                true
+            }
+            ("std::io::stdout" | "std::io::stderr", ..) => {
+                self.load_std_stream(path == "std::io::stderr");
+                self.store_call_result(destination);
+                true
+            }
+            (_, Some("std::io::Stdin" | "std::io::Stdout" | "std::io::Stderr"), "lock") => {
+                // A lock is the stream itself
+                self.load_operand(&args[0].node);
+                self.store_call_result(destination);
+                true
+            }
+            (_, Some("std::io::Stdin"), "read_line") => self.try_io(destination, |visitor| {
+                visitor.load_operand(&args[0].node);
+                visitor.load_operand(&args[1].node);
+                let class = RuntimeClass::Io;
+                visitor.invoke_static(
+                    &class.name(),
+                    "readLine",
+                    &format!("(L{BUFFERED_READER};Ljava/lang/StringBuilder;)I"),
+                );
+                visitor.int_to_usize();
+                visitor.runtime_classes.insert(class);
+            }),
+            ("std::io::Write::write_all" | "std::io::Write::write", Some(stream), _)
+                if STD_STREAMS.contains(&stream) =>
+            {
+                // `PrintStream`s never throw, `write` writes the whole buffer
+                let write = name.as_str() == "write";
+                self.store_io_ok(destination, |visitor| {
+                    visitor.load_operand(&args[0].node);
+                    visitor.load_operand(&args[1].node);
+                    visitor.write_bytes();
+                    if write {
+                        visitor.load_operand(&args[1].node);
+                        visitor
+                            .method_bytecode_instructions
+                            .push(Instruction::Arraylength);
+                        visitor.int_to_usize();
+                    }
+                })
+            }
+            ("std::io::Write::flush", Some(stream), _) if STD_STREAMS.contains(&stream) => self
+                .store_io_ok(destination, |visitor| {
+                    visitor.load_operand(&args[0].node);
+                    visitor.invoke_io_method(PRINT_STREAM, "flush", "()V");
+                }),
+            ("std::io::_print" | "std::io::_eprint", ..) => {
+                // The formatted string, encoded as UTF-8 whatever the platform encoding is
+                self.load_std_stream(path == "std::io::_eprint");
+                self.load_bytes(&args[0].node);
+                self.write_bytes();
+                true
+            }
+            _ => return false,
+        };
+        if lowered {
+            trace!("      Generated bytecode: {path}");
+        }
+        lowered
+    }
+
+    /// Wraps the code `emit` generates in `try { Ok(..) } catch (IOException e) { Err(e) }`,
+    /// storing the `io::Result` into `destination`. The code pushes the `Ok` value, if any.
+    fn try_io(&mut self, destination: &Place<'tcx>, emit: impl FnOnce(&mut Self)) -> bool {
+        self.try_catch(destination, IO_EXCEPTION, emit)
+    }
+
+    /// Wraps the code `emit` generates in `try { Ok(..) } catch (E e) { Err(e) }` for the
+    /// exception class `E`, storing the `Result` into `destination`. The code pushes the `Ok`
+    /// value, if any.
+    pub(crate) fn try_catch(
+        &mut self,
+        destination: &Place<'tcx>,
+        exception_class: &str,
+        emit: impl FnOnce(&mut Self),
+    ) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for result: {destination:?}");
+            return false;
+        }
+        let ok = VariantIdx::from_u32(0);
+        let err = VariantIdx::from_u32(1);
+        let field = FieldIdx::from_u32(0);
+
+        // try { destination = Ok(..); }
+        let start = self.method_bytecode_instructions.len();
+        emit(self);
+        let end = self.method_bytecode_instructions.len();
+        self.store_enum_field(destination.local, ok, field);
+        self.store_discriminant(destination.local, ok);
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        // catch (E e) { destination = Err(e); }
+        let handler = self.method_bytecode_instructions.len();
+        let exception =
+            control_flow::verification_type(&format!("L{exception_class};"), self.constant_pool)
+                .expect("Could not add class to constant pool");
+        self.extra_frames.push((handler, vec![exception]));
+        let catch_type = self
+            .constant_pool
+            .add_class(exception_class)
+            .expect("Could not add class to constant pool");
+        self.exception_table.push(ExceptionTableEntry {
+            range_pc: start as u16..end as u16,
+            handler_pc: handler as u16,
+            catch_type,
+        });
+        if self
+            .enum_field_slots
+            .contains_key(&(destination.local, err, field))
+        {
+            self.store_enum_field(destination.local, err, field);
+        } else {
+            self.method_bytecode_instructions.push(Instruction::Pop);
+        }
+        self.store_discriminant(destination.local, err);
+
+        let end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        self.extra_frames.push((end, Vec::new()));
+        true
+    }
+
+    /// Stores `Ok` of the value the code `emit` generates pushes, if any, into `destination`, for
+    /// operations that cannot fail
+    fn store_io_ok(&mut self, destination: &Place<'tcx>, emit: impl FnOnce(&mut Self)) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for I/O result: {destination:?}");
+            return false;
+        }
+        let ok = VariantIdx::from_u32(0);
+        emit(self);
+        self.store_enum_field(destination.local, ok, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, ok);
+        true
+    }
+
+    /// Pushes `System.out`, or `System.err`
+    fn load_std_stream(&mut self, stderr: bool) {
+        let system = self
+            .constant_pool
+            .add_class("java/lang/System")
+            .expect("Could not add class to constant pool");
+        let stream = self
+            .constant_pool
+            .add_field_ref(
+                system,
+                if stderr { "err" } else { "out" },
+                &format!("L{PRINT_STREAM};"),
+            )
+            .expect("Could not add field ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Getstatic(stream));
+    }
+
+    /// Writes the byte array on top of the stack to the `PrintStream` below it
+    fn write_bytes(&mut self) {
+        // stream.write(bytes, 0, bytes.length)
+        self.method_bytecode_instructions.extend([
+            Instruction::Dup,
+            Instruction::Arraylength,
+            Instruction::Iconst_0,
+            Instruction::Swap,
+        ]);
+        self.invoke_io_method(PRINT_STREAM, "write", "([BII)V");
+    }
+
+    /// Pushes `Paths.get(path)` for an `AsRef<Path>` operand, a string
+    fn load_path(&mut self, path: &Operand<'tcx>) {
+        self.load_as_java_string(path);
+        self.method_bytecode_instructions
+            .push(Instruction::Iconst_0);
+        let string = self
+            .constant_pool
+            .add_class("java/lang/String")
+            .expect("Could not add class to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Anewarray(string));
+        self.invoke_static(
+            "java/nio/file/Paths",
+            "get",
+            "(Ljava/lang/String;[Ljava/lang/String;)Ljava/nio/file/Path;",
+        );
+    }
+
+    /// Pushes the bytes of an `AsRef<[u8]>` operand: a byte array, or a string encoded as UTF-8
+    fn load_bytes(&mut self, bytes: &Operand<'tcx>) {
+        let descriptor = rust_ty_to_jvm_descriptor(bytes.ty(self.body, self.tcx), self.tcx);
+        if descriptor.starts_with('[') {
+            self.load_operand(bytes);
+            return;
+        }
+        self.load_as_java_string(bytes);
+        self.load_utf_8();
+        self.invoke_io_method(
+            "java/lang/String",
+            "getBytes",
+            "(Ljava/nio/charset/Charset;)[B",
+        );
+    }
+
+    /// Decodes the byte array on top of the stack into a `String`, as UTF-8
+    fn decode_utf_8(&mut self) {
+        let string = self
+            .constant_pool
+            .add_class("java/lang/String")
+            .expect("Could not add class to constant pool");
+        self.method_bytecode_instructions.extend([
+            Instruction::New(string),
+            Instruction::Dup_x1,
+            Instruction::Swap,
+        ]);
+        self.load_utf_8();
+        self.invoke_io_method(
+            "java/lang/String",
+            "<init>",
+            "([BLjava/nio/charset/Charset;)V",
+        );
+    }
+
+    /// Pushes a new `StringBuilder` twice, once to be initialized and once to be kept
+    fn push_new_string_builder(&mut self) {
+        let string_builder = self
+            .constant_pool
+            .add_class("java/lang/StringBuilder")
+            .expect("Could not add class to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::New(string_builder));
+        self.method_bytecode_instructions.push(Instruction::Dup);
+    }
+
+    /// Pushes `StandardCharsets.UTF_8`
+    fn load_utf_8(&mut self) {
+        let charsets = self
+            .constant_pool
+            .add_class("java/nio/charset/StandardCharsets")
+            .expect("Could not add class to constant pool");
+        let utf_8 = self
+            .constant_pool
+            .add_field_ref(charsets, "UTF_8", "Ljava/nio/charset/Charset;")
+            .expect("Could not add field ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Getstatic(utf_8));
+    }
+
+    /// Invokes an instance method, with `invokespecial` for constructors
+    fn invoke_io_method(&mut self, class_name: &str, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions.push(if name == "<init>" {
+            Instruction::Invokespecial(method)
+        } else {
+            Instruction::Invokevirtual(method)
+        });
+    }
+}
@@ -0,0 +1,161 @@
+//! The iterators `for` loops are desugared into.
+
+use crate::runtime::RuntimeClass;
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, range, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+use rustc_span::sym;
+
+const ITERATOR: &str = "java/util/Iterator";
+
+/// The core iterators lowered to `java.util.Iterator`, by crate and path
+const JAVA_ITERATORS: [(&str, &str); 4] = [
+    ("core", "::slice::iter::Iter"),
+    ("core", "::array::iter::IntoIter"),
+    ("alloc", "::vec::into_iter::IntoIter"),
+    ("std", "::env::Args"),
+];
+
+/// Whether the type is an iterator lowered to a `java.util.Iterator`
+pub(crate) fn is_java_iterator(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    let TyKind::Adt(adt_def, _) = ty.kind() else {
+        return false;
+    };
+    let did = adt_def.did();
+    let crate_name = tcx.crate_name(did.krate);
+    let path = tcx.def_path(did).to_string_no_crate_verbose();
+    JAVA_ITERATORS
+        .iter()
+        .any(|(krate, iterator)| crate_name.as_str() == *krate && path == *iterator)
+}
+
+/// Whether `into_iter()` of the type lowers to `ArrayList.iterator()`: it is a `Vec` or a
+/// reference to one
+fn is_vec_or_ref(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    match ty.peel_refs().kind() {
+        TyKind::Adt(adt_def, _) => tcx.is_diagnostic_item(sym::Vec, adt_def.did()),
+        _ => false,
+    }
+}
+
+/// Whether `into_iter()` of the type lowers to a runtime `ArrayIterator`
+fn is_array_or_ref(ty: Ty<'_>) -> bool {
+    matches!(ty.peel_refs().kind(), TyKind::Array(..) | TyKind::Slice(_))
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers `IntoIterator::into_iter` and `Iterator::next` of the supported iterators,
+    /// returning `false` for any other call
+    pub(crate) fn lower_iterator_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let lang_items = tcx.lang_items();
+        let self_ty = generic_args.type_at(0);
+        if Some(callee) == lang_items.into_iter_fn() {
+            if range::runtime_class(self_ty, tcx).is_some() || is_java_iterator(self_ty, tcx) {
+                // Already an iterator
+                self.load_operand(&args[0].node);
+            } else if is_vec_or_ref(self_ty, tcx) {
+                self.load_operand(&args[0].node);
+                self.invoke_iterator_method("java/util/ArrayList", "iterator", false);
+            } else if is_array_or_ref(self_ty) {
+                let class = RuntimeClass::ArrayIterator;
+                let class_index = self
+                    .constant_pool
+                    .add_class(class.name())
+                    .expect("Could not add class to constant pool");
+                let constructor = self
+                    .constant_pool
+                    .add_method_ref(class_index, "<init>", "(Ljava/lang/Object;)V")
+                    .expect("Could not add constructor to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::New(class_index));
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokespecial(constructor));
+                self.runtime_classes.insert(class);
+            } else {
+                trace!("      Unsupported into_iter() of: {self_ty:?}");
+                return false;
+            }
+        } else if Some(callee) == lang_items.next_fn() {
+            if let Some(class) = range::runtime_class(self_ty, tcx) {
+                return self.lower_range_next(class, &args[0].node, destination);
+            }
+            if !is_java_iterator(self_ty, tcx) {
+                trace!("      Unsupported next() of: {self_ty:?}");
+                return false;
+            }
+            return self.lower_java_iterator_next(&args[0].node, destination);
+        } else {
+            return false;
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `next()` on a `java.util.Iterator`: `Some(iterator.next())` while `hasNext()`
+    fn lower_java_iterator_next(
+        &mut self,
+        iterator: &Operand<'tcx>,
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !self.is_option_destination(destination) {
+            trace!("      Unsupported destination for Iterator::next: {destination:?}");
+            return false;
+        }
+        let TyKind::Adt(_, option_args) = destination.ty(self.body, self.tcx).ty.kind() else {
+            return false;
+        };
+        let item_descriptor = rust_ty_to_jvm_descriptor(option_args.type_at(0), self.tcx);
+
+        self.load_operand(iterator);
+        self.invoke_iterator_method(ITERATOR, "hasNext", true);
+        let exhausted = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Ifeq(0));
+        self.load_operand(iterator);
+        self.invoke_iterator_method(ITERATOR, "next", true);
+        self.cast_from_object(&item_descriptor);
+        self.store_option_result(exhausted, &item_descriptor, destination);
+        trace!("      Generated bytecode: Iterator::next");
+        true
+    }
+
+    /// Invokes `iterator()`, `hasNext()` or `next()`, on an interface or a class
+    fn invoke_iterator_method(&mut self, class_name: &str, name: &str, interface: bool) {
+        let descriptor = match name {
+            "iterator" => "()Ljava/util/Iterator;",
+            "hasNext" => "()Z",
+            _ => "()Ljava/lang/Object;",
+        };
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let instruction = if interface {
+            let method = self
+                .constant_pool
+                .add_interface_method_ref(class_index, name, descriptor)
+                .expect("Could not add interface method ref to constant pool");
+            Instruction::Invokeinterface(method, 1)
+        } else {
+            let method = self
+                .constant_pool
+                .add_method_ref(class_index, name, descriptor)
+                .expect("Could not add method ref to constant pool");
+            Instruction::Invokevirtual(method)
+        };
+        self.method_bytecode_instructions.push(instruction);
+    }
+}
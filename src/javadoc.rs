@@ -0,0 +1,205 @@
+//! Javadoc for the crate's API, from its Rust doc comments.
+
+use crate::{enums, export, naming, options, visibility};
+use rustc_ast::attr::AttributeExt;
+use rustc_data_structures::fx::FxHashMap;
+use rustc_hir::def_id::{CRATE_DEF_ID, DefId};
+use rustc_middle::ty::{AssocKind, TyCtxt};
+use std::fmt::Write;
+
+/// The documentation of a class of the API and of its members
+struct ClassDocs {
+    doc: Option<String>,
+    /// The documentation of the fields and methods, by name
+    members: FxHashMap<String, String>,
+}
+
+/// The documentation of the crate's API, by internal class name
+#[derive(Default)]
+pub(crate) struct ApiDocs {
+    classes: FxHashMap<String, ClassDocs>,
+    /// The documentation of the crate's free functions, which the crate class and the classes of
+    /// its other codegen units declare
+    functions: FxHashMap<String, String>,
+    crate_class: String,
+}
+
+impl ApiDocs {
+    /// The documentation of a class
+    pub(crate) fn class_doc(&self, class_name: &str) -> Option<&str> {
+        self.classes.get(class_name)?.doc.as_deref()
+    }
+
+    /// The documentation of a field or method of a class
+    pub(crate) fn member_doc(&self, class_name: &str, member: &str) -> Option<&str> {
+        let is_unit_class = class_name == self.crate_class
+            || class_name
+                .strip_prefix(self.crate_class.as_str())
+                .is_some_and(|suffix| suffix.starts_with("$cgu"));
+        if is_unit_class {
+            return self.functions.get(member).map(String::as_str);
+        }
+        self.classes
+            .get(class_name)?
+            .members
+            .get(member)
+            .map(String::as_str)
+    }
+}
+
+/// The Rust doc comment of an item, without the leading space of its lines
+pub(crate) fn doc_comment(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let lines: Vec<String> = tcx
+        .get_attrs_unchecked(def_id)
+        .iter()
+        .filter_map(AttributeExt::doc_str)
+        .flat_map(|doc| {
+            doc.as_str()
+                .lines()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line).to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let doc = lines.join("\n").trim().to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+/// The documentation of the crate's API, given the free functions it lowers, collected only when
+/// API stubs are written
+pub(crate) fn api_docs(tcx: TyCtxt<'_>, functions: &[DefId]) -> ApiDocs {
+    let mut api_docs = ApiDocs {
+        crate_class: naming::crate_class_name(tcx),
+        ..ApiDocs::default()
+    };
+    if options::get().api_stubs.is_none() {
+        return api_docs;
+    }
+
+    api_docs.classes.insert(
+        api_docs.crate_class.clone(),
+        ClassDocs {
+            doc: doc_comment(tcx, CRATE_DEF_ID.to_def_id()),
+            members: FxHashMap::default(),
+        },
+    );
+    for def_id in functions {
+        if visibility::is_api(tcx, *def_id)
+            && let Some(doc) = doc_comment(tcx, *def_id)
+        {
+            api_docs
+                .functions
+                .insert(naming::method_name(tcx, *def_id), doc);
+        }
+    }
+
+    for def_id in export::exported_structs(tcx) {
+        let mut class_docs = ClassDocs {
+            doc: doc_comment(tcx, def_id),
+            members: FxHashMap::default(),
+        };
+        for field in &tcx.adt_def(def_id).non_enum_variant().fields {
+            if let Some(doc) = doc_comment(tcx, field.did) {
+                class_docs.members.insert(field.name.to_string(), doc);
+            }
+        }
+        for impl_def_id in tcx.inherent_impls(def_id) {
+            for item in tcx.associated_items(*impl_def_id).in_definition_order() {
+                if item.kind == AssocKind::Fn
+                    && let Some(doc) = doc_comment(tcx, item.def_id)
+                {
+                    class_docs
+                        .members
+                        .insert(naming::checked_method_name(tcx, item.def_id), doc);
+                }
+            }
+        }
+        api_docs
+            .classes
+            .insert(export::class_name(tcx, def_id), class_docs);
+    }
+
+    for def_id in enums::exported_enums(tcx) {
+        api_docs.classes.insert(
+            export::class_name(tcx, def_id),
+            ClassDocs {
+                doc: doc_comment(tcx, def_id),
+                members: FxHashMap::default(),
+            },
+        );
+        for variant in tcx.adt_def(def_id).variants() {
+            let mut class_docs = ClassDocs {
+                doc: doc_comment(tcx, variant.def_id),
+                members: FxHashMap::default(),
+            };
+            for field in &variant.fields {
+                if let Some(doc) = doc_comment(tcx, field.did) {
+                    class_docs.members.insert(enums::field_name(field), doc);
+                }
+            }
+            api_docs
+                .classes
+                .insert(enums::variant_class_name(tcx, def_id, variant), class_docs);
+        }
+    }
+    api_docs
+}
+
+/// A Javadoc comment of the Markdown documentation, indented by `indent`
+pub(crate) fn javadoc_comment(doc: &str, indent: &str) -> String {
+    let mut comment = format!("{indent}/**\n");
+    let mut in_code_block = false;
+    let mut paragraph_start = false;
+    for line in doc.lines() {
+        if line.trim_start().starts_with("```") {
+            let tag = if in_code_block { "</pre>" } else { "<pre>" };
+            let _ = writeln!(comment, "{indent} * {tag}");
+            in_code_block = !in_code_block;
+            continue;
+        }
+        let line = if in_code_block {
+            line.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('@', "&#64;")
+        } else {
+            code_spans(line)
+        };
+        // `*/` would end the comment
+        let line = line.replace("*/", "*&#47;");
+        if line.trim().is_empty() && !in_code_block {
+            paragraph_start = true;
+            continue;
+        }
+        if paragraph_start {
+            let _ = writeln!(comment, "{indent} * <p>");
+            paragraph_start = false;
+        }
+        let _ = writeln!(comment, "{indent} * {line}");
+    }
+    let _ = writeln!(comment, "{indent} */");
+    comment
+}
+
+/// The line with its Markdown code spans as `{@code ...}`
+fn code_spans(line: &str) -> String {
+    let mut result = String::new();
+    let mut parts = line.split('`');
+    if let Some(text) = parts.next() {
+        result.push_str(text);
+    }
+    let mut in_code = false;
+    for part in parts {
+        in_code = !in_code;
+        if in_code {
+            result.push_str("{@code ");
+            result.push_str(part);
+        } else {
+            result.push('}');
+            result.push_str(part);
+        }
+    }
+    if in_code {
+        result.push('}');
+    }
+    result
+}
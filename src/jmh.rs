@@ -0,0 +1,133 @@
+//! Benchmarks as a JMH benchmark class.
+
+use crate::constant_pool::ConstantPool;
+use crate::{attributes, create_method, method_descriptor, naming, options, return_instruction};
+use ristretto_classfile::attributes::{Annotation, Attribute, Instruction};
+use ristretto_classfile::{ClassAccessFlags, ClassFile, Method, MethodAccessFlags};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+
+const BENCHMARK: &str = "Lorg/openjdk/jmh/annotations/Benchmark;";
+
+/// The name of the JMH benchmark class of a crate
+pub(crate) fn benchmark_class_name(crate_name: &str) -> String {
+    format!("{crate_name}Benchmarks")
+}
+
+/// The functions of the crate marked `#[jvm::benchmark]`, reporting those taking arguments
+fn benchmark_functions(tcx: TyCtxt<'_>) -> Vec<DefId> {
+    let mut benchmarks = Vec::new();
+    for item_id in tcx.hir_crate_items(()).free_items() {
+        let def_id = item_id.owner_id.to_def_id();
+        if tcx.def_kind(def_id) != DefKind::Fn
+            || !attributes::has_jvm_attr(tcx, def_id, "benchmark")
+        {
+            continue;
+        }
+        if !tcx
+            .fn_sig(def_id)
+            .skip_binder()
+            .inputs()
+            .skip_binder()
+            .is_empty()
+        {
+            tcx.dcx().span_err(
+                tcx.def_span(def_id),
+                "`#[jvm::benchmark]` functions cannot take arguments",
+            );
+            continue;
+        }
+        benchmarks.push(def_id);
+    }
+    benchmarks
+}
+
+/// The JMH benchmark class of the crate, `None` if no function is marked `#[jvm::benchmark]`
+pub(crate) fn generate_benchmark_class(
+    tcx: TyCtxt<'_>,
+    crate_name: &str,
+) -> ristretto_classfile::Result<Option<(String, Vec<u8>)>> {
+    let benchmarks = benchmark_functions(tcx);
+    if benchmarks.is_empty() {
+        return Ok(None);
+    }
+    let name = benchmark_class_name(crate_name);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let this_class = constant_pool.add_class(&name)?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+
+    let constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Return,
+    ];
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        "()V",
+        &constructor.into(),
+    )?];
+    for def_id in benchmarks {
+        methods.push(benchmark_method(
+            &mut constant_pool,
+            tcx,
+            crate_name,
+            def_id,
+        )?);
+    }
+
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    };
+    class_file.verify()?;
+
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok(Some((name, bytes)))
+}
+
+/// The `@Benchmark` method returning the result of a benchmarked function
+fn benchmark_method(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'_>,
+    crate_name: &str,
+    def_id: DefId,
+) -> ristretto_classfile::Result<Method> {
+    let method_name = naming::method_name(tcx, def_id);
+    let descriptor = method_descriptor(tcx, def_id, false);
+    let crate_class = constant_pool.add_class(crate_name)?;
+    let function = constant_pool.add_method_ref(crate_class, &method_name, &descriptor)?;
+    let return_descriptor = &descriptor[descriptor.find(')').map_or(0, |index| index + 1)..];
+
+    // return Crate.function();
+    let instructions = vec![
+        Instruction::Invokestatic(function),
+        return_instruction(return_descriptor),
+    ];
+    let mut method = create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        &method_name,
+        &descriptor,
+        &instructions.into(),
+    )?;
+    method
+        .attributes
+        .push(Attribute::RuntimeVisibleAnnotations {
+            name_index: constant_pool.add_utf8("RuntimeVisibleAnnotations")?,
+            annotations: vec![Annotation {
+                type_index: constant_pool.add_utf8(BENCHMARK)?,
+                elements: Vec::new(),
+            }],
+        });
+    Ok(method)
+}
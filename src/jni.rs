@@ -0,0 +1,94 @@
+//! Native functions, declared in `extern "C"` blocks without `#[jvm::import]`.
+
+use crate::constant_pool::ConstantPool;
+use crate::runtime::{RuntimeClass, RuntimeClasses};
+use crate::{MethodCode, create_method, interop, ldc, method_descriptor, options, visibility};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{Method, MethodAccessFlags};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_middle::ty::TyCtxt;
+
+/// Whether the function is a native function, a foreign function not imported from Java
+pub(crate) fn is_native(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    tcx.is_foreign_item(def_id)
+        && tcx.def_kind(def_id) == DefKind::Fn
+        && interop::import_class(tcx, def_id).is_none()
+}
+
+/// The name of the `native` method of a native function, which its JNI name is made from
+pub(crate) fn method_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    tcx.item_name(def_id).to_string()
+}
+
+/// The native functions the crate declares
+fn native_functions(tcx: TyCtxt<'_>) -> Vec<DefId> {
+    tcx.hir_crate_items(())
+        .foreign_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .filter(|def_id| is_native(tcx, *def_id))
+        .collect()
+}
+
+/// The names of the native libraries the crate links, in the order they are given
+fn native_libraries(tcx: TyCtxt<'_>) -> Vec<String> {
+    let mut libraries: Vec<String> = Vec::new();
+    for library in tcx.native_libraries(LOCAL_CRATE) {
+        let name = library.name.to_string();
+        if !libraries.contains(&name) {
+            libraries.push(name);
+        }
+    }
+    libraries
+}
+
+/// The `static native` methods of the crate's native functions, and the static initializer
+/// loading the native libraries, if it has any
+pub(crate) fn native_methods(
+    tcx: TyCtxt<'_>,
+    constant_pool: &mut ConstantPool,
+    runtime_classes: &mut RuntimeClasses,
+) -> ristretto_classfile::Result<Vec<Method>> {
+    // Without JNI, the calls of native functions are reported, see `unsupported`
+    if !options::get().jni {
+        return Ok(Vec::new());
+    }
+    let functions = native_functions(tcx);
+    if functions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut methods = Vec::with_capacity(functions.len() + 1);
+    for def_id in functions {
+        methods.push(Method {
+            access_flags: visibility::fn_access_flags(tcx, def_id)
+                | MethodAccessFlags::STATIC
+                | MethodAccessFlags::NATIVE,
+            name_index: constant_pool.add_utf8(method_name(tcx, def_id))?,
+            descriptor_index: constant_pool.add_utf8(method_descriptor(tcx, def_id, false))?,
+            attributes: Vec::new(),
+        });
+    }
+
+    let libraries = native_libraries(tcx);
+    if !libraries.is_empty() {
+        // NativeLoader.load("<library>"); for each library
+        let native_loader = constant_pool.add_class(RuntimeClass::NativeLoader.name())?;
+        let load = constant_pool.add_method_ref(native_loader, "load", "(Ljava/lang/String;)V")?;
+        let mut instructions = Vec::with_capacity(2 * libraries.len() + 1);
+        for library in &libraries {
+            instructions.push(ldc(constant_pool.add_string(library)?));
+            instructions.push(Instruction::Invokestatic(load));
+        }
+        instructions.push(Instruction::Return);
+        methods.push(create_method(
+            constant_pool,
+            MethodAccessFlags::STATIC,
+            "<clinit>",
+            "()V",
+            &MethodCode::from(instructions),
+        )?);
+        runtime_classes.insert(RuntimeClass::NativeLoader);
+    }
+    Ok(methods)
+}
@@ -0,0 +1,333 @@
+//! `#[test]` functions as a JUnit 5 test class.
+
+use crate::constant_pool::ConstantPool;
+use crate::runtime::{RuntimeClass, RuntimeClasses};
+use crate::trace::trace;
+use crate::{MethodCode, control_flow, create_method, harness, ldc, naming, options};
+use ristretto_classfile::attributes::{
+    Annotation, AnnotationElement, AnnotationValuePair, Attribute, ExceptionTableEntry,
+    Instruction, VerificationType,
+};
+use ristretto_classfile::{ClassAccessFlags, ClassFile, Method, MethodAccessFlags};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{Symbol, sym};
+
+const TEST: &str = "Lorg/junit/jupiter/api/Test;";
+const DISPLAY_NAME: &str = "Lorg/junit/jupiter/api/DisplayName;";
+const DISABLED: &str = "Lorg/junit/jupiter/api/Disabled;";
+const ASSERTION_ERROR: &str = "java/lang/AssertionError";
+
+/// A `#[test]` function
+pub(crate) struct TestFunction {
+    def_id: DefId,
+    /// The path of the test in its crate, as in `tests::it_works`
+    pub(crate) path: String,
+    pub(crate) ignored: bool,
+    /// `None` unless the test is `#[should_panic]`, the expected substring of the panic message
+    /// otherwise, empty if there is none
+    pub(crate) should_panic: Option<String>,
+}
+
+impl TestFunction {
+    /// The name of the `@Test` method running the test
+    pub(crate) fn method_name(&self) -> String {
+        self.path.replace("::", "$")
+    }
+}
+
+/// The name of the JUnit test class of a crate
+pub(crate) fn test_class_name(crate_name: &str) -> String {
+    format!("{crate_name}Test")
+}
+
+/// The `#[test]` functions of the crate returning `()`, found through the test descriptors
+/// `--test` generates next to them: constants named after the function and marked
+/// `#[rustc_test_marker]`
+fn test_functions(tcx: TyCtxt<'_>) -> Vec<TestFunction> {
+    let module_items = tcx.hir_crate_items(());
+    let mut tests = Vec::new();
+    for item_id in module_items.free_items() {
+        let marker_def_id = item_id.owner_id.to_def_id();
+        if tcx.def_kind(marker_def_id) != DefKind::Const {
+            continue;
+        }
+        let Some(path) = tcx
+            .get_attrs_by_path(marker_def_id, &[sym::rustc_test_marker])
+            .next()
+            .and_then(|attr| attr.value_str())
+        else {
+            continue;
+        };
+        let parent = tcx.parent(marker_def_id);
+        let name = tcx.item_name(marker_def_id);
+        let Some(def_id) = module_items
+            .free_items()
+            .map(|item_id| item_id.owner_id.to_def_id())
+            .find(|def_id| {
+                tcx.def_kind(*def_id) == DefKind::Fn
+                    && tcx.parent(*def_id) == parent
+                    && tcx.item_name(*def_id) == name
+            })
+        else {
+            continue;
+        };
+        let return_ty = tcx.fn_sig(def_id).skip_binder().output().skip_binder();
+        if !return_ty.is_unit() {
+            trace!(
+                "Unsupported test returning `{return_ty}`: {path}; only tests returning `()` are run"
+            );
+            continue;
+        }
+        tests.push(TestFunction {
+            def_id,
+            path: path.to_string(),
+            ignored: tcx
+                .get_attrs_by_path(def_id, &[sym::ignore])
+                .next()
+                .is_some(),
+            should_panic: should_panic(tcx, def_id),
+        });
+    }
+    tests
+}
+
+/// The expected substring of the panic message of a `#[should_panic]` test, empty if it expects
+/// any panic, `None` if it is not one
+fn should_panic(tcx: TyCtxt<'_>, def_id: DefId) -> Option<String> {
+    let attr = tcx.get_attrs_by_path(def_id, &[sym::should_panic]).next()?;
+    // `#[should_panic = "..."]` or `#[should_panic(expected = "...")]`
+    let expected = attr.value_str().or_else(|| {
+        attr.meta_item_list()?
+            .iter()
+            .find(|item| item.has_name(Symbol::intern("expected")))
+            .and_then(|item| item.value_str())
+    });
+    Some(
+        expected
+            .map(|expected| expected.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+/// The JUnit test class of a crate compiled with `--test`
+pub(crate) fn generate_test_class(
+    tcx: TyCtxt<'_>,
+    crate_name: &str,
+    runtime_classes: &mut RuntimeClasses,
+) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let tests = test_functions(tcx);
+    let name = test_class_name(crate_name);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let this_class = constant_pool.add_class(&name)?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+
+    let constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Return,
+    ];
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        "()V",
+        &constructor.into(),
+    )?];
+    for test in &tests {
+        methods.push(test_method(
+            &mut constant_pool,
+            tcx,
+            this_class,
+            crate_name,
+            test,
+            runtime_classes,
+        )?);
+    }
+    methods.push(harness::run_tests_method(
+        &mut constant_pool,
+        this_class,
+        &tests,
+    )?);
+
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    };
+    class_file.verify()?;
+
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((name, bytes))
+}
+
+/// The `@Test` method running a test
+fn test_method(
+    constant_pool: &mut ConstantPool,
+    tcx: TyCtxt<'_>,
+    this_class: u16,
+    crate_name: &str,
+    test: &TestFunction,
+    runtime_classes: &mut RuntimeClasses,
+) -> ristretto_classfile::Result<Method> {
+    let crate_class = constant_pool.add_class(crate_name)?;
+    let test_fn =
+        constant_pool.add_method_ref(crate_class, &naming::method_name(tcx, test.def_id), "()V")?;
+    let panic_class = constant_pool.add_class(RuntimeClass::Panic.name())?;
+    runtime_classes.insert(RuntimeClass::Panic);
+    let get_message =
+        constant_pool.add_method_ref(panic_class, "getMessage", "()Ljava/lang/String;")?;
+    let assertion_error = constant_pool.add_class(ASSERTION_ERROR)?;
+    let message_init =
+        constant_pool.add_method_ref(assertion_error, "<init>", "(Ljava/lang/Object;)V")?;
+
+    // try { Crate.test(); } catch (Panic panic) { .. }
+    let mut instructions = vec![Instruction::Invokestatic(test_fn)];
+    let try_end = instructions.len();
+    let mut frames = Vec::new();
+    match &test.should_panic {
+        None => {
+            // return; catch (Panic panic) { throw new AssertionError(panic.getMessage(), panic); }
+            let cause_init = constant_pool.add_method_ref(
+                assertion_error,
+                "<init>",
+                "(Ljava/lang/String;Ljava/lang/Throwable;)V",
+            )?;
+            instructions.push(Instruction::Return);
+            let handler = instructions.len();
+            frames.push((
+                handler,
+                vec![VerificationType::Object {
+                    cpool_index: panic_class,
+                }],
+            ));
+            instructions.extend([
+                Instruction::Astore_1,
+                Instruction::New(assertion_error),
+                Instruction::Dup,
+                Instruction::Aload_1,
+                Instruction::Invokevirtual(get_message),
+                Instruction::Aload_1,
+                Instruction::Invokespecial(cause_init),
+                Instruction::Athrow,
+            ]);
+        }
+        Some(expected) => {
+            // throw new AssertionError("test did not panic as expected");
+            let no_panic = constant_pool.add_string("test did not panic as expected")?;
+            instructions.extend([
+                Instruction::New(assertion_error),
+                Instruction::Dup,
+                ldc(no_panic),
+                Instruction::Invokespecial(message_init),
+                Instruction::Athrow,
+            ]);
+            let handler = instructions.len();
+            frames.push((
+                handler,
+                vec![VerificationType::Object {
+                    cpool_index: panic_class,
+                }],
+            ));
+            if expected.is_empty() {
+                // catch (Panic panic) { return; }
+                instructions.extend([Instruction::Pop, Instruction::Return]);
+            } else {
+                // catch (Panic panic) { if (panic.getMessage().contains(expected)) return; throw .. }
+                let string_class = constant_pool.add_class("java/lang/String")?;
+                let contains = constant_pool.add_method_ref(
+                    string_class,
+                    "contains",
+                    "(Ljava/lang/CharSequence;)Z",
+                )?;
+                let expected_string = constant_pool.add_string(expected)?;
+                let mismatch = constant_pool.add_string(format!(
+                    "panic did not contain expected string: `{expected}`"
+                ))?;
+                instructions.extend([
+                    Instruction::Invokevirtual(get_message),
+                    ldc(expected_string),
+                    Instruction::Invokevirtual(contains),
+                    Instruction::Ifeq(0),
+                    Instruction::Return,
+                ]);
+                let branch = instructions.len() - 2;
+                let failed = instructions.len();
+                instructions[branch] = Instruction::Ifeq(failed as u16);
+                frames.push((failed, Vec::new()));
+                instructions.extend([
+                    Instruction::New(assertion_error),
+                    Instruction::Dup,
+                    ldc(mismatch),
+                    Instruction::Invokespecial(message_init),
+                    Instruction::Athrow,
+                ]);
+            }
+        }
+    }
+    let handler = frames[0].0;
+
+    let locals = [VerificationType::Object {
+        cpool_index: this_class,
+    }];
+    let stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+    let exception_table = vec![ExceptionTableEntry {
+        range_pc: 0..try_end as u16,
+        handler_pc: handler as u16,
+        catch_type: panic_class,
+    }];
+    let mut method = create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        &test.method_name(),
+        "()V",
+        &MethodCode {
+            instructions,
+            exception_table,
+            stack_map_frames,
+        },
+    )?;
+    method
+        .attributes
+        .push(test_annotations(constant_pool, test)?);
+    Ok(method)
+}
+
+/// The `@Test`, `@DisplayName` and `@Disabled` annotations of a test method
+fn test_annotations(
+    constant_pool: &mut ConstantPool,
+    test: &TestFunction,
+) -> ristretto_classfile::Result<Attribute> {
+    let mut annotations = vec![
+        Annotation {
+            type_index: constant_pool.add_utf8(TEST)?,
+            elements: Vec::new(),
+        },
+        Annotation {
+            type_index: constant_pool.add_utf8(DISPLAY_NAME)?,
+            elements: vec![AnnotationValuePair {
+                name_index: constant_pool.add_utf8("value")?,
+                value: AnnotationElement::String {
+                    const_value_index: constant_pool.add_utf8(&test.path)?,
+                },
+            }],
+        },
+    ];
+    if test.ignored {
+        annotations.push(Annotation {
+            type_index: constant_pool.add_utf8(DISABLED)?,
+            elements: Vec::new(),
+        });
+    }
+    Ok(Attribute::RuntimeVisibleAnnotations {
+        name_index: constant_pool.add_utf8("RuntimeVisibleAnnotations")?,
+        annotations,
+    })
+}
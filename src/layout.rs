@@ -0,0 +1,86 @@
+//! `size_of`, `align_of` and `offset_of!`, and the checks the standard library compiles in.
+
+use crate::MirToBytecodeVisitor;
+use crate::options;
+use crate::trace::trace;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{NullOp, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TypingEnv};
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes the value of a nullary operator: a layout constant, or whether a kind of checks
+    /// runs. Returns whether it was pushed, which it is not for types without a layout.
+    pub(crate) fn load_nullary_op(&mut self, null_op: &NullOp<'tcx>, ty: Ty<'tcx>) -> bool {
+        let checks = match null_op {
+            NullOp::UbChecks => Some(self.tcx.sess.ub_checks()),
+            NullOp::ContractChecks => Some(self.tcx.sess.contract_checks()),
+            NullOp::SizeOf | NullOp::AlignOf | NullOp::OffsetOf(_) => None,
+        };
+        if let Some(checks) = checks {
+            let instruction = self.int_constant(i32::from(checks));
+            self.method_bytecode_instructions.push(instruction);
+            return true;
+        }
+
+        let ty = self.monomorphize(ty);
+        let typing_env = TypingEnv::fully_monomorphized();
+        let layout = match self.tcx.layout_of(typing_env.as_query_input(ty)) {
+            Ok(layout) => layout,
+            Err(err) => {
+                self.tcx
+                    .dcx()
+                    .span_err(self.span, format!("the layout of `{ty}` is unknown: {err}"));
+                return false;
+            }
+        };
+        let value = match null_op {
+            NullOp::SizeOf => layout.size.bytes(),
+            NullOp::AlignOf => layout.align.abi.bytes(),
+            NullOp::OffsetOf(fields) => self
+                .tcx
+                .offset_of_subfield(typing_env, layout, fields.iter())
+                .bytes(),
+            NullOp::UbChecks | NullOp::ContractChecks => unreachable!("checks were pushed"),
+        };
+        self.push_usize_constant(value);
+        true
+    }
+
+    /// Lowers calls of `mem::size_of` and `mem::align_of` to the layout constants they return,
+    /// returning `false` for any other call
+    pub(crate) fn lower_layout_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let Some(name) = self.tcx.get_diagnostic_name(callee) else {
+            return false;
+        };
+        let null_op = match name.as_str() {
+            "mem_size_of" => NullOp::SizeOf,
+            "mem_align_of" => NullOp::AlignOf,
+            _ => return false,
+        };
+        if self.load_nullary_op(&null_op, generic_args.type_at(0)) {
+            self.store_call_result(destination);
+        }
+        trace!(
+            "      Generated bytecode: {}",
+            self.tcx.def_path_str(callee)
+        );
+        true
+    }
+
+    /// Pushes a `usize` constant, an `int` or a `long` as the `usize-width` option has it
+    fn push_usize_constant(&mut self, value: u64) {
+        // Sizes are bounded by the 32-bit address space of the target
+        let value = i32::try_from(value).expect("layout constants fit an int");
+        let instruction = if options::get().usize_width == 64 {
+            self.long_constant(i64::from(value))
+        } else {
+            self.int_constant(value)
+        };
+        self.method_bytecode_instructions.push(instruction);
+    }
+}
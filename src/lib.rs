@@ -1,4 +1,3 @@
-#![feature(alloc_error_hook)]
 #![feature(box_patterns)]
 #![feature(rustc_private)]
 #![warn(clippy::pedantic)]
@@ -11,31 +10,127 @@
 //! Supports both Rust static libraries and binaries, generating a file - [cratename].class as it's output.
 //! The class file supports Java 8 or later.
 
+extern crate rustc_abi;
+extern crate rustc_ast;
+extern crate rustc_attr_data_structures;
 extern crate rustc_codegen_ssa;
 extern crate rustc_data_structures;
 extern crate rustc_driver;
 extern crate rustc_hir;
+extern crate rustc_incremental;
+extern crate rustc_index;
 extern crate rustc_metadata;
 extern crate rustc_middle;
 extern crate rustc_session;
+extern crate rustc_span;
 extern crate rustc_target;
+
+mod agent;
+mod allocator;
+mod annotations;
+mod array;
+mod atomics;
+mod attributes;
+mod boxing;
+mod cells;
+mod cgu;
+mod class_map;
+mod compare;
+mod constant_pool;
+mod control_flow;
+mod coroutines;
+mod crates;
+mod disassembly;
+mod entry;
+mod enums;
+mod env;
+mod export;
+mod fmt;
+mod harness;
+mod host;
+mod inline;
+mod interop;
+mod intrinsics;
+mod io;
+mod iterators;
+mod javadoc;
+mod jmh;
+mod jni;
+mod junit;
+mod layout;
+mod math;
+mod maven;
+mod mem;
+mod mono;
+mod multi_release;
+mod naming;
+mod nest;
+mod nullable;
+mod objects;
+mod opt_level;
+mod options;
+mod panic;
+mod peephole;
+mod pointers;
+mod range;
+mod records;
+mod references;
+mod runtime;
+mod rustlib;
+mod services;
+mod signature;
+mod simd;
+mod smart_pointers;
+mod string;
+mod stubs;
+mod support;
+mod target;
+mod time;
+mod trace;
+mod trait_objects;
+mod unions;
+mod unsupported;
+mod vec;
+mod verifier;
+mod visibility;
+
 use ristretto_classfile::attributes::MaxLocals;
 use ristretto_classfile::attributes::MaxStack;
 
+use constant_pool::ConstantPool;
+use opt_level::Optimizations;
+use runtime::RuntimeClasses;
+use rustc_abi::{FieldIdx, VariantIdx};
 use rustc_codegen_ssa::back::archive::{ArArchiveBuilder, ArchiveBuilder, ArchiveBuilderBuilder};
 use rustc_codegen_ssa::{
-    CodegenResults, CompiledModule, CrateInfo, ModuleKind, traits::CodegenBackend,
+    CodegenResults, CompiledModule, CrateInfo, ModuleKind, TargetConfig, traits::CodegenBackend,
 };
-use rustc_data_structures::fx::FxIndexMap;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_index::IndexVec;
 use rustc_metadata::EncodedMetadata;
 use rustc_middle::dep_graph::{WorkProduct, WorkProductId};
 use rustc_middle::mir::{
-    BasicBlock, BasicBlockData, BinOp, Body, Location, Rvalue, Statement, StatementKind,
-    Terminator, TerminatorKind, visit::Visitor,
+    AggregateKind, BasicBlock, BasicBlockData, BinOp, Body, BorrowKind, CastKind, Local, Location,
+    Operand, Place, ProjectionElem, RETURN_PLACE, RawPtrKind, Rvalue, Statement, StatementKind,
+    Terminator, TerminatorKind, UnOp, visit::Visitor,
+};
+use rustc_middle::ty::adjustment::PointerCoercion;
+use rustc_middle::ty::{AliasTyKind, Instance, Mutability, Ty, TyCtxt, TypingEnv};
+use rustc_session::{
+    Session,
+    config::{OutputFilenames, OutputType, PrintRequest},
+};
+use rustc_span::Span;
+use rustc_span::source_map::Spanned;
+use std::{
+    any::Any,
+    io::Write,
+    path::{Path, PathBuf},
+    vec,
 };
-use rustc_middle::ty::{Instance, Ty, TyCtxt};
-use rustc_session::{Session, config::OutputFilenames};
-use std::{any::Any, io::Write, path::Path, vec};
+use trace::trace;
 
 /// An instance of our Java bytecode codegen backend.
 struct MyBackend;
@@ -45,64 +140,178 @@ impl CodegenBackend for MyBackend {
         ""
     }
 
+    fn init(&self, sess: &Session) {
+        host::check_target(sess);
+        options::init(sess);
+    }
+
+    fn target_config(&self, _sess: &Session) -> TargetConfig {
+        target::target_config()
+    }
+
+    fn print(&self, req: &PrintRequest, out: &mut String, _sess: &Session) {
+        target::print(req, out);
+    }
+
     fn codegen_crate<'a>(
         &self,
         tcx: TyCtxt<'_>,
         metadata: EncodedMetadata,
         _need_metadata_module: bool,
     ) -> Box<dyn Any> {
-        let mut function_bytecodes = FxIndexMap::default();
-        let crate_name = tcx
-            .crate_name(rustc_hir::def_id::CRATE_DEF_ID.to_def_id().krate)
-            .to_string();
+        host::check_crate_types(tcx.sess);
+        // `--emit=metadata` and `--emit=dep-info` alone need no classes, rustc writes them itself
+        if !tcx.sess.opts.output_types.should_codegen() {
+            return Box::new((
+                Vec::<(String, Vec<u8>)>::new(),
+                None::<(String, Vec<u8>)>,
+                None::<Vec<u8>>,
+                Vec::<(String, cgu::UnitWorkProduct)>::new(),
+                metadata,
+                CrateInfo::new(tcx, "java_bytecode_basic_class".to_string()),
+            ));
+        }
+        trace::start(tcx);
+        let mut runtime_classes = RuntimeClasses::default();
+        let crate_name = naming::crate_class_name(tcx);
 
         // Iterate through all items in the crate and find functions
+        let mut functions = Vec::new();
+        let mut global_allocator = None;
         let module_items = tcx.hir_crate_items(()); // Get ModuleItems
         for item_id in module_items.free_items() {
             // Use free_items() iterator
             let item = tcx.hir_item(item_id);
             if let rustc_hir::ItemKind::Fn {
-                ident: i,
+                ident: _,
                 sig: _,
                 generics: _,
                 body: _,
                 has_body: _,
             } = item.kind
             {
-                // Corrected destructuring
+                // Generic functions are not lowered on their own, as they cannot be called yet
                 let def_id = item_id.owner_id.to_def_id();
-                let instance = rustc_middle::ty::Instance::mono(tcx, def_id);
-                let mir = tcx.optimized_mir(instance.def_id());
-
-                println!("--- Starting MIR Visitor for function: {i} ---");
-                let method_bytecode_instructions: Vec<Instruction> = Vec::new();
-                let function_name = i.to_string(); // Capture function name
-                let mut visitor = MirToBytecodeVisitor::new(
-                    method_bytecode_instructions,
-                    &function_name,
-                    tcx,
-                    instance,
-                ); // Pass tcx and instance
-                visitor.visit_body(mir);
-                let generated_bytecode = visitor.method_bytecode_instructions;
-                println!("--- MIR Visitor Finished for function: {i} ---");
-
-                function_bytecodes.insert(function_name, generated_bytecode); // Store bytecode
-            }
-        }
-
-        // Generate basic Java bytecode for a class with static methods,
-        // passing function_bytecodes which now contains bytecodes for each function
-        let bytecode = generate_class_with_static_methods_bytecode(
-            crate_name.as_str(),
-            &function_bytecodes,
+                if let Some(span) = allocator::global_allocator_of(tcx, def_id) {
+                    // The functions of a `#[global_allocator]` are never called on the JVM
+                    global_allocator = Some(span);
+                } else if !tcx.generics_of(def_id).requires_monomorphization(tcx) {
+                    functions.push(def_id);
+                }
+            }
+        }
+        if let Some(span) = global_allocator {
+            allocator::warn_unused_global_allocator(tcx, span);
+        }
+        naming::check_method_names(tcx, &functions);
+        naming::check_crate_names(tcx);
+        naming::check_package(tcx);
+        agent::check_premain_functions(tcx, &functions);
+        support::start();
+        unsupported::check_crate(tcx, &functions);
+        let api_docs = javadoc::api_docs(tcx, &functions);
+
+        // Trait methods, derived ones included, and the shims they need, as monomorphization
+        // collected them
+        let instances = mono::collected_instances(tcx);
+
+        // A class with a static method for every function and instance of each codegen unit, the
+        // crate class first
+        let mut classes = Vec::new();
+        let mut work_products = Vec::new();
+        let mut lowered_instances = Vec::new();
+        for unit in cgu::partition(tcx, &crate_name, functions, instances) {
+            lowered_instances.extend(
+                unit.functions
+                    .iter()
+                    .map(|def_id| Instance::mono(tcx, *def_id)),
+            );
+            lowered_instances.extend(unit.instances.iter().copied());
+            let class = cgu::codegen_unit_class(tcx, unit);
+            runtime_classes.extend(class.runtime_classes);
+            if let Some(work_product) = class.work_product {
+                work_products.push((class.class_name.clone(), work_product));
+            }
+            classes.push((class.class_name, class.bytecode));
+        }
+
+        // The crate class always comes first, followed by the classes of the other codegen units,
+        // one class per `#[jvm::export]` struct, the interface and variant classes of every
+        // `#[jvm::export]` enum and their variants for a multi-release JAR, the classes of `async`
+        // bodies, the interfaces of trait objects and their adapters, the JUnit test class of
+        // `--test` builds, the JMH benchmark class, and the runtime classes all of them use, for
+        // libraries
+        classes.extend(exported_type_classes(
             tcx,
-        )
-        .unwrap_or_default(); // Modified function to pass tcx
+            &mut runtime_classes,
+            options::get().class_version,
+        ));
+        classes.extend(multi_release::versioned_classes(tcx, &mut runtime_classes));
+        match coroutines::generate_coroutine_classes(tcx, &lowered_instances) {
+            Ok(coroutine_classes) => classes.extend(coroutine_classes),
+            Err(err) => {
+                tcx.dcx()
+                    .err(format!("could not generate coroutine classes: {err:?}"));
+            }
+        }
+        match trait_objects::generate_trait_object_classes(tcx, &lowered_instances) {
+            Ok(trait_object_classes) => classes.extend(trait_object_classes),
+            Err(err) => {
+                tcx.dcx()
+                    .err(format!("could not generate trait object classes: {err:?}"));
+            }
+        }
+        if tcx.sess.is_test_crate() {
+            match junit::generate_test_class(tcx, &crate_name, &mut runtime_classes) {
+                Ok(test_class) => classes.push(test_class),
+                Err(err) => {
+                    tcx.dcx()
+                        .err(format!("could not generate the test class: {err:?}"));
+                }
+            }
+        }
+        match jmh::generate_benchmark_class(tcx, &crate_name) {
+            Ok(benchmark_class) => classes.extend(benchmark_class),
+            Err(err) => {
+                tcx.dcx()
+                    .err(format!("could not generate the benchmark class: {err:?}"));
+            }
+        }
+        // Without the runtime bundled, it is expected on the class path. Executables link all of
+        // it as the runtime JAR instead, see `join_codegen`
+        if options::get().bundle_runtime && !rustlib::links_runtime_jar(tcx.sess) {
+            match runtime::generate_runtime_classes(&runtime_classes, tcx.sess.panic_strategy()) {
+                Ok(runtime_classes) => classes.extend(runtime_classes),
+                Err(err) => {
+                    tcx.dcx()
+                        .err(format!("could not generate runtime classes: {err:?}"));
+                }
+            }
+        }
+        if let Err(err) = nest::add_nest_attributes(tcx, &mut classes, options::get().class_version)
+        {
+            tcx.dcx()
+                .err(format!("could not add the nest attributes: {err:?}"));
+        }
+        stubs::emit_api_stubs(tcx, &classes, &api_docs);
+        trace::finish();
+        support::finish(tcx);
+
+        // The allocator module, when rustc asks for the allocator shim
+        let allocator_class = allocator::generate_allocator_class(tcx).unwrap_or_else(|err| {
+            tcx.dcx()
+                .err(format!("could not generate the allocator class: {err:?}"));
+            None
+        });
+
+        // The `META-INF/services` files of the crate's service providers
+        let services_jar = services::services_jar(tcx);
 
         Box::new((
-            bytecode,
-            crate_name,
+            classes,
+            allocator_class,
+            services_jar,
+            work_products,
             metadata,
             CrateInfo::new(tcx, "java_bytecode_basic_class".to_string()),
         ))
@@ -111,47 +320,148 @@ impl CodegenBackend for MyBackend {
     fn join_codegen(
         &self,
         ongoing_codegen: Box<dyn Any>,
-        _sess: &Session,
+        sess: &Session,
         outputs: &OutputFilenames,
     ) -> (CodegenResults, FxIndexMap<WorkProductId, WorkProduct>) {
         std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            let (bytecode, crate_name, metadata, crate_info) = *ongoing_codegen
-                .downcast::<(Vec<u8>, String, EncodedMetadata, CrateInfo)>()
-                .expect("in join_codegen: ongoing_codegen is not bytecode vector");
-
-            let class_path = outputs.temp_path_ext("class", None);
-
-            let mut class_file =
-                std::fs::File::create(&class_path).expect("Could not create the Java .class file!");
-            class_file
-                .write_all(&bytecode)
-                .expect("Could not write Java bytecode to file!");
-
-            let modules = vec![CompiledModule {
-                name: crate_name,
-                kind: ModuleKind::Regular,
-                object: Some(class_path),
-                bytecode: None,
-                dwarf_object: None,
-                llvm_ir: None,
-                links_from_incr_cache: Vec::new(), // Corrected to Vec::new()
-                assembly: None,
-            }];
+            let (mut classes, allocator_class, services_jar, work_products, metadata, crate_info) =
+                *ongoing_codegen
+                    .downcast::<(
+                        Vec<(String, Vec<u8>)>,
+                        Option<(String, Vec<u8>)>,
+                        Option<Vec<u8>>,
+                        Vec<(String, cgu::UnitWorkProduct)>,
+                        EncodedMetadata,
+                        CrateInfo,
+                    )>()
+                    .expect("in join_codegen: ongoing_codegen is not a list of classes");
+            let allocator_class_name = allocator_class.as_ref().map(|(name, _)| name.clone());
+            classes.extend(allocator_class);
+            if sess.opts.output_types.contains_key(&OutputType::Assembly) {
+                disassembly::emit_listing(sess, &classes, outputs);
+            }
+            if sess.opts.output_types.contains_key(&OutputType::Object) {
+                let objects: Vec<_> = classes
+                    .iter()
+                    .map(|(class_name, bytecode)| (class_name.as_str(), bytecode.as_slice()))
+                    .collect();
+                objects::emit_objects(sess, &objects, outputs);
+            }
+
+            // The crate class keeps the plain temp path unless it is in a package, any other class
+            // gets its name embedded so the paths are unique. The linker finds the names of the
+            // classes in the class map
+            let class_files: Vec<_> = classes
+                .into_iter()
+                .enumerate()
+                .map(|(index, (class_name, bytecode))| {
+                    let class_path = if index == 0 && !class_name.contains('/') {
+                        outputs.temp_path_ext("class", None)
+                    } else {
+                        outputs.temp_path_ext("class", Some(&class_name.replace('/', ".")))
+                    };
+                    (class_name, class_path, bytecode)
+                })
+                .collect();
+
+            // Classes are written concurrently, split evenly across the available threads
+            let threads = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+            let chunk_size = class_files.len().div_ceil(threads).max(1);
+            std::thread::scope(|scope| {
+                let writers: Vec<_> = class_files
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            for (_, class_path, bytecode) in chunk {
+                                let mut class_file = std::fs::File::create(class_path)
+                                    .expect("Could not create the Java .class file!");
+                                class_file
+                                    .write_all(bytecode)
+                                    .expect("Could not write Java bytecode to file!");
+                            }
+                        })
+                    })
+                    .collect();
+                for writer in writers {
+                    writer
+                        .join()
+                        .expect("Could not write the Java .class files!");
+                }
+            });
+
+            let class_paths = class_files
+                .iter()
+                .map(|(class_name, class_path, _)| (class_name.as_str(), class_path))
+                .collect();
+            let work_products = cgu::save_work_products(sess, work_products, &class_paths);
+            let class_map_path = outputs.temp_path_ext(class_map::CLASS_MAP_EXTENSION, None);
+            let class_map = class_map::class_map(
+                crate_info.local_crate_name.as_str(),
+                class_files
+                    .iter()
+                    .map(|(class_name, class_path, _)| (class_name.as_str(), class_path.as_path())),
+            );
+            std::fs::write(&class_map_path, class_map).expect("Could not write the class map!");
+
+            let (allocator_modules, mut modules): (Vec<_>, Vec<_>) = class_files
+                .into_iter()
+                .map(|(class_name, class_path, _)| {
+                    let kind = if allocator_class_name.as_ref() == Some(&class_name) {
+                        ModuleKind::Allocator
+                    } else {
+                        ModuleKind::Regular
+                    };
+                    linked_module(class_name, class_path, kind)
+                })
+                .partition(|module| module.kind == ModuleKind::Allocator);
+            // JARs the linker merges into the executable's JAR, or that libraries carry in their
+            // rlibs for it
+            let jar_module = |name: &str, jar: Vec<u8>| {
+                let jar_path = outputs.temp_path_ext("jar", Some(name));
+                std::fs::write(&jar_path, jar).expect("Could not write a JAR for the linker!");
+                linked_module(name.to_string(), jar_path, ModuleKind::Regular)
+            };
+            if options::get().bundle_runtime && rustlib::links_runtime_jar(sess) {
+                let jar = rustlib::runtime_jar(sess).expect("Could not generate the runtime JAR!");
+                modules.push(jar_module(rustlib::RUSTLIB_NAME, jar));
+            }
+            if rustlib::links_runtime_jar(sess)
+                && let Some(jar) = maven::metadata_jar(sess)
+            {
+                modules.push(jar_module(maven::MAVEN_METADATA_NAME, jar));
+            }
+            if let Some(jar) = services_jar {
+                modules.push(jar_module(services::SERVICES_NAME, jar));
+            }
+            // The crates an executable links, whose rlibs the linker may not be given
+            if rustlib::links_runtime_jar(sess) {
+                let crates_path = outputs.temp_path_ext(crates::CRATES_EXTENSION, None);
+                std::fs::write(&crates_path, crates::crate_list(&crate_info))
+                    .expect("Could not write the list of the linked crates!");
+                modules.push(linked_module(
+                    crates::CRATES_EXTENSION.to_string(),
+                    crates_path,
+                    ModuleKind::Regular,
+                ));
+            }
+            modules.push(linked_module(
+                class_map::CLASS_MAP_EXTENSION.to_string(),
+                class_map_path,
+                ModuleKind::Regular,
+            ));
             let codegen_results = CodegenResults {
                 modules,
-                allocator_module: None,
+                allocator_module: allocator_modules.into_iter().next(),
                 metadata_module: None,
                 metadata,
                 crate_info,
             };
-            (codegen_results, FxIndexMap::default())
+            (codegen_results, work_products)
         }))
         .expect("Could not join_codegen")
     }
 
     fn link(&self, sess: &Session, codegen_results: CodegenResults, outputs: &OutputFilenames) {
-        println!("linking!");
-
         use rustc_codegen_ssa::back::link::link_binary;
         link_binary(sess, &RlibArchiveBuilder, codegen_results, outputs);
     }
@@ -159,28 +469,84 @@ impl CodegenBackend for MyBackend {
 
 #[unsafe(no_mangle)]
 pub extern "Rust" fn __rustc_codegen_backend() -> Box<dyn CodegenBackend> {
-    std::alloc::set_alloc_error_hook(custom_alloc_error_hook);
     Box::new(MyBackend)
 }
 
-use ristretto_classfile::attributes::{Attribute, Instruction};
-use ristretto_classfile::{
-    BaseType, ClassAccessFlags, ClassFile, ConstantPool, Method, MethodAccessFlags, Version,
+use ristretto_classfile::attributes::{
+    Attribute, ExceptionTableEntry, Instruction, StackFrame, VerificationType,
 };
-use std::alloc::Layout;
-
-/// # Panics
-///
-/// Panics when called, every time, with a message statating the memory allocation of the bytes
-/// corresponding to the provided layout failed.
-pub fn custom_alloc_error_hook(layout: Layout) {
-    panic!("Memory allocation failed: {} bytes", layout.size());
-}
+use ristretto_classfile::{BaseType, ClassAccessFlags, ClassFile, Method, MethodAccessFlags};
 
 // --- Improved helper function to convert Rust Ty to JVM descriptor ---
-fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
+/// A file handed to the linker as one of the crate's objects
+fn linked_module(name: String, path: PathBuf, kind: ModuleKind) -> CompiledModule {
+    CompiledModule {
+        name,
+        kind,
+        object: Some(path),
+        bytecode: None,
+        dwarf_object: None,
+        llvm_ir: None,
+        links_from_incr_cache: Vec::new(),
+        assembly: None,
+    }
+}
+
+fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, tcx: TyCtxt<'_>) -> String {
     use rustc_middle::ty::{FloatTy, IntTy, TyKind, UintTy};
 
+    // `Option`s of references are the reference itself, `null` for `None`
+    if let Some(payload) = nullable::payload(rust_ty, tcx) {
+        return rust_ty_to_jvm_descriptor(payload, tcx);
+    }
+    // So are `Box`es, `Rc`s and `Arc`s of them
+    if let Some(pointee) = smart_pointers::pointee(rust_ty, tcx) {
+        return rust_ty_to_jvm_descriptor(pointee, tcx);
+    }
+    // `Pin`s are the pointer they pin, `Poll`s the value they are ready with, and
+    // `CoroutineState`s a runtime class, see `coroutines`
+    if let Some(pointer) = coroutines::pinned_pointer(rust_ty, tcx) {
+        return rust_ty_to_jvm_descriptor(pointer, tcx);
+    }
+    if let Some(output) = coroutines::poll_output(rust_ty, tcx) {
+        return rust_ty_to_jvm_descriptor(output, tcx);
+    }
+    if coroutines::is_coroutine_state(rust_ty, tcx) {
+        return format!("L{};", runtime::RuntimeClass::CoroutineState.name());
+    }
+    // `Cell`s and `RefCell`s are wrapped in runtime classes
+    if let Some(class) = cells::runtime_class(rust_ty, tcx) {
+        return format!("L{};", class.name());
+    }
+    // Ranges of integers are runtime classes too
+    if let Some(class) = range::runtime_class(rust_ty, tcx) {
+        return format!("L{};", class.name());
+    }
+    // Mutable references to primitives are ref cells, see `references`
+    if let Some(pointee_ty) = references::ref_cell_pointee(rust_ty, tcx) {
+        return format!("[{}", rust_ty_to_jvm_descriptor(pointee_ty, tcx));
+    }
+    // Atomics are `java.util.concurrent.atomic` classes
+    if let Some((class, _)) = atomics::java_class(rust_ty, tcx) {
+        return format!("L{class};");
+    }
+    // `Instant`s, `SystemTime`s and `Duration`s are nanoseconds
+    if time::is_time_type(rust_ty, tcx) {
+        return "J".to_string();
+    }
+    // Files and their readers and writers are `java.io` classes
+    if let Some(descriptor) = io::java_descriptor(rust_ty, tcx) {
+        return descriptor;
+    }
+    // Formatted text is a `String`, and a `Formatter` the `StringBuilder` it is written to
+    if let Some(descriptor) = fmt::java_descriptor(rust_ty, tcx) {
+        return descriptor.to_string();
+    }
+    // The iterators of collections are `java.util.Iterator`s
+    if iterators::is_java_iterator(rust_ty, tcx) {
+        return "Ljava/util/Iterator;".to_string();
+    }
+
     match rust_ty.kind() {
         // Primitive types
         TyKind::Bool => BaseType::Boolean.code().to_string(),
@@ -192,7 +558,7 @@ fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
             IntTy::I16 => BaseType::Short.code().to_string(),
             IntTy::I32 => BaseType::Int.code().to_string(),
             IntTy::I64 => BaseType::Long.code().to_string(),
-            IntTy::Isize => BaseType::Int.code().to_string(), // Fallback for isize
+            IntTy::Isize => options::get().usize_descriptor().to_string(),
             IntTy::I128 => "Ljava/math/BigInteger;".to_string(), // No primitive for i128
         },
 
@@ -202,7 +568,7 @@ fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
             UintTy::U16 => BaseType::Short.code().to_string(),
             UintTy::U32 => BaseType::Int.code().to_string(),
             UintTy::U64 => BaseType::Long.code().to_string(),
-            UintTy::Usize => BaseType::Int.code().to_string(), // Fallback for usize
+            UintTy::Usize => options::get().usize_descriptor().to_string(),
             UintTy::U128 => "Ljava/math/BigInteger;".to_string(), // No primitive for u128
         },
 
@@ -215,24 +581,73 @@ fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
         },
 
         // Handle references: if it’s a string slice, map it to java.lang.String;
-        // otherwise, use a generic object reference.
-        TyKind::Ref(_, inner_ty, _) => {
-            if let TyKind::Str = inner_ty.kind() {
-                "Ljava/lang/String;".to_string()
-            } else {
-                "Ljava/lang/Object;".to_string()
-            }
+        // references to structs are the object itself, otherwise, use a generic object reference.
+        TyKind::Ref(_, inner_ty, _) => match inner_ty.kind() {
+            TyKind::Str => "Ljava/lang/String;".to_string(),
+            TyKind::Adt(..)
+            | TyKind::Array(..)
+            | TyKind::Slice(_)
+            | TyKind::Dynamic(..)
+            | TyKind::Coroutine(..) => rust_ty_to_jvm_descriptor(*inner_ty, tcx),
+            _ => "Ljava/lang/Object;".to_string(),
+        },
+
+        // Arrays and slices are JVM arrays
+        TyKind::Array(element_ty, _) | TyKind::Slice(element_ty) => {
+            format!("[{}", rust_ty_to_jvm_descriptor(*element_ty, tcx))
+        }
+
+        // SIMD vectors are arrays of their lanes, see `simd`
+        TyKind::Adt(..) if rust_ty.is_simd() => {
+            let (_, element_ty) = rust_ty.simd_size_and_type(tcx);
+            format!("[{}", rust_ty_to_jvm_descriptor(element_ty, tcx))
+        }
+
+        // Unions of primitives are the bits of their fields
+        TyKind::Adt(..) if unions::is_primitive_union(rust_ty, tcx) => {
+            unions::UNION_DESCRIPTOR.to_string()
+        }
+
+        // Structs marked `#[jvm::export]` get a class of their own
+        TyKind::Adt(adt_def, _) if attributes::has_jvm_attr(tcx, adt_def.did(), "export") => {
+            format!("L{};", export::class_name(tcx, adt_def.did()))
+        }
+
+        // Vectors are backed by `java.util.ArrayList`
+        TyKind::Adt(adt_def, _) if tcx.is_diagnostic_item(rustc_span::sym::Vec, adt_def.did()) => {
+            "Ljava/util/ArrayList;".to_string()
+        }
+
+        // Owned strings are mutable, they are backed by `java.lang.StringBuilder`
+        TyKind::Adt(..) if string::is_string(rust_ty, tcx) => {
+            "Ljava/lang/StringBuilder;".to_string()
         }
 
-        // For raw pointers, allow string pointers but panic otherwise.
-        TyKind::RawPtr(ptr_ty, _) => {
-            if let TyKind::Str = ptr_ty.kind() {
-                "Ljava/lang/String;".to_string()
+        // Trait objects of local traits are interfaces, see `trait_objects`
+        TyKind::Dynamic(predicates, ..) => match predicates.principal_def_id() {
+            Some(trait_def_id) if trait_def_id.is_local() => {
+                format!("L{};", trait_objects::interface_name(tcx, trait_def_id))
+            }
+            _ => "Ljava/lang/Object;".to_string(),
+        },
+
+        // The state of `async` bodies is held in a class of its own, see `coroutines`
+        TyKind::Coroutine(def_id, _) => format!("L{};", coroutines::class_name(tcx, *def_id)),
+
+        // `impl Trait` is the type behind it
+        TyKind::Alias(AliasTyKind::Opaque, _) => {
+            let revealed_ty =
+                tcx.normalize_erasing_regions(TypingEnv::fully_monomorphized(), rust_ty);
+            if revealed_ty == rust_ty {
+                "Ljava/lang/Object;".to_string()
             } else {
-                panic!("Pointers are not supported in Java.")
+                rust_ty_to_jvm_descriptor(revealed_ty, tcx)
             }
         }
 
+        // Raw pointers are references, see `pointers`
+        TyKind::RawPtr(pointee_ty, _) => pointers::pointer_descriptor(*pointee_ty, tcx),
+
         // Map Rust string slices directly to java.lang.String
         TyKind::Str => "Ljava/lang/String;".to_string(),
 
@@ -246,8 +661,9 @@ fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
             }
         }
 
-        // Map Rust's never type to void (even though it is conceptually different)
-        TyKind::Never => "V".to_string(),
+        // No value has the never type, a `V` would leave diverging functions and locals of type
+        // `!` without a type, so it is a reference that is only ever null
+        TyKind::Never => "Ljava/lang/Void;".to_string(),
 
         // Fallback for any unhandled types
         _ => "Ljava/lang/Object;".to_string(),
@@ -256,204 +672,1930 @@ fn rust_ty_to_jvm_descriptor(rust_ty: Ty<'_>, _tcx: TyCtxt<'_>) -> String {
 
 // --- MIR Visitor ---
 
-struct MirToBytecodeVisitor<'tcx> {
+/// The code of a lowered method, ready to be put in a `Code` attribute
+#[derive(Default)]
+struct MethodCode {
+    instructions: Vec<Instruction>,
+    exception_table: Vec<ExceptionTableEntry>,
+    stack_map_frames: Vec<StackFrame>,
+}
+
+impl From<Vec<Instruction>> for MethodCode {
+    fn from(instructions: Vec<Instruction>) -> Self {
+        MethodCode {
+            instructions,
+            ..MethodCode::default()
+        }
+    }
+}
+
+/// Lowers the functions and instances of a unit into its class, returning the class and the
+/// runtime classes it uses, see [`cgu`]
+fn lower_class_unit<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    unit: cgu::ClassUnit<'tcx>,
+) -> (Vec<u8>, RuntimeClasses) {
+    let mut constant_pool = ConstantPool::default();
+    let mut runtime_classes = RuntimeClasses::default();
+    let mut function_bytecodes = FxIndexMap::default();
+    for def_id in unit.functions {
+        let generated_bytecode =
+            lower_function(tcx, def_id, &mut constant_pool, &mut runtime_classes);
+        function_bytecodes.insert(def_id, generated_bytecode);
+    }
+    let mut instance_methods = Vec::new();
+    for instance in unit.instances {
+        let name = naming::instance_method_name(tcx, instance);
+        let code = lower_instance(
+            tcx,
+            instance,
+            &name,
+            &mut constant_pool,
+            &mut runtime_classes,
+        );
+        instance_methods.push((
+            visibility::instance_access_flags(instance),
+            name,
+            mono::instance_descriptor(tcx, instance),
+            code,
+        ));
+    }
+    let bytecode = generate_class_with_static_methods_bytecode(
+        &unit.class_name,
+        &unit.super_class,
+        constant_pool,
+        function_bytecodes,
+        instance_methods,
+        &mut runtime_classes,
+        tcx,
+    )
+    .unwrap_or_else(|err| {
+        tcx.dcx().err(format!(
+            "could not generate the class `{}`: {err:?}",
+            unit.class_name
+        ));
+        Vec::new()
+    });
+    (bytecode, runtime_classes)
+}
+
+/// The classes of the crate's `#[jvm::export]` structs, and the interface and variant classes of
+/// its `#[jvm::export]` enums
+fn exported_type_classes(
+    tcx: TyCtxt<'_>,
+    runtime_classes: &mut RuntimeClasses,
+    class_version: u16,
+) -> Vec<(String, Vec<u8>)> {
+    let mut classes = Vec::new();
+    for struct_def_id in export::exported_structs(tcx) {
+        match export::generate_exported_struct_class(
+            tcx,
+            struct_def_id,
+            runtime_classes,
+            class_version,
+        ) {
+            Ok(class) => classes.push(class),
+            Err(err) => {
+                tcx.dcx().err(format!(
+                    "could not generate class for exported struct {}: {err:?}",
+                    tcx.def_path_str(struct_def_id)
+                ));
+            }
+        }
+    }
+    for enum_def_id in enums::exported_enums(tcx) {
+        match enums::generate_exported_enum_classes(tcx, enum_def_id, class_version) {
+            Ok(enum_classes) => classes.extend(enum_classes),
+            Err(err) => {
+                tcx.dcx().err(format!(
+                    "could not generate classes for exported enum {}: {err:?}",
+                    tcx.def_path_str(enum_def_id)
+                ));
+            }
+        }
+    }
+    classes
+}
+
+fn lower_function(
+    tcx: TyCtxt<'_>,
+    def_id: DefId,
+    constant_pool: &mut ConstantPool,
+    runtime_classes: &mut RuntimeClasses,
+) -> MethodCode {
+    // The `main` of a test binary runs the tests, see `harness`
+    if tcx.sess.is_test_crate() && entry::is_entry_fn(tcx, def_id) {
+        return harness::harness_main(tcx, constant_pool);
+    }
+    lower_instance(
+        tcx,
+        Instance::mono(tcx, def_id),
+        &tcx.def_path_str(def_id),
+        constant_pool,
+        runtime_classes,
+    )
+}
+
+/// Lowers the MIR of an instance, a function item or a compiler-generated shim
+fn lower_instance<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instance: Instance<'tcx>,
+    function_name: &str,
+    constant_pool: &mut ConstantPool,
+    runtime_classes: &mut RuntimeClasses,
+) -> MethodCode {
+    let mir = tcx.instance_mir(instance.def);
+
+    let method_bytecode_instructions: Vec<Instruction> = Vec::new();
+    let mut visitor = MirToBytecodeVisitor::new(
+        method_bytecode_instructions,
+        function_name,
+        tcx,
+        instance,
+        mir,
+        constant_pool,
+        runtime_classes,
+    );
+    support::count_function();
+    visitor.visit_body(mir);
+    if visitor.unsupported {
+        return visitor.unsupported_code();
+    }
+    visitor.finish()
+}
+
+struct MirToBytecodeVisitor<'a, 'tcx> {
     method_bytecode_instructions: Vec<Instruction>,
     function_name: String,    // Store function name
     tcx: TyCtxt<'tcx>,        // Store TyCtxt
     instance: Instance<'tcx>, // Store Instance
+    body: &'tcx Body<'tcx>,
+    constant_pool: &'a mut ConstantPool,
+    // Runtime classes the generated code uses
+    runtime_classes: &'a mut RuntimeClasses,
+    // JVM local variable slot of every MIR local, `None` for zero-sized locals like `()`
+    local_slots: Vec<Option<u16>>,
+    local_descriptors: Vec<String>,
+    // Locals holding the `(value, overflowed)` pair of a checked operation: the value is kept in
+    // the local's slot, and the flag of `overflowing_*` method results, and of the MIR operators
+    // when overflow checks read it, in a slot of its own
+    checked_locals: FxHashSet<Local>,
+    overflow_flag_slots: FxHashMap<Local, u16>,
+    // Enum locals are kept unpacked: their slot holds the discriminant, and every variant field
+    // gets a slot of its own
+    enum_field_slots: FxHashMap<(Local, VariantIdx, FieldIdx), (u16, String)>,
+    // Primitive locals that are mutably borrowed live in a ref cell, held in a slot of its own,
+    // see `references`
+    ref_cell_slots: FxHashMap<Local, u16>,
+    // Every allocated slot with its descriptor, and how many of them hold arguments
+    slot_descriptors: Vec<(u16, String)>,
+    argument_slot_count: usize,
+    // Instruction index each basic block starts at, and the branches still pointing at a block
+    block_starts: IndexVec<BasicBlock, usize>,
+    block_branches: Vec<(usize, BasicBlock)>,
+    exception_table: Vec<ExceptionTableEntry>,
+    // Instructions other than block starts that need a stack map frame, with their stack
+    extra_frames: Vec<(usize, Vec<VerificationType>)>,
+    // Whether this is a `main` returning a `Result`, whose return place is unpacked too
+    main_result: bool,
+    // Span of the statement or terminator being lowered, for diagnostics
+    span: Span,
+    // Instruction index each statement and terminator starts at, to report invalid code
+    instruction_sources: Vec<(usize, Location)>,
+    // Whether the function uses a construct that went to the support report, after which the rest
+    // of its MIR is left out and the method throws instead, see `support`
+    unsupported: bool,
 }
 
-impl<'tcx> MirToBytecodeVisitor<'tcx> {
+impl<'a, 'tcx> MirToBytecodeVisitor<'a, 'tcx> {
     fn new(
         method_bytecode_instructions: Vec<Instruction>,
         function_name: &str,
         tcx: TyCtxt<'tcx>,
         instance: Instance<'tcx>,
+        body: &'tcx Body<'tcx>,
+        constant_pool: &'a mut ConstantPool,
+        runtime_classes: &'a mut RuntimeClasses,
     ) -> Self {
         MirToBytecodeVisitor {
             method_bytecode_instructions,
             function_name: function_name.to_string(), // Store function name
             tcx,                                      // Store TyCtxt
             instance,                                 // Store Instance
+            body,
+            constant_pool,
+            runtime_classes,
+            local_slots: Vec::new(),
+            local_descriptors: Vec::new(),
+            checked_locals: FxHashSet::default(),
+            overflow_flag_slots: FxHashMap::default(),
+            enum_field_slots: FxHashMap::default(),
+            ref_cell_slots: FxHashMap::default(),
+            slot_descriptors: Vec::new(),
+            argument_slot_count: 0,
+            block_starts: IndexVec::new(),
+            block_branches: Vec::new(),
+            exception_table: Vec::new(),
+            extra_frames: Vec::new(),
+            main_result: entry::is_result_main(tcx, instance.def_id()),
+            span: body.span,
+            instruction_sources: Vec::new(),
+            unsupported: false,
         }
     }
-}
 
-impl Visitor<'_> for MirToBytecodeVisitor<'_> {
-    fn visit_body(&mut self, body: &Body<'_>) {
-        println!(
-            "Visiting function body for function: {}...",
-            self.function_name
+    /// Assigns a JVM local variable slot to every MIR local. Arguments take the slots the JVM
+    /// passes them in (a `self` receiver becomes `this` in slot 0), everything else follows.
+    fn allocate_local_slots(&mut self, body: &Body<'tcx>) {
+        for local_decl in &body.local_decls {
+            unions::check_union(local_decl.ty, self.tcx, local_decl.source_info.span);
+        }
+        let mut overflowing_locals = FxHashSet::default();
+        for block in body.basic_blocks.iter() {
+            for statement in &block.statements {
+                if let StatementKind::Assign(box (place, Rvalue::BinaryOp(bin_op, _))) =
+                    &statement.kind
+                {
+                    if matches!(
+                        bin_op,
+                        BinOp::AddWithOverflow | BinOp::SubWithOverflow | BinOp::MulWithOverflow
+                    ) {
+                        self.checked_locals.insert(place.local);
+                        // The flag is only read by overflow checks
+                        if self.tcx.sess.overflow_checks() {
+                            overflowing_locals.insert(place.local);
+                        }
+                    }
+                }
+            }
+            if let Some(Terminator {
+                kind:
+                    TerminatorKind::Call {
+                        func, destination, ..
+                    },
+                ..
+            }) = &block.terminator
+                && let Some((callee, _)) = func.const_fn_def()
+                && integers::is_overflowing_method(self.tcx, callee)
+            {
+                self.checked_locals.insert(destination.local);
+                overflowing_locals.insert(destination.local);
+            }
+        }
+
+        self.local_descriptors = body
+            .local_decls
+            .iter_enumerated()
+            .map(|(local, decl)| match decl.ty.kind() {
+                rustc_middle::ty::TyKind::Tuple(fields) if self.checked_locals.contains(&local) => {
+                    rust_ty_to_jvm_descriptor(fields[0], self.tcx)
+                }
+                _ => rust_ty_to_jvm_descriptor(decl.ty, self.tcx),
+            })
+            .collect();
+        self.local_slots = vec![None; body.local_decls.len()];
+        let ref_cell_locals = self.ref_cell_locals(body);
+
+        let mut next_slot: u16 = 0;
+        for local in body.args_iter() {
+            let descriptor = self.local_descriptors[local.as_usize()].clone();
+            self.local_slots[local.as_usize()] = self.allocate_slot(&mut next_slot, descriptor);
+        }
+        self.argument_slot_count = self.slot_descriptors.len();
+        // Arguments are passed in their slot, and copied into their ref cell on entry
+        for local in body.args_iter() {
+            if ref_cell_locals.contains(&local) {
+                self.allocate_ref_cell(&mut next_slot, local);
+            }
+        }
+
+        let other_locals = body
+            .local_decls
+            .indices()
+            .filter(|local| *local == RETURN_PLACE || local.as_usize() > body.arg_count);
+        for local in other_locals {
+            if self.is_unpacked_enum(local) {
+                let rustc_middle::ty::TyKind::Adt(adt_def, generic_args) =
+                    body.local_decls[local].ty.kind()
+                else {
+                    unreachable!("unpacked enums are ADTs");
+                };
+                // Unpacked enum: discriminant first, then every field of every variant
+                self.local_descriptors[local.as_usize()] = "I".to_string();
+                self.local_slots[local.as_usize()] =
+                    self.allocate_slot(&mut next_slot, "I".to_string());
+                for (variant_index, variant) in adt_def.variants().iter_enumerated() {
+                    for (field_index, field) in variant.fields.iter_enumerated() {
+                        let descriptor =
+                            rust_ty_to_jvm_descriptor(field.ty(self.tcx, generic_args), self.tcx);
+                        if let Some(slot) = self.allocate_slot(&mut next_slot, descriptor.clone()) {
+                            self.enum_field_slots
+                                .insert((local, variant_index, field_index), (slot, descriptor));
+                        }
+                    }
+                }
+                continue;
+            }
+            if ref_cell_locals.contains(&local) {
+                self.allocate_ref_cell(&mut next_slot, local);
+                continue;
+            }
+            let descriptor = self.local_descriptors[local.as_usize()].clone();
+            self.local_slots[local.as_usize()] = self.allocate_slot(&mut next_slot, descriptor);
+            if overflowing_locals.contains(&local)
+                && let Some(flag_slot) = self.allocate_slot(&mut next_slot, "Z".to_string())
+            {
+                self.overflow_flag_slots.insert(local, flag_slot);
+            }
+        }
+    }
+
+    /// Hands out the next free slot for a value of the given type, `None` for zero-sized types
+    /// and once the function is out of slots
+    fn allocate_slot(&mut self, next_slot: &mut u16, descriptor: String) -> Option<u16> {
+        let size = match descriptor.as_str() {
+            "V" => return None,
+            "J" | "D" => 2,
+            _ => 1,
+        };
+        let slot = *next_slot;
+        let Some(end) = slot.checked_add(size) else {
+            self.tcx.dcx().span_err(
+                self.tcx.def_span(self.instance.def_id()),
+                format!(
+                    "`{}` has more locals than the {} slots of a JVM method",
+                    self.tcx.def_path_str(self.instance.def_id()),
+                    u16::MAX
+                ),
+            );
+            return None;
+        };
+        self.slot_descriptors.push((slot, descriptor));
+        *next_slot = end;
+        Some(slot)
+    }
+
+    /// Whether a local is a shared reference or `*const` pointer to a primitive, which holds the
+    /// primitive boxed
+    fn is_boxed_primitive_ref(&self, local: Local) -> bool {
+        match self.body.local_decls[local].ty.kind() {
+            rustc_middle::ty::TyKind::Ref(_, pointee_ty, Mutability::Not)
+            | rustc_middle::ty::TyKind::RawPtr(pointee_ty, Mutability::Not) => !matches!(
+                rust_ty_to_jvm_descriptor(*pointee_ty, self.tcx)
+                    .as_bytes()
+                    .first(),
+                Some(b'L' | b'[' | b'V')
+            ),
+            _ => false,
+        }
+    }
+
+    fn is_unpacked_enum(&self, local: Local) -> bool {
+        let unpackable = if local == RETURN_PLACE {
+            self.main_result
+        } else {
+            local.as_usize() > self.body.arg_count
+        };
+        unpackable
+            && matches!(
+                self.body.local_decls[local].ty.kind(),
+                rustc_middle::ty::TyKind::Adt(adt_def, _)
+                    if adt_def.is_enum() && !enums::is_exported_enum(self.tcx, adt_def.did())
+            )
+            && !self.is_nullable_option(local)
+            && !self.is_ready_poll(local)
+            && !self.is_coroutine_state_local(local)
+    }
+
+    /// The discriminant value of a variant of the enum stored in `local`
+    fn discriminant_value(&self, local: Local, variant_index: VariantIdx) -> i32 {
+        match self.body.local_decls[local].ty.kind() {
+            rustc_middle::ty::TyKind::Adt(adt_def, _) => {
+                adt_def
+                    .discriminant_for_variant(self.tcx, variant_index)
+                    .val as i32
+            }
+            _ => variant_index.as_u32() as i32,
+        }
+    }
+
+    fn load_enum_field(&mut self, local: Local, variant_index: VariantIdx, field: FieldIdx) {
+        if let Some((slot, descriptor)) = self.enum_field_slots.get(&(local, variant_index, field))
+        {
+            if let Some(instruction) = load_instruction(descriptor, *slot) {
+                self.method_bytecode_instructions.push(instruction);
+            }
+        }
+    }
+
+    /// Stores the value on top of the stack into a field of an unpacked enum local
+    fn store_enum_field(&mut self, local: Local, variant_index: VariantIdx, field: FieldIdx) {
+        if let Some((slot, descriptor)) = self.enum_field_slots.get(&(local, variant_index, field))
+        {
+            if let Some(instruction) = store_instruction(descriptor, *slot) {
+                self.method_bytecode_instructions.push(instruction);
+            }
+        }
+    }
+
+    /// Sets the discriminant of an unpacked enum local
+    fn store_discriminant(&mut self, local: Local, variant_index: VariantIdx) {
+        let instruction = self.int_constant(self.discriminant_value(local, variant_index));
+        self.method_bytecode_instructions.push(instruction);
+        self.store_local(local);
+    }
+
+    /// Handles assignments to unpacked enum locals, returning `false` if the rvalue is not
+    /// supported
+    fn assign_enum_local(&mut self, local: Local, rvalue: &Rvalue<'tcx>) -> bool {
+        match rvalue {
+            Rvalue::Aggregate(box AggregateKind::Adt(_, variant_index, ..), operands) => {
+                for (field, operand) in operands.iter_enumerated() {
+                    self.load_operand(operand);
+                    self.store_enum_field(local, *variant_index, field);
+                }
+                self.store_discriminant(local, *variant_index);
+                true
+            }
+            Rvalue::Use(Operand::Copy(source) | Operand::Move(source))
+                if source.projection.is_empty() && self.is_unpacked_enum(source.local) =>
+            {
+                // Copy the discriminant and every field slot
+                self.load_local(source.local);
+                self.store_local(local);
+                let fields: Vec<_> = self
+                    .enum_field_slots
+                    .keys()
+                    .filter(|(field_local, ..)| *field_local == source.local)
+                    .map(|(_, variant_index, field)| (*variant_index, *field))
+                    .collect();
+                for (variant_index, field) in fields {
+                    self.load_enum_field(source.local, variant_index, field);
+                    self.store_enum_field(local, variant_index, field);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Emits a jump to `target`, unless it is the block right after `current`
+    fn jump_to(&mut self, current: BasicBlock, target: BasicBlock) {
+        if target.as_usize() != current.as_usize() + 1 {
+            self.branch_to(Instruction::Goto(0), target);
+        }
+    }
+
+    /// Emits a branch instruction to `target`, its target index is filled in by `finish`
+    fn branch_to(&mut self, instruction: Instruction, target: BasicBlock) {
+        self.block_branches
+            .push((self.method_bytecode_instructions.len(), target));
+        self.method_bytecode_instructions.push(instruction);
+    }
+
+    /// Checks the lowered code with the [`verifier`], reporting invalid code against the MIR
+    /// statement or terminator it was lowered from
+    fn verify(&self) {
+        let Err(invalid) = verifier::verify_method(
+            &self.method_bytecode_instructions,
+            &self.exception_table,
+            &self.slot_descriptors,
+            self.constant_pool,
+        ) else {
+            return;
+        };
+        let instruction = &self.method_bytecode_instructions[invalid.index];
+        let source = self
+            .instruction_sources
+            .partition_point(|(start, _)| *start <= invalid.index)
+            .checked_sub(1)
+            .map(|source| self.instruction_sources[source].1);
+        let Some(location) = source else {
+            self.tcx.dcx().span_err(
+                self.body.span,
+                format!(
+                    "generated invalid bytecode for `{}`: instruction {} `{instruction:?}` {}",
+                    self.function_name, invalid.index, invalid.message
+                ),
+            );
+            return;
+        };
+        let block = &self.body.basic_blocks[location.block];
+        let (mir, span) = match block.statements.get(location.statement_index) {
+            Some(statement) => (format!("{statement:?}"), statement.source_info.span),
+            None => {
+                let terminator = block.terminator();
+                (
+                    format!("{:?}", terminator.kind),
+                    terminator.source_info.span,
+                )
+            }
+        };
+        self.tcx.dcx().span_err(
+            span,
+            format!(
+                "generated invalid bytecode for `{}`: instruction {} `{instruction:?}`, lowered \
+                 from `{mir}` at {location:?}, {}",
+                self.function_name, invalid.index, invalid.message
+            ),
         );
-        self.super_body(body);
-        println!(
-            "...Finished visiting function body for function: {}.",
-            self.function_name
+    }
+
+    /// Runs the [`peephole`] optimizer over the lowered code, moving frames along with their
+    /// instructions and dropping those of dead code
+    fn optimize(&mut self, optimizations: Optimizations) {
+        // Branches were resolved, block starts only matter to frames from here on
+        let frames: Vec<(usize, Vec<VerificationType>)> = std::mem::take(&mut self.block_starts)
+            .into_iter()
+            .map(|start| (start, Vec::new()))
+            .chain(std::mem::take(&mut self.extra_frames))
+            .collect();
+        let frame_indices: Vec<usize> = frames.iter().map(|(index, _)| *index).collect();
+        let new_indices = peephole::optimize(
+            &mut self.method_bytecode_instructions,
+            &mut self.exception_table,
+            &frame_indices,
+            self.constant_pool,
+            optimizations.dead_code,
         );
+        self.extra_frames = frames
+            .into_iter()
+            .filter_map(|(index, stack)| Some((new_indices[index]?, stack)))
+            .collect();
+    }
+
+    /// Resolves branches to basic blocks and, if the method branches or throws at all,
+    /// initializes its locals on entry and builds its stack map frames
+    fn finish(mut self) -> MethodCode {
+        for (index, target) in std::mem::take(&mut self.block_branches) {
+            let target = u16::try_from(self.block_starts[target]).expect("Method too large");
+            self.method_bytecode_instructions[index] =
+                control_flow::with_branch_target(&self.method_bytecode_instructions[index], target);
+        }
+        self.verify();
+        let optimizations = Optimizations::of(self.tcx.sess);
+        if optimizations.peephole {
+            self.optimize(optimizations);
+        }
+
+        let has_branches = self.method_bytecode_instructions.iter().any(|instruction| {
+            control_flow::branch_target(instruction).is_some()
+                || matches!(instruction, Instruction::Athrow)
+        });
+        if !has_branches && self.exception_table.is_empty() {
+            return MethodCode::from(self.method_bytecode_instructions);
+        }
+
+        let preamble = control_flow::initialize_locals(
+            self.slot_descriptors[self.argument_slot_count..]
+                .iter()
+                .map(|(slot, descriptor)| (*slot, descriptor.as_str())),
+        );
+        let shift = preamble.len();
+        let shift_index = |index: u16| index + shift as u16;
+        let mut instructions = preamble;
+        for instruction in &self.method_bytecode_instructions {
+            instructions.push(match control_flow::branch_target(instruction) {
+                Some(target) => control_flow::with_branch_target(instruction, shift_index(target)),
+                None => instruction.clone(),
+            });
+        }
+        let exception_table = self
+            .exception_table
+            .iter()
+            .map(|entry| ExceptionTableEntry {
+                range_pc: shift_index(entry.range_pc.start)..shift_index(entry.range_pc.end),
+                handler_pc: shift_index(entry.handler_pc),
+                catch_type: entry.catch_type,
+            })
+            .collect();
+
+        let locals = self
+            .slot_descriptors
+            .iter()
+            .map(|(_, descriptor)| control_flow::verification_type(descriptor, self.constant_pool))
+            .collect::<ristretto_classfile::Result<Vec<_>>>()
+            .expect("Could not add local types to constant pool");
+        let frames = self
+            .block_starts
+            .iter()
+            .map(|start| (start + shift, Vec::new()))
+            .chain(
+                self.extra_frames
+                    .into_iter()
+                    .map(|(index, stack)| (index + shift, stack)),
+            )
+            .collect();
+        let stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+
+        MethodCode {
+            instructions,
+            exception_table,
+            stack_map_frames,
+        }
+    }
+
+    fn load_local(&mut self, local: Local) {
+        if let Some(cell_slot) = self.ref_cell_slots.get(&local).copied() {
+            self.method_bytecode_instructions.push(slot_instruction(
+                cell_slot,
+                Instruction::Aload,
+                Instruction::Aload_w,
+            ));
+            let descriptor = self.local_descriptors[local.as_usize()].clone();
+            self.load_ref_cell_value(&descriptor);
+            return;
+        }
+        if let Some(slot) = self.local_slots[local.as_usize()] {
+            let descriptor = &self.local_descriptors[local.as_usize()];
+            if let Some(instruction) = load_instruction(descriptor, slot) {
+                self.method_bytecode_instructions.push(instruction);
+            }
+        }
+    }
+
+    fn store_local(&mut self, local: Local) {
+        if let Some(cell_slot) = self.ref_cell_slots.get(&local).copied() {
+            self.method_bytecode_instructions.push(slot_instruction(
+                cell_slot,
+                Instruction::Aload,
+                Instruction::Aload_w,
+            ));
+            let descriptor = self.local_descriptors[local.as_usize()].clone();
+            self.store_ref_cell_value(&descriptor);
+            return;
+        }
+        if let Some(slot) = self.local_slots[local.as_usize()] {
+            let descriptor = &self.local_descriptors[local.as_usize()];
+            if let Some(instruction) = store_instruction(descriptor, slot) {
+                self.method_bytecode_instructions.push(instruction);
+            }
+        }
+    }
+
+    /// Resolves `place.field` to a field ref in the constant pool, if `place` is (a reference to)
+    /// an exported struct, or a field of a coroutine.
+    fn field_ref(&mut self, place: &Place<'tcx>) -> Option<u16> {
+        if let Some(field_ref) = self.coroutine_field_ref(place) {
+            return Some(field_ref);
+        }
+        let (deref, field, field_ty) = match &place.projection[..] {
+            [ProjectionElem::Field(field, field_ty)] => (false, *field, *field_ty),
+            [
+                ProjectionElem::Deref,
+                ProjectionElem::Field(field, field_ty),
+            ] => (true, *field, *field_ty),
+            _ => return None,
+        };
+        let mut base_ty = self.body.local_decls[place.local].ty;
+        if deref {
+            base_ty = base_ty.builtin_deref(true)?;
+        }
+        let rustc_middle::ty::TyKind::Adt(adt_def, _) = base_ty.kind() else {
+            return None;
+        };
+        let (class_name, field_descriptor) =
+            if let Some(class) = range::runtime_class(base_ty, self.tcx) {
+                // Range bounds are held as JVM ints or longs
+                (class.name(), range::bound_descriptor(class).to_string())
+            } else if attributes::has_jvm_attr(self.tcx, adt_def.did(), "export") {
+                (
+                    export::class_name(self.tcx, adt_def.did()),
+                    rust_ty_to_jvm_descriptor(field_ty, self.tcx),
+                )
+            } else {
+                return None;
+            };
+
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let field_name = adt_def.non_enum_variant().fields[field].name.to_string();
+        Some(
+            self.constant_pool
+                .add_field_ref(class_index, &field_name, &field_descriptor)
+                .expect("Could not add field ref to constant pool"),
+        )
+    }
+
+    fn load_place(&mut self, place: &Place<'tcx>) {
+        match &place.projection[..] {
+            [] if self.is_unpacked_enum(place.local) => {
+                trace!("      Unsupported use of a whole enum value: {place:?}");
+                self.record_unsupported("enum-value", format_args!("{place:?}"));
+            }
+            [
+                ProjectionElem::Downcast(_, variant_index),
+                ProjectionElem::Field(field, _),
+            ] if self.is_unpacked_enum(place.local) => {
+                self.load_enum_field(place.local, *variant_index, *field);
+            }
+            _ if self.is_simd_field(place) => self.load_simd_field(place),
+            [ProjectionElem::Field(_, field_ty)] if self.is_union_field(place) => {
+                self.load_union_field(place, *field_ty);
+            }
+            // The pointer inside an erased `Box` is the box itself
+            _ if self.is_box_pointer_place(place) => self.load_local(place.local),
+            // The payload of a nullable `Option` is the reference itself
+            [ProjectionElem::Downcast(..), ProjectionElem::Field(..)]
+                if self.is_nullable_option(place.local) =>
+            {
+                self.load_local(place.local);
+            }
+            // The payload of a `Poll` is the `Poll` itself, see `coroutines`
+            [ProjectionElem::Downcast(..), ProjectionElem::Field(..)]
+                if self.is_ready_poll(place.local) =>
+            {
+                self.load_local(place.local);
+            }
+            [
+                ProjectionElem::Downcast(..),
+                ProjectionElem::Field(_, value_ty),
+            ] if self.is_coroutine_state_local(place.local) => {
+                self.load_coroutine_state_value(place.local, *value_ty);
+            }
+            // The pointer inside a `Pin` is the pin itself, and a coroutine the object it points to
+            [ProjectionElem::Field(..)] if self.is_pin_local(place.local) => {
+                self.load_local(place.local);
+            }
+            _ if self.is_coroutine_object(place) => self.load_local(place.local),
+            [.., ProjectionElem::Downcast(..), ProjectionElem::Field(..)]
+                if self.is_exported_enum_place(place) =>
+            {
+                self.load_exported_enum_field(place);
+            }
+            // Mutable references to primitives are ref cells
+            [ProjectionElem::Deref] if self.is_ref_cell_ref(place.local) => {
+                self.load_local(place.local);
+                let descriptor = self.ref_cell_value_descriptor(place.local);
+                self.load_ref_cell_value(&descriptor);
+            }
+            // Shared references to primitives hold them boxed
+            [ProjectionElem::Deref] if self.is_boxed_primitive_ref(place.local) => {
+                self.load_local(place.local);
+                let pointee_ty = self.body.local_decls[place.local]
+                    .ty
+                    .builtin_deref(true)
+                    .expect("references can be dereferenced");
+                self.cast_from_object(&rust_ty_to_jvm_descriptor(pointee_ty, self.tcx));
+            }
+            // References are just the object they point to
+            [] | [ProjectionElem::Deref] => self.load_local(place.local),
+            [ProjectionElem::Field(field, _)] if self.checked_locals.contains(&place.local) => {
+                if field.as_u32() == 0 {
+                    self.load_local(place.local);
+                } else if let Some(flag_slot) = self.overflow_flag_slots.get(&place.local) {
+                    self.method_bytecode_instructions.push(slot_instruction(
+                        *flag_slot,
+                        Instruction::Iload,
+                        Instruction::Iload_w,
+                    ));
+                } else {
+                    // Without overflow checks nothing reads the flag
+                    self.method_bytecode_instructions
+                        .push(Instruction::Iconst_0);
+                }
+            }
+            _ => {
+                if let Some(field_ref) = self.field_ref(place) {
+                    self.load_local(place.local);
+                    self.method_bytecode_instructions
+                        .push(Instruction::Getfield(field_ref));
+                } else {
+                    trace!("      Unsupported place: {place:?}");
+                    self.record_unsupported("place", format_args!("{place:?}"));
+                }
+            }
+        }
+    }
+
+    /// Loads an operand passed to Java as a size or index, narrowing it to the `int` Java takes if
+    /// it is a 64 bit `usize` or `isize`, see [`options`]
+    fn load_usize_as_int(&mut self, operand: &Operand<'tcx>) {
+        self.load_operand(operand);
+        let ty = operand.ty(self.body, self.tcx);
+        if ty.is_ptr_sized_integral() && options::get().usize_width == 64 {
+            self.method_bytecode_instructions.push(Instruction::L2i);
+        }
+    }
+
+    /// Widens the `int` size or index Java returned to a `usize`, if it is 64 bits wide
+    fn int_to_usize(&mut self) {
+        if options::get().usize_width == 64 {
+            self.method_bytecode_instructions.push(Instruction::I2l);
+        }
+    }
+
+    fn load_operand(&mut self, operand: &Operand<'tcx>) {
+        match operand {
+            Operand::Copy(place) | Operand::Move(place) => self.load_place(place),
+            Operand::Constant(constant) => {
+                let descriptor = rust_ty_to_jvm_descriptor(constant.ty(), self.tcx);
+                if descriptor == "V" {
+                    return; // Zero-sized constants have no value on the JVM
+                }
+                if self.load_exported_enum_constant(constant) {
+                    return;
+                }
+                if nullable::payload(constant.ty(), self.tcx).is_some() {
+                    // The only constant nullable `Option` is `None`
+                    self.method_bytecode_instructions
+                        .push(Instruction::Aconst_null);
+                    return;
+                }
+                if let Some(value) = string::str_constant(constant, self.tcx) {
+                    self.load_str_constant(&value);
+                    return;
+                }
+                if let Some(nanos) = time::time_constant(constant, self.tcx) {
+                    let instruction = self.long_constant(nanos);
+                    self.method_bytecode_instructions.push(instruction);
+                    return;
+                }
+                let Some(scalar) = constant
+                    .const_
+                    .try_eval_scalar_int(self.tcx, TypingEnv::fully_monomorphized())
+                else {
+                    trace!("      Unsupported constant: {constant:?}");
+                    self.record_unsupported("constant", format_args!("{constant:?}"));
+                    return;
+                };
+                let bits = scalar.to_bits_unchecked();
+                let instruction = match descriptor.as_str() {
+                    "J" => self.long_constant(bits as i64),
+                    "F" => self.float_constant(f32::from_bits(bits as u32)),
+                    "D" => self.double_constant(f64::from_bits(bits as u64)),
+                    _ => self.int_constant(scalar.to_int(scalar.size()) as i32),
+                };
+                self.method_bytecode_instructions.push(instruction);
+            }
+        }
+    }
+
+    fn int_constant(&mut self, value: i32) -> Instruction {
+        match value {
+            -1 => Instruction::Iconst_m1,
+            0 => Instruction::Iconst_0,
+            1 => Instruction::Iconst_1,
+            2 => Instruction::Iconst_2,
+            3 => Instruction::Iconst_3,
+            4 => Instruction::Iconst_4,
+            5 => Instruction::Iconst_5,
+            _ => {
+                if let Ok(byte) = i8::try_from(value) {
+                    Instruction::Bipush(byte)
+                } else if let Ok(short) = i16::try_from(value) {
+                    Instruction::Sipush(short)
+                } else {
+                    let index = self
+                        .constant_pool
+                        .add_integer(value)
+                        .expect("Could not add integer to constant pool");
+                    ldc(index)
+                }
+            }
+        }
+    }
+
+    fn long_constant(&mut self, value: i64) -> Instruction {
+        match value {
+            0 => Instruction::Lconst_0,
+            1 => Instruction::Lconst_1,
+            _ => Instruction::Ldc2_w(
+                self.constant_pool
+                    .add_long(value)
+                    .expect("Could not add long to constant pool"),
+            ),
+        }
+    }
+
+    fn float_constant(&mut self, value: f32) -> Instruction {
+        if value.to_bits() == 0.0f32.to_bits() {
+            Instruction::Fconst_0
+        } else if value == 1.0 {
+            Instruction::Fconst_1
+        } else if value == 2.0 {
+            Instruction::Fconst_2
+        } else {
+            let index = self
+                .constant_pool
+                .add_float(value)
+                .expect("Could not add float to constant pool");
+            ldc(index)
+        }
+    }
+
+    fn double_constant(&mut self, value: f64) -> Instruction {
+        if value.to_bits() == 0.0f64.to_bits() {
+            Instruction::Dconst_0
+        } else if value == 1.0 {
+            Instruction::Dconst_1
+        } else {
+            Instruction::Ldc2_w(
+                self.constant_pool
+                    .add_double(value)
+                    .expect("Could not add double to constant pool"),
+            )
+        }
+    }
+
+    /// Pushes the value of `rvalue` onto the operand stack. Returns `false` if the rvalue is not
+    /// supported, in which case nothing was pushed.
+    fn lower_rvalue(&mut self, rvalue: &Rvalue<'tcx>) -> bool {
+        match rvalue {
+            Rvalue::Use(operand) => {
+                self.load_operand(operand);
+                true
+            }
+            // A copy of a reference that is dereferenced right away, as in `&&T` or a reference
+            // held in a field
+            Rvalue::CopyForDeref(place) => {
+                self.load_place(place);
+                true
+            }
+            Rvalue::BinaryOp(
+                bin_op @ (BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge),
+                box (left, right),
+            ) => self.lower_comparison(*bin_op, left, right),
+            Rvalue::BinaryOp(BinOp::Offset, box (pointer, _)) => {
+                let pointer_ty = pointer.ty(self.body, self.tcx);
+                self.report_pointer_operation("pointer arithmetic on", pointer_ty);
+                false
+            }
+            Rvalue::BinaryOp(
+                bin_op @ (BinOp::Shl | BinOp::ShlUnchecked | BinOp::Shr | BinOp::ShrUnchecked),
+                box (left, right),
+            ) => self.lower_shift(*bin_op, left, right),
+            Rvalue::BinaryOp(bin_op, box (left, right)) => {
+                let descriptor = rust_ty_to_jvm_descriptor(left.ty(self.body, self.tcx), self.tcx);
+                let instruction = match (bin_op, descriptor.as_str()) {
+                    (BinOp::Add | BinOp::AddWithOverflow, "J") => Instruction::Ladd,
+                    (BinOp::Add | BinOp::AddWithOverflow, "F") => Instruction::Fadd,
+                    (BinOp::Add | BinOp::AddWithOverflow, "D") => Instruction::Dadd,
+                    (BinOp::Add | BinOp::AddWithOverflow, _) => Instruction::Iadd,
+                    (BinOp::Sub | BinOp::SubWithOverflow, "J") => Instruction::Lsub,
+                    (BinOp::Sub | BinOp::SubWithOverflow, "F") => Instruction::Fsub,
+                    (BinOp::Sub | BinOp::SubWithOverflow, "D") => Instruction::Dsub,
+                    (BinOp::Sub | BinOp::SubWithOverflow, _) => Instruction::Isub,
+                    (BinOp::Mul | BinOp::MulWithOverflow, "J") => Instruction::Lmul,
+                    (BinOp::Mul | BinOp::MulWithOverflow, "F") => Instruction::Fmul,
+                    (BinOp::Mul | BinOp::MulWithOverflow, "D") => Instruction::Dmul,
+                    (BinOp::Mul | BinOp::MulWithOverflow, _) => Instruction::Imul,
+                    (BinOp::BitAnd, "J") => Instruction::Land,
+                    (BinOp::BitAnd, "B" | "S" | "I" | "Z") => Instruction::Iand,
+                    (BinOp::BitOr, "J") => Instruction::Lor,
+                    (BinOp::BitOr, "B" | "S" | "I" | "Z") => Instruction::Ior,
+                    (BinOp::BitXor, "J") => Instruction::Lxor,
+                    (BinOp::BitXor, "B" | "S" | "I" | "Z") => Instruction::Ixor,
+                    _ => {
+                        trace!("      Unsupported binary operation: {bin_op:?}");
+                        self.record_unsupported("binary-operation", format_args!("{bin_op:?}"));
+                        return false;
+                    }
+                };
+                trace!("      Found binary operation: {left:?} {bin_op:?} {right:?}");
+                self.load_operand(left);
+                self.load_operand(right);
+                self.method_bytecode_instructions.push(instruction);
+                // `byte`s and `short`s wrap around at their own width, not at the int's. Bitwise
+                // operations keep them sign-extended
+                if !matches!(bin_op, BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor)
+                    && let Some((_, bits)) = intrinsics::integer_class(&descriptor)
+                {
+                    self.method_bytecode_instructions
+                        .extend(intrinsics::narrowing_instruction(bits));
+                }
+                true
+            }
+            Rvalue::UnaryOp(un_op @ (UnOp::Neg | UnOp::Not), operand) => {
+                let descriptor =
+                    rust_ty_to_jvm_descriptor(operand.ty(self.body, self.tcx), self.tcx);
+                let instructions = match (un_op, descriptor.as_str()) {
+                    (UnOp::Neg, "J") => vec![Instruction::Lneg],
+                    (UnOp::Neg, "F") => vec![Instruction::Fneg],
+                    (UnOp::Neg, "D") => vec![Instruction::Dneg],
+                    // `-i8::MIN` wraps around to itself
+                    (UnOp::Neg, "B" | "S" | "I") => [Instruction::Ineg]
+                        .into_iter()
+                        .chain(
+                            intrinsics::integer_class(&descriptor)
+                                .and_then(|(_, bits)| intrinsics::narrowing_instruction(bits)),
+                        )
+                        .collect(),
+                    (UnOp::Not, "Z") => vec![Instruction::Iconst_1, Instruction::Ixor],
+                    (UnOp::Not, "B" | "S" | "I") => vec![Instruction::Iconst_m1, Instruction::Ixor],
+                    (UnOp::Not, "J") => vec![self.long_constant(-1), Instruction::Lxor],
+                    _ => {
+                        trace!("      Unsupported unary operation: {un_op:?}");
+                        self.record_unsupported("unary-operation", format_args!("{un_op:?}"));
+                        return false;
+                    }
+                };
+                self.load_operand(operand);
+                self.method_bytecode_instructions.extend(instructions);
+                true
+            }
+            Rvalue::Discriminant(place)
+                if place.projection.is_empty() && self.is_unpacked_enum(place.local) =>
+            {
+                self.load_local(place.local);
+                true
+            }
+            Rvalue::Discriminant(place) if self.is_exported_enum_place(place) => {
+                self.load_exported_enum_discriminant(place);
+                true
+            }
+            Rvalue::Discriminant(place)
+                if place.projection.is_empty() && self.is_nullable_option(place.local) =>
+            {
+                self.load_nullable_discriminant(place.local);
+                true
+            }
+            Rvalue::Discriminant(place)
+                if place.projection.is_empty() && self.is_ready_poll(place.local) =>
+            {
+                // Polling is always `Ready`
+                self.method_bytecode_instructions
+                    .push(Instruction::Iconst_0);
+                true
+            }
+            Rvalue::Discriminant(place)
+                if place.projection.is_empty() && self.is_coroutine_state_local(place.local) =>
+            {
+                self.load_coroutine_state_discriminant(place.local);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(_, variant_index, ..), operands)
+                if coroutines::is_coroutine_state(rvalue.ty(self.body, self.tcx), self.tcx) =>
+            {
+                self.load_coroutine_state_aggregate(*variant_index, operands);
+                true
+            }
+            Rvalue::Discriminant(place) if self.is_coroutine_object(place) => {
+                self.load_coroutine_state(place);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Coroutine(def_id, generic_args), operands) => {
+                self.load_coroutine_aggregate(*def_id, generic_args, operands);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(_, variant_index, ..), operands)
+                if coroutines::poll_output(rvalue.ty(self.body, self.tcx), self.tcx).is_some() =>
+            {
+                self.load_poll_aggregate(rvalue.ty(self.body, self.tcx), *variant_index, operands);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(_, variant_index, ..), operands)
+                if nullable::payload(rvalue.ty(self.body, self.tcx), self.tcx).is_some() =>
+            {
+                self.load_nullable_aggregate(*variant_index, operands.iter().next());
+                true
+            }
+            Rvalue::Ref(_, BorrowKind::Mut { .. }, place)
+            | Rvalue::RawPtr(RawPtrKind::Mut, place)
+                if references::is_primitive(&rust_ty_to_jvm_descriptor(
+                    place.ty(self.body, self.tcx).ty,
+                    self.tcx,
+                )) =>
+            {
+                self.load_ref_cell_borrow(place)
+            }
+            Rvalue::Ref(_, _, place)
+                if matches!(
+                    rust_ty_to_jvm_descriptor(place.ty(self.body, self.tcx).ty, self.tcx)
+                        .as_bytes()
+                        .first(),
+                    Some(b'L' | b'[')
+                ) =>
+            {
+                // A reference to an object is the object itself
+                self.load_place(place);
+                true
+            }
+            Rvalue::Ref(_, BorrowKind::Shared, place)
+                if rust_ty_to_jvm_descriptor(place.ty(self.body, self.tcx).ty, self.tcx) != "V" =>
+            {
+                // A shared reference to a primitive is a boxed copy of it, the value cannot change
+                // while it is borrowed
+                let descriptor =
+                    rust_ty_to_jvm_descriptor(place.ty(self.body, self.tcx).ty, self.tcx);
+                self.load_place(place);
+                self.box_value(&descriptor);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(..), operands)
+                if rvalue.ty(self.body, self.tcx).is_simd() =>
+            {
+                self.load_simd_aggregate(rvalue.ty(self.body, self.tcx), operands);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(..), operands)
+                if unions::is_primitive_union(rvalue.ty(self.body, self.tcx), self.tcx) =>
+            {
+                // The only operand is the field the union is built from
+                if let Some(operand) = operands.iter().next() {
+                    self.load_union_bits(operand);
+                }
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(..), operands)
+                if matches!(
+                    range::runtime_class(rvalue.ty(self.body, self.tcx), self.tcx),
+                    Some(runtime::RuntimeClass::Range(_))
+                ) =>
+            {
+                // `start..end`; inclusive ranges are built by `RangeInclusive::new`
+                let ty = rvalue.ty(self.body, self.tcx);
+                if let Some(class) = range::runtime_class(ty, self.tcx) {
+                    self.load_range_aggregate(class, operands);
+                }
+                true
+            }
+            Rvalue::RawPtr(_, place) => {
+                // Raw pointers are references: the object itself, or a boxed copy of a primitive
+                // for `*const` pointers
+                let descriptor =
+                    rust_ty_to_jvm_descriptor(place.ty(self.body, self.tcx).ty, self.tcx);
+                self.load_place(place);
+                match descriptor.as_bytes().first() {
+                    Some(b'L' | b'[') => {}
+                    Some(b'V') => self
+                        .method_bytecode_instructions
+                        .push(Instruction::Aconst_null),
+                    _ => self.box_value(&descriptor),
+                }
+                true
+            }
+            Rvalue::Cast(
+                CastKind::PointerCoercion(PointerCoercion::MutToConstPointer, ..),
+                operand,
+                _,
+            ) => {
+                self.load_operand(operand);
+                // A `*const` pointer to a primitive is a boxed copy of the value in the ref cell
+                let operand_ty = operand.ty(self.body, self.tcx);
+                if let Some(pointee_ty) = references::ref_cell_pointee(operand_ty, self.tcx) {
+                    let descriptor = rust_ty_to_jvm_descriptor(pointee_ty, self.tcx);
+                    self.load_ref_cell_value(&descriptor);
+                    self.box_value(&descriptor);
+                }
+                true
+            }
+            Rvalue::Cast(
+                CastKind::PointerCoercion(PointerCoercion::Unsize, ..),
+                operand,
+                target_ty,
+            ) => self.lower_unsize(operand, *target_ty),
+            Rvalue::Cast(CastKind::IntToInt, operand, target_ty) => {
+                self.load_int_cast(operand, *target_ty)
+            }
+            Rvalue::Cast(CastKind::PtrToPtr, operand, target_ty) => {
+                let operand_ty = operand.ty(self.body, self.tcx);
+                if !self.is_same_pointer(operand_ty, *target_ty) {
+                    self.report_pointer_operation("casting", operand_ty);
+                    return false;
+                }
+                self.load_operand(operand);
+                true
+            }
+            Rvalue::Cast(CastKind::PointerExposeProvenance, operand, _) => {
+                let operand_ty = operand.ty(self.body, self.tcx);
+                self.report_pointer_operation("exposing the address of", operand_ty);
+                false
+            }
+            Rvalue::Cast(CastKind::PointerWithExposedProvenance, _, target_ty) => {
+                self.report_pointer_operation("casting an integer to", *target_ty);
+                false
+            }
+            // The pointer inside a `Box` is the box itself
+            Rvalue::Cast(CastKind::Transmute, Operand::Copy(place) | Operand::Move(place), _)
+                if self.is_box_pointer_place(place) =>
+            {
+                self.load_place(place);
+                true
+            }
+            Rvalue::Cast(CastKind::Transmute, operand, target_ty) => {
+                self.load_transmuted(operand, operand.ty(self.body, self.tcx), *target_ty)
+            }
+            Rvalue::Aggregate(box AggregateKind::Array(element_ty), operands) => {
+                self.load_array_aggregate(*element_ty, operands);
+                true
+            }
+            Rvalue::Repeat(operand, count) => self.load_array_repeat(operand, *count),
+            Rvalue::NullaryOp(null_op, ty) => self.load_nullary_op(null_op, *ty),
+            Rvalue::ShallowInitBox(..) => {
+                // The box is the object it holds, which is only stored into it afterwards
+                let descriptor =
+                    rust_ty_to_jvm_descriptor(rvalue.ty(self.body, self.tcx), self.tcx);
+                self.method_bytecode_instructions
+                    .push(control_flow::default_value(&descriptor));
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(adt_def_id, variant_index, ..), operands)
+                if enums::is_exported_enum(self.tcx, *adt_def_id) =>
+            {
+                self.load_variant_aggregate(*adt_def_id, *variant_index, operands);
+                true
+            }
+            Rvalue::Aggregate(box AggregateKind::Adt(adt_def_id, ..), operands)
+                if attributes::has_jvm_attr(self.tcx, *adt_def_id, "export") =>
+            {
+                // Struct literals of exported structs call the generated constructor
+                let class_index = self
+                    .constant_pool
+                    .add_class(export::class_name(self.tcx, *adt_def_id))
+                    .expect("Could not add class to constant pool");
+                let constructor_ref = self
+                    .constant_pool
+                    .add_method_ref(
+                        class_index,
+                        "<init>",
+                        &export::constructor_descriptor(self.tcx, *adt_def_id),
+                    )
+                    .expect("Could not add constructor to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::New(class_index));
+                self.method_bytecode_instructions.push(Instruction::Dup);
+                for operand in operands {
+                    self.load_operand(operand);
+                }
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokespecial(constructor_ref));
+                true
+            }
+            _ => {
+                trace!("      Unsupported rvalue: {rvalue:?}");
+                self.record_unsupported("rvalue", format_args!("{rvalue:?}"));
+                false
+            }
+        }
+    }
+}
+
+/// Picks the `ldc` variant able to address the given constant pool index
+fn ldc(index: u16) -> Instruction {
+    match u8::try_from(index) {
+        Ok(index) => Instruction::Ldc(index),
+        Err(_) => Instruction::Ldc_w(index),
+    }
+}
+
+/// Picks the variant of a load or store able to address the given slot, the `wide` one past
+/// slot 255
+fn slot_instruction(
+    slot: u16,
+    narrow: fn(u8) -> Instruction,
+    wide: fn(u16) -> Instruction,
+) -> Instruction {
+    match u8::try_from(slot) {
+        Ok(slot) => narrow(slot),
+        Err(_) => wide(slot),
+    }
+}
+
+fn load_instruction(descriptor: &str, slot: u16) -> Option<Instruction> {
+    let (narrow, wide): (fn(u8) -> Instruction, fn(u16) -> Instruction) =
+        match descriptor.as_bytes().first()? {
+            b'Z' | b'B' | b'C' | b'S' | b'I' => (Instruction::Iload, Instruction::Iload_w),
+            b'J' => (Instruction::Lload, Instruction::Lload_w),
+            b'F' => (Instruction::Fload, Instruction::Fload_w),
+            b'D' => (Instruction::Dload, Instruction::Dload_w),
+            b'L' | b'[' => (Instruction::Aload, Instruction::Aload_w),
+            _ => return None,
+        };
+    Some(slot_instruction(slot, narrow, wide))
+}
+
+fn store_instruction(descriptor: &str, slot: u16) -> Option<Instruction> {
+    let (narrow, wide): (fn(u8) -> Instruction, fn(u16) -> Instruction) =
+        match descriptor.as_bytes().first()? {
+            b'Z' | b'B' | b'C' | b'S' | b'I' => (Instruction::Istore, Instruction::Istore_w),
+            b'J' => (Instruction::Lstore, Instruction::Lstore_w),
+            b'F' => (Instruction::Fstore, Instruction::Fstore_w),
+            b'D' => (Instruction::Dstore, Instruction::Dstore_w),
+            b'L' | b'[' => (Instruction::Astore, Instruction::Astore_w),
+            _ => return None,
+        };
+    Some(slot_instruction(slot, narrow, wide))
+}
+
+/// The instruction returning a value of the given descriptor
+fn return_instruction(descriptor: &str) -> Instruction {
+    match descriptor.as_bytes().first() {
+        Some(b'Z' | b'B' | b'C' | b'S' | b'I') => Instruction::Ireturn,
+        Some(b'J') => Instruction::Lreturn,
+        Some(b'F') => Instruction::Freturn,
+        Some(b'D') => Instruction::Dreturn,
+        Some(b'L' | b'[') => Instruction::Areturn,
+        _ => Instruction::Return,
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for MirToBytecodeVisitor<'_, 'tcx> {
+    fn visit_body(&mut self, body: &Body<'tcx>) {
+        trace!("\nfn {}:", self.function_name);
+        self.allocate_local_slots(body);
+        self.new_ref_cells();
+        self.super_body(body);
     }
 
-    fn visit_basic_block_data(&mut self, block: BasicBlock, data: &BasicBlockData<'_>) {
-        println!("  Visiting basic block: {block:?}");
+    fn visit_basic_block_data(&mut self, block: BasicBlock, data: &BasicBlockData<'tcx>) {
+        trace!("  {block:?}:");
+        self.block_starts
+            .push(self.method_bytecode_instructions.len());
         self.super_basic_block_data(block, data);
     }
 
-    fn visit_statement(&mut self, statement: &Statement<'_>, location: Location) {
-        println!(
-            "    Visiting statement in block {:?}: {:?}",
-            location.block, statement
-        );
-        if let StatementKind::Assign(box (_place, Rvalue::BinaryOp(bin_op, operands))) =
-            &statement.kind
+    fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
+        trace!("    {statement:?}");
+        if self.unsupported {
+            return;
+        }
+        let start = self.method_bytecode_instructions.len();
+        self.instruction_sources.push((start, location));
+        self.span = statement.source_info.span;
+        if let StatementKind::SetDiscriminant {
+            place,
+            variant_index,
+        } = &statement.kind
         {
-            match bin_op {
-                BinOp::Add | BinOp::AddWithOverflow => {
-                    println!(
-                        "      Found addition operation: {:?} + {:?}",
-                        operands.0, operands.1
-                    );
-
-                    // --- Generate Java bytecode for iadd ---
-                    // Load the first operand (argument 0)
-                    self.method_bytecode_instructions.push(Instruction::Iload_0);
-                    // Load the second operand (argument 1)
-                    self.method_bytecode_instructions.push(Instruction::Iload_1);
-                    // Perform integer addition
-                    self.method_bytecode_instructions.push(Instruction::Iadd);
-                    println!("      Generated bytecode: iload_0, iload_1, iadd");
-                    // --- End bytecode generation ---
-                }
-                BinOp::Sub | BinOp::SubWithOverflow => {
-                    println!(
-                        "      Found subtraction operation: {:?} - {:?}",
-                        operands.0, operands.1
-                    );
-
-                    // --- Generate Java bytecode for isub ---
-                    // Load the first operand (argument 0)
-                    self.method_bytecode_instructions.push(Instruction::Iload_0);
-                    // Load the second operand (argument 1)
-                    self.method_bytecode_instructions.push(Instruction::Iload_1);
-                    // Perform integer subtraction
-                    self.method_bytecode_instructions.push(Instruction::Isub);
-                    println!("      Generated bytecode: iload_0, iload_1, isub");
-                    // --- End bytecode generation ---
+            if place.projection.is_empty() && self.is_unpacked_enum(place.local) {
+                self.store_discriminant(place.local, *variant_index);
+            } else if place.projection.is_empty() && self.is_nullable_option(place.local) {
+                self.store_nullable_discriminant(place.local, *variant_index);
+            } else if self.is_coroutine_object(place) {
+                self.store_coroutine_state(place, *variant_index);
+            }
+        }
+        if let StatementKind::Assign(box (place, rvalue)) = &statement.kind {
+            let enum_field = match &place.projection[..] {
+                [
+                    ProjectionElem::Downcast(_, variant_index),
+                    ProjectionElem::Field(field, _),
+                ] if self.is_unpacked_enum(place.local) => Some((*variant_index, *field)),
+                _ => None,
+            };
+            if place.projection.is_empty() && self.is_unpacked_enum(place.local) {
+                if !self.assign_enum_local(place.local, rvalue) {
+                    trace!("      Unsupported enum assignment: {rvalue:?}");
+                    self.record_unsupported("enum-assignment", format_args!("{rvalue:?}"));
                 }
-                _ => {
-                    println!("      Unsupported binary operation: {bin_op:?}");
+            } else if self.is_union_field(place)
+                && let Rvalue::Use(operand) = rvalue
+            {
+                self.store_union_field(place, operand);
+            } else if let Some((variant_index, field)) = enum_field {
+                if self.lower_rvalue(rvalue) {
+                    self.store_enum_field(place.local, variant_index, field);
                 }
+            } else if place.projection.is_empty()
+                || ((self.is_nullable_option(place.local) || self.is_ready_poll(place.local))
+                    && matches!(
+                        place.projection[..],
+                        [ProjectionElem::Downcast(..), ProjectionElem::Field(..)]
+                    ))
+            {
+                // Storing the payload of a nullable `Option` or a `Poll` stores the whole value
+                if self.lower_rvalue(rvalue) {
+                    self.store_local(place.local);
+                    self.store_overflow_flag(place, rvalue);
+                }
+            } else if self.is_ref_cell_deref(place) {
+                self.assign_through_ref_cell(place.local, rvalue);
+            } else if let [ProjectionElem::Deref] = place.projection[..]
+                && let Some(box_local) = self.pointer_box(place.local)
+            {
+                self.assign_through_box_pointer(place.local, box_local, rvalue);
+            } else if let Some(field_ref) = self.field_ref(place) {
+                // Assignment to a field of an exported struct
+                self.load_local(place.local);
+                if self.lower_rvalue(rvalue) {
+                    self.method_bytecode_instructions
+                        .push(Instruction::Putfield(field_ref));
+                } else {
+                    self.method_bytecode_instructions.push(Instruction::Pop);
+                }
+            } else {
+                trace!("      Unsupported assignment target: {place:?}");
+                self.record_unsupported("assignment-target", format_args!("{place:?}"));
             }
         }
         self.super_statement(statement, location);
+        self.trace_instructions(start);
     }
 
-    fn visit_terminator(&mut self, terminator: &Terminator<'_>, location: Location) {
-        println!(
-            "    Visiting terminator in block {:?}: {:?}",
-            location.block, terminator
-        );
+    fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+        trace!("    {:?}", terminator.kind);
+        if self.unsupported {
+            return;
+        }
+        let start = self.method_bytecode_instructions.len();
+        self.instruction_sources.push((start, location));
+        self.span = terminator.source_info.span;
         if terminator.kind == TerminatorKind::Return {
-            println!(
-                "      Found return terminator in function: {}",
-                self.function_name
-            );
-
             // Determine return type and generate appropriate bytecode
-            let fn_sig = self.tcx.fn_sig(self.instance.def_id());
-            let return_ty = fn_sig.skip_binder().output().skip_binder(); // Skip binder twice!
+            let return_ty = self.body.return_ty();
             let jvm_return_descriptor = rust_ty_to_jvm_descriptor(return_ty, self.tcx);
 
             match jvm_return_descriptor.as_str() {
+                _ if self.main_result => {
+                    self.return_main_result();
+                }
                 "V" => {
                     self.method_bytecode_instructions.push(Instruction::Return); // _return for void
-                    println!("      Generated bytecode: return (_return)");
                 }
-                "I" | "F" | "Z" | "B" | "C" | "S" => {
-                    // Integer, Float, Boolean, Byte, Char, Short returns
-                    self.method_bytecode_instructions.push(Instruction::Ireturn); // ireturn (return integer value) - Correct return for i32, and others mapped to 'I'
-                    println!("      Generated bytecode: ireturn");
+                "I" | "Z" | "B" | "C" | "S" => {
+                    // Integer, Boolean, Byte, Char, Short returns
+                    self.load_local(RETURN_PLACE);
+                    self.method_bytecode_instructions.push(Instruction::Ireturn);
                 }
-                "Ljava/lang/String;" | "Ljava/lang/Object;" => {
-                    // Object returns (String, etc. for now)
-                    self.method_bytecode_instructions.push(Instruction::Areturn); // areturn (return object reference)
-                    println!("      Generated bytecode: areturn");
+                "J" => {
+                    self.load_local(RETURN_PLACE);
+                    self.method_bytecode_instructions.push(Instruction::Lreturn);
+                }
+                "F" => {
+                    self.load_local(RETURN_PLACE);
+                    self.method_bytecode_instructions.push(Instruction::Freturn);
+                }
+                "D" => {
+                    self.load_local(RETURN_PLACE);
+                    self.method_bytecode_instructions.push(Instruction::Dreturn);
                 }
                 _ => {
-                    self.method_bytecode_instructions.push(Instruction::Return); // default to void return if type is unknown or unsupported for now
-                    println!(
-                        "      Generated bytecode: return (_return) - default void return for unknown type"
-                    );
+                    // Object returns (String, exported structs, etc.)
+                    self.load_local(RETURN_PLACE);
+                    self.method_bytecode_instructions.push(Instruction::Areturn); // areturn (return object reference)
                 }
             }
         }
+        match &terminator.kind {
+            TerminatorKind::Goto { target } => {
+                self.jump_to(location.block, *target);
+            }
+            TerminatorKind::Assert {
+                cond,
+                expected,
+                msg,
+                target,
+                ..
+            } => {
+                self.lower_assert(
+                    location.block,
+                    cond,
+                    *expected,
+                    msg,
+                    *target,
+                    terminator.source_info.span,
+                );
+            }
+            TerminatorKind::Drop { place, target, .. } => {
+                self.lower_cell_drop(place);
+                self.jump_to(location.block, *target);
+            }
+            TerminatorKind::SwitchInt { discr, targets } => {
+                let discr_ty = self.monomorphize(discr.ty(self.body, self.tcx));
+                let descriptor = rust_ty_to_jvm_descriptor(discr_ty, self.tcx);
+                let size = discr_ty.primitive_size(self.tcx);
+                for (value, target) in targets.iter() {
+                    match descriptor.as_str() {
+                        "J" => {
+                            self.load_operand(discr);
+                            let value = self.long_constant(value as i64);
+                            self.method_bytecode_instructions.push(value);
+                            self.method_bytecode_instructions.push(Instruction::Lcmp);
+                            self.branch_to(Instruction::Ifeq(0), target);
+                        }
+                        "Z" | "B" | "C" | "S" | "I" => {
+                            // Ints hold narrow integers sign-extended, as their constants are
+                            // loaded, so `255u8` is compared as -1
+                            self.load_operand(discr);
+                            let value = self.int_constant(size.sign_extend(value) as i32);
+                            self.method_bytecode_instructions.push(value);
+                            self.branch_to(Instruction::If_icmpeq(0), target);
+                        }
+                        // 128-bit integers are `BigInteger`s
+                        _ => {
+                            trace!("      Unsupported switch on: {discr_ty}");
+                            if !self.record_unsupported("switch", format_args!("on `{discr_ty}`")) {
+                                self.tcx.dcx().span_err(
+                                    self.span,
+                                    format!("matching on `{discr_ty}` is not supported on the JVM"),
+                                );
+                            }
+                            break;
+                        }
+                    }
+                }
+                self.jump_to(location.block, targets.otherwise());
+            }
+            TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                target,
+                ..
+            } => {
+                self.lower_call(func, args, destination, terminator.source_info.span);
+                match target {
+                    Some(target) => self.jump_to(location.block, *target),
+                    // The callee diverges, unless it is not a Rust function
+                    None => self.throw_unreachable(),
+                }
+            }
+            TerminatorKind::InlineAsm { .. } => {
+                self.report_inline_asm(terminator.source_info.span);
+            }
+            TerminatorKind::Unreachable => self.throw_unreachable(),
+            _ => {}
+        }
         self.super_terminator(terminator, location);
+        self.trace_instructions(start);
     }
 }
 
-fn generate_class_with_static_methods_bytecode(
-    crate_name: &str,
-    function_bytecodes: &FxIndexMap<String, Vec<Instruction>>,
-    tcx: TyCtxt<'_>, // Take TyCtxt as argument
-) -> ristretto_classfile::Result<Vec<u8>> {
-    let mut constant_pool = ConstantPool::default();
-    let super_class = constant_pool.add_class("java/lang/Object")?;
-    let this_class = constant_pool.add_class(crate_name)?;
-    let code_index = constant_pool.add_utf8("Code")?;
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Writes the instructions lowered since `start` to the trace, under the statement or
+    /// terminator they were lowered from
+    fn trace_instructions(&self, start: usize) {
+        for (index, instruction) in self.method_bytecode_instructions[start..]
+            .iter()
+            .enumerate()
+        {
+            trace!("      {}: {instruction:?}", start + index);
+        }
+    }
 
-    let mut methods = Vec::new();
+    /// Lowers a direct call to a function of this crate, a method of an exported struct or a
+    /// Java method imported with `#[jvm::import]`, storing the result in `destination`
+    fn lower_call(
+        &mut self,
+        func: &Operand<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+        span: Span,
+    ) {
+        let rustc_middle::ty::TyKind::FnDef(callee, generic_args) =
+            *func.ty(self.body, self.tcx).kind()
+        else {
+            trace!("      Unsupported call of: {func:?}");
+            self.record_unsupported("call", format_args!("{func:?}"));
+            return;
+        };
+        if let Some(class_name) = interop::import_class(self.tcx, callee) {
+            self.lower_java_call(callee, &class_name, args, destination);
+            return;
+        }
+        if self.lower_env_call(callee, args, destination)
+            || self.lower_panic_call(callee, generic_args, args, destination, span)
+        {
+            return;
+        }
+        if self.lower_intrinsic_call(callee, generic_args, args, destination)
+            || self.lower_float_method_call(callee, generic_args, args, destination)
+            || self.lower_integer_method_call(callee, generic_args, args, destination)
+            || self.lower_smart_pointer_call(callee, generic_args, args, destination)
+            || self.lower_atomic_call(callee, generic_args, args, destination)
+            || self.lower_cell_call(callee, generic_args, args, destination)
+            || self.lower_vec_call(callee, generic_args, args, destination)
+            || self.lower_string_call(callee, generic_args, args, destination)
+            || self.lower_fmt_call(callee, generic_args, args, destination)
+            || self.lower_range_call(callee, generic_args, args, destination)
+            || self.lower_iterator_call(callee, generic_args, args, destination)
+            || self.lower_time_call(callee, generic_args, args, destination)
+            || self.lower_io_call(callee, generic_args, args, destination)
+            || self.lower_mem_call(callee, args, destination)
+            || self.lower_layout_call(callee, generic_args, destination)
+            || self.lower_alloc_call(callee)
+            || self.lower_simd_call(callee, generic_args, args, destination)
+            || self.lower_pointer_call(callee, generic_args, args, destination)
+            || self.lower_coroutine_call(callee, args, destination)
+            || self.lower_trait_call(callee, generic_args, args, destination)
+            || self.lower_dyn_call(callee, generic_args, args, destination)
+            || self.lower_any_call(callee, generic_args, args, destination)
+        {
+            return;
+        }
+        if self
+            .tcx
+            .generics_of(callee)
+            .requires_monomorphization(self.tcx)
+        {
+            trace!("      Unsupported call of generic function: {func:?}");
+            self.record_unsupported("generic-call", format_args!("{func:?}"));
+            return;
+        }
+
+        let (class_name, method_name, descriptor, is_instance_method) =
+            if let Some(struct_def_id) = export::exported_struct_of_method(self.tcx, callee) {
+                let has_self = self.tcx.associated_item(callee).fn_has_self_parameter;
+                (
+                    export::class_name(self.tcx, struct_def_id),
+                    naming::method_name(self.tcx, callee),
+                    method_descriptor(self.tcx, callee, has_self),
+                    has_self,
+                )
+            } else if jni::is_native(self.tcx, callee) {
+                if !options::get().jni {
+                    self.report_native_call(callee, span);
+                    return;
+                }
+                // A `native` method of the class of the crate declaring the function
+                (
+                    naming::crate_class_name_of(self.tcx, callee.krate),
+                    jni::method_name(self.tcx, callee),
+                    method_descriptor(self.tcx, callee, false),
+                    false,
+                )
+            } else if self.tcx.def_kind(callee) == DefKind::Fn {
+                // A free function of this crate or of a crate it depends on
+                (
+                    naming::crate_class_name_of(self.tcx, callee.krate),
+                    naming::method_name(self.tcx, callee),
+                    method_descriptor(self.tcx, callee, false),
+                    false,
+                )
+            } else {
+                trace!("      Unsupported call of: {func:?}");
+                self.record_unsupported("call", format_args!("{func:?}"));
+                return;
+            };
 
-    for (function_name, method_bytecode_instructions) in function_bytecodes {
-        let method_name_index = constant_pool.add_utf8(function_name)?;
-        // Method descriptor - determine based on function signature, special case for "main"
-        let instance =
-            find_instance_by_name(tcx, function_name).expect("Instance not found for function");
-        let fn_sig = tcx.fn_sig(instance.def_id());
-        let mut method_descriptor = String::new();
-
-        if function_name == "main" && fn_sig.skip_binder().inputs().skip_binder().is_empty() {
-            // Check for main and no args
-            method_descriptor = "([Ljava/lang/String;)V".to_string(); // Special main descriptor, needed as rust main = 0 args but java main expects an array of strings
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method_ref = self
+            .constant_pool
+            .add_method_ref(class_index, &method_name, &descriptor)
+            .expect("Could not add method ref to constant pool");
+        for arg in args {
+            self.load_operand(&arg.node);
+        }
+        if is_instance_method {
+            self.method_bytecode_instructions
+                .push(Instruction::Invokevirtual(method_ref));
         } else {
-            // Regular descriptor generation
-            method_descriptor.push('(');
-            // Add argument descriptors
-            for arg_ty in fn_sig.skip_binder().inputs().skip_binder() {
-                method_descriptor.push_str(&rust_ty_to_jvm_descriptor(*arg_ty, tcx));
-            }
-            method_descriptor.push(')');
+            self.method_bytecode_instructions
+                .push(Instruction::Invokestatic(method_ref));
+        }
+        trace!(
+            "      Generated bytecode: call to {}",
+            self.tcx.def_path_str(callee)
+        );
+        self.store_call_result(destination);
+        self.write_back_arguments(args);
+    }
+
+    /// Stores the value a call left on the stack in its destination
+    fn store_call_result(&mut self, destination: &Place<'tcx>) {
+        if destination.projection.is_empty() && !self.is_unpacked_enum(destination.local) {
+            self.store_local(destination.local);
+            return;
+        }
+        trace!("      Unsupported call destination: {destination:?}");
+        self.record_unsupported("call-destination", format_args!("{destination:?}"));
+        let destination_ty = destination.ty(self.body, self.tcx).ty;
+        match rust_ty_to_jvm_descriptor(destination_ty, self.tcx).as_str() {
+            "V" => {}
+            "J" | "D" => self.method_bytecode_instructions.push(Instruction::Pop2),
+            _ => self.method_bytecode_instructions.push(Instruction::Pop),
+        }
+    }
+
+    /// Whether `destination` can hold an `Option` built by [`Self::store_option_result`]: it
+    /// must be a nullable or an unpacked `Option` local
+    fn is_option_destination(&self, destination: &Place<'tcx>) -> bool {
+        destination.projection.is_empty()
+            && (self.is_nullable_option(destination.local)
+                || self.is_unpacked_enum(destination.local))
+    }
+
+    /// Stores an `Option` computed by a conditional into `destination`. The caller emits a
+    /// branch instruction at `none_branch`, taken for `None`, and then pushes the `Some` value
+    /// with the given descriptor.
+    fn store_option_result(
+        &mut self,
+        none_branch: usize,
+        descriptor: &str,
+        destination: &Place<'tcx>,
+    ) {
+        let unpacked = self.is_unpacked_enum(destination.local);
+        let none = VariantIdx::from_u32(0);
+        let some = VariantIdx::from_u32(1);
+        if unpacked {
+            self.store_enum_field(destination.local, some, FieldIdx::from_u32(0));
+            self.store_discriminant(destination.local, some);
+        }
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
 
-            // Add return descriptor
-            let output_ty = fn_sig.skip_binder().output();
-            method_descriptor.push_str(&rust_ty_to_jvm_descriptor(output_ty.skip_binder(), tcx));
+        let none_start = self.method_bytecode_instructions.len();
+        self.extra_frames.push((none_start, Vec::new()));
+        if unpacked {
+            self.store_discriminant(destination.local, none);
+        } else {
+            self.method_bytecode_instructions
+                .push(Instruction::Aconst_null);
         }
-        let method_descriptor_index = constant_pool.add_utf8(method_descriptor)?;
 
-        let mut method = Method {
-            access_flags: MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
-            name_index: method_name_index,
-            descriptor_index: method_descriptor_index,
-            attributes: Vec::new(),
+        let end = self.method_bytecode_instructions.len();
+        let end_stack = if unpacked {
+            Vec::new()
+        } else {
+            vec![
+                control_flow::verification_type(descriptor, self.constant_pool)
+                    .expect("Could not add class to constant pool"),
+            ]
         };
+        self.extra_frames.push((end, end_stack));
+        self.method_bytecode_instructions[none_branch] = control_flow::with_branch_target(
+            &self.method_bytecode_instructions[none_branch],
+            none_start as u16,
+        );
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        if !unpacked {
+            self.store_local(destination.local);
+        }
+    }
+}
+
+/// Builds the JVM method descriptor of a Rust function. `skip_receiver` leaves out the `self`
+/// argument of methods that become JVM instance methods.
+fn method_descriptor(tcx: TyCtxt<'_>, def_id: DefId, skip_receiver: bool) -> String {
+    let fn_sig = tcx.fn_sig(def_id);
+    let mut method_descriptor = String::from("(");
+    // Add argument descriptors
+    for arg_ty in fn_sig
+        .skip_binder()
+        .inputs()
+        .skip_binder()
+        .iter()
+        .skip(usize::from(skip_receiver))
+    {
+        method_descriptor.push_str(&rust_ty_to_jvm_descriptor(*arg_ty, tcx));
+    }
+    method_descriptor.push(')');
+
+    // Add return descriptor
+    let output_ty = fn_sig.skip_binder().output();
+    method_descriptor.push_str(&rust_ty_to_jvm_descriptor(output_ty.skip_binder(), tcx));
+    method_descriptor
+}
+
+/// Creates a method with a `Code` attribute holding the given code
+fn create_method(
+    constant_pool: &mut ConstantPool,
+    access_flags: MethodAccessFlags,
+    name: &str,
+    descriptor: &str,
+    code: &MethodCode,
+) -> ristretto_classfile::Result<Method> {
+    let code_index = constant_pool.add_utf8("Code")?;
+    let name_index = constant_pool.add_utf8(name)?;
+    let descriptor_index = constant_pool.add_utf8(descriptor)?;
+    let instructions = code.instructions.clone();
+
+    let mut code_attributes = Vec::new();
+    if !code.stack_map_frames.is_empty() && options::get().emit_stack_maps {
+        code_attributes.push(Attribute::StackMapTable {
+            name_index: constant_pool.add_utf8("StackMapTable")?,
+            frames: code.stack_map_frames.clone(),
+        });
+    }
 
-        let max_stack = method_bytecode_instructions.max_stack(&constant_pool)?;
-        let max_locals =
-            method_bytecode_instructions.max_locals(&constant_pool, method_descriptor_index)?;
-        method.attributes.push(Attribute::Code {
+    let max_stack = instructions.max_stack(constant_pool)?;
+    let max_locals = instructions.max_locals(constant_pool, descriptor_index)?;
+    Ok(Method {
+        access_flags,
+        name_index,
+        descriptor_index,
+        attributes: vec![Attribute::Code {
             name_index: code_index,
             max_stack,
             max_locals,
-            code: method_bytecode_instructions.clone(),
-            exception_table: Vec::new(),
-            attributes: Vec::new(),
-        });
+            code: instructions,
+            exception_table: code.exception_table.clone(),
+            attributes: code_attributes,
+        }],
+    })
+}
+
+/// Generates the class of a codegen unit, see [`cgu`]
+fn generate_class_with_static_methods_bytecode(
+    class_name: &str,
+    super_class_name: &str,
+    mut constant_pool: ConstantPool,
+    function_bytecodes: FxIndexMap<DefId, MethodCode>,
+    mut instance_methods: Vec<(MethodAccessFlags, String, String, MethodCode)>,
+    runtime_classes: &mut RuntimeClasses,
+    tcx: TyCtxt<'_>, // Take TyCtxt as argument
+) -> ristretto_classfile::Result<Vec<u8>> {
+    let super_class = constant_pool.add_class(super_class_name)?;
+    let this_class = constant_pool.add_class(class_name)?;
+
+    let mut methods = Vec::new();
+    let mut fields = Vec::new();
+
+    let mut functions = Vec::with_capacity(function_bytecodes.len());
+    for (def_id, code) in function_bytecodes {
+        // Named only now, so that functions sharing a name get told apart, see `naming`
+        let function_name = naming::checked_method_name(tcx, def_id);
+        let method_descriptor = if entry::is_entry_fn(tcx, def_id) {
+            // The JVM starts the program with a synthetic `main(String[])` calling this one
+            let main_descriptor = entry::main_descriptor(tcx, def_id);
+            fields.push(env::args_field(&mut constant_pool)?);
+            methods.push(entry::main_wrapper(
+                &mut constant_pool,
+                class_name,
+                &function_name,
+                main_descriptor,
+                runtime_classes,
+            )?);
+            main_descriptor.to_string()
+        } else {
+            method_descriptor(tcx, def_id, false)
+        };
+        if agent::is_premain(tcx, def_id) {
+            // The JVM starts the agent with a synthetic `premain(String, Instrumentation)`
+            fields.push(agent::instrumentation_field(&mut constant_pool)?);
+            methods.push(agent::premain_wrapper(
+                &mut constant_pool,
+                class_name,
+                &function_name,
+                &method_descriptor,
+            )?);
+        }
+        functions.push((def_id, function_name, method_descriptor, code));
+    }
+
+    if Optimizations::of(tcx.sess).inline {
+        let mut all_methods: Vec<(&str, &str, &mut MethodCode)> = functions
+            .iter_mut()
+            .map(|(_, name, descriptor, code)| (name.as_str(), descriptor.as_str(), code))
+            .chain(
+                instance_methods
+                    .iter_mut()
+                    .map(|(_, name, descriptor, code)| (name.as_str(), descriptor.as_str(), code)),
+            )
+            .collect();
+        // Calls name the crate class, whichever unit the method is in
+        let crate_class = constant_pool.add_class(naming::crate_class_name(tcx))?;
+        inline::inline_tiny_methods(&constant_pool, crate_class, &mut all_methods);
+    }
+
+    for (def_id, function_name, method_descriptor, code) in &functions {
+        let def_id = *def_id;
+        let mut method = create_method(
+            &mut constant_pool,
+            visibility::fn_access_flags(tcx, def_id) | MethodAccessFlags::STATIC,
+            function_name,
+            method_descriptor,
+            code,
+        )?;
+        method.attributes.extend(signature::signature_attribute(
+            &mut constant_pool,
+            &signature::method_signature(tcx, def_id, false),
+            method_descriptor,
+        )?);
+        method.attributes.extend(annotations::fn_nullability(
+            &mut constant_pool,
+            tcx,
+            def_id,
+            false,
+        )?);
+        method.attributes.extend(annotations::java_annotations(
+            &mut constant_pool,
+            tcx,
+            def_id,
+        )?);
         methods.push(method);
     }
+    for (access_flags, name, descriptor, code) in &instance_methods {
+        methods.push(create_method(
+            &mut constant_pool,
+            *access_flags | MethodAccessFlags::STATIC,
+            name,
+            descriptor,
+            code,
+        )?);
+    }
+    if class_name == naming::crate_class_name(tcx) {
+        // The crate class holds the `native` methods of the crate's `extern "C"` blocks
+        methods.extend(jni::native_methods(
+            tcx,
+            &mut constant_pool,
+            runtime_classes,
+        )?);
+    }
 
     let class_file = ClassFile {
-        version: Version::Java8 { minor: 0 },
+        version: options::get().version(),
         access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
-        constant_pool,
+        constant_pool: constant_pool.into_inner(),
         this_class,
         super_class,
+        fields,
         methods,
         ..Default::default()
     };
@@ -464,21 +2606,6 @@ fn generate_class_with_static_methods_bytecode(
     Ok(bytes)
 }
 
-// Helper function to find Instance by function name (for descriptor generation)
-fn find_instance_by_name<'tcx>(tcx: TyCtxt<'tcx>, function_name: &str) -> Option<Instance<'tcx>> {
-    let module_items = tcx.hir_crate_items(());
-    for item_id in module_items.free_items() {
-        let item = tcx.hir_item(item_id);
-        if let rustc_hir::ItemKind::Fn { ident, .. } = &item.kind {
-            if ident.to_string() == function_name {
-                let def_id = item_id.owner_id.to_def_id();
-                return Some(Instance::mono(tcx, def_id));
-            }
-        }
-    }
-    None // Instance not found
-}
-
 struct RlibArchiveBuilder;
 impl ArchiveBuilderBuilder for RlibArchiveBuilder {
     fn new_archive_builder<'a>(&self, sess: &'a Session) -> Box<dyn ArchiveBuilder + 'a> {
@@ -0,0 +1,133 @@
+//! `f32` and `f64` math as `java.lang.Math` calls.
+
+use crate::MirToBytecodeVisitor;
+use crate::trace::trace;
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{FloatTy, GenericArgsRef, TyKind};
+use rustc_span::source_map::Spanned;
+
+/// Float methods implemented by `java.lang.Math`: the Rust method, the `Math` method, its number
+/// of float arguments, and whether it has a `float` overload
+const FLOAT_METHODS: [(&str, &str, usize, bool); 26] = [
+    ("sqrt", "sqrt", 1, false),
+    ("cbrt", "cbrt", 1, false),
+    ("abs", "abs", 1, true),
+    ("copysign", "copySign", 2, true),
+    ("min", "min", 2, true),
+    ("max", "max", 2, true),
+    ("floor", "floor", 1, false),
+    ("ceil", "ceil", 1, false),
+    ("round_ties_even", "rint", 1, false),
+    ("sin", "sin", 1, false),
+    ("cos", "cos", 1, false),
+    ("tan", "tan", 1, false),
+    ("asin", "asin", 1, false),
+    ("acos", "acos", 1, false),
+    ("atan", "atan", 1, false),
+    ("atan2", "atan2", 2, false),
+    ("sinh", "sinh", 1, false),
+    ("cosh", "cosh", 1, false),
+    ("tanh", "tanh", 1, false),
+    ("exp", "exp", 1, false),
+    ("exp_m1", "expm1", 1, false),
+    ("ln", "log", 1, false),
+    ("ln_1p", "log1p", 1, false),
+    ("log10", "log10", 1, false),
+    ("powf", "pow", 2, false),
+    ("hypot", "hypot", 2, false),
+];
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `f32` and `f64` math methods, returning `false` for any other call
+    pub(crate) fn lower_float_method_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        if tcx.impl_trait_ref(impl_def_id).is_some() {
+            return false;
+        }
+        let precision = match tcx
+            .type_of(impl_def_id)
+            .instantiate(tcx, generic_args)
+            .kind()
+        {
+            TyKind::Float(FloatTy::F32) => "F",
+            TyKind::Float(FloatTy::F64) => "D",
+            _ => return false,
+        };
+        let name = tcx.item_name(callee);
+        let operands: Vec<_> = args.iter().map(|arg| &arg.node).collect();
+        match name.as_str() {
+            "powi" => {
+                // The exponent is an `i32`
+                self.load_operand(operands[0]);
+                if precision == "F" {
+                    self.method_bytecode_instructions.push(Instruction::F2d);
+                }
+                self.load_operand(operands[1]);
+                self.method_bytecode_instructions.push(Instruction::I2d);
+                self.invoke_static("java/lang/Math", "pow", "(DD)D");
+                if precision == "F" {
+                    self.method_bytecode_instructions.push(Instruction::D2f);
+                }
+            }
+            "to_degrees" | "to_radians" => {
+                let method = if name.as_str() == "to_degrees" {
+                    "toDegrees"
+                } else {
+                    "toRadians"
+                };
+                self.invoke_math(method, &operands, precision, false);
+            }
+            name => {
+                let Some((_, method, arity, has_float)) = FLOAT_METHODS
+                    .iter()
+                    .find(|(rust_method, ..)| *rust_method == name)
+                else {
+                    trace!(
+                        "      Unsupported float method: {}",
+                        tcx.def_path_str(callee)
+                    );
+                    return false;
+                };
+                self.invoke_math(method, &operands[..*arity], precision, *has_float);
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Calls the `Math` method on float `operands` of the given precision (`F` or `D`), through
+    /// its `double` overload unless `has_float`
+    pub(crate) fn invoke_math(
+        &mut self,
+        method: &str,
+        operands: &[&Operand<'tcx>],
+        precision: &str,
+        has_float: bool,
+    ) {
+        let widen = precision == "F" && !has_float;
+        let computed = if widen { "D" } else { precision };
+        for operand in operands {
+            self.load_operand(operand);
+            if widen {
+                self.method_bytecode_instructions.push(Instruction::F2d);
+            }
+        }
+        let descriptor = format!("({}){computed}", computed.repeat(operands.len()));
+        self.invoke_static("java/lang/Math", method, &descriptor);
+        if widen {
+            self.method_bytecode_instructions.push(Instruction::D2f);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Maven metadata of an executable's JAR, with `-C llvm-args=maven-group=<group>`.
+
+use crate::{options, rustlib};
+use rustc_session::Session;
+use std::fmt::Write;
+
+/// The name of the metadata JAR
+pub(crate) const MAVEN_METADATA_NAME: &str = "maven-metadata";
+
+/// The Maven coordinates of the output
+struct Coordinates {
+    group: String,
+    artifact: String,
+    version: String,
+    description: Option<String>,
+}
+
+/// The value of a Cargo package variable, if it is set and not empty
+fn cargo_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn coordinates(sess: &Session) -> Option<Coordinates> {
+    let group = options::get().maven_group.clone()?;
+    let artifact = cargo_var("CARGO_PKG_NAME")
+        .or_else(|| sess.opts.crate_name.clone())
+        .unwrap_or_else(|| "main".to_string());
+    Some(Coordinates {
+        group,
+        artifact,
+        version: cargo_var("CARGO_PKG_VERSION").unwrap_or_else(|| "0.0.0".to_string()),
+        description: cargo_var("CARGO_PKG_DESCRIPTION"),
+    })
+}
+
+/// The value as XML text
+fn xml_text(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn pom_xml(coordinates: &Coordinates) -> String {
+    let mut pom = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<project \
+         xmlns=\"http://maven.apache.org/POM/4.0.0\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"http://maven.apache.org/POM/4.0.0 \
+         https://maven.apache.org/xsd/maven-4.0.0.xsd\">\n  <modelVersion>4.0.0</modelVersion>\n  \
+         <groupId>{}</groupId>\n  <artifactId>{}</artifactId>\n  <version>{}</version>\n  \
+         <packaging>jar</packaging>\n",
+        xml_text(&coordinates.group),
+        xml_text(&coordinates.artifact),
+        xml_text(&coordinates.version),
+    );
+    if let Some(description) = &coordinates.description {
+        let _ = writeln!(
+            pom,
+            "  <description>{}</description>",
+            xml_text(description)
+        );
+    }
+    pom.push_str("</project>\n");
+    pom
+}
+
+/// The bytes of the metadata JAR, if the executable has a Maven group
+pub(crate) fn metadata_jar(sess: &Session) -> Option<Vec<u8>> {
+    let coordinates = coordinates(sess)?;
+    let directory = format!(
+        "META-INF/maven/{}/{}",
+        coordinates.group, coordinates.artifact
+    );
+    let properties = format!(
+        "artifactId={}\ngroupId={}\nversion={}\n",
+        coordinates.artifact, coordinates.group, coordinates.version
+    );
+    let entries = [
+        (
+            format!("{directory}/pom.xml"),
+            pom_xml(&coordinates).into_bytes(),
+        ),
+        (
+            format!("{directory}/pom.properties"),
+            properties.into_bytes(),
+        ),
+    ];
+    Some(rustlib::stored_zip(&entries))
+}
@@ -0,0 +1,183 @@
+//! `mem::swap`, `mem::replace` and `mem::take`, as loads and stores of the borrowed places.
+
+use crate::array::array_store_instruction;
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, nullable, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{BorrowKind, Local, Operand, Place, ProjectionElem, Rvalue, StatementKind};
+use rustc_middle::ty::Ty;
+use rustc_span::source_map::Spanned;
+use rustc_span::sym;
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `mem::swap`, `mem::replace` and `mem::take`, returning `false` for any
+    /// other call
+    pub(crate) fn lower_mem_call(
+        &mut self,
+        callee: DefId,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let Some(name) = self.tcx.get_diagnostic_name(callee) else {
+            return false;
+        };
+        if ![sym::mem_swap, sym::mem_replace, sym::mem_take].contains(&name) {
+            return false;
+        }
+        let places: Option<Vec<_>> = args
+            .iter()
+            .take(if name == sym::mem_swap { 2 } else { 1 })
+            .map(|arg| self.borrowed_place(&arg.node))
+            .collect();
+        let Some(places) = places else {
+            trace!("      Unsupported {name} of a borrow that is not a local or a field");
+            return false;
+        };
+
+        if name == sym::mem_swap {
+            // The object of each field is pushed before the value stored into it
+            self.begin_store(&places[1]);
+            self.load_place(&places[0]);
+            self.begin_store(&places[0]);
+            self.load_place(&places[1]);
+            self.end_store(&places[0]);
+            self.end_store(&places[1]);
+        } else {
+            let ty = places[0].ty(self.body, self.tcx).ty;
+            if name == sym::mem_take && !self.can_load_default(ty) {
+                trace!("      Unsupported mem::take of: {ty:?}");
+                return false;
+            }
+            self.load_place(&places[0]);
+            self.store_call_result(destination);
+            self.begin_store(&places[0]);
+            if name == sym::mem_take {
+                self.load_default(ty);
+            } else {
+                self.load_operand(&args[1].node);
+            }
+            self.end_store(&places[0]);
+        }
+        trace!(
+            "      Generated bytecode: {}",
+            self.tcx.def_path_str(callee)
+        );
+        true
+    }
+
+    /// The local or exported struct field that a `&mut` operand borrows, following reborrows
+    fn borrowed_place(&mut self, operand: &Operand<'tcx>) -> Option<Place<'tcx>> {
+        let (Operand::Copy(reference) | Operand::Move(reference)) = operand else {
+            return None;
+        };
+        if !reference.projection.is_empty() {
+            return None;
+        }
+        let body = self.body;
+        let mut borrows = body
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.statements)
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(box (place, rvalue)) if place.local == reference.local => {
+                    Some((place, rvalue))
+                }
+                _ => None,
+            });
+        let (Some((place, rvalue)), None) = (borrows.next(), borrows.next()) else {
+            return self.ref_cell_value_place(reference.local);
+        };
+        let Rvalue::Ref(_, BorrowKind::Mut { .. }, borrowed) = rvalue else {
+            return self.ref_cell_value_place(reference.local);
+        };
+        if !place.projection.is_empty() {
+            return None;
+        }
+        let borrowed = *borrowed;
+        match &borrowed.projection[..] {
+            [] if !self.is_unpacked_enum(borrowed.local) => Some(borrowed),
+            // `&mut *reference`
+            [ProjectionElem::Deref] => self.borrowed_place(&Operand::Copy(borrowed.local.into())),
+            _ => self.field_ref(&borrowed).map(|_| borrowed),
+        }
+    }
+
+    /// `*reference` for a reference that is a ref cell, which is borrowed from its caller or
+    /// a callee
+    fn ref_cell_value_place(&self, reference: Local) -> Option<Place<'tcx>> {
+        self.is_ref_cell_ref(reference)
+            .then(|| self.tcx.mk_place_deref(reference.into()))
+    }
+
+    /// Pushes the object of a field or the ref cell and index of a value about to be stored into,
+    /// nothing for a local
+    fn begin_store(&mut self, place: &Place<'tcx>) {
+        if !place.projection.is_empty() {
+            self.load_local(place.local);
+        }
+        if self.is_ref_cell_deref(place) {
+            self.method_bytecode_instructions
+                .push(Instruction::Iconst_0);
+        }
+    }
+
+    /// Stores the value on top of the stack into a local, or into a field or ref cell whose
+    /// object [`Self::begin_store`] pushed
+    fn end_store(&mut self, place: &Place<'tcx>) {
+        if self.is_ref_cell_deref(place) {
+            let descriptor = self.ref_cell_value_descriptor(place.local);
+            self.method_bytecode_instructions
+                .push(array_store_instruction(&descriptor));
+            return;
+        }
+        match self.field_ref(place) {
+            Some(field_ref) if !place.projection.is_empty() => self
+                .method_bytecode_instructions
+                .push(Instruction::Putfield(field_ref)),
+            _ => self.store_local(place.local),
+        }
+    }
+
+    /// Whether [`Self::load_default`] knows the default value of `ty`
+    fn can_load_default(&self, ty: Ty<'tcx>) -> bool {
+        nullable::payload(ty, self.tcx).is_some()
+            || matches!(
+                rust_ty_to_jvm_descriptor(ty, self.tcx).as_str(),
+                "Z" | "B"
+                    | "C"
+                    | "S"
+                    | "I"
+                    | "J"
+                    | "F"
+                    | "D"
+                    | "Ljava/lang/StringBuilder;"
+                    | "Ljava/util/ArrayList;"
+            )
+    }
+
+    /// Pushes the `Default` value of `ty`: zero, `None`, or an empty `String` or `Vec`
+    fn load_default(&mut self, ty: Ty<'tcx>) {
+        if nullable::payload(ty, self.tcx).is_some() {
+            self.method_bytecode_instructions
+                .push(Instruction::Aconst_null);
+            return;
+        }
+        match rust_ty_to_jvm_descriptor(ty, self.tcx).as_str() {
+            "J" => self
+                .method_bytecode_instructions
+                .push(Instruction::Lconst_0),
+            "F" => self
+                .method_bytecode_instructions
+                .push(Instruction::Fconst_0),
+            "D" => self
+                .method_bytecode_instructions
+                .push(Instruction::Dconst_0),
+            "Ljava/lang/StringBuilder;" => self.new_string_builder(None, "()V"),
+            "Ljava/util/ArrayList;" => self.new_array_list(None, "()V"),
+            _ => self
+                .method_bytecode_instructions
+                .push(Instruction::Iconst_0),
+        }
+    }
+}
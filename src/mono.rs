@@ -0,0 +1,137 @@
+//! Trait methods and compiler-generated shims, collected as mono items.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, allocator, coroutines, naming, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_data_structures::fx::FxIndexSet;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::mono::MonoItem;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{EarlyBinder, GenericArgsRef, Instance, InstanceKind, TyCtxt, TypingEnv};
+use rustc_span::source_map::Spanned;
+
+/// Whether the instance is a non-generic method of a trait impl of this crate other than
+/// `GlobalAlloc` (see [`crate::allocator`]), the `poll` or `resume` of a coroutine of this crate
+/// (see [`crate::coroutines`]), or a `Clone` shim
+pub(crate) fn is_collected<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>) -> bool {
+    match instance.def {
+        InstanceKind::Item(def_id) if coroutines::is_lowered_coroutine(tcx, def_id) => {
+            !tcx.generics_of(def_id).requires_monomorphization(tcx)
+        }
+        InstanceKind::Item(def_id) => {
+            def_id.is_local()
+                && tcx.def_kind(def_id) == DefKind::AssocFn
+                && tcx
+                    .impl_of_method(def_id)
+                    .is_some_and(|impl_def_id| tcx.trait_id_of_impl(impl_def_id).is_some())
+                && !tcx.generics_of(def_id).requires_monomorphization(tcx)
+                && !allocator::is_global_alloc_method(tcx, def_id)
+        }
+        InstanceKind::CloneShim(..) => true,
+        _ => false,
+    }
+}
+
+/// The instances of trait methods and shims the crate uses, in a stable order
+pub(crate) fn collected_instances(tcx: TyCtxt<'_>) -> Vec<Instance<'_>> {
+    let partitions = tcx.collect_and_partition_mono_items(());
+    let mut instances = FxIndexSet::default();
+    for codegen_unit in partitions.codegen_units {
+        for item in codegen_unit.items().keys() {
+            if let MonoItem::Fn(instance) = item
+                && is_collected(tcx, *instance)
+            {
+                instances.insert(*instance);
+            }
+        }
+    }
+    instances.into_iter().collect()
+}
+
+/// The descriptor of the method generated for an instance, from its monomorphic signature
+pub(crate) fn instance_descriptor<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>) -> String {
+    let typing_env = TypingEnv::fully_monomorphized();
+    let (input_tys, output_ty) = match instance.def {
+        // Coroutines have no function signature, their resume function is their body
+        InstanceKind::Item(def_id) if tcx.is_coroutine(def_id) => {
+            let body = tcx.instance_mir(instance.def);
+            let instantiate = |ty| {
+                instance.instantiate_mir_and_normalize_erasing_regions(
+                    tcx,
+                    typing_env,
+                    EarlyBinder::bind(ty),
+                )
+            };
+            let input_tys = body
+                .args_iter()
+                .map(|local| instantiate(body.local_decls[local].ty))
+                .collect();
+            (input_tys, instantiate(body.return_ty()))
+        }
+        _ => {
+            let fn_sig = tcx.normalize_erasing_late_bound_regions(
+                typing_env,
+                instance.ty(tcx, typing_env).fn_sig(tcx),
+            );
+            (fn_sig.inputs().to_vec(), fn_sig.output())
+        }
+    };
+    let mut descriptor = String::from("(");
+    for input_ty in input_tys {
+        // Zero-sized arguments, like the `()` coroutines are usually resumed with, are not passed
+        let input_descriptor = rust_ty_to_jvm_descriptor(input_ty, tcx);
+        if input_descriptor != "V" {
+            descriptor.push_str(&input_descriptor);
+        }
+    }
+    descriptor.push(')');
+    descriptor.push_str(&rust_ty_to_jvm_descriptor(output_ty, tcx));
+    descriptor
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of trait methods resolving to a collected instance, returning `false` for any
+    /// other call
+    pub(crate) fn lower_trait_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        if tcx.trait_of_item(callee).is_none() {
+            return false;
+        }
+        let Ok(Some(instance)) =
+            Instance::try_resolve(tcx, TypingEnv::fully_monomorphized(), callee, generic_args)
+        else {
+            return false;
+        };
+        if !is_collected(tcx, instance) {
+            return false;
+        }
+
+        let class_index = self
+            .constant_pool
+            .add_class(naming::crate_class_name(tcx))
+            .expect("Could not add class to constant pool");
+        let method_ref = self
+            .constant_pool
+            .add_method_ref(
+                class_index,
+                &naming::instance_method_name(tcx, instance),
+                &instance_descriptor(tcx, instance),
+            )
+            .expect("Could not add method ref to constant pool");
+        for arg in args {
+            self.load_operand(&arg.node);
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokestatic(method_ref));
+        trace!("      Generated bytecode: call to {instance}");
+        self.store_call_result(destination);
+        true
+    }
+}
@@ -0,0 +1,31 @@
+//! Classes for a multi-release JAR, with `-C llvm-args=multi-release=<version>`.
+
+use crate::runtime::RuntimeClasses;
+use crate::{exported_type_classes, nest, options};
+use rustc_middle::ty::TyCtxt;
+
+/// The exported struct and enum classes for the `multi-release` version, named after their
+/// entries in the JAR, if the option is given
+pub(crate) fn versioned_classes(
+    tcx: TyCtxt<'_>,
+    runtime_classes: &mut RuntimeClasses,
+) -> Vec<(String, Vec<u8>)> {
+    let Some(version) = options::get().multi_release else {
+        return Vec::new();
+    };
+    let mut classes = exported_type_classes(tcx, runtime_classes, version);
+    if let Err(err) = nest::add_nest_attributes(tcx, &mut classes, version) {
+        tcx.dcx().err(format!(
+            "could not add the nest attributes of the Java {version} classes: {err:?}"
+        ));
+    }
+    classes
+        .into_iter()
+        .map(|(class_name, bytecode)| {
+            (
+                format!("META-INF/versions/{version}/{class_name}"),
+                bytecode,
+            )
+        })
+        .collect()
+}
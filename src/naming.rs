@@ -0,0 +1,330 @@
+//! Names of generated JVM methods. The crate's API is named for Java callers, every other method
+//! after a name that stays stable and unique.
+
+use crate::options::CratePackages;
+use crate::{options, visibility};
+use rustc_data_structures::fx::FxIndexMap;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{CrateNum, DefId, LOCAL_CRATE};
+use rustc_middle::middle::codegen_fn_attrs::CodegenFnAttrFlags;
+use rustc_middle::ty::print::with_no_trimmed_paths;
+use rustc_middle::ty::{Instance, InstanceKind, Ty, TyCtxt, TyKind, TypingEnv};
+use rustc_session::config::CrateType;
+use rustc_span::Symbol;
+
+/// Characters the JVM does not allow in method names (JVMS §4.2.2)
+const INVALID_METHOD_NAME_CHARACTERS: [char; 6] = ['.', ';', '[', '/', '<', '>'];
+
+/// The internal name of the package of a crate's classes, `None` for the default package. Without
+/// `crate-packages`, only the crate being compiled is put in the `package`, as the options of other
+/// crates are not known here, see [`check_package`].
+pub(crate) fn crate_package(tcx: TyCtxt<'_>, krate: CrateNum) -> Option<String> {
+    let options = options::get();
+    let Some(crate_packages) = options.crate_packages else {
+        return options.package.clone().filter(|_| krate == LOCAL_CRATE);
+    };
+    let root = options.package.as_deref().unwrap_or("rust");
+    let name = tcx.crate_name(krate);
+    Some(match crate_packages {
+        CratePackages::Name => format!("{root}/{name}"),
+        CratePackages::Disambiguated => {
+            format!(
+                "{root}/{name}/h{:016x}",
+                tcx.stable_crate_id(krate).as_u64()
+            )
+        }
+    })
+}
+
+/// The internal name of a class of a crate, in the crate's package
+pub(crate) fn in_crate_package(tcx: TyCtxt<'_>, krate: CrateNum, class_name: &str) -> String {
+    match crate_package(tcx, krate) {
+        Some(package) => format!("{package}/{class_name}"),
+        None => class_name.to_string(),
+    }
+}
+
+/// The internal name of the crate class, in the crate's package
+pub(crate) fn crate_class_name(tcx: TyCtxt<'_>) -> String {
+    crate_class_name_of(tcx, LOCAL_CRATE)
+}
+
+/// The internal name of the class of a crate, the crate being compiled or one it depends on
+pub(crate) fn crate_class_name_of(tcx: TyCtxt<'_>, krate: CrateNum) -> String {
+    in_crate_package(tcx, krate, tcx.crate_name(krate).as_str())
+}
+
+/// Reports the crates of an executable's dependency graph that share a name, as two versions of a
+/// crate do, whose classes collide in its JAR unless their packages are disambiguated
+pub(crate) fn check_crate_names(tcx: TyCtxt<'_>) {
+    if options::get().crate_packages == Some(CratePackages::Disambiguated)
+        || !tcx.crate_types().contains(&CrateType::Executable)
+    {
+        return;
+    }
+    let mut crates: FxIndexMap<Symbol, usize> = FxIndexMap::default();
+    for krate in std::iter::once(LOCAL_CRATE).chain(tcx.crates(()).iter().copied()) {
+        // Proc-macro crates run in the compiler, and are not linked
+        if krate != LOCAL_CRATE && tcx.dep_kind(krate).macros_only() {
+            continue;
+        }
+        *crates.entry(tcx.crate_name(krate)).or_default() += 1;
+    }
+    for (name, count) in crates.into_iter().filter(|(_, count)| *count > 1) {
+        tcx.dcx()
+            .struct_err(format!(
+                "the dependency graph has {count} crates named `{name}`, whose classes collide \
+                 in the JAR"
+            ))
+            .with_help(
+                "put each crate's classes in a package of its own with \
+                 `-C llvm-args=crate-packages=disambiguated`",
+            )
+            .emit();
+    }
+}
+
+/// Reports a `package` without `crate-packages` given to a crate with dependencies, whose classes
+/// would be called in the default package whatever package they were built with
+pub(crate) fn check_package(tcx: TyCtxt<'_>) {
+    let options = options::get();
+    let Some(package) = &options.package else {
+        return;
+    };
+    if options.crate_packages.is_some() {
+        return;
+    }
+    // Proc-macro crates run in the compiler, and have no classes
+    let Some(dependency) = tcx
+        .crates(())
+        .iter()
+        .find(|krate| !tcx.dep_kind(**krate).macros_only())
+    else {
+        return;
+    };
+    let package = package.replace('/', ".");
+    tcx.dcx()
+        .struct_err(format!(
+            "`-C llvm-args=package={package}` only applies to `{}`, which depends on `{}`, whose \
+             package is not known",
+            tcx.crate_name(LOCAL_CRATE),
+            tcx.crate_name(*dependency)
+        ))
+        .with_help(format!(
+            "put each crate's classes in a package of its own under `{package}` with \
+             `-C llvm-args=crate-packages=name`"
+        ))
+        .emit();
+}
+
+/// The package part of an internal class name, empty for the default package
+pub(crate) fn package_of(class_name: &str) -> &str {
+    class_name
+        .rsplit_once('/')
+        .map_or("", |(package, _)| package)
+}
+
+/// The name of the JVM method generated for a Rust function
+pub(crate) fn method_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let attrs = tcx.codegen_fn_attrs(def_id);
+    if let Some(export_name) = attrs.export_name {
+        return export_name.to_string();
+    }
+    if attrs.flags.contains(CodegenFnAttrFlags::NO_MANGLE) {
+        return tcx.item_name(def_id).to_string();
+    }
+    let name = api_name(tcx, def_id);
+    if tcx.def_kind(def_id) != DefKind::Fn {
+        return name;
+    }
+    let path = tcx.def_path(def_id).to_string_no_crate_verbose();
+    let parent = path
+        .trim_start_matches("::")
+        .rsplit_once("::")
+        .map(|(parent, _)| parent);
+    match parent {
+        Some(parent) => format!("{}${name}", parent.replace("::", "$")),
+        None => name,
+    }
+}
+
+/// The name of a function, in camelCase if it is part of the crate's API and the `method-names`
+/// option asks for it. The functions of other crates are assumed to have been compiled with the
+/// same option.
+fn api_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let name = tcx.item_name(def_id).to_string();
+    if options::get().camel_case_names && visibility::is_api(tcx, def_id) {
+        camel_case(&name)
+    } else {
+        name
+    }
+}
+
+/// A `snake_case` name in camelCase, as in `addNumbers` for `add_numbers`. Leading underscores are
+/// kept.
+pub(crate) fn camel_case(name: &str) -> String {
+    let body = name.trim_start_matches('_');
+    let mut camel_case = name[..name.len() - body.len()].to_string();
+    for (index, word) in body.split('_').filter(|word| !word.is_empty()).enumerate() {
+        let mut chars = word.chars();
+        if index > 0
+            && let Some(first) = chars.next()
+        {
+            camel_case.extend(first.to_uppercase());
+        }
+        camel_case.push_str(chars.as_str());
+    }
+    camel_case
+}
+
+/// Reports the functions of the crate whose methods would share a name, which the JVM cannot tell
+/// apart
+pub(crate) fn check_method_names(tcx: TyCtxt<'_>, functions: &[DefId]) {
+    let mut functions_by_name: FxIndexMap<String, Vec<DefId>> = FxIndexMap::default();
+    for def_id in functions {
+        functions_by_name
+            .entry(method_name(tcx, *def_id))
+            .or_default()
+            .push(*def_id);
+    }
+    for (name, def_ids) in functions_by_name {
+        let [first, others @ ..] = def_ids.as_slice() else {
+            continue;
+        };
+        for other in others {
+            tcx.dcx()
+                .struct_span_err(
+                    tcx.def_span(*other),
+                    format!(
+                        "`{}` and `{}` are both generated as the JVM method `{name}`",
+                        tcx.def_path_str(*first),
+                        tcx.def_path_str(*other)
+                    ),
+                )
+                .with_span_note(tcx.def_span(*first), format!("`{name}` is first used here"))
+                .with_help("rename one of them, or name its method with `#[export_name = \"...\"]`")
+                .emit();
+        }
+    }
+}
+
+/// The name of the JVM method generated for a Rust function, reporting an error if it is not a
+/// valid JVM method name. Used where the method is defined, so each name is checked once.
+pub(crate) fn checked_method_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let name = method_name(tcx, def_id);
+    if name.is_empty() || name.contains(INVALID_METHOD_NAME_CHARACTERS) {
+        tcx.dcx().span_err(
+            tcx.def_span(def_id),
+            format!(
+                "`{name}` is not a valid JVM method name: it must be non-empty and not contain any \
+                 of `. ; [ / < >`"
+            ),
+        );
+    }
+    name
+}
+
+/// The name of the JVM method generated for a collected trait method or shim instance, see
+/// [`crate::mono`]
+pub(crate) fn instance_method_name<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>) -> String {
+    if let InstanceKind::Item(def_id) = instance.def
+        && tcx.is_coroutine(def_id)
+    {
+        return format!(
+            "{}${}",
+            coroutine_class_name(tcx, def_id),
+            coroutine_method_name(tcx, def_id)
+        );
+    }
+    if let InstanceKind::Item(def_id) = instance.def
+        && let Some(impl_def_id) = tcx.impl_of_method(def_id)
+        && let Some(trait_def_id) = tcx.trait_id_of_impl(impl_def_id)
+    {
+        let self_ty = tcx.normalize_erasing_regions(
+            TypingEnv::fully_monomorphized(),
+            tcx.type_of(impl_def_id).instantiate(tcx, instance.args),
+        );
+        let name = format!(
+            "{}${}${}",
+            type_name(tcx, self_ty),
+            tcx.item_name(trait_def_id),
+            tcx.item_name(def_id)
+        );
+        return name.replace(INVALID_METHOD_NAME_CHARACTERS, "$");
+    }
+    tcx.symbol_name(instance)
+        .name
+        .replace(INVALID_METHOD_NAME_CHARACTERS, "$")
+}
+
+/// The name of the adapter class implementing the interface of a trait for the values of a type
+/// coerced to trait objects, as in `Circle$Shape`, see [`crate::trait_objects`]
+pub(crate) fn adapter_class_name<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    ty: Ty<'tcx>,
+    trait_def_id: DefId,
+) -> String {
+    let name = format!("{}${}", type_name(tcx, ty), tcx.item_name(trait_def_id))
+        .replace(INVALID_METHOD_NAME_CHARACTERS, "$");
+    in_crate_package(tcx, LOCAL_CRATE, &name)
+}
+
+/// The name of the class holding the state of a coroutine, after the function it is defined in,
+/// its kind and its index there, as in `fetch$async0` or `counter$coroutine0`, see
+/// [`crate::coroutines`]
+pub(crate) fn coroutine_class_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let parent = tcx.parent(def_id);
+    let parent_name = if tcx.is_coroutine(parent) {
+        coroutine_class_name(tcx, parent)
+    } else {
+        method_name(tcx, parent)
+    };
+    let kind = if tcx.coroutine_is_async(def_id) {
+        "async"
+    } else {
+        "coroutine"
+    };
+    format!(
+        "{parent_name}${kind}{}",
+        tcx.def_key(def_id).disambiguated_data.disambiguator
+    )
+    .replace(INVALID_METHOD_NAME_CHARACTERS, "$")
+}
+
+/// The name of the method resuming a coroutine: `poll` for `async` bodies, `resume` otherwise
+pub(crate) fn coroutine_method_name(tcx: TyCtxt<'_>, def_id: DefId) -> &'static str {
+    if tcx.coroutine_is_async(def_id) {
+        "poll"
+    } else {
+        "resume"
+    }
+}
+
+/// A type as it appears in generated names: an ADT by its path in its crate, followed by a hash
+/// of the type if it has generic arguments, as in `shapes$Point` or `Wrapper$h1a2b3c4d5e6f7a8b`,
+/// anything else as Rust prints it. The hash is the one of `TypeId`, the same in every crate.
+fn type_name<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> String {
+    match ty.kind() {
+        TyKind::Adt(adt_def, args) => {
+            let path = path_name(tcx, adt_def.did());
+            if args.is_empty() {
+                path
+            } else {
+                format!("{path}$h{:016x}", tcx.type_id_hash(ty).truncate().as_u64())
+            }
+        }
+        _ => with_no_trimmed_paths!(ty.to_string()),
+    }
+}
+
+/// The path of an item with `$` for `::`, as in `shapes$Point` for `shapes::Point`, after the name
+/// of its crate if it is not the crate being compiled
+fn path_name(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let path = tcx.def_path(def_id).to_string_no_crate_verbose();
+    let path = path.trim_start_matches("::").replace("::", "$");
+    if def_id.is_local() {
+        path
+    } else {
+        format!("{}${path}", tcx.crate_name(def_id.krate))
+    }
+}
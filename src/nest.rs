@@ -0,0 +1,116 @@
+//! Nestmates of the generated helper classes.
+
+use crate::naming;
+use ristretto_classfile::ClassFile;
+use ristretto_classfile::attributes::Attribute;
+use rustc_data_structures::fx::{FxHashSet, FxIndexMap};
+use rustc_middle::ty::TyCtxt;
+use std::io::Cursor;
+
+/// The first class version with nestmates, from Java 11
+const NEST_CLASS_VERSION: u16 = 11;
+
+/// The class a generated class belongs to, if it is a helper class of another class of the crate
+fn host_of<'a>(
+    class_name: &str,
+    crate_class: &'a str,
+    class_names: &FxHashSet<&'a str>,
+) -> Option<&'a str> {
+    let simple_name_start = class_name.rfind('/').map_or(0, |slash| slash + 1);
+    if !class_name[simple_name_start..].contains('$') {
+        return None;
+    }
+    // The longest prefix that is a class, as in `Shape` for `Shape$Circle`
+    class_name
+        .rmatch_indices('$')
+        .filter(|(index, _)| *index > simple_name_start)
+        .find_map(|(index, _)| class_names.get(&class_name[..index]).copied())
+        .or_else(|| class_names.contains(crate_class).then_some(crate_class))
+        .filter(|host| *host != class_name)
+}
+
+/// Adds the `NestHost` and `NestMembers` attributes tying the crate's helper classes, generated
+/// for the Java version, to the classes they belong to
+pub(crate) fn add_nest_attributes(
+    tcx: TyCtxt<'_>,
+    classes: &mut [(String, Vec<u8>)],
+    class_version: u16,
+) -> ristretto_classfile::Result<()> {
+    if class_version < NEST_CLASS_VERSION {
+        return Ok(());
+    }
+    let crate_class = naming::crate_class_name(tcx);
+    let package = naming::package_of(&crate_class);
+    let class_names: FxHashSet<&str> = classes
+        .iter()
+        .map(|(class_name, _)| class_name.as_str())
+        .filter(|class_name| naming::package_of(class_name) == package)
+        .collect();
+
+    let mut direct_hosts = FxIndexMap::default();
+    for class_name in &class_names {
+        if let Some(host) = host_of(class_name, &crate_class, &class_names) {
+            direct_hosts.insert(*class_name, host);
+        }
+    }
+    // A nest has a single host, the outermost class, as the crate class for the `async` block in
+    // an `async` function, `fetch$async0$async1`
+    let mut hosts = FxIndexMap::default();
+    let mut members: FxIndexMap<&str, Vec<&str>> = FxIndexMap::default();
+    for (class_name, mut host) in direct_hosts.iter().map(|(name, host)| (*name, *host)) {
+        while let Some(outer_host) = direct_hosts.get(host) {
+            host = *outer_host;
+        }
+        hosts.insert(class_name.to_string(), host.to_string());
+        members.entry(host).or_default().push(class_name);
+    }
+    if hosts.is_empty() {
+        return Ok(());
+    }
+    let members: FxIndexMap<String, Vec<String>> = members
+        .into_iter()
+        .map(|(host, members)| {
+            let mut members: Vec<String> = members.into_iter().map(str::to_string).collect();
+            members.sort();
+            (host.to_string(), members)
+        })
+        .collect();
+
+    for (class_name, bytecode) in classes.iter_mut() {
+        let host = hosts.get(class_name.as_str());
+        let nest_members = members.get(class_name.as_str());
+        if host.is_none() && nest_members.is_none() {
+            continue;
+        }
+        let mut class_file = ClassFile::from_bytes(&mut Cursor::new(bytecode.clone()))?;
+        // Classes reused from the previous build were saved with the attributes of its nest
+        class_file.attributes.retain(|attribute| {
+            !matches!(
+                attribute,
+                Attribute::NestHost { .. } | Attribute::NestMembers { .. }
+            )
+        });
+        if let Some(host) = host {
+            let attribute = Attribute::NestHost {
+                name_index: class_file.constant_pool.add_utf8("NestHost")?,
+                host_class_index: class_file.constant_pool.add_class(host)?,
+            };
+            class_file.attributes.push(attribute);
+        }
+        if let Some(nest_members) = nest_members {
+            let mut class_indexes = Vec::with_capacity(nest_members.len());
+            for member in nest_members {
+                class_indexes.push(class_file.constant_pool.add_class(member)?);
+            }
+            let attribute = Attribute::NestMembers {
+                name_index: class_file.constant_pool.add_utf8("NestMembers")?,
+                class_indexes,
+            };
+            class_file.attributes.push(attribute);
+        }
+        class_file.verify()?;
+        bytecode.clear();
+        class_file.to_bytes(bytecode)?;
+    }
+    Ok(())
+}
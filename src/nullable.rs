@@ -0,0 +1,82 @@
+//! `Option`s of references as nullable JVM references.
+
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::VariantIdx;
+use rustc_middle::mir::{Local, Operand};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_span::sym;
+
+/// The payload of an `Option` represented as a nullable reference, or `None` if the type is not
+/// such an `Option`. Nested `Option`s of references are not, as `null` could not tell `None` from
+/// `Some(None)`.
+pub(crate) fn payload<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    if !tcx.is_diagnostic_item(sym::Option, adt_def.did()) {
+        return None;
+    }
+    let payload_ty = generic_args.type_at(0);
+    if payload(payload_ty, tcx).is_some() {
+        return None;
+    }
+    match rust_ty_to_jvm_descriptor(payload_ty, tcx)
+        .as_bytes()
+        .first()
+    {
+        Some(b'L' | b'[') => Some(payload_ty),
+        _ => None,
+    }
+}
+
+/// Whether a variant of an `Option` is `None`, which comes first
+fn is_none(variant_index: VariantIdx) -> bool {
+    variant_index.as_u32() == 0
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Whether a local holds an `Option` represented as a nullable reference
+    pub(crate) fn is_nullable_option(&self, local: Local) -> bool {
+        payload(self.body.local_decls[local].ty, self.tcx).is_some()
+    }
+
+    /// Pushes the discriminant of the nullable `Option` in `local`: 0 for `None`, 1 for `Some`
+    pub(crate) fn load_nullable_discriminant(&mut self, local: Local) {
+        let objects = self
+            .constant_pool
+            .add_class("java/util/Objects")
+            .expect("Could not add class to constant pool");
+        let non_null = self
+            .constant_pool
+            .add_method_ref(objects, "nonNull", "(Ljava/lang/Object;)Z")
+            .expect("Could not add method ref to constant pool");
+        self.load_local(local);
+        self.method_bytecode_instructions
+            .push(Instruction::Invokestatic(non_null));
+    }
+
+    /// Pushes a nullable `Option` built from a variant and its payload
+    pub(crate) fn load_nullable_aggregate(
+        &mut self,
+        variant_index: VariantIdx,
+        payload: Option<&Operand<'tcx>>,
+    ) {
+        match payload {
+            Some(operand) if !is_none(variant_index) => self.load_operand(operand),
+            _ => self
+                .method_bytecode_instructions
+                .push(Instruction::Aconst_null),
+        }
+    }
+
+    /// Sets the discriminant of the nullable `Option` in `local`. `Some` needs nothing, as its
+    /// payload has been stored in the local already.
+    pub(crate) fn store_nullable_discriminant(&mut self, local: Local, variant_index: VariantIdx) {
+        if is_none(variant_index) {
+            self.method_bytecode_instructions
+                .push(Instruction::Aconst_null);
+            self.store_local(local);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! `--emit=obj` as the crate's class files.
+
+use rustc_session::Session;
+use rustc_session::config::{OutFileName, OutputFilenames, OutputType};
+use std::path::Path;
+use std::{fs, io};
+
+/// Writes the classes, by internal class name, to the `--emit=obj` output
+pub(crate) fn emit_objects(sess: &Session, classes: &[(&str, &[u8])], outputs: &OutputFilenames) {
+    let path = match outputs.path(OutputType::Object) {
+        OutFileName::Stdout => {
+            sess.dcx().err(
+                "class files cannot be written to the standard output, give `--emit=obj` a path",
+            );
+            return;
+        }
+        OutFileName::Real(path) => path,
+    };
+    let result = match classes {
+        [(_, bytecode)] => fs::write(&path, bytecode),
+        _ => write_class_tree(&path.with_extension(""), classes),
+    };
+    if let Err(err) = result {
+        sess.dcx().err(format!(
+            "could not write the class files of `--emit=obj`: {err}"
+        ));
+    }
+}
+
+/// Writes the classes under the directory, each in the directories of its package
+fn write_class_tree(directory: &Path, classes: &[(&str, &[u8])]) -> io::Result<()> {
+    for (class_name, bytecode) in classes {
+        let class_path = directory.join(format!("{class_name}.class"));
+        if let Some(parent) = class_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&class_path, bytecode)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! The optimizations each `-C opt-level` enables.
+
+use crate::options;
+use rustc_session::Session;
+use rustc_session::config::OptLevel;
+
+/// The bytecode optimizations enabled for a crate
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Optimizations {
+    pub(crate) peephole: bool,
+    /// Removing instructions no path reaches
+    pub(crate) dead_code: bool,
+    /// Inlining crate functions whose code is a few stack operations on their arguments
+    pub(crate) inline: bool,
+}
+
+impl Optimizations {
+    /// The optimizations of the session's `-C opt-level`
+    pub(crate) fn of(sess: &Session) -> Self {
+        let optimizations = match sess.opts.optimize {
+            OptLevel::No => Optimizations::default(),
+            OptLevel::Less | OptLevel::Size | OptLevel::SizeMin => Optimizations {
+                peephole: true,
+                ..Optimizations::default()
+            },
+            OptLevel::More | OptLevel::Aggressive => Optimizations {
+                peephole: true,
+                dead_code: true,
+                inline: true,
+            },
+        };
+        Optimizations {
+            inline: optimizations.inline && !options::get().hot_swap,
+            ..optimizations
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! The options of the backend, given as `-C llvm-args`.
+
+use ristretto_classfile::Version;
+use rustc_session::Session;
+use std::sync::{LazyLock, RwLock};
+
+/// Every option, with the values it takes and what it does, as `help` lists them
+const OPTIONS: [(&str, &str, &str); 17] = [
+    (
+        "class-version",
+        "6|7|8|11|17|21",
+        "the Java version of the generated class files (default: 8)",
+    ),
+    (
+        "multi-release",
+        "11|17|21",
+        "the Java version the exported structs and enums also get classes for, under \
+         `META-INF/versions`, see `multi_release` (default: none)",
+    ),
+    (
+        "package",
+        "<name>",
+        "the Java package of the crate's classes, as in `com.example`, which needs \
+         `crate-packages` if the crate has dependencies (default: none)",
+    ),
+    (
+        "crate-packages",
+        "none|name|disambiguated",
+        "whether each crate's classes get a package of their own under `rust`, or the `package`, \
+         named after the crate, and its disambiguator too, see `naming` (default: none)",
+    ),
+    (
+        "bundle-runtime",
+        "yes|no",
+        "whether the runtime classes are generated into the output (default: yes)",
+    ),
+    (
+        "panic-class",
+        "<name>",
+        "the exception class Rust panics are thrown as (default: `rustjvm.Panic`)",
+    ),
+    (
+        "usize-width",
+        "32|64",
+        "whether `usize` and `isize` are `int`s or `long`s (default: 32)",
+    ),
+    (
+        "method-names",
+        "rust|camel-case",
+        "how the methods of the crate's `pub` functions are named, `add_numbers` or `addNumbers` \
+         (default: rust)",
+    ),
+    (
+        "emit-stack-maps",
+        "yes|no",
+        "whether methods get a `StackMapTable`, which only class version 6 can go without \
+         (default: yes)",
+    ),
+    (
+        "strip-unreachable",
+        "yes|no",
+        "whether the functions an executable never calls are left out of its classes (default: \
+         no)",
+    ),
+    (
+        "trace",
+        "yes|no",
+        "whether a `<crate>.trace` of the lowering is written, see `trace` (default: no)",
+    ),
+    (
+        "api-stubs",
+        "none|java|jar",
+        "whether the crate's API is also written as `.java` stubs or as an API JAR, see `stubs` \
+         (default: none)",
+    ),
+    (
+        "maven-group",
+        "<group>",
+        "the Maven group of an executable, which gets a `pom.xml` and `pom.properties` under \
+         `META-INF/maven`, see `maven` (default: none)",
+    ),
+    (
+        "hot-swap",
+        "yes|no",
+        "whether the classes keep their methods across builds, for debuggers to reload them: \
+         every function in the crate class and no inlining (default: no)",
+    ),
+    (
+        "jni",
+        "yes|no",
+        "whether the functions of `extern \"C\"` blocks are called as `native` methods bound \
+         through JNI, see `jni` (default: no)",
+    ),
+    (
+        "support-report",
+        "yes|no",
+        "whether a `<crate>.support.json` of the constructs the backend does not support is \
+         written, instead of reporting them as errors, see `support` (default: no)",
+    ),
+    ("help", "", "lists these options"),
+];
+
+/// The options of the backend
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct Options {
+    /// The Java version of the generated classes, as in 8 or 17
+    pub(crate) class_version: u16,
+    /// The Java version of the variants of the exported classes in a multi-release JAR
+    pub(crate) multi_release: Option<u16>,
+    /// The internal name of the package of the crate's classes, as in `com/example`
+    pub(crate) package: Option<String>,
+    /// How each crate's classes are put in a package of their own, if they are, see `naming`
+    pub(crate) crate_packages: Option<CratePackages>,
+    pub(crate) bundle_runtime: bool,
+    /// The internal name of the class panics are thrown as
+    pub(crate) panic_class: String,
+    /// The width of `usize` and `isize` in bits, 32 or 64
+    pub(crate) usize_width: u8,
+    /// Whether the methods of the crate's API are named in camelCase, see `naming`
+    pub(crate) camel_case_names: bool,
+    pub(crate) emit_stack_maps: bool,
+    /// Whether the free functions of an executable that rustc did not collect are left out
+    pub(crate) strip_unreachable: bool,
+    pub(crate) trace: bool,
+    /// The stubs of the crate's API written next to its output, if any
+    pub(crate) api_stubs: Option<ApiStubs>,
+    /// The Maven group of the executable, as in `com.example`
+    pub(crate) maven_group: Option<String>,
+    /// Whether the classes are generated for HotSwap, see `cgu` and `opt_level`
+    pub(crate) hot_swap: bool,
+    /// Whether the crate's native functions are bound through JNI, see `jni`
+    pub(crate) jni: bool,
+    pub(crate) support_report: bool,
+}
+
+/// The package each crate's classes are put in, see `naming`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CratePackages {
+    /// A package named after the crate, as in `rust/geometry`
+    Name,
+    /// A package named after the crate and its disambiguator, as in
+    /// `rust/geometry/h1a2b3c4d5e6f7a8b`, which tells two versions of a crate apart
+    Disambiguated,
+}
+
+/// The form of the API stubs, see `stubs`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ApiStubs {
+    /// `.java` source files declaring the API, with bodies that throw
+    Java,
+    /// A JAR of the API classes, without their code
+    Jar,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            class_version: 8,
+            multi_release: None,
+            package: None,
+            crate_packages: None,
+            bundle_runtime: true,
+            panic_class: "rustjvm/Panic".to_string(),
+            usize_width: 32,
+            camel_case_names: false,
+            emit_stack_maps: true,
+            strip_unreachable: false,
+            trace: false,
+            api_stubs: None,
+            maven_group: None,
+            hot_swap: false,
+            jni: false,
+            support_report: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the backend arguments, returning `None` for `help`
+    fn parse(args: &[String]) -> Result<Option<Options>, String> {
+        let mut options = Options::default();
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            let name = name.trim_start_matches('-');
+            match name {
+                "class-version" => {
+                    options.class_version = match value {
+                        Some("6") => 6,
+                        Some("7") => 7,
+                        Some("8") => 8,
+                        Some("11") => 11,
+                        Some("17") => 17,
+                        Some("21") => 21,
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "multi-release" => {
+                    options.multi_release = match value {
+                        Some("11") => Some(11),
+                        Some("17") => Some(17),
+                        Some("21") => Some(21),
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "package" => options.package = Some(class_name_value(name, value)?),
+                "crate-packages" => {
+                    options.crate_packages = match value {
+                        Some("none") => None,
+                        Some("name") => Some(CratePackages::Name),
+                        Some("disambiguated") => Some(CratePackages::Disambiguated),
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "bundle-runtime" => options.bundle_runtime = switch_value(name, value)?,
+                "panic-class" => options.panic_class = class_name_value(name, value)?,
+                "usize-width" => {
+                    options.usize_width = match value {
+                        Some("32") => 32,
+                        Some("64") => 64,
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "method-names" => {
+                    options.camel_case_names = match value {
+                        Some("rust") => false,
+                        Some("camel-case") => true,
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "emit-stack-maps" => options.emit_stack_maps = switch_value(name, value)?,
+                "strip-unreachable" => options.strip_unreachable = switch_value(name, value)?,
+                "trace" => options.trace = switch_value(name, value)?,
+                "api-stubs" => {
+                    options.api_stubs = match value {
+                        Some("none") => None,
+                        Some("java") => Some(ApiStubs::Java),
+                        Some("jar") => Some(ApiStubs::Jar),
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+                "maven-group" => {
+                    options.maven_group = Some(class_name_value(name, value)?.replace('/', "."));
+                }
+                "hot-swap" => options.hot_swap = switch_value(name, value)?,
+                "jni" => options.jni = switch_value(name, value)?,
+                "support-report" => options.support_report = switch_value(name, value)?,
+                "help" => return Ok(None),
+                _ => {
+                    return Err(format!(
+                        "unknown backend option `{name}`, `-C llvm-args=help` lists them"
+                    ));
+                }
+            }
+        }
+        if !options.emit_stack_maps && options.class_version > 6 {
+            return Err(format!(
+                "`emit-stack-maps=no` needs `class-version=6`, class version {} requires stack \
+                 maps",
+                options.class_version
+            ));
+        }
+        if let Some(multi_release) = options.multi_release
+            && multi_release <= options.class_version
+        {
+            return Err(format!(
+                "`multi-release={multi_release}` needs an older `class-version`, the classes \
+                 already target Java {}",
+                options.class_version
+            ));
+        }
+        if options.hot_swap && options.strip_unreachable {
+            return Err(
+                "`hot-swap=yes` needs `strip-unreachable=no`, the methods of the functions left \
+                 out would come and go with their calls"
+                    .to_string(),
+            );
+        }
+        Ok(Some(options))
+    }
+
+    /// The class file version of the generated classes
+    pub(crate) fn version(&self) -> Version {
+        class_file_version(self.class_version)
+    }
+
+    /// The descriptor of `usize` and `isize`
+    pub(crate) fn usize_descriptor(&self) -> &'static str {
+        if self.usize_width == 64 { "J" } else { "I" }
+    }
+}
+
+/// The options of the session, defaults until [`init`] parsed them
+static SESSION_OPTIONS: RwLock<Option<&'static Options>> = RwLock::new(None);
+
+static DEFAULT_OPTIONS: LazyLock<Options> = LazyLock::new(Options::default);
+
+/// Parses the session's backend arguments, reporting invalid ones, or lists the options and exits
+/// for `help`
+pub(crate) fn init(sess: &Session) {
+    let options = match Options::parse(&sess.opts.cg.llvm_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            std::process::exit(0);
+        }
+        Err(message) => sess.dcx().fatal(message),
+    };
+    // Leaked, as the options live as long as the session
+    *SESSION_OPTIONS
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Box::leak(Box::new(options)));
+}
+
+/// The class file version of classes for a Java version, such as the `class-version` option or
+/// the one of a multi-release JAR
+pub(crate) fn class_file_version(class_version: u16) -> Version {
+    match class_version {
+        6 => Version::Java6 { minor: 0 },
+        7 => Version::Java7 { minor: 0 },
+        11 => Version::Java11 { minor: 0 },
+        17 => Version::Java17 { minor: 0 },
+        21 => Version::Java21 { minor: 0 },
+        _ => Version::Java8 { minor: 0 },
+    }
+}
+
+/// The options of the session
+pub(crate) fn get() -> &'static Options {
+    SESSION_OPTIONS
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .unwrap_or(&DEFAULT_OPTIONS)
+}
+
+fn print_help() {
+    println!("Options of the JVM backend, given as `-C llvm-args=<option>[=<value>]`:\n");
+    let width = OPTIONS
+        .iter()
+        .map(|(name, values, _)| name.len() + values.len())
+        .max()
+        .unwrap_or_default();
+    for (name, values, description) in OPTIONS {
+        let usage = if values.is_empty() {
+            name.to_string()
+        } else {
+            format!("{name}={values}")
+        };
+        println!("    {usage:<width$}  {description}", width = width + 1);
+    }
+}
+
+fn invalid_value(name: &str, value: Option<&str>) -> String {
+    let values = OPTIONS
+        .iter()
+        .find(|(option, ..)| *option == name)
+        .map_or("", |(_, values, _)| values);
+    match value {
+        Some(value) => {
+            format!("invalid value `{value}` for backend option `{name}`, expected `{values}`")
+        }
+        None => format!("backend option `{name}` needs a value, `{values}`"),
+    }
+}
+
+/// A `yes|no` value, on when it is left out
+fn switch_value(name: &str, value: Option<&str>) -> Result<bool, String> {
+    match value {
+        None | Some("yes" | "y" | "on" | "true") => Ok(true),
+        Some("no" | "n" | "off" | "false") => Ok(false),
+        _ => Err(invalid_value(name, value)),
+    }
+}
+
+/// A Java class or package name, in its internal form with `/`s
+fn class_name_value(name: &str, value: Option<&str>) -> Result<String, String> {
+    let Some(value) = value else {
+        return Err(invalid_value(name, value));
+    };
+    let internal = value.replace('.', "/");
+    let is_identifier = |part: &str| {
+        part.chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+            && part
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    };
+    if !internal.split('/').all(is_identifier) {
+        return Err(format!(
+            "invalid value `{value}` for backend option `{name}`, expected a Java name like \
+             `com.example`"
+        ));
+    }
+    Ok(internal)
+}
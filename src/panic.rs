@@ -0,0 +1,247 @@
+//! Panics, as `rustjvm/Panic` exceptions.
+
+use crate::runtime::{PANIC_BEGIN, RuntimeClass, panic_begin_descriptor};
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, method_descriptor, naming, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{AssertKind, AssertMessage, BasicBlock, Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, TyKind};
+use rustc_span::Span;
+use rustc_span::source_map::Spanned;
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of the panic entry points and of `catch_unwind`, returning `false` for any
+    /// other call
+    pub(crate) fn lower_panic_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+        span: Span,
+    ) -> bool {
+        let lang_items = self.tcx.lang_items();
+        let path = self.tcx.def_path_str(callee);
+        let is_lang_item = |item: Option<DefId>| item == Some(callee);
+        if is_lang_item(lang_items.panic_fn())
+            || is_lang_item(lang_items.panic_nounwind())
+            || is_lang_item(lang_items.panic_fmt())
+            || is_lang_item(lang_items.panic_display())
+            || is_lang_item(lang_items.begin_panic_fn())
+            || path == "core::option::expect_failed"
+        {
+            self.push_location(span);
+            self.load_message(&args[0].node);
+            self.throw_panic();
+        } else if is_lang_item(lang_items.panic_bounds_check_fn()) {
+            self.push_location(span);
+            self.load_bounds_check_message(&args[0].node, &args[1].node);
+            self.throw_panic();
+        } else if path == "core::panicking::panic_explicit" {
+            self.push_location(span);
+            self.load_str_constant("explicit panic");
+            self.throw_panic();
+        } else if path == "core::option::unwrap_failed" {
+            self.push_location(span);
+            self.load_str_constant("called `Option::unwrap()` on a `None` value");
+            self.throw_panic();
+        } else if path == "std::panic::catch_unwind" {
+            return self.lower_catch_unwind(generic_args, destination);
+        } else {
+            return false;
+        }
+        trace!("      Generated bytecode: panic in {path}");
+        true
+    }
+
+    /// Lowers the `Assert` terminator of `block`: continues at `target` if `cond` is `expected`,
+    /// otherwise panics with the message of the failed check
+    pub(crate) fn lower_assert(
+        &mut self,
+        block: BasicBlock,
+        cond: &Operand<'tcx>,
+        expected: bool,
+        msg: &AssertMessage<'tcx>,
+        target: BasicBlock,
+        span: Span,
+    ) {
+        if msg.is_optional_overflow_check() && !self.tcx.sess.overflow_checks() {
+            // Arithmetic wraps without overflow checks, as `rustc_codegen_ssa` skips them too
+            self.jump_to(block, target);
+            return;
+        }
+        self.load_operand(cond);
+        self.branch_to(
+            if expected {
+                Instruction::Ifne(0)
+            } else {
+                Instruction::Ifeq(0)
+            },
+            target,
+        );
+        self.push_location(span);
+        match &**msg {
+            AssertKind::BoundsCheck { len, index } => self.load_bounds_check_message(index, len),
+            AssertKind::Overflow(..)
+            | AssertKind::OverflowNeg(..)
+            | AssertKind::DivisionByZero(..)
+            | AssertKind::RemainderByZero(..)
+            | AssertKind::ResumedAfterReturn(..)
+            | AssertKind::ResumedAfterPanic(..) => self.load_str_constant(msg.description()),
+            _ => self.load_str_constant("assertion failed"),
+        }
+        self.throw_panic();
+        trace!("      Generated bytecode: assert");
+    }
+
+    /// Throws a `java.lang.AssertionError` for code that cannot be reached, unless the code before
+    /// it already threw
+    pub(crate) fn throw_unreachable(&mut self) {
+        if matches!(
+            self.method_bytecode_instructions.last(),
+            Some(Instruction::Athrow)
+        ) {
+            return;
+        }
+        let class_index = self
+            .constant_pool
+            .add_class("java/lang/AssertionError")
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class_index, "<init>", "(Ljava/lang/Object;)V")
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions
+            .extend([Instruction::New(class_index), Instruction::Dup]);
+        self.load_str_constant("entered unreachable code");
+        self.method_bytecode_instructions
+            .extend([Instruction::Invokespecial(constructor), Instruction::Athrow]);
+        trace!("      Generated bytecode: unreachable");
+    }
+
+    /// `catch_unwind(f)` for a function item `f` of this crate
+    fn lower_catch_unwind(
+        &mut self,
+        generic_args: GenericArgsRef<'tcx>,
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let f = generic_args.type_at(0);
+        let TyKind::FnDef(def_id, _) = *f.kind() else {
+            trace!("      Unsupported catch_unwind of: {f:?}");
+            return false;
+        };
+        if !def_id.is_local()
+            || self.tcx.def_kind(def_id) != DefKind::Fn
+            || self
+                .tcx
+                .generics_of(def_id)
+                .requires_monomorphization(self.tcx)
+        {
+            trace!("      Unsupported catch_unwind of: {f:?}");
+            return false;
+        }
+        let class_name = naming::crate_class_name(self.tcx);
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method_ref = self
+            .constant_pool
+            .add_method_ref(
+                class_index,
+                &naming::method_name(self.tcx, def_id),
+                &method_descriptor(self.tcx, def_id, false),
+            )
+            .expect("Could not add method ref to constant pool");
+        let class = RuntimeClass::Panic;
+        self.runtime_classes.insert(class);
+        let lowered = self.try_catch(destination, &class.name(), |this| {
+            this.method_bytecode_instructions
+                .push(Instruction::Invokestatic(method_ref));
+        });
+        if lowered {
+            trace!("      Generated bytecode: std::panic::catch_unwind");
+        }
+        lowered
+    }
+
+    /// Pushes the `file:line:column` of the code that panics, the outermost macro call if the
+    /// panic comes from a macro
+    fn push_location(&mut self, span: Span) {
+        let source_map = self.tcx.sess.source_map();
+        let location = source_map.lookup_char_pos(span.source_callsite().lo());
+        let location = format!(
+            "{}:{}:{}",
+            source_map.filename_for_diagnostics(&location.file.name),
+            location.line,
+            location.col.0 + 1
+        );
+        self.load_str_constant(&location);
+    }
+
+    /// Pushes the message of a panic as a `java.lang.String`
+    fn load_message(&mut self, message: &Operand<'tcx>) {
+        let descriptor = rust_ty_to_jvm_descriptor(message.ty(self.body, self.tcx), self.tcx);
+        self.load_operand(message);
+        self.box_value(&descriptor);
+        self.invoke_static(
+            "java/lang/String",
+            "valueOf",
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+        );
+    }
+
+    /// Pushes `index out of bounds: the len is {len} but the index is {index}`
+    fn load_bounds_check_message(&mut self, index: &Operand<'tcx>, len: &Operand<'tcx>) {
+        let string_builder = self
+            .constant_pool
+            .add_class("java/lang/StringBuilder")
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")
+            .expect("Could not add constructor to constant pool");
+        let append_string = self
+            .constant_pool
+            .add_method_ref(
+                string_builder,
+                "append",
+                "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+            )
+            .expect("Could not add method ref to constant pool");
+        let append_int = self
+            .constant_pool
+            .add_method_ref(string_builder, "append", "(I)Ljava/lang/StringBuilder;")
+            .expect("Could not add method ref to constant pool");
+        let to_string = self
+            .constant_pool
+            .add_method_ref(string_builder, "toString", "()Ljava/lang/String;")
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .extend([Instruction::New(string_builder), Instruction::Dup]);
+        self.load_str_constant("index out of bounds: the len is ");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+        self.load_operand(len);
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(append_int));
+        self.load_str_constant(" but the index is ");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(append_string));
+        self.load_operand(index);
+        self.method_bytecode_instructions.extend([
+            Instruction::Invokevirtual(append_int),
+            Instruction::Invokevirtual(to_string),
+        ]);
+    }
+
+    /// Throws the panic for the location and message on the stack
+    fn throw_panic(&mut self) {
+        let class = RuntimeClass::Panic;
+        self.invoke_static(&class.name(), PANIC_BEGIN, &panic_begin_descriptor());
+        self.method_bytecode_instructions.push(Instruction::Athrow);
+        self.runtime_classes.insert(class);
+    }
+}
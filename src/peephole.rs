@@ -0,0 +1,411 @@
+//! Peephole optimization and dead code elimination of lowered methods, see
+//! [`crate::opt_level`].
+
+use crate::constant_pool::ConstantPool;
+use crate::control_flow;
+use crate::verifier::{self, Value};
+use ristretto_classfile::Constant;
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction};
+
+/// Optimizes the code of a method in place, removing dead code too if `dead_code` is set.
+/// `frames` are the instructions with a stack map frame; returns the new index of every
+/// instruction, and of the end of the code, removed instructions mapping to the instruction
+/// following them, and dead code to `None`.
+pub(crate) fn optimize(
+    instructions: &mut Vec<Instruction>,
+    exception_table: &mut Vec<ExceptionTableEntry>,
+    frames: &[usize],
+    constant_pool: &ConstantPool,
+    dead_code: bool,
+) -> Vec<Option<usize>> {
+    let mut new_indices: Vec<Option<usize>> = (0..=instructions.len()).map(Some).collect();
+    let mut frames = frames.to_vec();
+    loop {
+        let dead = if dead_code {
+            unreachable_instructions(instructions, exception_table)
+        } else {
+            vec![false; instructions.len()]
+        };
+        let is_dead = |index: usize| dead.get(index).copied().unwrap_or(false);
+        frames.retain(|index| !is_dead(*index));
+        // A handler of dead code only covers dead code
+        exception_table.retain(|entry| !is_dead(entry.handler_pc.into()));
+
+        let labels = labels(instructions, exception_table, &frames);
+        let mut removed = dead.clone();
+        let mut changed = dead.contains(&true);
+        changed |= fold_branches(instructions, &mut removed);
+        changed |= forward_constants(instructions, &labels, &removed);
+        changed |= remove_dead_stores(instructions);
+        changed |= remove_dead_pairs(instructions, &labels, &mut removed);
+        changed |= remove_single_use_slots(instructions, &labels, &mut removed);
+        changed |= remove_checkcasts(instructions, &labels, &mut removed, constant_pool);
+        if !changed {
+            return new_indices;
+        }
+
+        let compacted = compact(instructions, exception_table, &removed);
+        for index in &mut new_indices {
+            *index = index
+                .filter(|index| !is_dead(*index))
+                .map(|index| compacted[index]);
+        }
+        for index in &mut frames {
+            *index = compacted[*index];
+        }
+    }
+}
+
+/// Whether each instruction is unreachable, from the start of the method or from the handler of
+/// an exception range covering reachable code
+fn unreachable_instructions(
+    instructions: &[Instruction],
+    exception_table: &[ExceptionTableEntry],
+) -> Vec<bool> {
+    let mut reachable = vec![false; instructions.len()];
+    let mut pending = vec![0];
+    loop {
+        while let Some(index) = pending.pop() {
+            if index >= instructions.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            if let Some(target) = control_flow::branch_target(&instructions[index]) {
+                pending.push(target.into());
+            }
+            if control_flow::falls_through(&instructions[index]) {
+                pending.push(index + 1);
+            }
+        }
+        for entry in exception_table {
+            let handler = usize::from(entry.handler_pc);
+            let range = usize::from(entry.range_pc.start)..usize::from(entry.range_pc.end);
+            if !reachable[handler] && reachable[range].contains(&true) {
+                pending.push(handler);
+            }
+        }
+        if pending.is_empty() {
+            return reachable.into_iter().map(|reachable| !reachable).collect();
+        }
+    }
+}
+
+/// Whether every instruction is a label, plus the end of the code
+fn labels(
+    instructions: &[Instruction],
+    exception_table: &[ExceptionTableEntry],
+    frames: &[usize],
+) -> Vec<bool> {
+    let mut labels = vec![false; instructions.len() + 1];
+    for instruction in instructions {
+        if let Some(target) = control_flow::branch_target(instruction) {
+            labels[usize::from(target)] = true;
+        }
+    }
+    for entry in exception_table {
+        labels[usize::from(entry.range_pc.start)] = true;
+        labels[usize::from(entry.range_pc.end)] = true;
+        labels[usize::from(entry.handler_pc)] = true;
+    }
+    for frame in frames {
+        labels[*frame] = true;
+    }
+    labels
+}
+
+/// Retargets branches to a `goto` to the target of the `goto`, and removes `goto`s to the next
+/// instruction
+fn fold_branches(instructions: &mut [Instruction], removed: &mut [bool]) -> bool {
+    let mut changed = false;
+    for index in 0..instructions.len() {
+        let Some(target) = control_flow::branch_target(&instructions[index]) else {
+            continue;
+        };
+        let mut final_target = target;
+        // Bounded, as `goto`s may loop
+        for _ in 0..instructions.len() {
+            match &instructions[usize::from(final_target)] {
+                Instruction::Goto(next) if *next != final_target => final_target = *next,
+                _ => break,
+            }
+        }
+        if final_target != target {
+            instructions[index] =
+                control_flow::with_branch_target(&instructions[index], final_target);
+            changed = true;
+        }
+        if matches!(instructions[index], Instruction::Goto(_))
+            && usize::from(final_target) == index + 1
+        {
+            removed[index] = true;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Whether an instruction pushes a constant
+fn is_constant(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Aconst_null
+            | Instruction::Iconst_m1
+            | Instruction::Iconst_0
+            | Instruction::Iconst_1
+            | Instruction::Iconst_2
+            | Instruction::Iconst_3
+            | Instruction::Iconst_4
+            | Instruction::Iconst_5
+            | Instruction::Lconst_0
+            | Instruction::Lconst_1
+            | Instruction::Fconst_0
+            | Instruction::Fconst_1
+            | Instruction::Fconst_2
+            | Instruction::Dconst_0
+            | Instruction::Dconst_1
+            | Instruction::Bipush(_)
+            | Instruction::Sipush(_)
+            | Instruction::Ldc(_)
+            | Instruction::Ldc_w(_)
+            | Instruction::Ldc2_w(_)
+    )
+}
+
+/// The type of the value an instruction pushes without any other effect, if it does
+fn pushed_value(instruction: &Instruction) -> Option<Value> {
+    match instruction {
+        // Only whether the value is wide matters to the callers
+        Instruction::Lconst_0 | Instruction::Lconst_1 | Instruction::Ldc2_w(_) => Some(Value::Long),
+        Instruction::Dconst_0 | Instruction::Dconst_1 => Some(Value::Double),
+        Instruction::Aconst_null => Some(Value::Reference),
+        Instruction::Fconst_0 | Instruction::Fconst_1 | Instruction::Fconst_2 => Some(Value::Float),
+        Instruction::Dup => Some(Value::Int),
+        _ if is_constant(instruction) => Some(Value::Int),
+        _ => match verifier::local_access(instruction) {
+            Some((_, value, false)) => Some(value),
+            _ => None,
+        },
+    }
+}
+
+/// Replaces the load in `constant; store n; load n` with the constant
+fn forward_constants(instructions: &mut [Instruction], labels: &[bool], removed: &[bool]) -> bool {
+    let mut changed = false;
+    for index in 2..instructions.len() {
+        if labels[index - 1] || labels[index] || removed[index - 2..=index].contains(&true) {
+            continue;
+        }
+        if !is_constant(&instructions[index - 2]) {
+            continue;
+        }
+        let (Some((stored, _, true)), Some((loaded, _, false))) = (
+            verifier::local_access(&instructions[index - 1]),
+            verifier::local_access(&instructions[index]),
+        ) else {
+            continue;
+        };
+        if stored == loaded {
+            instructions[index] = instructions[index - 2].clone();
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Replaces stores to slots that are never loaded with pops
+fn remove_dead_stores(instructions: &mut [Instruction]) -> bool {
+    let mut loaded_slots = Vec::new();
+    for instruction in instructions.iter() {
+        match instruction {
+            Instruction::Iinc(slot, _) => loaded_slots.push(u16::from(*slot)),
+            Instruction::Iinc_w(slot, _) => loaded_slots.push(*slot),
+            _ => {
+                if let Some((slot, _, false)) = verifier::local_access(instruction) {
+                    loaded_slots.push(slot);
+                }
+            }
+        }
+    }
+
+    let mut changed = false;
+    for instruction in instructions.iter_mut() {
+        if let Some((slot, value, true)) = verifier::local_access(instruction)
+            && !loaded_slots.contains(&slot)
+        {
+            *instruction = if value.is_wide() {
+                Instruction::Pop2
+            } else {
+                Instruction::Pop
+            };
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Removes values pushed and popped right away, and values loaded and stored back to their slot
+fn remove_dead_pairs(instructions: &[Instruction], labels: &[bool], removed: &mut [bool]) -> bool {
+    let mut changed = false;
+    let mut index = 1;
+    while index < instructions.len() {
+        if labels[index] || removed[index - 1] || removed[index] {
+            index += 1;
+            continue;
+        }
+        let dead = match (&instructions[index - 1], &instructions[index]) {
+            (pushed, Instruction::Pop) => {
+                pushed_value(pushed).is_some_and(|value| !value.is_wide())
+            }
+            (pushed, Instruction::Pop2) => pushed_value(pushed).is_some_and(Value::is_wide),
+            (load, store) => matches!(
+                (verifier::local_access(load), verifier::local_access(store)),
+                (Some((loaded, loaded_value, false)), Some((stored, stored_value, true)))
+                    if loaded == stored && loaded_value == stored_value
+            ),
+        };
+        if dead {
+            removed[index - 1] = true;
+            removed[index] = true;
+            changed = true;
+            index += 2;
+        } else {
+            index += 1;
+        }
+    }
+    changed
+}
+
+/// Removes `store n; load n` when the slot is stored and loaded nowhere else
+fn remove_single_use_slots(
+    instructions: &[Instruction],
+    labels: &[bool],
+    removed: &mut [bool],
+) -> bool {
+    let mut uses = rustc_data_structures::fx::FxHashMap::<u16, usize>::default();
+    for instruction in instructions {
+        let slot = match instruction {
+            Instruction::Iinc(slot, _) => u16::from(*slot),
+            Instruction::Iinc_w(slot, _) => *slot,
+            _ => match verifier::local_access(instruction) {
+                Some((slot, _, _)) => slot,
+                None => continue,
+            },
+        };
+        *uses.entry(slot).or_default() += 1;
+    }
+
+    let mut changed = false;
+    for index in 1..instructions.len() {
+        if labels[index] || removed[index - 1] || removed[index] {
+            continue;
+        }
+        if let (Some((stored, _, true)), Some((loaded, _, false))) = (
+            verifier::local_access(&instructions[index - 1]),
+            verifier::local_access(&instructions[index]),
+        ) && stored == loaded
+            && uses.get(&stored) == Some(&2)
+        {
+            removed[index - 1] = true;
+            removed[index] = true;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Removes `checkcast`s of values already known to have the type
+fn remove_checkcasts(
+    instructions: &[Instruction],
+    labels: &[bool],
+    removed: &mut [bool],
+    constant_pool: &ConstantPool,
+) -> bool {
+    let mut changed = false;
+    for index in 1..instructions.len() {
+        let Instruction::Checkcast(class) = &instructions[index] else {
+            continue;
+        };
+        if labels[index] || removed[index - 1] {
+            continue;
+        }
+        let redundant = match &instructions[index - 1] {
+            Instruction::Aconst_null => true,
+            Instruction::Checkcast(previous) => previous == class,
+            Instruction::Getfield(member)
+            | Instruction::Getstatic(member)
+            | Instruction::Invokestatic(member)
+            | Instruction::Invokevirtual(member)
+            | Instruction::Invokespecial(member)
+            | Instruction::Invokeinterface(member, _) => {
+                produced_type(constant_pool, *member) == class_descriptor(constant_pool, *class)
+            }
+            _ => false,
+        };
+        if redundant {
+            removed[index] = true;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// The field descriptor of the value a field or method constant produces
+fn produced_type(constant_pool: &ConstantPool, member: u16) -> Option<String> {
+    let descriptor = verifier::member_descriptor(constant_pool, member).ok()?;
+    let produced = descriptor
+        .rsplit_once(')')
+        .map_or(descriptor, |(_, return_descriptor)| return_descriptor);
+    Some(produced.to_string())
+}
+
+/// The field descriptor of the type a class constant names
+fn class_descriptor(constant_pool: &ConstantPool, class: u16) -> Option<String> {
+    let Some(Constant::Class(name_index)) = constant_pool.get(class) else {
+        return None;
+    };
+    let Some(Constant::Utf8(name)) = constant_pool.get(*name_index) else {
+        return None;
+    };
+    // Array classes are named by their descriptor
+    Some(if name.starts_with('[') {
+        name.clone()
+    } else {
+        format!("L{name};")
+    })
+}
+
+/// Drops the removed instructions, retargeting branches and exception table entries, and
+/// returns the new index of every instruction and of the end of the code
+fn compact(
+    instructions: &mut Vec<Instruction>,
+    exception_table: &mut Vec<ExceptionTableEntry>,
+    removed: &[bool],
+) -> Vec<usize> {
+    let mut new_indices = Vec::with_capacity(instructions.len() + 1);
+    let mut next = 0;
+    for is_removed in removed {
+        new_indices.push(next);
+        if !is_removed {
+            next += 1;
+        }
+    }
+    new_indices.push(next);
+    let new_index = |index: u16| new_indices[usize::from(index)] as u16;
+
+    let mut index = 0;
+    instructions.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+    for instruction in instructions.iter_mut() {
+        if let Some(target) = control_flow::branch_target(instruction) {
+            *instruction = control_flow::with_branch_target(instruction, new_index(target));
+        }
+    }
+    exception_table.retain_mut(|entry| {
+        entry.range_pc = new_index(entry.range_pc.start)..new_index(entry.range_pc.end);
+        entry.handler_pc = new_index(entry.handler_pc);
+        !entry.range_pc.is_empty()
+    });
+    new_indices
+}
@@ -0,0 +1,112 @@
+//! Raw pointers, with the semantics of references.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+use rustc_span::sym;
+
+/// The descriptor of a raw pointer to `pointee_ty`, other than a `*mut` pointer to a primitive
+pub(crate) fn pointer_descriptor<'tcx>(pointee_ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    if let TyKind::Str = pointee_ty.kind() {
+        return "Ljava/lang/String;".to_string();
+    }
+    let pointee_descriptor = rust_ty_to_jvm_descriptor(pointee_ty, tcx);
+    if matches!(pointee_descriptor.as_bytes().first(), Some(b'L' | b'[')) {
+        pointee_descriptor
+    } else {
+        // Primitives are boxed behind `*const` pointers, and pointers to `()` or `c_void` are
+        // opaque objects
+        "Ljava/lang/Object;".to_string()
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers `ptr::null()`, `ptr::null_mut()` and the methods of raw pointers, returning `false`
+    /// for any other call. Methods depending on addresses are reported as errors.
+    pub(crate) fn lower_pointer_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        if tcx.is_diagnostic_item(sym::ptr_null, callee)
+            || tcx.is_diagnostic_item(sym::ptr_null_mut, callee)
+        {
+            self.method_bytecode_instructions
+                .push(Instruction::Aconst_null);
+            self.store_call_result(destination);
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            return true;
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        if tcx.impl_trait_ref(impl_def_id).is_some() {
+            return false;
+        }
+        let self_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        if !matches!(self_ty.kind(), TyKind::RawPtr(..)) {
+            return false;
+        }
+        match tcx.item_name(callee).as_str() {
+            "is_null" => {
+                self.load_operand(&args[0].node);
+                self.invoke_static("java/util/Objects", "isNull", "(Ljava/lang/Object;)Z");
+                self.store_call_result(destination);
+            }
+            "cast" | "cast_mut" | "cast_const" => {
+                let target_ty = destination.ty(self.body, tcx).ty;
+                if !self.is_same_pointer(self_ty, target_ty) {
+                    self.report_pointer_operation("casting", self_ty);
+                    return false;
+                }
+                self.load_operand(&args[0].node);
+                self.store_call_result(destination);
+            }
+            name => {
+                self.report_pointer_operation(&format!("calling `{name}` on"), self_ty);
+                return false;
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        true
+    }
+
+    /// Whether two pointer types have the same JVM representation, so casting between them
+    /// needs no conversion
+    pub(crate) fn is_same_pointer(&self, from_ty: Ty<'tcx>, to_ty: Ty<'tcx>) -> bool {
+        let pointee = |ty: Ty<'tcx>| ty.builtin_deref(true).unwrap_or(ty);
+        rust_ty_to_jvm_descriptor(from_ty, self.tcx) == rust_ty_to_jvm_descriptor(to_ty, self.tcx)
+            && rust_ty_to_jvm_descriptor(pointee(from_ty), self.tcx)
+                == rust_ty_to_jvm_descriptor(pointee(to_ty), self.tcx)
+    }
+
+    /// Reports an operation on a raw pointer that the JVM cannot do, as in `pointer arithmetic
+    /// on` the pointer type
+    pub(crate) fn report_pointer_operation(&mut self, operation: &str, pointer_ty: Ty<'tcx>) {
+        if self.record_unsupported("raw-pointer", format_args!("{operation} `{pointer_ty}`")) {
+            return;
+        }
+        let function = self.tcx.def_path_str(self.instance.def_id());
+        self.tcx
+            .dcx()
+            .struct_span_err(
+                self.span,
+                format!(
+                    "unsupported raw pointer operation in `{function}`: {operation} `{pointer_ty}`"
+                ),
+            )
+            .with_note(
+                "raw pointers are references on the JVM: they can be created, cast to pointers \
+                 to the same type, dereferenced and compared to null, but have no address",
+            )
+            .emit();
+    }
+}
@@ -0,0 +1,214 @@
+//! `Range` and `RangeInclusive` as runtime classes.
+
+use crate::runtime::{RuntimeClass, ValueKind};
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::FieldIdx;
+use rustc_hir::def_id::DefId;
+use rustc_index::IndexVec;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+/// The type of the bounds of a `Range` or `RangeInclusive` of integers, and the runtime class
+/// it is lowered to
+fn range_bounds<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<(RuntimeClass, Ty<'tcx>)> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    let lang_items = tcx.lang_items();
+    let inclusive = if Some(adt_def.did()) == lang_items.range_struct() {
+        false
+    } else if Some(adt_def.did()) == lang_items.range_inclusive_struct() {
+        true
+    } else {
+        return None;
+    };
+    let bound_ty = generic_args.type_at(0);
+    if !bound_ty.is_integral() {
+        return None;
+    }
+    let kind = match ValueKind::of(&rust_ty_to_jvm_descriptor(bound_ty, tcx))? {
+        kind @ (ValueKind::Int | ValueKind::Long) => kind,
+        _ => return None,
+    };
+    let class = if inclusive {
+        RuntimeClass::RangeInclusive(kind)
+    } else {
+        RuntimeClass::Range(kind)
+    };
+    Some((class, bound_ty))
+}
+
+/// The runtime class a `Range` or `RangeInclusive` of integers is lowered to
+pub(crate) fn runtime_class<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<RuntimeClass> {
+    range_bounds(ty, tcx).map(|(class, _)| class)
+}
+
+/// The kind of the bounds of a range class
+fn bound_kind(class: RuntimeClass) -> ValueKind {
+    match class {
+        RuntimeClass::Range(kind) | RuntimeClass::RangeInclusive(kind) => kind,
+        _ => unreachable!("not a range class: {class:?}"),
+    }
+}
+
+/// The descriptor of the `start` and `end` fields of a range class
+pub(crate) fn bound_descriptor(class: RuntimeClass) -> &'static str {
+    bound_kind(class).descriptor()
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes a new range with the bounds in `operands`, `start` first
+    pub(crate) fn load_range_aggregate(
+        &mut self,
+        class: RuntimeClass,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        let operands: Vec<_> = operands.iter().collect();
+        self.new_range(class, &operands);
+    }
+
+    /// Lowers calls of range methods other than `next()`, returning `false` for any other call
+    pub(crate) fn lower_range_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let name = tcx.item_name(callee);
+        let range_ty = if tcx.trait_of_item(callee).is_some() {
+            // `ExactSizeIterator::len`, `next()` is lowered with the other iterators
+            if name.as_str() != "len" {
+                return false;
+            }
+            generic_args.type_at(0)
+        } else if let Some(impl_def_id) = tcx.impl_of_method(callee) {
+            tcx.type_of(impl_def_id).instantiate(tcx, generic_args)
+        } else {
+            return false;
+        };
+        let Some((class, bound_ty)) = range_bounds(range_ty, tcx) else {
+            return false;
+        };
+        let bound_ty_descriptor = rust_ty_to_jvm_descriptor(bound_ty, tcx);
+        match name.as_str() {
+            "new" if matches!(class, RuntimeClass::RangeInclusive(_)) => {
+                self.new_range(class, &[&args[0].node, &args[1].node]);
+            }
+            "contains" => {
+                // The value is behind a reference, so it is boxed
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.cast_from_object(&bound_ty_descriptor);
+                let descriptor = format!("({})Z", bound_descriptor(class));
+                self.invoke_range_method(class, "contains", &descriptor);
+            }
+            "is_empty" => {
+                self.load_operand(&args[0].node);
+                self.invoke_range_method(class, "isEmpty", "()Z");
+            }
+            "len" => {
+                self.load_operand(&args[0].node);
+                self.invoke_range_method(class, "len", "()I");
+                self.int_to_usize();
+            }
+            field @ ("start" | "end") => {
+                // `RangeInclusive::start()` and `end()` return references, which box primitives
+                let field = self.range_field(class, field);
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Getfield(field));
+                self.box_value(&bound_ty_descriptor);
+            }
+            _ => {
+                trace!(
+                    "      Unsupported range method: {}",
+                    tcx.def_path_str(callee)
+                );
+                return false;
+            }
+        }
+        self.runtime_classes.insert(class);
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `next()` on a range: `Some(range.next())` while `range.hasNext()`, otherwise `None`
+    pub(crate) fn lower_range_next(
+        &mut self,
+        class: RuntimeClass,
+        range: &Operand<'tcx>,
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !self.is_option_destination(destination) {
+            trace!("      Unsupported destination for Range::next: {destination:?}");
+            return false;
+        }
+        let descriptor = bound_descriptor(class);
+        self.load_operand(range);
+        self.invoke_range_method(class, "hasNext", "()Z");
+        let exhausted = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Ifeq(0));
+        self.load_operand(range);
+        self.invoke_range_method(class, "next", &format!("(){descriptor}"));
+        self.store_option_result(exhausted, descriptor, destination);
+        self.runtime_classes.insert(class);
+        trace!("      Generated bytecode: Range::next");
+        true
+    }
+
+    /// `new <range class>(start, end)`
+    fn new_range(&mut self, class: RuntimeClass, bounds: &[&Operand<'tcx>]) {
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        let descriptor = bound_descriptor(class);
+        let constructor = self
+            .constant_pool
+            .add_method_ref(
+                class_index,
+                "<init>",
+                &format!("({descriptor}{descriptor})V"),
+            )
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::New(class_index));
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        for bound in bounds {
+            self.load_operand(bound);
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+        self.runtime_classes.insert(class);
+    }
+
+    /// A bound field of a range class
+    fn range_field(&mut self, class: RuntimeClass, name: &str) -> u16 {
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        self.constant_pool
+            .add_field_ref(class_index, name, bound_descriptor(class))
+            .expect("Could not add field ref to constant pool")
+    }
+
+    fn invoke_range_method(&mut self, class: RuntimeClass, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(class.name())
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(method));
+    }
+}
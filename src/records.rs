@@ -0,0 +1,307 @@
+//! Java records for plain-data exported structs.
+
+use crate::constant_pool::ConstantPool;
+use crate::{
+    MethodCode, attributes, boxing, control_flow, create_method, ldc, return_instruction,
+    rust_ty_to_jvm_descriptor, signature,
+};
+use ristretto_classfile::attributes::{Attribute, Instruction, Record, VerificationType};
+use ristretto_classfile::{Method, MethodAccessFlags};
+use rustc_hir::def::{CtorKind, DefKind};
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_span::sym;
+
+/// The superclass of every record class
+pub(crate) const RECORD_CLASS: &str = "java/lang/Record";
+
+/// The first class version with records, as final in Java 16
+const RECORD_CLASS_VERSION: u16 = 16;
+
+/// Whether the exported struct is generated as a record class for the Java version
+pub(crate) fn is_record(tcx: TyCtxt<'_>, def_id: DefId, class_version: u16) -> bool {
+    let variant = tcx.adt_def(def_id).non_enum_variant();
+    class_version >= RECORD_CLASS_VERSION
+        && variant.ctor_kind() != Some(CtorKind::Fn)
+        && variant.fields.iter().all(|field| field.vis.is_public())
+        && !attributes::has_jvm_attr(tcx, def_id, "accessors")
+        && derives_partial_eq(tcx, def_id)
+}
+
+/// Whether the crate derives `PartialEq` for the struct
+fn derives_partial_eq(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    let Some(partial_eq) = tcx.get_diagnostic_item(sym::PartialEq) else {
+        return false;
+    };
+    tcx.hir_crate_items(())
+        .free_items()
+        .map(|item_id| item_id.owner_id.to_def_id())
+        .any(|impl_def_id| {
+            matches!(tcx.def_kind(impl_def_id), DefKind::Impl { of_trait: true })
+                && tcx.trait_id_of_impl(impl_def_id) == Some(partial_eq)
+                && tcx.is_automatically_derived(impl_def_id)
+                && matches!(
+                    tcx.type_of(impl_def_id).instantiate_identity().kind(),
+                    TyKind::Adt(adt_def, _) if adt_def.did() == def_id
+                )
+        })
+}
+
+/// The components of a record, named after the fields of the struct
+pub(crate) fn struct_components(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<(String, Ty<'_>)> {
+    tcx.adt_def(def_id)
+        .non_enum_variant()
+        .fields
+        .iter()
+        .map(|field| {
+            let field_ty = tcx.type_of(field.did).instantiate_identity();
+            (field.name.to_string(), field_ty)
+        })
+        .collect()
+}
+
+/// The `Record` attribute listing the components of a record, each held by the field of its name
+pub(crate) fn record_attribute<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    components: &[(String, Ty<'tcx>)],
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<Attribute> {
+    let mut records = Vec::with_capacity(components.len());
+    for (name, ty) in components {
+        let descriptor = rust_ty_to_jvm_descriptor(*ty, tcx);
+        records.push(Record {
+            name_index: constant_pool.add_utf8(name)?,
+            descriptor_index: constant_pool.add_utf8(&descriptor)?,
+            attributes: signature::signature_attribute(
+                constant_pool,
+                &signature::type_signature(*ty, tcx),
+                &descriptor,
+            )?
+            .into_iter()
+            .collect(),
+        });
+    }
+    Ok(Attribute::Record {
+        name_index: constant_pool.add_utf8("Record")?,
+        records,
+    })
+}
+
+/// The accessors of the record's components, leaving out those a method of the class already
+/// declares, and its `equals`, `hashCode` and, unless it already has one, `toString`
+pub(crate) fn record_methods<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    record_name: &str,
+    components: &[(String, Ty<'tcx>)],
+    this_class: u16,
+    constant_pool: &mut ConstantPool,
+    existing_methods: &[Method],
+) -> ristretto_classfile::Result<Vec<Method>> {
+    let mut fields = Vec::with_capacity(components.len());
+    for (name, ty) in components {
+        let descriptor = rust_ty_to_jvm_descriptor(*ty, tcx);
+        let field_ref = constant_pool.add_field_ref(this_class, name, &descriptor)?;
+        fields.push((name.clone(), descriptor, field_ref));
+    }
+    let declares = |constant_pool: &mut ConstantPool, name: &str| {
+        let name_index = constant_pool.add_utf8(name)?;
+        Ok::<_, ristretto_classfile::Error>(
+            existing_methods
+                .iter()
+                .any(|method| method.name_index == name_index),
+        )
+    };
+
+    let mut methods = Vec::new();
+    for (name, descriptor, field_ref) in &fields {
+        if declares(constant_pool, name)? {
+            continue;
+        }
+        methods.push(create_method(
+            constant_pool,
+            MethodAccessFlags::PUBLIC,
+            name,
+            &format!("(){descriptor}"),
+            &MethodCode::from(vec![
+                Instruction::Aload_0,
+                Instruction::Getfield(*field_ref),
+                return_instruction(descriptor),
+            ]),
+        )?);
+    }
+    methods.push(equals(this_class, constant_pool, &fields)?);
+    methods.push(hash_code(constant_pool, &fields)?);
+    if !declares(constant_pool, "toString")? {
+        methods.push(to_string(record_name, constant_pool, &fields)?);
+    }
+    Ok(methods)
+}
+
+/// `equals(Object)`, comparing every component with `Objects.equals` once the other object is a
+/// record of the same class
+fn equals(
+    this_class: u16,
+    constant_pool: &mut ConstantPool,
+    components: &[(String, String, u16)],
+) -> ristretto_classfile::Result<Method> {
+    let objects = constant_pool.add_class("java/util/Objects")?;
+    let objects_equals = constant_pool.add_method_ref(
+        objects,
+        "equals",
+        "(Ljava/lang/Object;Ljava/lang/Object;)Z",
+    )?;
+
+    // if (this == other) return true;
+    let mut instructions = vec![
+        Instruction::Aload_0,
+        Instruction::Aload_1,
+        Instruction::If_acmpne(0),
+        Instruction::Iconst_1,
+        Instruction::Ireturn,
+    ];
+    let not_same = instructions.len();
+    instructions[2] = Instruction::If_acmpne(not_same as u16);
+    // if (!(other instanceof This)) return false;
+    let mut to_false = vec![instructions.len() + 2];
+    instructions.extend([
+        Instruction::Aload_1,
+        Instruction::Instanceof(this_class),
+        Instruction::Ifeq(0),
+        Instruction::Aload_1,
+        Instruction::Checkcast(this_class),
+        Instruction::Astore_2,
+    ]);
+    // if (!Objects.equals(this.x, other.x)) return false; ...
+    for (_, descriptor, field_ref) in components {
+        instructions.extend([Instruction::Aload_0, Instruction::Getfield(*field_ref)]);
+        instructions.extend(boxing::box_instructions(constant_pool, descriptor)?);
+        instructions.extend([Instruction::Aload_2, Instruction::Getfield(*field_ref)]);
+        instructions.extend(boxing::box_instructions(constant_pool, descriptor)?);
+        instructions.extend([
+            Instruction::Invokestatic(objects_equals),
+            Instruction::Ifeq(0),
+        ]);
+        to_false.push(instructions.len() - 1);
+    }
+    instructions.extend([Instruction::Iconst_1, Instruction::Ireturn]);
+    let not_equal = instructions.len();
+    for index in to_false {
+        instructions[index] = Instruction::Ifeq(not_equal as u16);
+    }
+    instructions.extend([Instruction::Iconst_0, Instruction::Ireturn]);
+
+    let object = constant_pool.add_class("java/lang/Object")?;
+    let locals = [
+        VerificationType::Object {
+            cpool_index: this_class,
+        },
+        VerificationType::Object {
+            cpool_index: object,
+        },
+    ];
+    let frames = vec![(not_same, Vec::new()), (not_equal, Vec::new())];
+    let stack_map_frames = control_flow::stack_map_frames(&instructions, &locals, frames);
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::FINAL,
+        "equals",
+        "(Ljava/lang/Object;)Z",
+        &MethodCode {
+            instructions,
+            stack_map_frames,
+            ..MethodCode::default()
+        },
+    )
+}
+
+/// `hashCode()`, hashing the components with `Objects.hash`
+fn hash_code(
+    constant_pool: &mut ConstantPool,
+    components: &[(String, String, u16)],
+) -> ristretto_classfile::Result<Method> {
+    let object = constant_pool.add_class("java/lang/Object")?;
+    let objects = constant_pool.add_class("java/util/Objects")?;
+    let hash = constant_pool.add_method_ref(objects, "hash", "([Ljava/lang/Object;)I")?;
+
+    // Objects.hash(new Object[] { x, y, .. }), the constructor taking at most 255 components
+    let sipush = |value: usize| {
+        Instruction::Sipush(i16::try_from(value).expect("a record has at most 255 components"))
+    };
+    let mut instructions = vec![sipush(components.len()), Instruction::Anewarray(object)];
+    for (index, (_, descriptor, field_ref)) in components.iter().enumerate() {
+        instructions.extend([
+            Instruction::Dup,
+            sipush(index),
+            Instruction::Aload_0,
+            Instruction::Getfield(*field_ref),
+        ]);
+        instructions.extend(boxing::box_instructions(constant_pool, descriptor)?);
+        instructions.push(Instruction::Aastore);
+    }
+    instructions.extend([Instruction::Invokestatic(hash), Instruction::Ireturn]);
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::FINAL,
+        "hashCode",
+        "()I",
+        &MethodCode::from(instructions),
+    )
+}
+
+/// `toString()` printing the record as Java records do: `Name[x=1, y=2]`
+fn to_string(
+    name: &str,
+    constant_pool: &mut ConstantPool,
+    components: &[(String, String, u16)],
+) -> ristretto_classfile::Result<Method> {
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let append_object = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/Object;)Ljava/lang/StringBuilder;",
+    )?;
+    let string_builder_to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+
+    // new StringBuilder("Name[").append("x=").append(x).append(", y=")...append("]").toString()
+    let open = constant_pool.add_string(format!("{name}["))?;
+    let mut instructions = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        ldc(open),
+        Instruction::Invokespecial(string_builder_init),
+    ];
+    for (index, (field_name, descriptor, field_ref)) in components.iter().enumerate() {
+        let separator = if index == 0 { "" } else { ", " };
+        let prefix = constant_pool.add_string(format!("{separator}{field_name}="))?;
+        instructions.extend([
+            ldc(prefix),
+            Instruction::Invokevirtual(append),
+            Instruction::Aload_0,
+            Instruction::Getfield(*field_ref),
+        ]);
+        instructions.extend(boxing::box_instructions(constant_pool, descriptor)?);
+        instructions.push(Instruction::Invokevirtual(append_object));
+    }
+    let close = constant_pool.add_string("]")?;
+    instructions.extend([
+        ldc(close),
+        Instruction::Invokevirtual(append),
+        Instruction::Invokevirtual(string_builder_to_string),
+        Instruction::Areturn,
+    ]);
+    create_method(
+        constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "toString",
+        "()Ljava/lang/String;",
+        &MethodCode::from(instructions),
+    )
+}
@@ -0,0 +1,295 @@
+//! References and raw pointers, and how writes through them alias. A borrow of an object is the
+//! object, and a mutable borrow of a primitive a one-element array the borrowed local lives in.
+
+use crate::array::{array_load_instruction, array_store_instruction};
+use crate::{MirToBytecodeVisitor, load_instruction, rust_ty_to_jvm_descriptor, slot_instruction};
+use ristretto_classfile::attributes::Instruction;
+use rustc_data_structures::fx::FxHashSet;
+use rustc_middle::mir::{
+    Body, BorrowKind, Local, Place, ProjectionElem, RETURN_PLACE, RawPtrKind, Rvalue, StatementKind,
+};
+use rustc_middle::ty::{Mutability, Ty, TyCtxt, TyKind};
+
+/// Whether the descriptor is that of a primitive, which a mutable reference holds in a ref cell
+pub(crate) fn is_primitive(descriptor: &str) -> bool {
+    matches!(descriptor, "Z" | "B" | "C" | "S" | "I" | "J" | "F" | "D")
+}
+
+/// The primitive a mutable reference or `*mut` pointer points to, `None` for any other type
+pub(crate) fn ref_cell_pointee<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    match ty.kind() {
+        TyKind::Ref(_, pointee_ty, Mutability::Mut)
+        | TyKind::RawPtr(pointee_ty, Mutability::Mut)
+            if is_primitive(&rust_ty_to_jvm_descriptor(*pointee_ty, tcx)) =>
+        {
+            Some(*pointee_ty)
+        }
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// The primitive locals of the body that are mutably borrowed, which live in ref cells
+    pub(crate) fn ref_cell_locals(&self, body: &Body<'tcx>) -> FxHashSet<Local> {
+        body.basic_blocks
+            .iter()
+            .flat_map(|block| &block.statements)
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(box (
+                    _,
+                    Rvalue::Ref(_, BorrowKind::Mut { .. }, place)
+                    | Rvalue::RawPtr(RawPtrKind::Mut, place),
+                )) if place.projection.is_empty() => Some(place.local),
+                _ => None,
+            })
+            .filter(|local| {
+                !self.checked_locals.contains(local)
+                    && is_primitive(&self.local_descriptors[local.as_usize()])
+            })
+            .collect()
+    }
+
+    /// Hands out the slot holding the ref cell of a local
+    pub(crate) fn allocate_ref_cell(&mut self, next_slot: &mut u16, local: Local) {
+        let descriptor = format!("[{}", self.local_descriptors[local.as_usize()]);
+        if let Some(slot) = self.allocate_slot(next_slot, descriptor) {
+            self.ref_cell_slots.insert(local, slot);
+        }
+    }
+
+    /// Creates the ref cells of the body's locals on entry, copying arguments into theirs
+    pub(crate) fn new_ref_cells(&mut self) {
+        let mut cells: Vec<(Local, u16)> = self
+            .ref_cell_slots
+            .iter()
+            .map(|(local, slot)| (*local, *slot))
+            .collect();
+        cells.sort_unstable();
+        for (local, cell_slot) in cells {
+            let descriptor = self.local_descriptors[local.as_usize()].clone();
+            self.push_new_array(&descriptor, 1);
+            let is_argument = local != RETURN_PLACE && local.as_usize() <= self.body.arg_count;
+            if is_argument && let Some(slot) = self.local_slots[local.as_usize()] {
+                self.method_bytecode_instructions
+                    .extend([Instruction::Dup, Instruction::Iconst_0]);
+                if let Some(load) = load_instruction(&descriptor, slot) {
+                    self.method_bytecode_instructions.push(load);
+                }
+                self.method_bytecode_instructions
+                    .push(array_store_instruction(&descriptor));
+            }
+            self.method_bytecode_instructions.push(slot_instruction(
+                cell_slot,
+                Instruction::Astore,
+                Instruction::Astore_w,
+            ));
+        }
+    }
+
+    /// Whether a local is a mutable reference or `*mut` pointer to a primitive, a ref cell
+    pub(crate) fn is_ref_cell_ref(&self, local: Local) -> bool {
+        ref_cell_pointee(self.body.local_decls[local].ty, self.tcx).is_some()
+    }
+
+    /// The descriptor of the primitive in the ref cell a local refers to
+    pub(crate) fn ref_cell_value_descriptor(&self, local: Local) -> String {
+        let pointee_ty = ref_cell_pointee(self.body.local_decls[local].ty, self.tcx)
+            .expect("ref cell references point to primitives");
+        rust_ty_to_jvm_descriptor(pointee_ty, self.tcx)
+    }
+
+    /// Replaces the ref cell on top of the stack with the value it holds
+    pub(crate) fn load_ref_cell_value(&mut self, descriptor: &str) {
+        self.method_bytecode_instructions
+            .extend([Instruction::Iconst_0, array_load_instruction(descriptor)]);
+    }
+
+    /// Stores the value below the ref cell on top of the stack into the cell
+    pub(crate) fn store_ref_cell_value(&mut self, descriptor: &str) {
+        // value, cell -> cell, 0, value
+        if matches!(descriptor, "J" | "D") {
+            self.method_bytecode_instructions.extend([
+                Instruction::Dup_x2,
+                Instruction::Pop,
+                Instruction::Iconst_0,
+                Instruction::Dup_x2,
+                Instruction::Pop,
+            ]);
+        } else {
+            self.method_bytecode_instructions.extend([
+                Instruction::Swap,
+                Instruction::Iconst_0,
+                Instruction::Swap,
+            ]);
+        }
+        self.method_bytecode_instructions
+            .push(array_store_instruction(descriptor));
+    }
+
+    /// Pushes a mutable borrow of a primitive, the ref cell of a local or the cell a reborrowed
+    /// reference already is. Returns whether it was pushed, which it is not for other places.
+    pub(crate) fn load_ref_cell_borrow(&mut self, place: &Place<'tcx>) -> bool {
+        match &place.projection[..] {
+            [] if self.ref_cell_slots.contains_key(&place.local) => {
+                let cell_slot = self.ref_cell_slots[&place.local];
+                self.method_bytecode_instructions.push(slot_instruction(
+                    cell_slot,
+                    Instruction::Aload,
+                    Instruction::Aload_w,
+                ));
+                true
+            }
+            // `&mut *reference`
+            [ProjectionElem::Deref] if self.is_ref_cell_ref(place.local) => {
+                self.load_local(place.local);
+                true
+            }
+            // A field gets a ref cell holding a copy of it, see `Self::write_back`
+            _ if self.is_write_back_place(place) => {
+                let descriptor =
+                    rust_ty_to_jvm_descriptor(place.ty(self.body, self.tcx).ty, self.tcx);
+                self.push_new_array(&descriptor, 1);
+                self.method_bytecode_instructions
+                    .extend([Instruction::Dup, Instruction::Iconst_0]);
+                self.load_place(place);
+                self.method_bytecode_instructions
+                    .push(array_store_instruction(&descriptor));
+                true
+            }
+            _ => {
+                self.report_mutable_borrow(place);
+                false
+            }
+        }
+    }
+
+    /// Lowers `*reference = rvalue`, storing the value into the ref cell `reference` is
+    pub(crate) fn assign_through_ref_cell(&mut self, reference: Local, rvalue: &Rvalue<'tcx>) {
+        let descriptor = self.ref_cell_value_descriptor(reference);
+        self.load_local(reference);
+        self.method_bytecode_instructions
+            .push(Instruction::Iconst_0);
+        if self.lower_rvalue(rvalue) {
+            self.method_bytecode_instructions
+                .push(array_store_instruction(&descriptor));
+            self.write_back(reference);
+        } else {
+            self.method_bytecode_instructions.push(Instruction::Pop2);
+        }
+    }
+
+    /// Whether a place is `*reference`, the value in the ref cell `reference` is
+    pub(crate) fn is_ref_cell_deref(&self, place: &Place<'tcx>) -> bool {
+        matches!(place.projection[..], [ProjectionElem::Deref]) && self.is_ref_cell_ref(place.local)
+    }
+
+    /// Whether a place is a primitive field that a mutable borrow copies into a ref cell of its
+    /// own: a field of an exported struct, or of an unpacked enum local
+    fn is_write_back_place(&mut self, place: &Place<'tcx>) -> bool {
+        match place.projection[..] {
+            [ProjectionElem::Downcast(..), ProjectionElem::Field(..)] => {
+                self.is_unpacked_enum(place.local)
+            }
+            [ProjectionElem::Field(..)] | [ProjectionElem::Deref, ProjectionElem::Field(..)] => {
+                self.field_ref(place).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// The field whose copy the ref cell `reference` holds, following reborrows and copies of
+    /// the reference, `None` if it is not the ref cell of a field
+    fn written_back_place(&mut self, reference: Local) -> Option<Place<'tcx>> {
+        let body = self.body;
+        let mut assignments = body
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.statements)
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(box (place, rvalue))
+                    if place.local == reference && place.projection.is_empty() =>
+                {
+                    Some(rvalue)
+                }
+                _ => None,
+            });
+        let (Some(rvalue), None) = (assignments.next(), assignments.next()) else {
+            return None;
+        };
+        match rvalue {
+            Rvalue::Ref(_, BorrowKind::Mut { .. }, borrowed)
+            | Rvalue::RawPtr(RawPtrKind::Mut, borrowed) => match borrowed.projection[..] {
+                // `&mut *reference`
+                [ProjectionElem::Deref] => self.written_back_place(borrowed.local),
+                _ if self.is_write_back_place(borrowed) => Some(*borrowed),
+                _ => None,
+            },
+            Rvalue::Use(Operand::Copy(source) | Operand::Move(source))
+                if source.projection.is_empty() =>
+            {
+                self.written_back_place(source.local)
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes the value in the ref cell `reference` back to the field it holds a copy of, if it
+    /// holds one. Borrow checking keeps the field from being used while it is borrowed, so the
+    /// field is up to date whenever it can be used again.
+    pub(crate) fn write_back(&mut self, reference: Local) {
+        let Some(place) = self.written_back_place(reference) else {
+            return;
+        };
+        let descriptor = self.ref_cell_value_descriptor(reference);
+        if let [
+            ProjectionElem::Downcast(_, variant_index),
+            ProjectionElem::Field(field, _),
+        ] = place.projection[..]
+        {
+            self.load_local(reference);
+            self.load_ref_cell_value(&descriptor);
+            self.store_enum_field(place.local, variant_index, field);
+        } else if let Some(field_ref) = self.field_ref(&place) {
+            self.load_local(place.local);
+            self.load_local(reference);
+            self.load_ref_cell_value(&descriptor);
+            self.method_bytecode_instructions
+                .push(Instruction::Putfield(field_ref));
+        }
+    }
+
+    /// Writes back the ref cells passed to a call, whose writes through them are done when it
+    /// returns, see [`Self::write_back`]
+    pub(crate) fn write_back_arguments(&mut self, args: &[Spanned<Operand<'tcx>>]) {
+        for arg in args {
+            if let Operand::Copy(place) | Operand::Move(place) = &arg.node
+                && place.projection.is_empty()
+                && self.is_ref_cell_ref(place.local)
+            {
+                self.write_back(place.local);
+            }
+        }
+    }
+
+    /// Reports a mutable borrow of a primitive that can't be given a ref cell
+    fn report_mutable_borrow(&mut self, place: &Place<'tcx>) {
+        if self.record_unsupported("mutable-borrow", format_args!("{place:?}")) {
+            return;
+        }
+        let function = self.tcx.def_path_str(self.instance.def_id());
+        self.tcx
+            .dcx()
+            .struct_span_err(
+                self.span,
+                format!(
+                    "unsupported mutable borrow in `{function}`: `{place:?}` is a primitive that \
+                     is neither a local nor a field"
+                ),
+            )
+            .with_note(
+                "a mutable reference to a primitive is a ref cell, which locals are kept in and \
+                 fields of exported structs and enums are copied into",
+            )
+            .emit();
+    }
+}
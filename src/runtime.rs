@@ -0,0 +1,2152 @@
+//! Support classes generated into the output alongside the crate's own classes.
+
+use crate::constant_pool::ConstantPool;
+use crate::{MethodCode, control_flow, create_method, ldc, load_instruction, options};
+use ristretto_classfile::attributes::{
+    ArrayType, ExceptionTableEntry, Instruction, StackFrame, VerificationType,
+};
+use ristretto_classfile::{
+    ClassAccessFlags, ClassFile, Field, FieldAccessFlags, FieldType, MethodAccessFlags,
+};
+use rustc_data_structures::fx::FxIndexSet;
+use rustc_target::spec::PanicStrategy;
+
+/// The runtime classes used by the generated code
+pub(crate) type RuntimeClasses = FxIndexSet<RuntimeClass>;
+
+/// The static method of `Panic` starting a panic, taking the location (or `null`) and the message
+pub(crate) const PANIC_BEGIN: &str = "begin";
+
+/// The descriptor of [`PANIC_BEGIN`], returning the panic
+pub(crate) fn panic_begin_descriptor() -> String {
+    format!(
+        "(Ljava/lang/String;Ljava/lang/String;)L{};",
+        RuntimeClass::Panic.name()
+    )
+}
+
+/// The fields of `CoroutineState` and its constructor, taking both
+pub(crate) const COROUTINE_STATE_COMPLETE: &str = "complete";
+pub(crate) const COROUTINE_STATE_VALUE: &str = "value";
+pub(crate) const COROUTINE_STATE_CONSTRUCTOR: &str = "(ZLjava/lang/Object;)V";
+
+/// How a value is held by a runtime class, by its JVM type
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ValueKind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl ValueKind {
+    /// The kind of values with the given field descriptor, `None` for `void`
+    pub(crate) fn of(descriptor: &str) -> Option<ValueKind> {
+        match descriptor.as_bytes().first()? {
+            b'J' => Some(ValueKind::Long),
+            b'F' => Some(ValueKind::Float),
+            b'D' => Some(ValueKind::Double),
+            b'L' | b'[' => Some(ValueKind::Reference),
+            b'V' => None,
+            _ => Some(ValueKind::Int),
+        }
+    }
+
+    /// The descriptor of the field holding the value
+    pub(crate) fn descriptor(self) -> &'static str {
+        match self {
+            ValueKind::Int => "I",
+            ValueKind::Long => "J",
+            ValueKind::Float => "F",
+            ValueKind::Double => "D",
+            ValueKind::Reference => "Ljava/lang/Object;",
+        }
+    }
+
+    fn class_prefix(self) -> &'static str {
+        match self {
+            ValueKind::Int => "Int",
+            ValueKind::Long => "Long",
+            ValueKind::Float => "Float",
+            ValueKind::Double => "Double",
+            ValueKind::Reference => "Object",
+        }
+    }
+}
+
+/// A class of the runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RuntimeClass {
+    /// The exception Rust panics are thrown as
+    Panic,
+    /// A `Cell` of a value of the given kind
+    Cell(ValueKind),
+    /// A `RefCell` of a value of the given kind, which its `Ref`s and `RefMut`s are as well
+    RefCell(ValueKind),
+    /// A `Range` of integers of the given kind
+    Range(ValueKind),
+    /// A `RangeInclusive` of integers of the given kind
+    RangeInclusive(ValueKind),
+    /// A `java.util.Iterator` over the elements of any JVM array
+    ArrayIterator,
+    /// The command line arguments as `env::args()` yields them
+    Env,
+    /// Reading helpers of `std::io`, and the reader of the standard input
+    Io,
+    /// The `Display` and `Debug` formatting of values, and the assembly of formatted strings
+    Fmt,
+    /// What resuming a coroutine gives: whether it completed, and the value it yielded or returned
+    CoroutineState,
+    /// The loader of the native libraries of `extern "C"` blocks, see `jni`
+    NativeLoader,
+}
+
+impl RuntimeClass {
+    /// The internal name of the class
+    pub(crate) fn name(self) -> String {
+        match self {
+            // The one runtime class whose name is an option
+            RuntimeClass::Panic => options::get().panic_class.clone(),
+            RuntimeClass::Cell(kind) => format!("rustjvm/{}Cell", kind.class_prefix()),
+            RuntimeClass::RefCell(kind) => format!("rustjvm/{}RefCell", kind.class_prefix()),
+            RuntimeClass::Range(kind) => format!("rustjvm/{}Range", kind.class_prefix()),
+            RuntimeClass::RangeInclusive(kind) => {
+                format!("rustjvm/{}RangeInclusive", kind.class_prefix())
+            }
+            RuntimeClass::ArrayIterator => "rustjvm/ArrayIterator".to_string(),
+            RuntimeClass::Env => "rustjvm/Env".to_string(),
+            RuntimeClass::Io => "rustjvm/Io".to_string(),
+            RuntimeClass::Fmt => "rustjvm/Fmt".to_string(),
+            RuntimeClass::CoroutineState => "rustjvm/CoroutineState".to_string(),
+            RuntimeClass::NativeLoader => "rustjvm/NativeLoader".to_string(),
+        }
+    }
+
+    /// Every class of the runtime
+    pub(crate) fn all() -> impl Iterator<Item = RuntimeClass> {
+        let kinds = [
+            ValueKind::Int,
+            ValueKind::Long,
+            ValueKind::Float,
+            ValueKind::Double,
+            ValueKind::Reference,
+        ];
+        let holders = kinds.into_iter().flat_map(|kind| {
+            [
+                RuntimeClass::Cell(kind),
+                RuntimeClass::RefCell(kind),
+                RuntimeClass::Range(kind),
+                RuntimeClass::RangeInclusive(kind),
+            ]
+        });
+        [
+            RuntimeClass::Panic,
+            RuntimeClass::ArrayIterator,
+            RuntimeClass::Env,
+            RuntimeClass::Io,
+            RuntimeClass::Fmt,
+            RuntimeClass::CoroutineState,
+            RuntimeClass::NativeLoader,
+        ]
+        .into_iter()
+        .chain(holders)
+    }
+
+    /// The runtime class with the given internal name, if any
+    pub(crate) fn from_name(name: &str) -> Option<RuntimeClass> {
+        RuntimeClass::all().find(|class| class.name() == name)
+    }
+
+    /// The runtime classes this class uses
+    fn dependencies(self) -> Vec<RuntimeClass> {
+        match self {
+            RuntimeClass::RefCell(_) => vec![RuntimeClass::Panic],
+            RuntimeClass::Panic
+            | RuntimeClass::Cell(_)
+            | RuntimeClass::Range(_)
+            | RuntimeClass::RangeInclusive(_)
+            | RuntimeClass::ArrayIterator
+            | RuntimeClass::Env
+            | RuntimeClass::Io
+            | RuntimeClass::Fmt
+            | RuntimeClass::CoroutineState
+            | RuntimeClass::NativeLoader => Vec::new(),
+        }
+    }
+}
+
+/// Generates every runtime class in `classes` and the ones they depend on, returning their names
+/// and bytes
+pub(crate) fn generate_runtime_classes(
+    classes: &RuntimeClasses,
+    panic_strategy: PanicStrategy,
+) -> ristretto_classfile::Result<Vec<(String, Vec<u8>)>> {
+    let mut all_classes = classes.clone();
+    for class in classes {
+        all_classes.extend(class.dependencies());
+    }
+    all_classes
+        .into_iter()
+        .map(|class| Ok((class.name(), generate_runtime_class(class, panic_strategy)?)))
+        .collect()
+}
+
+fn generate_runtime_class(
+    class: RuntimeClass,
+    panic_strategy: PanicStrategy,
+) -> ristretto_classfile::Result<Vec<u8>> {
+    let mut constant_pool = ConstantPool::default();
+    let this_class = constant_pool.add_class(class.name())?;
+    let class_file = match class {
+        RuntimeClass::Panic => panic_class(constant_pool, this_class, panic_strategy)?,
+        RuntimeClass::Cell(kind) => cell_class(constant_pool, this_class, kind, false)?,
+        RuntimeClass::RefCell(kind) => cell_class(constant_pool, this_class, kind, true)?,
+        RuntimeClass::Range(kind) => range_class(constant_pool, this_class, kind, false)?,
+        RuntimeClass::RangeInclusive(kind) => range_class(constant_pool, this_class, kind, true)?,
+        RuntimeClass::ArrayIterator => array_iterator_class(constant_pool, this_class)?,
+        RuntimeClass::Env => env_class(constant_pool, this_class)?,
+        RuntimeClass::Io => io_class(constant_pool, this_class)?,
+        RuntimeClass::Fmt => fmt_class(constant_pool, this_class)?,
+        RuntimeClass::CoroutineState => coroutine_state_class(constant_pool, this_class)?,
+        RuntimeClass::NativeLoader => native_loader_class(constant_pool, this_class)?,
+    };
+    class_file.verify()?;
+
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// `class Panic extends RuntimeException { Panic(String message) }`, whose static `begin(location,
+/// message)` starts a panic: it prints the message to the standard error as Rust's default panic
+/// hook does, and returns the exception to throw, or exits with code 101 right away if panics abort
+fn panic_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+    panic_strategy: PanicStrategy,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/RuntimeException")?;
+    let super_init =
+        constant_pool.add_method_ref(super_class, "<init>", "(Ljava/lang/String;)V")?;
+    let this_init = constant_pool.add_method_ref(this_class, "<init>", "(Ljava/lang/String;)V")?;
+    let constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Aload_1,
+        Instruction::Invokespecial(super_init),
+        Instruction::Return,
+    ];
+
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let system = constant_pool.add_class("java/lang/System")?;
+    let stderr = constant_pool.add_field_ref(system, "err", "Ljava/io/PrintStream;")?;
+    let exit = constant_pool.add_method_ref(system, "exit", "(I)V")?;
+    let print_stream = constant_pool.add_class("java/io/PrintStream")?;
+    let println = constant_pool.add_method_ref(print_stream, "println", "(Ljava/lang/String;)V")?;
+    let header = constant_pool.add_string("thread 'main' panicked")?;
+    let at = constant_pool.add_string(" at ")?;
+    let separator = constant_pool.add_string(":\n")?;
+
+    // header = new StringBuilder("thread 'main' panicked");
+    // if (location != null) header.append(" at ").append(location);
+    let mut begin = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        ldc(header),
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Astore_2,
+        Instruction::Aload_0,
+        Instruction::Ifnull(0),
+        Instruction::Aload_2,
+        ldc(at),
+        Instruction::Invokevirtual(append),
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(append),
+        Instruction::Pop,
+    ];
+    let located = begin.len();
+    patch_branch(&mut begin, 6, located);
+    // System.err.println(header.append(":\n").append(message).toString());
+    begin.extend([
+        Instruction::Getstatic(stderr),
+        Instruction::Aload_2,
+        ldc(separator),
+        Instruction::Invokevirtual(append),
+        Instruction::Aload_1,
+        Instruction::Invokevirtual(append),
+        Instruction::Invokevirtual(to_string),
+        Instruction::Invokevirtual(println),
+    ]);
+    if panic_strategy == PanicStrategy::Abort {
+        begin.extend([Instruction::Bipush(101), Instruction::Invokestatic(exit)]);
+    }
+    // return new Panic(message);
+    begin.extend([
+        Instruction::New(this_class),
+        Instruction::Dup,
+        Instruction::Aload_1,
+        Instruction::Invokespecial(this_init),
+        Instruction::Areturn,
+    ]);
+    let string = constant_pool.add_class("java/lang/String")?;
+    let locals = [
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Object {
+            cpool_index: string_builder,
+        },
+    ];
+    let stack_map_frames =
+        control_flow::stack_map_frames(&begin, &locals, vec![(located, Vec::new())]);
+
+    let methods = vec![
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "<init>",
+            "(Ljava/lang/String;)V",
+            &MethodCode::from(constructor),
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            PANIC_BEGIN,
+            &panic_begin_descriptor(),
+            &MethodCode {
+                instructions: begin,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?,
+    ];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// A cell holding a public `value` field. A `RefCell` also tracks its borrows in `borrows`: the
+/// number of shared borrows, or -1 while mutably borrowed.
+fn cell_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+    kind: ValueKind,
+    ref_cell: bool,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+    let descriptor = kind.descriptor();
+    let value = constant_pool.add_field_ref(this_class, "value", descriptor)?;
+
+    let mut fields = vec![Field {
+        access_flags: FieldAccessFlags::PUBLIC,
+        name_index: constant_pool.add_utf8("value")?,
+        descriptor_index: constant_pool.add_utf8(descriptor)?,
+        field_type: FieldType::parse(descriptor)?,
+        attributes: Vec::new(),
+    }];
+
+    let mut constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Aload_0,
+    ];
+    constructor.extend(load_instruction(descriptor, 1));
+    constructor.push(Instruction::Putfield(value));
+    constructor.push(Instruction::Return);
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        &format!("({descriptor})V"),
+        &MethodCode::from(constructor),
+    )?];
+
+    if ref_cell {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PRIVATE,
+            name_index: constant_pool.add_utf8("borrows")?,
+            descriptor_index: constant_pool.add_utf8("I")?,
+            field_type: FieldType::parse("I")?,
+            attributes: Vec::new(),
+        });
+        let borrows = constant_pool.add_field_ref(this_class, "borrows", "I")?;
+        let panic_class = constant_pool.add_class(RuntimeClass::Panic.name())?;
+        let panic_begin =
+            constant_pool.add_method_ref(panic_class, PANIC_BEGIN, &panic_begin_descriptor())?;
+        let this = [VerificationType::Object {
+            cpool_index: this_class,
+        }];
+        let self_descriptor = format!("()L{};", RuntimeClass::RefCell(kind).name());
+
+        // borrow(): fails while mutably borrowed, otherwise counts one more shared borrow
+        let already_mutably_borrowed = constant_pool.add_string("already mutably borrowed")?;
+        let mut borrow = vec![
+            Instruction::Aload_0,
+            Instruction::Getfield(borrows),
+            Instruction::Ifge(0),
+        ];
+        push_panic(&mut borrow, panic_begin, already_mutably_borrowed);
+        let borrowed = borrow.len();
+        borrow[2] = Instruction::Ifge(borrowed as u16);
+        borrow.extend([
+            Instruction::Aload_0,
+            Instruction::Dup,
+            Instruction::Getfield(borrows),
+            Instruction::Iconst_1,
+            Instruction::Iadd,
+            Instruction::Putfield(borrows),
+            Instruction::Aload_0,
+            Instruction::Areturn,
+        ]);
+        let stack_map_frames =
+            control_flow::stack_map_frames(&borrow, &this, vec![(borrowed, Vec::new())]);
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "borrow",
+            &self_descriptor,
+            &MethodCode {
+                instructions: borrow,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?);
+
+        // borrowMut(): fails while borrowed at all, otherwise marks the cell mutably borrowed
+        let already_borrowed = constant_pool.add_string("already borrowed")?;
+        let mut borrow_mut = vec![
+            Instruction::Aload_0,
+            Instruction::Getfield(borrows),
+            Instruction::Ifeq(0),
+        ];
+        push_panic(&mut borrow_mut, panic_begin, already_borrowed);
+        let borrowed = borrow_mut.len();
+        borrow_mut[2] = Instruction::Ifeq(borrowed as u16);
+        borrow_mut.extend([
+            Instruction::Aload_0,
+            Instruction::Iconst_m1,
+            Instruction::Putfield(borrows),
+            Instruction::Aload_0,
+            Instruction::Areturn,
+        ]);
+        let stack_map_frames =
+            control_flow::stack_map_frames(&borrow_mut, &this, vec![(borrowed, Vec::new())]);
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "borrowMut",
+            &self_descriptor,
+            &MethodCode {
+                instructions: borrow_mut,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?);
+
+        // release(): ends a borrow, called when a `Ref` or `RefMut` is dropped
+        let mut release = vec![
+            Instruction::Aload_0,
+            Instruction::Getfield(borrows),
+            Instruction::Ifge(0),
+            Instruction::Aload_0,
+            Instruction::Iconst_0,
+            Instruction::Putfield(borrows),
+            Instruction::Return,
+        ];
+        let shared = release.len();
+        release[2] = Instruction::Ifge(shared as u16);
+        release.extend([
+            Instruction::Aload_0,
+            Instruction::Dup,
+            Instruction::Getfield(borrows),
+            Instruction::Iconst_1,
+            Instruction::Isub,
+            Instruction::Putfield(borrows),
+            Instruction::Return,
+        ]);
+        let stack_map_frames =
+            control_flow::stack_map_frames(&release, &this, vec![(shared, Vec::new())]);
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "release",
+            "()V",
+            &MethodCode {
+                instructions: release,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?);
+    }
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        fields,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// A range with public `start` and `end` fields, iterated by advancing `start` as in Rust. An
+/// inclusive range also has an `exhausted` flag, set once it yielded `end`.
+fn range_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+    kind: ValueKind,
+    inclusive: bool,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+    let descriptor = kind.descriptor();
+    let is_long = kind == ValueKind::Long;
+    let start = constant_pool.add_field_ref(this_class, "start", descriptor)?;
+    let end = constant_pool.add_field_ref(this_class, "end", descriptor)?;
+    let has_next_ref = constant_pool.add_method_ref(this_class, "hasNext", "()Z")?;
+    let this = VerificationType::Object {
+        cpool_index: this_class,
+    };
+
+    let mut fields = Vec::new();
+    for name in ["start", "end"] {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PUBLIC,
+            name_index: constant_pool.add_utf8(name)?,
+            descriptor_index: constant_pool.add_utf8(descriptor)?,
+            field_type: FieldType::parse(descriptor)?,
+            attributes: Vec::new(),
+        });
+    }
+    let exhausted = if inclusive {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PRIVATE,
+            name_index: constant_pool.add_utf8("exhausted")?,
+            descriptor_index: constant_pool.add_utf8("Z")?,
+            field_type: FieldType::parse("Z")?,
+            attributes: Vec::new(),
+        });
+        Some(constant_pool.add_field_ref(this_class, "exhausted", "Z")?)
+    } else {
+        None
+    };
+
+    let mut constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Aload_0,
+    ];
+    constructor.extend(load_instruction(descriptor, 1));
+    constructor.extend([Instruction::Putfield(start), Instruction::Aload_0]);
+    constructor.extend(load_instruction(descriptor, if is_long { 3 } else { 2 }));
+    constructor.extend([Instruction::Putfield(end), Instruction::Return]);
+
+    // hasNext(): start < end, or start <= end while not exhausted
+    let mut has_next = Vec::new();
+    let mut to_false = Vec::new();
+    if let Some(exhausted) = exhausted {
+        has_next.extend([Instruction::Aload_0, Instruction::Getfield(exhausted)]);
+        to_false.push(has_next.len());
+        has_next.push(Instruction::Ifne(0));
+    }
+    has_next.extend([
+        Instruction::Aload_0,
+        Instruction::Getfield(start),
+        Instruction::Aload_0,
+        Instruction::Getfield(end),
+    ]);
+    has_next.extend(if inclusive {
+        compare(kind, Instruction::If_icmpgt(0), Instruction::Ifgt(0))
+    } else {
+        compare(kind, Instruction::If_icmpge(0), Instruction::Ifge(0))
+    });
+    to_false.push(has_next.len() - 1);
+    has_next.extend([Instruction::Iconst_1, Instruction::Ireturn]);
+    let has_next_false = has_next.len();
+    has_next.extend([Instruction::Iconst_0, Instruction::Ireturn]);
+    for index in to_false {
+        patch_branch(&mut has_next, index, has_next_false);
+    }
+    let has_next_frames = control_flow::stack_map_frames(
+        &has_next,
+        std::slice::from_ref(&this),
+        vec![(has_next_false, Vec::new())],
+    );
+
+    // next(): yields start and advances it; an inclusive range yielding its end is exhausted
+    let (one, add, value_return) = if is_long {
+        (
+            Instruction::Lconst_1,
+            Instruction::Ladd,
+            Instruction::Lreturn,
+        )
+    } else {
+        (
+            Instruction::Iconst_1,
+            Instruction::Iadd,
+            Instruction::Ireturn,
+        )
+    };
+    let mut next = Vec::new();
+    let mut next_frames = Vec::new();
+    if let Some(exhausted) = exhausted {
+        next.extend([
+            Instruction::Aload_0,
+            Instruction::Getfield(start),
+            Instruction::Aload_0,
+            Instruction::Getfield(end),
+        ]);
+        next.extend(compare(
+            kind,
+            Instruction::If_icmplt(0),
+            Instruction::Iflt(0),
+        ));
+        let to_advance = next.len() - 1;
+        next.extend([
+            Instruction::Aload_0,
+            Instruction::Iconst_1,
+            Instruction::Putfield(exhausted),
+            Instruction::Aload_0,
+            Instruction::Getfield(start),
+            value_return.clone(),
+        ]);
+        let advance = next.len();
+        patch_branch(&mut next, to_advance, advance);
+        next_frames.push((advance, Vec::new()));
+    }
+    next.extend([
+        Instruction::Aload_0,
+        Instruction::Getfield(start),
+        Instruction::Aload_0,
+        Instruction::Aload_0,
+        Instruction::Getfield(start),
+        one,
+        add,
+        Instruction::Putfield(start),
+        value_return,
+    ]);
+    let next_frames =
+        control_flow::stack_map_frames(&next, std::slice::from_ref(&this), next_frames);
+
+    // contains(value): start <= value < end, or start <= value <= end while not exhausted
+    let mut contains = vec![Instruction::Aload_0, Instruction::Getfield(start)];
+    contains.extend(load_instruction(descriptor, 1));
+    contains.extend(compare(
+        kind,
+        Instruction::If_icmpgt(0),
+        Instruction::Ifgt(0),
+    ));
+    let mut to_false = vec![contains.len() - 1];
+    contains.extend(load_instruction(descriptor, 1));
+    contains.extend([Instruction::Aload_0, Instruction::Getfield(end)]);
+    contains.extend(if inclusive {
+        compare(kind, Instruction::If_icmpgt(0), Instruction::Ifgt(0))
+    } else {
+        compare(kind, Instruction::If_icmpge(0), Instruction::Ifge(0))
+    });
+    to_false.push(contains.len() - 1);
+    if let Some(exhausted) = exhausted {
+        contains.extend([Instruction::Aload_0, Instruction::Getfield(exhausted)]);
+        to_false.push(contains.len());
+        contains.push(Instruction::Ifne(0));
+    }
+    contains.extend([Instruction::Iconst_1, Instruction::Ireturn]);
+    let contains_false = contains.len();
+    contains.extend([Instruction::Iconst_0, Instruction::Ireturn]);
+    for index in to_false {
+        patch_branch(&mut contains, index, contains_false);
+    }
+    let value_type = if is_long {
+        VerificationType::Long
+    } else {
+        VerificationType::Integer
+    };
+    let contains_frames = control_flow::stack_map_frames(
+        &contains,
+        &[this.clone(), value_type],
+        vec![(contains_false, Vec::new())],
+    );
+
+    // isEmpty(): !hasNext()
+    let is_empty = vec![
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(has_next_ref),
+        Instruction::Iconst_1,
+        Instruction::Ixor,
+        Instruction::Ireturn,
+    ];
+
+    // len(): the number of values left, 0 once the range is empty
+    let mut len = vec![
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(has_next_ref),
+        Instruction::Ifeq(0),
+        Instruction::Aload_0,
+        Instruction::Getfield(end),
+        Instruction::Aload_0,
+        Instruction::Getfield(start),
+    ];
+    if is_long {
+        len.extend([Instruction::Lsub, Instruction::L2i]);
+    } else {
+        len.push(Instruction::Isub);
+    }
+    if inclusive {
+        len.extend([Instruction::Iconst_1, Instruction::Iadd]);
+    }
+    len.push(Instruction::Ireturn);
+    let len_empty = len.len();
+    len.extend([Instruction::Iconst_0, Instruction::Ireturn]);
+    patch_branch(&mut len, 2, len_empty);
+    let len_frames = control_flow::stack_map_frames(
+        &len,
+        std::slice::from_ref(&this),
+        vec![(len_empty, Vec::new())],
+    );
+
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        &format!("({descriptor}{descriptor})V"),
+        &MethodCode::from(constructor),
+    )?];
+    for (name, method_descriptor, instructions, stack_map_frames) in [
+        ("hasNext", "()Z".to_string(), has_next, has_next_frames),
+        ("next", format!("(){descriptor}"), next, next_frames),
+        (
+            "contains",
+            format!("({descriptor})Z"),
+            contains,
+            contains_frames,
+        ),
+        ("isEmpty", "()Z".to_string(), is_empty, Vec::new()),
+        ("len", "()I".to_string(), len, len_frames),
+    ] {
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            name,
+            &method_descriptor,
+            &MethodCode {
+                instructions,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?);
+    }
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        fields,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// Compares the two values of the given kind on top of the stack, with `int_branch` for ints or
+/// `long_branch` after an `lcmp` for longs. The branch comes last, its target is to be patched.
+fn compare(kind: ValueKind, int_branch: Instruction, long_branch: Instruction) -> Vec<Instruction> {
+    if kind == ValueKind::Long {
+        vec![Instruction::Lcmp, long_branch]
+    } else {
+        vec![int_branch]
+    }
+}
+
+/// Points the branch instruction at `index` to `target`
+fn patch_branch(instructions: &mut [Instruction], index: usize, target: usize) {
+    instructions[index] = control_flow::with_branch_target(&instructions[index], target as u16);
+}
+
+/// `class ArrayIterator implements Iterator`, walking any array (primitive arrays included)
+/// through `java.lang.reflect.Array`, which boxes primitive elements
+fn array_iterator_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let iterator = constant_pool.add_class("java/util/Iterator")?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+    let array = constant_pool.add_field_ref(this_class, "array", "Ljava/lang/Object;")?;
+    let index = constant_pool.add_field_ref(this_class, "index", "I")?;
+    let reflect_array = constant_pool.add_class("java/lang/reflect/Array")?;
+    let get_length =
+        constant_pool.add_method_ref(reflect_array, "getLength", "(Ljava/lang/Object;)I")?;
+    let get = constant_pool.add_method_ref(
+        reflect_array,
+        "get",
+        "(Ljava/lang/Object;I)Ljava/lang/Object;",
+    )?;
+
+    let mut fields = Vec::new();
+    for (name, descriptor) in [("array", "Ljava/lang/Object;"), ("index", "I")] {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PRIVATE,
+            name_index: constant_pool.add_utf8(name)?,
+            descriptor_index: constant_pool.add_utf8(descriptor)?,
+            field_type: FieldType::parse(descriptor)?,
+            attributes: Vec::new(),
+        });
+    }
+
+    let constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Aload_0,
+        Instruction::Aload_1,
+        Instruction::Putfield(array),
+        Instruction::Return,
+    ];
+
+    // hasNext(): index < Array.getLength(array)
+    let mut has_next = vec![
+        Instruction::Aload_0,
+        Instruction::Getfield(index),
+        Instruction::Aload_0,
+        Instruction::Getfield(array),
+        Instruction::Invokestatic(get_length),
+        Instruction::If_icmpge(0),
+        Instruction::Iconst_1,
+        Instruction::Ireturn,
+    ];
+    let exhausted = has_next.len();
+    has_next[5] = Instruction::If_icmpge(exhausted as u16);
+    has_next.extend([Instruction::Iconst_0, Instruction::Ireturn]);
+    let this = [VerificationType::Object {
+        cpool_index: this_class,
+    }];
+    let stack_map_frames =
+        control_flow::stack_map_frames(&has_next, &this, vec![(exhausted, Vec::new())]);
+
+    // next(): Array.get(array, index++)
+    let next = vec![
+        Instruction::Aload_0,
+        Instruction::Getfield(array),
+        Instruction::Aload_0,
+        Instruction::Getfield(index),
+        Instruction::Invokestatic(get),
+        Instruction::Aload_0,
+        Instruction::Dup,
+        Instruction::Getfield(index),
+        Instruction::Iconst_1,
+        Instruction::Iadd,
+        Instruction::Putfield(index),
+        Instruction::Areturn,
+    ];
+
+    let methods = vec![
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "<init>",
+            "(Ljava/lang/Object;)V",
+            &MethodCode::from(constructor),
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "hasNext",
+            "()Z",
+            &MethodCode {
+                instructions: has_next,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "next",
+            "()Ljava/lang/Object;",
+            &MethodCode::from(next),
+        )?,
+    ];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        interfaces: vec![iterator],
+        fields,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// `class Env { static Iterator args(String program, String[] args) }`, an iterator over the
+/// program name and the arguments, as `StringBuilder`s
+fn env_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let array_list = constant_pool.add_class("java/util/ArrayList")?;
+    let array_list_init = constant_pool.add_method_ref(array_list, "<init>", "()V")?;
+    let add = constant_pool.add_method_ref(array_list, "add", "(Ljava/lang/Object;)Z")?;
+    let iterator =
+        constant_pool.add_method_ref(array_list, "iterator", "()Ljava/util/Iterator;")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+
+    // list = new ArrayList(); list.add(new StringBuilder(program)); i = 0
+    let mut args = vec![
+        Instruction::New(array_list),
+        Instruction::Dup,
+        Instruction::Invokespecial(array_list_init),
+        Instruction::Astore_2,
+        Instruction::Aload_2,
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        Instruction::Aload_0,
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Invokevirtual(add),
+        Instruction::Pop,
+        Instruction::Iconst_0,
+        Instruction::Istore_3,
+    ];
+    // while (i < args.length) list.add(new StringBuilder(args[i++]))
+    let condition = args.len();
+    args.extend([
+        Instruction::Iload_3,
+        Instruction::Aload_1,
+        Instruction::Arraylength,
+        Instruction::If_icmpge(0),
+        Instruction::Aload_2,
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        Instruction::Aload_1,
+        Instruction::Iload_3,
+        Instruction::Aaload,
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Invokevirtual(add),
+        Instruction::Pop,
+        Instruction::Iinc(3, 1),
+        Instruction::Goto(condition as u16),
+    ]);
+    let end = args.len();
+    patch_branch(&mut args, condition + 3, end);
+    args.extend([
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(iterator),
+        Instruction::Areturn,
+    ]);
+    let locals = [
+        VerificationType::Object {
+            cpool_index: constant_pool.add_class("java/lang/String")?,
+        },
+        VerificationType::Object {
+            cpool_index: constant_pool.add_class("[Ljava/lang/String;")?,
+        },
+        VerificationType::Object {
+            cpool_index: array_list,
+        },
+        VerificationType::Integer,
+    ];
+    let stack_map_frames = control_flow::stack_map_frames(
+        &args,
+        &locals,
+        vec![(condition, Vec::new()), (end, Vec::new())],
+    );
+
+    let methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        "args",
+        "(Ljava/lang/String;[Ljava/lang/String;)Ljava/util/Iterator;",
+        &MethodCode {
+            instructions: args,
+            stack_map_frames,
+            ..Default::default()
+        },
+    )?];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// `class NativeLoader`, whose static `load(String)` loads a native library: from the library path
+/// with `System.loadLibrary`, or else from the `META-INF/native` directory of the class path, as
+/// `java-linker --native-library` bundles it, copied to a temporary file first
+fn native_loader_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let system = constant_pool.add_class("java/lang/System")?;
+    let load_library =
+        constant_pool.add_method_ref(system, "loadLibrary", "(Ljava/lang/String;)V")?;
+    let map_library_name = constant_pool.add_method_ref(
+        system,
+        "mapLibraryName",
+        "(Ljava/lang/String;)Ljava/lang/String;",
+    )?;
+    let load = constant_pool.add_method_ref(system, "load", "(Ljava/lang/String;)V")?;
+    let link_error = constant_pool.add_class("java/lang/UnsatisfiedLinkError")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init =
+        constant_pool.add_method_ref(string_builder, "<init>", "(Ljava/lang/String;)V")?;
+    let append = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let class = constant_pool.add_class("java/lang/Class")?;
+    let get_resource = constant_pool.add_method_ref(
+        class,
+        "getResourceAsStream",
+        "(Ljava/lang/String;)Ljava/io/InputStream;",
+    )?;
+    let input_stream = constant_pool.add_class("java/io/InputStream")?;
+    let close = constant_pool.add_method_ref(input_stream, "close", "()V")?;
+    let files = constant_pool.add_class("java/nio/file/Files")?;
+    let file_attribute = constant_pool.add_class("java/nio/file/attribute/FileAttribute")?;
+    let create_temp_file = constant_pool.add_method_ref(
+        files,
+        "createTempFile",
+        "(Ljava/lang/String;Ljava/lang/String;[Ljava/nio/file/attribute/FileAttribute;)\
+         Ljava/nio/file/Path;",
+    )?;
+    let copy_option = constant_pool.add_class("java/nio/file/CopyOption")?;
+    let standard_copy_option = constant_pool.add_class("java/nio/file/StandardCopyOption")?;
+    let replace_existing = constant_pool.add_field_ref(
+        standard_copy_option,
+        "REPLACE_EXISTING",
+        "Ljava/nio/file/StandardCopyOption;",
+    )?;
+    let copy = constant_pool.add_method_ref(
+        files,
+        "copy",
+        "(Ljava/io/InputStream;Ljava/nio/file/Path;[Ljava/nio/file/CopyOption;)J",
+    )?;
+    let path = constant_pool.add_class("java/nio/file/Path")?;
+    let to_file = constant_pool.add_interface_method_ref(path, "toFile", "()Ljava/io/File;")?;
+    let file = constant_pool.add_class("java/io/File")?;
+    let delete_on_exit = constant_pool.add_method_ref(file, "deleteOnExit", "()V")?;
+    let absolute_path =
+        constant_pool.add_method_ref(file, "getAbsolutePath", "()Ljava/lang/String;")?;
+    let directory = constant_pool.add_string("/META-INF/native/")?;
+
+    // The locals are set before the first frame, which sees them all
+    let mut instructions = vec![
+        Instruction::Aconst_null,
+        Instruction::Astore_1,
+        Instruction::Aconst_null,
+        Instruction::Astore_2,
+        Instruction::Aconst_null,
+        Instruction::Astore_3,
+        Instruction::Aconst_null,
+        Instruction::Astore(4),
+    ];
+    // try { System.loadLibrary(name); return; }
+    let try_start = instructions.len();
+    instructions.extend([
+        Instruction::Aload_0,
+        Instruction::Invokestatic(load_library),
+    ]);
+    let try_end = instructions.len();
+    instructions.push(Instruction::Return);
+    // catch (UnsatisfiedLinkError error) {
+    //     String fileName = System.mapLibraryName(name);
+    //     InputStream input =
+    //         NativeLoader.class.getResourceAsStream("/META-INF/native/" + fileName);
+    //     if (input == null) throw error;
+    let handler = instructions.len();
+    instructions.extend([
+        Instruction::Astore_1,
+        Instruction::Aload_0,
+        Instruction::Invokestatic(map_library_name),
+        Instruction::Astore_2,
+        ldc(this_class),
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        ldc(directory),
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(append),
+        Instruction::Invokevirtual(to_string),
+        Instruction::Invokevirtual(get_resource),
+        Instruction::Astore_3,
+        Instruction::Aload_3,
+        Instruction::Ifnonnull(0),
+        Instruction::Aload_1,
+        Instruction::Athrow,
+    ]);
+    let found = instructions.len();
+    patch_branch(&mut instructions, found - 3, found);
+    //     Path path = Files.createTempFile(null, fileName); File file = path.toFile();
+    //     Files.copy(input, path, REPLACE_EXISTING); input.close();
+    //     file.deleteOnExit(); System.load(file.getAbsolutePath());
+    // }
+    instructions.extend([
+        Instruction::Aconst_null,
+        Instruction::Aload_2,
+        Instruction::Iconst_0,
+        Instruction::Anewarray(file_attribute),
+        Instruction::Invokestatic(create_temp_file),
+        Instruction::Dup,
+        Instruction::Invokeinterface(to_file, 1),
+        Instruction::Astore(4),
+        Instruction::Aload_3,
+        Instruction::Swap,
+        Instruction::Iconst_1,
+        Instruction::Anewarray(copy_option),
+        Instruction::Dup,
+        Instruction::Iconst_0,
+        Instruction::Getstatic(replace_existing),
+        Instruction::Aastore,
+        Instruction::Invokestatic(copy),
+        Instruction::Pop2,
+        Instruction::Aload_3,
+        Instruction::Invokevirtual(close),
+        Instruction::Aload(4),
+        Instruction::Invokevirtual(delete_on_exit),
+        Instruction::Aload(4),
+        Instruction::Invokevirtual(absolute_path),
+        Instruction::Invokestatic(load),
+        Instruction::Return,
+    ]);
+
+    let string = constant_pool.add_class("java/lang/String")?;
+    let locals = [
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Object {
+            cpool_index: link_error,
+        },
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Object {
+            cpool_index: input_stream,
+        },
+        VerificationType::Object { cpool_index: file },
+    ];
+    let stack_map_frames = control_flow::stack_map_frames(
+        &instructions,
+        &locals,
+        vec![
+            (
+                handler,
+                vec![VerificationType::Object {
+                    cpool_index: link_error,
+                }],
+            ),
+            (found, Vec::new()),
+        ],
+    );
+    let exception_table = vec![ExceptionTableEntry {
+        range_pc: try_start as u16..try_end as u16,
+        handler_pc: handler as u16,
+        catch_type: link_error,
+    }];
+
+    let methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+        "load",
+        "(Ljava/lang/String;)V",
+        &MethodCode {
+            instructions,
+            exception_table,
+            stack_map_frames,
+        },
+    )?];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// `class Io`, whose static methods read text into a `StringBuilder` and return the number of
+/// UTF-8 bytes read:
+/// - `readToString(RandomAccessFile, StringBuilder)` reads the rest of a file
+/// - `readLine(BufferedReader, StringBuilder)` reads a line, appending it with a `\n`, and returns
+///   0 at the end of the input
+///
+/// `stdin()` returns the `BufferedReader` over `System.in`, created on first use.
+fn io_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let random_access_file = constant_pool.add_class("java/io/RandomAccessFile")?;
+    let length = constant_pool.add_method_ref(random_access_file, "length", "()J")?;
+    let file_pointer = constant_pool.add_method_ref(random_access_file, "getFilePointer", "()J")?;
+    let read_fully = constant_pool.add_method_ref(random_access_file, "readFully", "([B)V")?;
+    let buffered_reader = constant_pool.add_class("java/io/BufferedReader")?;
+    let read_line =
+        constant_pool.add_method_ref(buffered_reader, "readLine", "()Ljava/lang/String;")?;
+    let string = constant_pool.add_class("java/lang/String")?;
+    let string_init =
+        constant_pool.add_method_ref(string, "<init>", "([BLjava/nio/charset/Charset;)V")?;
+    let get_bytes =
+        constant_pool.add_method_ref(string, "getBytes", "(Ljava/nio/charset/Charset;)[B")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let append_string = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let append_char =
+        constant_pool.add_method_ref(string_builder, "append", "(C)Ljava/lang/StringBuilder;")?;
+    let charsets = constant_pool.add_class("java/nio/charset/StandardCharsets")?;
+    let utf_8 = constant_pool.add_field_ref(charsets, "UTF_8", "Ljava/nio/charset/Charset;")?;
+    let stdin = constant_pool.add_field_ref(this_class, "stdin", "Ljava/io/BufferedReader;")?;
+
+    // bytes = new byte[(int) (file.length() - file.getFilePointer())]; file.readFully(bytes);
+    // builder.append(new String(bytes, UTF_8)); return bytes.length;
+    let read_to_string = vec![
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(length),
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(file_pointer),
+        Instruction::Lsub,
+        Instruction::L2i,
+        Instruction::Newarray(ArrayType::Byte),
+        Instruction::Astore_2,
+        Instruction::Aload_0,
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(read_fully),
+        Instruction::Aload_1,
+        Instruction::New(string),
+        Instruction::Dup,
+        Instruction::Aload_2,
+        Instruction::Getstatic(utf_8),
+        Instruction::Invokespecial(string_init),
+        Instruction::Invokevirtual(append_string),
+        Instruction::Pop,
+        Instruction::Aload_2,
+        Instruction::Arraylength,
+        Instruction::Ireturn,
+    ];
+
+    // line = reader.readLine(); if (line == null) return 0;
+    // builder.append(line).append('\n'); return line.getBytes(UTF_8).length + 1;
+    let mut read_line_code = vec![
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(read_line),
+        Instruction::Astore_2,
+        Instruction::Aload_2,
+        Instruction::Ifnonnull(0),
+        Instruction::Iconst_0,
+        Instruction::Ireturn,
+    ];
+    let found = read_line_code.len();
+    patch_branch(&mut read_line_code, 4, found);
+    read_line_code.extend([
+        Instruction::Aload_1,
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(append_string),
+        Instruction::Bipush(b'\n' as i8),
+        Instruction::Invokevirtual(append_char),
+        Instruction::Pop,
+        Instruction::Aload_2,
+        Instruction::Getstatic(utf_8),
+        Instruction::Invokevirtual(get_bytes),
+        Instruction::Arraylength,
+        Instruction::Iconst_1,
+        Instruction::Iadd,
+        Instruction::Ireturn,
+    ]);
+    let locals = [
+        VerificationType::Object {
+            cpool_index: buffered_reader,
+        },
+        VerificationType::Object {
+            cpool_index: string_builder,
+        },
+        VerificationType::Object {
+            cpool_index: string,
+        },
+    ];
+    let stack_map_frames =
+        control_flow::stack_map_frames(&read_line_code, &locals, vec![(found, Vec::new())]);
+
+    // if (stdin == null) stdin = new BufferedReader(new InputStreamReader(System.in, UTF_8));
+    // return stdin;
+    let input_stream_reader = constant_pool.add_class("java/io/InputStreamReader")?;
+    let input_stream_reader_init = constant_pool.add_method_ref(
+        input_stream_reader,
+        "<init>",
+        "(Ljava/io/InputStream;Ljava/nio/charset/Charset;)V",
+    )?;
+    let buffered_reader_init =
+        constant_pool.add_method_ref(buffered_reader, "<init>", "(Ljava/io/Reader;)V")?;
+    let system = constant_pool.add_class("java/lang/System")?;
+    let system_in = constant_pool.add_field_ref(system, "in", "Ljava/io/InputStream;")?;
+    let mut stdin_code = vec![
+        Instruction::Getstatic(stdin),
+        Instruction::Ifnonnull(0),
+        Instruction::New(buffered_reader),
+        Instruction::Dup,
+        Instruction::New(input_stream_reader),
+        Instruction::Dup,
+        Instruction::Getstatic(system_in),
+        Instruction::Getstatic(utf_8),
+        Instruction::Invokespecial(input_stream_reader_init),
+        Instruction::Invokespecial(buffered_reader_init),
+        Instruction::Putstatic(stdin),
+    ];
+    let created = stdin_code.len();
+    patch_branch(&mut stdin_code, 1, created);
+    stdin_code.extend([Instruction::Getstatic(stdin), Instruction::Areturn]);
+    let stdin_frames =
+        control_flow::stack_map_frames(&stdin_code, &[], vec![(created, Vec::new())]);
+
+    let fields = vec![Field {
+        access_flags: FieldAccessFlags::PRIVATE | FieldAccessFlags::STATIC,
+        name_index: constant_pool.add_utf8("stdin")?,
+        descriptor_index: constant_pool.add_utf8("Ljava/io/BufferedReader;")?,
+        field_type: FieldType::parse("Ljava/io/BufferedReader;")?,
+        attributes: Vec::new(),
+    }];
+
+    let methods = vec![
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            "stdin",
+            "()Ljava/io/BufferedReader;",
+            &MethodCode {
+                instructions: stdin_code,
+                stack_map_frames: stdin_frames,
+                ..Default::default()
+            },
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            "readToString",
+            "(Ljava/io/RandomAccessFile;Ljava/lang/StringBuilder;)I",
+            &MethodCode::from(read_to_string),
+        )?,
+        create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            "readLine",
+            "(Ljava/io/BufferedReader;Ljava/lang/StringBuilder;)I",
+            &MethodCode {
+                instructions: read_line_code,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?,
+    ];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        fields,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// `class Fmt`, whose static methods format values as Rust does:
+/// - `format(pieces, args)` interleaves the literal pieces of a format string with its formatted
+///   arguments
+/// - `display(value)` and `debug(value)` format a (boxed) value with `Display` and `Debug`: floats
+///   never use an exponent for `Display`, and `Debug` quotes and escapes strings and characters.
+///   Anything else is its `toString()`
+/// - `unsigned(value)` and `radix(value, radix, upperCase)` format an integer as unsigned, in the
+///   given radix, its width being that of its boxed class
+fn fmt_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let object = constant_pool.add_class("java/lang/Object")?;
+    let object_to_string =
+        constant_pool.add_method_ref(object, "toString", "()Ljava/lang/String;")?;
+    let string = constant_pool.add_class("java/lang/String")?;
+    let value_of = constant_pool.add_method_ref(
+        string,
+        "valueOf",
+        "(Ljava/lang/Object;)Ljava/lang/String;",
+    )?;
+    let string_array = constant_pool.add_class("[Ljava/lang/String;")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init = constant_pool.add_method_ref(string_builder, "<init>", "()V")?;
+    let append_string = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let builder_to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+    let double_class = constant_pool.add_class("java/lang/Double")?;
+    let float_class = constant_pool.add_class("java/lang/Float")?;
+    let char_sequence = constant_pool.add_class("java/lang/CharSequence")?;
+    let character = constant_pool.add_class("java/lang/Character")?;
+    let float_method = constant_pool.add_method_ref(
+        this_class,
+        "float",
+        "(Ljava/lang/Object;Z)Ljava/lang/String;",
+    )?;
+    let quote = constant_pool.add_method_ref(
+        this_class,
+        "quote",
+        "(Ljava/lang/String;C)Ljava/lang/String;",
+    )?;
+    let radix = constant_pool.add_method_ref(
+        this_class,
+        "radix",
+        "(Ljava/lang/Object;IZ)Ljava/lang/String;",
+    )?;
+
+    // builder = new StringBuilder();
+    // for (i = 0; i < pieces.length; i++) {
+    //     builder.append(pieces[i]); if (i < args.length) builder.append(args[i]);
+    // }
+    // return builder.toString();
+    let mut format = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Astore_2,
+        Instruction::Iconst_0,
+        Instruction::Istore_3,
+    ];
+    let format_loop = format.len();
+    format.extend([
+        Instruction::Iload_3,
+        Instruction::Aload_0,
+        Instruction::Arraylength,
+        Instruction::If_icmpge(0),
+        Instruction::Aload_2,
+        Instruction::Aload_0,
+        Instruction::Iload_3,
+        Instruction::Aaload,
+        Instruction::Invokevirtual(append_string),
+        Instruction::Pop,
+        Instruction::Iload_3,
+        Instruction::Aload_1,
+        Instruction::Arraylength,
+        Instruction::If_icmpge(0),
+        Instruction::Aload_2,
+        Instruction::Aload_1,
+        Instruction::Iload_3,
+        Instruction::Aaload,
+        Instruction::Invokevirtual(append_string),
+        Instruction::Pop,
+    ]);
+    let format_next = format.len();
+    patch_branch(&mut format, format_loop + 13, format_next);
+    format.extend([
+        Instruction::Iinc(3, 1),
+        Instruction::Goto(format_loop as u16),
+    ]);
+    let format_end = format.len();
+    patch_branch(&mut format, format_loop + 3, format_end);
+    format.extend([
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(builder_to_string),
+        Instruction::Areturn,
+    ]);
+    let format_frames = control_flow::stack_map_frames(
+        &format,
+        &[
+            VerificationType::Object {
+                cpool_index: string_array,
+            },
+            VerificationType::Object {
+                cpool_index: string_array,
+            },
+            VerificationType::Object {
+                cpool_index: string_builder,
+            },
+            VerificationType::Integer,
+        ],
+        vec![
+            (format_loop, Vec::new()),
+            (format_next, Vec::new()),
+            (format_end, Vec::new()),
+        ],
+    );
+
+    // if (value instanceof Double || value instanceof Float) return float(value, debug);
+    // ..the other cases
+    let float_checks = [
+        Instruction::Aload_0,
+        Instruction::Instanceof(double_class),
+        Instruction::Ifne(0),
+        Instruction::Aload_0,
+        Instruction::Instanceof(float_class),
+        Instruction::Ifne(0),
+    ];
+    let float_case = |debug: bool| {
+        [
+            Instruction::Aload_0,
+            if debug {
+                Instruction::Iconst_1
+            } else {
+                Instruction::Iconst_0
+            },
+            Instruction::Invokestatic(float_method),
+            Instruction::Areturn,
+        ]
+    };
+    let object_locals = [VerificationType::Object {
+        cpool_index: object,
+    }];
+
+    // return String.valueOf(value);
+    let mut display = float_checks.to_vec();
+    display.extend([
+        Instruction::Aload_0,
+        Instruction::Invokestatic(value_of),
+        Instruction::Areturn,
+    ]);
+    let display_float = display.len();
+    patch_branch(&mut display, 2, display_float);
+    patch_branch(&mut display, 5, display_float);
+    display.extend(float_case(false));
+    let display_frames =
+        control_flow::stack_map_frames(&display, &object_locals, vec![(display_float, Vec::new())]);
+
+    // if (value instanceof CharSequence) return quote(value.toString(), '"');
+    // if (value instanceof Character) return quote(value.toString(), '\'');
+    // return String.valueOf(value);
+    let mut debug = float_checks.to_vec();
+    let mut debug_frames = Vec::new();
+    for (class, delimiter) in [(char_sequence, b'"'), (character, b'\'')] {
+        let check = debug.len();
+        debug.extend([
+            Instruction::Aload_0,
+            Instruction::Instanceof(class),
+            Instruction::Ifeq(0),
+            Instruction::Aload_0,
+            Instruction::Invokevirtual(object_to_string),
+            Instruction::Bipush(delimiter as i8),
+            Instruction::Invokestatic(quote),
+            Instruction::Areturn,
+        ]);
+        let next = debug.len();
+        patch_branch(&mut debug, check + 2, next);
+        debug_frames.push((next, Vec::new()));
+    }
+    debug.extend([
+        Instruction::Aload_0,
+        Instruction::Invokestatic(value_of),
+        Instruction::Areturn,
+    ]);
+    let debug_float = debug.len();
+    patch_branch(&mut debug, 2, debug_float);
+    patch_branch(&mut debug, 5, debug_float);
+    debug.extend(float_case(true));
+    debug_frames.push((debug_float, Vec::new()));
+    let debug_frames = control_flow::stack_map_frames(&debug, &object_locals, debug_frames);
+
+    // return radix(value, 10, false);
+    let unsigned = vec![
+        Instruction::Aload_0,
+        Instruction::Bipush(10),
+        Instruction::Iconst_0,
+        Instruction::Invokestatic(radix),
+        Instruction::Areturn,
+    ];
+
+    let (radix_code, radix_frames) = fmt_radix(&mut constant_pool)?;
+    let (float_code, float_frames) = fmt_float(&mut constant_pool)?;
+    let (quote_code, quote_frames) = fmt_quote(&mut constant_pool)?;
+
+    let mut methods = Vec::new();
+    for (name, descriptor, instructions, stack_map_frames) in [
+        (
+            "format",
+            "([Ljava/lang/String;[Ljava/lang/String;)Ljava/lang/String;",
+            format,
+            format_frames,
+        ),
+        (
+            "display",
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+            display,
+            display_frames,
+        ),
+        (
+            "debug",
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+            debug,
+            debug_frames,
+        ),
+        (
+            "unsigned",
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+            unsigned,
+            Vec::new(),
+        ),
+        (
+            "radix",
+            "(Ljava/lang/Object;IZ)Ljava/lang/String;",
+            radix_code,
+            radix_frames,
+        ),
+        (
+            "float",
+            "(Ljava/lang/Object;Z)Ljava/lang/String;",
+            float_code,
+            float_frames,
+        ),
+        (
+            "quote",
+            "(Ljava/lang/String;C)Ljava/lang/String;",
+            quote_code,
+            quote_frames,
+        ),
+    ] {
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            name,
+            descriptor,
+            &MethodCode {
+                instructions,
+                stack_map_frames,
+                ..Default::default()
+            },
+        )?);
+    }
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        methods,
+        ..Default::default()
+    })
+}
+
+/// `Fmt.radix(value, radix, upperCase)`: a `Long` as unsigned 64 bits, any other `Number` as
+/// unsigned 32 bits, masked to 8 or 16 for a `Byte` or a `Short`
+fn fmt_radix(
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<(Vec<Instruction>, Vec<StackFrame>)> {
+    let long = constant_pool.add_class("java/lang/Long")?;
+    let long_value = constant_pool.add_method_ref(long, "longValue", "()J")?;
+    let long_to_string =
+        constant_pool.add_method_ref(long, "toUnsignedString", "(JI)Ljava/lang/String;")?;
+    let integer = constant_pool.add_class("java/lang/Integer")?;
+    let int_to_string =
+        constant_pool.add_method_ref(integer, "toUnsignedString", "(II)Ljava/lang/String;")?;
+    let number = constant_pool.add_class("java/lang/Number")?;
+    let int_value = constant_pool.add_method_ref(number, "intValue", "()I")?;
+    let byte = constant_pool.add_class("java/lang/Byte")?;
+    let short = constant_pool.add_class("java/lang/Short")?;
+    let short_mask = constant_pool.add_integer(0xFFFF)?;
+    let string = constant_pool.add_class("java/lang/String")?;
+    let to_upper_case =
+        constant_pool.add_method_ref(string, "toUpperCase", "()Ljava/lang/String;")?;
+
+    // text = null; mask = -1;
+    // if (value instanceof Long) { text = Long.toUnsignedString(value.longValue(), radix); }
+    let mut radix = vec![
+        Instruction::Aconst_null,
+        Instruction::Astore_3,
+        Instruction::Iconst_m1,
+        Instruction::Istore(4),
+        Instruction::Aload_0,
+        Instruction::Instanceof(long),
+        Instruction::Ifeq(0),
+        Instruction::Aload_0,
+        Instruction::Checkcast(long),
+        Instruction::Invokevirtual(long_value),
+        Instruction::Iload_1,
+        Instruction::Invokestatic(long_to_string),
+        Instruction::Astore_3,
+        Instruction::Goto(0),
+    ];
+    let mut frames = Vec::new();
+    // else { if (value instanceof Byte) mask = 0xFF; if (value instanceof Short) mask = 0xFFFF;
+    //        text = Integer.toUnsignedString(value.intValue() & mask, radix); }
+    let not_long = radix.len();
+    patch_branch(&mut radix, 6, not_long);
+    for (class, mask) in [(byte, Instruction::Sipush(0xFF)), (short, ldc(short_mask))] {
+        let check = radix.len();
+        frames.push((check, Vec::new()));
+        radix.extend([
+            Instruction::Aload_0,
+            Instruction::Instanceof(class),
+            Instruction::Ifeq(0),
+            mask,
+            Instruction::Istore(4),
+        ]);
+        let next = radix.len();
+        patch_branch(&mut radix, check + 2, next);
+    }
+    frames.push((radix.len(), Vec::new()));
+    radix.extend([
+        Instruction::Aload_0,
+        Instruction::Checkcast(number),
+        Instruction::Invokevirtual(int_value),
+        Instruction::Iload(4),
+        Instruction::Iand,
+        Instruction::Iload_1,
+        Instruction::Invokestatic(int_to_string),
+        Instruction::Astore_3,
+    ]);
+    // if (upperCase) text = text.toUpperCase(); return text;
+    let case = radix.len();
+    frames.push((case, Vec::new()));
+    patch_branch(&mut radix, 13, case);
+    radix.extend([
+        Instruction::Iload_2,
+        Instruction::Ifeq(0),
+        Instruction::Aload_3,
+        Instruction::Invokevirtual(to_upper_case),
+        Instruction::Astore_3,
+    ]);
+    let end = radix.len();
+    frames.push((end, Vec::new()));
+    patch_branch(&mut radix, case + 1, end);
+    radix.extend([Instruction::Aload_3, Instruction::Areturn]);
+
+    let locals = [
+        VerificationType::Object {
+            cpool_index: constant_pool.add_class("java/lang/Object")?,
+        },
+        VerificationType::Integer,
+        VerificationType::Integer,
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Integer,
+    ];
+    let frames = control_flow::stack_map_frames(&radix, &locals, frames);
+    Ok((radix, frames))
+}
+
+/// `Fmt.float(value, debug)` formats a `Double` or a `Float` from its Java representation:
+/// infinities are `inf` and `-inf`, `Display` never uses an exponent and drops a `.0` fraction,
+/// and `Debug` keeps it but switches to an exponent below 1e-4 and from 1e16 on, as Rust does
+fn fmt_float(
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<(Vec<Instruction>, Vec<StackFrame>)> {
+    let object = constant_pool.add_class("java/lang/Object")?;
+    let object_to_string =
+        constant_pool.add_method_ref(object, "toString", "()Ljava/lang/String;")?;
+    let number = constant_pool.add_class("java/lang/Number")?;
+    let double_value = constant_pool.add_method_ref(number, "doubleValue", "()D")?;
+    let math = constant_pool.add_class("java/lang/Math")?;
+    let abs = constant_pool.add_method_ref(math, "abs", "(D)D")?;
+    let double = constant_pool.add_class("java/lang/Double")?;
+    let is_finite = constant_pool.add_method_ref(double, "isFinite", "(D)Z")?;
+    let string = constant_pool.add_class("java/lang/String")?;
+    let replace = constant_pool.add_method_ref(
+        string,
+        "replace",
+        "(Ljava/lang/CharSequence;Ljava/lang/CharSequence;)Ljava/lang/String;",
+    )?;
+    let contains =
+        constant_pool.add_method_ref(string, "contains", "(Ljava/lang/CharSequence;)Z")?;
+    let ends_with = constant_pool.add_method_ref(string, "endsWith", "(Ljava/lang/String;)Z")?;
+    let length = constant_pool.add_method_ref(string, "length", "()I")?;
+    let substring = constant_pool.add_method_ref(string, "substring", "(II)Ljava/lang/String;")?;
+    let index_of = constant_pool.add_method_ref(string, "indexOf", "(I)I")?;
+    let concat =
+        constant_pool.add_method_ref(string, "concat", "(Ljava/lang/String;)Ljava/lang/String;")?;
+    let big_decimal = constant_pool.add_class("java/math/BigDecimal")?;
+    let big_decimal_init =
+        constant_pool.add_method_ref(big_decimal, "<init>", "(Ljava/lang/String;)V")?;
+    let strip_trailing_zeros = constant_pool.add_method_ref(
+        big_decimal,
+        "stripTrailingZeros",
+        "()Ljava/math/BigDecimal;",
+    )?;
+    let to_plain_string =
+        constant_pool.add_method_ref(big_decimal, "toPlainString", "()Ljava/lang/String;")?;
+    let infinity = constant_pool.add_string("Infinity")?;
+    let inf = constant_pool.add_string("inf")?;
+    let point_zero_exponent = constant_pool.add_string(".0E")?;
+    let exponent = constant_pool.add_string("E")?;
+    let lower_exponent = constant_pool.add_string("e")?;
+    let point_zero = constant_pool.add_string(".0")?;
+    let smallest_plain = constant_pool.add_double(1e-4)?;
+    let largest_plain = constant_pool.add_double(1e16)?;
+
+    // text = value.toString(); magnitude = Math.abs(value.doubleValue());
+    // if (!Double.isFinite(magnitude)) return text.replace("Infinity", "inf");
+    let mut float = vec![
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(object_to_string),
+        Instruction::Astore_2,
+        Instruction::Aload_0,
+        Instruction::Checkcast(number),
+        Instruction::Invokevirtual(double_value),
+        Instruction::Invokestatic(abs),
+        Instruction::Dstore_3,
+        Instruction::Dload_3,
+        Instruction::Invokestatic(is_finite),
+        Instruction::Ifne(0),
+        Instruction::Aload_2,
+        ldc(infinity),
+        ldc(inf),
+        Instruction::Invokevirtual(replace),
+        Instruction::Areturn,
+    ];
+    // if (debug && magnitude != 0 && (magnitude < 1e-4 || magnitude >= 1e16)) {
+    //     return text.replace(".0E", "E").replace("E", "e");
+    // }
+    let finite = float.len();
+    patch_branch(&mut float, 10, finite);
+    float.extend([
+        Instruction::Iload_1,
+        Instruction::Ifeq(0),
+        Instruction::Dload_3,
+        Instruction::Dconst_0,
+        Instruction::Dcmpl,
+        Instruction::Ifeq(0),
+        Instruction::Dload_3,
+        Instruction::Ldc2_w(smallest_plain),
+        Instruction::Dcmpg,
+        Instruction::Iflt(0),
+        Instruction::Dload_3,
+        Instruction::Ldc2_w(largest_plain),
+        Instruction::Dcmpl,
+        Instruction::Iflt(0),
+    ]);
+    let exponential = float.len();
+    patch_branch(&mut float, finite + 9, exponential);
+    float.extend([
+        Instruction::Aload_2,
+        ldc(point_zero_exponent),
+        ldc(exponent),
+        Instruction::Invokevirtual(replace),
+        ldc(exponent),
+        ldc(lower_exponent),
+        Instruction::Invokevirtual(replace),
+        Instruction::Areturn,
+    ]);
+    // if (text.contains("E")) text = new BigDecimal(text).stripTrailingZeros().toPlainString();
+    // else if (text.endsWith(".0")) text = text.substring(0, text.length() - 2);
+    let plain = float.len();
+    for branch in [finite + 1, finite + 5, finite + 13] {
+        patch_branch(&mut float, branch, plain);
+    }
+    float.extend([
+        Instruction::Aload_2,
+        ldc(exponent),
+        Instruction::Invokevirtual(contains),
+        Instruction::Ifeq(0),
+        Instruction::New(big_decimal),
+        Instruction::Dup,
+        Instruction::Aload_2,
+        Instruction::Invokespecial(big_decimal_init),
+        Instruction::Invokevirtual(strip_trailing_zeros),
+        Instruction::Invokevirtual(to_plain_string),
+        Instruction::Astore_2,
+        Instruction::Goto(0),
+    ]);
+    let no_exponent = float.len();
+    patch_branch(&mut float, plain + 3, no_exponent);
+    float.extend([
+        Instruction::Aload_2,
+        ldc(point_zero),
+        Instruction::Invokevirtual(ends_with),
+        Instruction::Ifeq(0),
+        Instruction::Aload_2,
+        Instruction::Iconst_0,
+        Instruction::Aload_2,
+        Instruction::Invokevirtual(length),
+        Instruction::Iconst_2,
+        Instruction::Isub,
+        Instruction::Invokevirtual(substring),
+        Instruction::Astore_2,
+    ]);
+    // if (debug && text.indexOf('.') < 0) text = text.concat(".0"); return text;
+    let fraction = float.len();
+    patch_branch(&mut float, plain + 11, fraction);
+    patch_branch(&mut float, no_exponent + 3, fraction);
+    float.extend([
+        Instruction::Iload_1,
+        Instruction::Ifeq(0),
+        Instruction::Aload_2,
+        Instruction::Bipush(b'.' as i8),
+        Instruction::Invokevirtual(index_of),
+        Instruction::Ifge(0),
+        Instruction::Aload_2,
+        ldc(point_zero),
+        Instruction::Invokevirtual(concat),
+        Instruction::Astore_2,
+    ]);
+    let end = float.len();
+    patch_branch(&mut float, fraction + 1, end);
+    patch_branch(&mut float, fraction + 5, end);
+    float.extend([Instruction::Aload_2, Instruction::Areturn]);
+
+    let locals = [
+        VerificationType::Object {
+            cpool_index: object,
+        },
+        VerificationType::Integer,
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Double,
+    ];
+    let frames = control_flow::stack_map_frames(
+        &float,
+        &locals,
+        [finite, exponential, plain, no_exponent, fraction, end]
+            .into_iter()
+            .map(|index| (index, Vec::new()))
+            .collect(),
+    );
+    Ok((float, frames))
+}
+
+/// `Fmt.quote(text, delimiter)` wraps the text in the delimiter, escaping it, backslashes and
+/// the control characters Rust's `Debug` escapes
+fn fmt_quote(
+    constant_pool: &mut ConstantPool,
+) -> ristretto_classfile::Result<(Vec<Instruction>, Vec<StackFrame>)> {
+    let string = constant_pool.add_class("java/lang/String")?;
+    let length = constant_pool.add_method_ref(string, "length", "()I")?;
+    let char_at = constant_pool.add_method_ref(string, "charAt", "(I)C")?;
+    let string_builder = constant_pool.add_class("java/lang/StringBuilder")?;
+    let string_builder_init = constant_pool.add_method_ref(string_builder, "<init>", "()V")?;
+    let append_string = constant_pool.add_method_ref(
+        string_builder,
+        "append",
+        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+    )?;
+    let append_char =
+        constant_pool.add_method_ref(string_builder, "append", "(C)Ljava/lang/StringBuilder;")?;
+    let to_string =
+        constant_pool.add_method_ref(string_builder, "toString", "()Ljava/lang/String;")?;
+
+    // builder = new StringBuilder().append(delimiter); i = 0; c = 0;
+    let mut quote = vec![
+        Instruction::New(string_builder),
+        Instruction::Dup,
+        Instruction::Invokespecial(string_builder_init),
+        Instruction::Astore_2,
+        Instruction::Aload_2,
+        Instruction::Iload_1,
+        Instruction::Invokevirtual(append_char),
+        Instruction::Pop,
+        Instruction::Iconst_0,
+        Instruction::Istore_3,
+        Instruction::Iconst_0,
+        Instruction::Istore(4),
+    ];
+    // while (i < text.length()) { c = text.charAt(i); ..; i++; }
+    let condition = quote.len();
+    quote.extend([
+        Instruction::Iload_3,
+        Instruction::Aload_0,
+        Instruction::Invokevirtual(length),
+        Instruction::If_icmpge(0),
+        Instruction::Aload_0,
+        Instruction::Iload_3,
+        Instruction::Invokevirtual(char_at),
+        Instruction::Istore(4),
+    ]);
+    let mut frames = vec![condition];
+    // if (c == '\n') builder.append("\\n"); else if ..
+    let mut to_next = Vec::new();
+    for (character, escaped) in [
+        (b'\n', "\\n"),
+        (b'\r', "\\r"),
+        (b'\t', "\\t"),
+        (b'\0', "\\0"),
+    ] {
+        let escaped = constant_pool.add_string(escaped)?;
+        let check = quote.len();
+        frames.push(check);
+        quote.extend([
+            Instruction::Iload(4),
+            Instruction::Bipush(character as i8),
+            Instruction::If_icmpne(0),
+            Instruction::Aload_2,
+            ldc(escaped),
+            Instruction::Invokevirtual(append_string),
+            Instruction::Pop,
+            Instruction::Goto(0),
+        ]);
+        to_next.push(quote.len() - 1);
+        let next_check = quote.len();
+        patch_branch(&mut quote, check + 2, next_check);
+    }
+    // else { if (c == '\\' || c == delimiter) builder.append('\\'); builder.append(c); }
+    let check = quote.len();
+    frames.push(check);
+    quote.extend([
+        Instruction::Iload(4),
+        Instruction::Bipush(b'\\' as i8),
+        Instruction::If_icmpeq(0),
+        Instruction::Iload(4),
+        Instruction::Iload_1,
+        Instruction::If_icmpne(0),
+    ]);
+    let escape = quote.len();
+    frames.push(escape);
+    patch_branch(&mut quote, check + 2, escape);
+    quote.extend([
+        Instruction::Aload_2,
+        Instruction::Bipush(b'\\' as i8),
+        Instruction::Invokevirtual(append_char),
+        Instruction::Pop,
+    ]);
+    let append = quote.len();
+    frames.push(append);
+    patch_branch(&mut quote, check + 5, append);
+    quote.extend([
+        Instruction::Aload_2,
+        Instruction::Iload(4),
+        Instruction::Invokevirtual(append_char),
+        Instruction::Pop,
+    ]);
+    let next = quote.len();
+    frames.push(next);
+    for branch in to_next {
+        patch_branch(&mut quote, branch, next);
+    }
+    quote.extend([Instruction::Iinc(3, 1), Instruction::Goto(condition as u16)]);
+    // return builder.append(delimiter).toString();
+    let end = quote.len();
+    frames.push(end);
+    patch_branch(&mut quote, condition + 3, end);
+    quote.extend([
+        Instruction::Aload_2,
+        Instruction::Iload_1,
+        Instruction::Invokevirtual(append_char),
+        Instruction::Invokevirtual(to_string),
+        Instruction::Areturn,
+    ]);
+
+    let locals = [
+        VerificationType::Object {
+            cpool_index: string,
+        },
+        VerificationType::Integer,
+        VerificationType::Object {
+            cpool_index: string_builder,
+        },
+        VerificationType::Integer,
+        VerificationType::Integer,
+    ];
+    let frames = control_flow::stack_map_frames(
+        &quote,
+        &locals,
+        frames
+            .into_iter()
+            .map(|index| (index, Vec::new()))
+            .collect(),
+    );
+    Ok((quote, frames))
+}
+
+/// Appends `throw Panic.begin(null, message)`
+fn push_panic(instructions: &mut Vec<Instruction>, panic_begin: u16, message: u16) {
+    instructions.extend([
+        Instruction::Aconst_null,
+        ldc(message),
+        Instruction::Invokestatic(panic_begin),
+        Instruction::Athrow,
+    ]);
+}
+
+/// `class CoroutineState { final boolean complete; final Object value; }`, with `complete`
+/// being the discriminant of `CoroutineState::Yielded` (0) and `CoroutineState::Complete` (1)
+fn coroutine_state_class(
+    mut constant_pool: ConstantPool,
+    this_class: u16,
+) -> ristretto_classfile::Result<ClassFile> {
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+    let mut fields = Vec::new();
+    let mut constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+    ];
+    for (slot, (name, descriptor)) in [
+        (COROUTINE_STATE_COMPLETE, "Z"),
+        (COROUTINE_STATE_VALUE, "Ljava/lang/Object;"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        fields.push(Field {
+            access_flags: FieldAccessFlags::PUBLIC | FieldAccessFlags::FINAL,
+            name_index: constant_pool.add_utf8(name)?,
+            descriptor_index: constant_pool.add_utf8(descriptor)?,
+            field_type: FieldType::parse(descriptor)?,
+            attributes: Vec::new(),
+        });
+        let field = constant_pool.add_field_ref(this_class, name, descriptor)?;
+        constructor.push(Instruction::Aload_0);
+        constructor.extend(load_instruction(descriptor, slot as u16 + 1));
+        constructor.push(Instruction::Putfield(field));
+    }
+    constructor.push(Instruction::Return);
+    let methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        COROUTINE_STATE_CONSTRUCTOR,
+        &MethodCode::from(constructor),
+    )?];
+
+    Ok(ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        fields,
+        methods,
+        ..Default::default()
+    })
+}
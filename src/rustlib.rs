@@ -0,0 +1,110 @@
+//! The `rustlib-jvm` runtime JAR linked into executables.
+
+use crate::runtime::{self, RuntimeClass};
+use rustc_session::Session;
+use rustc_session::config::CrateType;
+
+/// The name of the runtime JAR
+pub(crate) const RUSTLIB_NAME: &str = "rustlib-jvm";
+
+/// The manifest of the runtime JAR
+const MANIFEST: &str = "Manifest-Version: 1.0\r\nImplementation-Title: rustlib-jvm\r\nCreated-By: \
+                        rustc_codegen_jvm\r\n\r\n";
+
+/// Whether the crate links the runtime JAR, rather than its classes carrying the runtime classes
+/// they use
+pub(crate) fn links_runtime_jar(sess: &Session) -> bool {
+    sess.crate_types().contains(&CrateType::Executable)
+}
+
+/// The bytes of the runtime JAR, with every runtime class
+pub(crate) fn runtime_jar(sess: &Session) -> ristretto_classfile::Result<Vec<u8>> {
+    let classes =
+        runtime::generate_runtime_classes(&RuntimeClass::all().collect(), sess.panic_strategy())?;
+    let mut entries = vec![(
+        "META-INF/MANIFEST.MF".to_string(),
+        MANIFEST.as_bytes().to_vec(),
+    )];
+    entries.extend(
+        classes
+            .into_iter()
+            .map(|(class_name, bytecode)| (format!("{class_name}.class"), bytecode)),
+    );
+    Ok(stored_zip(&entries))
+}
+
+/// A ZIP archive of the given entries, stored without compression
+pub(crate) fn stored_zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    // 1980-01-01 00:00, the earliest time a ZIP entry can have, so that builds are reproducible
+    const TIME: u16 = 0;
+    const DATE: u16 = (1 << 5) | 1;
+
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, data) in entries {
+        let offset = archive.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        // Local file header
+        archive.extend(0x0403_4b50_u32.to_le_bytes());
+        archive.extend(10_u16.to_le_bytes()); // version needed to extract
+        archive.extend(0_u16.to_le_bytes()); // flags
+        archive.extend(0_u16.to_le_bytes()); // stored
+        archive.extend(TIME.to_le_bytes());
+        archive.extend(DATE.to_le_bytes());
+        archive.extend(crc.to_le_bytes());
+        archive.extend(size.to_le_bytes());
+        archive.extend(size.to_le_bytes());
+        archive.extend((name.len() as u16).to_le_bytes());
+        archive.extend(0_u16.to_le_bytes()); // extra field length
+        archive.extend(name.as_bytes());
+        archive.extend(data);
+
+        // Central directory header
+        central_directory.extend(0x0201_4b50_u32.to_le_bytes());
+        central_directory.extend(10_u16.to_le_bytes()); // version made by
+        central_directory.extend(10_u16.to_le_bytes()); // version needed to extract
+        central_directory.extend(0_u16.to_le_bytes()); // flags
+        central_directory.extend(0_u16.to_le_bytes()); // stored
+        central_directory.extend(TIME.to_le_bytes());
+        central_directory.extend(DATE.to_le_bytes());
+        central_directory.extend(crc.to_le_bytes());
+        central_directory.extend(size.to_le_bytes());
+        central_directory.extend(size.to_le_bytes());
+        central_directory.extend((name.len() as u16).to_le_bytes());
+        central_directory.extend([0; 8]); // extra field and comment lengths, disk, attributes
+        central_directory.extend(0_u32.to_le_bytes()); // external attributes
+        central_directory.extend(offset.to_le_bytes());
+        central_directory.extend(name.as_bytes());
+    }
+
+    // End of central directory record
+    let central_directory_offset = archive.len() as u32;
+    let entry_count = entries.len() as u16;
+    archive.extend(&central_directory);
+    archive.extend(0x0605_4b50_u32.to_le_bytes());
+    archive.extend([0; 4]); // disk numbers
+    archive.extend(entry_count.to_le_bytes());
+    archive.extend(entry_count.to_le_bytes());
+    archive.extend((central_directory.len() as u32).to_le_bytes());
+    archive.extend(central_directory_offset.to_le_bytes());
+    archive.extend(0_u16.to_le_bytes()); // comment length
+    archive
+}
+
+/// The CRC-32 of the data, as ZIP entries record it
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,63 @@
+//! Java service providers, registered with `#[jvm::service_provider("<service>")]`.
+
+use crate::{attributes, export, rustlib};
+use rustc_data_structures::fx::FxIndexMap;
+use rustc_middle::ty::TyCtxt;
+
+/// The name of the services JAR
+pub(crate) const SERVICES_NAME: &str = "services";
+
+/// The binary names of the crate's providers of each service, reporting the providers
+/// `ServiceLoader` could not create
+fn service_providers(tcx: TyCtxt<'_>) -> FxIndexMap<String, Vec<String>> {
+    let mut services: FxIndexMap<String, Vec<String>> = FxIndexMap::default();
+    for def_id in export::exported_structs(tcx) {
+        let service_names = attributes::jvm_attr_string_args(tcx, def_id, "service_provider");
+        if service_names.is_empty() {
+            continue;
+        }
+        if !tcx.adt_def(def_id).non_enum_variant().fields.is_empty() {
+            tcx.dcx()
+                .struct_span_err(
+                    tcx.def_span(def_id),
+                    format!("service provider `{}` has fields", tcx.def_path_str(def_id)),
+                )
+                .with_note(
+                    "`ServiceLoader` creates providers with a no-argument constructor, and the \
+                     class of an exported struct has a constructor taking each of its fields",
+                )
+                .emit();
+            continue;
+        }
+        let provider = export::class_name(tcx, def_id).replace('/', ".");
+        for service_name in service_names {
+            services
+                .entry(service_name)
+                .or_default()
+                .push(provider.clone());
+        }
+    }
+    services
+}
+
+/// The bytes of the services JAR, if the crate has service providers
+pub(crate) fn services_jar(tcx: TyCtxt<'_>) -> Option<Vec<u8>> {
+    let services = service_providers(tcx);
+    if services.is_empty() {
+        return None;
+    }
+    let entries: Vec<(String, Vec<u8>)> = services
+        .into_iter()
+        .map(|(service_name, providers)| {
+            let lines: String = providers
+                .iter()
+                .map(|provider| format!("{provider}\n"))
+                .collect();
+            (
+                format!("META-INF/services/{service_name}"),
+                lines.into_bytes(),
+            )
+        })
+        .collect();
+    Some(rustlib::stored_zip(&entries))
+}
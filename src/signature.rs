@@ -0,0 +1,71 @@
+//! Java generic signatures (`Signature` attributes) for exported APIs.
+
+use crate::constant_pool::ConstantPool;
+use crate::{nullable, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Attribute;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_span::sym;
+
+/// The Java type signature of a Rust type, with type arguments where its Java class is generic
+pub(crate) fn type_signature<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    if let Some(payload) = nullable::payload(ty, tcx) {
+        return type_signature(payload, tcx);
+    }
+    match ty.kind() {
+        TyKind::Ref(_, inner_ty, _) if matches!(inner_ty.kind(), TyKind::Adt(..)) => {
+            type_signature(*inner_ty, tcx)
+        }
+        TyKind::Adt(adt_def, args) if tcx.is_diagnostic_item(sym::Vec, adt_def.did()) => {
+            format!(
+                "Ljava/util/ArrayList<{}>;",
+                type_argument_signature(args.type_at(0), tcx)
+            )
+        }
+        _ => rust_ty_to_jvm_descriptor(ty, tcx),
+    }
+}
+
+/// The signature of a type used as a type argument, boxing primitives
+fn type_argument_signature<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    let boxed = match type_signature(ty, tcx).as_str() {
+        "Z" => "Ljava/lang/Boolean;",
+        "C" => "Ljava/lang/Character;",
+        "B" => "Ljava/lang/Byte;",
+        "S" => "Ljava/lang/Short;",
+        "I" => "Ljava/lang/Integer;",
+        "J" => "Ljava/lang/Long;",
+        "F" => "Ljava/lang/Float;",
+        "D" => "Ljava/lang/Double;",
+        "V" => "Ljava/lang/Void;",
+        signature => return signature.to_string(),
+    };
+    boxed.to_string()
+}
+
+/// The Java method signature of a Rust function, see [`crate::method_descriptor`]
+pub(crate) fn method_signature(tcx: TyCtxt<'_>, def_id: DefId, skip_receiver: bool) -> String {
+    let fn_sig = tcx.fn_sig(def_id).skip_binder().skip_binder();
+    let mut signature = String::from("(");
+    for arg_ty in fn_sig.inputs().iter().skip(usize::from(skip_receiver)) {
+        signature.push_str(&type_signature(*arg_ty, tcx));
+    }
+    signature.push(')');
+    signature.push_str(&type_signature(fn_sig.output(), tcx));
+    signature
+}
+
+/// A `Signature` attribute, if the signature says more than the descriptor
+pub(crate) fn signature_attribute(
+    constant_pool: &mut ConstantPool,
+    signature: &str,
+    descriptor: &str,
+) -> ristretto_classfile::Result<Option<Attribute>> {
+    if signature == descriptor {
+        return Ok(None);
+    }
+    Ok(Some(Attribute::Signature {
+        name_index: constant_pool.add_utf8("Signature")?,
+        signature_index: constant_pool.add_utf8(signature)?,
+    }))
+}
@@ -0,0 +1,537 @@
+//! SIMD vectors as JVM arrays of their lanes.
+
+use crate::array::{array_load_instruction, array_store_instruction};
+use crate::intrinsics::{integer_class, narrowing_instruction};
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::FieldIdx;
+use rustc_hir::def_id::DefId;
+use rustc_index::IndexVec;
+use rustc_middle::mir::{Operand, Place, ProjectionElem};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+/// The first Java release with the `jdk.incubator.vector` module
+const VECTOR_API_RELEASE: u32 = 16;
+
+/// The package of the Vector API
+const VECTOR_PACKAGE: &str = "jdk/incubator/vector";
+
+/// A lane-wise binary operation
+#[derive(Clone, Copy)]
+enum LaneOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Min,
+    Max,
+}
+
+impl LaneOp {
+    /// The operation of a `simd_*` intrinsic, by its name without the prefix, or of an operator
+    /// method of `Simd`
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "add" => LaneOp::Add,
+            "sub" => LaneOp::Sub,
+            "mul" => LaneOp::Mul,
+            "div" => LaneOp::Div,
+            "rem" => LaneOp::Rem,
+            "and" | "bitand" => LaneOp::And,
+            "or" | "bitor" => LaneOp::Or,
+            "xor" | "bitxor" => LaneOp::Xor,
+            "fmin" => LaneOp::Min,
+            "fmax" => LaneOp::Max,
+            _ => return None,
+        })
+    }
+
+    /// The `VectorOperators` constant of the operation and its type, `None` if there is none
+    fn vector_operator(self) -> Option<(&'static str, &'static str)> {
+        Some(match self {
+            LaneOp::Add => ("ADD", "Associative"),
+            LaneOp::Sub => ("SUB", "Binary"),
+            LaneOp::Mul => ("MUL", "Associative"),
+            LaneOp::Div => ("DIV", "Binary"),
+            LaneOp::And => ("AND", "Associative"),
+            LaneOp::Or => ("OR", "Associative"),
+            LaneOp::Xor => ("XOR", "Associative"),
+            LaneOp::Min => ("MIN", "Associative"),
+            LaneOp::Max => ("MAX", "Associative"),
+            LaneOp::Rem => return None,
+        })
+    }
+
+    /// The instruction computing the operation on two lanes with the given descriptor, `None`
+    /// for `Min` and `Max`, which are `Math` calls
+    fn instruction(self, descriptor: &str) -> Option<Instruction> {
+        Some(match (self, descriptor) {
+            (LaneOp::Add, "J") => Instruction::Ladd,
+            (LaneOp::Add, "F") => Instruction::Fadd,
+            (LaneOp::Add, "D") => Instruction::Dadd,
+            (LaneOp::Add, _) => Instruction::Iadd,
+            (LaneOp::Sub, "J") => Instruction::Lsub,
+            (LaneOp::Sub, "F") => Instruction::Fsub,
+            (LaneOp::Sub, "D") => Instruction::Dsub,
+            (LaneOp::Sub, _) => Instruction::Isub,
+            (LaneOp::Mul, "J") => Instruction::Lmul,
+            (LaneOp::Mul, "F") => Instruction::Fmul,
+            (LaneOp::Mul, "D") => Instruction::Dmul,
+            (LaneOp::Mul, _) => Instruction::Imul,
+            (LaneOp::Div, "J") => Instruction::Ldiv,
+            (LaneOp::Div, "F") => Instruction::Fdiv,
+            (LaneOp::Div, "D") => Instruction::Ddiv,
+            (LaneOp::Div, _) => Instruction::Idiv,
+            (LaneOp::Rem, "J") => Instruction::Lrem,
+            (LaneOp::Rem, "F") => Instruction::Frem,
+            (LaneOp::Rem, "D") => Instruction::Drem,
+            (LaneOp::Rem, _) => Instruction::Irem,
+            (LaneOp::And, "J") => Instruction::Land,
+            (LaneOp::And, _) => Instruction::Iand,
+            (LaneOp::Or, "J") => Instruction::Lor,
+            (LaneOp::Or, _) => Instruction::Ior,
+            (LaneOp::Xor, "J") => Instruction::Lxor,
+            (LaneOp::Xor, _) => Instruction::Ixor,
+            (LaneOp::Min | LaneOp::Max, _) => return None,
+        })
+    }
+}
+
+/// Whether the crate targets a JVM with the Vector API, with `-C target-cpu=java<release>`
+fn targets_vector_api(tcx: TyCtxt<'_>) -> bool {
+    tcx.sess
+        .opts
+        .cg
+        .target_cpu
+        .as_deref()
+        .and_then(|cpu| cpu.strip_prefix("java"))
+        .and_then(|release| release.parse::<u32>().ok())
+        .is_some_and(|release| release >= VECTOR_API_RELEASE)
+}
+
+/// The Vector API class for vectors of `lanes` lanes with the given descriptor and their size in
+/// bits, `None` if the API is not targeted or has no such vectors
+fn vector_class(tcx: TyCtxt<'_>, lanes: u64, descriptor: &str) -> Option<(&'static str, u64)> {
+    if !targets_vector_api(tcx) {
+        return None;
+    }
+    let (class, lane_bits) = match descriptor {
+        "B" => ("ByteVector", 8),
+        "S" => ("ShortVector", 16),
+        "I" => ("IntVector", 32),
+        "J" => ("LongVector", 64),
+        "F" => ("FloatVector", 32),
+        "D" => ("DoubleVector", 64),
+        _ => return None,
+    };
+    match lanes * lane_bits {
+        bits @ (64 | 128 | 256 | 512) => Some((class, bits)),
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes the result of the `simd_*` intrinsic `name`, returning `false` if it is not
+    /// supported
+    pub(crate) fn lower_simd_intrinsic(
+        &mut self,
+        name: &str,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+    ) -> bool {
+        let Some(name) = name.strip_prefix("simd_") else {
+            return false;
+        };
+        let vector_ty = generic_args.type_at(0);
+        match name {
+            "neg" => self.load_negated_lanes(vector_ty, &args[0].node),
+            "extract" | "extract_dyn" => {
+                let (_, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+                let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.method_bytecode_instructions
+                    .push(array_load_instruction(&element));
+                true
+            }
+            "insert" | "insert_dyn" => {
+                self.load_inserted_lane(vector_ty, &args[0].node, &args[1].node, &args[2].node);
+                true
+            }
+            "reduce_add_ordered" => {
+                self.load_reduction(LaneOp::Add, vector_ty, &args[0].node, Some(&args[1].node))
+            }
+            "reduce_mul_ordered" => {
+                self.load_reduction(LaneOp::Mul, vector_ty, &args[0].node, Some(&args[1].node))
+            }
+            "reduce_add_unordered" => {
+                self.load_reduction(LaneOp::Add, vector_ty, &args[0].node, None)
+            }
+            "reduce_mul_unordered" => {
+                self.load_reduction(LaneOp::Mul, vector_ty, &args[0].node, None)
+            }
+            name => match LaneOp::from_name(name) {
+                Some(op) => self.load_lanewise(op, vector_ty, &args[0].node, &args[1].node),
+                None => false,
+            },
+        }
+    }
+
+    /// Lowers calls of the methods and operators of `std::simd::Simd`, returning `false` for any
+    /// other call
+    pub(crate) fn lower_simd_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        if callee.is_local() {
+            return false;
+        }
+        let vector_ty = if tcx.trait_of_item(callee).is_some() {
+            generic_args.type_at(0)
+        } else if let Some(impl_def_id) = tcx.impl_of_method(callee) {
+            tcx.type_of(impl_def_id).instantiate(tcx, generic_args)
+        } else {
+            return false;
+        };
+        if !vector_ty.is_simd() {
+            return false;
+        }
+        let lowered = match tcx.item_name(callee).as_str() {
+            // The vector is the array of its lanes
+            "from_array" | "to_array" | "as_array" => {
+                self.load_operand(&args[0].node);
+                true
+            }
+            "splat" => {
+                self.load_splat(vector_ty, &args[0].node);
+                true
+            }
+            "neg" => self.load_negated_lanes(vector_ty, &args[0].node),
+            "reduce_sum" => self.load_reduction(LaneOp::Add, vector_ty, &args[0].node, None),
+            "reduce_product" => self.load_reduction(LaneOp::Mul, vector_ty, &args[0].node, None),
+            name => match LaneOp::from_name(name) {
+                Some(op) if args.len() == 2 => {
+                    self.load_lanewise(op, vector_ty, &args[0].node, &args[1].node)
+                }
+                _ => false,
+            },
+        };
+        if !lowered {
+            trace!("      Unsupported SIMD call: {}", tcx.def_path_str(callee));
+            return false;
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Pushes a new vector holding `operands`: the array a vector wraps, or its lanes
+    pub(crate) fn load_simd_aggregate(
+        &mut self,
+        vector_ty: Ty<'tcx>,
+        operands: &IndexVec<FieldIdx, Operand<'tcx>>,
+    ) {
+        if let [operand] = &operands.raw[..]
+            && operand.ty(self.body, self.tcx).is_array()
+        {
+            self.load_operand(operand);
+            return;
+        }
+        let (_, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        self.load_array_aggregate(element_ty, operands);
+    }
+
+    /// Whether `place` is a field of (a reference to) a SIMD vector
+    pub(crate) fn is_simd_field(&self, place: &Place<'tcx>) -> bool {
+        let base_ty = self.body.local_decls[place.local].ty;
+        match &place.projection[..] {
+            [ProjectionElem::Field(..)] => base_ty.is_simd(),
+            [ProjectionElem::Deref, ProjectionElem::Field(..)] => {
+                base_ty.builtin_deref(true).is_some_and(|ty| ty.is_simd())
+            }
+            _ => false,
+        }
+    }
+
+    /// Pushes a field of a SIMD vector: the whole array for vectors wrapping one, a lane
+    /// otherwise
+    pub(crate) fn load_simd_field(&mut self, place: &Place<'tcx>) {
+        let Some(ProjectionElem::Field(field, field_ty)) = place.projection.last() else {
+            return;
+        };
+        self.load_local(place.local);
+        if !field_ty.is_array() {
+            let lane = self.int_constant(field.as_u32() as i32);
+            let element = rust_ty_to_jvm_descriptor(*field_ty, self.tcx);
+            self.method_bytecode_instructions
+                .extend([lane, array_load_instruction(&element)]);
+        }
+    }
+
+    /// Pushes a new vector of `op` applied to the lanes of `lhs` and `rhs`, returning `false` if
+    /// it is not supported on their lanes
+    fn load_lanewise(
+        &mut self,
+        op: LaneOp,
+        vector_ty: Ty<'tcx>,
+        lhs: &Operand<'tcx>,
+        rhs: &Operand<'tcx>,
+    ) -> bool {
+        let (lanes, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        if matches!(op, LaneOp::Div | LaneOp::Rem) && matches!(element_ty.kind(), TyKind::Uint(_)) {
+            return false;
+        }
+        let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        if let Some((class, bits)) = vector_class(self.tcx, lanes, &element)
+            && let Some((operator, operator_type)) = op.vector_operator()
+        {
+            // Species.fromArray(lhs).lanewise(OP, Species.fromArray(rhs)).toArray()
+            self.load_vector(class, bits, &element, lhs);
+            self.load_vector_operator(operator, operator_type);
+            self.load_vector(class, bits, &element, rhs);
+            self.invoke_vector_method(
+                class,
+                "lanewise",
+                &format!(
+                    "(L{VECTOR_PACKAGE}/VectorOperators$Binary;L{VECTOR_PACKAGE}/Vector;)\
+                     L{VECTOR_PACKAGE}/{class};"
+                ),
+            );
+            self.invoke_vector_method(class, "toArray", &format!("()[{element}"));
+            return true;
+        }
+        self.push_new_array(&element, lanes as usize);
+        for lane in 0..lanes {
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            let index = self.int_constant(lane as i32);
+            self.method_bytecode_instructions.push(index);
+            self.load_lane(lhs, lane, &element);
+            self.load_lane(rhs, lane, &element);
+            self.apply_lane_op(op, &element);
+            self.method_bytecode_instructions
+                .push(array_store_instruction(&element));
+        }
+        true
+    }
+
+    /// Pushes a new vector of the negated lanes of `operand`, returning `false` if its lanes are
+    /// not numbers
+    fn load_negated_lanes(&mut self, vector_ty: Ty<'tcx>, operand: &Operand<'tcx>) -> bool {
+        let (lanes, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        let negation = match element.as_str() {
+            "B" | "S" | "I" => Instruction::Ineg,
+            "J" => Instruction::Lneg,
+            "F" => Instruction::Fneg,
+            "D" => Instruction::Dneg,
+            _ => return false,
+        };
+        if let Some((class, bits)) = vector_class(self.tcx, lanes, &element) {
+            self.load_vector(class, bits, &element, operand);
+            self.load_vector_operator("NEG", "Unary");
+            self.invoke_vector_method(
+                class,
+                "lanewise",
+                &format!("(L{VECTOR_PACKAGE}/VectorOperators$Unary;)L{VECTOR_PACKAGE}/{class};"),
+            );
+            self.invoke_vector_method(class, "toArray", &format!("()[{element}"));
+            return true;
+        }
+        self.push_new_array(&element, lanes as usize);
+        for lane in 0..lanes {
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            let index = self.int_constant(lane as i32);
+            self.method_bytecode_instructions.push(index);
+            self.load_lane(operand, lane, &element);
+            self.method_bytecode_instructions
+                .extend([negation.clone(), array_store_instruction(&element)]);
+        }
+        true
+    }
+
+    /// Pushes the lanes of `vector` combined with `op`, in order after `accumulator` if there is
+    /// one, returning `false` if it is not supported on its lanes
+    fn load_reduction(
+        &mut self,
+        op: LaneOp,
+        vector_ty: Ty<'tcx>,
+        vector: &Operand<'tcx>,
+        accumulator: Option<&Operand<'tcx>>,
+    ) -> bool {
+        let (lanes, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        if accumulator.is_none()
+            && let Some((class, bits)) = vector_class(self.tcx, lanes, &element)
+            && let Some((operator, "Associative")) = op.vector_operator()
+        {
+            self.load_vector(class, bits, &element, vector);
+            self.load_vector_operator(operator, "Associative");
+            self.invoke_vector_method(
+                class,
+                "reduceLanes",
+                &format!("(L{VECTOR_PACKAGE}/VectorOperators$Associative;){element}"),
+            );
+            return true;
+        }
+        let first_lane = match accumulator {
+            Some(accumulator) => {
+                self.load_operand(accumulator);
+                0
+            }
+            None => {
+                self.load_lane(vector, 0, &element);
+                1
+            }
+        };
+        for lane in first_lane..lanes {
+            self.load_lane(vector, lane, &element);
+            self.apply_lane_op(op, &element);
+        }
+        // Narrow lanes are computed as ints, and wrap around at their width
+        if let Some((_, bits)) = integer_class(&element) {
+            self.method_bytecode_instructions
+                .extend(narrowing_instruction(bits));
+        }
+        true
+    }
+
+    /// Pushes a new vector with every lane set to `value`
+    fn load_splat(&mut self, vector_ty: Ty<'tcx>, value: &Operand<'tcx>) {
+        let (lanes, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        self.push_new_array(&element, lanes as usize);
+        for lane in 0..lanes {
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            let index = self.int_constant(lane as i32);
+            self.method_bytecode_instructions.push(index);
+            self.load_operand(value);
+            self.method_bytecode_instructions
+                .push(array_store_instruction(&element));
+        }
+    }
+
+    /// Pushes a copy of `vector` with the lane at `index` set to `value`
+    fn load_inserted_lane(
+        &mut self,
+        vector_ty: Ty<'tcx>,
+        vector: &Operand<'tcx>,
+        index: &Operand<'tcx>,
+        value: &Operand<'tcx>,
+    ) {
+        let (_, element_ty) = vector_ty.simd_size_and_type(self.tcx);
+        let element = rust_ty_to_jvm_descriptor(element_ty, self.tcx);
+        let array = format!("[{element}");
+        self.load_operand(vector);
+        let array_class = self
+            .constant_pool
+            .add_class(&array)
+            .expect("Could not add class to constant pool");
+        let clone = self
+            .constant_pool
+            .add_method_ref(array_class, "clone", "()Ljava/lang/Object;")
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(clone));
+        self.cast_from_object(&array);
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        self.load_operand(index);
+        self.load_operand(value);
+        self.method_bytecode_instructions
+            .push(array_store_instruction(&element));
+    }
+
+    /// Pushes the lane `lane` of `vector`
+    fn load_lane(&mut self, vector: &Operand<'tcx>, lane: u64, element: &str) {
+        self.load_operand(vector);
+        let index = self.int_constant(lane as i32);
+        self.method_bytecode_instructions
+            .extend([index, array_load_instruction(element)]);
+    }
+
+    /// Combines the two lanes on top of the stack with `op`
+    fn apply_lane_op(&mut self, op: LaneOp, element: &str) {
+        match op.instruction(element) {
+            Some(instruction) => self.method_bytecode_instructions.push(instruction),
+            None => {
+                let method = if matches!(op, LaneOp::Min) {
+                    "min"
+                } else {
+                    "max"
+                };
+                self.invoke_static(
+                    "java/lang/Math",
+                    method,
+                    &format!("({element}{element}){element}"),
+                );
+            }
+        }
+    }
+
+    /// Pushes the Vector API vector of `operand`, an array of `bits` bits
+    fn load_vector(&mut self, class: &str, bits: u64, element: &str, operand: &Operand<'tcx>) {
+        let vector_class = self
+            .constant_pool
+            .add_class(format!("{VECTOR_PACKAGE}/{class}"))
+            .expect("Could not add class to constant pool");
+        let species = self
+            .constant_pool
+            .add_field_ref(
+                vector_class,
+                &format!("SPECIES_{bits}"),
+                &format!("L{VECTOR_PACKAGE}/VectorSpecies;"),
+            )
+            .expect("Could not add field ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Getstatic(species));
+        self.load_operand(operand);
+        self.method_bytecode_instructions
+            .push(Instruction::Iconst_0);
+        self.invoke_static(
+            &format!("{VECTOR_PACKAGE}/{class}"),
+            "fromArray",
+            &format!("(L{VECTOR_PACKAGE}/VectorSpecies;[{element}I)L{VECTOR_PACKAGE}/{class};"),
+        );
+    }
+
+    /// Pushes the `VectorOperators` constant `operator` of type `VectorOperators.<operator_type>`
+    fn load_vector_operator(&mut self, operator: &str, operator_type: &str) {
+        let operators = self
+            .constant_pool
+            .add_class(format!("{VECTOR_PACKAGE}/VectorOperators"))
+            .expect("Could not add class to constant pool");
+        let constant = self
+            .constant_pool
+            .add_field_ref(
+                operators,
+                operator,
+                &format!("L{VECTOR_PACKAGE}/VectorOperators${operator_type};"),
+            )
+            .expect("Could not add field ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Getstatic(constant));
+    }
+
+    /// Calls the method `name` of the Vector API class `class` on the vector below its arguments
+    fn invoke_vector_method(&mut self, class: &str, name: &str, descriptor: &str) {
+        let vector_class = self
+            .constant_pool
+            .add_class(format!("{VECTOR_PACKAGE}/{class}"))
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(vector_class, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(method));
+    }
+}
@@ -0,0 +1,172 @@
+//! `Box`, `Rc` and `Arc` as plain references.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, references, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{CastKind, Local, Operand, Place, ProjectionElem, Rvalue, StatementKind};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+use rustc_span::{Symbol, sym};
+
+/// The type a `Box`, `Rc` or `Arc` points to
+fn smart_pointee<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    if !adt_def.is_box()
+        && !tcx.is_diagnostic_item(sym::Rc, adt_def.did())
+        && !tcx.is_diagnostic_item(sym::Arc, adt_def.did())
+    {
+        return None;
+    }
+    Some(generic_args.type_at(0))
+}
+
+/// The type a `Box`, `Rc` or `Arc` points to, if it is erased to a reference to that type
+pub(crate) fn pointee<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let pointee = smart_pointee(ty, tcx)?;
+    match rust_ty_to_jvm_descriptor(pointee, tcx).as_bytes().first() {
+        Some(b'L' | b'[') => Some(pointee),
+        _ => None,
+    }
+}
+
+/// The primitive a `Box`, `Rc` or `Arc` points to, which it holds boxed
+pub(crate) fn boxed_primitive<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    smart_pointee(ty, tcx)
+        .filter(|pointee| references::is_primitive(&rust_ty_to_jvm_descriptor(*pointee, tcx)))
+}
+
+/// Whether a smart pointer is erased to the object it points to or the boxed primitive
+fn is_erased<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    pointee(ty, tcx).is_some() || boxed_primitive(ty, tcx).is_some()
+}
+
+/// Whether a call is the identity on erased smart pointers: `Box::new`, `Rc::new`, `Arc::new`,
+/// their `clone()` and their `deref()`, except for `new` boxing a primitive
+fn is_identity_call<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    callee: DefId,
+    generic_args: GenericArgsRef<'tcx>,
+) -> bool {
+    let lang_items = tcx.lang_items();
+    let trait_def_id = tcx.trait_of_item(callee);
+    if trait_def_id.is_some()
+        && (trait_def_id == lang_items.clone_trait() || trait_def_id == lang_items.deref_trait())
+    {
+        return is_erased(generic_args.type_at(0), tcx);
+    }
+
+    let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+        return false;
+    };
+    tcx.item_name(callee) == Symbol::intern("new")
+        && tcx.impl_trait_ref(impl_def_id).is_none()
+        && is_erased(new_output(tcx, callee, generic_args), tcx)
+}
+
+/// The type a `new` function returns
+fn new_output<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    callee: DefId,
+    generic_args: GenericArgsRef<'tcx>,
+) -> Ty<'tcx> {
+    tcx.fn_sig(callee)
+        .instantiate(tcx, generic_args)
+        .output()
+        .skip_binder()
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls that only move an erased smart pointer around, returning `false` for any
+    /// other call
+    pub(crate) fn lower_smart_pointer_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if args.len() != 1 || !is_identity_call(self.tcx, callee, generic_args) {
+            return false;
+        }
+        self.load_operand(&args[0].node);
+        // `new` boxes a primitive, `clone()` and `deref()` share the box
+        if self.tcx.trait_of_item(callee).is_none()
+            && let Some(primitive) =
+                boxed_primitive(new_output(self.tcx, callee, generic_args), self.tcx)
+        {
+            self.box_value(&rust_ty_to_jvm_descriptor(primitive, self.tcx));
+        }
+        trace!(
+            "      Generated bytecode: {} on an erased smart pointer",
+            self.tcx.def_path_str(callee)
+        );
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Whether a place reads the pointer inside an erased `Box`, as in the
+    /// `((box.0: Unique<T>).0: NonNull<T>).0` paths derefs of boxes are elaborated into. The
+    /// pointer is the box itself.
+    pub(crate) fn is_box_pointer_place(&self, place: &Place<'tcx>) -> bool {
+        let ty = self.body.local_decls[place.local].ty;
+        ty.is_box()
+            && is_erased(ty, self.tcx)
+            && !place.projection.is_empty()
+            && place
+                .projection
+                .iter()
+                .all(|elem| matches!(elem, ProjectionElem::Field(..)))
+    }
+
+    /// The `Box` a local holds the pointer inside of, read or transmuted from
+    /// [`Self::is_box_pointer_place`]
+    pub(crate) fn pointer_box(&self, local: Local) -> Option<Local> {
+        let mut assignments = self
+            .body
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.statements)
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(box (place, rvalue))
+                    if place.local == local && place.projection.is_empty() =>
+                {
+                    Some(rvalue)
+                }
+                _ => None,
+            });
+        let (Some(rvalue), None) = (assignments.next(), assignments.next()) else {
+            return None;
+        };
+        match rvalue {
+            Rvalue::Use(Operand::Copy(pointer) | Operand::Move(pointer))
+            | Rvalue::Cast(
+                CastKind::Transmute,
+                Operand::Copy(pointer) | Operand::Move(pointer),
+                _,
+            ) if self.is_box_pointer_place(pointer) => Some(pointer.local),
+            _ => None,
+        }
+    }
+
+    /// Lowers `*pointer = rvalue` for the pointer inside the `Box` in `box_local`, storing the
+    /// value into the box, boxed if it is a primitive
+    pub(crate) fn assign_through_box_pointer(
+        &mut self,
+        pointer: Local,
+        box_local: Local,
+        rvalue: &Rvalue<'tcx>,
+    ) {
+        if self.lower_rvalue(rvalue) {
+            let box_ty = self.body.local_decls[box_local].ty;
+            if let Some(primitive) = boxed_primitive(box_ty, self.tcx) {
+                self.box_value(&rust_ty_to_jvm_descriptor(primitive, self.tcx));
+            }
+            self.method_bytecode_instructions.push(Instruction::Dup);
+            self.store_local(pointer);
+            self.store_local(box_local);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! `String` as `java.lang.StringBuilder`, and `str` as `java.lang.String`.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, ldc};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::{Size, VariantIdx};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::interpret::{GlobalAlloc, Scalar, alloc_range};
+use rustc_middle::mir::{ConstOperand, ConstValue, Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind, TypingEnv};
+use rustc_span::source_map::Spanned;
+use rustc_span::{Symbol, sym};
+
+const STRING: &str = "java/lang/String";
+const STRING_BUILDER: &str = "java/lang/StringBuilder";
+
+/// Whether the type is `String`
+pub(crate) fn is_string(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) => tcx.is_diagnostic_item(sym::String, adt_def.did()),
+        _ => false,
+    }
+}
+
+/// Whether the type is `String` or `str`, behind any number of references
+fn is_string_like(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    let ty = ty.peel_refs();
+    ty.is_str() || is_string(ty, tcx)
+}
+
+/// The value of a string literal
+pub(crate) fn str_constant<'tcx>(
+    constant: &ConstOperand<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<String> {
+    let TyKind::Ref(_, pointee, _) = constant.ty().kind() else {
+        return None;
+    };
+    if !pointee.is_str() {
+        return None;
+    }
+    let value = constant
+        .const_
+        .eval(tcx, TypingEnv::fully_monomorphized(), constant.span)
+        .ok()?;
+    let bytes = value.try_get_slice_bytes_for_diagnostics(tcx)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// The values of a reference to an array of string literals, such as the pieces of a format
+/// string, read from the memory of the constant
+pub(crate) fn str_array_constant<'tcx>(
+    constant: &ConstOperand<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<Vec<String>> {
+    let TyKind::Ref(_, array_ty, _) = constant.ty().kind() else {
+        return None;
+    };
+    let TyKind::Array(element_ty, length) = array_ty.kind() else {
+        return None;
+    };
+    if !element_ty
+        .builtin_deref(true)
+        .is_some_and(|pointee| pointee.is_str())
+    {
+        return None;
+    }
+    let length = length.try_to_target_usize(tcx)?;
+    let typing_env = TypingEnv::fully_monomorphized();
+    let ConstValue::Scalar(Scalar::Ptr(array, _)) =
+        constant.const_.eval(tcx, typing_env, constant.span).ok()?
+    else {
+        return None;
+    };
+
+    // Every element is a (pointer, length) pair
+    let element_size = tcx
+        .layout_of(typing_env.as_query_input(*element_ty))
+        .ok()?
+        .size;
+    let pointer_size = Size::from_bytes(element_size.bytes() / 2);
+    let (provenance, offset) = array.into_parts();
+    let GlobalAlloc::Memory(array) = tcx.global_alloc(provenance.alloc_id()) else {
+        return None;
+    };
+    let array = array.inner();
+    (0..length)
+        .map(|index| {
+            let element = offset + element_size * index;
+            let Scalar::Ptr(data, _) = array
+                .read_scalar(&tcx, alloc_range(element, pointer_size), true)
+                .ok()?
+            else {
+                return None;
+            };
+            let Scalar::Int(data_length) = array
+                .read_scalar(
+                    &tcx,
+                    alloc_range(element + pointer_size, pointer_size),
+                    false,
+                )
+                .ok()?
+            else {
+                return None;
+            };
+            let (provenance, data_offset) = data.into_parts();
+            let GlobalAlloc::Memory(data) = tcx.global_alloc(provenance.alloc_id()) else {
+                return None;
+            };
+            let data_length = Size::from_bytes(data_length.to_bits_unchecked());
+            let bytes = data
+                .inner()
+                .get_bytes_unchecked(alloc_range(data_offset, data_length));
+            String::from_utf8(bytes.to_vec()).ok()
+        })
+        .collect()
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes a string literal
+    pub(crate) fn load_str_constant(&mut self, value: &str) {
+        let index = self
+            .constant_pool
+            .add_string(value)
+            .expect("Could not add string to constant pool");
+        self.method_bytecode_instructions.push(ldc(index));
+    }
+
+    /// Lowers calls of `String` and `str` methods and of the traits they implement, returning
+    /// `false` for any other call
+    pub(crate) fn lower_string_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let lang_items = tcx.lang_items();
+        if let Some(trait_def_id) = tcx.trait_of_item(callee) {
+            let self_ty = generic_args.type_at(0);
+            if !is_string_like(self_ty, tcx) {
+                return false;
+            }
+            let is_trait = |name: Symbol| tcx.is_diagnostic_item(name, trait_def_id);
+            let is_owned = is_string(self_ty, tcx);
+            if (is_trait(sym::From) && is_owned && is_string_like(generic_args.type_at(1), tcx))
+                || is_trait(sym::ToOwned)
+                || is_trait(sym::ToString)
+                || (Some(trait_def_id) == lang_items.clone_trait() && is_owned)
+            {
+                self.new_string_builder(Some(&args[0].node), "(Ljava/lang/CharSequence;)V");
+            } else if Some(trait_def_id) == lang_items.deref_trait() && is_owned {
+                self.load_operand(&args[0].node);
+                self.invoke_string_method(STRING_BUILDER, "toString", "()Ljava/lang/String;");
+            } else if Some(trait_def_id) == lang_items.add_trait() && is_owned {
+                // `string + str` appends to the string and returns it
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.append();
+            } else if Some(trait_def_id) == lang_items.eq_trait()
+                && is_string_like(generic_args.type_at(1), tcx)
+            {
+                self.load_as_java_string(&args[0].node);
+                self.load_as_java_string(&args[1].node);
+                self.invoke_string_method(STRING, "equals", "(Ljava/lang/Object;)Z");
+                if tcx.item_name(callee) == Symbol::intern("ne") {
+                    self.method_bytecode_instructions
+                        .push(Instruction::Iconst_1);
+                    self.method_bytecode_instructions.push(Instruction::Ixor);
+                }
+            } else if is_trait(Symbol::intern("FmtWrite"))
+                && is_owned
+                && tcx.item_name(callee) == Symbol::intern("write_str")
+            {
+                return self.lower_write_str(args, destination);
+            } else {
+                return false;
+            }
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            self.store_call_result(destination);
+            return true;
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        let self_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        match (tcx.item_name(callee).as_str(), is_string(self_ty, tcx)) {
+            ("len", _) if is_string_like(self_ty, tcx) => {
+                // Rust strings are measured in UTF-8 bytes
+                self.load_as_java_string(&args[0].node);
+                let charsets = self
+                    .constant_pool
+                    .add_class("java/nio/charset/StandardCharsets")
+                    .expect("Could not add class to constant pool");
+                let utf_8 = self
+                    .constant_pool
+                    .add_field_ref(charsets, "UTF_8", "Ljava/nio/charset/Charset;")
+                    .expect("Could not add field ref to constant pool");
+                self.method_bytecode_instructions
+                    .push(Instruction::Getstatic(utf_8));
+                self.invoke_string_method(STRING, "getBytes", "(Ljava/nio/charset/Charset;)[B");
+                self.method_bytecode_instructions
+                    .push(Instruction::Arraylength);
+                self.int_to_usize();
+            }
+            ("is_empty", _) if is_string_like(self_ty, tcx) => {
+                self.load_as_java_string(&args[0].node);
+                self.invoke_string_method(STRING, "isEmpty", "()Z");
+            }
+            ("new", true) => self.new_string_builder(None, "()V"),
+            ("with_capacity", true) => self.new_string_builder(Some(&args[0].node), "(I)V"),
+            ("push_str", true) => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.append();
+                self.method_bytecode_instructions.push(Instruction::Pop);
+            }
+            ("push", true) => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.invoke_string_method(STRING_BUILDER, "append", "(C)Ljava/lang/StringBuilder;");
+                self.method_bytecode_instructions.push(Instruction::Pop);
+            }
+            ("as_str", true) => {
+                self.load_operand(&args[0].node);
+                self.invoke_string_method(STRING_BUILDER, "toString", "()Ljava/lang/String;");
+            }
+            ("clear", true) => {
+                self.load_operand(&args[0].node);
+                self.method_bytecode_instructions
+                    .push(Instruction::Iconst_0);
+                self.invoke_string_method(STRING_BUILDER, "setLength", "(I)V");
+            }
+            (_, is_owned) => {
+                if is_owned || self_ty.is_str() {
+                    trace!(
+                        "      Unsupported string method: {}",
+                        tcx.def_path_str(callee)
+                    );
+                }
+                return false;
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `fmt::Write::write_str` appends to the string and cannot fail, its result is `Ok(())`
+    fn lower_write_str(
+        &mut self,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for fmt::Write::write_str: {destination:?}");
+            return false;
+        }
+        self.load_operand(&args[0].node);
+        self.load_operand(&args[1].node);
+        self.append();
+        self.method_bytecode_instructions.push(Instruction::Pop);
+        self.store_discriminant(destination.local, VariantIdx::from_u32(0));
+        trace!("      Generated bytecode: fmt::Write::write_str");
+        true
+    }
+
+    /// Pushes a string or string slice operand as a `java.lang.String`
+    pub(crate) fn load_as_java_string(&mut self, operand: &Operand<'tcx>) {
+        self.load_operand(operand);
+        self.invoke_string_method("java/lang/Object", "toString", "()Ljava/lang/String;");
+    }
+
+    /// Appends the string or string slice on top of the stack to the `StringBuilder` below it
+    fn append(&mut self) {
+        self.invoke_string_method(
+            STRING_BUILDER,
+            "append",
+            "(Ljava/lang/CharSequence;)Ljava/lang/StringBuilder;",
+        );
+    }
+
+    /// `new StringBuilder(argument)`, without an argument if `argument` is `None`
+    pub(crate) fn new_string_builder(
+        &mut self,
+        argument: Option<&Operand<'tcx>>,
+        descriptor: &str,
+    ) {
+        let class_index = self
+            .constant_pool
+            .add_class(STRING_BUILDER)
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class_index, "<init>", descriptor)
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::New(class_index));
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        if let Some(argument) = argument {
+            self.load_usize_as_int(argument);
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+    }
+
+    fn invoke_string_method(&mut self, class_name: &str, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(class_name)
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(method));
+    }
+}
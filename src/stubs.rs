@@ -0,0 +1,437 @@
+//! Stubs of the crate's API, with `-C llvm-args=api-stubs=java|jar`.
+
+use crate::constant_pool::{class_name, utf8};
+use crate::javadoc::{self, ApiDocs};
+use crate::naming::package_of;
+use crate::options::{self, ApiStubs};
+use crate::{jmh, junit, rustlib};
+use ristretto_classfile::attributes::Attribute;
+use ristretto_classfile::{ClassAccessFlags, ClassFile, FieldAccessFlags, MethodAccessFlags};
+use rustc_middle::ty::TyCtxt;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::Path;
+
+/// The manifest of the API JAR
+const MANIFEST: &str = "Manifest-Version: 1.0\r\nCreated-By: rustc_codegen_jvm\r\n\r\n";
+
+/// The Java keywords and literals, which cannot name a method in Java source
+const JAVA_KEYWORDS: [&str; 53] = [
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Writes the API stubs the `api-stubs` option asks for, given the crate's classes with the crate
+/// class first
+pub(crate) fn emit_api_stubs(tcx: TyCtxt<'_>, classes: &[(String, Vec<u8>)], api_docs: &ApiDocs) {
+    let Some(api_stubs) = options::get().api_stubs else {
+        return;
+    };
+    let api_classes = match api_classes(classes) {
+        Ok(api_classes) => api_classes,
+        Err(err) => {
+            tcx.dcx().err(format!(
+                "could not read the classes of the API stubs: {err:?}"
+            ));
+            return;
+        }
+    };
+    let outputs = tcx.output_filenames(());
+    let result = match api_stubs {
+        ApiStubs::Jar => {
+            write_api_jar(&api_classes, &outputs.with_extension("api.jar")).and_then(|()| {
+                write_sources_jar(
+                    &api_classes,
+                    api_docs,
+                    &outputs.with_extension("api-sources.jar"),
+                )
+            })
+        }
+        ApiStubs::Java => write_java_stubs(&api_classes, api_docs, &outputs.with_extension("api")),
+    };
+    if let Err(err) = result {
+        tcx.dcx()
+            .err(format!("could not write the API stubs: {err}"));
+    }
+}
+
+/// Whether a method is part of the API of its class
+fn is_api_method(access_flags: MethodAccessFlags) -> bool {
+    access_flags.intersects(MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED)
+        && !access_flags.contains(MethodAccessFlags::SYNTHETIC)
+}
+
+/// Whether a field is part of the API of its class
+fn is_api_field(access_flags: FieldAccessFlags) -> bool {
+    access_flags.intersects(FieldAccessFlags::PUBLIC | FieldAccessFlags::PROTECTED)
+        && !access_flags.contains(FieldAccessFlags::SYNTHETIC)
+}
+
+/// The API classes of the crate, with only their API members and without code
+fn api_classes(classes: &[(String, Vec<u8>)]) -> ristretto_classfile::Result<Vec<ClassFile>> {
+    let Some((crate_class, _)) = classes.first() else {
+        return Ok(Vec::new());
+    };
+    let package = package_of(crate_class);
+    let test_class = junit::test_class_name(crate_class);
+    let benchmark_class = jmh::benchmark_class_name(crate_class);
+
+    let mut api_classes = Vec::new();
+    for (class_name, bytecode) in classes {
+        if package_of(class_name) != package
+            || *class_name == test_class
+            || *class_name == benchmark_class
+        {
+            continue;
+        }
+        let mut class_file = ClassFile::from_bytes(&mut Cursor::new(bytecode.clone()))?;
+        if !class_file.access_flags.contains(ClassAccessFlags::PUBLIC)
+            || class_file
+                .access_flags
+                .contains(ClassAccessFlags::SYNTHETIC)
+        {
+            continue;
+        }
+        class_file
+            .methods
+            .retain(|method| is_api_method(method.access_flags));
+        for method in &mut class_file.methods {
+            method
+                .attributes
+                .retain(|attribute| !matches!(attribute, Attribute::Code { .. }));
+        }
+        class_file
+            .fields
+            .retain(|field| is_api_field(field.access_flags));
+        // The nest may name classes that are not part of the API
+        class_file.attributes.retain(|attribute| {
+            !matches!(
+                attribute,
+                Attribute::NestHost { .. } | Attribute::NestMembers { .. }
+            )
+        });
+        api_classes.push(class_file);
+    }
+    Ok(api_classes)
+}
+
+fn write_api_jar(api_classes: &[ClassFile], path: &Path) -> Result<(), String> {
+    let mut entries = vec![(
+        "META-INF/MANIFEST.MF".to_string(),
+        MANIFEST.as_bytes().to_vec(),
+    )];
+    for class_file in api_classes {
+        let class_name = class_name(&class_file.constant_pool, class_file.this_class)
+            .ok_or("an API class has no name")?;
+        let mut bytecode = Vec::new();
+        class_file
+            .to_bytes(&mut bytecode)
+            .map_err(|err| format!("{err:?}"))?;
+        entries.push((format!("{class_name}.class"), bytecode));
+    }
+    std::fs::write(path, rustlib::stored_zip(&entries))
+        .map_err(|err| format!("`{}`: {err}", path.display()))
+}
+
+/// A JAR of the `.java` stubs of the API classes
+fn write_sources_jar(
+    api_classes: &[ClassFile],
+    api_docs: &ApiDocs,
+    path: &Path,
+) -> Result<(), String> {
+    let mut entries = vec![(
+        "META-INF/MANIFEST.MF".to_string(),
+        MANIFEST.as_bytes().to_vec(),
+    )];
+    for class_file in api_classes {
+        let (class_name, source) =
+            java_source(class_file, api_docs).ok_or("an API class could not be read")?;
+        entries.push((format!("{class_name}.java"), source.into_bytes()));
+    }
+    std::fs::write(path, rustlib::stored_zip(&entries))
+        .map_err(|err| format!("`{}`: {err}", path.display()))
+}
+
+fn write_java_stubs(
+    api_classes: &[ClassFile],
+    api_docs: &ApiDocs,
+    directory: &Path,
+) -> Result<(), String> {
+    for class_file in api_classes {
+        let (class_name, source) =
+            java_source(class_file, api_docs).ok_or("an API class could not be read")?;
+        let path = directory.join(format!("{class_name}.java"));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("`{}`: {err}", parent.display()))?;
+        }
+        std::fs::write(&path, source).map_err(|err| format!("`{}`: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The Java name of a class, as in `com.example.Shape$Circle`
+fn java_class_name(class_name: &str) -> String {
+    class_name.replace('/', ".")
+}
+
+/// The Java type of the field descriptor at the start of `descriptor`, with the rest of it
+fn java_type(descriptor: &str) -> Option<(String, &str)> {
+    let (first, rest) = descriptor.split_at_checked(1)?;
+    let java_type = match first {
+        "B" => "byte",
+        "C" => "char",
+        "D" => "double",
+        "F" => "float",
+        "I" => "int",
+        "J" => "long",
+        "S" => "short",
+        "Z" => "boolean",
+        "V" => "void",
+        "L" => {
+            let (class_name, rest) = rest.split_once(';')?;
+            return Some((java_class_name(class_name), rest));
+        }
+        "[" => {
+            let (element_type, rest) = java_type(rest)?;
+            return Some((format!("{element_type}[]"), rest));
+        }
+        _ => return None,
+    };
+    Some((java_type.to_string(), rest))
+}
+
+/// The Java parameter and return types of a method descriptor
+fn java_method_types(descriptor: &str) -> Option<(Vec<String>, String)> {
+    let mut rest = descriptor.strip_prefix('(')?;
+    let mut parameters = Vec::new();
+    while !rest.starts_with(')') {
+        let (parameter, after) = java_type(rest)?;
+        parameters.push(parameter);
+        rest = after;
+    }
+    let (return_type, rest) = java_type(rest.strip_prefix(')')?)?;
+    rest.is_empty().then_some((parameters, return_type))
+}
+
+fn is_java_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && name != "_"
+        && !JAVA_KEYWORDS.contains(&name)
+}
+
+/// The internal name and the Java source of an API class
+fn java_source(class_file: &ClassFile, api_docs: &ApiDocs) -> Option<(String, String)> {
+    let constant_pool = &class_file.constant_pool;
+    let class_name = class_name(constant_pool, class_file.this_class)?;
+    let simple_name = class_name.rsplit('/').next()?;
+    let access_flags = class_file.access_flags;
+    let is_interface = access_flags.contains(ClassAccessFlags::INTERFACE);
+
+    let mut record_components = None;
+    let mut permitted_subclasses = Vec::new();
+    let mut deprecated = false;
+    for attribute in &class_file.attributes {
+        match attribute {
+            Attribute::Record { records, .. } => {
+                let mut components = Vec::with_capacity(records.len());
+                for record in records {
+                    let (java_type, _) = java_type(utf8(constant_pool, record.descriptor_index)?)?;
+                    components.push(format!(
+                        "{java_type} {}",
+                        utf8(constant_pool, record.name_index)?
+                    ));
+                }
+                record_components = Some(components);
+            }
+            Attribute::PermittedSubclasses { class_indexes, .. } => {
+                for class_index in class_indexes {
+                    permitted_subclasses
+                        .push(java_class_name(class_name(constant_pool, *class_index)?));
+                }
+            }
+            Attribute::Deprecated { .. } => deprecated = true,
+            _ => {}
+        }
+    }
+    let mut interfaces = Vec::with_capacity(class_file.interfaces.len());
+    for interface in &class_file.interfaces {
+        interfaces.push(java_class_name(class_name(constant_pool, *interface)?));
+    }
+
+    let mut source = String::new();
+    let _ = writeln!(
+        source,
+        "// API stub of `{class_name}`, generated by rustc_codegen_jvm"
+    );
+    let package = package_of(class_name);
+    if !package.is_empty() {
+        let _ = writeln!(source, "package {};\n", java_class_name(package));
+    }
+    if let Some(doc) = api_docs.class_doc(class_name) {
+        source.push_str(&javadoc::javadoc_comment(doc, ""));
+    }
+    if deprecated {
+        source.push_str("@Deprecated\n");
+    }
+    source.push_str("public ");
+    if is_interface {
+        if !permitted_subclasses.is_empty() {
+            source.push_str("sealed ");
+        }
+        let _ = write!(source, "interface {simple_name}");
+        if !interfaces.is_empty() {
+            let _ = write!(source, " extends {}", interfaces.join(", "));
+        }
+    } else {
+        if let Some(components) = &record_components {
+            let _ = write!(source, "record {simple_name}({})", components.join(", "));
+        } else {
+            if access_flags.contains(ClassAccessFlags::ABSTRACT) {
+                source.push_str("abstract ");
+            } else if access_flags.contains(ClassAccessFlags::FINAL) {
+                source.push_str("final ");
+            }
+            let _ = write!(source, "class {simple_name}");
+            let super_class = class_name(constant_pool, class_file.super_class)?;
+            if super_class != "java/lang/Object" {
+                let _ = write!(source, " extends {}", java_class_name(super_class));
+            }
+        }
+        if !interfaces.is_empty() {
+            let _ = write!(source, " implements {}", interfaces.join(", "));
+        }
+    }
+    if !permitted_subclasses.is_empty() {
+        let _ = write!(source, " permits {}", permitted_subclasses.join(", "));
+    }
+    source.push_str(" {\n");
+
+    if !is_interface {
+        for field in &class_file.fields {
+            let (java_type, _) = java_type(utf8(constant_pool, field.descriptor_index)?)?;
+            let name = utf8(constant_pool, field.name_index)?;
+            if let Some(doc) = api_docs.member_doc(class_name, name) {
+                source.push_str(&javadoc::javadoc_comment(doc, "    "));
+            }
+            let is_static = field.access_flags.contains(FieldAccessFlags::STATIC);
+            let _ = writeln!(
+                source,
+                "    public {}{java_type} {name};",
+                if is_static { "static " } else { "" },
+            );
+        }
+    }
+    for method in &class_file.methods {
+        let name = utf8(constant_pool, method.name_index)?;
+        let (parameter_types, return_type) =
+            java_method_types(utf8(constant_pool, method.descriptor_index)?)?;
+        // A record's only constructor is its canonical one, which its header declares
+        let is_constructor = name == "<init>";
+        if (is_constructor && record_components.is_some())
+            || (!is_constructor && !is_java_identifier(name))
+        {
+            continue;
+        }
+        let method_flags = method.access_flags;
+        let parameters: Vec<String> = parameter_types
+            .iter()
+            .enumerate()
+            .map(|(index, parameter_type)| format!("{parameter_type} arg{index}"))
+            .collect();
+        let parameters = parameters.join(", ");
+        if let Some(doc) = api_docs.member_doc(class_name, name) {
+            source.push_str(&javadoc::javadoc_comment(doc, "    "));
+        }
+        if method
+            .attributes
+            .iter()
+            .any(|attribute| matches!(attribute, Attribute::Deprecated { .. }))
+        {
+            source.push_str("    @Deprecated\n");
+        }
+        source.push_str("    ");
+        if !is_interface {
+            source.push_str(if method_flags.contains(MethodAccessFlags::PUBLIC) {
+                "public "
+            } else {
+                "protected "
+            });
+        }
+        if is_constructor {
+            let _ = writeln!(source, "{simple_name}({parameters}) {{");
+        } else {
+            if method_flags.contains(MethodAccessFlags::STATIC) {
+                source.push_str("static ");
+            } else if method_flags.contains(MethodAccessFlags::ABSTRACT) {
+                if !is_interface {
+                    source.push_str("abstract ");
+                }
+                let _ = writeln!(source, "{return_type} {name}({parameters});");
+                continue;
+            } else if is_interface {
+                source.push_str("default ");
+            }
+            let _ = writeln!(source, "{return_type} {name}({parameters}) {{");
+        }
+        source.push_str("        throw new UnsupportedOperationException(\"API stub\");\n    }\n");
+    }
+    source.push_str("}\n");
+    Some((class_name.to_string(), source))
+}
@@ -0,0 +1,208 @@
+//! The support report, of what a crate uses that the JVM cannot run, written as
+//! `<crate>.support.json` with `-C llvm-args=support-report`.
+
+use crate::{MethodCode, MirToBytecodeVisitor, ldc, options};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+
+/// A use of a construct that could not be lowered
+struct Finding {
+    function: String,
+    /// What was used, as in `inline-assembly` or `rvalue`
+    construct: &'static str,
+    location: String,
+    detail: String,
+}
+
+/// The report being gathered
+struct Report {
+    /// How many functions were lowered, trait methods and shims included
+    functions: usize,
+    findings: Vec<Finding>,
+}
+
+/// The report being gathered, `None` unless it is enabled
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Report>> {
+    REPORT
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Starts the report of the crate if `-C llvm-args=support-report` is given
+pub(crate) fn start() {
+    if options::get().support_report {
+        *lock() = Some(Report {
+            functions: 0,
+            findings: Vec::new(),
+        });
+    }
+}
+
+/// Whether the report is being gathered
+pub(crate) fn is_enabled() -> bool {
+    lock().is_some()
+}
+
+/// Counts a function being lowered, out of which the report gives those using unsupported
+/// constructs
+pub(crate) fn count_function() {
+    if let Some(report) = lock().as_mut() {
+        report.functions += 1;
+    }
+}
+
+/// Adds a use of `construct` by `function` to the report, returning whether the report is
+/// enabled, in which case the use is not an error
+pub(crate) fn record(
+    tcx: TyCtxt<'_>,
+    span: Span,
+    function: &str,
+    construct: &'static str,
+    detail: impl fmt::Display,
+) -> bool {
+    let mut report = lock();
+    let Some(Report { findings, .. }) = report.as_mut() else {
+        return false;
+    };
+    let source_map = tcx.sess.source_map();
+    let location = source_map.lookup_char_pos(span.source_callsite().lo());
+    findings.push(Finding {
+        function: function.to_string(),
+        construct,
+        location: format!(
+            "{}:{}:{}",
+            source_map.filename_for_diagnostics(&location.file.name),
+            location.line,
+            location.col.0 + 1
+        ),
+        detail: detail.to_string(),
+    });
+    true
+}
+
+/// Writes the report, warning about what it found
+pub(crate) fn finish(tcx: TyCtxt<'_>) {
+    let Some(Report {
+        functions,
+        findings,
+    }) = lock().take()
+    else {
+        return;
+    };
+    let crate_name = tcx.crate_name(LOCAL_CRATE);
+    let unsupported_functions = findings
+        .iter()
+        .map(|finding| finding.function.as_str())
+        .collect::<BTreeSet<_>>()
+        .len();
+    let mut json = String::new();
+    let _ = writeln!(json, "{{");
+    let _ = writeln!(json, "  \"crate\": {},", json_string(crate_name.as_str()));
+    let _ = writeln!(json, "  \"functions\": {functions},");
+    let _ = writeln!(
+        json,
+        "  \"unsupported_functions\": {unsupported_functions},"
+    );
+    let _ = write!(json, "  \"unsupported\": [");
+    for (index, finding) in findings.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "," };
+        let _ = write!(
+            json,
+            "{separator}\n    {{\n      \"function\": {},\n      \"construct\": {},\n      \
+             \"location\": {},\n      \"detail\": {}\n    }}",
+            json_string(&finding.function),
+            json_string(finding.construct),
+            json_string(&finding.location),
+            json_string(&finding.detail),
+        );
+    }
+    let _ = writeln!(json, "{}]", if findings.is_empty() { "" } else { "\n  " });
+    let _ = writeln!(json, "}}");
+
+    let path = tcx.output_filenames(()).with_extension("support.json");
+    if let Err(err) = std::fs::write(&path, json) {
+        tcx.dcx().err(format!(
+            "could not write the support report `{}`: {err}",
+            path.display()
+        ));
+    } else if !findings.is_empty() {
+        tcx.dcx().warn(format!(
+            "{unsupported_functions} of the {functions} functions of `{crate_name}` use \
+             constructs the JVM backend does not support, see `{}`",
+            path.display()
+        ));
+    }
+}
+
+/// A JSON string of the text
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            character if character.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(character));
+            }
+            character => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl MirToBytecodeVisitor<'_, '_> {
+    /// Adds a use of `construct` by the function being lowered, at the statement or terminator
+    /// being lowered, to the report. Returns whether the report is enabled, in which case the rest
+    /// of the function is not lowered, see [`MirToBytecodeVisitor::unsupported`]
+    pub(crate) fn record_unsupported(
+        &mut self,
+        construct: &'static str,
+        detail: impl fmt::Display,
+    ) -> bool {
+        let recorded = record(
+            self.tcx,
+            self.span,
+            &self.tcx.def_path_str(self.instance.def_id()),
+            construct,
+            detail,
+        );
+        self.unsupported |= recorded;
+        recorded
+    }
+    /// The code of a function that went to the report, throwing an
+    /// `UnsupportedOperationException` instead of running what was lowered of it
+    pub(crate) fn unsupported_code(self) -> MethodCode {
+        let function = self.tcx.def_path_str(self.instance.def_id());
+        let class = self
+            .constant_pool
+            .add_class("java/lang/UnsupportedOperationException")
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class, "<init>", "(Ljava/lang/String;)V")
+            .expect("Could not add constructor to constant pool");
+        let message = self
+            .constant_pool
+            .add_string(format!(
+                "`{function}` uses constructs the JVM backend does not support"
+            ))
+            .expect("Could not add string to constant pool");
+        MethodCode::from(vec![
+            Instruction::New(class),
+            Instruction::Dup,
+            ldc(message),
+            Instruction::Invokespecial(constructor),
+            Instruction::Athrow,
+        ])
+    }
+}
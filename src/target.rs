@@ -0,0 +1,49 @@
+//! The JVM target, as `--print` and `cfg` show it.
+
+use crate::options;
+use rustc_codegen_ssa::TargetConfig;
+use rustc_session::config::{PrintKind, PrintRequest};
+use rustc_span::Symbol;
+use std::fmt::Write;
+
+/// The Java versions the `class-version` option takes, which are the features of the target
+const JAVA_VERSIONS: [u16; 6] = [6, 7, 8, 11, 17, 21];
+
+/// The configuration of the target, which `cfg(target_feature)` and
+/// `cfg(target_has_reliable_f16)` follow
+pub(crate) fn target_config() -> TargetConfig {
+    let target_features: Vec<Symbol> = JAVA_VERSIONS
+        .iter()
+        .filter(|version| **version <= options::get().class_version)
+        .map(|version| Symbol::intern(&format!("java{version}")))
+        .collect();
+    TargetConfig {
+        unstable_target_features: target_features.clone(),
+        target_features,
+        has_reliable_f16: false,
+        has_reliable_f16_math: false,
+        has_reliable_f128: false,
+        has_reliable_f128_math: false,
+    }
+}
+
+/// The `--print` requests rustc leaves to the backend: `target-cpus` and `target-features`
+pub(crate) fn print(req: &PrintRequest, out: &mut String) {
+    match req.kind {
+        PrintKind::TargetCPUs => {
+            let _ = writeln!(out, "Available CPUs for this target:");
+            let _ = writeln!(out, "    jvm - The Java virtual machine (default).");
+        }
+        PrintKind::TargetFeatures => {
+            let _ = writeln!(out, "Available features for this target:");
+            for version in JAVA_VERSIONS {
+                let _ = writeln!(
+                    out,
+                    "    java{version:<2} - Classes run on Java {version}, with \
+                     `-C llvm-args=class-version={version}` or newer."
+                );
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,333 @@
+//! `std::time` on top of the JVM clocks.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, control_flow};
+use ristretto_classfile::attributes::Instruction;
+use rustc_abi::{FieldIdx, VariantIdx};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Const, ConstOperand, Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind};
+use rustc_span::source_map::Spanned;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const NANOS_PER_MILLI: i64 = 1_000_000;
+const NANOS_PER_MICRO: i64 = 1_000;
+
+/// The time types lowered to nanoseconds
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimeType {
+    Instant,
+    SystemTime,
+    Duration,
+    SystemTimeError,
+}
+
+/// The time type a type is, by its path
+fn time_type(ty: Ty<'_>, tcx: TyCtxt<'_>) -> Option<TimeType> {
+    let TyKind::Adt(adt_def, _) = ty.kind() else {
+        return None;
+    };
+    match tcx.def_path_str(adt_def.did()).as_str() {
+        "std::time::Instant" => Some(TimeType::Instant),
+        "std::time::SystemTime" => Some(TimeType::SystemTime),
+        "std::time::Duration" | "core::time::Duration" => Some(TimeType::Duration),
+        "std::time::SystemTimeError" => Some(TimeType::SystemTimeError),
+        _ => None,
+    }
+}
+
+/// Whether the type is lowered to a `long` of nanoseconds
+pub(crate) fn is_time_type(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    time_type(ty, tcx).is_some()
+}
+
+/// The value in nanoseconds of a supported time constant
+pub(crate) fn time_constant<'tcx>(constant: &ConstOperand<'tcx>, tcx: TyCtxt<'tcx>) -> Option<i64> {
+    time_type(constant.ty(), tcx)?;
+    let Const::Unevaluated(unevaluated, _) = constant.const_ else {
+        return None;
+    };
+    match tcx.item_name(unevaluated.def).as_str() {
+        "ZERO" | "UNIX_EPOCH" => Some(0),
+        "MAX" => Some(i64::MAX),
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `Instant`, `SystemTime`, `Duration` and `SystemTimeError` methods, of the
+    /// operators on them, and of `thread::sleep`, returning `false` for any other call
+    pub(crate) fn lower_time_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        if tcx.def_path_str(callee) == "std::thread::sleep" {
+            // Thread.sleep(millis, nanos)
+            self.load_operand(&args[0].node);
+            self.load_long_constant(NANOS_PER_MILLI);
+            self.method_bytecode_instructions.push(Instruction::Ldiv);
+            self.load_operand(&args[0].node);
+            self.load_long_constant(NANOS_PER_MILLI);
+            self.method_bytecode_instructions.push(Instruction::Lrem);
+            self.method_bytecode_instructions.push(Instruction::L2i);
+            self.invoke_static("java/lang/Thread", "sleep", "(JI)V");
+            trace!("      Generated bytecode: std::thread::sleep");
+            return true;
+        }
+
+        let name = tcx.item_name(callee);
+        let lang_items = tcx.lang_items();
+        if let Some(trait_def_id) = tcx.trait_of_item(callee) {
+            let Some(time) = time_type(generic_args.type_at(0), tcx) else {
+                return false;
+            };
+            let rhs = generic_args.types().nth(1).and_then(|ty| {
+                if ty.is_integral() {
+                    Some(None)
+                } else {
+                    time_type(ty, tcx).map(Some)
+                }
+            });
+            let trait_def_id = Some(trait_def_id);
+            let instructions = match rhs {
+                // Instants and system times minus another one are durations that saturate at
+                // zero
+                Some(Some(rhs)) if trait_def_id == lang_items.sub_trait() && rhs == time => {
+                    vec![Instruction::Lsub]
+                }
+                Some(Some(TimeType::Duration)) if trait_def_id == lang_items.add_trait() => {
+                    vec![Instruction::Ladd]
+                }
+                Some(Some(TimeType::Duration)) if trait_def_id == lang_items.sub_trait() => {
+                    vec![Instruction::Lsub]
+                }
+                // Durations are multiplied and divided by `u32`s
+                Some(None) if trait_def_id == lang_items.mul_trait() => {
+                    vec![Instruction::I2l, Instruction::Lmul]
+                }
+                Some(None) if trait_def_id == lang_items.div_trait() => {
+                    vec![Instruction::I2l, Instruction::Ldiv]
+                }
+                _ => {
+                    trace!(
+                        "      Unsupported time operator: {}",
+                        tcx.def_path_str(callee)
+                    );
+                    return false;
+                }
+            };
+            self.load_operand(&args[0].node);
+            self.load_operand(&args[1].node);
+            self.method_bytecode_instructions.extend(instructions);
+            if rhs == Some(Some(time)) && time != TimeType::Duration {
+                self.saturate_at_zero();
+            }
+        } else {
+            let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+                return false;
+            };
+            let Some(time) = time_type(tcx.type_of(impl_def_id).instantiate_identity(), tcx) else {
+                return false;
+            };
+            match (time, name.as_str()) {
+                (TimeType::Instant, "now") => {
+                    self.invoke_static("java/lang/System", "nanoTime", "()J");
+                }
+                (TimeType::SystemTime, "now") => {
+                    self.invoke_static("java/lang/System", "currentTimeMillis", "()J");
+                    self.load_long_constant(NANOS_PER_MILLI);
+                    self.method_bytecode_instructions.push(Instruction::Lmul);
+                }
+                (TimeType::Instant, "elapsed") => {
+                    self.invoke_static("java/lang/System", "nanoTime", "()J");
+                    self.load_operand(&args[0].node);
+                    self.method_bytecode_instructions.push(Instruction::Lsub);
+                    self.saturate_at_zero();
+                }
+                (TimeType::Instant, "duration_since" | "saturating_duration_since") => {
+                    self.load_operand(&args[0].node);
+                    self.load_operand(&args[1].node);
+                    self.method_bytecode_instructions.push(Instruction::Lsub);
+                    self.saturate_at_zero();
+                }
+                (TimeType::SystemTime, "duration_since" | "elapsed") => {
+                    return self.lower_system_time_difference(name.as_str(), args, destination);
+                }
+                (TimeType::SystemTimeError, "duration") => self.load_operand(&args[0].node),
+                (TimeType::Duration, "new") => {
+                    self.load_operand(&args[0].node);
+                    self.load_long_constant(NANOS_PER_SEC);
+                    self.method_bytecode_instructions.push(Instruction::Lmul);
+                    self.load_operand(&args[1].node);
+                    self.method_bytecode_instructions.push(Instruction::I2l);
+                    self.method_bytecode_instructions.push(Instruction::Ladd);
+                }
+                (TimeType::Duration, "from_secs" | "from_millis" | "from_micros") => {
+                    self.load_operand(&args[0].node);
+                    self.load_long_constant(nanos_per_unit(name.as_str()));
+                    self.method_bytecode_instructions.push(Instruction::Lmul);
+                }
+                (TimeType::Duration, "from_nanos") => self.load_operand(&args[0].node),
+                (TimeType::Duration, "from_secs_f64" | "from_secs_f32") => {
+                    self.load_operand(&args[0].node);
+                    if name.as_str() == "from_secs_f32" {
+                        self.method_bytecode_instructions.push(Instruction::F2d);
+                    }
+                    self.load_double_constant(NANOS_PER_SEC as f64);
+                    self.method_bytecode_instructions.push(Instruction::Dmul);
+                    self.method_bytecode_instructions.push(Instruction::D2l);
+                }
+                (TimeType::Duration, "as_secs") => {
+                    self.load_operand(&args[0].node);
+                    self.load_long_constant(NANOS_PER_SEC);
+                    self.method_bytecode_instructions.push(Instruction::Ldiv);
+                }
+                (TimeType::Duration, "as_millis" | "as_micros" | "as_nanos") => {
+                    // These return `u128`s, which are `BigInteger`s
+                    self.load_operand(&args[0].node);
+                    if name.as_str() != "as_nanos" {
+                        self.load_long_constant(nanos_per_unit(name.as_str()));
+                        self.method_bytecode_instructions.push(Instruction::Ldiv);
+                    }
+                    self.invoke_static(
+                        "java/math/BigInteger",
+                        "valueOf",
+                        "(J)Ljava/math/BigInteger;",
+                    );
+                }
+                (TimeType::Duration, "as_secs_f64" | "as_secs_f32") => {
+                    self.load_operand(&args[0].node);
+                    self.method_bytecode_instructions.push(Instruction::L2d);
+                    self.load_double_constant(NANOS_PER_SEC as f64);
+                    self.method_bytecode_instructions.push(Instruction::Ddiv);
+                    if name.as_str() == "as_secs_f32" {
+                        self.method_bytecode_instructions.push(Instruction::D2f);
+                    }
+                }
+                (TimeType::Duration, "subsec_nanos" | "subsec_micros" | "subsec_millis") => {
+                    self.load_operand(&args[0].node);
+                    self.load_long_constant(NANOS_PER_SEC);
+                    self.method_bytecode_instructions.push(Instruction::Lrem);
+                    let unit = match name.as_str() {
+                        "subsec_millis" => NANOS_PER_MILLI,
+                        "subsec_micros" => NANOS_PER_MICRO,
+                        _ => 1,
+                    };
+                    if unit != 1 {
+                        self.load_long_constant(unit);
+                        self.method_bytecode_instructions.push(Instruction::Ldiv);
+                    }
+                    self.method_bytecode_instructions.push(Instruction::L2i);
+                }
+                (TimeType::Duration, "is_zero") => {
+                    // Durations are never negative, so lcmp is 0 or 1
+                    self.load_operand(&args[0].node);
+                    self.method_bytecode_instructions
+                        .push(Instruction::Lconst_0);
+                    self.method_bytecode_instructions.push(Instruction::Lcmp);
+                    self.method_bytecode_instructions
+                        .push(Instruction::Iconst_1);
+                    self.method_bytecode_instructions.push(Instruction::Iand);
+                    self.method_bytecode_instructions
+                        .push(Instruction::Iconst_1);
+                    self.method_bytecode_instructions.push(Instruction::Ixor);
+                }
+                _ => {
+                    trace!(
+                        "      Unsupported time method: {}",
+                        tcx.def_path_str(callee)
+                    );
+                    return false;
+                }
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `SystemTime::duration_since(earlier)` and `elapsed()` return `Ok` of the difference if it
+    /// is positive, and otherwise an `Err`
+    fn lower_system_time_difference(
+        &mut self,
+        name: &str,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !destination.projection.is_empty() || !self.is_unpacked_enum(destination.local) {
+            trace!("      Unsupported destination for SystemTime::{name}: {destination:?}");
+            return false;
+        }
+        let ok = VariantIdx::from_u32(0);
+        let err = VariantIdx::from_u32(1);
+        if name == "elapsed" {
+            self.invoke_static("java/lang/System", "currentTimeMillis", "()J");
+            self.load_long_constant(NANOS_PER_MILLI);
+            self.method_bytecode_instructions.push(Instruction::Lmul);
+            self.load_operand(&args[0].node);
+        } else {
+            self.load_operand(&args[0].node);
+            self.load_operand(&args[1].node);
+        }
+        self.method_bytecode_instructions.push(Instruction::Lsub);
+
+        // if (difference >= 0) Ok(difference) else Err(-difference)
+        self.method_bytecode_instructions.push(Instruction::Dup2);
+        self.method_bytecode_instructions
+            .push(Instruction::Lconst_0);
+        self.method_bytecode_instructions.push(Instruction::Lcmp);
+        let to_err = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Iflt(0));
+        self.store_enum_field(destination.local, ok, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, ok);
+        let goto_end = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Goto(0));
+
+        let err_start = self.method_bytecode_instructions.len();
+        let difference = control_flow::verification_type("J", self.constant_pool)
+            .expect("Could not add class to constant pool");
+        self.extra_frames.push((err_start, vec![difference]));
+        self.method_bytecode_instructions.push(Instruction::Lneg);
+        self.store_enum_field(destination.local, err, FieldIdx::from_u32(0));
+        self.store_discriminant(destination.local, err);
+
+        let end = self.method_bytecode_instructions.len();
+        self.extra_frames.push((end, Vec::new()));
+        self.method_bytecode_instructions[to_err] = Instruction::Iflt(err_start as u16);
+        self.method_bytecode_instructions[goto_end] = Instruction::Goto(end as u16);
+        trace!("      Generated bytecode: SystemTime::{name}");
+        true
+    }
+
+    /// Replaces a negative `long` on top of the stack with 0
+    fn saturate_at_zero(&mut self) {
+        self.method_bytecode_instructions
+            .push(Instruction::Lconst_0);
+        self.invoke_static("java/lang/Math", "max", "(JJ)J");
+    }
+
+    fn load_long_constant(&mut self, value: i64) {
+        let instruction = self.long_constant(value);
+        self.method_bytecode_instructions.push(instruction);
+    }
+
+    fn load_double_constant(&mut self, value: f64) {
+        let instruction = self.double_constant(value);
+        self.method_bytecode_instructions.push(instruction);
+    }
+}
+
+/// The nanoseconds in the unit of a `Duration` constructor or accessor
+fn nanos_per_unit(method: &str) -> i64 {
+    if method.ends_with("secs") {
+        NANOS_PER_SEC
+    } else if method.ends_with("millis") {
+        NANOS_PER_MILLI
+    } else {
+        NANOS_PER_MICRO
+    }
+}
@@ -0,0 +1,61 @@
+//! The MIR to bytecode trace.
+
+use crate::options;
+use rustc_middle::ty::TyCtxt;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+/// The trace being written, `None` unless it is enabled
+static TRACE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+/// Writes a line to the trace, if it is enabled
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::trace::write_line(format_args!($($arg)*))
+    };
+}
+pub(crate) use trace;
+
+/// Starts the trace of the crate if `-C llvm-args=trace` is given
+pub(crate) fn start(tcx: TyCtxt<'_>) {
+    if !options::get().trace {
+        return;
+    }
+    let path = tcx.output_filenames(()).with_extension("trace");
+    match File::create(&path) {
+        Ok(file) => *lock() = Some(BufWriter::new(file)),
+        Err(err) => {
+            tcx.dcx().err(format!(
+                "could not create the trace `{}`: {err}",
+                path.display()
+            ));
+        }
+    }
+}
+
+/// Whether the trace is being written
+pub(crate) fn is_enabled() -> bool {
+    lock().is_some()
+}
+
+/// Flushes and closes the trace
+pub(crate) fn finish() {
+    if let Some(mut trace) = lock().take() {
+        let _ = trace.flush();
+    }
+}
+
+/// Writes a line to the trace, if it is enabled, see [`trace!`]
+pub(crate) fn write_line(line: fmt::Arguments<'_>) {
+    if let Some(trace) = lock().as_mut() {
+        let _ = writeln!(trace, "{line}");
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<BufWriter<File>>> {
+    TRACE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
@@ -0,0 +1,505 @@
+//! Unsize coercions, and trait objects as JVM interfaces.
+
+use crate::constant_pool::ConstantPool;
+use crate::mono::instance_descriptor;
+use crate::trace::trace;
+use crate::{
+    MirToBytecodeVisitor, boxing, control_flow, create_method, load_instruction, naming, options,
+    references, return_instruction, rust_ty_to_jvm_descriptor,
+};
+use ristretto_classfile::attributes::Instruction;
+use ristretto_classfile::{
+    ClassAccessFlags, ClassFile, Field, FieldAccessFlags, FieldType, Method, MethodAccessFlags,
+};
+use rustc_data_structures::fx::FxIndexSet;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{CastKind, Operand, Place, Rvalue, StatementKind};
+use rustc_middle::ty::adjustment::PointerCoercion;
+use rustc_middle::ty::{
+    AssocKind, GenericArgsRef, Instance, InstanceKind, Ty, TyCtxt, TyKind, TypingEnv,
+};
+use rustc_span::source_map::Spanned;
+use rustc_span::sym;
+
+const ADAPTED_FIELD: &str = "value";
+
+/// The type a pointer or smart pointer points to
+fn pointee(ty: Ty<'_>) -> Option<Ty<'_>> {
+    match ty.kind() {
+        TyKind::Adt(_, generic_args) => generic_args.types().next(),
+        _ => ty.builtin_deref(true),
+    }
+}
+
+/// The principal trait of a trait object type
+fn principal_trait(ty: Ty<'_>) -> Option<DefId> {
+    match ty.kind() {
+        TyKind::Dynamic(predicates, ..) => predicates.principal_def_id(),
+        _ => None,
+    }
+}
+
+/// The name of the interface a trait is
+pub(crate) fn interface_name(tcx: TyCtxt<'_>, trait_def_id: DefId) -> String {
+    naming::in_crate_package(
+        tcx,
+        trait_def_id.krate,
+        tcx.item_name(trait_def_id).as_str(),
+    )
+}
+
+/// The methods of a trait that its interface has
+fn interface_methods(tcx: TyCtxt<'_>, trait_def_id: DefId) -> Vec<DefId> {
+    tcx.associated_items(trait_def_id)
+        .in_definition_order()
+        .filter(|item| matches!(item.kind, AssocKind::Fn { has_self: true, .. }))
+        .filter(|item| !tcx.generics_of(item.def_id).requires_monomorphization(tcx))
+        .map(|item| item.def_id)
+        .collect()
+}
+
+/// The parameter and return descriptors of the interface method of a trait method, without the
+/// receiver
+fn interface_method_signature(tcx: TyCtxt<'_>, def_id: DefId) -> (Vec<String>, String) {
+    let fn_sig = tcx.fn_sig(def_id).instantiate_identity().skip_binder();
+    let parameters = fn_sig
+        .inputs()
+        .iter()
+        .skip(1)
+        .map(|input_ty| rust_ty_to_jvm_descriptor(*input_ty, tcx))
+        .filter(|descriptor| descriptor != "V")
+        .collect();
+    (parameters, rust_ty_to_jvm_descriptor(fn_sig.output(), tcx))
+}
+
+/// The descriptor of the interface method of a trait method
+fn interface_method_descriptor(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+    let (parameters, return_descriptor) = interface_method_signature(tcx, def_id);
+    format!("({}){return_descriptor}", parameters.concat())
+}
+
+/// The local traits of the trait objects the given instances coerce to, and the types coerced to
+/// each, `None` for upcasts from other trait objects
+fn coercions<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instances: &[Instance<'tcx>],
+) -> FxIndexSet<(Option<Ty<'tcx>>, DefId)> {
+    let mut coercions = FxIndexSet::default();
+    for instance in instances {
+        let body = tcx.instance_mir(instance.def);
+        for statement in body.basic_blocks.iter().flat_map(|block| &block.statements) {
+            let StatementKind::Assign(box (
+                _,
+                Rvalue::Cast(
+                    CastKind::PointerCoercion(PointerCoercion::Unsize, ..),
+                    operand,
+                    target_ty,
+                ),
+            )) = &statement.kind
+            else {
+                continue;
+            };
+            let source_ty = operand.ty(body, tcx);
+            if let (Some(source_pointee), Some(trait_def_id)) = (
+                pointee(source_ty),
+                pointee(*target_ty).and_then(principal_trait),
+            ) && trait_def_id.is_local()
+                && !source_pointee.has_param()
+            {
+                let source_ty = (!source_pointee.is_trait()).then_some(source_pointee);
+                coercions.insert((source_ty, trait_def_id));
+            }
+        }
+    }
+    coercions
+}
+
+/// The local traits a trait object of `trait_def_id` can be upcast to, the trait itself included
+fn local_supertraits(tcx: TyCtxt<'_>, trait_def_id: DefId) -> impl Iterator<Item = DefId> {
+    tcx.supertrait_def_ids(trait_def_id)
+        .filter(|supertrait_def_id| supertrait_def_id.is_local())
+}
+
+/// Generates the interfaces of the traits the given instances coerce values to trait objects of,
+/// and of their supertraits, and the adapter classes of the coerced types
+pub(crate) fn generate_trait_object_classes<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instances: &[Instance<'tcx>],
+) -> ristretto_classfile::Result<Vec<(String, Vec<u8>)>> {
+    let coercions = coercions(tcx, instances);
+    let traits: FxIndexSet<DefId> = coercions
+        .iter()
+        .flat_map(|(_, trait_def_id)| local_supertraits(tcx, *trait_def_id))
+        .collect();
+    let mut classes = Vec::new();
+    for trait_def_id in traits {
+        classes.push(class_bytes(interface_class(tcx, trait_def_id)?)?);
+    }
+    for (ty, trait_def_id) in coercions {
+        if let Some(ty) = ty {
+            classes.push(class_bytes(adapter_class(tcx, ty, trait_def_id)?)?);
+        }
+    }
+    Ok(classes)
+}
+
+fn class_bytes(class: (String, ClassFile)) -> ristretto_classfile::Result<(String, Vec<u8>)> {
+    let (name, class_file) = class;
+    class_file.verify()?;
+    let mut bytes = Vec::new();
+    class_file.to_bytes(&mut bytes)?;
+    Ok((name, bytes))
+}
+
+/// `interface Trait extends Supertrait... { R method(A...); ... }`
+fn interface_class(
+    tcx: TyCtxt<'_>,
+    trait_def_id: DefId,
+) -> ristretto_classfile::Result<(String, ClassFile)> {
+    let name = interface_name(tcx, trait_def_id);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let this_class = constant_pool.add_class(&name)?;
+    let mut interfaces = Vec::new();
+    for supertrait_def_id in
+        local_supertraits(tcx, trait_def_id).filter(|def_id| *def_id != trait_def_id)
+    {
+        interfaces.push(constant_pool.add_class(interface_name(tcx, supertrait_def_id))?);
+    }
+    let mut methods = Vec::new();
+    for method_def_id in interface_methods(tcx, trait_def_id) {
+        methods.push(Method {
+            access_flags: MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT,
+            name_index: constant_pool.add_utf8(tcx.item_name(method_def_id).as_str())?,
+            descriptor_index: constant_pool
+                .add_utf8(interface_method_descriptor(tcx, method_def_id))?,
+            attributes: Vec::new(),
+        });
+    }
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC
+            | ClassAccessFlags::INTERFACE
+            | ClassAccessFlags::ABSTRACT,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        interfaces,
+        methods,
+        ..Default::default()
+    };
+    Ok((name, class_file))
+}
+
+/// `final class T$Trait implements Trait`, holding a `&T` and forwarding every method of the
+/// interface and of the interfaces of its supertraits to the impls of the traits for `T`
+fn adapter_class<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    ty: Ty<'tcx>,
+    trait_def_id: DefId,
+) -> ristretto_classfile::Result<(String, ClassFile)> {
+    let name = naming::adapter_class_name(tcx, ty, trait_def_id);
+    let value_descriptor =
+        rust_ty_to_jvm_descriptor(Ty::new_imm_ref(tcx, tcx.lifetimes.re_erased, ty), tcx);
+    let mut constant_pool = ConstantPool::default();
+    let super_class = constant_pool.add_class("java/lang/Object")?;
+    let this_class = constant_pool.add_class(&name)?;
+    let interface = constant_pool.add_class(interface_name(tcx, trait_def_id))?;
+    let crate_class = constant_pool.add_class(naming::crate_class_name(tcx))?;
+    let object_init = constant_pool.add_method_ref(super_class, "<init>", "()V")?;
+    let value = constant_pool.add_field_ref(this_class, ADAPTED_FIELD, &value_descriptor)?;
+
+    let fields = vec![Field {
+        access_flags: FieldAccessFlags::PRIVATE | FieldAccessFlags::FINAL,
+        name_index: constant_pool.add_utf8(ADAPTED_FIELD)?,
+        descriptor_index: constant_pool.add_utf8(&value_descriptor)?,
+        field_type: FieldType::parse(&value_descriptor)?,
+        attributes: Vec::new(),
+    }];
+
+    let constructor = vec![
+        Instruction::Aload_0,
+        Instruction::Invokespecial(object_init),
+        Instruction::Aload_0,
+        Instruction::Aload_1,
+        Instruction::Putfield(value),
+        Instruction::Return,
+    ];
+    let mut methods = vec![create_method(
+        &mut constant_pool,
+        MethodAccessFlags::PUBLIC,
+        "<init>",
+        &format!("({value_descriptor})V"),
+        &constructor.into(),
+    )?];
+
+    let method_def_ids: Vec<DefId> = local_supertraits(tcx, trait_def_id)
+        .flat_map(|trait_def_id| interface_methods(tcx, trait_def_id))
+        .collect();
+    for method_def_id in method_def_ids {
+        let generic_args = tcx.mk_args(&[ty.into()]);
+        let Ok(Some(instance)) = Instance::try_resolve(
+            tcx,
+            TypingEnv::fully_monomorphized(),
+            method_def_id,
+            generic_args,
+        ) else {
+            continue;
+        };
+        let (parameters, return_descriptor) = interface_method_signature(tcx, method_def_id);
+        let impl_method = constant_pool.add_method_ref(
+            crate_class,
+            &naming::instance_method_name(tcx, instance),
+            &instance_descriptor(tcx, instance),
+        )?;
+
+        // return Crate.T$Trait$method(this.value, arguments...)
+        let mut instructions = vec![Instruction::Aload_0, Instruction::Getfield(value)];
+        let mut slot: u16 = 1;
+        for parameter in &parameters {
+            instructions.extend(load_instruction(parameter, slot));
+            slot += if matches!(parameter.as_str(), "J" | "D") {
+                2
+            } else {
+                1
+            };
+        }
+        instructions.push(Instruction::Invokestatic(impl_method));
+        instructions.push(return_instruction(&return_descriptor));
+        methods.push(create_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            tcx.item_name(method_def_id).as_str(),
+            &interface_method_descriptor(tcx, method_def_id),
+            &instructions.into(),
+        )?);
+    }
+
+    let class_file = ClassFile {
+        version: options::get().version(),
+        access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::FINAL | ClassAccessFlags::SUPER,
+        constant_pool: constant_pool.into_inner(),
+        this_class,
+        super_class,
+        interfaces: vec![interface],
+        fields,
+        methods,
+        ..Default::default()
+    };
+    Ok((name, class_file))
+}
+
+/// The class values of `ty` are instances of once coerced to `dyn Any`, `None` if they share
+/// `java.lang.Object` with other types
+fn downcast_class(tcx: TyCtxt<'_>, ty: Ty<'_>) -> Option<String> {
+    let descriptor = rust_ty_to_jvm_descriptor(ty, tcx);
+    if let Some((wrapper_class, _)) = boxing::wrapper(&descriptor) {
+        return Some(wrapper_class.to_string());
+    }
+    if descriptor == "Ljava/lang/Object;" {
+        return None;
+    }
+    match descriptor.as_bytes().first() {
+        Some(b'L') => Some(descriptor[1..descriptor.len() - 1].to_string()),
+        Some(b'[') => Some(descriptor),
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Pushes `operand` coerced to the unsized `target_ty`: an array as a slice, or a value as a
+    /// trait object. Returns `false` for other coercions, in which case nothing was pushed.
+    pub(crate) fn lower_unsize(&mut self, operand: &Operand<'tcx>, target_ty: Ty<'tcx>) -> bool {
+        let source_ty = operand.ty(self.body, self.tcx);
+        let (Some(source_pointee), Some(target_pointee)) = (pointee(source_ty), pointee(target_ty))
+        else {
+            trace!("      Unsupported unsize coercion to: {target_ty:?}");
+            return false;
+        };
+        match (source_pointee.kind(), principal_trait(target_pointee)) {
+            (TyKind::Array(..), _) if matches!(target_pointee.kind(), TyKind::Slice(_)) => {
+                self.load_operand(operand);
+            }
+            (TyKind::Dynamic(..), Some(trait_def_id)) => {
+                // Upcasting to a supertrait, whose interface the adapters implement too
+                self.load_operand(operand);
+                if trait_def_id.is_local() {
+                    let interface = self
+                        .constant_pool
+                        .add_class(interface_name(self.tcx, trait_def_id))
+                        .expect("Could not add class to constant pool");
+                    self.method_bytecode_instructions
+                        .push(Instruction::Checkcast(interface));
+                }
+            }
+            (_, Some(trait_def_id)) if self.tcx.is_diagnostic_item(sym::Any, trait_def_id) => {
+                // `dyn Any` is any object, primitives boxed
+                self.load_operand(operand);
+                self.box_value(&rust_ty_to_jvm_descriptor(source_ty, self.tcx));
+            }
+            (_, Some(trait_def_id)) if trait_def_id.is_local() => {
+                // new T$Trait(operand)
+                let adapter = self
+                    .constant_pool
+                    .add_class(naming::adapter_class_name(
+                        self.tcx,
+                        source_pointee,
+                        trait_def_id,
+                    ))
+                    .expect("Could not add class to constant pool");
+                let value_descriptor = rust_ty_to_jvm_descriptor(
+                    Ty::new_imm_ref(self.tcx, self.tcx.lifetimes.re_erased, source_pointee),
+                    self.tcx,
+                );
+                let constructor = self
+                    .constant_pool
+                    .add_method_ref(adapter, "<init>", &format!("({value_descriptor})V"))
+                    .expect("Could not add constructor to constant pool");
+                self.method_bytecode_instructions
+                    .extend([Instruction::New(adapter), Instruction::Dup]);
+                self.load_operand(operand);
+                self.method_bytecode_instructions
+                    .push(Instruction::Invokespecial(constructor));
+            }
+            _ => {
+                trace!("      Unsupported unsize coercion from {source_ty:?} to {target_ty:?}");
+                return false;
+            }
+        }
+        trace!("      Generated bytecode: unsize coercion to {target_ty:?}");
+        true
+    }
+
+    /// Lowers calls of trait methods through trait objects to `invokeinterface`, returning
+    /// `false` for any other call
+    pub(crate) fn lower_dyn_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let Some(trait_def_id) = tcx.trait_of_item(callee) else {
+            return false;
+        };
+        let Ok(Some(instance)) =
+            Instance::try_resolve(tcx, TypingEnv::fully_monomorphized(), callee, generic_args)
+        else {
+            return false;
+        };
+        if !matches!(instance.def, InstanceKind::Virtual(..)) || !trait_def_id.is_local() {
+            return false;
+        }
+
+        let interface = self
+            .constant_pool
+            .add_class(interface_name(tcx, trait_def_id))
+            .expect("Could not add class to constant pool");
+        let descriptor = interface_method_descriptor(tcx, callee);
+        let method = self
+            .constant_pool
+            .add_interface_method_ref(interface, tcx.item_name(callee).as_str(), &descriptor)
+            .expect("Could not add interface method ref to constant pool");
+        let mut argument_slots = 0;
+        for arg in args {
+            self.load_operand(&arg.node);
+            argument_slots +=
+                match rust_ty_to_jvm_descriptor(arg.node.ty(self.body, tcx), tcx).as_str() {
+                    "V" => 0,
+                    "J" | "D" => 2,
+                    _ => 1,
+                };
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokeinterface(method, argument_slots));
+        trace!(
+            "      Generated bytecode: dynamic call to {}",
+            tcx.def_path_str(callee)
+        );
+        self.store_call_result(destination);
+        true
+    }
+
+    /// Lowers `is`, `downcast_ref` and `downcast_mut` of `dyn Any` to `instanceof` and
+    /// `checkcast`, returning `false` for any other call
+    pub(crate) fn lower_any_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        if tcx.impl_trait_ref(impl_def_id).is_some()
+            || !principal_trait(tcx.type_of(impl_def_id).instantiate_identity())
+                .is_some_and(|trait_def_id| tcx.is_diagnostic_item(sym::Any, trait_def_id))
+        {
+            return false;
+        }
+        let name = tcx.item_name(callee);
+        if !matches!(name.as_str(), "is" | "downcast_ref" | "downcast_mut") {
+            return false;
+        }
+        let ty = generic_args.type_at(0);
+        // A mutable reference to a primitive is a ref cell, which a `dyn Any` does not hold
+        if name.as_str() == "downcast_mut"
+            && references::is_primitive(&rust_ty_to_jvm_descriptor(ty, tcx))
+        {
+            trace!("      Unsupported downcast_mut to a primitive: {ty:?}");
+            return false;
+        }
+        let Some(class) = downcast_class(tcx, ty) else {
+            trace!("      Unsupported {name} to a type without a class of its own: {ty:?}");
+            return false;
+        };
+        let class = self
+            .constant_pool
+            .add_class(class)
+            .expect("Could not add class to constant pool");
+
+        self.load_operand(&args[0].node);
+        if name.as_str() == "is" {
+            self.method_bytecode_instructions
+                .push(Instruction::Instanceof(class));
+        } else {
+            // value instanceof T ? (T) value : null
+            self.method_bytecode_instructions
+                .extend([Instruction::Dup, Instruction::Instanceof(class)]);
+            let is_instance = self.method_bytecode_instructions.len();
+            self.method_bytecode_instructions.extend([
+                Instruction::Ifne(0),
+                Instruction::Pop,
+                Instruction::Aconst_null,
+                Instruction::Goto(0),
+            ]);
+
+            let cast = self.method_bytecode_instructions.len();
+            let value_descriptor = rust_ty_to_jvm_descriptor(args[0].node.ty(self.body, tcx), tcx);
+            let cast_stack = vec![
+                control_flow::verification_type(&value_descriptor, self.constant_pool)
+                    .expect("Could not add class to constant pool"),
+            ];
+            self.extra_frames.push((cast, cast_stack));
+            self.method_bytecode_instructions
+                .push(Instruction::Checkcast(class));
+
+            let end = self.method_bytecode_instructions.len();
+            let result_descriptor =
+                rust_ty_to_jvm_descriptor(destination.ty(self.body, tcx).ty, tcx);
+            let end_stack = vec![
+                control_flow::verification_type(&result_descriptor, self.constant_pool)
+                    .expect("Could not add class to constant pool"),
+            ];
+            self.extra_frames.push((end, end_stack));
+            self.method_bytecode_instructions[is_instance] = Instruction::Ifne(cast as u16);
+            self.method_bytecode_instructions[is_instance + 3] = Instruction::Goto(end as u16);
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+}
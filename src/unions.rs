@@ -0,0 +1,133 @@
+//! Unions of primitives, as the bits of their fields in a JVM `long`.
+
+use crate::{MirToBytecodeVisitor, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_middle::mir::{Operand, Place, ProjectionElem};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind, TypingEnv};
+use rustc_span::Span;
+
+/// The descriptor of a union of primitives
+pub(crate) const UNION_DESCRIPTOR: &str = "J";
+
+/// Whether `ty` is a union of primitives, held in a `long`
+pub(crate) fn is_primitive_union<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    matches!(ty.kind(), TyKind::Adt(adt_def, _) if adt_def.is_union())
+        && unsupported_field(ty, tcx).is_none()
+}
+
+/// The first field of a union that is not a primitive, `None` if there is none or `ty` is not
+/// a union
+fn unsupported_field<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    let TyKind::Adt(adt_def, generic_args) = ty.kind() else {
+        return None;
+    };
+    if !adt_def.is_union() {
+        return None;
+    }
+    adt_def
+        .all_fields()
+        .map(|field| {
+            tcx.normalize_erasing_regions(
+                TypingEnv::fully_monomorphized(),
+                field.ty(tcx, generic_args),
+            )
+        })
+        .find(|field_ty| field_bits(&rust_ty_to_jvm_descriptor(*field_ty, tcx)).is_none())
+}
+
+/// Reports a union that cannot be held in a `long`, used by a local declared at `span`
+pub(crate) fn check_union<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>, span: Span) {
+    if let Some(field_ty) = unsupported_field(ty, tcx) {
+        tcx.dcx()
+            .struct_span_err(
+                span,
+                format!("the union `{ty}` is not supported on the JVM"),
+            )
+            .with_note(format!(
+                "only unions of primitives are supported, and it has a field of type `{field_ty}`"
+            ))
+            .emit();
+    }
+}
+
+/// The number of bits of a field held in a union, by its descriptor, `None` if it is not a
+/// primitive
+fn field_bits(descriptor: &str) -> Option<u32> {
+    match descriptor {
+        "Z" | "B" => Some(8),
+        "S" | "C" => Some(16),
+        "I" | "F" => Some(32),
+        "J" | "D" => Some(64),
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Whether a place is a field of a union local
+    pub(crate) fn is_union_field(&self, place: &Place<'tcx>) -> bool {
+        matches!(place.projection[..], [ProjectionElem::Field(..)])
+            && is_primitive_union(self.body.local_decls[place.local].ty, self.tcx)
+    }
+
+    /// Pushes a field of a union local, reinterpreting its bits
+    pub(crate) fn load_union_field(&mut self, place: &Place<'tcx>, field_ty: Ty<'tcx>) {
+        self.load_local(place.local);
+        match rust_ty_to_jvm_descriptor(field_ty, self.tcx).as_str() {
+            "J" => {}
+            "D" => self.invoke_static("java/lang/Double", "longBitsToDouble", "(J)D"),
+            "F" => {
+                self.method_bytecode_instructions.push(Instruction::L2i);
+                self.invoke_static("java/lang/Float", "intBitsToFloat", "(I)F");
+            }
+            descriptor => {
+                self.method_bytecode_instructions.push(Instruction::L2i);
+                self.method_bytecode_instructions.extend(match descriptor {
+                    "B" => Some(Instruction::I2b),
+                    "S" => Some(Instruction::I2s),
+                    "C" => Some(Instruction::I2c),
+                    _ => None,
+                });
+            }
+        }
+    }
+
+    /// Stores `operand` into a field of a union local, keeping the bits of the union above it
+    pub(crate) fn store_union_field(&mut self, place: &Place<'tcx>, operand: &Operand<'tcx>) {
+        let descriptor = rust_ty_to_jvm_descriptor(operand.ty(self.body, self.tcx), self.tcx);
+        let bits = field_bits(&descriptor).expect("unions hold primitives");
+        self.load_union_bits(operand);
+        if bits < 64 {
+            // (union & !mask) | bits
+            self.load_local(place.local);
+            let kept = self.long_constant(!((1i64 << bits) - 1));
+            self.method_bytecode_instructions
+                .extend([kept, Instruction::Land, Instruction::Lor]);
+        }
+        self.store_local(place.local);
+    }
+
+    /// Pushes the bits of a primitive operand as a long, zero-extended
+    pub(crate) fn load_union_bits(&mut self, operand: &Operand<'tcx>) {
+        let descriptor = rust_ty_to_jvm_descriptor(operand.ty(self.body, self.tcx), self.tcx);
+        self.load_operand(operand);
+        match descriptor.as_str() {
+            "J" => {}
+            "D" => self.invoke_static("java/lang/Double", "doubleToRawLongBits", "(D)J"),
+            "F" => {
+                self.invoke_static("java/lang/Float", "floatToRawIntBits", "(F)I");
+                self.invoke_static("java/lang/Integer", "toUnsignedLong", "(I)J");
+            }
+            "I" => self.invoke_static("java/lang/Integer", "toUnsignedLong", "(I)J"),
+            "Z" => self.method_bytecode_instructions.push(Instruction::I2l),
+            descriptor => {
+                let bits = field_bits(descriptor).expect("unions hold primitives");
+                let mask = self.int_constant((1 << bits) - 1);
+                self.method_bytecode_instructions.extend([
+                    mask,
+                    Instruction::Iand,
+                    Instruction::I2l,
+                ]);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Diagnostics for the constructs the JVM cannot run.
+
+use crate::{MirToBytecodeVisitor, support};
+use rustc_abi::ExternAbi;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+
+/// Whether functions of the ABI can be lowered to methods
+fn is_supported_abi(abi: ExternAbi) -> bool {
+    matches!(
+        abi,
+        ExternAbi::Rust
+            | ExternAbi::RustCall
+            | ExternAbi::RustCold
+            | ExternAbi::C { .. }
+            | ExternAbi::System { .. }
+    )
+}
+
+/// Reports the crate's `global_asm!` items and the functions with an ABI the JVM has no
+/// equivalent of
+pub(crate) fn check_crate(tcx: TyCtxt<'_>, functions: &[DefId]) {
+    for item_id in tcx.hir_crate_items(()).free_items() {
+        let item = tcx.hir_item(item_id);
+        if matches!(item.kind, rustc_hir::ItemKind::GlobalAsm { .. }) {
+            let module = tcx.def_path_str(item_id.owner_id.to_def_id());
+            if support::record(tcx, item.span, &module, "global-assembly", "`global_asm!`") {
+                continue;
+            }
+            tcx.dcx()
+                .struct_span_err(item.span, "`global_asm!` is not supported on the JVM")
+                .with_help(
+                    "the JVM runs no machine code, move the assembly into a native library \
+                     called through `-C llvm-args=jni`",
+                )
+                .emit();
+        }
+    }
+    for def_id in functions {
+        let abi = tcx.fn_sig(*def_id).skip_binder().abi();
+        if is_supported_abi(abi) {
+            continue;
+        }
+        let function = tcx.def_path_str(*def_id);
+        let span = tcx.def_span(*def_id);
+        if support::record(
+            tcx,
+            span,
+            &function,
+            "abi",
+            format!("the `extern {abi}` ABI"),
+        ) {
+            continue;
+        }
+        tcx.dcx()
+            .struct_span_err(
+                span,
+                format!(
+                    "`{function}` has the `extern {abi}` ABI, which is not supported on the JVM"
+                ),
+            )
+            .with_help("functions on the JVM have the `Rust`, `C` or `system` ABI")
+            .emit();
+    }
+}
+
+impl MirToBytecodeVisitor<'_, '_> {
+    /// Reports the inline assembly of the function
+    pub(crate) fn report_inline_asm(&mut self, span: Span) {
+        if self.record_unsupported("inline-assembly", "`asm!`") {
+            return;
+        }
+        let function = self.tcx.def_path_str(self.instance.def_id());
+        self.tcx
+            .dcx()
+            .struct_span_err(
+                span,
+                format!("inline assembly is not supported on the JVM, in `{function}`"),
+            )
+            .with_help(
+                "the JVM runs no machine code, use a portable implementation or move the \
+                 assembly into a native library called through `-C llvm-args=jni`",
+            )
+            .emit();
+    }
+
+    /// Reports a call of a native function, which needs JNI
+    pub(crate) fn report_native_call(&mut self, callee_def_id: DefId, span: Span) {
+        let callee = self.tcx.def_path_str(callee_def_id);
+        if self.record_unsupported(
+            "native-call",
+            format_args!("calling the native function `{callee}` is not supported without JNI"),
+        ) {
+            return;
+        }
+        let function = self.tcx.def_path_str(self.instance.def_id());
+        self.tcx
+            .dcx()
+            .struct_span_err(
+                span,
+                format!(
+                    "calling the native function `{callee}` is not supported without JNI, in \
+                     `{function}`"
+                ),
+            )
+            .with_span_note(
+                self.tcx.def_span(callee_def_id),
+                "the function is declared in an `extern` block without `#[jvm::import]`",
+            )
+            .with_help(
+                "bind it to its native library with `-C llvm-args=jni`, or import a Java method \
+                 with `#[jvm::import]`",
+            )
+            .emit();
+    }
+}
@@ -0,0 +1,190 @@
+//! `Vec<T>` as `java.util.ArrayList`.
+
+use crate::trace::trace;
+use crate::{MirToBytecodeVisitor, references, rust_ty_to_jvm_descriptor};
+use ristretto_classfile::attributes::Instruction;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Operand, Place};
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TyKind, UintTy};
+use rustc_span::source_map::Spanned;
+use rustc_span::sym;
+
+const ARRAY_LIST: &str = "java/util/ArrayList";
+
+/// The element type of a `Vec`
+fn element_ty<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    match ty.kind() {
+        TyKind::Adt(adt_def, generic_args) if tcx.is_diagnostic_item(sym::Vec, adt_def.did()) => {
+            Some(generic_args.type_at(0))
+        }
+        _ => None,
+    }
+}
+
+impl<'tcx> MirToBytecodeVisitor<'_, 'tcx> {
+    /// Lowers calls of `Vec` methods and of the traits it implements, returning `false` for any
+    /// other call
+    pub(crate) fn lower_vec_call(
+        &mut self,
+        callee: DefId,
+        generic_args: GenericArgsRef<'tcx>,
+        args: &[Spanned<Operand<'tcx>>],
+        destination: &Place<'tcx>,
+    ) -> bool {
+        let tcx = self.tcx;
+        let lang_items = tcx.lang_items();
+        if let Some(trait_def_id) = tcx.trait_of_item(callee) {
+            let Some(element_ty) = element_ty(generic_args.type_at(0), tcx) else {
+                return false;
+            };
+            if Some(trait_def_id) == lang_items.index_trait()
+                || Some(trait_def_id) == lang_items.index_mut_trait()
+            {
+                if !matches!(generic_args.type_at(1).kind(), TyKind::Uint(UintTy::Usize)) {
+                    trace!("      Unsupported index into a Vec: {generic_args:?}");
+                    return false;
+                }
+                // A mutable reference to a primitive is a ref cell, which elements are not kept in
+                if Some(trait_def_id) == lang_items.index_mut_trait()
+                    && references::is_primitive(&rust_ty_to_jvm_descriptor(element_ty, tcx))
+                {
+                    trace!("      Unsupported mutable index into a Vec of: {element_ty:?}");
+                    return false;
+                }
+                self.load_operand(&args[0].node);
+                self.load_usize_as_int(&args[1].node);
+                self.invoke_array_list("get", "(I)Ljava/lang/Object;");
+                // Primitives stay boxed, as they are behind a reference
+                let descriptor = rust_ty_to_jvm_descriptor(element_ty, tcx);
+                if matches!(descriptor.as_bytes().first(), Some(b'L' | b'[')) {
+                    self.cast_from_object(&descriptor);
+                }
+            } else if Some(trait_def_id) == lang_items.clone_trait() {
+                self.new_array_list(Some(&args[0].node), "(Ljava/util/Collection;)V");
+            } else {
+                return false;
+            }
+            trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+            self.store_call_result(destination);
+            return true;
+        }
+
+        let Some(impl_def_id) = tcx.impl_of_method(callee) else {
+            return false;
+        };
+        let vec_ty = tcx.type_of(impl_def_id).instantiate(tcx, generic_args);
+        let Some(element_ty) = element_ty(vec_ty, tcx) else {
+            return false;
+        };
+        let element_descriptor = rust_ty_to_jvm_descriptor(element_ty, tcx);
+        match tcx.item_name(callee).as_str() {
+            "new" => self.new_array_list(None, "()V"),
+            "with_capacity" => self.new_array_list(Some(&args[0].node), "(I)V"),
+            "len" => {
+                self.load_operand(&args[0].node);
+                self.invoke_array_list("size", "()I");
+                self.int_to_usize();
+            }
+            "is_empty" => {
+                self.load_operand(&args[0].node);
+                self.invoke_array_list("isEmpty", "()Z");
+            }
+            "clear" => {
+                self.load_operand(&args[0].node);
+                self.invoke_array_list("clear", "()V");
+            }
+            "push" => {
+                self.load_operand(&args[0].node);
+                self.load_operand(&args[1].node);
+                self.box_value(&element_descriptor);
+                self.invoke_array_list("add", "(Ljava/lang/Object;)Z");
+                self.method_bytecode_instructions.push(Instruction::Pop);
+            }
+            "insert" => {
+                self.load_operand(&args[0].node);
+                self.load_usize_as_int(&args[1].node);
+                self.load_operand(&args[2].node);
+                self.box_value(&element_descriptor);
+                self.invoke_array_list("add", "(ILjava/lang/Object;)V");
+            }
+            "remove" => {
+                self.load_operand(&args[0].node);
+                self.load_usize_as_int(&args[1].node);
+                self.invoke_array_list("remove", "(I)Ljava/lang/Object;");
+                self.cast_from_object(&element_descriptor);
+            }
+            "pop" => return self.lower_vec_pop(&args[0].node, &element_descriptor, destination),
+            _ => {
+                trace!("      Unsupported Vec method: {}", tcx.def_path_str(callee));
+                return false;
+            }
+        }
+        trace!("      Generated bytecode: {}", tcx.def_path_str(callee));
+        self.store_call_result(destination);
+        true
+    }
+
+    /// `pop()` returns an `Option`: `None` if the list is empty, otherwise `Some` of the removed
+    /// last element
+    fn lower_vec_pop(
+        &mut self,
+        vec: &Operand<'tcx>,
+        element_descriptor: &str,
+        destination: &Place<'tcx>,
+    ) -> bool {
+        if !self.is_option_destination(destination) {
+            trace!("      Unsupported destination for Vec::pop: {destination:?}");
+            return false;
+        }
+
+        // if (!list.isEmpty()) { value = list.remove(list.size() - 1); }
+        self.load_operand(vec);
+        self.invoke_array_list("isEmpty", "()Z");
+        let if_empty = self.method_bytecode_instructions.len();
+        self.method_bytecode_instructions.push(Instruction::Ifne(0));
+        self.load_operand(vec);
+        self.load_operand(vec);
+        self.invoke_array_list("size", "()I");
+        self.method_bytecode_instructions
+            .push(Instruction::Iconst_1);
+        self.method_bytecode_instructions.push(Instruction::Isub);
+        self.invoke_array_list("remove", "(I)Ljava/lang/Object;");
+        self.cast_from_object(element_descriptor);
+        self.store_option_result(if_empty, element_descriptor, destination);
+        trace!("      Generated bytecode: Vec::pop");
+        true
+    }
+
+    /// `new ArrayList(argument)`, without an argument if `argument` is `None`
+    pub(crate) fn new_array_list(&mut self, argument: Option<&Operand<'tcx>>, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(ARRAY_LIST)
+            .expect("Could not add class to constant pool");
+        let constructor = self
+            .constant_pool
+            .add_method_ref(class_index, "<init>", descriptor)
+            .expect("Could not add constructor to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::New(class_index));
+        self.method_bytecode_instructions.push(Instruction::Dup);
+        if let Some(argument) = argument {
+            self.load_usize_as_int(argument);
+        }
+        self.method_bytecode_instructions
+            .push(Instruction::Invokespecial(constructor));
+    }
+
+    fn invoke_array_list(&mut self, name: &str, descriptor: &str) {
+        let class_index = self
+            .constant_pool
+            .add_class(ARRAY_LIST)
+            .expect("Could not add class to constant pool");
+        let method = self
+            .constant_pool
+            .add_method_ref(class_index, name, descriptor)
+            .expect("Could not add method ref to constant pool");
+        self.method_bytecode_instructions
+            .push(Instruction::Invokevirtual(method));
+    }
+}
@@ -0,0 +1,604 @@
+//! Verification of lowered methods before they are serialized.
+
+use crate::constant_pool::ConstantPool;
+use crate::control_flow;
+use ristretto_classfile::Constant;
+use ristretto_classfile::attributes::{ExceptionTableEntry, Instruction};
+
+/// The type of a value on the operand stack or in a local, as far as the verifier is concerned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    Int,
+    Float,
+    Long,
+    Double,
+    Reference,
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Value::Int => "int",
+            Value::Float => "float",
+            Value::Long => "long",
+            Value::Double => "double",
+            Value::Reference => "reference",
+        })
+    }
+}
+
+impl Value {
+    /// The type of a value with the given field descriptor, `None` for `V`
+    fn from_descriptor(descriptor: &str) -> Option<Value> {
+        Some(match descriptor.as_bytes().first()? {
+            b'V' => return None,
+            b'J' => Value::Long,
+            b'F' => Value::Float,
+            b'D' => Value::Double,
+            b'L' | b'[' => Value::Reference,
+            _ => Value::Int,
+        })
+    }
+
+    /// Whether the value takes two slots, which matters to the `pop2` and `dup2` families
+    pub(crate) fn is_wide(self) -> bool {
+        matches!(self, Value::Long | Value::Double)
+    }
+}
+
+/// An instruction of a method that does not verify
+pub(crate) struct InvalidCode {
+    /// The index of the offending instruction
+    pub(crate) index: usize,
+    pub(crate) message: String,
+}
+
+/// Checks the code of a method whose slots were allocated with the given descriptors
+pub(crate) fn verify_method(
+    instructions: &[Instruction],
+    exception_table: &[ExceptionTableEntry],
+    slot_descriptors: &[(u16, String)],
+    constant_pool: &ConstantPool,
+) -> Result<(), InvalidCode> {
+    let mut locals = Vec::new();
+    for (slot, descriptor) in slot_descriptors {
+        let slot = usize::from(*slot);
+        if locals.len() <= slot {
+            locals.resize(slot + 1, None);
+        }
+        locals[slot] = Value::from_descriptor(descriptor);
+    }
+
+    let mut verifier = Verifier {
+        instructions,
+        locals,
+        constant_pool,
+        stacks: vec![None; instructions.len()],
+        pending: Vec::new(),
+    };
+    if !instructions.is_empty() {
+        verifier.reach(0, 0, Vec::new())?;
+    }
+    for entry in exception_table {
+        let start = usize::from(entry.range_pc.start);
+        let invalid = |message: String| InvalidCode {
+            index: start.min(instructions.len().saturating_sub(1)),
+            message,
+        };
+        if usize::from(entry.range_pc.end) > instructions.len()
+            || start >= entry.range_pc.end.into()
+        {
+            return Err(invalid(format!(
+                "exception range {:?} is not within the method",
+                entry.range_pc
+            )));
+        }
+        if usize::from(entry.handler_pc) >= instructions.len() {
+            return Err(invalid(format!(
+                "exception handler {} is not within the method",
+                entry.handler_pc
+            )));
+        }
+        verifier.reach(start, entry.handler_pc.into(), vec![Value::Reference])?;
+    }
+    while let Some(index) = verifier.pending.pop() {
+        verifier.step(index)?;
+    }
+    Ok(())
+}
+
+struct Verifier<'a> {
+    instructions: &'a [Instruction],
+    /// The type of every allocated slot, `None` for unallocated slots and the upper half of
+    /// `long`s and `double`s
+    locals: Vec<Option<Value>>,
+    constant_pool: &'a ConstantPool,
+    /// The operand stack every instruction is reached with, once it is reached
+    stacks: Vec<Option<Vec<Value>>>,
+    /// Instructions reached but not followed yet
+    pending: Vec<usize>,
+}
+
+impl Verifier<'_> {
+    /// Records that the instruction at `from` leads to `target` with the given stack
+    fn reach(&mut self, from: usize, target: usize, stack: Vec<Value>) -> Result<(), InvalidCode> {
+        if target >= self.instructions.len() {
+            return Err(InvalidCode {
+                index: from,
+                message: format!("jumps to instruction {target}, past the end of the method"),
+            });
+        }
+        match &self.stacks[target] {
+            Some(existing) if *existing != stack => Err(InvalidCode {
+                index: from,
+                message: format!(
+                    "reaches instruction {target} with the stack {stack:?}, but another path \
+                     reaches it with {existing:?}"
+                ),
+            }),
+            Some(_) => Ok(()),
+            None => {
+                self.stacks[target] = Some(stack);
+                self.pending.push(target);
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies the instruction at `index` to the stack it is reached with, and follows its
+    /// successors
+    fn step(&mut self, index: usize) -> Result<(), InvalidCode> {
+        let instruction = &self.instructions[index];
+        let mut stack = self.stacks[index].clone().unwrap_or_default();
+        self.execute(instruction, &mut stack)
+            .map_err(|message| InvalidCode { index, message })?;
+
+        if let Some(target) = control_flow::branch_target(instruction) {
+            self.reach(index, target.into(), stack.clone())?;
+        }
+        if !control_flow::falls_through(instruction) {
+            return Ok(());
+        }
+        if index + 1 == self.instructions.len() {
+            return Err(InvalidCode {
+                index,
+                message: "falls off the end of the method".to_string(),
+            });
+        }
+        self.reach(index, index + 1, stack)
+    }
+
+    /// Applies the effect of an instruction to the operand stack
+    fn execute(&self, instruction: &Instruction, stack: &mut Vec<Value>) -> Result<(), String> {
+        if let Some((pops, pushes)) = fixed_effect(instruction) {
+            for expected in pops.iter().rev() {
+                pop(stack, *expected)?;
+            }
+            stack.extend_from_slice(pushes);
+            return Ok(());
+        }
+        if let Some((slot, value, is_store)) = local_access(instruction) {
+            match self.locals.get(usize::from(slot)).copied().flatten() {
+                Some(declared) if declared == value => {}
+                Some(declared) => {
+                    return Err(format!(
+                        "accesses slot {slot}, allocated for {declared}, as {value}"
+                    ));
+                }
+                None => return Err(format!("accesses slot {slot}, which is not allocated")),
+            }
+            if is_store {
+                pop(stack, value)?;
+            } else {
+                stack.push(value);
+            }
+            return Ok(());
+        }
+        match instruction {
+            Instruction::Iinc(slot, _) => self.check_int_slot(u16::from(*slot)),
+            Instruction::Iinc_w(slot, _) => self.check_int_slot(*slot),
+            Instruction::Ldc(index) => {
+                stack.push(self.loadable_constant(u16::from(*index))?);
+                Ok(())
+            }
+            Instruction::Ldc_w(index) | Instruction::Ldc2_w(index) => {
+                stack.push(self.loadable_constant(*index)?);
+                Ok(())
+            }
+            Instruction::Getstatic(index) => {
+                stack.extend(self.field_type(*index)?);
+                Ok(())
+            }
+            Instruction::Putstatic(index) => {
+                if let Some(value) = self.field_type(*index)? {
+                    pop(stack, value)?;
+                }
+                Ok(())
+            }
+            Instruction::Getfield(index) => {
+                pop(stack, Value::Reference)?;
+                stack.extend(self.field_type(*index)?);
+                Ok(())
+            }
+            Instruction::Putfield(index) => {
+                if let Some(value) = self.field_type(*index)? {
+                    pop(stack, value)?;
+                }
+                pop(stack, Value::Reference)
+            }
+            Instruction::Invokestatic(index) => self.invoke(stack, *index, false),
+            Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokeinterface(index, _) => self.invoke(stack, *index, true),
+            Instruction::Multianewarray(_, dimensions) => {
+                for _ in 0..*dimensions {
+                    pop(stack, Value::Int)?;
+                }
+                stack.push(Value::Reference);
+                Ok(())
+            }
+            Instruction::Pop => {
+                pop_narrow(stack)?;
+                Ok(())
+            }
+            Instruction::Pop2 => {
+                if pop_any(stack)?.is_wide() {
+                    return Ok(());
+                }
+                pop_narrow(stack)?;
+                Ok(())
+            }
+            Instruction::Dup => {
+                let value = pop_narrow(stack)?;
+                stack.extend([value, value]);
+                Ok(())
+            }
+            Instruction::Dup_x1 => {
+                let first = pop_narrow(stack)?;
+                let second = pop_narrow(stack)?;
+                stack.extend([first, second, first]);
+                Ok(())
+            }
+            Instruction::Dup_x2 => {
+                let first = pop_narrow(stack)?;
+                let second = pop_any(stack)?;
+                if second.is_wide() {
+                    stack.extend([first, second, first]);
+                } else {
+                    let third = pop_narrow(stack)?;
+                    stack.extend([first, third, second, first]);
+                }
+                Ok(())
+            }
+            Instruction::Dup2 => {
+                let first = pop_any(stack)?;
+                if first.is_wide() {
+                    stack.extend([first, first]);
+                } else {
+                    let second = pop_narrow(stack)?;
+                    stack.extend([second, first, second, first]);
+                }
+                Ok(())
+            }
+            Instruction::Swap => {
+                let first = pop_narrow(stack)?;
+                let second = pop_narrow(stack)?;
+                stack.extend([first, second]);
+                Ok(())
+            }
+            _ => Err(format!("`{instruction:?}` is never generated")),
+        }
+    }
+
+    fn check_int_slot(&self, slot: u16) -> Result<(), String> {
+        match self.locals.get(usize::from(slot)).copied().flatten() {
+            Some(Value::Int) => Ok(()),
+            Some(declared) => Err(format!("increments slot {slot}, allocated for {declared}")),
+            None => Err(format!("increments slot {slot}, which is not allocated")),
+        }
+    }
+
+    /// The type of the constant an `ldc` pushes
+    fn loadable_constant(&self, index: u16) -> Result<Value, String> {
+        match self.constant_pool.get(index) {
+            Some(Constant::Integer(_)) => Ok(Value::Int),
+            Some(Constant::Float(_)) => Ok(Value::Float),
+            Some(Constant::Long(_)) => Ok(Value::Long),
+            Some(Constant::Double(_)) => Ok(Value::Double),
+            Some(Constant::String(_) | Constant::Class(_) | Constant::MethodType(_)) => {
+                Ok(Value::Reference)
+            }
+            other => Err(format!("loads constant #{index}, which is {other:?}")),
+        }
+    }
+
+    fn field_type(&self, index: u16) -> Result<Option<Value>, String> {
+        Ok(Value::from_descriptor(member_descriptor(
+            self.constant_pool,
+            index,
+        )?))
+    }
+
+    /// Pops the arguments of a call, and its receiver for instance methods, and pushes its result
+    fn invoke(&self, stack: &mut Vec<Value>, index: u16, has_receiver: bool) -> Result<(), String> {
+        let descriptor = member_descriptor(self.constant_pool, index)?;
+        let Some((arguments, return_descriptor)) = descriptor
+            .strip_prefix('(')
+            .and_then(|descriptor| descriptor.split_once(')'))
+        else {
+            return Err(format!("calls a method with the descriptor `{descriptor}`"));
+        };
+        for argument in argument_types(arguments).iter().rev() {
+            pop(stack, *argument)
+                .map_err(|message| format!("{message}, calling `{descriptor}`"))?;
+        }
+        if has_receiver {
+            pop(stack, Value::Reference)
+                .map_err(|message| format!("{message} for the receiver, calling `{descriptor}`"))?;
+        }
+        stack.extend(Value::from_descriptor(return_descriptor));
+        Ok(())
+    }
+}
+
+/// The descriptor of the field or method a constant refers to
+pub(crate) fn member_descriptor(constant_pool: &ConstantPool, index: u16) -> Result<&str, String> {
+    let name_and_type_index = match constant_pool.get(index) {
+        Some(
+            Constant::FieldRef {
+                name_and_type_index,
+                ..
+            }
+            | Constant::MethodRef {
+                name_and_type_index,
+                ..
+            }
+            | Constant::InterfaceMethodRef {
+                name_and_type_index,
+                ..
+            },
+        ) => *name_and_type_index,
+        other => return Err(format!("refers to constant #{index}, which is {other:?}")),
+    };
+    let Some(Constant::NameAndType {
+        descriptor_index, ..
+    }) = constant_pool.get(name_and_type_index)
+    else {
+        return Err(format!(
+            "refers to constant #{index}, which has no descriptor"
+        ));
+    };
+    match constant_pool.get(*descriptor_index) {
+        Some(Constant::Utf8(descriptor)) => Ok(descriptor),
+        _ => Err(format!(
+            "refers to constant #{index}, which has no descriptor"
+        )),
+    }
+}
+
+/// The types of the arguments in the parameter list of a method descriptor
+pub(crate) fn argument_types(arguments: &str) -> Vec<Value> {
+    let mut types = Vec::new();
+    let mut rest = arguments;
+    while !rest.is_empty() {
+        let dimensions = rest.len() - rest.trim_start_matches('[').len();
+        let element = &rest[dimensions..];
+        let length = if element.starts_with('L') {
+            element.find(';').map_or(element.len(), |end| end + 1)
+        } else {
+            1
+        };
+        let value = if dimensions > 0 {
+            Value::Reference
+        } else {
+            Value::from_descriptor(element).unwrap_or(Value::Int)
+        };
+        types.push(value);
+        rest = &element[length.min(element.len())..];
+    }
+    types
+}
+
+fn pop_any(stack: &mut Vec<Value>) -> Result<Value, String> {
+    stack
+        .pop()
+        .ok_or_else(|| "pops from an empty operand stack".to_string())
+}
+
+/// Pops a value that is not a `long` or a `double`
+fn pop_narrow(stack: &mut Vec<Value>) -> Result<Value, String> {
+    let value = pop_any(stack)?;
+    if value.is_wide() {
+        return Err(format!("expects a single slot value but finds {value}"));
+    }
+    Ok(value)
+}
+
+fn pop(stack: &mut Vec<Value>, expected: Value) -> Result<(), String> {
+    match stack.pop() {
+        Some(value) if value == expected => Ok(()),
+        Some(value) => Err(format!("expects {expected} but finds {value}")),
+        None => Err(format!("expects {expected} on an empty operand stack")),
+    }
+}
+
+/// The slot an instruction loads or stores, with the type of the slot and whether it is a store
+pub(crate) fn local_access(instruction: &Instruction) -> Option<(u16, Value, bool)> {
+    use Value::{Double, Float, Int, Long, Reference};
+    Some(match instruction {
+        Instruction::Iload(slot) => (u16::from(*slot), Int, false),
+        Instruction::Lload(slot) => (u16::from(*slot), Long, false),
+        Instruction::Fload(slot) => (u16::from(*slot), Float, false),
+        Instruction::Dload(slot) => (u16::from(*slot), Double, false),
+        Instruction::Aload(slot) => (u16::from(*slot), Reference, false),
+        Instruction::Istore(slot) => (u16::from(*slot), Int, true),
+        Instruction::Lstore(slot) => (u16::from(*slot), Long, true),
+        Instruction::Fstore(slot) => (u16::from(*slot), Float, true),
+        Instruction::Dstore(slot) => (u16::from(*slot), Double, true),
+        Instruction::Astore(slot) => (u16::from(*slot), Reference, true),
+        Instruction::Iload_w(slot) => (*slot, Int, false),
+        Instruction::Lload_w(slot) => (*slot, Long, false),
+        Instruction::Fload_w(slot) => (*slot, Float, false),
+        Instruction::Dload_w(slot) => (*slot, Double, false),
+        Instruction::Aload_w(slot) => (*slot, Reference, false),
+        Instruction::Istore_w(slot) => (*slot, Int, true),
+        Instruction::Lstore_w(slot) => (*slot, Long, true),
+        Instruction::Fstore_w(slot) => (*slot, Float, true),
+        Instruction::Dstore_w(slot) => (*slot, Double, true),
+        Instruction::Astore_w(slot) => (*slot, Reference, true),
+        Instruction::Iload_0 => (0, Int, false),
+        Instruction::Iload_1 => (1, Int, false),
+        Instruction::Iload_2 => (2, Int, false),
+        Instruction::Iload_3 => (3, Int, false),
+        Instruction::Lload_0 => (0, Long, false),
+        Instruction::Lload_1 => (1, Long, false),
+        Instruction::Lload_2 => (2, Long, false),
+        Instruction::Lload_3 => (3, Long, false),
+        Instruction::Fload_0 => (0, Float, false),
+        Instruction::Fload_1 => (1, Float, false),
+        Instruction::Fload_2 => (2, Float, false),
+        Instruction::Fload_3 => (3, Float, false),
+        Instruction::Dload_0 => (0, Double, false),
+        Instruction::Dload_1 => (1, Double, false),
+        Instruction::Dload_2 => (2, Double, false),
+        Instruction::Dload_3 => (3, Double, false),
+        Instruction::Aload_0 => (0, Reference, false),
+        Instruction::Aload_1 => (1, Reference, false),
+        Instruction::Aload_2 => (2, Reference, false),
+        Instruction::Aload_3 => (3, Reference, false),
+        Instruction::Istore_0 => (0, Int, true),
+        Instruction::Istore_1 => (1, Int, true),
+        Instruction::Istore_2 => (2, Int, true),
+        Instruction::Istore_3 => (3, Int, true),
+        Instruction::Lstore_0 => (0, Long, true),
+        Instruction::Lstore_1 => (1, Long, true),
+        Instruction::Lstore_2 => (2, Long, true),
+        Instruction::Lstore_3 => (3, Long, true),
+        Instruction::Fstore_0 => (0, Float, true),
+        Instruction::Fstore_1 => (1, Float, true),
+        Instruction::Fstore_2 => (2, Float, true),
+        Instruction::Fstore_3 => (3, Float, true),
+        Instruction::Dstore_0 => (0, Double, true),
+        Instruction::Dstore_1 => (1, Double, true),
+        Instruction::Dstore_2 => (2, Double, true),
+        Instruction::Dstore_3 => (3, Double, true),
+        Instruction::Astore_0 => (0, Reference, true),
+        Instruction::Astore_1 => (1, Reference, true),
+        Instruction::Astore_2 => (2, Reference, true),
+        Instruction::Astore_3 => (3, Reference, true),
+        _ => return None,
+    })
+}
+
+/// The operands an instruction pops (deepest first) and the values it pushes, for instructions
+/// whose effect does not depend on the constant pool or the locals
+fn fixed_effect(instruction: &Instruction) -> Option<(&'static [Value], &'static [Value])> {
+    use Value::{Double, Float, Int, Long, Reference};
+    Some(match instruction {
+        Instruction::Nop => (&[], &[]),
+        Instruction::Aconst_null | Instruction::New(_) => (&[], &[Reference]),
+        Instruction::Iconst_m1
+        | Instruction::Iconst_0
+        | Instruction::Iconst_1
+        | Instruction::Iconst_2
+        | Instruction::Iconst_3
+        | Instruction::Iconst_4
+        | Instruction::Iconst_5
+        | Instruction::Bipush(_)
+        | Instruction::Sipush(_) => (&[], &[Int]),
+        Instruction::Lconst_0 | Instruction::Lconst_1 => (&[], &[Long]),
+        Instruction::Fconst_0 | Instruction::Fconst_1 | Instruction::Fconst_2 => (&[], &[Float]),
+        Instruction::Dconst_0 | Instruction::Dconst_1 => (&[], &[Double]),
+        Instruction::Iaload | Instruction::Baload | Instruction::Caload | Instruction::Saload => {
+            (&[Reference, Int], &[Int])
+        }
+        Instruction::Laload => (&[Reference, Int], &[Long]),
+        Instruction::Faload => (&[Reference, Int], &[Float]),
+        Instruction::Daload => (&[Reference, Int], &[Double]),
+        Instruction::Aaload => (&[Reference, Int], &[Reference]),
+        Instruction::Iastore
+        | Instruction::Bastore
+        | Instruction::Castore
+        | Instruction::Sastore => (&[Reference, Int, Int], &[]),
+        Instruction::Lastore => (&[Reference, Int, Long], &[]),
+        Instruction::Fastore => (&[Reference, Int, Float], &[]),
+        Instruction::Dastore => (&[Reference, Int, Double], &[]),
+        Instruction::Aastore => (&[Reference, Int, Reference], &[]),
+        Instruction::Iadd
+        | Instruction::Isub
+        | Instruction::Imul
+        | Instruction::Idiv
+        | Instruction::Irem
+        | Instruction::Ishl
+        | Instruction::Ishr
+        | Instruction::Iushr
+        | Instruction::Iand
+        | Instruction::Ior
+        | Instruction::Ixor => (&[Int, Int], &[Int]),
+        Instruction::Ladd
+        | Instruction::Lsub
+        | Instruction::Lmul
+        | Instruction::Ldiv
+        | Instruction::Lrem
+        | Instruction::Land
+        | Instruction::Lor
+        | Instruction::Lxor => (&[Long, Long], &[Long]),
+        Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => (&[Long, Int], &[Long]),
+        Instruction::Fadd
+        | Instruction::Fsub
+        | Instruction::Fmul
+        | Instruction::Fdiv
+        | Instruction::Frem => (&[Float, Float], &[Float]),
+        Instruction::Dadd
+        | Instruction::Dsub
+        | Instruction::Dmul
+        | Instruction::Ddiv
+        | Instruction::Drem => (&[Double, Double], &[Double]),
+        Instruction::Ineg | Instruction::I2b | Instruction::I2c | Instruction::I2s => {
+            (&[Int], &[Int])
+        }
+        Instruction::Lneg => (&[Long], &[Long]),
+        Instruction::Fneg => (&[Float], &[Float]),
+        Instruction::Dneg => (&[Double], &[Double]),
+        Instruction::I2l => (&[Int], &[Long]),
+        Instruction::I2f => (&[Int], &[Float]),
+        Instruction::I2d => (&[Int], &[Double]),
+        Instruction::L2i => (&[Long], &[Int]),
+        Instruction::L2f => (&[Long], &[Float]),
+        Instruction::L2d => (&[Long], &[Double]),
+        Instruction::F2i => (&[Float], &[Int]),
+        Instruction::F2l => (&[Float], &[Long]),
+        Instruction::F2d => (&[Float], &[Double]),
+        Instruction::D2i => (&[Double], &[Int]),
+        Instruction::D2l => (&[Double], &[Long]),
+        Instruction::D2f => (&[Double], &[Float]),
+        Instruction::Lcmp => (&[Long, Long], &[Int]),
+        Instruction::Fcmpl | Instruction::Fcmpg => (&[Float, Float], &[Int]),
+        Instruction::Dcmpl | Instruction::Dcmpg => (&[Double, Double], &[Int]),
+        Instruction::Ifeq(_)
+        | Instruction::Ifne(_)
+        | Instruction::Iflt(_)
+        | Instruction::Ifge(_)
+        | Instruction::Ifgt(_)
+        | Instruction::Ifle(_) => (&[Int], &[]),
+        Instruction::If_icmpeq(_)
+        | Instruction::If_icmpne(_)
+        | Instruction::If_icmplt(_)
+        | Instruction::If_icmpge(_)
+        | Instruction::If_icmpgt(_)
+        | Instruction::If_icmple(_) => (&[Int, Int], &[]),
+        Instruction::If_acmpeq(_) | Instruction::If_acmpne(_) => (&[Reference, Reference], &[]),
+        Instruction::Ifnull(_) | Instruction::Ifnonnull(_) => (&[Reference], &[]),
+        Instruction::Goto(_) | Instruction::Return => (&[], &[]),
+        Instruction::Ireturn => (&[Int], &[]),
+        Instruction::Lreturn => (&[Long], &[]),
+        Instruction::Freturn => (&[Float], &[]),
+        Instruction::Dreturn => (&[Double], &[]),
+        Instruction::Areturn | Instruction::Athrow => (&[Reference], &[]),
+        Instruction::Newarray(_) | Instruction::Anewarray(_) => (&[Int], &[Reference]),
+        Instruction::Arraylength | Instruction::Instanceof(_) => (&[Reference], &[Int]),
+        Instruction::Checkcast(_) => (&[Reference], &[Reference]),
+        Instruction::Monitorenter | Instruction::Monitorexit => (&[Reference], &[]),
+        _ => return None,
+    })
+}
@@ -0,0 +1,34 @@
+//! The JVM access of generated methods, after the Rust visibility of their functions.
+
+use ristretto_classfile::MethodAccessFlags;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{Instance, InstanceKind, TyCtxt};
+
+/// Whether a function is part of the crate's API, reachable by its path from the crate root or
+/// exported by its symbol
+pub(crate) fn is_api(tcx: TyCtxt<'_>, def_id: DefId) -> bool {
+    let Some(local_def_id) = def_id.as_local() else {
+        return true;
+    };
+    tcx.effective_visibilities(()).is_exported(local_def_id)
+        || tcx.codegen_fn_attrs(def_id).contains_extern_indicator()
+}
+
+/// The access of the method of a function, `public` for the crate's API and package-private
+/// otherwise
+pub(crate) fn fn_access_flags(tcx: TyCtxt<'_>, def_id: DefId) -> MethodAccessFlags {
+    if is_api(tcx, def_id) {
+        MethodAccessFlags::PUBLIC
+    } else {
+        MethodAccessFlags::empty()
+    }
+}
+
+/// The access of the method of an instance: package-private, and synthetic unless it is the body
+/// of a function
+pub(crate) fn instance_access_flags(instance: Instance<'_>) -> MethodAccessFlags {
+    match instance.def {
+        InstanceKind::Item(_) => MethodAccessFlags::empty(),
+        _ => MethodAccessFlags::SYNTHETIC,
+    }
+}
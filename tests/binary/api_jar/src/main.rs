@@ -0,0 +1,97 @@
+//! Geometry for Java callers.
+
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+/// A point on the grid.
+///
+/// Both coordinates count from the top left corner.
+#[jvm::export]
+pub struct Point {
+    /// The column of the point
+    pub x: i32,
+    /// The row of the point
+    pub y: i32,
+}
+
+impl Point {
+    /// The point at `(x, y)`
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+}
+
+/// The number of steps from `from` to `to`, moving along the grid only.
+pub fn steps(from: &Point, to: &Point) -> i32 {
+    (to.x - from.x) + (to.y - from.y)
+}
+
+fn main() {
+    match steps(&Point::new(1, 2), &Point::new(4, 6)) {
+        7 => {}
+        _ => unsafe { illegal_state("(1, 2) is not 7 steps from (4, 6)") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "sub"]
+trait Sub<Rhs = Self> {
+    type Output;
+
+    fn sub(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Sub for i32 {
+    type Output = i32;
+
+    fn sub(self, rhs: i32) -> i32 {
+        self - rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
+
+#[lang = "panic_const_sub_overflow"]
+fn panic_const_sub_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+const SIZE: usize = 4;
+
+// The length is evaluated for each instance, as 3 for `filled::<3>`
+fn filled<const N: usize>(value: i32) -> [i32; N] {
+    [value; N]
+}
+
+fn last_of_size() -> i32 {
+    let values = [9; SIZE];
+    values[3]
+}
+
+fn main() {
+    let values = filled::<3>(7);
+    match values[2] {
+        7 => {}
+        _ => unsafe { illegal_state("[7; 3] does not end with 7") },
+    }
+    match last_of_size() {
+        9 => {}
+        _ => unsafe { illegal_state("[9; SIZE] does not end with 9") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for usize {}
+
+#[lang = "panic_bounds_check"]
+fn panic_bounds_check(_index: usize, _len: usize) -> ! {
+    loop {}
+}
@@ -0,0 +1,127 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+async fn answer() -> i32 {
+    42
+}
+
+async fn next_answer() -> i32 {
+    answer().await + 1
+}
+
+// An executor, which the backend recognizes by its name and lowers to polling the future once
+fn block_on<F: Future>(_future: F) -> F::Output {
+    loop {}
+}
+
+fn main() {
+    match block_on(next_answer()) {
+        43 => {}
+        _ => unsafe { illegal_state("the awaited answer is not 42") },
+    }
+}
+
+// The lang items `async` bodies and `.await` are desugared into
+#[lang = "future_trait"]
+pub trait Future {
+    #[lang = "future_output"]
+    type Output;
+
+    #[lang = "poll"]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output>;
+}
+
+pub trait IntoFuture {
+    type Output;
+    type IntoFuture: Future<Output = Self::Output>;
+
+    #[lang = "into_future"]
+    fn into_future(self) -> Self::IntoFuture;
+}
+
+impl<F: Future> IntoFuture for F {
+    type Output = F::Output;
+    type IntoFuture = F;
+
+    fn into_future(self) -> F {
+        self
+    }
+}
+
+#[lang = "Poll"]
+pub enum Poll<T> {
+    #[lang = "Ready"]
+    Ready(T),
+    #[lang = "Pending"]
+    Pending,
+}
+
+#[lang = "Context"]
+pub struct Context<'a> {
+    _waker: &'a (),
+}
+
+#[lang = "ResumeTy"]
+pub struct ResumeTy(*mut Context<'static>);
+
+#[lang = "get_context"]
+unsafe fn get_context<'a, 'b>(_cx: ResumeTy) -> &'a mut Context<'b> {
+    loop {}
+}
+
+#[lang = "pin"]
+pub struct Pin<Ptr> {
+    pointer: Ptr,
+}
+
+impl<Ptr> Pin<Ptr> {
+    #[lang = "new_unchecked"]
+    pub unsafe fn new_unchecked(pointer: Ptr) -> Pin<Ptr> {
+        Pin { pointer }
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
+impl<Ptr: LegacyReceiver> LegacyReceiver for Pin<Ptr> {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,142 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn next_id(counter: &AtomicI32) -> i32 {
+    counter.fetch_add(1, Ordering::SeqCst)
+}
+
+fn publish(flag: &AtomicBool) -> bool {
+    flag.store(true, Ordering::Release);
+    flag.load(Ordering::Acquire)
+}
+
+fn claimed(counter: &AtomicI32, expected: i32) -> bool {
+    match counter.compare_exchange(expected, 100, Ordering::AcqRel, Ordering::Acquire) {
+        Result::Ok(_) => true,
+        Result::Err(_) => false,
+    }
+}
+
+fn main() {
+    let counter = AtomicI32::new(7);
+    match next_id(&counter) {
+        7 => {}
+        _ => unsafe { illegal_state("fetch_add() did not return the previous value") },
+    }
+    match counter.load(Ordering::Relaxed) {
+        8 => {}
+        _ => unsafe { illegal_state("fetch_add() did not add") },
+    }
+    match counter.swap(1, Ordering::SeqCst) {
+        8 => {}
+        _ => unsafe { illegal_state("swap() did not return the previous value") },
+    }
+    match claimed(&counter, 5) {
+        false => {}
+        true => unsafe { illegal_state("compare_exchange() replaced another value") },
+    }
+    match counter.load(Ordering::SeqCst) {
+        1 => {}
+        _ => unsafe { illegal_state("a failed compare_exchange() changed the value") },
+    }
+    match claimed(&counter, 1) {
+        true => {}
+        false => unsafe { illegal_state("compare_exchange() did not replace the value") },
+    }
+    match counter.load(Ordering::Acquire) {
+        100 => {}
+        _ => unsafe { illegal_state("compare_exchange() did not store the new value") },
+    }
+    match publish(&AtomicBool::new(false)) {
+        true => {}
+        false => unsafe { illegal_state("the stored flag was not loaded") },
+    }
+}
+
+// Stand in for `core::sync::atomic`'s types, which the backend recognizes by their diagnostic
+// items and whose methods it lowers itself
+pub enum Ordering {
+    Relaxed,
+    Release,
+    Acquire,
+    AcqRel,
+    SeqCst,
+}
+
+#[rustc_diagnostic_item = "AtomicI32"]
+pub struct AtomicI32(i32);
+
+impl AtomicI32 {
+    pub fn new(_value: i32) -> AtomicI32 {
+        loop {}
+    }
+
+    pub fn load(&self, _order: Ordering) -> i32 {
+        loop {}
+    }
+
+    pub fn swap(&self, _value: i32, _order: Ordering) -> i32 {
+        loop {}
+    }
+
+    pub fn fetch_add(&self, _value: i32, _order: Ordering) -> i32 {
+        loop {}
+    }
+
+    pub fn compare_exchange(
+        &self,
+        _current: i32,
+        _new: i32,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<i32, i32> {
+        loop {}
+    }
+}
+
+#[rustc_diagnostic_item = "AtomicBool"]
+pub struct AtomicBool(bool);
+
+impl AtomicBool {
+    pub fn new(_value: bool) -> AtomicBool {
+        loop {}
+    }
+
+    pub fn load(&self, _order: Ordering) -> bool {
+        loop {}
+    }
+
+    pub fn store(&self, _value: bool, _order: Ordering) {
+        loop {}
+    }
+}
+
+pub enum Result<T, E> {
+    Ok(T),
+    Err(E),
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,31 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn identity(value: isize) -> isize {
+    value
+}
+
+fn main() {
+    match identity(42) {
+        42 => {}
+        _ => unsafe { illegal_state("42 is not 42 as an isize") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for isize {}
@@ -0,0 +1,380 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn and_u8(left: u8, right: u8) -> u8 {
+    left & right
+}
+
+fn or_i16(left: i16, right: i16) -> i16 {
+    left | right
+}
+
+fn xor_u64(left: u64, right: u64) -> u64 {
+    left ^ right
+}
+
+fn shl_u8(value: u8, amount: u32) -> u8 {
+    value << amount
+}
+
+fn shr_u8(value: u8, amount: u32) -> u8 {
+    value >> amount
+}
+
+fn shr_i8(value: i8, amount: u32) -> i8 {
+    value >> amount
+}
+
+fn shr_u32(value: u32, amount: u32) -> u32 {
+    value >> amount
+}
+
+fn shr_i64(value: i64, amount: u32) -> i64 {
+    value >> amount
+}
+
+fn not_u8(value: u8) -> u8 {
+    !value
+}
+
+fn not_bool(value: bool) -> bool {
+    !value
+}
+
+fn not_i64(value: i64) -> i64 {
+    !value
+}
+
+fn neg_i16(value: i16) -> i16 {
+    -value
+}
+
+fn u8_as_u32(value: u8) -> u32 {
+    value as u32
+}
+
+fn i8_as_u32(value: i8) -> u32 {
+    value as u32
+}
+
+fn u32_as_u64(value: u32) -> u64 {
+    value as u64
+}
+
+fn i32_as_i64(value: i32) -> i64 {
+    value as i64
+}
+
+fn u64_as_u8(value: u64) -> u8 {
+    value as u8
+}
+
+fn u16_as_i8(value: u16) -> i8 {
+    value as i8
+}
+
+fn u8_as_i16(value: u8) -> i16 {
+    value as i16
+}
+
+fn bool_as_u8(value: bool) -> u8 {
+    value as u8
+}
+
+fn main() {
+    match and_u8(0xF0, 0x3C) {
+        0x30 => {}
+        _ => unsafe { illegal_state("0xF0u8 & 0x3C is not 0x30") },
+    }
+    match or_i16(-256, 0x0F) {
+        -241 => {}
+        _ => unsafe { illegal_state("-256i16 | 0x0F is not -241") },
+    }
+    match xor_u64(0xFFFF_FFFF_FFFF_FFFF, 1) {
+        0xFFFF_FFFF_FFFF_FFFE => {}
+        _ => unsafe { illegal_state("u64::MAX ^ 1 is not u64::MAX - 1") },
+    }
+    match shl_u8(0x81, 1) {
+        0x02 => {}
+        _ => unsafe { illegal_state("0x81u8 << 1 is not 0x02") },
+    }
+    match shr_u8(0x80, 7) {
+        1 => {}
+        _ => unsafe { illegal_state("0x80u8 >> 7 is not 1") },
+    }
+    match shr_i8(-128, 7) {
+        -1 => {}
+        _ => unsafe { illegal_state("-128i8 >> 7 is not -1") },
+    }
+    match shr_u32(0x8000_0000, 31) {
+        1 => {}
+        _ => unsafe { illegal_state("0x80000000u32 >> 31 is not 1") },
+    }
+    match shr_i64(-8, 1) {
+        -4 => {}
+        _ => unsafe { illegal_state("-8i64 >> 1 is not -4") },
+    }
+    match not_u8(0x0F) {
+        0xF0 => {}
+        _ => unsafe { illegal_state("!0x0Fu8 is not 0xF0") },
+    }
+    match not_bool(false) {
+        true => {}
+        _ => unsafe { illegal_state("!false is not true") },
+    }
+    match not_i64(0) {
+        -1 => {}
+        _ => unsafe { illegal_state("!0i64 is not -1") },
+    }
+    match neg_i16(300) {
+        -300 => {}
+        _ => unsafe { illegal_state("-300i16 is not -300") },
+    }
+    match u8_as_u32(0xFF) {
+        255 => {}
+        _ => unsafe { illegal_state("0xFFu8 as u32 is not 255") },
+    }
+    match i8_as_u32(-1) {
+        0xFFFF_FFFF => {}
+        _ => unsafe { illegal_state("-1i8 as u32 is not u32::MAX") },
+    }
+    match u32_as_u64(0xFFFF_FFFF) {
+        0xFFFF_FFFF => {}
+        _ => unsafe { illegal_state("u32::MAX as u64 is not 0xFFFFFFFF") },
+    }
+    match i32_as_i64(-1) {
+        -1 => {}
+        _ => unsafe { illegal_state("-1i32 as i64 is not -1") },
+    }
+    match u64_as_u8(0x102) {
+        2 => {}
+        _ => unsafe { illegal_state("0x102u64 as u8 is not 2") },
+    }
+    match u16_as_i8(0xFF80) {
+        -128 => {}
+        _ => unsafe { illegal_state("0xFF80u16 as i8 is not -128") },
+    }
+    match u8_as_i16(200) {
+        200 => {}
+        _ => unsafe { illegal_state("200u8 as i16 is not 200") },
+    }
+    match bool_as_u8(true) {
+        1 => {}
+        _ => unsafe { illegal_state("true as u8 is not 1") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for u8 {}
+impl Copy for i8 {}
+impl Copy for u16 {}
+impl Copy for i16 {}
+impl Copy for u32 {}
+impl Copy for i32 {}
+impl Copy for u64 {}
+impl Copy for i64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "bitand"]
+trait BitAnd<Rhs = Self> {
+    type Output;
+
+    fn bitand(self, rhs: Rhs) -> Self::Output;
+}
+
+impl BitAnd for u8 {
+    type Output = u8;
+
+    fn bitand(self, rhs: u8) -> u8 {
+        self & rhs
+    }
+}
+
+#[lang = "bitor"]
+trait BitOr<Rhs = Self> {
+    type Output;
+
+    fn bitor(self, rhs: Rhs) -> Self::Output;
+}
+
+impl BitOr for i16 {
+    type Output = i16;
+
+    fn bitor(self, rhs: i16) -> i16 {
+        self | rhs
+    }
+}
+
+#[lang = "bitxor"]
+trait BitXor<Rhs = Self> {
+    type Output;
+
+    fn bitxor(self, rhs: Rhs) -> Self::Output;
+}
+
+impl BitXor for u64 {
+    type Output = u64;
+
+    fn bitxor(self, rhs: u64) -> u64 {
+        self ^ rhs
+    }
+}
+
+#[lang = "shl"]
+trait Shl<Rhs = Self> {
+    type Output;
+
+    fn shl(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Shl<u32> for u8 {
+    type Output = u8;
+
+    fn shl(self, rhs: u32) -> u8 {
+        self << rhs
+    }
+}
+
+#[lang = "shr"]
+trait Shr<Rhs = Self> {
+    type Output;
+
+    fn shr(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Shr<u32> for u8 {
+    type Output = u8;
+
+    fn shr(self, rhs: u32) -> u8 {
+        self >> rhs
+    }
+}
+
+impl Shr<u32> for i8 {
+    type Output = i8;
+
+    fn shr(self, rhs: u32) -> i8 {
+        self >> rhs
+    }
+}
+
+impl Shr<u32> for u32 {
+    type Output = u32;
+
+    fn shr(self, rhs: u32) -> u32 {
+        self >> rhs
+    }
+}
+
+impl Shr<u32> for i64 {
+    type Output = i64;
+
+    fn shr(self, rhs: u32) -> i64 {
+        self >> rhs
+    }
+}
+
+#[lang = "not"]
+trait Not {
+    type Output;
+
+    fn not(self) -> Self::Output;
+}
+
+impl Not for bool {
+    type Output = bool;
+
+    fn not(self) -> bool {
+        !self
+    }
+}
+
+impl Not for u8 {
+    type Output = u8;
+
+    fn not(self) -> u8 {
+        !self
+    }
+}
+
+impl Not for i64 {
+    type Output = i64;
+
+    fn not(self) -> i64 {
+        !self
+    }
+}
+
+#[lang = "neg"]
+trait Neg {
+    type Output;
+
+    fn neg(self) -> Self::Output;
+}
+
+impl Neg for i8 {
+    type Output = i8;
+
+    fn neg(self) -> i8 {
+        -self
+    }
+}
+
+impl Neg for i16 {
+    type Output = i16;
+
+    fn neg(self) -> i16 {
+        -self
+    }
+}
+
+impl Neg for i32 {
+    type Output = i32;
+
+    fn neg(self) -> i32 {
+        -self
+    }
+}
+
+impl Neg for i64 {
+    type Output = i64;
+
+    fn neg(self) -> i64 {
+        -self
+    }
+}
+
+#[lang = "panic_const_shl_overflow"]
+fn panic_const_shl_overflow() -> ! {
+    loop {}
+}
+
+#[lang = "panic_const_shr_overflow"]
+fn panic_const_shr_overflow() -> ! {
+    loop {}
+}
+
+#[lang = "panic_const_neg_overflow"]
+fn panic_const_neg_overflow() -> ! {
+    loop {}
+}
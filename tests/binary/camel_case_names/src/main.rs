@@ -0,0 +1,55 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+pub fn add_numbers(left: i32, right: i32) -> i32 {
+    left + right
+}
+
+fn double_it(value: i32) -> i32 {
+    value + value
+}
+
+fn main() {
+    match double_it(add_numbers(20, 1)) {
+        42 => {}
+        _ => unsafe { illegal_state("(20 + 1) * 2 is not 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,158 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+}
+
+fn replace_count(count: &Cell<i32>) -> i32 {
+    count.set(3);
+    count.replace(4)
+}
+
+fn borrowed_x(point: &RefCell<Point>) -> i32 {
+    let shared = point.borrow();
+    shared.x
+}
+
+fn mutably_borrowed_x(point: &RefCell<Point>) -> i32 {
+    let exclusive = point.borrow_mut();
+    exclusive.x
+}
+
+fn main() {
+    let count = Cell::new(1);
+    match replace_count(&count) {
+        3 => {}
+        _ => unsafe { illegal_state("replace() did not return the value set") },
+    }
+    match count.get() {
+        4 => {}
+        _ => unsafe { illegal_state("replace() did not set the value") },
+    }
+
+    // The first borrow is released when its guard is dropped, or borrowing mutably would panic
+    let point = RefCell::new(Point { x: 2 });
+    match borrowed_x(&point) {
+        2 => {}
+        _ => unsafe { illegal_state("a borrow does not read the value") },
+    }
+    match mutably_borrowed_x(&point) {
+        2 => {}
+        _ => unsafe { illegal_state("a mutable borrow does not read the value") },
+    }
+}
+
+// Stand in for `core::cell`'s types, which the backend recognizes by their diagnostic items and
+// whose methods it lowers itself
+#[rustc_diagnostic_item = "Cell"]
+pub struct Cell<T>(T);
+
+impl<T> Cell<T> {
+    pub fn new(_value: T) -> Cell<T> {
+        loop {}
+    }
+
+    pub fn get(&self) -> T {
+        loop {}
+    }
+
+    pub fn set(&self, _value: T) {
+        loop {}
+    }
+
+    pub fn replace(&self, _value: T) -> T {
+        loop {}
+    }
+}
+
+#[rustc_diagnostic_item = "RefCell"]
+pub struct RefCell<T>(T);
+
+impl<T> RefCell<T> {
+    pub fn new(_value: T) -> RefCell<T> {
+        loop {}
+    }
+
+    pub fn borrow(&self) -> Ref<T> {
+        loop {}
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<T> {
+        loop {}
+    }
+}
+
+#[rustc_diagnostic_item = "RefCellRef"]
+pub struct Ref<T>(T);
+
+impl<T> Deref for Ref<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        loop {}
+    }
+}
+
+impl<T> Drop for Ref<T> {
+    fn drop(&mut self) {}
+}
+
+#[rustc_diagnostic_item = "RefCellRefMut"]
+pub struct RefMut<T>(T);
+
+impl<T> Deref for RefMut<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        loop {}
+    }
+}
+
+impl<T> Drop for RefMut<T> {
+    fn drop(&mut self) {}
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "deref"]
+trait Deref {
+    #[lang = "deref_target"]
+    type Target: ?Sized;
+
+    fn deref(&self) -> &Self::Target;
+}
+
+#[lang = "drop"]
+trait Drop {
+    fn drop(&mut self);
+}
+
+#[lang = "drop_in_place"]
+unsafe fn drop_in_place<T: ?Sized>(to_drop: *mut T) {
+    unsafe { drop_in_place(to_drop) }
+}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
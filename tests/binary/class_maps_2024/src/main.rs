@@ -0,0 +1,36 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+// The crate, module and class names end in digits, which look like the hashes of the class files
+mod v8 {
+    #[jvm::export]
+    pub struct Cafe1 {
+        pub beans: i32,
+    }
+
+    impl Cafe1 {
+        pub fn beans(&self) -> i32 {
+            self.beans
+        }
+    }
+}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
@@ -0,0 +1,209 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn less_u32(left: u32, right: u32) -> bool {
+    left < right
+}
+
+fn greater_u64(left: u64, right: u64) -> bool {
+    left > right
+}
+
+fn less_i32(left: i32, right: i32) -> bool {
+    left < right
+}
+
+fn greater_u8(left: u8, right: u8) -> bool {
+    left > right
+}
+
+fn equal_i32(left: i32, right: i32) -> bool {
+    left == right
+}
+
+fn main() {
+    match less_u32(0, 0xFFFF_FFFF) {
+        true => {}
+        _ => unsafe { illegal_state("0u32 < u32::MAX") },
+    }
+    match less_u32(0xFFFF_FFFF, 0) {
+        false => {}
+        _ => unsafe { illegal_state("u32::MAX < 0u32") },
+    }
+    match greater_u64(0xFFFF_FFFF_FFFF_FFFF, 1) {
+        true => {}
+        _ => unsafe { illegal_state("u64::MAX > 1u64") },
+    }
+    match less_i32(-2147483648, 2147483647) {
+        true => {}
+        _ => unsafe { illegal_state("i32::MIN < i32::MAX") },
+    }
+    match less_i32(2147483647, -2147483648) {
+        false => {}
+        _ => unsafe { illegal_state("i32::MAX < i32::MIN") },
+    }
+    match greater_u8(200, 100) {
+        true => {}
+        _ => unsafe { illegal_state("200u8 > 100u8") },
+    }
+    match equal_i32(-2147483648, 2147483647) {
+        false => {}
+        _ => unsafe { illegal_state("i32::MIN == i32::MAX") },
+    }
+    match equal_i32(7, 7) {
+        true => {}
+        _ => unsafe { illegal_state("7 == 7") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for i32 {}
+impl Copy for u8 {}
+impl Copy for u32 {}
+impl Copy for u64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "neg"]
+trait Neg {
+    type Output;
+
+    fn neg(self) -> Self::Output;
+}
+
+impl Neg for i32 {
+    type Output = i32;
+
+    fn neg(self) -> i32 {
+        -self
+    }
+}
+
+#[lang = "eq"]
+trait PartialEq<Rhs: ?Sized = Self> {
+    fn eq(&self, other: &Rhs) -> bool;
+    fn ne(&self, other: &Rhs) -> bool;
+}
+
+#[lang = "partial_ord"]
+trait PartialOrd<Rhs: ?Sized = Self>: PartialEq<Rhs> {
+    fn lt(&self, other: &Rhs) -> bool;
+    fn le(&self, other: &Rhs) -> bool;
+    fn gt(&self, other: &Rhs) -> bool;
+    fn ge(&self, other: &Rhs) -> bool;
+}
+
+impl PartialEq for i32 {
+    fn eq(&self, other: &i32) -> bool {
+        (*self) == (*other)
+    }
+    fn ne(&self, other: &i32) -> bool {
+        (*self) != (*other)
+    }
+}
+
+impl PartialOrd for i32 {
+    fn lt(&self, other: &i32) -> bool {
+        (*self) < (*other)
+    }
+    fn le(&self, other: &i32) -> bool {
+        (*self) <= (*other)
+    }
+    fn gt(&self, other: &i32) -> bool {
+        (*self) > (*other)
+    }
+    fn ge(&self, other: &i32) -> bool {
+        (*self) >= (*other)
+    }
+}
+
+impl PartialEq for u8 {
+    fn eq(&self, other: &u8) -> bool {
+        (*self) == (*other)
+    }
+    fn ne(&self, other: &u8) -> bool {
+        (*self) != (*other)
+    }
+}
+
+impl PartialOrd for u8 {
+    fn lt(&self, other: &u8) -> bool {
+        (*self) < (*other)
+    }
+    fn le(&self, other: &u8) -> bool {
+        (*self) <= (*other)
+    }
+    fn gt(&self, other: &u8) -> bool {
+        (*self) > (*other)
+    }
+    fn ge(&self, other: &u8) -> bool {
+        (*self) >= (*other)
+    }
+}
+
+impl PartialEq for u32 {
+    fn eq(&self, other: &u32) -> bool {
+        (*self) == (*other)
+    }
+    fn ne(&self, other: &u32) -> bool {
+        (*self) != (*other)
+    }
+}
+
+impl PartialOrd for u32 {
+    fn lt(&self, other: &u32) -> bool {
+        (*self) < (*other)
+    }
+    fn le(&self, other: &u32) -> bool {
+        (*self) <= (*other)
+    }
+    fn gt(&self, other: &u32) -> bool {
+        (*self) > (*other)
+    }
+    fn ge(&self, other: &u32) -> bool {
+        (*self) >= (*other)
+    }
+}
+
+impl PartialEq for u64 {
+    fn eq(&self, other: &u64) -> bool {
+        (*self) == (*other)
+    }
+    fn ne(&self, other: &u64) -> bool {
+        (*self) != (*other)
+    }
+}
+
+impl PartialOrd for u64 {
+    fn lt(&self, other: &u64) -> bool {
+        (*self) < (*other)
+    }
+    fn le(&self, other: &u64) -> bool {
+        (*self) <= (*other)
+    }
+    fn gt(&self, other: &u64) -> bool {
+        (*self) > (*other)
+    }
+    fn ge(&self, other: &u64) -> bool {
+        (*self) >= (*other)
+    }
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.Math")]
+unsafe extern "C" {
+    fn abs(value: i32) -> i32;
+}
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn magnitude(value: i32) -> i32 {
+    unsafe { abs(value) }
+}
+
+fn distance(from: i32, to: i32) -> i32 {
+    unsafe { abs(to - from) }
+}
+
+fn main() {
+    match magnitude(7) {
+        7 => {}
+        _ => unsafe { illegal_state("the magnitude of 7 is not 7") },
+    }
+    match distance(50, 8) {
+        42 => {}
+        _ => unsafe { illegal_state("the distance from 50 to 8 is not 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "sub"]
+trait Sub<Rhs = Self> {
+    type Output;
+
+    fn sub(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Sub for i32 {
+    type Output = i32;
+
+    fn sub(self, rhs: i32) -> i32 {
+        self - rhs
+    }
+}
+
+#[lang = "panic_const_sub_overflow"]
+fn panic_const_sub_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+struct Point {
+    x: i32,
+}
+
+struct Wrapper<'a> {
+    inner: &'a Point,
+}
+
+// Both dereference a reference read through another one, which MIR copies out first
+fn deref_twice(value: &&i32) -> i32 {
+    **value
+}
+
+fn inner_x(wrapper: &Wrapper) -> i32 {
+    wrapper.inner.x
+}
+
+fn main() {
+    let answer = 42;
+    let reference = &answer;
+    match deref_twice(&reference) {
+        42 => {}
+        _ => unsafe { illegal_state("the reference to a reference to 42 is not 42") },
+    }
+    let point = Point { x: 7 };
+    let wrapper = Wrapper { inner: &point };
+    match inner_x(&wrapper) {
+        7 => {}
+        _ => unsafe { illegal_state("the point held by the wrapper is not at x = 7") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
@@ -0,0 +1,102 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(coroutines)]
+#![feature(stmt_expr_attributes)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn sum_of_yields() -> i32 {
+    let mut counter = #[coroutine]
+    || {
+        yield 1;
+        yield 2;
+        3
+    };
+    let mut sum = 0;
+    loop {
+        match unsafe { Pin::new_unchecked(&mut counter) }.resume(()) {
+            CoroutineState::Yielded(value) => sum = sum + value,
+            CoroutineState::Complete(value) => return sum + value,
+        }
+    }
+}
+
+fn main() {
+    match sum_of_yields() {
+        6 => {}
+        _ => unsafe { illegal_state("the coroutine did not yield 1 and 2 and return 3") },
+    }
+}
+
+// The lang items coroutines are desugared into
+#[lang = "coroutine"]
+pub trait Coroutine<R = ()> {
+    #[lang = "coroutine_yield"]
+    type Yield;
+    #[lang = "coroutine_return"]
+    type Return;
+
+    #[lang = "coroutine_resume"]
+    fn resume(self: Pin<&mut Self>, arg: R) -> CoroutineState<Self::Yield, Self::Return>;
+}
+
+#[lang = "coroutine_state"]
+pub enum CoroutineState<Y, R> {
+    Yielded(Y),
+    Complete(R),
+}
+
+#[lang = "pin"]
+pub struct Pin<Ptr> {
+    pointer: Ptr,
+}
+
+impl<Ptr> Pin<Ptr> {
+    #[lang = "new_unchecked"]
+    pub unsafe fn new_unchecked(pointer: Ptr) -> Pin<Ptr> {
+        Pin { pointer }
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
+impl<Ptr: LegacyReceiver> LegacyReceiver for Pin<Ptr> {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+pub fn sum(left: i32, right: i32) -> i32 {
+    left + right
+}
+
+// The lang items of the whole crate graph
+#[lang = "sized"]
+pub trait Sized {}
+
+#[lang = "copy"]
+pub trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "add"]
+pub trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
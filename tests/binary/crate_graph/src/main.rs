@@ -0,0 +1,19 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// `numbers` is built with the backend too, and its classes are linked into the JAR from its rlib
+fn main() {
+    match numbers::sum(40, 2) {
+        42 => {}
+        _ => unsafe { illegal_state("40 + 2 is not 42") },
+    }
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// Calls the crate class of `numbers` in its own package
+fn main() {
+    match numbers::sum(40, 2) {
+        42 => {}
+        _ => unsafe { illegal_state("40 + 2 is not 42") },
+    }
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+#[unsafe(export_name = "addNumbers")]
+pub fn add_numbers(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[unsafe(no_mangle)]
+pub fn sub_numbers(a: i32, b: i32) -> i32 {
+    a - b
+}
+
+fn main() {
+    let sum = add_numbers(1, 2);
+    sub_numbers(sum, 3);
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
@@ -0,0 +1,2 @@
+cargo clean
+cargo build --target ../../../jvm-unknown-unknown.json
@@ -0,0 +1,52 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+    y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    #[jvm::annotation("java.beans.Transient", value = true)]
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    pub fn set_y(&mut self, y: i32) {
+        self.y = y;
+    }
+}
+
+trait Runnable {
+    fn run(&self);
+}
+
+#[jvm::implements("java.lang.Runnable")]
+impl Runnable for Point {
+    fn run(&self) {}
+}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
@@ -0,0 +1,53 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+#[jvm::accessors]
+pub struct Account {
+    pub balance: i64,
+    pub frozen: bool,
+    #[jvm::readonly]
+    pub owner_id: i32,
+    pin: i32,
+}
+
+pub fn open(owner_id: i32, pin: i32) -> Account {
+    Account {
+        balance: 0,
+        frozen: false,
+        owner_id,
+        pin,
+    }
+}
+
+fn main() {
+    let account = open(42, 1234);
+    match account.owner_id {
+        42 => {}
+        _ => unsafe { illegal_state("the account does not belong to 42") },
+    }
+    match account.pin {
+        1234 => {}
+        _ => unsafe { illegal_state("the pin of the account is not 1234") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for i64 {}
+impl Copy for bool {}
@@ -0,0 +1,59 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Counter {
+    pub count: i32,
+}
+
+fn bump(value: &mut i32) {
+    *value = *value + 1;
+}
+
+// The field is copied into a one-element array for `bump`, and written back after the call
+fn main() {
+    let mut counter = Counter { count: 41 };
+    bump(&mut counter.count);
+    match counter.count {
+        42 => {}
+        _ => unsafe { illegal_state("bumping the count of 41 does not give 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,109 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![rustc_coherence_is_core]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// Compares the results in Java, whatever the lowering of float comparisons
+#[jvm::import("java.lang.Double")]
+unsafe extern "C" {
+    #[link_name = "compare"]
+    fn compare_f64(left: f64, right: f64) -> i32;
+}
+
+#[jvm::import("java.lang.Float")]
+unsafe extern "C" {
+    #[link_name = "compare"]
+    fn compare_f32(left: f32, right: f32) -> i32;
+}
+
+fn hypotenuse(a: f64, b: f64) -> f64 {
+    (a.powf(2.0) + b.powf(2.0)).sqrt()
+}
+
+fn root_f32(value: f32) -> f32 {
+    value.sqrt()
+}
+
+fn main() {
+    match unsafe { compare_f64(hypotenuse(3.0, 4.0), 5.0) } {
+        0 => {}
+        _ => unsafe { illegal_state("the hypotenuse of 3 and 4 is not 5") },
+    }
+    match unsafe { compare_f32(root_f32(2.25), 1.5) } {
+        0 => {}
+        _ => unsafe { illegal_state("the square root of 2.25f32 is not 1.5") },
+    }
+    match unsafe { compare_f64(2.5f64.floor(), 2.0) } {
+        0 => {}
+        _ => unsafe { illegal_state("2.5f64.floor() is not 2") },
+    }
+    match unsafe { compare_f64(1.0f64.max(3.0), 3.0) } {
+        0 => {}
+        _ => unsafe { illegal_state("1f64.max(3) is not 3") },
+    }
+}
+
+// Stand in for the float methods of `std`, which the backend lowers to `java.lang.Math` itself
+impl f64 {
+    pub fn sqrt(self) -> f64 {
+        loop {}
+    }
+
+    pub fn powf(self, _n: f64) -> f64 {
+        loop {}
+    }
+
+    pub fn floor(self) -> f64 {
+        loop {}
+    }
+
+    pub fn max(self, _other: f64) -> f64 {
+        loop {}
+    }
+}
+
+impl f32 {
+    pub fn sqrt(self) -> f32 {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for f32 {}
+impl Copy for f64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for f64 {
+    type Output = f64;
+
+    fn add(self, rhs: f64) -> f64 {
+        self + rhs
+    }
+}
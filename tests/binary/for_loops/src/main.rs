@@ -0,0 +1,141 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn sum(end: i32) -> i32 {
+    let mut total = 0;
+    for i in 0..end {
+        total = total + i;
+    }
+    total
+}
+
+fn count_up_to(end: i64) -> i64 {
+    let mut count = 0;
+    for _ in 0..end {
+        count = count + 1;
+    }
+    count
+}
+
+fn main() {
+    match sum(5) {
+        10 => {}
+        _ => unsafe { illegal_state("the sum of 0..5 is not 10") },
+    }
+    match sum(0) {
+        0 => {}
+        _ => unsafe { illegal_state("0..0 is not empty") },
+    }
+    match count_up_to(3) {
+        3 => {}
+        _ => unsafe { illegal_state("0..3 does not have 3 elements") },
+    }
+}
+
+// What `for` loops are desugared into, which the backend lowers for ranges of integers
+#[lang = "Range"]
+pub struct Range<Idx> {
+    pub start: Idx,
+    pub end: Idx,
+}
+
+#[lang = "Option"]
+pub enum Option<T> {
+    #[lang = "None"]
+    None,
+    #[lang = "Some"]
+    Some(T),
+}
+
+trait Iterator {
+    type Item;
+
+    #[lang = "next"]
+    fn next(&mut self) -> Option<Self::Item>;
+}
+
+impl Iterator for Range<i32> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        loop {}
+    }
+}
+
+impl Iterator for Range<i64> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        loop {}
+    }
+}
+
+trait IntoIterator {
+    type Item;
+    type IntoIter: Iterator<Item = Self::Item>;
+
+    #[lang = "into_iter"]
+    fn into_iter(self) -> Self::IntoIter;
+}
+
+impl<I: Iterator> IntoIterator for I {
+    type Item = I::Item;
+    type IntoIter = I;
+
+    fn into_iter(self) -> I {
+        self
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for i64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+impl Add for i64 {
+    type Output = i64;
+
+    fn add(self, rhs: i64) -> i64 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// Functions of the C and system ABIs are lowered like any other, only calling foreign functions
+// of those ABIs needs JNI
+extern "C" fn twice(value: i32) -> i32 {
+    value + value
+}
+
+extern "system" fn plus_one(value: i32) -> i32 {
+    value + 1
+}
+
+fn main() {
+    match plus_one(twice(20)) {
+        41 => {}
+        _ => unsafe { illegal_state("twice 20 plus 1 is not 41") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
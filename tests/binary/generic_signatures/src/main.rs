@@ -0,0 +1,55 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+// Stands in for `alloc::vec::Vec`, which the backend recognizes by its diagnostic item
+#[rustc_diagnostic_item = "Vec"]
+pub struct Vec<T>(T);
+
+impl<T> Vec<T> {
+    pub fn new() -> Vec<T> {
+        loop {}
+    }
+
+    pub fn push(&mut self, _value: T) {
+        loop {}
+    }
+
+    pub fn len(&self) -> usize {
+        loop {}
+    }
+}
+
+pub fn squares() -> Vec<i32> {
+    let mut squares = Vec::new();
+    squares.push(1);
+    squares.push(4);
+    squares.push(9);
+    squares
+}
+
+pub fn length(values: &Vec<i32>) -> usize {
+    values.len()
+}
+
+// Only the signatures are checked, the stand-in `Vec` is not run
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for usize {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
@@ -0,0 +1,134 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![rustc_coherence_is_core]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn checked_mul_or(left: u8, right: u8, default: u8) -> u8 {
+    match left.checked_mul(right) {
+        Option::Some(product) => product,
+        Option::None => default,
+    }
+}
+
+fn i64_mul_overflows(left: i64, right: i64) -> bool {
+    left.overflowing_mul(right).1
+}
+
+fn u64_mul_overflows(left: u64, right: u64) -> bool {
+    left.overflowing_mul(right).1
+}
+
+fn main() {
+    match 250u8.wrapping_add(10) {
+        4 => {}
+        _ => unsafe { illegal_state("250u8.wrapping_add(10) is not 4") },
+    }
+    match 10u8.saturating_sub(20) {
+        0 => {}
+        _ => unsafe { illegal_state("10u8.saturating_sub(20) is not 0") },
+    }
+    match 0x7FFF_FFFF_FFFF_FFFFi64.saturating_add(1) {
+        0x7FFF_FFFF_FFFF_FFFF => {}
+        _ => unsafe { illegal_state("i64::MAX.saturating_add(1) is not i64::MAX") },
+    }
+    match checked_mul_or(16, 15, 0) {
+        240 => {}
+        _ => unsafe { illegal_state("16u8.checked_mul(15) is not Some(240)") },
+    }
+    match checked_mul_or(16, 16, 0) {
+        0 => {}
+        _ => unsafe { illegal_state("16u8.checked_mul(16) is not None") },
+    }
+    match 0xFFFF_FFFFu32.overflowing_add(2) {
+        (1, true) => {}
+        _ => unsafe { illegal_state("u32::MAX.overflowing_add(2) is not (1, true)") },
+    }
+    match i64_mul_overflows(0x1_0000_0000, 0x8000_0000) {
+        true => {}
+        false => unsafe { illegal_state("2^32 * 2^31 did not overflow i64") },
+    }
+    match i64_mul_overflows(0x1_0000_0000, 0x7FFF_FFFF) {
+        false => {}
+        true => unsafe { illegal_state("2^32 * (2^31 - 1) overflowed i64") },
+    }
+    match u64_mul_overflows(0x1_0000_0000, 0x1_0000_0000) {
+        true => {}
+        false => unsafe { illegal_state("2^32 * 2^32 did not overflow u64") },
+    }
+    match u64_mul_overflows(0xFFFF_FFFF, 0xFFFF_FFFF) {
+        false => {}
+        true => unsafe { illegal_state("(2^32 - 1) * (2^32 - 1) overflowed u64") },
+    }
+}
+
+// Stand in for the integer methods of `core`, which the backend lowers itself
+impl u8 {
+    pub fn wrapping_add(self, _rhs: u8) -> u8 {
+        loop {}
+    }
+
+    pub fn saturating_sub(self, _rhs: u8) -> u8 {
+        loop {}
+    }
+
+    pub fn checked_mul(self, _rhs: u8) -> Option<u8> {
+        loop {}
+    }
+}
+
+impl u32 {
+    pub fn overflowing_add(self, _rhs: u32) -> (u32, bool) {
+        loop {}
+    }
+}
+
+impl i64 {
+    pub fn saturating_add(self, _rhs: i64) -> i64 {
+        loop {}
+    }
+
+    pub fn overflowing_mul(self, _rhs: i64) -> (i64, bool) {
+        loop {}
+    }
+}
+
+impl u64 {
+    pub fn overflowing_mul(self, _rhs: u64) -> (u64, bool) {
+        loop {}
+    }
+}
+
+#[lang = "Option"]
+pub enum Option<T> {
+    #[lang = "None"]
+    None,
+    #[lang = "Some"]
+    Some(T),
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for u8 {}
+impl Copy for u32 {}
+impl Copy for i64 {}
+impl Copy for u64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,150 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn classify_u8(value: u8) -> i32 {
+    match value {
+        0 => 0,
+        128 => 1,
+        255 => 2,
+        _ => 3,
+    }
+}
+
+fn classify_i8(value: i8) -> i32 {
+    match value {
+        -128 => 0,
+        -1 => 1,
+        127 => 2,
+        _ => 3,
+    }
+}
+
+fn classify_u16(value: u16) -> i32 {
+    match value {
+        0x8000 => 0,
+        0xFFFF => 1,
+        _ => 2,
+    }
+}
+
+fn classify_u64(value: u64) -> i32 {
+    match value {
+        0x1_0000_0000 => 0,
+        0xFFFF_FFFF_FFFF_FFFF => 1,
+        _ => 2,
+    }
+}
+
+fn classify_i64(value: i64) -> i32 {
+    match value {
+        -0x8000_0000_0000 => 0,
+        0x7FFF_FFFF_FFFF => 1,
+        _ => 2,
+    }
+}
+
+fn main() {
+    match classify_u8(255) {
+        2 => {}
+        _ => unsafe { illegal_state("255u8 did not match 255") },
+    }
+    match classify_u8(128) {
+        1 => {}
+        _ => unsafe { illegal_state("128u8 did not match 128") },
+    }
+    match classify_u8(127) {
+        3 => {}
+        _ => unsafe { illegal_state("127u8 matched another arm") },
+    }
+    match classify_i8(-1) {
+        1 => {}
+        _ => unsafe { illegal_state("-1i8 did not match -1") },
+    }
+    match classify_i8(-128) {
+        0 => {}
+        _ => unsafe { illegal_state("-128i8 did not match -128") },
+    }
+    match classify_i8(127) {
+        2 => {}
+        _ => unsafe { illegal_state("127i8 did not match 127") },
+    }
+    match classify_u16(0xFFFF) {
+        1 => {}
+        _ => unsafe { illegal_state("0xFFFFu16 did not match 0xFFFF") },
+    }
+    match classify_u16(0x8000) {
+        0 => {}
+        _ => unsafe { illegal_state("0x8000u16 did not match 0x8000") },
+    }
+    match classify_u64(0xFFFF_FFFF_FFFF_FFFF) {
+        1 => {}
+        _ => unsafe { illegal_state("u64::MAX did not match u64::MAX") },
+    }
+    match classify_u64(0x1_0000_0000) {
+        0 => {}
+        _ => unsafe { illegal_state("2^32 as u64 did not match 2^32") },
+    }
+    match classify_u64(0) {
+        2 => {}
+        _ => unsafe { illegal_state("0u64 matched another arm") },
+    }
+    match classify_i64(-0x8000_0000_0000) {
+        0 => {}
+        _ => unsafe { illegal_state("-2^47 as i64 did not match -2^47") },
+    }
+    match classify_i64(0x7FFF_FFFF_FFFF) {
+        1 => {}
+        _ => unsafe { illegal_state("2^47 - 1 as i64 did not match 2^47 - 1") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for u8 {}
+impl Copy for i8 {}
+impl Copy for u16 {}
+impl Copy for u64 {}
+impl Copy for i64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "neg"]
+trait Neg {
+    type Output;
+
+    fn neg(self) -> Self::Output;
+}
+
+impl Neg for i8 {
+    type Output = i8;
+
+    fn neg(self) -> i8 {
+        -self
+    }
+}
+
+impl Neg for i64 {
+    type Output = i64;
+
+    fn neg(self) -> i64 {
+        -self
+    }
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(intrinsics)]
+#![feature(register_tool)]
+#![allow(internal_features)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[rustc_intrinsic]
+const fn ctpop<T: Copy>(x: T) -> u32;
+
+#[rustc_intrinsic]
+const fn ctlz<T: Copy>(x: T) -> u32;
+
+#[rustc_intrinsic]
+const fn bswap<T: Copy>(x: T) -> T;
+
+#[rustc_intrinsic]
+const unsafe fn transmute<Src, Dst>(src: Src) -> Dst;
+
+#[rustc_intrinsic]
+fn black_box<T>(x: T) -> T;
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn main() {
+    match ctpop(black_box(0b1011_i32)) {
+        3 => {}
+        _ => unsafe { illegal_state("ctpop") },
+    }
+    match ctlz(black_box(1_u8)) {
+        7 => {}
+        _ => unsafe { illegal_state("ctlz of a u8") },
+    }
+    match bswap(black_box(0x1234_5678_i32)) {
+        0x7856_3412 => {}
+        _ => unsafe { illegal_state("bswap") },
+    }
+    match unsafe { transmute::<f32, u32>(black_box(1.0_f32)) } {
+        0x3f80_0000 => {}
+        _ => unsafe { illegal_state("transmute") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for u8 {}
+impl Copy for u32 {}
+impl Copy for f32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
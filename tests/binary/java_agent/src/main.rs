@@ -0,0 +1,18 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+// Called with the options of `-javaagent:java_agent.jar=<options>`, before `main`
+#[jvm::premain]
+fn start(_options: &str) {}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
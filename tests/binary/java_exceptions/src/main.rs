@@ -0,0 +1,55 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+pub enum Result<T, E> {
+    Ok(T),
+    Err(E),
+}
+
+#[jvm::import("java.lang.Integer")]
+unsafe extern "C" {
+    #[link_name = "parseInt"]
+    #[jvm::catch("java.lang.NumberFormatException")]
+    fn parse_int(s: &str) -> Result<i32, &'static str>;
+}
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn parse_or(s: &str, default: i32) -> i32 {
+    match unsafe { parse_int(s) } {
+        Result::Ok(value) => value,
+        Result::Err(_) => default,
+    }
+}
+
+fn main() {
+    match parse_or("42", 0) {
+        42 => {}
+        _ => unsafe { illegal_state("valid number was not parsed") },
+    }
+    match parse_or("forty-two", 7) {
+        7 => {}
+        _ => unsafe { illegal_state("invalid number was parsed") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
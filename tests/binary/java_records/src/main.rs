@@ -0,0 +1,77 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![allow(internal_features)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+// Stands in for `#[derive(PartialEq)]`, whose expansion names `::core`
+#[automatically_derived]
+impl PartialEq for Point {
+    fn eq(&self, other: &Point) -> bool {
+        self.x == other.x && self.y == other.y
+    }
+
+    fn ne(&self, other: &Point) -> bool {
+        match self.eq(other) {
+            true => false,
+            false => true,
+        }
+    }
+}
+
+pub fn origin() -> Point {
+    Point { x: 0, y: 0 }
+}
+
+fn main() {
+    match origin() == (Point { x: 0, y: 0 }) {
+        true => {}
+        false => unsafe { illegal_state("the origin is not (0, 0)") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for bool {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "eq"]
+#[rustc_diagnostic_item = "PartialEq"]
+trait PartialEq<Rhs: ?Sized = Self> {
+    fn eq(&self, other: &Rhs) -> bool;
+    fn ne(&self, other: &Rhs) -> bool;
+}
+
+impl PartialEq for i32 {
+    fn eq(&self, other: &i32) -> bool {
+        (*self) == (*other)
+    }
+
+    fn ne(&self, other: &i32) -> bool {
+        (*self) != (*other)
+    }
+}
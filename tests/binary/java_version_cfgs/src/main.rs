@@ -0,0 +1,52 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// Class files for Java 11 have the features of Java 11 and older
+#[cfg(all(target_vendor = "jvm", target_feature = "java8", target_feature = "java11"))]
+fn has_java11() -> bool {
+    true
+}
+
+#[cfg(not(all(target_vendor = "jvm", target_feature = "java8", target_feature = "java11")))]
+fn has_java11() -> bool {
+    false
+}
+
+#[cfg(target_feature = "java17")]
+fn has_java17() -> bool {
+    true
+}
+
+#[cfg(not(target_feature = "java17"))]
+fn has_java17() -> bool {
+    false
+}
+
+fn main() {
+    match has_java11() {
+        true => {}
+        false => unsafe { illegal_state("the java11 feature is not enabled") },
+    }
+    match has_java17() {
+        false => {}
+        true => unsafe { illegal_state("the java17 feature is enabled") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
@@ -0,0 +1,15 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+// Bound through JNI as `Java_jni_1natives_checksum`. No library is linked, so the crate class
+// loads none, and the program runs as long as it does not call the function
+unsafe extern "C" {
+    fn checksum(value: i32) -> i32;
+}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
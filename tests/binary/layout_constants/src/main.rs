@@ -0,0 +1,50 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(intrinsics)]
+#![feature(register_tool)]
+#![allow(internal_features)]
+#![register_tool(jvm)]
+#![no_core]
+
+// MIR has calls of the layout intrinsics as nullary operators
+#[rustc_intrinsic]
+const fn size_of<T>() -> usize;
+
+#[rustc_intrinsic]
+const fn align_of<T>() -> usize;
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// 16 bytes aligned on 8, with the padding after `first`
+struct Pair {
+    first: i32,
+    second: i64,
+}
+
+fn main() {
+    match size_of::<i64>() {
+        8 => {}
+        _ => unsafe { illegal_state("an i64 is not 8 bytes") },
+    }
+    match size_of::<Pair>() {
+        16 => {}
+        _ => unsafe { illegal_state("a pair of an i32 and an i64 is not 16 bytes") },
+    }
+    match align_of::<Pair>() {
+        8 => {}
+        _ => unsafe { illegal_state("a pair of an i32 and an i64 is not aligned on 8 bytes") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for usize {}
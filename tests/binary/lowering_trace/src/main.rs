@@ -0,0 +1,35 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn tens(value: i32) -> i32 {
+    match value {
+        0 => 0,
+        1 => 10,
+        _ => 100,
+    }
+}
+
+fn main() {
+    match tens(42) {
+        100 => {}
+        _ => unsafe { illegal_state("42 did not reach the default arm") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
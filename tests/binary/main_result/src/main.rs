@@ -0,0 +1,57 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+fn check(value: i32) -> Result<i32, &'static str> {
+    match value {
+        0 => Result::Err("zero"),
+        value => Result::Ok(value),
+    }
+}
+
+// Returning an `Err` from `main` prints it and exits with code 1
+fn main() -> Result<(), &'static str> {
+    match check(1) {
+        Result::Ok(_) => {}
+        Result::Err(error) => return Result::Err(error),
+    }
+    match check(0) {
+        Result::Ok(_) => Result::Ok(()),
+        Result::Err(error) => Result::Err(error),
+    }
+}
+
+// Stands in for `core::result::Result`, which the backend recognizes by its diagnostic item
+#[rustc_diagnostic_item = "Result"]
+pub enum Result<T, E> {
+    Ok(T),
+    Err(E),
+}
+
+#[lang = "termination"]
+trait Termination {
+    fn report(self) -> i32;
+}
+
+impl<T, E> Termination for Result<T, E> {
+    fn report(self) -> i32 {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
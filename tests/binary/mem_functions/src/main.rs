@@ -0,0 +1,90 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Pair {
+    pub first: i32,
+    pub second: i32,
+}
+
+fn swapped(mut pair: Pair) -> Pair {
+    mem::swap(&mut pair.first, &mut pair.second);
+    pair
+}
+
+fn first_of(pair: &Pair) -> i32 {
+    pair.first
+}
+
+fn main() {
+    let mut a = 1;
+    let mut b = 2;
+    mem::swap(&mut a, &mut b);
+    match a {
+        2 => {}
+        _ => unsafe { illegal_state("swap() did not move b into a") },
+    }
+    match mem::replace(&mut b, 5) {
+        1 => {}
+        _ => unsafe { illegal_state("replace() did not return the previous value") },
+    }
+    match b {
+        5 => {}
+        _ => unsafe { illegal_state("replace() did not store the new value") },
+    }
+    match mem::take(&mut b) {
+        5 => {}
+        _ => unsafe { illegal_state("take() did not return the value") },
+    }
+    match b {
+        0 => {}
+        _ => unsafe { illegal_state("take() did not leave the default value") },
+    }
+    match first_of(&swapped(Pair { first: 3, second: 4 })) {
+        4 => {}
+        _ => unsafe { illegal_state("swap() did not swap the fields") },
+    }
+}
+
+// Stands in for `core::mem`, whose functions the backend recognizes by their diagnostic items
+mod mem {
+    #[rustc_diagnostic_item = "mem_swap"]
+    pub fn swap<T>(_x: &mut T, _y: &mut T) {
+        loop {}
+    }
+
+    #[rustc_diagnostic_item = "mem_replace"]
+    pub fn replace<T>(_dest: &mut T, _src: T) -> T {
+        loop {}
+    }
+
+    #[rustc_diagnostic_item = "mem_take"]
+    pub fn take<T>(_dest: &mut T) -> T {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
@@ -0,0 +1,68 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+pub mod shapes {
+    pub fn sides() -> i32 {
+        4
+    }
+}
+
+mod internal {
+    // `pub`, but not reachable from the crate root
+    pub fn helper() -> i32 {
+        2
+    }
+}
+
+pub fn api() -> i32 {
+    1
+}
+
+fn hidden() -> i32 {
+    3
+}
+
+fn main() {
+    match api() + internal::helper() + hidden() + shapes::sides() {
+        10 => {}
+        _ => unsafe { illegal_state("the functions did not return 1, 2, 3 and 4") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+mod shapes {
+    pub mod circle {
+        pub fn id() -> i32 {
+            1
+        }
+    }
+
+    pub mod square {
+        pub fn id() -> i32 {
+            2
+        }
+    }
+
+    pub fn unique() -> i32 {
+        3
+    }
+}
+
+fn id() -> i32 {
+    0
+}
+
+fn main() {
+    match id() {
+        0 => {}
+        _ => unsafe { illegal_state("id called another function") },
+    }
+    match shapes::circle::id() {
+        1 => {}
+        _ => unsafe { illegal_state("shapes::circle::id called another function") },
+    }
+    match shapes::square::id() {
+        2 => {}
+        _ => unsafe { illegal_state("shapes::square::id called another function") },
+    }
+    match shapes::unique() {
+        3 => {}
+        _ => unsafe { illegal_state("shapes::unique called another function") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,45 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub enum Light {
+    Off,
+    Dimmed(i32),
+}
+
+pub fn brightness(light: &Light) -> i32 {
+    match light {
+        Light::Off => 0,
+        Light::Dimmed(level) => *level,
+    }
+}
+
+fn main() {
+    match brightness(&Light::Dimmed(42)) {
+        42 => {}
+        _ => unsafe { illegal_state("the light is not dimmed to 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
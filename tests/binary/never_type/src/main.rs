@@ -0,0 +1,45 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// Returns a `java.lang.Void`, which it never does
+fn give_up(message: &str) -> ! {
+    unsafe { illegal_state(message) }
+}
+
+enum Light {
+    Red,
+    Green,
+}
+
+// The discriminants other than those of `Red` and `Green` lead to an `Unreachable` terminator
+fn code(light: Light) -> i32 {
+    match light {
+        Light::Red => 1,
+        Light::Green => 2,
+    }
+}
+
+fn main() {
+    match code(Light::Green) {
+        2 => {}
+        _ => give_up("the code of green is not 2"),
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
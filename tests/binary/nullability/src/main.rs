@@ -0,0 +1,58 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+}
+
+pub fn or_origin<'a>(point: Option<&'a Point>, origin: &'a Point) -> &'a Point {
+    match point {
+        Option::Some(point) => point,
+        Option::None => origin,
+    }
+}
+
+pub fn x_of(point: &Point) -> i32 {
+    point.x
+}
+
+// Only the annotations of `or_origin` are checked, it is not run
+fn main() {
+    let point = Point { x: 2 };
+    match x_of(&point) {
+        2 => {}
+        _ => unsafe { illegal_state("the point is not at x = 2") },
+    }
+}
+
+// Stands in for `core::option::Option`, which the backend recognizes by its diagnostic item
+#[rustc_diagnostic_item = "Option"]
+pub enum Option<T> {
+    None,
+    Some(T),
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,82 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+}
+
+fn find(found: bool, point: &Point) -> Option<&Point> {
+    if found { Option::Some(point) } else { Option::None }
+}
+
+fn x_or(point: Option<&Point>, default: i32) -> i32 {
+    match point {
+        Option::Some(point) => point.x,
+        Option::None => default,
+    }
+}
+
+fn main() {
+    let point = Point { x: 2 };
+    match x_or(find(true, &point), -1) {
+        2 => {}
+        _ => unsafe { illegal_state("Some(point) is not the point") },
+    }
+    match x_or(find(false, &point), -1) {
+        -1 => {}
+        _ => unsafe { illegal_state("None is not None") },
+    }
+}
+
+// Stands in for `core::option::Option`, which the backend recognizes by its diagnostic item
+#[rustc_diagnostic_item = "Option"]
+pub enum Option<T> {
+    None,
+    Some(T),
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "neg"]
+trait Neg {
+    type Output;
+
+    fn neg(self) -> Self::Output;
+}
+
+impl Neg for i32 {
+    type Output = i32;
+
+    fn neg(self) -> i32 {
+        -self
+    }
+}
+
+#[lang = "panic_const_neg_overflow"]
+fn panic_const_neg_overflow() -> ! {
+    loop {}
+}
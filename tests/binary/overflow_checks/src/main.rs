@@ -0,0 +1,121 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn mul_u8(left: u8, right: u8) -> u8 {
+    left * right
+}
+
+fn mul_i16(left: i16, right: i16) -> i16 {
+    left * right
+}
+
+fn mul_i64(left: i64, right: i64) -> i64 {
+    left * right
+}
+
+fn add_u8(left: u8, right: u8) -> u8 {
+    left + right
+}
+
+fn main() {
+    match mul_u8(15, 17) {
+        255 => {}
+        _ => unsafe { illegal_state("15u8 * 17 is not 255") },
+    }
+    match mul_i16(181, 181) {
+        32761 => {}
+        _ => unsafe { illegal_state("181i16 * 181 is not 32761") },
+    }
+    match mul_i64(3_000_000_000, 3) {
+        9_000_000_000 => {}
+        _ => unsafe { illegal_state("3000000000i64 * 3 is not 9000000000") },
+    }
+    match add_u8(200, 55) {
+        255 => {}
+        _ => unsafe { illegal_state("200u8 + 55 is not 255") },
+    }
+    // Overflows a `u8` but not an int: panics with exit code 101 in this debug build
+    mul_u8(16, 16);
+    unsafe { illegal_state("16u8 * 16 did not panic") }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for u8 {}
+impl Copy for i16 {}
+impl Copy for i64 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for u8 {
+    type Output = u8;
+
+    fn add(self, rhs: u8) -> u8 {
+        self + rhs
+    }
+}
+
+#[lang = "mul"]
+trait Mul<Rhs = Self> {
+    type Output;
+
+    fn mul(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Mul for u8 {
+    type Output = u8;
+
+    fn mul(self, rhs: u8) -> u8 {
+        self * rhs
+    }
+}
+
+impl Mul for i16 {
+    type Output = i16;
+
+    fn mul(self, rhs: i16) -> i16 {
+        self * rhs
+    }
+}
+
+impl Mul for i64 {
+    type Output = i64;
+
+    fn mul(self, rhs: i64) -> i64 {
+        self * rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
+
+#[lang = "panic_const_mul_overflow"]
+fn panic_const_mul_overflow() -> ! {
+    loop {}
+}
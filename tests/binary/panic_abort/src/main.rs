@@ -0,0 +1,56 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn add_u8(left: u8, right: u8) -> u8 {
+    left + right
+}
+
+// The target aborts on panic: the overflow exits with code 101 as soon as it is reported, rather
+// than throwing a `rustjvm.Panic` that unwinds out of `main`
+fn main() {
+    add_u8(200, 56);
+    unsafe { illegal_state("200u8 + 56 did not panic") }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for u8 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for u8 {
+    type Output = u8;
+
+    fn add(self, rhs: u8) -> u8 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
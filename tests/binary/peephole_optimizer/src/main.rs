@@ -0,0 +1,27 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[inline(never)]
+pub fn answer() -> i32 {
+    42
+}
+
+fn main() {
+    match answer() {
+        42 => {}
+        _ => unsafe { illegal_state("the answer is not 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
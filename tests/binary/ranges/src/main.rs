@@ -0,0 +1,154 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn sum_inclusive(start: i32, end: i32) -> i32 {
+    let mut total = 0;
+    for i in start..=end {
+        total = total + i;
+    }
+    total
+}
+
+fn main() {
+    match sum_inclusive(1, 4) {
+        10 => {}
+        _ => unsafe { illegal_state("the sum of 1..=4 is not 10") },
+    }
+    match sum_inclusive(3, 3) {
+        3 => {}
+        _ => unsafe { illegal_state("3..=3 does not yield 3") },
+    }
+    let range = 2..5;
+    match range.contains(&4) {
+        true => {}
+        false => unsafe { illegal_state("2..5 does not contain 4") },
+    }
+    match range.contains(&5) {
+        false => {}
+        true => unsafe { illegal_state("2..5 contains 5") },
+    }
+    match range.is_empty() {
+        false => {}
+        true => unsafe { illegal_state("2..5 is empty") },
+    }
+    match (5..2).is_empty() {
+        true => {}
+        false => unsafe { illegal_state("5..2 is not empty") },
+    }
+}
+
+// The range types and what `for` loops are desugared into, which the backend lowers for ranges
+// of integers
+#[lang = "Range"]
+pub struct Range<Idx> {
+    pub start: Idx,
+    pub end: Idx,
+}
+
+impl<Idx> Range<Idx> {
+    pub fn contains(&self, _item: &Idx) -> bool {
+        loop {}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        loop {}
+    }
+}
+
+#[lang = "RangeInclusive"]
+pub struct RangeInclusive<Idx> {
+    start: Idx,
+    end: Idx,
+}
+
+impl<Idx> RangeInclusive<Idx> {
+    #[lang = "range_inclusive_new"]
+    pub fn new(start: Idx, end: Idx) -> RangeInclusive<Idx> {
+        RangeInclusive { start, end }
+    }
+}
+
+#[lang = "Option"]
+pub enum Option<T> {
+    #[lang = "None"]
+    None,
+    #[lang = "Some"]
+    Some(T),
+}
+
+trait Iterator {
+    type Item;
+
+    #[lang = "next"]
+    fn next(&mut self) -> Option<Self::Item>;
+}
+
+impl Iterator for RangeInclusive<i32> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        loop {}
+    }
+}
+
+trait IntoIterator {
+    type Item;
+    type IntoIter: Iterator<Item = Self::Item>;
+
+    #[lang = "into_iter"]
+    fn into_iter(self) -> Self::IntoIter;
+}
+
+impl<I: Iterator> IntoIterator for I {
+    type Item = I::Item;
+    type IntoIter = I;
+
+    fn into_iter(self) -> I {
+        self
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+}
+
+unsafe fn write(target: *mut i32, value: i32) {
+    unsafe { *target = value }
+}
+
+unsafe fn read(source: *const i32) -> i32 {
+    unsafe { *source }
+}
+
+unsafe fn x_of(point: *const Point) -> i32 {
+    unsafe { (*point).x }
+}
+
+fn main() {
+    let mut value = 1;
+    unsafe { write(&raw mut value, 5) };
+    match value {
+        5 => {}
+        _ => unsafe { illegal_state("the write through the pointer was not seen") },
+    }
+    match unsafe { read(&raw const value) } {
+        5 => {}
+        _ => unsafe { illegal_state("the read through the pointer did not see 5") },
+    }
+    let point = Point { x: 2 };
+    match unsafe { x_of(&raw const point) } {
+        2 => {}
+        _ => unsafe { illegal_state("the pointer to the point does not point to it") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
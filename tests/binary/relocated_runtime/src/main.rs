@@ -0,0 +1,11 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+// The `main(String[])` wrapper catches `rustjvm.Panic`, so even an empty `main` refers to the
+// runtime
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
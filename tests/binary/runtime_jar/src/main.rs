@@ -0,0 +1,10 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+// Uses no runtime class itself, the executable still gets all of them
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
@@ -0,0 +1,73 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+mod narrow {
+    pub fn scale(value: i32) -> i32 {
+        value
+    }
+}
+
+mod wide {
+    pub fn scale(value: i64, offset: i64) -> i64 {
+        value + offset
+    }
+}
+
+fn scale(value: u8) -> u8 {
+    value
+}
+
+fn main() {
+    match narrow::scale(42) {
+        42 => {}
+        _ => unsafe { illegal_state("narrow::scale called another function") },
+    }
+    match wide::scale(40, 2) {
+        42 => {}
+        _ => unsafe { illegal_state("wide::scale called another function") },
+    }
+    match scale(42) {
+        42 => {}
+        _ => unsafe { illegal_state("scale called another function") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for i64 {}
+impl Copy for u8 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i64 {
+    type Output = i64;
+
+    fn add(self, rhs: i64) -> i64 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,69 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub enum Shape {
+    Circle(i32),
+    Square { side: i32 },
+}
+
+pub fn width(shape: &Shape) -> i32 {
+    match shape {
+        Shape::Circle(radius) => *radius + *radius,
+        Shape::Square { side } => *side,
+    }
+}
+
+fn main() {
+    match width(&Shape::Circle(2)) {
+        4 => {}
+        _ => unsafe { illegal_state("the width of a circle of radius 2 is not 4") },
+    }
+    match width(&Shape::Square { side: 3 }) {
+        3 => {}
+        _ => unsafe { illegal_state("the width of a square of side 3 is not 3") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
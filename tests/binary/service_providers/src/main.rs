@@ -0,0 +1,34 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+// Listed in `META-INF/services/java.lang.Runnable`, for `ServiceLoader` to create
+#[jvm::export]
+#[jvm::service_provider("java.lang.Runnable")]
+pub struct Greeter;
+
+trait Runnable {
+    fn run(&self);
+}
+
+#[jvm::implements("java.lang.Runnable")]
+impl Runnable for Greeter {
+    fn run(&self) {}
+}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
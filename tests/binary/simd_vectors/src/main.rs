@@ -0,0 +1,54 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(intrinsics)]
+#![feature(repr_simd)]
+#![feature(register_tool)]
+#![allow(internal_features)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[rustc_intrinsic]
+unsafe fn simd_add<T>(x: T, y: T) -> T;
+
+#[rustc_intrinsic]
+unsafe fn simd_mul<T>(x: T, y: T) -> T;
+
+#[rustc_intrinsic]
+unsafe fn simd_reduce_add_unordered<T, U>(x: T) -> U;
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[repr(simd)]
+struct I32x4([i32; 4]);
+
+fn dot(a: I32x4, b: I32x4) -> i32 {
+    unsafe { simd_reduce_add_unordered(simd_mul(a, b)) }
+}
+
+fn sum(a: I32x4, b: I32x4) -> I32x4 {
+    unsafe { simd_add(a, b) }
+}
+
+fn main() {
+    match dot(I32x4([1, 2, 3, 4]), I32x4([5, 6, 7, 8])) {
+        70 => {}
+        _ => unsafe { illegal_state("the dot product is not 70") },
+    }
+    match dot(sum(I32x4([1, 2, 3, 4]), I32x4([1, 1, 1, 1])), I32x4([1, 1, 1, 1])) {
+        14 => {}
+        _ => unsafe { illegal_state("the lanes were not added") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
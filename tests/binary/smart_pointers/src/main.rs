@@ -0,0 +1,90 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+#[jvm::export]
+pub struct Point {
+    pub x: i32,
+}
+
+fn shared_x(point: Rc<Point>) -> i32 {
+    let shared = point.clone();
+    shared.x
+}
+
+fn counter() -> i32 {
+    let count = Rc::new(5);
+    *count.clone()
+}
+
+fn main() {
+    match shared_x(Rc::new(Point { x: 2 })) {
+        2 => {}
+        _ => unsafe { illegal_state("a clone of an Rc does not point to its value") },
+    }
+    match counter() {
+        5 => {}
+        _ => unsafe { illegal_state("an Rc of a primitive lost its value") },
+    }
+}
+
+// Stands in for `alloc::rc::Rc`, which the backend recognizes by its diagnostic item and whose
+// methods it lowers itself
+#[rustc_diagnostic_item = "Rc"]
+pub struct Rc<T>(T);
+
+impl<T> Rc<T> {
+    pub fn new(_value: T) -> Rc<T> {
+        loop {}
+    }
+}
+
+impl<T> Clone for Rc<T> {
+    fn clone(&self) -> Rc<T> {
+        loop {}
+    }
+}
+
+impl<T> Deref for Rc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "clone"]
+trait Clone: Sized {
+    fn clone(&self) -> Self;
+}
+
+#[lang = "deref"]
+trait Deref {
+    #[lang = "deref_target"]
+    type Target: ?Sized;
+
+    fn deref(&self) -> &Self::Target;
+}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
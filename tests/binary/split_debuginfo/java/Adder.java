@@ -0,0 +1,7 @@
+// Compiled with `javac -g --release 8 -d .. Adder.java`, for the class file's debug attributes
+public class Adder {
+    public static int sum(int left, int right) {
+        int total = left + right;
+        return total;
+    }
+}
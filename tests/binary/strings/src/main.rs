@@ -0,0 +1,108 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn greeting(name: &str) -> String {
+    let mut greeting = String::new();
+    greeting.push_str("hé ");
+    greeting.push_str(name);
+    greeting.push('!');
+    greeting
+}
+
+fn main() {
+    let greeting = greeting("you");
+    // Lengths are in UTF-8 bytes
+    match greeting.len() {
+        8 => {}
+        _ => unsafe { illegal_state("\"hé you!\" is not 8 bytes long") },
+    }
+    match greeting.as_str() == "hé you!" {
+        true => {}
+        false => unsafe { illegal_state("the greeting is not \"hé you!\"") },
+    }
+    let question = greeting + "?";
+    match question.as_str() == "hé you!?" {
+        true => {}
+        false => unsafe { illegal_state("+ did not append to the string") },
+    }
+}
+
+// Stands in for `alloc::string::String`, which the backend recognizes by its diagnostic item and
+// whose methods it lowers itself
+#[rustc_diagnostic_item = "String"]
+pub struct String(u8);
+
+impl String {
+    pub fn new() -> String {
+        loop {}
+    }
+
+    pub fn push_str(&mut self, _string: &str) {
+        loop {}
+    }
+
+    pub fn push(&mut self, _ch: char) {
+        loop {}
+    }
+
+    pub fn len(&self) -> usize {
+        loop {}
+    }
+
+    pub fn as_str(&self) -> &str {
+        loop {}
+    }
+}
+
+impl Add<&str> for String {
+    type Output = String;
+
+    fn add(self, _other: &str) -> String {
+        loop {}
+    }
+}
+
+impl PartialEq for &str {
+    fn eq(&self, _other: &&str) -> bool {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for char {}
+impl Copy for usize {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+#[lang = "eq"]
+trait PartialEq<Rhs: ?Sized = Self> {
+    fn eq(&self, other: &Rhs) -> bool;
+}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
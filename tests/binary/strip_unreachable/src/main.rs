@@ -0,0 +1,31 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![allow(dead_code)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn called() -> i32 {
+    42
+}
+
+fn never_called() -> i32 {
+    7
+}
+
+fn main() {
+    match called() {
+        42 => {}
+        _ => unsafe { illegal_state("called did not return 42") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
@@ -0,0 +1,25 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![no_core]
+
+unsafe extern "C" {
+    fn sysconf(name: i32) -> i64;
+}
+
+// Calls a native function without JNI, which goes to `support_report.support.json` instead of
+// failing the build
+pub fn cpu_count() -> i64 {
+    unsafe { sysconf(84) }
+}
+
+fn main() {}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for i64 {}
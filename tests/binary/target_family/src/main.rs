@@ -0,0 +1,38 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// The target rustc prints for `--print cfg` is the one code is configured for
+#[cfg(all(target_family = "jvm", target_pointer_width = "32", target_endian = "little"))]
+fn on_jvm() -> bool {
+    true
+}
+
+#[cfg(not(all(target_family = "jvm", target_pointer_width = "32", target_endian = "little")))]
+fn on_jvm() -> bool {
+    false
+}
+
+fn main() {
+    match on_jvm() {
+        true => {}
+        false => unsafe { illegal_state("the target is not the JVM's") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
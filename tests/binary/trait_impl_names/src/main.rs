@@ -0,0 +1,83 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+#![allow(dead_code)]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+trait Describe {
+    fn id() -> i32;
+}
+
+mod shapes {
+    pub struct Point;
+
+    impl super::Describe for Point {
+        fn id() -> i32 {
+            1
+        }
+    }
+}
+
+mod pixels {
+    pub struct Point;
+
+    impl super::Describe for Point {
+        fn id() -> i32 {
+            2
+        }
+    }
+}
+
+struct Wrapper<T>(T);
+
+impl Describe for Wrapper<i32> {
+    fn id() -> i32 {
+        3
+    }
+}
+
+impl Describe for Wrapper<u8> {
+    fn id() -> i32 {
+        4
+    }
+}
+
+fn main() {
+    match <shapes::Point as Describe>::id() {
+        1 => {}
+        _ => unsafe { illegal_state("shapes::Point called another impl") },
+    }
+    match <pixels::Point as Describe>::id() {
+        2 => {}
+        _ => unsafe { illegal_state("pixels::Point called another impl") },
+    }
+    match <Wrapper<i32> as Describe>::id() {
+        3 => {}
+        _ => unsafe { illegal_state("Wrapper<i32> called another impl") },
+    }
+    match <Wrapper<u8> as Describe>::id() {
+        4 => {}
+        _ => unsafe { illegal_state("Wrapper<u8> called another impl") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,100 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+trait Shape {
+    fn perimeter(&self) -> i32;
+}
+
+trait Polygon: Shape {
+    fn sides(&self) -> i32;
+}
+
+#[jvm::export]
+pub struct Square {
+    pub side: i32,
+}
+
+impl Shape for Square {
+    fn perimeter(&self) -> i32 {
+        self.side + self.side + self.side + self.side
+    }
+}
+
+impl Polygon for Square {
+    fn sides(&self) -> i32 {
+        4
+    }
+}
+
+fn as_shape(polygon: &dyn Polygon) -> &dyn Shape {
+    polygon
+}
+
+fn main() {
+    let square = Square { side: 3 };
+    let polygon: &dyn Polygon = &square;
+    match polygon.sides() {
+        4 => {}
+        _ => unsafe { illegal_state("a square does not have 4 sides") },
+    }
+    match as_shape(polygon).perimeter() {
+        12 => {}
+        _ => unsafe { illegal_state("the upcast square does not have a perimeter of 12") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "unsize"]
+trait Unsize<T: ?Sized> {}
+
+#[lang = "coerce_unsized"]
+trait CoerceUnsized<T: ?Sized> {}
+
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<&'a U> for &'a T {}
+
+#[lang = "dispatch_from_dyn"]
+trait DispatchFromDyn<T> {}
+
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> DispatchFromDyn<&'a U> for &'a T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+union Bits {
+    float: f32,
+    int: u32,
+}
+
+union Word {
+    whole: u32,
+    low: u8,
+}
+
+fn float_bits(value: f32) -> u32 {
+    let bits = Bits { float: value };
+    unsafe { bits.int }
+}
+
+fn with_low_byte(value: u32, low: u8) -> u32 {
+    let mut word = Word { whole: value };
+    word.low = low;
+    unsafe { word.whole }
+}
+
+fn main() {
+    match float_bits(1.0) {
+        0x3F80_0000 => {}
+        _ => unsafe { illegal_state("the bits of 1f32 are not 0x3F800000") },
+    }
+    // Writing a narrower field keeps the other bits
+    match with_low_byte(0x1234_5678, 0xFF) {
+        0x1234_56FF => {}
+        _ => unsafe { illegal_state("writing the low byte did not keep the high bits") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for u8 {}
+impl Copy for u32 {}
+impl Copy for f32 {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
@@ -0,0 +1,99 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+trait Shape {
+    fn perimeter(&self) -> i32;
+}
+
+#[jvm::export]
+pub struct Square {
+    pub side: i32,
+}
+
+impl Shape for Square {
+    fn perimeter(&self) -> i32 {
+        self.side + self.side + self.side + self.side
+    }
+}
+
+fn perimeter_of(shape: &dyn Shape) -> i32 {
+    shape.perimeter()
+}
+
+fn first_and_last(values: &[i32]) -> i32 {
+    values[0] + values[2]
+}
+
+fn main() {
+    match first_and_last(&[1, 2, 3]) {
+        4 => {}
+        _ => unsafe { illegal_state("the slice of [1, 2, 3] does not start with 1 and end with 3") },
+    }
+    match perimeter_of(&Square { side: 3 }) {
+        12 => {}
+        _ => unsafe { illegal_state("the perimeter of a square of side 3 is not 12") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i32 {}
+impl Copy for usize {}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+
+#[lang = "unsize"]
+trait Unsize<T: ?Sized> {}
+
+#[lang = "coerce_unsized"]
+trait CoerceUnsized<T: ?Sized> {}
+
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<&'a U> for &'a T {}
+
+#[lang = "dispatch_from_dyn"]
+trait DispatchFromDyn<T> {}
+
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> DispatchFromDyn<&'a U> for &'a T {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i32 {
+    type Output = i32;
+
+    fn add(self, rhs: i32) -> i32 {
+        self + rhs
+    }
+}
+
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
+
+#[lang = "panic_bounds_check"]
+fn panic_bounds_check(_index: usize, _len: usize) -> ! {
+    loop {}
+}
@@ -0,0 +1,112 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![feature(rustc_attrs)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+fn numbers() -> Vec<i32> {
+    let mut numbers = Vec::with_capacity(4);
+    numbers.push(1);
+    numbers.push(3);
+    numbers.insert(1, 2);
+    numbers
+}
+
+fn main() {
+    let mut numbers = numbers();
+    match numbers.len() {
+        3 => {}
+        _ => unsafe { illegal_state("the Vec does not have 3 elements") },
+    }
+    match numbers[1] {
+        2 => {}
+        _ => unsafe { illegal_state("the inserted element is not at its index") },
+    }
+    match numbers.remove(0) {
+        1 => {}
+        _ => unsafe { illegal_state("remove() did not return the first element") },
+    }
+    match numbers[0] {
+        2 => {}
+        _ => unsafe { illegal_state("remove() did not shift the elements") },
+    }
+    numbers.clear();
+    match numbers.is_empty() {
+        true => {}
+        false => unsafe { illegal_state("clear() left elements") },
+    }
+}
+
+// Stands in for `alloc::vec::Vec`, which the backend recognizes by its diagnostic item and whose
+// methods it lowers itself
+#[rustc_diagnostic_item = "Vec"]
+pub struct Vec<T>(T);
+
+impl<T> Vec<T> {
+    pub fn with_capacity(_capacity: usize) -> Vec<T> {
+        loop {}
+    }
+
+    pub fn len(&self) -> usize {
+        loop {}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        loop {}
+    }
+
+    pub fn push(&mut self, _value: T) {
+        loop {}
+    }
+
+    pub fn insert(&mut self, _index: usize, _value: T) {
+        loop {}
+    }
+
+    pub fn remove(&mut self, _index: usize) -> T {
+        loop {}
+    }
+
+    pub fn clear(&mut self) {
+        loop {}
+    }
+}
+
+impl<T> Index<usize> for Vec<T> {
+    type Output = T;
+
+    fn index(&self, _index: usize) -> &T {
+        loop {}
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for bool {}
+impl Copy for i32 {}
+impl Copy for usize {}
+
+#[lang = "index"]
+trait Index<Idx> {
+    type Output: ?Sized;
+
+    fn index(&self, index: Idx) -> &Self::Output;
+}
+
+#[lang = "legacy_receiver"]
+trait LegacyReceiver {}
+
+impl<T: ?Sized> LegacyReceiver for &T {}
+impl<T: ?Sized> LegacyReceiver for &mut T {}
@@ -0,0 +1,60 @@
+#![no_std]
+#![feature(no_core)]
+#![feature(lang_items)]
+#![feature(register_tool)]
+#![register_tool(jvm)]
+#![no_core]
+
+#[jvm::import("java.lang.IllegalStateException")]
+unsafe extern "C" {
+    #[jvm::throw]
+    fn illegal_state(message: &str) -> !;
+}
+
+// The double takes two slots, so `narrow` is read from the one after them
+fn total(whole: bool, wide: i64, _real: f64, narrow: i64) -> i64 {
+    let mut total = wide;
+    if whole {
+        total = total + narrow;
+    }
+    total
+}
+
+fn main() {
+    match total(true, 40, 0.5, 2) {
+        42 => {}
+        _ => unsafe { illegal_state("the long after the double was not added") },
+    }
+    match total(false, 42, 2.5, 2) {
+        42 => {}
+        _ => unsafe { illegal_state("the long after the double was added") },
+    }
+}
+
+#[lang = "sized"]
+trait Sized {}
+
+#[lang = "copy"]
+trait Copy {}
+
+impl Copy for i64 {}
+
+#[lang = "add"]
+trait Add<Rhs = Self> {
+    type Output;
+
+    fn add(self, rhs: Rhs) -> Self::Output;
+}
+
+impl Add for i64 {
+    type Output = i64;
+
+    fn add(self, rhs: i64) -> i64 {
+        self + rhs
+    }
+}
+
+#[lang = "panic_const_add_overflow"]
+fn panic_const_add_overflow() -> ! {
+    loop {}
+}
@@ -0,0 +1,53 @@
+import java.lang.reflect.Method;
+
+/**
+ * Prints what the {@code case_<n>} methods of a crate class built by the differential tests
+ * return, one line each, as the native build of the same crate prints them.
+ *
+ * <p>Usage: {@code java Runner <crate class> <Rust type of case_0> <Rust type of case_1> ...}
+ */
+public class Runner {
+    public static void main(String[] args) throws Exception {
+        Class<?> crateClass = Class.forName(args[0]);
+        for (int index = 1; index < args.length; index++) {
+            Method method = crateClass.getMethod("case_" + (index - 1));
+            String line;
+            try {
+                line = format(args[index], method.invoke(null));
+            } catch (ReflectiveOperationException | RuntimeException e) {
+                Throwable cause = e.getCause() != null ? e.getCause() : e;
+                line = "threw " + cause;
+            }
+            System.out.println(line);
+        }
+    }
+
+    /**
+     * The value of a Rust integer type the way Rust prints it. A Java type as wide as the Rust
+     * type holds its bits, to read as unsigned for the unsigned types; a wider Java type holds the
+     * value itself, and one out of the range of the Rust type shows up as a difference.
+     */
+    static String format(String rustType, Object value) {
+        int javaBits;
+        long raw;
+        if (value instanceof Character) {
+            javaBits = 16;
+            raw = (Character) value;
+        } else {
+            Number number = (Number) value;
+            javaBits = number instanceof Byte ? 8
+                    : number instanceof Short ? 16
+                    : number instanceof Integer ? 32
+                    : 64;
+            raw = number.longValue();
+        }
+        int rustBits = Integer.parseInt(rustType.substring(1));
+        if (rustType.startsWith("i") || javaBits > rustBits) {
+            return Long.toString(raw);
+        }
+        if (javaBits == 64) {
+            return Long.toUnsignedString(raw);
+        }
+        return Long.toString(raw & ((1L << javaBits) - 1));
+    }
+}